@@ -4,6 +4,18 @@
 
 local HttpService = game:GetService("HttpService")
 
+-- Identifies this plugin load. Re-registering after a dropped connection keeps the
+-- key, so the server can tell a reconnect from a plugin reload (which loses open
+-- ChangeHistory recordings).
+local INSTANCE_KEY = HttpService:GenerateGUID(false)
+
+-- Background traffic the server allows, sent on every /pull response: 3 full,
+-- 2 reduced, 1 minimal, 0 paused. Servers without it leave the level at full.
+local BACKGROUND_LEVEL_HEADER = "x-yippie-background-level"
+local FULL_BACKGROUND_LEVEL = 3
+-- How much longer background intervals get at each level
+local BACKGROUND_INTERVAL_SCALE = { [0] = 30, [1] = 10, [2] = 4, [3] = 1 }
+
 local Bridge = {}
 Bridge.__index = Bridge
 
@@ -15,6 +27,11 @@ function Bridge.new(baseUrl, token)
 		connected = false,
 		lastError = nil,
 		lastPollTime = 0,
+		-- Number pushed events so the server can apply them in order
+		-- (set when the server accepts the event_seq feature)
+		numberEvents = false,
+		eventSeq = 0,
+		backgroundLevel = FULL_BACKGROUND_LEVEL,
 	}, Bridge)
 end
 
@@ -51,6 +68,11 @@ function Bridge:_request(method, path, body)
 	end
 
 	if response.StatusCode >= 200 and response.StatusCode < 300 then
+		local headers = response.Headers or {}
+		local level = tonumber(headers[BACKGROUND_LEVEL_HEADER] or headers["X-Yippie-Background-Level"])
+		if level then
+			self.backgroundLevel = math.clamp(math.floor(level), 0, FULL_BACKGROUND_LEVEL)
+		end
 		local decodeOk, decoded = pcall(function()
 			return HttpService:JSONDecode(response.Body)
 		end)
@@ -70,10 +92,15 @@ end
 function Bridge:register()
 	local ok, data, err = self:_request("POST", "/register", {
 		plugin_version = "0.1.0",
+		instance_key = INSTANCE_KEY,
+		features = { "event_seq" },
 	})
 	if ok and data then
 		self.clientId = data.client_id
 		self.connected = true
+		-- A new client id starts a new sequence
+		self.numberEvents = type(data.features) == "table" and table.find(data.features, "event_seq") ~= nil
+		self.eventSeq = 0
 		return true, data.client_id
 	end
 	self.connected = false
@@ -104,17 +131,33 @@ function Bridge:pushResponse(requestId, success, result, errorMsg)
 end
 
 function Bridge:pushEvent(eventType, data)
+	self:pushEvents({ { event_type = eventType, data = data } })
+end
+
+-- Several events in one request, numbered in order
+function Bridge:pushEvents(events)
+	for _, event in ipairs(events) do
+		if self.numberEvents then
+			self.eventSeq += 1
+			event.seq = self.eventSeq
+		end
+	end
 	self:_request("POST", "/push", {
 		responses = {},
-		events = {
-			{
-				event_type = eventType,
-				data = data,
-			},
-		},
+		events = events,
 	})
 end
 
+-- True while the server asks for less background traffic than usual
+function Bridge:isBackgroundReduced()
+	return self.backgroundLevel < FULL_BACKGROUND_LEVEL
+end
+
+-- `base` seconds stretched for the current background level
+function Bridge:backgroundInterval(base)
+	return base * (BACKGROUND_INTERVAL_SCALE[self.backgroundLevel] or 1)
+end
+
 function Bridge:isConnected()
 	return self.connected and self.clientId ~= nil
 end
@@ -25,9 +25,11 @@ end
 local Bridge = require(script.bridge)
 local ToolRouter = require(script.tools)
 local Playtest = require(script.tools.playtest)
+local Logs = require(script.tools.logs)
 local Widget = require(script.ui.widget)
 local CommandTrace = require(script.ui.command_trace)
 local PLAYTEST_BRIDGE_SOURCE = require(script.playtest_bridge_source)
+local INPUT_PROBE_SOURCE = require(script.input_probe_source)
 
 -- ─── Check HttpService ────────────────────────────────────────
 
@@ -84,6 +86,42 @@ end
 -- ─── Playtest Bridge Injection ───────────────────────────────
 
 local BRIDGE_SCRIPT_NAME = "_YippieBloxPlaytestBridge"
+local INPUT_PROBE_NAME = "_YippieBloxInputProbe"
+
+-- The probe answers client-only queries (ContextActionService bindings) for the bridge
+local function getStarterPlayerScripts()
+	local ok, starterPlayer = pcall(function()
+		return game:GetService("StarterPlayer")
+	end)
+	if not ok or not starterPlayer then
+		return nil
+	end
+	return starterPlayer:FindFirstChildOfClass("StarterPlayerScripts")
+end
+
+local function injectInputProbe()
+	local container = getStarterPlayerScripts()
+	if not container then
+		warn("[MCP] StarterPlayerScripts not found; studio-get_bindable_actions will be unavailable")
+		return
+	end
+	local existing = container:FindFirstChild(INPUT_PROBE_NAME)
+	if existing then
+		existing:Destroy()
+	end
+	local probe = Instance.new("LocalScript")
+	probe.Name = INPUT_PROBE_NAME
+	probe.Source = INPUT_PROBE_SOURCE
+	probe.Parent = container
+end
+
+local function removeInputProbe()
+	local container = getStarterPlayerScripts()
+	local existing = container and container:FindFirstChild(INPUT_PROBE_NAME)
+	if existing then
+		existing:Destroy()
+	end
+end
 
 local function injectPlaytestBridge(serverUrl, token)
 	-- Remove old one if it exists
@@ -109,6 +147,7 @@ local function injectPlaytestBridge(serverUrl, token)
 	tokenVal.Parent = bridgeScript
 
 	bridgeScript.Parent = ServerScriptService
+	injectInputProbe()
 	print("[MCP] Injected playtest bridge into ServerScriptService")
 end
 
@@ -118,6 +157,10 @@ local function ensurePlaytestBridge()
 	-- leaving a broken bridge that won't connect. Force fresh injection every time.
 	if currentServerUrl then
 		local existing = ServerScriptService:FindFirstChild(BRIDGE_SCRIPT_NAME)
+		local container = getStarterPlayerScripts()
+		if container and not container:FindFirstChild(INPUT_PROBE_NAME) then
+			injectInputProbe()
+		end
 		if not existing or not existing:FindFirstChild("_YippieBlox_URL") then
 			if existing then
 				print("[MCP] Bridge script found but missing config children, re-injecting...")
@@ -135,6 +178,7 @@ local function removePlaytestBridge()
 		existing:Destroy()
 		print("[MCP] Removed playtest bridge from ServerScriptService")
 	end
+	removeInputProbe()
 end
 
 -- ─── Main Plugin Logic ────────────────────────────────────────
@@ -167,7 +211,11 @@ local currentToken = nil
 local features = detectFeatures()
 
 -- Build context table passed to tool handlers
-local function makeContext()
+-- Tool calls in progress by request id, for studio-cancel: the thread running each
+-- and cleanups its handler registered for threads and connections of its own
+local runningCalls = {}
+
+local function makeContext(requestId)
 	-- Ensure bridge script exists before every dispatch
 	-- (checkpoint_undo can destroy it)
 	ensurePlaytestBridge()
@@ -175,9 +223,50 @@ local function makeContext()
 		features = features,
 		bridge = bridge,
 		plugin = plugin,
+		onCancel = function(cleanup)
+			local call = runningCalls[requestId]
+			if call then
+				table.insert(call.cleanups, cleanup)
+			end
+		end,
 	}
 end
 
+-- Stop the thread running a call (studio-cancel). The server has already ended the
+-- call, so no response is pushed for it.
+local function cancelCall(requestId)
+	local call = runningCalls[requestId]
+	if not call then
+		return { stopped = false, note = "Not running in Studio; it already finished" }
+	end
+	runningCalls[requestId] = nil
+	local ok, err = pcall(task.cancel, call.thread)
+	for _, cleanup in ipairs(call.cleanups) do
+		pcall(cleanup)
+	end
+	if not ok then
+		return { stopped = false, tool = call.tool, note = "task.cancel failed: " .. tostring(err) }
+	end
+	print("[MCP] x " .. call.tool .. " cancelled (id: " .. requestId .. ")")
+	return { stopped = true, tool = call.tool }
+end
+
+-- Report effective settings so the server can show and check them (studio-status)
+local function pushSettings()
+	if not bridge then
+		return
+	end
+	local activeBridge = bridge
+	task.spawn(function()
+		activeBridge:pushEvent("studio-settings", {
+			serverUrl = currentServerUrl,
+			pollIntervalSecs = 0,
+			logForwarding = if Logs.isForwarding() then "all" else "off",
+			playtestBridge = ServerScriptService:FindFirstChild(BRIDGE_SCRIPT_NAME) ~= nil,
+		})
+	end)
+end
+
 -- ─── Poll Loop ────────────────────────────────────────────────
 
 local function startPollLoop()
@@ -206,6 +295,7 @@ local function startPollLoop()
 				if ok then
 					widgetController:setStatus("Connected (" .. clientId .. ")", true)
 					consecutiveFailures = 0
+					pushSettings()
 				end
 			end
 
@@ -232,7 +322,14 @@ local function startPollLoop()
 							end
 						end
 
-						local success, result = ToolRouter.dispatch(toolName, arguments, makeContext())
+						local success, result
+						if toolName == "studio-cancel" then
+							success, result = true, cancelCall(tostring(arguments.requestId))
+						else
+							runningCalls[requestId] = { thread = coroutine.running(), tool = toolName, cleanups = {} }
+							success, result = ToolRouter.dispatch(toolName, arguments, makeContext(requestId))
+							runningCalls[requestId] = nil
+						end
 						local elapsed = os.clock() - startTime
 
 						-- Send response back to server
@@ -247,6 +344,9 @@ local function startPollLoop()
 						end
 
 						bridge:pushResponse(requestId, success, result, errorMsg)
+						if toolName == "studio-logs_subscribe" or toolName == "studio-logs_unsubscribe" then
+							pushSettings()
+						end
 
 						-- Log to command trace
 						local details = if not success then tostring(errorMsg or "") else nil
@@ -273,6 +373,7 @@ local function startPollLoop()
 							connected = true
 							widgetController:setStatus("Connected (" .. clientId .. ")", true)
 							consecutiveFailures = 0
+							pushSettings()
 						else
 							widgetController:setStatus("Disconnected (server unreachable)", false)
 						end
@@ -330,6 +431,7 @@ local function doConnect(serverUrl, token)
 
 		-- Inject playtest bridge Script so it's available when playtest starts
 		injectPlaytestBridge(serverUrl, token)
+		pushSettings()
 
 		startPollLoop()
 	else
@@ -0,0 +1,360 @@
+-- input_probe_source.lua
+-- Returns the Luau source code for a LocalScript that gets injected into
+-- StarterPlayerScripts alongside the playtest bridge. ContextActionService
+-- bindings only exist on the client, so the server-side bridge asks this probe
+-- for them through a RemoteFunction it creates in ReplicatedStorage. The probe
+-- also performs input only the local player can (ProximityPrompt holds and GUI
+-- button clicks), maps the player's GUI and lists sounds playing on the client.
+
+return [==[
+local ContextActionService = game:GetService("ContextActionService")
+local GuiService = game:GetService("GuiService")
+local Players = game:GetService("Players")
+local ReplicatedStorage = game:GetService("ReplicatedStorage")
+local SoundService = game:GetService("SoundService")
+
+local remote = ReplicatedStorage:WaitForChild("_YippieBloxInputProbe", 30)
+if not remote then
+	return
+end
+
+-- "Enum.KeyCode.E" -> "KeyCode.E"
+local function describeInput(input)
+	return (string.gsub(tostring(input), "^Enum%.", ""))
+end
+
+local function boundActions()
+	local ok, info = pcall(function()
+		return ContextActionService:GetAllBoundActionInfo()
+	end)
+	if not ok then
+		return { error = "ContextActionService:GetAllBoundActionInfo failed: " .. tostring(info) }
+	end
+	local actions = {}
+	for name, action in pairs(info) do
+		local inputs = {}
+		for _, input in ipairs(action.inputTypes or {}) do
+			table.insert(inputs, describeInput(input))
+		end
+		table.insert(actions, {
+			name = name,
+			inputs = inputs,
+			priority = action.priorityLevel,
+			stackOrder = action.stackOrder,
+			createTouchButton = action.createTouchButton,
+			title = action.title,
+			description = action.description,
+		})
+	end
+	table.sort(actions, function(a, b)
+		return a.name < b.name
+	end)
+	return { actions = actions }
+end
+
+-- Starts the hold and returns; the bridge waits for Triggered on the server
+local function holdPrompt(prompt)
+	if typeof(prompt) ~= "Instance" or not prompt:IsA("ProximityPrompt") then
+		return { error = "The prompt has not replicated to the client (streamed out, or created server-side after join)" }
+	end
+	local ok, err = pcall(function()
+		prompt:InputHoldBegin()
+	end)
+	if not ok then
+		return { error = "ProximityPrompt:InputHoldBegin failed: " .. tostring(err) }
+	end
+	task.delay(prompt.HoldDuration + 0.1, function()
+		pcall(function()
+			prompt:InputHoldEnd()
+		end)
+	end)
+	return { started = true, holdDuration = prompt.HoldDuration }
+end
+
+-- How long a click waits for the button's Activated / MouseButton1Click
+local CLICK_WAIT = 3
+
+-- "StarterGui.Menu.Play" is looked up in this player's PlayerGui; anything else
+-- is a full name from game
+local function resolveGuiPath(path)
+	local parts = string.split(path, ".")
+	local current = game
+	local first = 1
+	if parts[1] == "StarterGui" then
+		current = Players.LocalPlayer:FindFirstChildOfClass("PlayerGui")
+		first = 2
+	end
+	for i = first, #parts do
+		current = current and current:FindFirstChild(parts[i])
+	end
+	return current
+end
+
+local function clickGuiButton(path)
+	path = tostring(path)
+	local button = resolveGuiPath(path)
+	if not button then
+		return { error = "No GUI object at '" .. tostring(path) .. "' on the client" }
+	end
+	if not button:IsA("GuiButton") then
+		return { error = "not_a_gui_button: " .. path .. " is a " .. button.ClassName }
+	end
+	local screenGui = button:FindFirstAncestorOfClass("ScreenGui")
+	if not screenGui or not screenGui.Enabled then
+		return { error = "Button " .. path .. " is not in an enabled ScreenGui" }
+	end
+	if not button.Visible or not button.Active then
+		return { error = string.format("Button %s can't be clicked (Visible: %s, Active: %s)", path, tostring(button.Visible), tostring(button.Active)) }
+	end
+
+	-- Absolute* is below the top bar inset unless the ScreenGui ignores it; input is in
+	-- screen space, GetGuiObjectsAtPosition in inset space
+	local inset = GuiService:GetGuiInset()
+	local center = button.AbsolutePosition + button.AbsoluteSize / 2
+	if not screenGui.IgnoreGuiInset then
+		center += inset
+	end
+
+	local fired = { activated = false, mouseButton1Click = false }
+	local connections = {
+		button.Activated:Connect(function()
+			fired.activated = true
+		end),
+		button.MouseButton1Click:Connect(function()
+			fired.mouseButton1Click = true
+		end),
+	}
+	local ok, err = pcall(function()
+		local VirtualUser = game:GetService("VirtualUser")
+		VirtualUser:CaptureController()
+		VirtualUser:Button1Down(center)
+		task.wait()
+		VirtualUser:Button1Up(center)
+	end)
+	local deadline = os.clock() + CLICK_WAIT
+	while ok and not (fired.activated or fired.mouseButton1Click) and os.clock() < deadline do
+		task.wait()
+	end
+	for _, connection in ipairs(connections) do
+		connection:Disconnect()
+	end
+	if not ok then
+		return { error = "Simulating the click failed (VirtualUser unavailable?): " .. tostring(err) }
+	end
+
+	-- Let the game's own handlers (deferred signals included) finish
+	task.wait()
+	task.wait()
+
+	local topObject
+	local playerGui = Players.LocalPlayer:FindFirstChildOfClass("PlayerGui")
+	local hits = playerGui and playerGui:GetGuiObjectsAtPosition(center.X - inset.X, center.Y - inset.Y)
+	if hits and hits[1] then
+		topObject = hits[1]:GetFullName()
+	end
+	if not (fired.activated or fired.mouseButton1Click) then
+		return { error = string.format("gui_click_not_received: %s did not fire Activated or MouseButton1Click within %ds. Topmost GUI object at the click point: %s", path, CLICK_WAIT, topObject or "none") }
+	end
+	return {
+		path = button:GetFullName(),
+		className = button.ClassName,
+		activated = fired.activated,
+		mouseButton1Click = fired.mouseButton1Click,
+		position = { x = center.X, y = center.Y },
+		topObject = topObject,
+	}
+end
+
+-- Bounds for gui_tree (defaults, hard maximums)
+local DEFAULT_TREE_DEPTH = 8
+local MAX_TREE_DEPTH = 16
+local DEFAULT_TREE_NODES = 200
+local MAX_TREE_NODES = 1000
+local MAX_TREE_TEXT = 80
+
+local function vec2(v)
+	return { x = math.round(v.X), y = math.round(v.Y) }
+end
+
+-- GUI objects and the containers that hold them; scripts, layouts and constraints
+-- are left out
+local function isTreeNode(instance)
+	return instance:IsA("GuiBase2d") or instance:IsA("Folder")
+end
+
+-- Whether everything above `instance` lets it show: Visible GuiObjects up to an
+-- Enabled LayerCollector
+local function ancestorsShown(instance)
+	local parent = instance.Parent
+	while parent do
+		if parent:IsA("GuiObject") and not parent.Visible then
+			return false
+		end
+		if parent:IsA("LayerCollector") then
+			return parent.Enabled
+		end
+		parent = parent.Parent
+	end
+	return true
+end
+
+local function guiTree(query)
+	local maxDepth = math.clamp(math.floor(tonumber(query.maxDepth) or DEFAULT_TREE_DEPTH), 1, MAX_TREE_DEPTH)
+	local maxNodes = math.clamp(math.floor(tonumber(query.maxNodes) or DEFAULT_TREE_NODES), 1, MAX_TREE_NODES)
+	local visibleOnly = query.visibleOnly == true
+
+	local root = Players.LocalPlayer:FindFirstChildOfClass("PlayerGui")
+	if query.root then
+		root = resolveGuiPath(tostring(query.root))
+		if not root then
+			return { error = "No GUI object at '" .. tostring(query.root) .. "' on the client" }
+		end
+	end
+	if not root then
+		return { error = "The player has no PlayerGui yet" }
+	end
+
+	local camera = workspace.CurrentCamera
+	local viewport = camera and camera.ViewportSize or Vector2.new(0, 0)
+	local inset = GuiService:GetGuiInset()
+	local count = 0
+	local truncated = false
+
+	-- `shown`: this and every ancestor is Visible, in an Enabled LayerCollector.
+	-- `layer`: the nearest LayerCollector; only ScreenGui contents are in screen space
+	local function visit(instance, depth, shown, layer)
+		if count >= maxNodes then
+			truncated = true
+			return nil
+		end
+		local node = { name = instance.Name, className = instance.ClassName }
+		if instance:IsA("LayerCollector") then
+			layer = instance
+			shown = shown and instance.Enabled
+			node.enabled = instance.Enabled
+			if instance:IsA("ScreenGui") then
+				node.displayOrder = instance.DisplayOrder
+				node.ignoreGuiInset = instance.IgnoreGuiInset
+			end
+		elseif instance:IsA("GuiObject") then
+			shown = shown and instance.Visible
+			local position = instance.AbsolutePosition
+			local size = instance.AbsoluteSize
+			local onScreen = true
+			if layer and layer:IsA("ScreenGui") then
+				-- Same screen space as studio-click_gui_button's position
+				if not layer.IgnoreGuiInset then
+					position += inset
+				end
+				onScreen = size.X > 0 and size.Y > 0
+					and position.X < viewport.X and position.Y < viewport.Y
+					and position.X + size.X > 0 and position.Y + size.Y > 0
+			end
+			node.position = vec2(position)
+			node.size = vec2(size)
+			node.visible = shown and onScreen
+			node.zIndex = instance.ZIndex
+			-- Only set on objects that take input; not checked for being covered
+			if instance:IsA("GuiButton") then
+				node.interactable = node.visible and instance.Active
+			elseif instance:IsA("TextBox") then
+				node.interactable = node.visible and instance.TextEditable
+			end
+			if instance:IsA("TextLabel") or instance:IsA("TextButton") or instance:IsA("TextBox") then
+				local text = instance.Text
+				if #text > MAX_TREE_TEXT then
+					text = string.sub(text, 1, MAX_TREE_TEXT) .. "..."
+				end
+				node.text = text
+			end
+		end
+		if visibleOnly and depth > 0 and not shown then
+			return nil
+		end
+		count += 1
+
+		local children = {}
+		for _, child in ipairs(instance:GetChildren()) do
+			if isTreeNode(child) then
+				table.insert(children, child)
+			end
+		end
+		if #children > 0 then
+			if depth >= maxDepth then
+				node.omittedChildren = #children
+			else
+				node.children = {}
+				for _, child in ipairs(children) do
+					local childNode = visit(child, depth + 1, shown, layer)
+					if childNode then
+						table.insert(node.children, childNode)
+					end
+				end
+			end
+		end
+		return node
+	end
+
+	local tree = visit(root, 0, ancestorsShown(root), root:FindFirstAncestorOfClass("LayerCollector"))
+	return {
+		root = root:GetFullName(),
+		tree = tree,
+		nodeCount = count,
+		truncated = truncated,
+		maxDepth = maxDepth,
+		maxNodes = maxNodes,
+		viewport = vec2(viewport),
+	}
+end
+
+-- Sounds playing on this client: LocalScript sounds and replicated ones alike.
+-- The bridge keeps the server's entry for sounds it sees playing too.
+local function playingSounds(query)
+	local limit = math.max(1, math.floor(tonumber(query.limit) or 200))
+	local roots = { workspace, SoundService }
+	local playerGui = Players.LocalPlayer:FindFirstChildOfClass("PlayerGui")
+	if playerGui then
+		table.insert(roots, playerGui)
+	end
+	local sounds = {}
+	for _, root in ipairs(roots) do
+		for _, descendant in ipairs(root:GetDescendants()) do
+			if #sounds >= limit then
+				return { sounds = sounds }
+			end
+			if descendant:IsA("Sound") and descendant.IsPlaying then
+				table.insert(sounds, {
+					path = descendant:GetFullName(),
+					side = "client",
+					soundId = descendant.SoundId,
+					volume = descendant.Volume,
+					timePosition = math.floor(descendant.TimePosition * 100 + 0.5) / 100,
+					timeLength = math.floor(descendant.TimeLength * 100 + 0.5) / 100,
+					looped = descendant.Looped,
+					playbackSpeed = descendant.PlaybackSpeed,
+				})
+			end
+		end
+	end
+	return { sounds = sounds }
+end
+
+remote.OnClientInvoke = function(query)
+	if query == "bound_actions" then
+		return boundActions()
+	end
+	if type(query) == "table" and query.kind == "prompt_hold" then
+		return holdPrompt(query.prompt)
+	end
+	if type(query) == "table" and query.kind == "gui_click" then
+		return clickGuiButton(query.path)
+	end
+	if type(query) == "table" and query.kind == "gui_tree" then
+		return guiTree(query)
+	end
+	if type(query) == "table" and query.kind == "sounds_playing" then
+		return playingSounds(query)
+	end
+	return { error = "Unknown probe query: " .. tostring(query) }
+end
+]==]
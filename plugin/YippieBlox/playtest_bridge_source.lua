@@ -8,6 +8,7 @@ local HttpService = game:GetService("HttpService")
 local RunService = game:GetService("RunService")
 local LogService = game:GetService("LogService")
 local Players = game:GetService("Players")
+local ReplicatedStorage = game:GetService("ReplicatedStorage")
 
 print("[MCP-Playtest] Bridge script loaded, IsRunning: " .. tostring(RunService:IsRunning()))
 
@@ -36,12 +37,16 @@ print("[MCP-Playtest] Bridge starting (server context), URL: " .. BASE_URL)
 -- Minimal HTTP Bridge
 
 local clientId = nil
+-- Pushed events are numbered when the server accepts the event_seq feature
+local numberEvents = false
+local eventSeq = 0
 
 local function request(method, path, body)
 	local url = BASE_URL .. path
 	if clientId then
 		local sep = if string.find(path, "?", 1, true) then "&" else "?"
-		url = url .. sep .. "clientId=" .. clientId
+		-- The server estimates our clock offset from the send time (latency-compensated sequences)
+		url = url .. sep .. "clientId=" .. clientId .. "&clock=" .. string.format("%.6f", os.clock())
 	end
 
 	local headers = { ["Content-Type"] = "application/json" }
@@ -79,6 +84,16 @@ local function pushResponse(requestId, success, result, errorMsg)
 	})
 end
 
+local function pushEvents(events)
+	if numberEvents then
+		for _, event in ipairs(events) do
+			eventSeq += 1
+			event.seq = eventSeq
+		end
+	end
+	request("POST", "/push", { responses = {}, events = events })
+end
+
 -- Tool Handlers (server-context subset)
 
 local logBuffer = {}
@@ -174,10 +189,137 @@ local function cleanupVirtualInput()
 	releaseNetworkOwnership()
 end
 
--- ─── NPC Driver State ─────────────────────────────────────────
+-- ─── Input Recording ──────────────────────────────────────────
+-- Samples the human player's character and streams key transitions and look
+-- directions to the server as studio-input_sample events. Keys are derived from
+-- world-space velocity so they match how studio-virtualuser_key moves on replay.
 
-local npcDrivers = {}
-local nextDriverId = 1
+local RECORD_FLUSH_INTERVAL = 0.5
+local RECORD_LOOK_DOT = 0.995 -- ~5.7 degrees of turn before a new look sample
+local MOVE_KEY_THRESHOLD = 0.3 -- fraction of WalkSpeed along an axis
+
+local recording = nil -- { name, startedAt, maxSeconds, keys, lastLook, pending, conn }
+
+local function flushInputSamples()
+	if not recording or #recording.pending == 0 then return end
+	local samples = recording.pending
+	recording.pending = {}
+	pushEvents({ { event_type = "studio-input_sample", data = { recording = recording.name, samples = samples } } })
+end
+
+local function sampleInput()
+	if not recording then return end
+	local elapsed = os.clock() - recording.startedAt
+	if elapsed > recording.maxSeconds then
+		recording.conn:Disconnect()
+		return
+	end
+	local _, character, humanoid = getPlayerCharacterHumanoid()
+	if not humanoid then return end
+	local root = character:FindFirstChild("HumanoidRootPart")
+	if not root then return end
+
+	local tMs = math.floor(elapsed * 1000)
+	local velocity = root.AssemblyLinearVelocity
+	local threshold = math.max(humanoid.WalkSpeed, 1) * MOVE_KEY_THRESHOLD
+	local airborne = humanoid.FloorMaterial == Enum.Material.Air
+	local held = {
+		W = velocity.Z < -threshold,
+		S = velocity.Z > threshold,
+		A = velocity.X < -threshold,
+		D = velocity.X > threshold,
+		Space = airborne and (velocity.Y > 1 or recording.keys.Space == true),
+	}
+	for _, key in ipairs({ "W", "A", "S", "D", "Space" }) do
+		local isDown = held[key] == true
+		if isDown ~= (recording.keys[key] == true) then
+			recording.keys[key] = isDown
+			table.insert(recording.pending, {
+				kind = "key", tMs = tMs, keyCode = key, action = if isDown then "down" else "up",
+			})
+		end
+	end
+
+	local look = root.CFrame.LookVector
+	if not recording.lastLook or look:Dot(recording.lastLook) < RECORD_LOOK_DOT then
+		recording.lastLook = look
+		table.insert(recording.pending, {
+			kind = "look", tMs = tMs, direction = { x = look.X, y = look.Y, z = look.Z },
+		})
+	end
+end
+
+local function stopRecording()
+	if not recording then return nil end
+	recording.conn:Disconnect()
+	local durationMs = math.floor(math.min(os.clock() - recording.startedAt, recording.maxSeconds) * 1000)
+	flushInputSamples()
+	local name = recording.name
+	recording = nil
+	return name, durationMs
+end
+
+-- ─── Client Input Probe ───────────────────────────────────────
+-- ContextActionService bindings only exist on the client. The plugin injects a
+-- LocalScript (_YippieBloxInputProbe) into StarterPlayerScripts that answers
+-- queries through this RemoteFunction.
+
+local PROBE_TIMEOUT = 5
+
+local inputProbe = ReplicatedStorage:FindFirstChild("_YippieBloxInputProbe")
+if not inputProbe then
+	inputProbe = Instance.new("RemoteFunction")
+	inputProbe.Name = "_YippieBloxInputProbe"
+	inputProbe.Parent = ReplicatedStorage
+end
+
+-- InvokeClient yields until the client answers, so bound it with a timeout
+local function queryClient(player, query)
+	local done, ok, result = false, false, nil
+	task.spawn(function()
+		ok, result = pcall(function()
+			return inputProbe:InvokeClient(player, query)
+		end)
+		done = true
+	end)
+	local deadline = os.clock() + PROBE_TIMEOUT
+	while not done and os.clock() < deadline do
+		task.wait(0.05)
+	end
+	if not done then
+		return false, "The client did not answer within " .. PROBE_TIMEOUT .. "s (the input probe LocalScript may not be running)"
+	end
+	if not ok then
+		return false, "Querying the client failed: " .. tostring(result)
+	end
+	if type(result) ~= "table" then
+		return false, "The input probe returned no data"
+	end
+	if result.error then
+		return false, result.error
+	end
+	return true, result
+end
+
+-- Player (first one, or args.player by name) with a spawned character, or an error
+local function resolvePlayerCharacter(playerName)
+	local player, character, humanoid = getPlayerCharacterHumanoid()
+	if playerName then
+		player = Players:FindFirstChild(playerName)
+		if not player then
+			return nil, "No player named '" .. tostring(playerName) .. "' in the playtest"
+		end
+		character = player.Character
+		humanoid = character and character:FindFirstChildOfClass("Humanoid")
+	end
+	if not player then
+		return nil, "No player in the playtest. Characters only exist in Play mode (F5)."
+	end
+	if not character or not humanoid then
+		return nil, "Player " .. player.Name .. " has no character with a Humanoid yet (not spawned, or respawning)"
+	end
+	return player, character, humanoid
+end
 
 local function resolveInstancePath(path)
 	local parts = string.split(path, ".")
@@ -189,6 +331,496 @@ local function resolveInstancePath(path)
 	return current
 end
 
+-- ─── Humanoid Description ─────────────────────────────────────
+
+local BODY_PARTS = { "Head", "Torso", "LeftArm", "RightArm", "LeftLeg", "RightLeg" }
+local SCALES = { "HeightScale", "WidthScale", "DepthScale", "HeadScale", "BodyTypeScale", "ProportionScale" }
+local CLOTHING = { "Shirt", "Pants", "GraphicTShirt", "Face" }
+local ANIMATIONS = { "IdleAnimation", "WalkAnimation", "RunAnimation", "JumpAnimation", "FallAnimation", "ClimbAnimation", "SwimAnimation" }
+-- Rigid accessory properties, used when GetAccessories isn't available
+local ACCESSORY_PROPERTIES = {
+	HatAccessory = "Hat", HairAccessory = "Hair", FaceAccessory = "Face", NeckAccessory = "Neck",
+	ShouldersAccessory = "Shoulder", FrontAccessory = "Front", BackAccessory = "Back", WaistAccessory = "Waist",
+}
+
+local function readProperty(instance, name)
+	local ok, value = pcall(function()
+		return instance[name]
+	end)
+	if ok then return value end
+	return nil
+end
+
+local function colorHex(color)
+	local ok, hex = pcall(function()
+		return color:ToHex()
+	end)
+	if ok then return "#" .. hex end
+	return string.format("#%02x%02x%02x", color.R * 255, color.G * 255, color.B * 255)
+end
+
+local function describeAccessories(description)
+	local ok, list = pcall(function()
+		return description:GetAccessories(true)
+	end)
+	local accessories = {}
+	if ok and list then
+		for _, item in ipairs(list) do
+			table.insert(accessories, {
+				assetId = item.AssetId,
+				type = if typeof(item.AccessoryType) == "EnumItem" then item.AccessoryType.Name else tostring(item.AccessoryType),
+				layered = item.IsLayered == true,
+				order = item.Order,
+			})
+		end
+		return accessories
+	end
+	for property, accessoryType in pairs(ACCESSORY_PROPERTIES) do
+		local ids = readProperty(description, property)
+		if type(ids) == "string" then
+			for id in string.gmatch(ids, "%d+") do
+				table.insert(accessories, { assetId = tonumber(id), type = accessoryType, layered = false })
+			end
+		end
+	end
+	return accessories
+end
+
+local function describeHumanoid(player, character, humanoid)
+	local ok, description = pcall(function()
+		return humanoid:GetAppliedDescription()
+	end)
+	if not ok then
+		return false, "Could not read the character's HumanoidDescription: " .. tostring(description)
+	end
+
+	local bodyColors, bodyParts, scales, clothing, animations = {}, {}, {}, {}, {}
+	for _, part in ipairs(BODY_PARTS) do
+		local color = readProperty(description, part .. "Color")
+		if typeof(color) == "Color3" then
+			bodyColors[part] = colorHex(color)
+		end
+		bodyParts[part] = readProperty(description, part)
+	end
+	for _, name in ipairs(SCALES) do
+		scales[name] = readProperty(description, name)
+	end
+	for _, name in ipairs(CLOTHING) do
+		clothing[name] = readProperty(description, name)
+	end
+	for _, name in ipairs(ANIMATIONS) do
+		animations[name] = readProperty(description, name)
+	end
+
+	-- What the character actually wears, including accessories added outside the description
+	local equipped = {}
+	for _, child in ipairs(character:GetChildren()) do
+		if child:IsA("Accoutrement") then
+			local accessoryType = readProperty(child, "AccessoryType")
+			table.insert(equipped, {
+				name = child.Name,
+				type = if typeof(accessoryType) == "EnumItem" then accessoryType.Name else nil,
+			})
+		end
+	end
+
+	return true, {
+		player = player.Name,
+		character = character:GetFullName(),
+		rigType = humanoid.RigType.Name,
+		bodyColors = bodyColors,
+		bodyParts = bodyParts,
+		scales = scales,
+		clothing = clothing,
+		animations = animations,
+		accessories = describeAccessories(description),
+		equipped = equipped,
+	}
+end
+
+-- ─── Proximity Prompts ────────────────────────────────────────
+
+local DEFAULT_PROMPT_RADIUS = 100
+local MAX_PROMPT_RADIUS = 2000
+local DEFAULT_PROMPT_LIMIT = 50
+local MAX_PROMPT_LIMIT = 200
+
+-- World position a prompt is shown at: its Attachment, BasePart or Model pivot
+local function promptPosition(prompt)
+	local parent = prompt.Parent
+	if not parent then return nil end
+	if parent:IsA("Attachment") then
+		return parent.WorldPosition
+	elseif parent:IsA("BasePart") then
+		return parent.Position
+	elseif parent:IsA("Model") then
+		local ok, pivot = pcall(function()
+			return parent:GetPivot()
+		end)
+		if ok then return pivot.Position end
+	end
+	return nil
+end
+
+local function listProximityPrompts(player, character, args)
+	local root = character.PrimaryPart or character:FindFirstChild("HumanoidRootPart")
+	if not root then
+		return false, "Character of " .. player.Name .. " has no root part"
+	end
+	local origin = root.Position
+	local radius = math.clamp(tonumber(args.radius) or DEFAULT_PROMPT_RADIUS, 1, MAX_PROMPT_RADIUS)
+	local limit = math.clamp(tonumber(args.limit) or DEFAULT_PROMPT_LIMIT, 1, MAX_PROMPT_LIMIT)
+	local includeDisabled = args.includeDisabled ~= false
+
+	local found = {}
+	for _, descendant in ipairs(workspace:GetDescendants()) do
+		if descendant:IsA("ProximityPrompt") and (includeDisabled or descendant.Enabled) then
+			local position = promptPosition(descendant)
+			local distance = position and (position - origin).Magnitude
+			if distance and distance <= radius then
+				table.insert(found, {
+					path = descendant:GetFullName(),
+					actionText = descendant.ActionText,
+					objectText = descendant.ObjectText,
+					holdDuration = descendant.HoldDuration,
+					enabled = descendant.Enabled,
+					keyboardKeyCode = descendant.KeyboardKeyCode.Name,
+					maxActivationDistance = descendant.MaxActivationDistance,
+					requiresLineOfSight = descendant.RequiresLineOfSight,
+					distance = math.floor(distance * 10 + 0.5) / 10,
+					inRange = distance <= descendant.MaxActivationDistance,
+				})
+			end
+		end
+	end
+	table.sort(found, function(a, b)
+		return a.distance < b.distance
+	end)
+
+	local prompts = {}
+	for i = 1, math.min(limit, #found) do
+		prompts[i] = found[i]
+	end
+	return true, {
+		player = player.Name,
+		origin = { origin.X, origin.Y, origin.Z },
+		radius = radius,
+		count = #found,
+		prompts = prompts,
+		truncated = #found > limit,
+	}
+end
+
+-- The hold itself runs on the client (InputHoldBegin only acts for the local
+-- player), so the bridge asks the input probe to start it and waits for the
+-- server-side Triggered event.
+local function triggerProximityPrompt(player, character, args)
+	local prompt = resolveInstancePath(tostring(args.path))
+	if not prompt then
+		return false, "No instance at '" .. tostring(args.path) .. "' in the running game"
+	end
+	if not prompt:IsA("ProximityPrompt") then
+		return false, "not_a_proximity_prompt: " .. args.path .. " is a " .. prompt.ClassName
+	end
+	if not prompt.Enabled then
+		return false, "Prompt " .. args.path .. " is disabled (Enabled = false)"
+	end
+	local timeout = math.clamp((tonumber(args.timeoutMs) or 10000) / 1000, 1, 60)
+
+	local root = character.PrimaryPart or character:FindFirstChild("HumanoidRootPart")
+	local position = promptPosition(prompt)
+	local distance = root and position and math.floor((position - root.Position).Magnitude * 10 + 0.5) / 10
+
+	local triggered, holdBegan = false, false
+	local connections = {
+		prompt.Triggered:Connect(function(who)
+			if who == player then triggered = true end
+		end),
+		prompt.PromptButtonHoldBegan:Connect(function(who)
+			if who == player then holdBegan = true end
+		end),
+	}
+	local startedAt = os.clock()
+	local ok, result = queryClient(player, { kind = "prompt_hold", prompt = prompt })
+	if ok then
+		local deadline = startedAt + timeout
+		while not triggered and os.clock() < deadline do
+			task.wait(0.05)
+		end
+	end
+	for _, connection in ipairs(connections) do
+		connection:Disconnect()
+	end
+	if not ok then
+		return false, result
+	end
+
+	if not triggered then
+		return false, string.format(
+			"prompt_not_triggered: %s did not fire Triggered within %gs (holdBegan: %s, distance: %s studs, maxActivationDistance: %g, requiresLineOfSight: %s). Move the character into range with studio-virtualuser_key or studio-npc_driver_* and retry.",
+			args.path, timeout, tostring(holdBegan), tostring(distance or "unknown"),
+			prompt.MaxActivationDistance, tostring(prompt.RequiresLineOfSight)
+		)
+	end
+	return true, {
+		path = args.path,
+		player = player.Name,
+		triggered = true,
+		holdDuration = prompt.HoldDuration,
+		elapsedMs = math.floor((os.clock() - startedAt) * 1000),
+		distance = distance,
+	}
+end
+
+-- ─── Sounds ───────────────────────────────────────────────────
+
+local DEFAULT_SOUND_LIMIT = 50
+local MAX_SOUND_LIMIT = 200
+-- Where game sounds live on the server; client-only sounds come from the probe
+local SERVER_SOUND_ROOTS = { "Workspace", "SoundService" }
+
+local function describeSound(sound, side)
+	return {
+		path = sound:GetFullName(),
+		side = side,
+		soundId = sound.SoundId,
+		volume = sound.Volume,
+		timePosition = math.floor(sound.TimePosition * 100 + 0.5) / 100,
+		timeLength = math.floor(sound.TimeLength * 100 + 0.5) / 100,
+		looped = sound.Looped,
+		playbackSpeed = sound.PlaybackSpeed,
+	}
+end
+
+local function listPlayingSounds(args)
+	local limit = math.clamp(math.floor(tonumber(args.limit) or DEFAULT_SOUND_LIMIT), 1, MAX_SOUND_LIMIT)
+	local found, seen = {}, {}
+	for _, serviceName in ipairs(SERVER_SOUND_ROOTS) do
+		for _, descendant in ipairs(game:GetService(serviceName):GetDescendants()) do
+			if descendant:IsA("Sound") and descendant.IsPlaying then
+				local entry = describeSound(descendant, "server")
+				seen[entry.path] = true
+				table.insert(found, entry)
+			end
+		end
+	end
+
+	-- Sounds started by LocalScripts only play on the client
+	local player, clientError = nil, nil
+	if args.includeClient ~= false then
+		player = getPlayerCharacterHumanoid()
+		if args.player then
+			player = Players:FindFirstChild(args.player)
+			if not player then
+				return false, "No player named '" .. tostring(args.player) .. "' in the playtest"
+			end
+		end
+		if player then
+			local ok, result = queryClient(player, { kind = "sounds_playing", limit = MAX_SOUND_LIMIT })
+			if ok then
+				for _, entry in ipairs(result.sounds or {}) do
+					if not seen[entry.path] then
+						seen[entry.path] = true
+						table.insert(found, entry)
+					end
+				end
+			else
+				clientError = result
+			end
+		end
+	end
+
+	table.sort(found, function(a, b)
+		return a.path < b.path
+	end)
+	local sounds = {}
+	for i = 1, math.min(limit, #found) do
+		sounds[i] = found[i]
+	end
+	return true, {
+		player = player and player.Name or nil,
+		count = #found,
+		sounds = sounds,
+		truncated = #found > limit,
+		clientError = clientError,
+	}
+end
+
+-- ─── Network Ownership ────────────────────────────────────────
+
+-- A BasePart by path, or an error naming what was found instead
+local function resolveBasePart(path)
+	if type(path) ~= "string" or path == "" then
+		return nil, "Missing 'path' argument"
+	end
+	local instance = resolveInstancePath(path)
+	if not instance then
+		return nil, "Instance not found: " .. path
+	end
+	if not instance:IsA("BasePart") then
+		return nil, path .. " is a " .. instance.ClassName .. ", not a BasePart. Network ownership belongs to parts (their assembly)."
+	end
+	return instance
+end
+
+-- Ownership is per assembly; the root part's answer holds for every part in it
+local function describeOwnership(part)
+	local canSet, reason = part:CanSetNetworkOwnership()
+	local ok, owner = pcall(function()
+		return part:GetNetworkOwner()
+	end)
+	local autoOk, auto = pcall(function()
+		return part:GetNetworkOwnershipAuto()
+	end)
+	local root = part.AssemblyRootPart
+	return {
+		path = part:GetFullName(),
+		className = part.ClassName,
+		owner = if not ok then nil elseif owner then owner.Name else "server",
+		ownerError = if ok then nil else tostring(owner),
+		auto = if autoOk then auto else nil,
+		anchored = part.Anchored,
+		assemblyRoot = root and root:GetFullName() or nil,
+		canSetOwner = canSet,
+		cannotSetReason = if canSet then nil else reason,
+	}
+end
+
+local function getNetworkOwnership(args)
+	local part, err = resolveBasePart(args.path)
+	if not part then
+		return false, err
+	end
+	return true, describeOwnership(part)
+end
+
+-- args.owner: a player name or "server"; args.auto = true hands it back to the engine
+local function setNetworkOwnership(args)
+	local part, err = resolveBasePart(args.path)
+	if not part then
+		return false, err
+	end
+	local canSet, reason = part:CanSetNetworkOwnership()
+	if not canSet then
+		return false, "Cannot set network ownership of " .. part:GetFullName() .. ": " .. tostring(reason)
+	end
+	local previous = describeOwnership(part)
+	if args.auto == true then
+		part:SetNetworkOwnershipAuto()
+	elseif args.owner == "server" then
+		part:SetNetworkOwner(nil)
+	elseif type(args.owner) == "string" then
+		local player = Players:FindFirstChild(args.owner)
+		if not player or not player:IsA("Player") then
+			return false, "No player named '" .. args.owner .. "' in the playtest. Pass 'server' for the server."
+		end
+		part:SetNetworkOwner(player)
+	else
+		return false, "Pass 'owner' (a player name or 'server') or auto = true"
+	end
+	return true, {
+		path = part:GetFullName(),
+		previous = previous,
+		current = describeOwnership(part),
+	}
+end
+
+-- ─── Replication Stats ────────────────────────────────────────
+
+local REPLICATION_SAMPLE_INTERVAL = 0.25
+-- Stats properties sampled over the window, as { result key, property }
+local REPLICATION_METRICS = {
+	{ "dataSendKbps", "DataSendKbps" },
+	{ "dataReceiveKbps", "DataReceiveKbps" },
+	{ "physicsSendKbps", "PhysicsSendKbps" },
+	{ "physicsReceiveKbps", "PhysicsReceiveKbps" },
+	{ "heartbeatTimeMs", "HeartbeatTimeMs" },
+}
+local MAX_REPLICATION_PLAYERS = 20
+-- Data a player's connection sustains (KB/s) before replication falls behind; a note
+-- warns from 80% of it
+local REPLICATION_PLAYER_BUDGET_KBPS = 50
+
+local function round2(n)
+	return math.floor(n * 100 + 0.5) / 100
+end
+
+-- args.windowMs is filled in and clamped by the server
+local function getReplicationStats(args)
+	local Stats = game:GetService("Stats")
+	local windowMs = tonumber(args.windowMs) or 2000
+	local sampleCount = math.max(2, math.floor(windowMs / 1000 / REPLICATION_SAMPLE_INTERVAL) + 1)
+
+	local totals, peaks, seen = {}, {}, {}
+	for i = 1, sampleCount do
+		for _, metric in ipairs(REPLICATION_METRICS) do
+			local ok, value = pcall(function()
+				return Stats[metric[2]]
+			end)
+			if ok and type(value) == "number" then
+				local key = metric[1]
+				totals[key] = (totals[key] or 0) + value
+				peaks[key] = math.max(peaks[key] or value, value)
+				seen[key] = (seen[key] or 0) + 1
+			end
+		end
+		if i < sampleCount then
+			task.wait(REPLICATION_SAMPLE_INTERVAL)
+		end
+	end
+
+	local metrics, unavailable = {}, {}
+	for _, metric in ipairs(REPLICATION_METRICS) do
+		local key = metric[1]
+		if seen[key] then
+			metrics[key] = { avg = round2(totals[key] / seen[key]), peak = round2(peaks[key]) }
+		else
+			table.insert(unavailable, key)
+		end
+	end
+
+	local players = {}
+	local allPlayers = Players:GetPlayers()
+	for i = 1, math.min(#allPlayers, MAX_REPLICATION_PLAYERS) do
+		local player = allPlayers[i]
+		local ok, ping = pcall(function()
+			return player:GetNetworkPing()
+		end)
+		table.insert(players, { name = player.Name, pingMs = if ok then math.floor(ping * 1000 + 0.5) else nil })
+	end
+
+	local notes = {}
+	local sendPerPlayer = nil
+	if metrics.dataSendKbps and #allPlayers > 0 then
+		sendPerPlayer = round2(metrics.dataSendKbps.avg / #allPlayers)
+		if sendPerPlayer >= REPLICATION_PLAYER_BUDGET_KBPS * 0.8 then
+			table.insert(notes, string.format(
+				"The server sends %.1f KB/s per player; near %d KB/s clients start to fall behind. Look for values or RemoteEvents updated every frame.",
+				sendPerPlayer,
+				REPLICATION_PLAYER_BUDGET_KBPS
+			))
+		end
+	elseif #allPlayers == 0 then
+		table.insert(notes, "No players in the playtest (Run mode), so nothing replicates to clients")
+	end
+
+	return true, {
+		windowMs = windowMs,
+		samples = sampleCount,
+		metrics = metrics,
+		unavailable = unavailable,
+		players = players,
+		playerCount = #allPlayers,
+		sendPerPlayerKbps = sendPerPlayer,
+		notes = notes,
+	}
+end
+
+-- ─── NPC Driver State ─────────────────────────────────────────
+
+local npcDrivers = {}
+local nextDriverId = 1
+
 local function cleanupNpcDrivers()
 	npcDrivers = {}
 	nextDriverId = 1
@@ -205,6 +837,16 @@ local function handleTool(toolName, args)
 			context = "playtest-bridge",
 		}
 
+	elseif toolName == "studio-get_version" then
+		if type(version) ~= "function" then
+			return false, "version() is not available in this Studio build"
+		end
+		local ok, studioVersion = pcall(version)
+		if not ok then
+			return false, "version() failed: " .. tostring(studioVersion)
+		end
+		return true, { studioVersion = studioVersion, luauVersion = _VERSION }
+
 	elseif toolName == "studio-logs_subscribe" then
 		if logConnection then
 			return true, { already_subscribed = true, buffered_count = #logBuffer }
@@ -271,6 +913,7 @@ local function handleTool(toolName, args)
 		return true, { entries = entries, nextSeq = logSeq, subscribed = (logConnection ~= nil) }
 
 	elseif toolName == "studio-playtest_stop" then
+		stopRecording()
 		cleanupVirtualInput()
 		cleanupNpcDrivers()
 		local ok, err = pcall(function()
@@ -453,6 +1096,195 @@ local function handleTool(toolName, args)
 			},
 		}
 
+	elseif toolName == "studio-virtualuser_sequence" then
+		local _, _, humanoid = getPlayerCharacterHumanoid()
+		if not humanoid then
+			return false, "No player character found. Requires Play mode playtest (F5) with a spawned character."
+		end
+		local steps = args.steps
+		if type(steps) ~= "table" or #steps == 0 then
+			return false, "Missing required argument: steps (non-empty array)"
+		end
+		table.sort(steps, function(a, b) return (a.atMs or 0) < (b.atMs or 0) end)
+
+		-- Schedule every step against one start time so sleep overshoot doesn't accumulate.
+		-- With dueClock the server already converted the schedule to our clock.
+		local startedAt = os.clock()
+		local maxLateMs = 0
+		local timings = {}
+		for i, step in ipairs(steps) do
+			local dueAt = step.dueClock or (startedAt + (step.atMs or 0) / 1000)
+			local remaining = dueAt - os.clock()
+			if remaining > 0 then
+				task.wait(remaining)
+			end
+			local firedAt = os.clock()
+			maxLateMs = math.max(maxLateMs, (firedAt - dueAt) * 1000)
+			if step.dueClock then
+				table.insert(timings, { atMs = step.atMs or 0, dueClock = step.dueClock, firedClock = firedAt })
+			end
+
+			local ok, result
+			if step.keyCode then
+				ok, result = handleTool("studio-virtualuser_key", { keyCode = step.keyCode, action = step.action or "down" })
+			elseif step.lookAt then
+				ok, result = handleTool("studio-virtualuser_move_mouse", { lookAt = step.lookAt })
+			elseif step.lookDirection then
+				local _, character = getPlayerCharacterHumanoid()
+				local root = character and character:FindFirstChild("HumanoidRootPart")
+				if root then
+					local d = step.lookDirection
+					local target = root.Position + Vector3.new(d.x or 0, 0, d.z or 0) * 10
+					ok, result = handleTool("studio-virtualuser_move_mouse", { lookAt = { x = target.X, y = target.Y, z = target.Z } })
+				else
+					ok, result = false, "Character has no HumanoidRootPart"
+				end
+			else
+				ok, result = false, "step needs keyCode, lookAt or lookDirection"
+			end
+			if not ok then
+				cleanupVirtualInput()
+				return false, "Step " .. i .. " failed: " .. tostring(result)
+			end
+		end
+
+		-- Never leave the character walking after a sequence
+		local released = {}
+		for key in pairs(virtualKeys) do
+			table.insert(released, key)
+		end
+		if #released > 0 then
+			cleanupVirtualInput()
+		end
+		return true, {
+			steps = #steps,
+			elapsedMs = math.floor((os.clock() - startedAt) * 1000),
+			maxLateMs = math.floor(maxLateMs),
+			releasedKeys = released,
+			timings = if #timings > 0 then timings else nil,
+		}
+
+	elseif toolName == "studio-virtualuser_record_start" then
+		local _, _, humanoid = getPlayerCharacterHumanoid()
+		if not humanoid then
+			return false, "No player character found. Requires Play mode playtest (F5) with a spawned character."
+		end
+		if not args.name then
+			return false, "Missing required argument: name"
+		end
+		if recording then
+			return false, "Already recording '" .. recording.name .. "'"
+		end
+		recording = {
+			name = args.name,
+			startedAt = os.clock(),
+			maxSeconds = args.maxSeconds or 60,
+			keys = {},
+			lastLook = nil,
+			pending = {},
+		}
+		recording.conn = RunService.Heartbeat:Connect(sampleInput)
+		task.spawn(function()
+			while recording and recording.name == args.name do
+				task.wait(RECORD_FLUSH_INTERVAL)
+				flushInputSamples()
+			end
+		end)
+		print("[MCP-Playtest] Input recording started: " .. args.name)
+		return true, { recording = args.name, maxSeconds = recording.maxSeconds }
+
+	elseif toolName == "studio-virtualuser_record_stop" then
+		local name, durationMs = stopRecording()
+		if not name then
+			return false, "No input recording in progress"
+		end
+		print("[MCP-Playtest] Input recording stopped: " .. name)
+		return true, { recording = name, durationMs = durationMs }
+
+	elseif toolName == "studio-get_bindable_actions" then
+		local player = getPlayerCharacterHumanoid()
+		if not player then
+			return false, "No player in the playtest. Bound actions live on the client; use Play mode (F5)."
+		end
+		local ok, result = queryClient(player, "bound_actions")
+		if not ok then
+			return false, result
+		end
+		local response = { player = player.Name, actions = result.actions, count = #result.actions }
+		if #result.actions == 0 then
+			response.note = "No actions are bound via ContextActionService. The game may read input through UserInputService instead."
+		end
+		return true, response
+
+	elseif toolName == "studio-get_humanoid_description" then
+		local player, character, humanoid = resolvePlayerCharacter(args.player)
+		if not player then
+			return false, character
+		end
+		return describeHumanoid(player, character, humanoid)
+
+	elseif toolName == "studio-get_proximity_prompts" then
+		local player, character = resolvePlayerCharacter(args.player)
+		if not player then
+			return false, character
+		end
+		return listProximityPrompts(player, character, args)
+
+	elseif toolName == "studio-trigger_proximity_prompt" then
+		local player, character = resolvePlayerCharacter(args.player)
+		if not player then
+			return false, character
+		end
+		return triggerProximityPrompt(player, character, args)
+
+	elseif toolName == "studio-get_sound_playing" then
+		return listPlayingSounds(args)
+
+	elseif toolName == "studio-get_network_ownership" then
+		return getNetworkOwnership(args)
+
+	elseif toolName == "studio-set_network_ownership" then
+		return setNetworkOwnership(args)
+
+	elseif toolName == "studio-get_replication_stats" then
+		return getReplicationStats(args)
+
+	elseif toolName == "studio-click_gui_button" then
+		local player = getPlayerCharacterHumanoid()
+		if args.player then
+			player = Players:FindFirstChild(args.player)
+		end
+		if not player then
+			return false, "No player in the playtest. GUI lives on the client; use Play mode (F5)."
+		end
+		local ok, result = queryClient(player, { kind = "gui_click", path = args.path })
+		if not ok then
+			return false, result
+		end
+		result.player = player.Name
+		return true, result
+
+	elseif toolName == "studio-get_gui_tree" then
+		local player = getPlayerCharacterHumanoid()
+		if args.player then
+			player = Players:FindFirstChild(args.player)
+		end
+		if not player then
+			return false, "No player in the playtest. GUI lives on the client; use Play mode (F5)."
+		end
+		local ok, result = queryClient(player, {
+			kind = "gui_tree",
+			root = args.root,
+			maxDepth = args.maxDepth,
+			maxNodes = args.maxNodes,
+			visibleOnly = args.visibleOnly,
+		})
+		if not ok then
+			return false, result
+		end
+		result.player = player.Name
+		return true, result
+
 	elseif toolName == "studio-npc_driver_start" then
 		local targetPath = args.target
 		if not targetPath then
@@ -603,7 +1435,7 @@ local function handleTool(toolName, args)
 		return true, { driverId = driverId, stopped = true }
 
 	else
-		return false, "Tool '" .. tostring(toolName) .. "' is not available during playtest. Available: studio-status, studio-logs_*, studio-playtest_stop, studio-virtualuser_*, studio-npc_driver_*"
+		return false, "Tool '" .. tostring(toolName) .. "' is not available during playtest. Available: studio-status, studio-logs_*, studio-playtest_stop, studio-virtualuser_*, studio-npc_driver_*, studio-get_bindable_actions, studio-get_humanoid_description, studio-get_proximity_prompts, studio-trigger_proximity_prompt, studio-click_gui_button, studio-get_gui_tree, studio-get_sound_playing, studio-get_network_ownership, studio-set_network_ownership, studio-get_replication_stats"
 	end
 end
 
@@ -611,12 +1443,13 @@ end
 
 task.wait(1)
 
-local ok, data, err = request("POST", "/register", { plugin_version = "0.1.0-playtest" })
+local REGISTER_BODY = { plugin_version = "0.1.0-playtest", features = { "event_seq" } }
+local ok, data, err = request("POST", "/register", REGISTER_BODY)
 if not ok then
 	warn("[MCP-Playtest] Failed to register: " .. tostring(err))
 	for i = 1, 5 do
 		task.wait(2)
-		ok, data, err = request("POST", "/register", { plugin_version = "0.1.0-playtest" })
+		ok, data, err = request("POST", "/register", REGISTER_BODY)
 		if ok then break end
 		warn("[MCP-Playtest] Retry " .. i .. " failed: " .. tostring(err))
 	end
@@ -624,12 +1457,57 @@ end
 
 if ok and data then
 	clientId = data.client_id
+	numberEvents = type(data.features) == "table" and table.find(data.features, "event_seq") ~= nil
 	print("[MCP-Playtest] Registered with server, clientId: " .. tostring(clientId))
 else
 	warn("[MCP-Playtest] Could not register, giving up")
 	return
 end
 
+-- Readiness: the server holds character tools until characterSpawned
+
+local function pushReady(stage, player)
+	pushEvents({ { event_type = "studio-playtest_ready", data = { stage = stage, player = player and player.Name } } })
+end
+
+local watchedPlayer = nil
+
+-- Virtual input drives the first player, so only that one's stages are reported
+local function watchPlayer(player)
+	if watchedPlayer then return end
+	watchedPlayer = player
+	pushReady("playerAdded", player)
+
+	local function onCharacter(character)
+		-- Input needs the Humanoid and root part, which load after the model itself
+		local humanoid = character:WaitForChild("Humanoid", 10)
+		local root = character:WaitForChild("HumanoidRootPart", 10)
+		if humanoid and root and player.Character == character then
+			pushReady("characterSpawned", player)
+		end
+	end
+
+	player.CharacterAdded:Connect(onCharacter)
+	player.CharacterRemoving:Connect(function()
+		pushReady("playerAdded", player)
+	end)
+	if player.Character then
+		task.spawn(onCharacter, player.Character)
+	end
+end
+
+pushReady("serverStarted")
+Players.PlayerAdded:Connect(watchPlayer)
+Players.PlayerRemoving:Connect(function(player)
+	if player == watchedPlayer then
+		watchedPlayer = nil
+		pushReady("serverStarted")
+	end
+end)
+for _, player in ipairs(Players:GetPlayers()) do
+	watchPlayer(player)
+end
+
 while RunService:IsRunning() do
 	local pollOk, requests, pollErr = request("GET", "/pull")
 
@@ -661,7 +1539,9 @@ while RunService:IsRunning() do
 	end
 end
 
+stopRecording()
 cleanupVirtualInput()
 cleanupNpcDrivers()
+inputProbe:Destroy()
 print("[MCP-Playtest] Playtest ended, bridge shutting down")
 ]==]
@@ -0,0 +1,109 @@
+-- tools/camera.lua
+-- Frame the Studio edit camera on an instance or the current selection, like
+-- pressing F. Uses Camera:ZoomToExtents when available, otherwise places the
+-- camera along its current look direction at a distance that fits the bounds.
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local Camera = {}
+
+-- Extra room around the bounds when framing manually
+local FRAME_MARGIN = 1.2
+
+local function collectParts(instance, parts)
+	if instance:IsA("BasePart") then
+		table.insert(parts, instance)
+	end
+	for _, descendant in ipairs(instance:GetDescendants()) do
+		if descendant:IsA("BasePart") then
+			table.insert(parts, descendant)
+		end
+	end
+end
+
+-- World-axis-aligned box around every part's oriented box
+local function boundsOf(parts)
+	local minV, maxV = nil, nil
+	for _, part in ipairs(parts) do
+		local half = part.Size / 2
+		for _, sx in ipairs({ -1, 1 }) do
+			for _, sy in ipairs({ -1, 1 }) do
+				for _, sz in ipairs({ -1, 1 }) do
+					local corner = part.CFrame:PointToWorldSpace(Vector3.new(half.X * sx, half.Y * sy, half.Z * sz))
+					minV = if minV then minV:Min(corner) else corner
+					maxV = if maxV then maxV:Max(corner) else corner
+				end
+			end
+		end
+	end
+	return (minV + maxV) / 2, maxV - minV
+end
+
+local function vectorTable(v)
+	return { x = v.X, y = v.Y, z = v.Z }
+end
+
+function Camera.focusOn(args, _ctx)
+	local camera = workspace.CurrentCamera
+	if not camera then
+		return false, "No current camera in the edit DataModel"
+	end
+
+	local targets = {}
+	if args.path then
+		local instance = InstancePath.resolve(args.path)
+		if not instance then
+			return false, "Instance not found: " .. tostring(args.path)
+		end
+		table.insert(targets, instance)
+	else
+		local ok, selection = pcall(function()
+			return game:GetService("Selection"):Get()
+		end)
+		if not ok or #selection == 0 then
+			return false, "Nothing to focus on: pass 'path' or select instances in Studio"
+		end
+		targets = selection
+	end
+
+	local parts = {}
+	for _, instance in ipairs(targets) do
+		collectParts(instance, parts)
+	end
+	if #parts == 0 then
+		return false, "The target has no parts to frame (only BaseParts and their ancestors have bounds)"
+	end
+	local center, size = boundsOf(parts)
+
+	local method = "ZoomToExtents"
+	local zoomed = pcall(function()
+		camera:ZoomToExtents(CFrame.new(center), size)
+	end)
+	if not zoomed then
+		method = "manual"
+		local radius = math.max(size.Magnitude / 2, 0.5)
+		local distance = radius / math.tan(math.rad(camera.FieldOfView / 2)) * FRAME_MARGIN
+		local position = center - camera.CFrame.LookVector * distance
+		camera.CFrame = CFrame.lookAt(position, center)
+		camera.Focus = CFrame.new(center)
+	end
+
+	local cf = camera.CFrame
+	return true, {
+		targets = #targets,
+		method = method,
+		boundsCenter = vectorTable(center),
+		boundsSize = vectorTable(size),
+		camera = {
+			position = vectorTable(cf.Position),
+			lookVector = vectorTable(cf.LookVector),
+			components = { cf:GetComponents() },
+		},
+	}
+end
+
+-- Shared with viewport_render.lua, which frames instances the same way
+Camera.collectParts = collectParts
+Camera.boundsOf = boundsOf
+
+return Camera
@@ -1,14 +1,84 @@
 -- tools/checkpoint.lua
--- Undo/redo checkpoint management via ChangeHistoryService.
+-- Undo/redo checkpoint management and undo history via ChangeHistoryService.
 
 local ChangeHistoryService = game:GetService("ChangeHistoryService")
 
+local Playtest = require(script.Parent.playtest)
+
 local Checkpoint = {}
 
 -- Map of checkpointId -> recording handle
 local recordings = {}
 local nextId = 1
 
+-- Undo history as seen since the plugin loaded. Studio has no API that lists
+-- waypoints, so it is rebuilt from ChangeHistoryService events: entries[1..position]
+-- are applied (undoable), the rest were undone (redoable).
+local HISTORY_CAPACITY = 100
+local history = {
+	entries = {},
+	position = 0,
+	seq = 0,
+	-- Set when an undo/redo names a waypoint the list doesn't have there
+	inSync = true,
+	trackedSince = os.time(),
+	unavailable = {},
+}
+
+local function connectHistoryEvent(eventName, handler)
+	local ok, event = pcall(function()
+		return ChangeHistoryService[eventName]
+	end)
+	if ok and typeof(event) == "RBXScriptSignal" then
+		event:Connect(handler)
+	else
+		table.insert(history.unavailable, eventName)
+	end
+end
+
+local function pushWaypoint(name)
+	-- A new waypoint drops everything that could have been redone
+	for i = #history.entries, history.position + 1, -1 do
+		table.remove(history.entries, i)
+	end
+	history.seq = history.seq + 1
+	table.insert(history.entries, {
+		seq = history.seq,
+		name = name,
+		at = os.time(),
+	})
+	while #history.entries > HISTORY_CAPACITY do
+		table.remove(history.entries, 1)
+	end
+	history.position = #history.entries
+	return history.seq
+end
+
+connectHistoryEvent("OnRecordingFinished", function(name, displayName, _identifier, operation)
+	if operation ~= Enum.FinishRecordingOperation.Commit then
+		return
+	end
+	pushWaypoint(displayName or name)
+end)
+
+connectHistoryEvent("OnUndo", function(waypoint)
+	local current = history.entries[history.position]
+	if current and current.name == waypoint then
+		history.position = history.position - 1
+	else
+		history.inSync = false
+	end
+end)
+
+connectHistoryEvent("OnRedo", function(waypoint)
+	local nextEntry = history.entries[history.position + 1]
+	if nextEntry and nextEntry.name == waypoint then
+		history.position = history.position + 1
+	else
+		history.inSync = false
+	end
+end)
+
 function Checkpoint.beginRecording(args, _ctx)
 	local name = args.name or "MCP Checkpoint"
 
@@ -51,6 +121,38 @@ function Checkpoint.endRecording(args, _ctx)
 	}
 end
 
+function Checkpoint.setWaypoint(args, _ctx)
+	local name = args.name
+	if type(name) ~= "string" or name == "" then
+		return false, "Missing 'name' argument"
+	end
+	-- Edits made while a playtest runs are discarded (Run) or invisible to it (Play)
+	if Playtest.isActive() then
+		return false, "Stop the playtest first: studio-set_waypoint marks the edit DataModel's undo history, which the running session doesn't use"
+	end
+	-- A waypoint inside an open recording would split it
+	local openId = next(recordings)
+	if openId then
+		return false, "Checkpoint " .. openId .. " is still recording; end it with studio-checkpoint_end before setting a waypoint"
+	end
+
+	local ok, err = pcall(function()
+		ChangeHistoryService:SetWaypoint(name)
+	end)
+	if not ok then
+		return false, "SetWaypoint failed: " .. tostring(err)
+	end
+	-- SetWaypoint doesn't fire OnRecordingFinished, so the history is updated here
+	local seq = pushWaypoint(name)
+
+	print("[MCP] Waypoint set: " .. name)
+	return true, {
+		ok = true,
+		name = name,
+		seq = seq,
+	}
+end
+
 function Checkpoint.undo(args, _ctx)
 	local checkpointId = args.checkpointId
 
@@ -75,4 +177,54 @@ function Checkpoint.undo(args, _ctx)
 	}
 end
 
+function Checkpoint.recentChanges(args, _ctx)
+	local limit = math.clamp(math.floor(tonumber(args.limit) or 20), 1, HISTORY_CAPACITY)
+
+	-- Newest first, so the list starts at the top of the redo stack
+	local waypoints = {}
+	local first = math.max(1, #history.entries - limit + 1)
+	for i = #history.entries, first, -1 do
+		local entry = history.entries[i]
+		table.insert(waypoints, {
+			seq = entry.seq,
+			name = entry.name,
+			at = entry.at,
+			state = if i <= history.position then "applied" else "undone",
+			current = i == history.position,
+		})
+	end
+
+	local result = {
+		waypoints = waypoints,
+		position = history.position,
+		total = #history.entries,
+		truncated = first > 1,
+		inSync = history.inSync,
+		trackedSince = history.trackedSince,
+		unavailable = table.clone(history.unavailable),
+	}
+
+	-- Studio's own view of the next undo/redo, which covers waypoints the list missed
+	local okUndo, canUndo, undoName = pcall(function()
+		return ChangeHistoryService:GetCanUndo()
+	end)
+	if okUndo then
+		result.canUndo = canUndo and true or false
+		result.undoName = if canUndo then undoName else nil
+	else
+		table.insert(result.unavailable, "GetCanUndo")
+	end
+	local okRedo, canRedo, redoName = pcall(function()
+		return ChangeHistoryService:GetCanRedo()
+	end)
+	if okRedo then
+		result.canRedo = canRedo and true or false
+		result.redoName = if canRedo then redoName else nil
+	else
+		table.insert(result.unavailable, "GetCanRedo")
+	end
+
+	return true, result
+end
+
 return Checkpoint
@@ -0,0 +1,162 @@
+-- tools/collision.lua
+-- Collision groups: list the groups registered with PhysicsService, which pairs
+-- collide, and which group each part in a subtree belongs to; assign parts to a
+-- group in the edit DataModel.
+
+local PhysicsService = game:GetService("PhysicsService")
+local ChangeHistoryService = game:GetService("ChangeHistoryService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+local Playtest = require(script.Parent.playtest)
+
+local Collision = {}
+
+local DEFAULT_GROUP = "Default"
+local DEFAULT_PART_LIMIT = 200
+local MAX_PART_LIMIT = 1000
+
+local function registeredGroups()
+	local ok, groups = pcall(function()
+		return PhysicsService:GetRegisteredCollisionGroups()
+	end)
+	if not ok then
+		return nil, "PhysicsService:GetRegisteredCollisionGroups is not available: " .. tostring(groups)
+	end
+	return groups
+end
+
+-- Names of the groups `name` collides with, or nil when Studio can't tell us
+local function collidesWith(name, groups)
+	local names = {}
+	for _, other in ipairs(groups) do
+		local ok, collidable = pcall(function()
+			return PhysicsService:CollisionGroupsAreCollidable(name, other.name)
+		end)
+		if not ok then
+			return nil
+		end
+		if collidable then
+			table.insert(names, other.name)
+		end
+	end
+	return names
+end
+
+local function partsUnder(root)
+	local parts = {}
+	if root:IsA("BasePart") then
+		table.insert(parts, root)
+	end
+	for _, descendant in ipairs(root:GetDescendants()) do
+		if descendant:IsA("BasePart") then
+			table.insert(parts, descendant)
+		end
+	end
+	return parts
+end
+
+function Collision.getGroups(args, _ctx)
+	local groups, err = registeredGroups()
+	if not groups then
+		return false, err
+	end
+
+	local result = { groups = {} }
+	for _, group in ipairs(groups) do
+		table.insert(result.groups, {
+			name = group.name,
+			id = group.id,
+			collidesWith = collidesWith(group.name, groups),
+		})
+	end
+	if args.groupsOnly then
+		return true, result
+	end
+
+	local rootPath = args.path or "Workspace"
+	local root = InstancePath.resolve(rootPath)
+	if not root then
+		return false, "Instance not found: " .. tostring(rootPath)
+	end
+	local limit = math.clamp(tonumber(args.limit) or DEFAULT_PART_LIMIT, 1, MAX_PART_LIMIT)
+
+	local counts = {}
+	local parts = {}
+	local listed = 0
+	local matched = 0
+	for _, part in ipairs(partsUnder(root)) do
+		local group = part.CollisionGroup
+		counts[group] = (counts[group] or 0) + 1
+		if args.includeDefault or group ~= DEFAULT_GROUP then
+			matched += 1
+			if listed < limit then
+				listed += 1
+				table.insert(parts, { path = part:GetFullName(), group = group })
+			end
+		end
+	end
+
+	result.root = root:GetFullName()
+	result.countsByGroup = counts
+	result.parts = parts
+	result.truncated = matched > listed
+	return true, result
+end
+
+function Collision.setGroup(args, _ctx)
+	-- Edits made while a playtest runs are discarded (Run) or invisible to it (Play)
+	if Playtest.isActive() then
+		return false, "Stop the playtest first: studio-set_collision_group edits the edit DataModel, which the running session doesn't see"
+	end
+	if type(args.path) ~= "string" or args.path == "" then
+		return false, "Missing 'path' argument"
+	end
+	if type(args.group) ~= "string" or args.group == "" then
+		return false, "Missing 'group' argument"
+	end
+
+	local okRegistered, registered = pcall(function()
+		return PhysicsService:IsCollisionGroupRegistered(args.group)
+	end)
+	if okRegistered and not registered then
+		return false, "Collision group is not registered: " .. args.group
+	end
+
+	local target = InstancePath.resolve(args.path)
+	if not target then
+		return false, "Instance not found: " .. args.path
+	end
+	local parts
+	if target:IsA("BasePart") and not args.recursive then
+		parts = { target }
+	elseif args.recursive then
+		parts = partsUnder(target)
+	else
+		return false, target:GetFullName() .. " is a " .. target.ClassName .. ", not a BasePart; pass recursive = true to assign its descendant parts"
+	end
+	if #parts == 0 then
+		return false, "No BaseParts under " .. target:GetFullName()
+	end
+
+	-- One undo step; inside an open checkpoint the change joins that recording
+	local recording = ChangeHistoryService:TryBeginRecording("MCP Set Collision Group")
+	local changed = 0
+	for _, part in ipairs(parts) do
+		if part.CollisionGroup ~= args.group then
+			part.CollisionGroup = args.group
+			changed += 1
+		end
+	end
+	if recording then
+		ChangeHistoryService:FinishRecording(recording, Enum.FinishRecordingOperation.Commit)
+	end
+
+	return true, {
+		target = target:GetFullName(),
+		group = args.group,
+		parts = #parts,
+		changed = changed,
+	}
+end
+
+return Collision
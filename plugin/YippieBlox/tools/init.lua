@@ -8,6 +8,19 @@ local Logs = require(script.logs)
 local VirtualUserTools = require(script.virtualuser)
 local NpcDriver = require(script.npc_driver)
 local Capture = require(script.capture)
+local Tree = require(script.tree)
+local Camera = require(script.camera)
+local Collision = require(script.collision)
+local Properties = require(script.properties)
+local SelectionTools = require(script.selection)
+local Pivot = require(script.pivot)
+local WaitCondition = require(script.wait_condition)
+local ScriptSource = require(script.script_source)
+local ScriptEditor = require(script.script_editor)
+local ModelSnapshot = require(script.model_snapshot)
+local StudioSettings = require(script.studio_settings)
+local ViewportRender = require(script.viewport_render)
+local PlaceStats = require(script.place_stats)
 
 local ToolRouter = {}
 
@@ -23,13 +36,58 @@ local handlers = {
 		}
 	end,
 
+	-- Studio build, for studio-get_version (the server adds its own and the plugin version)
+	["studio-get_version"] = function(_args, _ctx)
+		if type(version) ~= "function" then
+			return false, "version() is not available in this Studio build"
+		end
+		local ok, studioVersion = pcall(version)
+		if not ok then
+			return false, "version() failed: " .. tostring(studioVersion)
+		end
+		return true, {
+			studioVersion = studioVersion,
+			luauVersion = _VERSION,
+		}
+	end,
+
+	-- Active ribbon tool and grid size, for studio-get_current_tool
+	["studio-get_current_tool"] = function(_args, ctx)
+		local plugin = ctx.plugin
+		local result = { unavailable = {} }
+
+		local okTool, tool = pcall(function()
+			return plugin:GetSelectedRibbonTool()
+		end)
+		if okTool and typeof(tool) == "EnumItem" then
+			result.activeTool = tool.Name
+		else
+			table.insert(result.unavailable, "activeTool")
+		end
+
+		local okGrid, gridSize = pcall(function()
+			return plugin.GridSize
+		end)
+		if okGrid and type(gridSize) == "number" then
+			result.gridSize = gridSize
+		else
+			table.insert(result.unavailable, "gridSize")
+		end
+
+		return true, result
+	end,
+
 	-- Script execution
 	["studio-run_script"] = RunScript.execute,
+	["studio-wait_for_condition"] = WaitCondition.wait,
+	["studio-get_lua_globals"] = RunScript.globals,
 
 	-- Checkpoint / undo
 	["studio-checkpoint_begin"] = Checkpoint.beginRecording,
 	["studio-checkpoint_end"] = Checkpoint.endRecording,
 	["studio-checkpoint_undo"] = Checkpoint.undo,
+	["studio-set_waypoint"] = Checkpoint.setWaypoint,
+	["studio-get_recent_changes"] = Checkpoint.recentChanges,
 
 	-- Playtest control
 	["studio-playtest_play"] = Playtest.play,
@@ -47,16 +105,106 @@ local handlers = {
 	["studio-virtualuser_key"] = VirtualUserTools.key,
 	["studio-virtualuser_mouse_button"] = VirtualUserTools.mouseButton,
 	["studio-virtualuser_move_mouse"] = VirtualUserTools.moveMouse,
+	["studio-virtualuser_sequence"] = VirtualUserTools.sequence,
+	["studio-virtualuser_record_start"] = VirtualUserTools.recordStart,
+	["studio-virtualuser_record_stop"] = VirtualUserTools.recordStop,
+
+	-- Answered by the playtest bridge via the client input probe; this is a safety net
+	["studio-get_bindable_actions"] = function(_args, _ctx)
+		return false, "studio-get_bindable_actions requires an active Play mode playtest (F5). ContextActionService bindings exist only on the client."
+	end,
+
+	-- Answered by the playtest bridge, which can see the player's character; safety nets
+	["studio-get_humanoid_description"] = function(_args, _ctx)
+		return false, "studio-get_humanoid_description requires an active Play mode playtest (F5) with a spawned character."
+	end,
+
+	["studio-get_proximity_prompts"] = function(_args, _ctx)
+		return false, "studio-get_proximity_prompts requires an active Play mode playtest (F5) with a spawned character."
+	end,
+
+	["studio-trigger_proximity_prompt"] = function(_args, _ctx)
+		return false, "studio-trigger_proximity_prompt requires an active Play mode playtest (F5) with a spawned character."
+	end,
+
+	-- Answered by the playtest bridge through the client input probe; safety net
+	["studio-click_gui_button"] = function(_args, _ctx)
+		return false, "studio-click_gui_button requires an active Play mode playtest (F5). Player GUI exists only on the client."
+	end,
+	["studio-get_gui_tree"] = function(_args, _ctx)
+		return false, "studio-get_gui_tree requires an active Play mode playtest (F5). Player GUI exists only on the client."
+	end,
+
+	-- Answered by the playtest bridge, which sees the running game's sounds; safety net
+	["studio-get_sound_playing"] = function(_args, _ctx)
+		return false, "studio-get_sound_playing requires an active playtest. Sounds only play while the game runs."
+	end,
+
+	-- Answered by the playtest bridge on the game server, which owns the physics; safety net
+	["studio-get_network_ownership"] = function(_args, _ctx)
+		return false, "studio-get_network_ownership requires an active playtest. Network ownership only exists while the game runs."
+	end,
+	["studio-set_network_ownership"] = function(_args, _ctx)
+		return false, "studio-set_network_ownership requires an active playtest. Network ownership only exists while the game runs."
+	end,
+
+	-- Answered by the playtest bridge, which sees the game server's network stats; safety net
+	["studio-get_replication_stats"] = function(_args, _ctx)
+		return false, "studio-get_replication_stats requires an active playtest. Nothing replicates while the game isn't running."
+	end,
 
 	-- NPC driver
 	["studio-npc_driver_start"] = NpcDriver.start,
 	["studio-npc_driver_command"] = NpcDriver.command,
 	["studio-npc_driver_stop"] = NpcDriver.stop,
 
+	-- Collision groups
+	["studio-get_collision_groups"] = Collision.getGroups,
+	["studio-set_collision_group"] = Collision.setGroup,
+
+	-- Pivots
+	["studio-get_pivot"] = Pivot.get,
+	["studio-set_pivot"] = Pivot.set,
+
+	-- Properties of many instances
+	["studio-batch_get_properties"] = Properties.batchGet,
+
+	-- Edit camera
+	["studio-focus_camera_on"] = Camera.focusOn,
+
+	-- Studio selection
+	["studio-select_matching"] = SelectionTools.selectMatching,
+
+	-- Script editor
+	["studio-open_script"] = ScriptEditor.open,
+	["studio-get_open_scripts"] = ScriptEditor.listOpen,
+
+	-- Instance counts for studio-place_stats (the server compares snapshots)
+	["studio-place_stats"] = PlaceStats.collect,
+
 	-- Capture
 	["studio-capture_screenshot"] = Capture.screenshot,
 	["studio-capture_video_start"] = Capture.videoStart,
 	["studio-capture_video_stop"] = Capture.videoStop,
+	["studio-capture_viewport_via_render"] = ViewportRender.render,
+
+	-- Path index feed (server-internal, not an MCP tool)
+	["studio-tree_snapshot"] = Tree.snapshot,
+
+	-- Property overrides for studio-test_matrix variants (server-internal)
+	["studio-set_properties"] = Properties.apply,
+
+	-- Property value type for studio-is_tweenable (server-internal)
+	["studio-get_property_type"] = Properties.typeOf,
+
+	-- Script sources for traceback annotations (server-internal)
+	["studio-get_script_sources"] = ScriptSource.read,
+
+	-- Subtree snapshots for studio-diff_places (server-internal)
+	["studio-snapshot_subtree"] = ModelSnapshot.take,
+
+	-- Studio settings for studio-set_studio_setting (server-internal)
+	["studio-set_studio_settings"] = StudioSettings.apply,
 }
 
 --- Dispatch a tool call to the appropriate handler.
@@ -14,6 +14,24 @@ local logBuffer = RingBuffer.new(500)
 local seq = 0
 local bridgeRef = nil  -- set when subscribe is called with ctx.bridge
 
+-- While the server asks for less background traffic, entries go out in batches
+-- every LOG_BATCH_INTERVAL seconds instead of one push each
+local LOG_BATCH_INTERVAL = 1
+local unsent = {}
+local batchScheduled = false
+
+local function sendBatch()
+	batchScheduled = false
+	local events = {}
+	for _, entry in ipairs(unsent) do
+		table.insert(events, { event_type = "studio-log", data = entry })
+	end
+	unsent = {}
+	if bridgeRef and #events > 0 then
+		bridgeRef:pushEvents(events)
+	end
+end
+
 -- Map Roblox MessageType enum to string level names
 local MESSAGE_TYPE_MAP = {
 	[Enum.MessageType.MessageOutput] = "output",
@@ -40,11 +58,20 @@ local function addLog(message, messageType, timestamp)
 
 	logBuffer:push(entry)
 
-	-- Stream to bridge if connected
+	-- Stream to bridge if connected. A batch already waiting keeps collecting, so
+	-- entries stay in order when the level rises again.
 	if bridgeRef then
-		task.spawn(function()
-			bridgeRef:pushEvent("studio-log", entry)
-		end)
+		if bridgeRef:isBackgroundReduced() or batchScheduled then
+			table.insert(unsent, entry)
+			if not batchScheduled then
+				batchScheduled = true
+				task.delay(LOG_BATCH_INTERVAL, sendBatch)
+			end
+		else
+			task.spawn(function()
+				bridgeRef:pushEvent("studio-log", entry)
+			end)
+		end
 	end
 end
 
@@ -109,6 +136,11 @@ function Logs.unsubscribe(_args, _ctx)
 	return true, { ok = true }
 end
 
+--- Log entries are being streamed to the server as studio-log events
+function Logs.isForwarding()
+	return subscribed and bridgeRef ~= nil
+end
+
 function Logs.get(args, _ctx)
 	local sinceSeq = args.sinceSeq or 0
 	local limit = args.limit or 200
@@ -0,0 +1,125 @@
+-- tools/model_snapshot.lua
+-- Snapshots of a subtree for studio-diff_places (server-internal): every instance
+-- under the root with its class, a per-class set of properties, attributes and tags.
+-- The server compares two snapshots and saves them as files for later sessions.
+
+local CollectionService = game:GetService("CollectionService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+local Properties = require(script.Parent.properties)
+
+local ModelSnapshot = {}
+
+local DEFAULT_MAX_INSTANCES = 2000
+
+-- Properties recorded per class (IsA, so BasePart covers every part type). Reads go
+-- through pcall, so a property some subclasses lack is just left out.
+local CLASS_PROPERTIES = {
+	{ "BasePart", { "Position", "Orientation", "Size", "Color", "Material", "Transparency", "Reflectance", "Anchored", "CanCollide" } },
+	{ "Model", { "PrimaryPart" } },
+	{ "ValueBase", { "Value" } },
+	{ "GuiObject", { "Position", "Size", "Visible", "BackgroundColor3", "BackgroundTransparency", "ZIndex" } },
+	{ "TextLabel", { "Text" } },
+	{ "TextButton", { "Text" } },
+	{ "TextBox", { "Text" } },
+	{ "Light", { "Enabled", "Brightness", "Color", "Range" } },
+	{ "FaceInstance", { "Face" } },
+	{ "Decal", { "Texture", "Transparency" } },
+	{ "Sound", { "SoundId", "Volume", "Looped" } },
+	{ "Humanoid", { "WalkSpeed", "JumpPower", "MaxHealth" } },
+	{ "BaseScript", { "Enabled" } },
+}
+
+-- Script sources go in as "<length>:<hash>", so an edit shows up without sending
+-- the text
+local function sourceDigest(source)
+	local hash = 5381
+	for i = 1, #source do
+		hash = (hash * 33 + string.byte(source, i)) % 4294967296
+	end
+	return string.format("%d:%08x", #source, hash)
+end
+
+local function record(instance)
+	local properties = {}
+	for _, entry in ipairs(CLASS_PROPERTIES) do
+		if instance:IsA(entry[1]) then
+			for _, property in ipairs(entry[2]) do
+				local ok, value = pcall(function()
+					return instance[property]
+				end)
+				-- A child with the property's name isn't the property
+				if ok and value ~= nil and not (typeof(value) == "Instance" and value.Parent == instance and value.Name == property) then
+					properties[property] = Properties.describe(value)
+				end
+			end
+		end
+	end
+	if instance:IsA("LuaSourceContainer") then
+		local ok, source = pcall(function()
+			return instance.Source
+		end)
+		if ok and type(source) == "string" then
+			properties.Source = sourceDigest(source)
+		end
+	end
+	for name, value in pairs(instance:GetAttributes()) do
+		properties["@" .. name] = Properties.describe(value)
+	end
+	local tags = CollectionService:GetTags(instance)
+	if #tags > 0 then
+		table.sort(tags)
+		properties.Tags = tags
+	end
+	return {
+		className = instance.ClassName,
+		properties = properties,
+	}
+end
+
+--- args.path: root of the subtree; args.maxInstances bounds the walk. Instances are
+--- keyed by their path below the root; a sibling with a name already taken gets
+--- "#2", "#3", ... in child order.
+function ModelSnapshot.take(args, _ctx)
+	if type(args.path) ~= "string" or args.path == "" then
+		return false, "Missing 'path' argument"
+	end
+	local root = InstancePath.resolve(args.path)
+	if not root then
+		return false, "Instance not found: " .. args.path
+	end
+	local maxInstances = tonumber(args.maxInstances) or DEFAULT_MAX_INSTANCES
+
+	local instances = {}
+	local count = 0
+	local truncated = false
+
+	local function walk(instance, key)
+		if count >= maxInstances then
+			truncated = true
+			return
+		end
+		count += 1
+		instances[key] = record(instance)
+		local seen = {}
+		for _, child in ipairs(instance:GetChildren()) do
+			local name = child.Name
+			seen[name] = (seen[name] or 0) + 1
+			local childName = if seen[name] > 1 then name .. "#" .. seen[name] else name
+			walk(child, if key == "" then childName else key .. "." .. childName)
+			if truncated then
+				return
+			end
+		end
+	end
+	walk(root, "")
+
+	return true, {
+		root = root:GetFullName(),
+		instances = instances,
+		count = count,
+		truncated = truncated,
+	}
+end
+
+return ModelSnapshot
@@ -0,0 +1,122 @@
+-- tools/pivot.lua
+-- Read and move the pivot of a Model or BasePart (PVInstance:GetPivot/PivotTo),
+-- so related objects can be positioned without telling models and parts apart.
+
+local ChangeHistoryService = game:GetService("ChangeHistoryService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+local Playtest = require(script.Parent.playtest)
+
+local Pivot = {}
+
+local function vectorTable(v)
+	return { x = v.X, y = v.Y, z = v.Z }
+end
+
+local function cframeTable(cf)
+	local rx, ry, rz = cf:ToOrientation()
+	return {
+		position = vectorTable(cf.Position),
+		orientation = { x = math.deg(rx), y = math.deg(ry), z = math.deg(rz) },
+		components = { cf:GetComponents() },
+	}
+end
+
+local function resolvePV(path)
+	if type(path) ~= "string" or path == "" then
+		return nil, "Missing 'path' argument"
+	end
+	local instance = InstancePath.resolve(path)
+	if not instance then
+		return nil, "Instance not found: " .. path
+	end
+	if not instance:IsA("PVInstance") then
+		return nil, instance:GetFullName() .. " is a " .. instance.ClassName .. "; only Models and BaseParts have a pivot"
+	end
+	return instance
+end
+
+local function readVector(value, name)
+	if type(value) ~= "table" or #value ~= 3 then
+		return nil, "'" .. name .. "' must be an [x, y, z] array"
+	end
+	for _, component in ipairs(value) do
+		if type(component) ~= "number" then
+			return nil, "'" .. name .. "' must be an [x, y, z] array of numbers"
+		end
+	end
+	return Vector3.new(value[1], value[2], value[3])
+end
+
+function Pivot.get(args, _ctx)
+	local instance, err = resolvePV(args.path)
+	if not instance then
+		return false, err
+	end
+
+	local result = {
+		path = instance:GetFullName(),
+		className = instance.ClassName,
+		pivot = cframeTable(instance:GetPivot()),
+	}
+	if instance:IsA("Model") then
+		result.primaryPart = if instance.PrimaryPart then instance.PrimaryPart:GetFullName() else nil
+		local ok, boxCFrame, boxSize = pcall(function()
+			return instance:GetBoundingBox()
+		end)
+		if ok then
+			result.boundingBox = { center = vectorTable(boxCFrame.Position), size = vectorTable(boxSize) }
+		end
+	elseif instance:IsA("BasePart") then
+		result.size = vectorTable(instance.Size)
+		result.pivotOffset = cframeTable(instance.PivotOffset)
+	end
+	return true, result
+end
+
+function Pivot.set(args, _ctx)
+	-- Edits made while a playtest runs are discarded (Run) or invisible to it (Play)
+	if Playtest.isActive() then
+		return false, "Stop the playtest first: studio-set_pivot edits the edit DataModel, which the running session doesn't see"
+	end
+	local instance, err = resolvePV(args.path)
+	if not instance then
+		return false, err
+	end
+	if args.position == nil and args.orientation == nil then
+		return false, "Pass 'position', 'orientation' or both"
+	end
+
+	local previous = instance:GetPivot()
+	local position = previous.Position
+	if args.position ~= nil then
+		local value, vectorErr = readVector(args.position, "position")
+		if not value then
+			return false, vectorErr
+		end
+		position = value
+	end
+	local rotation = previous - previous.Position
+	if args.orientation ~= nil then
+		local value, vectorErr = readVector(args.orientation, "orientation")
+		if not value then
+			return false, vectorErr
+		end
+		rotation = CFrame.fromOrientation(math.rad(value.X), math.rad(value.Y), math.rad(value.Z))
+	end
+
+	-- One undo step; inside an open checkpoint the change joins that recording
+	local recording = ChangeHistoryService:TryBeginRecording("MCP Set Pivot")
+	instance:PivotTo(CFrame.new(position) * rotation)
+	if recording then
+		ChangeHistoryService:FinishRecording(recording, Enum.FinishRecordingOperation.Commit)
+	end
+
+	return true, {
+		path = instance:GetFullName(),
+		previous = cframeTable(previous),
+		pivot = cframeTable(instance:GetPivot()),
+	}
+end
+
+return Pivot
@@ -0,0 +1,120 @@
+-- tools/place_stats.lua
+-- Counts for studio-place_stats: instances per class, script source length and
+-- texture references under a root. Only raw numbers; the server checks them, derives
+-- unions/mesh parts/texture memory and keeps the snapshots it compares.
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local PlaceStats = {}
+
+-- Instances between yields, so Studio stays responsive on a large place
+local INSTANCES_PER_YIELD = 5000
+
+-- Texture-holding properties by class (IsA). Empty ids don't count.
+local TEXTURE_PROPERTIES = {
+	{ "Decal", { "Texture" } },
+	{ "MeshPart", { "TextureID" } },
+	{ "SpecialMesh", { "TextureId" } },
+	{ "SurfaceAppearance", { "ColorMap", "NormalMap", "MetalnessMap", "RoughnessMap" } },
+	{ "ImageLabel", { "Image" } },
+	{ "ImageButton", { "Image" } },
+	{ "ParticleEmitter", { "Texture" } },
+	{ "Beam", { "Texture" } },
+	{ "Trail", { "Texture" } },
+	{ "Sky", { "SkyboxBk", "SkyboxDn", "SkyboxFt", "SkyboxLf", "SkyboxRt", "SkyboxUp" } },
+}
+
+local SCRIPT_CLASSES = { Script = true, LocalScript = true, ModuleScript = true }
+
+--- Top-level subtrees to walk: the root's children, or the services of the place.
+local function subtrees(args)
+	if args.root then
+		local instance = InstancePath.resolve(args.root)
+		if not instance then
+			return nil, nil, "Instance not found: " .. tostring(args.root)
+		end
+		return instance:GetFullName(), { instance }
+	end
+	return "game", game:GetChildren()
+end
+
+--- args: root?
+function PlaceStats.collect(args, _ctx)
+	local rootName, tops, err = subtrees(args)
+	if not rootName then
+		return false, err
+	end
+
+	local classes = {}
+	local instances = 0
+	local scripts = { count = 0, sourceBytes = 0, unreadable = 0 }
+	local textureRefs = 0
+	local textureIds = {}
+	local uniqueTextures = 0
+	local skipped = {}
+
+	local function count(instance)
+		local className = instance.ClassName
+		classes[className] = (classes[className] or 0) + 1
+		instances += 1
+		if SCRIPT_CLASSES[className] then
+			scripts.count += 1
+			local ok, source = pcall(function()
+				return instance.Source
+			end)
+			if ok then
+				scripts.sourceBytes += #source
+			else
+				scripts.unreadable += 1
+			end
+		end
+		for _, entry in ipairs(TEXTURE_PROPERTIES) do
+			if instance:IsA(entry[1]) then
+				for _, property in ipairs(entry[2]) do
+					local ok, id = pcall(function()
+						return instance[property]
+					end)
+					if ok and type(id) == "string" and id ~= "" then
+						textureRefs += 1
+						if not textureIds[id] then
+							textureIds[id] = true
+							uniqueTextures += 1
+						end
+					end
+				end
+			end
+		end
+		if instances % INSTANCES_PER_YIELD == 0 then
+			task.wait()
+		end
+	end
+
+	for _, top in ipairs(tops) do
+		-- Some services can't be read from a plugin; count the rest
+		local ok, descendants = pcall(function()
+			return top:GetDescendants()
+		end)
+		if ok then
+			-- A root counts only its descendants; the services of the place count too
+			if not args.root then
+				count(top)
+			end
+			for _, instance in ipairs(descendants) do
+				count(instance)
+			end
+		else
+			table.insert(skipped, top.Name)
+		end
+	end
+
+	return true, {
+		root = rootName,
+		instances = instances,
+		classes = classes,
+		scripts = scripts,
+		textures = { references = textureRefs, unique = uniqueTextures },
+		skipped = skipped,
+	}
+end
+
+return PlaceStats
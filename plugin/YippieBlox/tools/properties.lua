@@ -0,0 +1,243 @@
+-- tools/properties.lua
+-- Apply service property overrides and report the previous values, so the server
+-- can put them back later (studio-test_matrix variants, studio-set_runtime_flag).
+-- Also reports a property's value type for studio-is_tweenable and reads properties
+-- of many instances for studio-batch_get_properties.
+-- Overrides are server-internal; the server decides which properties may be set.
+
+local HttpService = game:GetService("HttpService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local Properties = {}
+
+-- Services whose properties may be overridden
+local ALLOWED_SERVICES = {
+	Lighting = true,
+	Players = true,
+	Workspace = true,
+}
+
+-- JSON value -> value of the same type as the property's current one
+local function decode(current, value)
+	local kind = typeof(current)
+	if kind == "Vector3" and type(value) == "table" then
+		return Vector3.new(value[1], value[2], value[3])
+	elseif kind == "Color3" and type(value) == "table" then
+		return Color3.new(value[1], value[2], value[3])
+	elseif kind == "EnumItem" and type(value) == "string" then
+		local ok, item = pcall(function()
+			return current.EnumType[value]
+		end)
+		if ok and item then
+			return item
+		end
+		return nil
+	elseif kind == type(value) then
+		return value
+	end
+	return nil
+end
+
+local function encode(value)
+	local kind = typeof(value)
+	if kind == "Vector3" then
+		return { value.X, value.Y, value.Z }
+	elseif kind == "Color3" then
+		return { value.R, value.G, value.B }
+	elseif kind == "EnumItem" then
+		return value.Name
+	end
+	return value
+end
+
+--- args.overrides: { [serviceName] = { [property] = jsonValue } }
+--- Checks every override before changing anything; if a set fails part-way, the
+--- ones already applied are put back.
+function Properties.apply(args, _ctx)
+	local overrides = args.overrides
+	if type(overrides) ~= "table" then
+		return false, "Missing 'overrides' argument"
+	end
+
+	local planned = {}
+	for serviceName, props in pairs(overrides) do
+		if not ALLOWED_SERVICES[serviceName] then
+			return false, "Property overrides are limited to Lighting, Players and Workspace, got " .. tostring(serviceName)
+		end
+		local service = game:GetService(serviceName)
+		for prop, value in pairs(props) do
+			local okRead, current = pcall(function()
+				return service[prop]
+			end)
+			if not okRead then
+				return false, serviceName .. "." .. tostring(prop) .. " is not a readable property"
+			end
+			local decoded = decode(current, value)
+			if decoded == nil then
+				return false, string.format("%s.%s is a %s; can't set it from %s", serviceName, prop, typeof(current), type(value))
+			end
+			table.insert(planned, { service = service, serviceName = serviceName, prop = prop, old = current, new = decoded })
+		end
+	end
+
+	local previous = {}
+	for i, change in ipairs(planned) do
+		local ok, err = pcall(function()
+			change.service[change.prop] = change.new
+		end)
+		if not ok then
+			for j = i - 1, 1, -1 do
+				pcall(function()
+					planned[j].service[planned[j].prop] = planned[j].old
+				end)
+			end
+			return false, string.format("Failed to set %s.%s: %s", change.serviceName, change.prop, tostring(err))
+		end
+		previous[change.serviceName] = previous[change.serviceName] or {}
+		previous[change.serviceName][change.prop] = encode(change.old)
+	end
+
+	return true, { previous = previous, applied = #planned }
+end
+
+--- args.property plus args.path or args.className. Reads the property of a fresh,
+--- unparented instance of the class when the class is creatable, so a child with
+--- the property's name can't be mistaken for it and nothing in the place is touched.
+--- Falls back to the instance at args.path.
+function Properties.typeOf(args, _ctx)
+	local property = args.property
+	if type(property) ~= "string" or property == "" then
+		return false, "Missing 'property' argument"
+	end
+
+	local target = nil
+	local className = args.className
+	if type(args.path) == "string" and args.path ~= "" then
+		target = InstancePath.resolve(args.path)
+		if not target then
+			return false, "Instance not found: " .. args.path
+		end
+		className = target.ClassName
+	elseif type(className) ~= "string" or className == "" then
+		return false, "Pass 'path' or 'className'"
+	end
+
+	local okNew, probe = pcall(Instance.new, className)
+	local source = "class"
+	if not okNew then
+		if not target then
+			return false, className .. " can't be created to inspect (" .. tostring(probe) .. "); pass the 'path' of an existing instance instead"
+		end
+		probe = target
+		source = "instance"
+	end
+
+	local okRead, value = pcall(function()
+		return probe[property]
+	end)
+	local isChild = okRead and source == "instance" and typeof(value) == "Instance" and value.Parent == probe and value.Name == property
+	if probe ~= target then
+		probe:Destroy()
+	end
+	if not okRead or isChild then
+		return false, property .. " is not a readable property of " .. className
+	end
+
+	return true, {
+		className = className,
+		property = property,
+		valueType = typeof(value),
+		enumType = if typeof(value) == "EnumItem" then tostring(value.EnumType) else nil,
+		source = source,
+	}
+end
+
+-- Property value -> JSON-friendly value for reading. Vectors and colors are arrays
+-- like encode() gives; other datatypes fall back to tostring.
+local function describe(value)
+	local kind = typeof(value)
+	if kind == "Vector3" or kind == "Color3" or kind == "EnumItem" then
+		return encode(value)
+	elseif kind == "Vector2" then
+		return { value.X, value.Y }
+	elseif kind == "CFrame" then
+		return { value:GetComponents() }
+	elseif kind == "UDim2" then
+		return { value.X.Scale, value.X.Offset, value.Y.Scale, value.Y.Offset }
+	elseif kind == "UDim" then
+		return { value.Scale, value.Offset }
+	elseif kind == "Instance" then
+		return value:GetFullName()
+	elseif kind == "BrickColor" then
+		return value.Name
+	elseif kind == "number" or kind == "boolean" or kind == "string" then
+		return value
+	end
+	return tostring(value)
+end
+
+Properties.describe = describe
+
+--- args.paths, args.properties: read every property of every instance in one call.
+--- args.maxBytes bounds the encoded values; paths past the limit aren't read and
+--- resumeAt is the 1-based index of the first of them. A nil value is left out of
+--- values, so the server reports it as null.
+function Properties.batchGet(args, _ctx)
+	if type(args.paths) ~= "table" or type(args.properties) ~= "table" then
+		return false, "Missing 'paths' or 'properties' argument"
+	end
+	local maxBytes = tonumber(args.maxBytes) or 262144
+
+	local values = {}
+	local errors = {}
+	local bytes = 0
+	local resumeAt = nil
+	for index, path in ipairs(args.paths) do
+		local instance = InstancePath.resolve(path)
+		if not instance then
+			errors[path] = "Instance not found"
+		else
+			local read = {}
+			local failed = {}
+			local size = #path
+			for _, property in ipairs(args.properties) do
+				local ok, value = pcall(function()
+					return instance[property]
+				end)
+				-- A child with the property's name isn't the property
+				if ok and typeof(value) == "Instance" and value.Parent == instance and value.Name == property then
+					ok = false
+				end
+				if ok then
+					local described = describe(value)
+					read[property] = described
+					local okSize, encoded = pcall(HttpService.JSONEncode, HttpService, { described })
+					size += #property + (if okSize then #encoded else 16)
+				else
+					failed[property] = "not a readable property of " .. instance.ClassName
+					size += #property + 40
+				end
+			end
+			if bytes + size > maxBytes and index > 1 then
+				resumeAt = index
+				break
+			end
+			bytes += size
+			values[path] = read
+			if next(failed) then
+				errors[path] = failed
+			end
+		end
+	end
+
+	return true, {
+		values = values,
+		errors = errors,
+		truncated = resumeAt ~= nil,
+		resumeAt = resumeAt,
+		bytes = bytes,
+	}
+end
+
+return Properties
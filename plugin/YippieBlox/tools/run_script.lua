@@ -1,11 +1,25 @@
 -- tools/run_script.lua
--- Execute Luau code in Studio's plugin context.
+-- Execute Luau code in Studio's plugin context, and list the globals that code shares.
 
 local LogService = game:GetService("LogService")
 local RunService = game:GetService("RunService")
 
 local RunScript = {}
 
+-- Bounds of studio-get_lua_globals, per scope
+local DEFAULT_GLOBALS_LIMIT = 100
+local MAX_GLOBALS_LIMIT = 500
+
+-- Roblox and Luau globals that come from the engine rather than the environment
+-- table, so pairs() doesn't see them; probed by name for includeBuiltins
+local BUILTIN_NAMES = {
+	"game", "workspace", "script", "plugin", "shared", "_G",
+	"Instance", "Enum", "Vector3", "Vector2", "CFrame", "Color3", "UDim", "UDim2",
+	"BrickColor", "Ray", "TweenInfo", "NumberRange", "NumberSequence", "ColorSequence",
+	"task", "math", "string", "table", "coroutine", "buffer", "bit32", "utf8", "os", "debug",
+	"print", "warn", "error", "typeof", "require", "loadstring", "getfenv", "setfenv", "tick", "wait", "delay", "spawn",
+}
+
 function RunScript.execute(args, ctx)
 	local code = args.code
 	if not code or type(code) ~= "string" then
@@ -22,6 +36,9 @@ function RunScript.execute(args, ctx)
 		return false, "Cannot run scripts during playtest. Set allowInPlay=true to override."
 	end
 
+	-- Optional execution deadline (validated and clamped by the server)
+	local scriptTimeoutMs = tonumber(args.scriptTimeoutMs)
+
 	-- Optional log capture
 	local captureLogsMs = args.captureLogsMs or 0
 	local capturedLogs = {}
@@ -39,6 +56,11 @@ function RunScript.execute(args, ctx)
 				ts = os.clock(),
 			})
 		end)
+		if ctx and ctx.onCancel then
+			ctx.onCancel(function()
+				logConnection:Disconnect()
+			end)
+		end
 	end
 
 	-- Compile
@@ -50,8 +72,40 @@ function RunScript.execute(args, ctx)
 		return false, "Compile error: " .. tostring(compileErr)
 	end
 
-	-- Execute with pcall
-	local ok, result = pcall(fn)
+	-- Execute with pcall. With a deadline, run in a separate thread so code that
+	-- yields (task.wait loops) can be cancelled. A loop that never yields cannot be
+	-- interrupted from Luau; Studio's own script timeout is the backstop there.
+	local ok, result
+	if scriptTimeoutMs then
+		local finished = false
+		local thread = task.spawn(function()
+			ok, result = pcall(fn)
+			finished = true
+		end)
+		-- studio-cancel stops the thread waiting here; stop the script with it
+		if ctx and ctx.onCancel then
+			ctx.onCancel(function()
+				pcall(task.cancel, thread)
+			end)
+		end
+		local deadline = os.clock() + scriptTimeoutMs / 1000
+		while not finished and os.clock() < deadline do
+			task.wait()
+		end
+		if not finished then
+			pcall(task.cancel, thread)
+			if logConnection then
+				logConnection:Disconnect()
+			end
+			return false, {
+				error = string.format("Script timed out after %d ms (scriptTimeoutMs) and was cancelled", scriptTimeoutMs),
+				timedOut = true,
+				logs = capturedLogs,
+			}
+		end
+	else
+		ok, result = pcall(fn)
+	end
 
 	-- Wait for log capture window if specified
 	if captureLogsMs > 0 and logConnection then
@@ -87,4 +141,54 @@ function RunScript.execute(args, ctx)
 	}
 end
 
+-- Sorted names and types of a table's keys, at most `limit` of them
+local function listGlobals(tbl, limit)
+	local entries = {}
+	for key, value in pairs(tbl) do
+		table.insert(entries, {
+			name = if type(key) == "string" then key else string.format("[%s]", tostring(key)),
+			type = typeof(value),
+		})
+	end
+	table.sort(entries, function(a, b)
+		return a.name < b.name
+	end)
+	local globals = {}
+	for i = 1, math.min(limit, #entries) do
+		globals[i] = entries[i]
+	end
+	return {
+		count = #entries,
+		truncated = #entries > limit,
+		globals = globals,
+	}
+end
+
+-- The globals run_script code shares: what earlier scripts assigned without `local`
+-- (the environment every loadstring chunk here runs in), and the _G and shared tables
+function RunScript.globals(args, _ctx)
+	local limit = math.clamp(math.floor(tonumber(args.limit) or DEFAULT_GLOBALS_LIMIT), 1, MAX_GLOBALS_LIMIT)
+	local probe = loadstring("return nil", "=MCP:get_lua_globals")
+	local env = getfenv(probe)
+
+	local result = {
+		env = listGlobals(env, limit),
+		_G = listGlobals(_G, limit),
+		shared = listGlobals(shared, limit),
+	}
+
+	if args.includeBuiltins then
+		local builtins = {}
+		for _, name in ipairs(BUILTIN_NAMES) do
+			local value = env[name]
+			if value ~= nil then
+				table.insert(builtins, { name = name, type = typeof(value) })
+			end
+		end
+		result.builtins = builtins
+	end
+
+	return true, result
+end
+
 return RunScript
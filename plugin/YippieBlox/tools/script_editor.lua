@@ -0,0 +1,138 @@
+-- tools/script_editor.lua
+-- Open a script in Studio's script editor (studio-open_script), so the person in
+-- Studio sees the script the assistant just changed. Uses
+-- ScriptEditorService:OpenScriptDocumentAsync, or plugin:OpenScript when a line is
+-- asked for or the service can't open the document.
+-- Also lists the scripts open in the editor (studio-get_open_scripts), so the
+-- assistant knows what the person is working on.
+
+local ScriptEditorService = game:GetService("ScriptEditorService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local ScriptEditor = {}
+
+--- args.path: full name of a Script, LocalScript or ModuleScript. args.line: line
+--- to put the cursor on (optional).
+function ScriptEditor.open(args, ctx)
+	if type(args.path) ~= "string" or args.path == "" then
+		return false, "Missing 'path' argument"
+	end
+	local line = args.line
+	if line ~= nil and (type(line) ~= "number" or line < 1 or line % 1 ~= 0) then
+		return false, "'line' must be a positive integer"
+	end
+
+	local instance = InstancePath.resolve(args.path)
+	if not instance then
+		return false, "Instance not found: " .. args.path
+	end
+	if not instance:IsA("LuaSourceContainer") then
+		return false, "not_a_script: " .. args.path .. " is a " .. instance.ClassName .. ", not a Script, LocalScript or ModuleScript"
+	end
+	if line then
+		local lineCount = select(2, string.gsub(instance.Source, "\n", "\n")) + 1
+		if line > lineCount then
+			return false, "'line' " .. line .. " is past the end of " .. args.path .. " (" .. lineCount .. " lines)"
+		end
+	end
+
+	local method = "OpenScriptDocumentAsync"
+	local opened, err = false, nil
+	if not line then
+		local ok, success, message = pcall(function()
+			return ScriptEditorService:OpenScriptDocumentAsync(instance)
+		end)
+		opened = ok and success
+		err = if ok then message else success
+	end
+
+	-- plugin:OpenScript takes a line; it is also the fallback when the service fails
+	if not opened and ctx.plugin then
+		method = "OpenScript"
+		local ok, message = pcall(function()
+			ctx.plugin:OpenScript(instance, line)
+		end)
+		opened = ok
+		err = if ok then nil else message
+	end
+
+	if not opened then
+		return false, "Studio couldn't open " .. args.path .. " in the script editor: " .. tostring(err or "no plugin to open it with")
+	end
+	return true, {
+		path = instance:GetFullName(),
+		className = instance.ClassName,
+		line = line,
+		method = method,
+	}
+end
+
+local DEFAULT_OPEN_LIMIT = 20
+local MAX_OPEN_LIMIT = 100
+
+--- args.limit: most scripts to list (default 20, max 100). The command bar is left out.
+function ScriptEditor.listOpen(args)
+	local limit = math.clamp(math.floor(tonumber(args.limit) or DEFAULT_OPEN_LIMIT), 1, MAX_OPEN_LIMIT)
+	local ok, documents = pcall(function()
+		return ScriptEditorService:GetScriptDocuments()
+	end)
+	if not ok then
+		return false, "Couldn't read the script editor's documents: " .. tostring(documents)
+	end
+
+	local scripts = {}
+	for _, document in ipairs(documents) do
+		if document:IsCommandBar() then
+			continue
+		end
+		local instance = document:GetScript()
+		if not instance then
+			continue
+		end
+		local entry = {
+			path = instance:GetFullName(),
+			className = instance.ClassName,
+		}
+		-- Cursor and selection are 1-based; they are missing while a document loads
+		local readOk, cursorLine, cursorChar, anchorLine, anchorChar = pcall(function()
+			return document:GetSelection()
+		end)
+		if readOk and cursorLine then
+			entry.cursor = { line = cursorLine, character = cursorChar }
+			if anchorLine ~= cursorLine or anchorChar ~= cursorChar then
+				entry.selectionAnchor = { line = anchorLine, character = anchorChar }
+			end
+		end
+		local viewOk, startLine, endLine = pcall(function()
+			return document:GetViewport()
+		end)
+		if viewOk and startLine then
+			entry.visibleLines = { first = startLine, last = endLine }
+		end
+		local countOk, lineCount = pcall(function()
+			return document:GetLineCount()
+		end)
+		if countOk then
+			entry.lineCount = lineCount
+		end
+		table.insert(scripts, entry)
+	end
+	table.sort(scripts, function(a, b)
+		return a.path < b.path
+	end)
+
+	local count = #scripts
+	local listed = {}
+	for i = 1, math.min(count, limit) do
+		listed[i] = scripts[i]
+	end
+	return true, {
+		scripts = listed,
+		count = count,
+		truncated = count > limit,
+		note = if count == 0 then "No scripts are open in the script editor" else nil,
+	}
+end
+
+return ScriptEditor
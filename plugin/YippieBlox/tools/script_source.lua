@@ -0,0 +1,50 @@
+-- tools/script_source.lua
+-- Script sources for the server's traceback annotations (studio-get_script_sources,
+-- server-internal). Paths that don't name a readable script are left out.
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local ScriptSource = {}
+
+local MAX_PATHS = 8
+local DEFAULT_MAX_BYTES = 200000
+
+--- args.paths: full names of scripts. args.maxBytes: larger sources are skipped.
+function ScriptSource.read(args, _ctx)
+	if type(args.paths) ~= "table" then
+		return false, "Missing 'paths' argument"
+	end
+	local maxBytes = tonumber(args.maxBytes) or DEFAULT_MAX_BYTES
+
+	local sources = {}
+	local skipped = {}
+	for i, path in ipairs(args.paths) do
+		if i > MAX_PATHS then
+			break
+		end
+		local instance = if type(path) == "string" then InstancePath.resolve(path) else nil
+		if not instance then
+			skipped[tostring(path)] = "not found"
+		elseif not instance:IsA("LuaSourceContainer") then
+			skipped[path] = "not a script (" .. instance.ClassName .. ")"
+		else
+			local ok, source = pcall(function()
+				return instance.Source
+			end)
+			if not ok then
+				skipped[path] = "Source not readable: " .. tostring(source)
+			elseif #source > maxBytes then
+				skipped[path] = "larger than " .. maxBytes .. " bytes"
+			else
+				sources[path] = source
+			end
+		end
+	end
+
+	return true, {
+		sources = sources,
+		skipped = skipped,
+	}
+end
+
+return ScriptSource
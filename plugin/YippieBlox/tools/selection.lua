@@ -0,0 +1,83 @@
+-- tools/selection.lua
+-- Select every instance under a root that matches class, name and tag filters, in
+-- one round-trip instead of a search followed by a separate select.
+
+local Selection = game:GetService("Selection")
+local CollectionService = game:GetService("CollectionService")
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+
+local SelectionTools = {}
+
+local DEFAULT_LIMIT = 500
+local MAX_LIMIT = 5000
+
+local function matches(instance, args, nameLower)
+	if args.className and not instance:IsA(args.className) then
+		return false
+	end
+	if args.name and instance.Name ~= args.name then
+		return false
+	end
+	if nameLower and not string.find(string.lower(instance.Name), nameLower, 1, true) then
+		return false
+	end
+	if args.tag and not CollectionService:HasTag(instance, args.tag) then
+		return false
+	end
+	return true
+end
+
+function SelectionTools.selectMatching(args, _ctx)
+	if not (args.className or args.name or args.nameContains or args.tag) then
+		return false, "Pass at least one filter: className, name, nameContains or tag"
+	end
+	local rootPath = args.root or "Workspace"
+	local root = InstancePath.resolve(rootPath)
+	if not root then
+		return false, "Instance not found: " .. tostring(rootPath)
+	end
+	local limit = math.clamp(tonumber(args.limit) or DEFAULT_LIMIT, 1, MAX_LIMIT)
+	local nameLower = args.nameContains and string.lower(args.nameContains)
+
+	local found = {}
+	local matched = 0
+	for _, descendant in ipairs(root:GetDescendants()) do
+		if matches(descendant, args, nameLower) then
+			matched += 1
+			if #found < limit then
+				table.insert(found, descendant)
+			end
+		end
+	end
+
+	local selection = found
+	if args.add then
+		selection = Selection:Get()
+		local already = {}
+		for _, instance in ipairs(selection) do
+			already[instance] = true
+		end
+		for _, instance in ipairs(found) do
+			if not already[instance] then
+				table.insert(selection, instance)
+			end
+		end
+	end
+	Selection:Set(selection)
+
+	local paths = {}
+	for i = 1, math.min(#found, 20) do
+		table.insert(paths, found[i]:GetFullName())
+	end
+	return true, {
+		root = root:GetFullName(),
+		matched = matched,
+		selected = #found,
+		selectionSize = #selection,
+		truncated = matched > #found,
+		sample = paths,
+	}
+end
+
+return SelectionTools
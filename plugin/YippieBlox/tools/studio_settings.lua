@@ -0,0 +1,88 @@
+-- tools/studio_settings.lua
+-- Change Studio settings (settings(), the user's own preferences) and report the
+-- previous values, so the server can put them back (studio-set_studio_setting).
+-- Server-internal; the server keeps the whitelist and decides what may be set.
+
+local StudioSettings = {}
+
+-- Groups of settings() that may be changed, by the name the server uses
+local ALLOWED_GROUPS = {
+	Physics = "PhysicsSettings",
+	Network = "NetworkSettings",
+}
+
+--- Convert a JSON value to the type of the current setting value. Enum items come
+--- as names. Returns nil when the value doesn't fit.
+local function decode(current, value)
+	local kind = typeof(current)
+	if kind == "EnumItem" then
+		if type(value) ~= "string" then
+			return nil
+		end
+		local ok, item = pcall(function()
+			return current.EnumType[value]
+		end)
+		return if ok then item else nil
+	end
+	if kind == type(value) then
+		return value
+	end
+	return nil
+end
+
+local function encode(value)
+	if typeof(value) == "EnumItem" then
+		return value.Name
+	end
+	return value
+end
+
+--- args.settings: { group = { property = value } }. Applies all or nothing.
+function StudioSettings.apply(args, _ctx)
+	if type(args.settings) ~= "table" then
+		return false, "Missing 'settings' argument"
+	end
+
+	local planned = {}
+	for groupName, props in pairs(args.settings) do
+		local className = ALLOWED_GROUPS[groupName]
+		if not className then
+			return false, "Studio settings are limited to Physics and Network, got " .. tostring(groupName)
+		end
+		local group = settings():GetService(className)
+		for prop, value in pairs(props) do
+			local okRead, current = pcall(function()
+				return group[prop]
+			end)
+			if not okRead then
+				return false, groupName .. "." .. tostring(prop) .. " is not a readable setting"
+			end
+			local decoded = decode(current, value)
+			if decoded == nil then
+				return false, string.format("%s.%s is a %s; can't set it from %s", groupName, prop, typeof(current), type(value))
+			end
+			table.insert(planned, { group = group, groupName = groupName, prop = prop, old = current, new = decoded })
+		end
+	end
+
+	local previous = {}
+	for i, change in ipairs(planned) do
+		local ok, err = pcall(function()
+			change.group[change.prop] = change.new
+		end)
+		if not ok then
+			for j = i - 1, 1, -1 do
+				pcall(function()
+					planned[j].group[planned[j].prop] = planned[j].old
+				end)
+			end
+			return false, string.format("Failed to set %s.%s: %s", change.groupName, change.prop, tostring(err))
+		end
+		previous[change.groupName] = previous[change.groupName] or {}
+		previous[change.groupName][change.prop] = encode(change.old)
+	end
+
+	return true, { previous = previous, applied = #planned }
+end
+
+return StudioSettings
@@ -0,0 +1,140 @@
+-- tools/tree.lua
+-- Instance path snapshots and batched tree deltas for the server's path index
+-- (YIPPIE_TREE_INDEX). The server asks for a snapshot, which also (re)starts
+-- watching the given roots; changes are then pushed as numbered studio-tree_delta
+-- events. Renames and moves within a root are not tracked and show up with the
+-- next snapshot.
+
+local Tree = {}
+
+-- Matches MAX_INDEXED_PATHS on the server
+local MAX_SNAPSHOT_PATHS = 20000
+-- At full background level; the bridge stretches it while the HttpService budget
+-- is tight
+local FLUSH_INTERVAL = 0.5
+-- Storm guard: past this many unflushed changes, drop them and flag overflow so
+-- the server takes a fresh snapshot instead
+local MAX_PENDING = 2000
+
+local connections = {}
+local pending = { added = {}, removed = {} }
+local pendingCount = 0
+local overflow = false
+local seq = 0
+local flushThread = nil
+local bridgeRef = nil
+
+local function clearPending()
+	pending = { added = {}, removed = {} }
+	pendingCount = 0
+	overflow = false
+end
+
+local function stopWatching()
+	for _, connection in ipairs(connections) do
+		connection:Disconnect()
+	end
+	connections = {}
+	if flushThread then
+		pcall(task.cancel, flushThread)
+		flushThread = nil
+	end
+	clearPending()
+end
+
+local function record(kind, entry)
+	if overflow then
+		return
+	end
+	if pendingCount >= MAX_PENDING then
+		clearPending()
+		overflow = true
+		return
+	end
+	table.insert(pending[kind], entry)
+	pendingCount = pendingCount + 1
+end
+
+local function flush()
+	if pendingCount == 0 and not overflow then
+		return
+	end
+	seq = seq + 1
+	local data = {
+		seq = seq,
+		added = pending.added,
+		removed = pending.removed,
+		overflow = overflow,
+	}
+	clearPending()
+	if bridgeRef then
+		bridgeRef:pushEvent("studio-tree_delta", data)
+	end
+end
+
+local function resolveRoots(names)
+	local roots = {}
+	for _, name in ipairs(names or {}) do
+		local ok, service = pcall(function()
+			return game:FindService(name)
+		end)
+		if ok and service then
+			table.insert(roots, service)
+		end
+	end
+	return roots
+end
+
+local function startWatching(roots)
+	for _, root in ipairs(roots) do
+		table.insert(connections, root.DescendantAdded:Connect(function(descendant)
+			record("added", { path = descendant:GetFullName(), class = descendant.ClassName })
+		end))
+		table.insert(connections, root.DescendantRemoving:Connect(function(descendant)
+			record("removed", descendant:GetFullName())
+		end))
+	end
+	flushThread = task.spawn(function()
+		while true do
+			task.wait(if bridgeRef then bridgeRef:backgroundInterval(FLUSH_INTERVAL) else FLUSH_INTERVAL)
+			flush()
+		end
+	end)
+end
+
+function Tree.snapshot(args, ctx)
+	local roots = resolveRoots(args.roots)
+	if #roots == 0 then
+		return false, "None of the requested roots exist: " .. table.concat(args.roots or {}, ", ")
+	end
+
+	-- Changes made before this point are part of the snapshot
+	stopWatching()
+	bridgeRef = ctx and ctx.bridge
+
+	local entries = {}
+	local truncated = false
+	for _, root in ipairs(roots) do
+		table.insert(entries, { path = root:GetFullName(), class = root.ClassName })
+		for _, descendant in ipairs(root:GetDescendants()) do
+			if #entries >= MAX_SNAPSHOT_PATHS then
+				truncated = true
+				break
+			end
+			table.insert(entries, { path = descendant:GetFullName(), class = descendant.ClassName })
+		end
+	end
+
+	if args.watch then
+		startWatching(roots)
+	end
+
+	return true, {
+		clientId = bridgeRef and bridgeRef.clientId,
+		seq = seq,
+		entries = entries,
+		truncated = truncated,
+	}
+end
+
+return Tree
@@ -0,0 +1,182 @@
+-- tools/viewport_render.lua
+-- Ray-traced picture of the edit camera's view (studio-capture_viewport_via_render).
+-- Plugins can't read the frame Studio draws (CaptureService only hands out
+-- rbxtemp:// ids), so this casts one ray per pixel and shades each hit by surface
+-- color and sun angle. Returns raw RGB rows, top to bottom, as base64; the server
+-- checks the size and encodes the PNG.
+
+local InstancePath = require(script.Parent.Parent.util.instance_path)
+local Camera = require(script.Parent.camera)
+
+local ViewportRender = {}
+
+-- Parts at least this transparent are seen through
+local SEE_THROUGH = 0.95
+-- See-through parts a ray may pass before it counts as sky
+local MAX_PASSES = 4
+-- Rays between yields, so Studio stays responsive during a large render
+local RAYS_PER_YIELD = 4000
+-- Light on faces turned away from the sun
+local AMBIENT = 0.35
+-- Extra room around the bounds when framing 'path'
+local FRAME_MARGIN = 1.2
+
+local SKY_HORIZON = Color3.fromRGB(190, 215, 240)
+local SKY_TOP = Color3.fromRGB(90, 150, 225)
+local TERRAIN_FALLBACK = Color3.fromRGB(100, 120, 80)
+
+local B64_CHARS = "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"
+
+local function vectorTable(v)
+	return { x = v.X, y = v.Y, z = v.Z }
+end
+
+local function base64(buf)
+	local ok, encoded = pcall(function()
+		return buffer.tostring(game:GetService("EncodingService"):Base64Encode(buf))
+	end)
+	if ok then
+		return encoded
+	end
+
+	local chars = {}
+	for i = 1, 64 do
+		chars[i - 1] = string.sub(B64_CHARS, i, i)
+	end
+	local len = buffer.len(buf)
+	local out = table.create(math.ceil(len / 3))
+	for i = 0, len - 1, 3 do
+		local remaining = len - i
+		local a = buffer.readu8(buf, i)
+		local b = if remaining > 1 then buffer.readu8(buf, i + 1) else 0
+		local c = if remaining > 2 then buffer.readu8(buf, i + 2) else 0
+		local n = a * 65536 + b * 256 + c
+		local chunk = chars[bit32.extract(n, 18, 6)] .. chars[bit32.extract(n, 12, 6)]
+		chunk ..= if remaining > 1 then chars[bit32.extract(n, 6, 6)] else "="
+		chunk ..= if remaining > 2 then chars[bit32.extract(n, 0, 6)] else "="
+		table.insert(out, chunk)
+	end
+	return table.concat(out)
+end
+
+--- The camera to render from: the edit camera, or one backed off along its look
+--- direction until `path` fits.
+local function renderCamera(args)
+	local camera = workspace.CurrentCamera
+	if not camera then
+		return nil, "No current camera in the edit DataModel"
+	end
+	local cf = camera.CFrame
+	local fieldOfView = camera.FieldOfView
+
+	if args.path then
+		local instance = InstancePath.resolve(args.path)
+		if not instance then
+			return nil, "Instance not found: " .. tostring(args.path)
+		end
+		local parts = {}
+		Camera.collectParts(instance, parts)
+		if #parts == 0 then
+			return nil, "The target has no parts to frame (only BaseParts and their ancestors have bounds)"
+		end
+		local center, size = Camera.boundsOf(parts)
+		local radius = math.max(size.Magnitude / 2, 0.5)
+		local distance = radius / math.tan(math.rad(fieldOfView / 2)) * FRAME_MARGIN
+		cf = CFrame.lookAt(center - cf.LookVector * distance, center)
+	end
+	return cf, fieldOfView
+end
+
+local function surfaceColor(result)
+	local hit = result.Instance
+	if hit:IsA("Terrain") then
+		local ok, color = pcall(function()
+			return hit:GetMaterialColor(result.Material)
+		end)
+		return if ok then color else TERRAIN_FALLBACK
+	end
+	return hit.Color
+end
+
+--- Color seen along one ray, and whether it hit anything.
+local function trace(origin, direction, params, sunDirection)
+	for _ = 1, MAX_PASSES do
+		local result = workspace:Raycast(origin, direction, params)
+		if not result then
+			break
+		end
+		local hit = result.Instance
+		if hit:IsA("Terrain") or hit.Transparency < SEE_THROUGH then
+			local light = AMBIENT + (1 - AMBIENT) * math.max(0, result.Normal:Dot(sunDirection))
+			local color = surfaceColor(result)
+			return Color3.new(color.R * light, color.G * light, color.B * light), true
+		end
+		-- Continue just past the see-through part with what is left of the ray
+		local travelled = (result.Position - origin).Magnitude + 0.01
+		local remaining = direction.Magnitude - travelled
+		if remaining <= 0 then
+			break
+		end
+		origin = result.Position + direction.Unit * 0.01
+		direction = direction.Unit * remaining
+	end
+	local up = math.clamp(direction.Unit.Y, 0, 1)
+	return SKY_HORIZON:Lerp(SKY_TOP, up), false
+end
+
+--- args: width, height (validated by the server), path?, maxDistance.
+function ViewportRender.render(args, _ctx)
+	local width, height = args.width, args.height
+	if type(width) ~= "number" or type(height) ~= "number" then
+		return false, "Missing 'width' or 'height' argument"
+	end
+	local cf, fieldOfView = renderCamera(args)
+	if not cf then
+		return false, fieldOfView
+	end
+
+	local started = os.clock()
+	local maxDistance = args.maxDistance or 1000
+	local sunDirection = game:GetService("Lighting"):GetSunDirection()
+	local params = RaycastParams.new()
+	params.FilterType = Enum.RaycastFilterType.Exclude
+	params.FilterDescendantsInstances = {}
+
+	local tanY = math.tan(math.rad(fieldOfView / 2))
+	local tanX = tanY * width / height
+	local pixels = buffer.create(width * height * 3)
+	local hits = 0
+	local rays = 0
+	for y = 0, height - 1 do
+		local v = (1 - (y + 0.5) / height * 2) * tanY
+		for x = 0, width - 1 do
+			local u = ((x + 0.5) / width * 2 - 1) * tanX
+			local direction = (cf.RightVector * u + cf.UpVector * v + cf.LookVector).Unit * maxDistance
+			local color, hit = trace(cf.Position, direction, params, sunDirection)
+			if hit then
+				hits += 1
+			end
+			local offset = (y * width + x) * 3
+			buffer.writeu8(pixels, offset, math.clamp(math.floor(color.R * 255 + 0.5), 0, 255))
+			buffer.writeu8(pixels, offset + 1, math.clamp(math.floor(color.G * 255 + 0.5), 0, 255))
+			buffer.writeu8(pixels, offset + 2, math.clamp(math.floor(color.B * 255 + 0.5), 0, 255))
+			rays += 1
+			if rays % RAYS_PER_YIELD == 0 then
+				task.wait()
+			end
+		end
+	end
+
+	return true, {
+		pixels = base64(pixels),
+		camera = {
+			position = vectorTable(cf.Position),
+			lookVector = vectorTable(cf.LookVector),
+			fieldOfView = fieldOfView,
+		},
+		hitPixels = hits,
+		elapsedMs = math.floor((os.clock() - started) * 1000 + 0.5),
+	}
+end
+
+return ViewportRender
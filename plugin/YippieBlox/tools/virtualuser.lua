@@ -23,4 +23,16 @@ function VirtualUserTools.moveMouse(_args, _ctx)
 	return false, PLAYTEST_MSG
 end
 
+function VirtualUserTools.sequence(_args, _ctx)
+	return false, PLAYTEST_MSG
+end
+
+function VirtualUserTools.recordStart(_args, _ctx)
+	return false, PLAYTEST_MSG
+end
+
+function VirtualUserTools.recordStop(_args, _ctx)
+	return false, PLAYTEST_MSG
+end
+
 return VirtualUserTools
@@ -0,0 +1,84 @@
+-- tools/wait_condition.lua
+-- Poll a Luau predicate until it holds or the wait times out (studio-wait_for_condition).
+-- The predicate runs in the plugin context like studio-run_script. The server validates
+-- and clamps the timing before the call gets here.
+
+local WaitCondition = {}
+
+local DEFAULT_TIMEOUT_MS = 10000
+local DEFAULT_INTERVAL_MS = 100
+
+-- An expression first, so `workspace:FindFirstChild("Door")` works as written;
+-- a chunk with its own `return` is accepted as well
+local function compile(predicate)
+	local fn = loadstring("return (" .. predicate .. ")", "=MCP:wait_for_condition")
+	if fn then
+		return fn
+	end
+	local chunk, err = loadstring(predicate, "=MCP:wait_for_condition")
+	if not chunk then
+		return nil, err
+	end
+	return chunk
+end
+
+-- Only JSON-friendly values go back as-is; Instances and the like are described
+local function describe(value)
+	local kind = type(value)
+	if kind == "boolean" or kind == "number" or kind == "string" then
+		return value
+	end
+	if typeof(value) == "Instance" then
+		return value:GetFullName()
+	end
+	return tostring(value)
+end
+
+function WaitCondition.wait(args, _ctx)
+	local predicate = args.predicate
+	if type(predicate) ~= "string" or predicate == "" then
+		return false, "Missing or invalid 'predicate' argument (must be a Luau expression)"
+	end
+
+	local fn, compileErr = compile(predicate)
+	if not fn then
+		return false, "Compile error: " .. tostring(compileErr)
+	end
+
+	local timeout = (tonumber(args.timeoutMs) or DEFAULT_TIMEOUT_MS) / 1000
+	local interval = (tonumber(args.intervalMs) or DEFAULT_INTERVAL_MS) / 1000
+
+	-- Errors count as "not yet": the instance a predicate indexes often doesn't exist
+	-- until the condition is close to holding. The last one is reported on timeout.
+	local started = os.clock()
+	local attempts = 0
+	local lastError = nil
+	while true do
+		attempts += 1
+		local ok, value = pcall(fn)
+		local waitedMs = math.floor((os.clock() - started) * 1000 + 0.5)
+		if ok and value then
+			return true, {
+				met = true,
+				waitedMs = waitedMs,
+				attempts = attempts,
+				value = describe(value),
+			}
+		end
+		lastError = if ok then nil else tostring(value)
+
+		local remaining = timeout - (os.clock() - started)
+		if remaining <= 0 then
+			return true, {
+				met = false,
+				timedOut = true,
+				waitedMs = waitedMs,
+				attempts = attempts,
+				lastError = lastError,
+			}
+		end
+		task.wait(math.min(interval, remaining))
+	end
+end
+
+return WaitCondition
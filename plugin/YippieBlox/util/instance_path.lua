@@ -0,0 +1,26 @@
+-- instance_path.lua
+-- Resolve dotted instance paths ("Workspace.Castle.Gate") used by tool arguments.
+-- The first segment is looked up as a service, so paths work before a service
+-- has been created under its class name.
+
+local InstancePath = {}
+
+function InstancePath.resolve(path)
+	local current = game
+	for _, part in ipairs(string.split(path, ".")) do
+		if current == game then
+			local ok, service = pcall(function()
+				return game:FindService(part)
+			end)
+			current = (ok and service) or game:FindFirstChild(part)
+		else
+			current = current:FindFirstChild(part)
+		end
+		if not current then
+			return nil
+		end
+	end
+	return current
+end
+
+return InstancePath
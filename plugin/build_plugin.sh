@@ -10,6 +10,7 @@
 #     main (Script)                  ← init.server.lua
 #       bridge (ModuleScript)        ← bridge.lua
 #       playtest_bridge_source (ModuleScript) ← returns Luau source for server-side bridge
+#       input_probe_source (ModuleScript) ← returns Luau source for the client-side input probe
 #       tools (ModuleScript)         ← tools/init.lua (router)
 #         run_script (ModuleScript)
 #         checkpoint (ModuleScript)
@@ -18,11 +19,16 @@
 #         virtualuser (ModuleScript)
 #         npc_driver (ModuleScript)
 #         capture (ModuleScript)
+#         tree (ModuleScript)
+#         camera (ModuleScript)
+#         collision (ModuleScript)
+#         properties (ModuleScript)
 #       ui (Folder)
 #         widget (ModuleScript)
 #         command_trace (ModuleScript)
 #       util (Folder)
 #         ring_buffer (ModuleScript)
+#         instance_path (ModuleScript)
 
 set -euo pipefail
 
@@ -94,6 +100,16 @@ echo "]]></ProtectedString>"
 echo "        </Properties>"
 echo "      </Item>"
 
+# ── input_probe_source (ModuleScript — returns source string for injection) ──
+echo "      <Item class=\"ModuleScript\" referent=\"$(next_ref)\">"
+echo "        <Properties>"
+echo "          <string name=\"Name\">input_probe_source</string>"
+echo "          <ProtectedString name=\"Source\"><![CDATA["
+cdata_file "$SRC/input_probe_source.lua"
+echo "]]></ProtectedString>"
+echo "        </Properties>"
+echo "      </Item>"
+
 # ── tools (ModuleScript with children) ──
 echo "      <Item class=\"ModuleScript\" referent=\"$(next_ref)\">"
 echo "        <Properties>"
@@ -0,0 +1,45 @@
+//! Embeds build provenance (git commit, build time, target, rustc) as compile-time
+//! env vars read by `src/build_info.rs`. Missing tools leave the values empty
+//! rather than failing the build.
+
+use std::process::Command;
+
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let out = Command::new(program).args(args).output().ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn main() {
+    let commit = output("git", &["rev-parse", "--short=12", "HEAD"]).unwrap_or_default();
+    let dirty = output("git", &["status", "--porcelain", "--untracked-files=no"])
+        .map(|s| (!s.is_empty()).to_string())
+        .unwrap_or_default();
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = output(&rustc, &["--version"]).unwrap_or_default();
+    // Reproducible builds set SOURCE_DATE_EPOCH
+    let built_at = std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0)
+        });
+
+    println!("cargo:rustc-env=YIPPIE_GIT_COMMIT={commit}");
+    println!("cargo:rustc-env=YIPPIE_GIT_DIRTY={dirty}");
+    println!("cargo:rustc-env=YIPPIE_RUSTC_VERSION={rustc_version}");
+    println!("cargo:rustc-env=YIPPIE_BUILT_AT_UNIX={built_at}");
+    println!("cargo:rustc-env=YIPPIE_TARGET={}", std::env::var("TARGET").unwrap_or_default());
+    println!("cargo:rustc-env=YIPPIE_PROFILE={}", std::env::var("PROFILE").unwrap_or_default());
+
+    // Re-run when HEAD moves or the index changes, not on every source edit
+    println!("cargo:rerun-if-changed=../.git/HEAD");
+    println!("cargo:rerun-if-changed=../.git/index");
+    println!("cargo:rerun-if-changed=../.git/refs/heads");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+}
@@ -0,0 +1,706 @@
+//! Alerting hooks: rules that turn server events into a webhook POST or a local
+//! command, configured with `YIPPIE_ALERT_RULES`.
+//!
+//! Alerting is strictly best effort. Actions run in their own tasks with timeouts and
+//! bounded concurrency, failures are only logged and counted, and nothing here can
+//! block or fail the code that emitted the event.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Timelike};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Semaphore};
+
+use crate::server_events::{self, ServerEvent};
+use crate::state::SharedState;
+
+/// Rate limit applied when a rule doesn't set `minIntervalSecs`.
+const DEFAULT_MIN_INTERVAL_SECS: u64 = 300;
+
+const DEFAULT_RETRIES: u32 = 2;
+const MAX_RETRIES: u32 = 5;
+
+/// Delay before the first retry; doubled for each further one.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Actions running at once across all rules. Past this, firings are dropped.
+const MAX_IN_FLIGHT: usize = 8;
+
+/// How often the watcher checks for stale playtests and clients.
+const WATCH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A playtest running this long starts producing `playtest_stale` events.
+const PLAYTEST_STALE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct AlertRule {
+    pub name: String,
+    /// Server event kind (see `server_events::KINDS`).
+    pub event: String,
+    #[serde(default)]
+    pub when: Condition,
+    pub action: Action,
+    #[serde(default)]
+    pub min_interval_secs: Option<u64>,
+    #[serde(default)]
+    pub retries: Option<u32>,
+}
+
+/// All given parts must hold.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Condition {
+    /// Local hours as "start-end", e.g. "9-18" (end exclusive; "22-6" wraps midnight).
+    #[serde(default)]
+    pub hours: Option<String>,
+    /// Only Monday to Friday (local time).
+    #[serde(default)]
+    pub weekdays: bool,
+    /// Numeric event fields that must be at least this value.
+    #[serde(default)]
+    pub min: BTreeMap<String, f64>,
+    /// Event fields that must equal this value.
+    #[serde(default)]
+    pub equals: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Action {
+    /// POST `body` (a JSON template) to `url`.
+    Http {
+        url: String,
+        #[serde(default)]
+        headers: BTreeMap<String, String>,
+        #[serde(default = "default_body")]
+        body: Value,
+    },
+    /// Run `program` with `args` (templates). Event fields are passed as
+    /// `YIPPIE_ALERT_*` environment variables.
+    Command {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+    },
+}
+
+fn default_body() -> Value {
+    json!({ "text": "YippieBlox {{event}}: {{rule}} at {{at}}" })
+}
+
+impl Action {
+    fn kind(&self) -> &'static str {
+        match self {
+            Action::Http { .. } => "http",
+            Action::Command { .. } => "command",
+        }
+    }
+}
+
+/// Parse `YIPPIE_ALERT_RULES`: a JSON array of rules.
+pub fn parse_rules(raw: &str) -> Result<Vec<AlertRule>> {
+    let rules: Vec<AlertRule> =
+        serde_json::from_str(raw).context("YIPPIE_ALERT_RULES must be a JSON array of alert rules")?;
+    for rule in &rules {
+        if rule.name.trim().is_empty() {
+            bail!("Alert rule with an empty name");
+        }
+        if !server_events::KINDS.contains(&rule.event.as_str()) {
+            bail!(
+                "Alert rule '{}': unknown event '{}' (expected one of {})",
+                rule.name,
+                rule.event,
+                server_events::KINDS.join(", ")
+            );
+        }
+        if let Some(hours) = &rule.when.hours {
+            parse_hours(hours).with_context(|| format!("Alert rule '{}'", rule.name))?;
+        }
+        if rule.retries.is_some_and(|r| r > MAX_RETRIES) {
+            bail!("Alert rule '{}': retries must be at most {MAX_RETRIES}", rule.name);
+        }
+        match &rule.action {
+            Action::Http { url, .. } if !(url.starts_with("http://") || url.starts_with("https://")) => {
+                bail!("Alert rule '{}': url must start with http:// or https://", rule.name)
+            }
+            Action::Command { program, .. } if program.trim().is_empty() => {
+                bail!("Alert rule '{}': program is empty", rule.name)
+            }
+            _ => {}
+        }
+    }
+    if let Some(dup) = rules.iter().enumerate().find_map(|(i, r)| {
+        rules[..i].iter().any(|o| o.name == r.name).then_some(&r.name)
+    }) {
+        bail!("Duplicate alert rule name '{dup}'");
+    }
+    Ok(rules)
+}
+
+fn parse_hours(hours: &str) -> Result<(u32, u32)> {
+    let (start, end) = hours
+        .split_once('-')
+        .context("hours must look like \"9-18\"")?;
+    let parse = |s: &str| -> Result<u32> {
+        let hour: u32 = s.trim().parse().with_context(|| format!("bad hour {s:?}"))?;
+        if hour > 24 {
+            bail!("hour {hour} is past 24");
+        }
+        Ok(hour)
+    };
+    Ok((parse(start)?, parse(end)?))
+}
+
+impl Condition {
+    fn matches(&self, event: &ServerEvent, now: chrono::DateTime<chrono::Local>) -> bool {
+        if let Some((start, end)) = self.hours.as_deref().and_then(|h| parse_hours(h).ok()) {
+            let hour = now.hour();
+            let inside = if start <= end {
+                hour >= start && hour < end
+            } else {
+                hour >= start || hour < end
+            };
+            if !inside {
+                return false;
+            }
+        }
+        if self.weekdays && now.weekday().number_from_monday() > 5 {
+            return false;
+        }
+        let field = |name: &str| event.data.get(name);
+        self.min
+            .iter()
+            .all(|(name, min)| field(name).and_then(|v| v.as_f64()).is_some_and(|v| v >= *min))
+            && self.equals.iter().all(|(name, want)| field(name) == Some(want))
+    }
+}
+
+/// Template fields of an event: `event`, `rule`, `at`, plus every data field.
+fn template_fields(rule: &AlertRule, event: &ServerEvent) -> Map<String, Value> {
+    let mut fields = event.data.as_object().cloned().unwrap_or_default();
+    fields.insert("event".into(), json!(event.kind));
+    fields.insert("rule".into(), json!(rule.name));
+    fields.insert("at".into(), json!(event.at.to_rfc3339()));
+    fields
+}
+
+/// Replace `{{field}}` in `text`. Strings are inserted as-is, other values as JSON;
+/// unknown fields are left in place so a typo is visible in the alert.
+fn render_str(text: &str, fields: &Map<String, Value>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + 2 + len].trim();
+        match fields.get(name) {
+            Some(Value::String(s)) => out.push_str(s),
+            Some(other) => out.push_str(&other.to_string()),
+            None => out.push_str(&rest[start..start + 4 + len]),
+        }
+        rest = &rest[start + 4 + len..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Render every string in a JSON template.
+fn render_value(template: &Value, fields: &Map<String, Value>) -> Value {
+    match template {
+        Value::String(s) => Value::String(render_str(s, fields)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| render_value(v, fields)).collect()),
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(k, v)| (k.clone(), render_value(v, fields)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+/// `clientId` -> `CLIENT_ID`
+fn env_name(field: &str) -> String {
+    let mut name = String::from("YIPPIE_ALERT_");
+    for (i, c) in field.chars().enumerate() {
+        if c.is_ascii_uppercase() && i > 0 {
+            name.push('_');
+        }
+        name.push(if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' });
+    }
+    name
+}
+
+#[derive(Debug, Default)]
+struct RuleStats {
+    fired: u64,
+    /// Matched but dropped by the rate limit or the in-flight cap.
+    suppressed: u64,
+    failed: u64,
+    last_fired: Option<Instant>,
+    last_fired_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_error: Option<String>,
+}
+
+/// Configured rules and their counters, owned by `SharedState`.
+pub struct Alerting {
+    rules: Vec<AlertRule>,
+    stats: Mutex<Vec<RuleStats>>,
+    in_flight: Arc<Semaphore>,
+    http: reqwest::Client,
+}
+
+impl Alerting {
+    pub fn new(rules: Vec<AlertRule>) -> Self {
+        let stats = rules.iter().map(|_| RuleStats::default()).collect();
+        Self {
+            rules,
+            stats: Mutex::new(stats),
+            in_flight: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    fn stats(&self) -> std::sync::MutexGuard<'_, Vec<RuleStats>> {
+        self.stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Per-rule counters for studio-status. Action targets are left out: webhook URLs
+    /// usually embed a secret.
+    pub fn report(&self) -> Value {
+        let stats = self.stats();
+        Value::Array(
+            self.rules
+                .iter()
+                .zip(stats.iter())
+                .map(|(rule, s)| {
+                    json!({
+                        "name": rule.name,
+                        "event": rule.event,
+                        "action": rule.action.kind(),
+                        "fired": s.fired,
+                        "suppressed": s.suppressed,
+                        "failed": s.failed,
+                        "lastFiredAt": s.last_fired_at.map(|t| t.to_rfc3339()),
+                        "lastError": s.last_error,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Start the action of every matching rule that isn't rate limited.
+    fn dispatch(self: &Arc<Self>, event: &ServerEvent) {
+        let now = chrono::Local::now();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.event != event.kind || !rule.when.matches(event, now) {
+                continue;
+            }
+            let min_interval = Duration::from_secs(rule.min_interval_secs.unwrap_or(DEFAULT_MIN_INTERVAL_SECS));
+            let permit = {
+                let mut stats = self.stats();
+                let stat = &mut stats[index];
+                if stat.last_fired.is_some_and(|t| t.elapsed() < min_interval) {
+                    stat.suppressed += 1;
+                    continue;
+                }
+                let Ok(permit) = self.in_flight.clone().try_acquire_owned() else {
+                    stat.suppressed += 1;
+                    tracing::warn!(rule = %rule.name, "Alert dropped: too many alert actions running");
+                    continue;
+                };
+                stat.fired += 1;
+                stat.last_fired = Some(Instant::now());
+                stat.last_fired_at = Some(event.at);
+                permit
+            };
+            let alerting = Arc::clone(self);
+            let event = event.clone();
+            tokio::spawn(async move {
+                alerting.execute(index, &event).await;
+                drop(permit);
+            });
+        }
+    }
+
+    /// Run one rule's action with retries, recording the outcome.
+    async fn execute(&self, index: usize, event: &ServerEvent) {
+        let rule = &self.rules[index];
+        let attempts = rule.retries.unwrap_or(DEFAULT_RETRIES) + 1;
+        let mut last_error = String::new();
+        for attempt in 0..attempts {
+            if attempt > 0 {
+                tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+            }
+            match self.run_action(rule, event).await {
+                Ok(()) => {
+                    tracing::info!(rule = %rule.name, event = event.kind, "Alert sent");
+                    return;
+                }
+                Err(e) => {
+                    last_error = format!("{e:#}");
+                    tracing::debug!(rule = %rule.name, attempt, error = %last_error, "Alert attempt failed");
+                }
+            }
+        }
+        tracing::warn!(rule = %rule.name, event = event.kind, attempts, error = %last_error, "Alert failed");
+        let mut stats = self.stats();
+        stats[index].failed += 1;
+        stats[index].last_error = Some(last_error);
+    }
+
+    async fn run_action(&self, rule: &AlertRule, event: &ServerEvent) -> Result<()> {
+        let fields = template_fields(rule, event);
+        match &rule.action {
+            Action::Http { url, headers, body } => {
+                let mut request = self.http.post(url).json(&render_value(body, &fields));
+                for (name, value) in headers {
+                    request = request.header(name, render_str(value, &fields));
+                }
+                // The URL is left out of errors: webhook URLs carry their secret
+                let response = request.send().await.map_err(|e| anyhow::anyhow!("POST failed: {}", e.without_url()))?;
+                if !response.status().is_success() {
+                    bail!("webhook answered {}", response.status());
+                }
+                Ok(())
+            }
+            Action::Command { program, args } => {
+                let mut command = tokio::process::Command::new(program);
+                command
+                    .args(args.iter().map(|a| render_str(a, &fields)))
+                    .env("YIPPIE_ALERT_RULE", &rule.name)
+                    .env("YIPPIE_ALERT_EVENT", event.kind)
+                    .env("YIPPIE_ALERT_AT", event.at.to_rfc3339())
+                    .env("YIPPIE_ALERT_DATA", event.data.to_string())
+                    .stdin(std::process::Stdio::null())
+                    .stdout(std::process::Stdio::null())
+                    .stderr(std::process::Stdio::null())
+                    .kill_on_drop(true);
+                for (name, value) in event.data.as_object().into_iter().flatten() {
+                    let text = match value {
+                        Value::String(s) => s.clone(),
+                        Value::Number(_) | Value::Bool(_) => value.to_string(),
+                        _ => continue,
+                    };
+                    command.env(env_name(name), text);
+                }
+                let mut child = command.spawn().with_context(|| format!("could not start {program}"))?;
+                match tokio::time::timeout(COMMAND_TIMEOUT, child.wait()).await {
+                    Ok(status) => {
+                        let status = status?;
+                        if !status.success() {
+                            bail!("{program} exited with {status}");
+                        }
+                        Ok(())
+                    }
+                    Err(_) => bail!("{program} did not finish within {}s", COMMAND_TIMEOUT.as_secs()),
+                }
+            }
+        }
+    }
+}
+
+/// Feed server events to the rules, and raise the events nobody else notices:
+/// playtests that keep running and clients that stopped polling.
+pub async fn run(state: SharedState, alerting: Arc<Alerting>) {
+    let mut events = state.subscribe_events();
+    let mut watch = tokio::time::interval(WATCH_INTERVAL);
+    watch.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) => alerting.dispatch(&event),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "Alerting fell behind; some server events were not checked");
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = watch.tick() => {
+                // Stale clients are otherwise only noticed when a tool call comes in
+                state.prune_stale_clients().await;
+                if let Some((session_id, mode, running_for)) = state.playtest_running_for().await {
+                    if running_for >= PLAYTEST_STALE_AFTER {
+                        state.emit(server_events::PLAYTEST_STALE, json!({
+                            "sessionId": session_id,
+                            "mode": mode,
+                            "durationSecs": running_for.as_secs(),
+                        }));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::wire::Casing;
+    use axum::http::{HeaderMap, StatusCode};
+    use chrono::TimeZone;
+
+    fn rule(raw: Value) -> AlertRule {
+        parse_rules(&json!([raw]).to_string()).unwrap().remove(0)
+    }
+
+    fn disconnected(client_id: &str) -> ServerEvent {
+        ServerEvent::new(
+            server_events::CLIENT_DISCONNECTED,
+            json!({ "clientId": client_id, "pluginVersion": "0.4.0", "playtestBridge": false, "reason": "stale" }),
+        )
+    }
+
+    /// A local webhook that answers with `statuses` in turn (then 200) and records
+    /// each request's headers and body.
+    async fn webhook(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<(HeaderMap, Value)>>>) {
+        let received: Arc<Mutex<Vec<(HeaderMap, Value)>>> = Arc::default();
+        let statuses = Arc::new(Mutex::new(statuses.into_iter()));
+        let app = axum::Router::new().route(
+            "/hook",
+            axum::routing::post({
+                let received = received.clone();
+                move |headers: HeaderMap, axum::Json(body): axum::Json<Value>| async move {
+                    received.lock().unwrap().push((headers, body));
+                    let status = statuses.lock().unwrap().next().unwrap_or(200);
+                    StatusCode::from_u16(status).unwrap()
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, received)
+    }
+
+    /// Wait for every action started by `dispatch` to finish.
+    async fn settle(alerting: &Alerting) {
+        for _ in 0..1000 {
+            if alerting.in_flight.available_permits() == MAX_IN_FLIGHT {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("alert actions did not finish");
+    }
+
+    fn stat(alerting: &Alerting, field: &str) -> Value {
+        alerting.report()[0][field].clone()
+    }
+
+    #[test]
+    fn rules_are_validated_when_parsed() {
+        let error = |raw: Value| format!("{:#}", parse_rules(&raw.to_string()).unwrap_err());
+        let http = json!({ "type": "http", "url": "https://hooks.example/x" });
+        let base = |patch: Value| {
+            let mut rule = json!({ "name": "r", "event": "client_disconnected", "action": http });
+            rule.as_object_mut().unwrap().extend(patch.as_object().unwrap().clone());
+            json!([rule])
+        };
+        assert!(error(json!({})).starts_with("YIPPIE_ALERT_RULES must be a JSON array of alert rules"));
+        assert_eq!(error(base(json!({ "name": " " }))), "Alert rule with an empty name");
+        assert!(error(base(json!({ "event": "disk_low" }))).starts_with("Alert rule 'r': unknown event 'disk_low' (expected one of client_connected, "));
+        assert_eq!(error(base(json!({ "when": { "hours": "9" } }))), "Alert rule 'r': hours must look like \"9-18\"");
+        assert_eq!(error(base(json!({ "when": { "hours": "9-25" } }))), "Alert rule 'r': hour 25 is past 24");
+        assert_eq!(error(base(json!({ "retries": 6 }))), "Alert rule 'r': retries must be at most 5");
+        assert_eq!(
+            error(base(json!({ "action": { "type": "http", "url": "ftp://x" } }))),
+            "Alert rule 'r': url must start with http:// or https://"
+        );
+        assert_eq!(error(base(json!({ "action": { "type": "command", "program": "" } }))), "Alert rule 'r': program is empty");
+        assert!(error(base(json!({ "action": { "type": "email" } }))).contains("unknown variant `email`"));
+        assert!(error(base(json!({ "when": { "hour": "9-18" } }))).contains("unknown field `hour`"));
+        let once = base(json!({}));
+        let twice = json!([once[0], once[0]]);
+        assert_eq!(error(twice), "Duplicate alert rule name 'r'");
+
+        let parsed = parse_rules(&base(json!({})).to_string()).unwrap();
+        let Action::Http { body, .. } = &parsed[0].action else { panic!() };
+        assert_eq!(body, &default_body());
+    }
+
+    #[test]
+    fn conditions_check_hours_weekdays_and_fields() {
+        // 2026-10-16 is a Friday
+        let at = |day: u32, hour: u32| chrono::Local.with_ymd_and_hms(2026, 10, day, hour, 30, 0).unwrap();
+        assert_eq!(at(16, 0).weekday(), chrono::Weekday::Fri);
+        let event = ServerEvent::new(server_events::PLAYTEST_STALE, json!({ "mode": "play", "durationSecs": 600 }));
+        let condition = |raw: Value| -> Condition { serde_json::from_value(raw).unwrap() };
+
+        let office = condition(json!({ "hours": "9-18", "weekdays": true }));
+        assert!(office.matches(&event, at(16, 9)));
+        assert!(office.matches(&event, at(16, 17)));
+        assert!(!office.matches(&event, at(16, 18)), "end hour is exclusive");
+        assert!(!office.matches(&event, at(17, 10)), "Saturday");
+
+        let night = condition(json!({ "hours": "22-6" }));
+        assert!(night.matches(&event, at(16, 23)));
+        assert!(night.matches(&event, at(17, 5)));
+        assert!(!night.matches(&event, at(16, 12)));
+
+        assert!(condition(json!({ "min": { "durationSecs": 600 } })).matches(&event, at(16, 12)));
+        assert!(!condition(json!({ "min": { "durationSecs": 601 } })).matches(&event, at(16, 12)));
+        assert!(!condition(json!({ "min": { "mode": 1 } })).matches(&event, at(16, 12)), "not a number");
+        assert!(condition(json!({ "equals": { "mode": "play" } })).matches(&event, at(16, 12)));
+        assert!(!condition(json!({ "equals": { "mode": "run" } })).matches(&event, at(16, 12)));
+        assert!(!condition(json!({ "equals": { "missing": null } })).matches(&event, at(16, 12)));
+    }
+
+    #[test]
+    fn templates_insert_fields_and_leave_unknown_ones_visible() {
+        let rule = rule(json!({ "name": "lost plugin", "event": "client_disconnected", "action": { "type": "command", "program": "true" } }));
+        let fields = template_fields(&rule, &disconnected("c-1"));
+        assert_eq!(
+            render_str("{{rule}}: {{ clientId }} ({{playtestBridge}}) {{typo}} {{event}}", &fields),
+            "lost plugin: c-1 (false) {{typo}} client_disconnected"
+        );
+        assert_eq!(render_str("{{clientId} unclosed {{", &fields), "{{clientId} unclosed {{");
+        assert_eq!(render_str("", &fields), "");
+        let body = json!({ "text": "{{clientId}} left", "blocks": [{ "n": 1, "t": "{{reason}}" }], "ok": true });
+        assert_eq!(
+            render_value(&body, &fields),
+            json!({ "text": "c-1 left", "blocks": [{ "n": 1, "t": "stale" }], "ok": true })
+        );
+        assert_eq!(env_name("clientId"), "YIPPIE_ALERT_CLIENT_ID");
+        assert_eq!(env_name("durationSecs"), "YIPPIE_ALERT_DURATION_SECS");
+        assert_eq!(env_name("odd-name"), "YIPPIE_ALERT_ODD_NAME");
+    }
+
+    #[tokio::test]
+    async fn webhooks_get_the_rendered_body_and_headers() {
+        let (url, received) = webhook(Vec::new()).await;
+        let alerting = Arc::new(Alerting::new(vec![rule(json!({
+            "name": "lost plugin",
+            "event": "client_disconnected",
+            "action": { "type": "http", "url": url, "headers": { "X-Client": "{{clientId}}" }, "body": { "text": "{{clientId}} {{reason}}" } },
+        }))]));
+        alerting.dispatch(&disconnected("c-1"));
+        // Other event kinds don't match
+        alerting.dispatch(&ServerEvent::new(server_events::CLIENT_CONNECTED, json!({ "clientId": "c-2" })));
+        settle(&alerting).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0["x-client"], "c-1");
+        assert_eq!(received[0].1, json!({ "text": "c-1 stale" }));
+        assert_eq!((stat(&alerting, "fired"), stat(&alerting, "failed")), (json!(1), json!(0)));
+    }
+
+    #[tokio::test]
+    async fn failed_webhooks_are_retried_then_recorded_without_the_url() {
+        let (url, received) = webhook(vec![500, 503]).await;
+        let secret_url = format!("{url}?token=hunter2");
+        let alerting = Arc::new(Alerting::new(vec![rule(json!({
+            "name": "flaky", "event": "client_disconnected", "retries": 1, "minIntervalSecs": 0,
+            "action": { "type": "http", "url": secret_url },
+        }))]));
+        alerting.dispatch(&disconnected("c-1"));
+        settle(&alerting).await;
+        assert_eq!(received.lock().unwrap().len(), 2);
+        assert_eq!(stat(&alerting, "failed"), 1);
+        assert_eq!(stat(&alerting, "lastError"), "webhook answered 503 Service Unavailable");
+
+        // The third answer is a 200, so the next firing goes through at once
+        alerting.dispatch(&disconnected("c-1"));
+        settle(&alerting).await;
+        assert_eq!(received.lock().unwrap().len(), 3);
+        assert_eq!(stat(&alerting, "failed"), 1);
+
+        // Nobody listening: the error names neither the URL nor its secret
+        let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let alerting = Arc::new(Alerting::new(vec![rule(json!({
+            "name": "down", "event": "client_disconnected", "retries": 0,
+            "action": { "type": "http", "url": format!("http://{closed}/hook?token=hunter2") },
+        }))]));
+        alerting.dispatch(&disconnected("c-1"));
+        settle(&alerting).await;
+        let error = stat(&alerting, "lastError");
+        assert!(error.as_str().unwrap().starts_with("POST failed"), "{error}");
+        assert!(!error.to_string().contains("hunter2") && !alerting.report().to_string().contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn rules_are_rate_limited_per_rule() {
+        let (url, received) = webhook(Vec::new()).await;
+        let alerting = Arc::new(Alerting::new(vec![
+            rule(json!({ "name": "limited", "event": "client_disconnected", "action": { "type": "http", "url": url } })),
+            rule(json!({ "name": "every", "event": "client_disconnected", "minIntervalSecs": 0, "action": { "type": "http", "url": url } })),
+        ]));
+        for _ in 0..3 {
+            alerting.dispatch(&disconnected("c-1"));
+            settle(&alerting).await;
+        }
+        let report = alerting.report();
+        assert_eq!((report[0]["fired"].clone(), report[0]["suppressed"].clone()), (json!(1), json!(2)));
+        assert_eq!((report[1]["fired"].clone(), report[1]["suppressed"].clone()), (json!(3), json!(0)));
+        assert_eq!(received.lock().unwrap().len(), 4);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn commands_get_event_fields_as_arguments_and_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("alert.txt");
+        let script = format!("echo \"$1 $YIPPIE_ALERT_CLIENT_ID $YIPPIE_ALERT_PLAYTEST_BRIDGE $YIPPIE_ALERT_EVENT\" > {}", out.display());
+        let alerting = Arc::new(Alerting::new(vec![
+            rule(json!({
+                "name": "log it", "event": "client_disconnected",
+                "action": { "type": "command", "program": "sh", "args": ["-c", script, "sh", "{{reason}}"] },
+            })),
+            rule(json!({
+                "name": "fails", "event": "client_disconnected", "retries": 0,
+                "action": { "type": "command", "program": "sh", "args": ["-c", "exit 3"] },
+            })),
+            rule(json!({
+                "name": "missing", "event": "client_disconnected", "retries": 0,
+                "action": { "type": "command", "program": "/nonexistent/alert-program" },
+            })),
+        ]));
+        alerting.dispatch(&disconnected("c-1"));
+        settle(&alerting).await;
+        assert_eq!(std::fs::read_to_string(&out).unwrap(), "stale c-1 false client_disconnected\n");
+        let report = alerting.report();
+        assert_eq!(report[0]["failed"], 0);
+        assert_eq!(report[1]["lastError"], "sh exited with exit status: 3");
+        assert!(report[2]["lastError"].as_str().unwrap().starts_with("could not start /nonexistent/alert-program"));
+    }
+
+    #[tokio::test]
+    async fn server_events_reach_the_rules_through_the_state() {
+        let (url, received) = webhook(Vec::new()).await;
+        let rules = parse_rules(&json!([{
+            "name": "connected", "event": "client_connected",
+            "when": { "equals": { "playtestBridge": true } },
+            "action": { "type": "http", "url": url, "body": { "text": "{{clientId}} {{pluginVersion}}" } },
+        }]).to_string()).unwrap();
+        let config = Config { alert_rules: rules, ..Config::default() };
+        let state = SharedState::for_tests(config);
+        let alerting = state.alerting().unwrap();
+        tokio::spawn(run(state.clone(), alerting.clone()));
+        // Let the watcher subscribe before anything is emitted
+        tokio::task::yield_now().await;
+
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        state.register_client("play".into(), "0.4.0-playtest".into(), None, Casing::Snake).await;
+        for _ in 0..1000 {
+            if !received.lock().unwrap().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        settle(&alerting).await;
+        let received = received.lock().unwrap();
+        assert_eq!(received.iter().map(|(_, body)| body.clone()).collect::<Vec<_>>(), [json!({ "text": "play 0.4.0-playtest" })]);
+    }
+}
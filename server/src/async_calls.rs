@@ -0,0 +1,230 @@
+//! Tool calls started with `async: true`, for `studio-get_operation`, `studio-wait_operation`
+//! and `studio-cancel`.
+//!
+//! An async call goes to the plugin like any other, but `tools/call` answers at once
+//! with the call's operation id, the request id its pending call and the plugin request
+//! carry. A task waits for the plugin meanwhile and stores the finished result here.
+//! Cancelling resolves the pending call, so that task finishes at once; the plugin is
+//! told separately. Finished calls are kept for `FINISHED_CALL_TTL`, or until
+//! `MAX_FINISHED_CALLS` newer ones have finished; running ones until they finish.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::providers::Clock;
+
+/// Tools that take `async: true`: the long-running ones answered by the plugin in one
+/// call.
+pub const ASYNC_TOOLS: &[&str] = &["studio-run_script", "studio-test_script", "studio-wait_for_condition"];
+
+/// Finished calls whose results are kept.
+const MAX_FINISHED_CALLS: usize = 50;
+
+/// How long a finished call's result is kept.
+pub const FINISHED_CALL_TTL: Duration = Duration::from_secs(10 * 60);
+
+pub const MAX_RESULT_WAIT_MS: u64 = 60_000;
+/// studio-wait_operation's wait when `timeoutMs` is left out.
+pub const DEFAULT_OPERATION_WAIT_MS: u64 = 30_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallStatus {
+    Running,
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+struct AsyncCall {
+    tool: String,
+    /// The client the request was queued for, which a cancel goes to.
+    client_id: String,
+    /// RFC 3339.
+    started_at: String,
+    started: Instant,
+    finished: Option<Instant>,
+    status: CallStatus,
+    /// The tool result, as the call would have returned it without `async`.
+    result: Option<Value>,
+}
+
+impl AsyncCall {
+    fn elapsed_ms(&self, clock: &dyn Clock) -> u64 {
+        let elapsed = match self.finished {
+            Some(finished) => clock.elapsed(self.started).saturating_sub(clock.elapsed(finished)),
+            None => clock.elapsed(self.started),
+        };
+        elapsed.as_millis() as u64
+    }
+
+    fn expired(&self, clock: &dyn Clock) -> bool {
+        self.finished.is_some_and(|finished| clock.elapsed(finished) >= FINISHED_CALL_TTL)
+    }
+}
+
+/// A running call `cancel` stopped waiting for.
+pub struct Cancelled {
+    pub tool: String,
+    pub client_id: String,
+    pub elapsed_ms: u64,
+}
+
+/// Elapsed times and expiry go through the server clock, so deterministic mode
+/// reports fixed values.
+pub struct AsyncCalls {
+    calls: HashMap<String, AsyncCall>,
+    /// Finished request ids, oldest first.
+    finished: VecDeque<String>,
+    clock: Arc<dyn Clock>,
+}
+
+impl AsyncCalls {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            calls: HashMap::new(),
+            finished: VecDeque::new(),
+            clock,
+        }
+    }
+
+    pub fn start(&mut self, request_id: String, tool: &str, client_id: String, started_at: String) {
+        self.expire();
+        self.calls.insert(
+            request_id,
+            AsyncCall {
+                tool: tool.to_string(),
+                client_id,
+                started_at,
+                started: Instant::now(),
+                finished: None,
+                status: CallStatus::Running,
+                result: None,
+            },
+        );
+    }
+
+    /// Store the result of a call that is still running. A cancelled call keeps its
+    /// status, so the result its wait returned after the cancel is dropped.
+    pub fn finish(&mut self, request_id: &str, result: Value) {
+        let Some(call) = self.calls.get_mut(request_id).filter(|c| c.status == CallStatus::Running) else {
+            return;
+        };
+        call.status = if result["isError"] == true { CallStatus::Failed } else { CallStatus::Succeeded };
+        call.result = Some(result);
+        call.finished = Some(Instant::now());
+        self.retire(request_id);
+    }
+
+    /// Mark a running call cancelled. Errors name why it can't be.
+    pub fn cancel(&mut self, request_id: &str) -> Result<Cancelled, String> {
+        if self.get(request_id).is_none() {
+            return Err(self.unknown(request_id));
+        }
+        let call = self.calls.get_mut(request_id).expect("checked above");
+        if call.status != CallStatus::Running {
+            return Err(format!(
+                "Call {request_id} ({}) is no longer running ({}); nothing to cancel. studio-get_operation has its outcome.",
+                call.tool,
+                status_name(call.status)
+            ));
+        }
+        call.status = CallStatus::Cancelled;
+        call.finished = Some(Instant::now());
+        let cancelled = Cancelled {
+            tool: call.tool.clone(),
+            client_id: call.client_id.clone(),
+            elapsed_ms: call.elapsed_ms(self.clock.as_ref()),
+        };
+        self.retire(request_id);
+        Ok(cancelled)
+    }
+
+    pub fn status(&self, request_id: &str) -> Option<CallStatus> {
+        self.get(request_id).map(|c| c.status)
+    }
+
+    /// What `studio-get_operation` and `studio-wait_operation` return for a call: its
+    /// state, with the tool result under `result` once it has finished.
+    pub fn operation(&self, request_id: &str) -> Result<Value, String> {
+        let call = self.get(request_id).ok_or_else(|| self.unknown(request_id))?;
+        let mut operation = json!({
+            "id": request_id,
+            "tool": call.tool,
+            "status": call.status,
+            "startedAt": call.started_at,
+            "elapsedMs": call.elapsed_ms(self.clock.as_ref()),
+        });
+        if let Some(finished) = call.finished {
+            let left = FINISHED_CALL_TTL.saturating_sub(self.clock.elapsed(finished));
+            operation["expiresInSecs"] = json!(left.as_secs());
+        }
+        if let Some(result) = &call.result {
+            operation["result"] = result.clone();
+        }
+        Ok(operation)
+    }
+
+    /// Calls still running, oldest first.
+    pub fn running(&self) -> Vec<Value> {
+        let mut running: Vec<(&String, &AsyncCall)> =
+            self.calls.iter().filter(|(_, c)| c.status == CallStatus::Running).collect();
+        running.sort_by_key(|(_, c)| c.started);
+        running
+            .into_iter()
+            .map(|(id, c)| json!({ "id": id, "tool": c.tool, "elapsedMs": c.elapsed_ms(self.clock.as_ref()) }))
+            .collect()
+    }
+
+    /// A call, unless its result has outlived `FINISHED_CALL_TTL`.
+    fn get(&self, request_id: &str) -> Option<&AsyncCall> {
+        self.calls.get(request_id).filter(|c| !c.expired(self.clock.as_ref()))
+    }
+
+    /// Drop finished calls past `FINISHED_CALL_TTL`.
+    fn expire(&mut self) {
+        let (calls, clock) = (&mut self.calls, self.clock.as_ref());
+        self.finished.retain(|id| match calls.get(id) {
+            Some(call) if call.expired(clock) => {
+                calls.remove(id);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        });
+    }
+
+    fn retire(&mut self, request_id: &str) {
+        self.finished.push_back(request_id.to_string());
+        while self.finished.len() > MAX_FINISHED_CALLS {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.calls.remove(&oldest);
+            }
+        }
+    }
+
+    fn unknown(&self, request_id: &str) -> String {
+        let running: Vec<String> = self
+            .running()
+            .iter()
+            .filter_map(|c| c["id"].as_str().map(String::from))
+            .collect();
+        let running = if running.is_empty() { "none".to_string() } else { running.join(", ") };
+        format!(
+            "Unknown operation id '{request_id}'. Only calls started with async: true are tracked, and only the last {MAX_FINISHED_CALLS} finished ones are kept, for {} minutes. Running: {running}",
+            FINISHED_CALL_TTL.as_secs() / 60
+        )
+    }
+}
+
+fn status_name(status: CallStatus) -> &'static str {
+    match status {
+        CallStatus::Running => "running",
+        CallStatus::Succeeded => "succeeded",
+        CallStatus::Failed => "failed",
+        CallStatus::Cancelled => "cancelled",
+    }
+}
@@ -0,0 +1,88 @@
+//! Latency summaries and the JSON report of `mcpctl bench`.
+//!
+//! Tool benchmarks are run by mcpctl; the SharedState hot path benchmark runs inside
+//! the server (`--bench-state`) and prints a report in the same format, so both can
+//! be kept and compared with `--baseline`.
+//!
+//! Also used by the `mcpctl` binary, through the library.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const REPORT_FORMAT: &str = "yippieblox-bench";
+pub const REPORT_VERSION: u32 = 1;
+
+/// One benchmark run: how it was driven and a summary per measured operation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    pub format: String,
+    pub version: u32,
+    /// `mock`, `studio` or `state`.
+    pub mode: String,
+    pub created_at: String,
+    pub concurrency: usize,
+    pub warmup: usize,
+    /// Target calls per second for an open-loop run; `None` is closed-loop.
+    pub rate: Option<f64>,
+    pub results: BTreeMap<String, Summary>,
+}
+
+impl Report {
+    pub fn new(mode: &str, concurrency: usize, warmup: usize, rate: Option<f64>) -> Self {
+        Self {
+            format: REPORT_FORMAT.into(),
+            version: REPORT_VERSION,
+            mode: mode.into(),
+            created_at: chrono::Utc::now().to_rfc3339(),
+            concurrency,
+            warmup,
+            rate,
+            results: BTreeMap::new(),
+        }
+    }
+}
+
+/// Latency distribution of one operation. Failed calls are counted in `errors` and
+/// left out of the percentiles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Summary {
+    pub calls: usize,
+    pub errors: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+    pub elapsed_secs: f64,
+    /// Completed calls (including failed ones) per second of wall time.
+    pub throughput: f64,
+}
+
+impl Summary {
+    pub fn from_latencies(mut latencies: Vec<Duration>, errors: usize, elapsed: Duration) -> Self {
+        latencies.sort_unstable();
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        // Nearest rank
+        let percentile = |p: f64| {
+            let rank = ((p / 100.0) * latencies.len() as f64).ceil() as usize;
+            latencies.get(rank.saturating_sub(1)).map_or(0.0, |d| ms(*d))
+        };
+        let total: Duration = latencies.iter().sum();
+        let calls = latencies.len();
+        let elapsed_secs = elapsed.as_secs_f64();
+        Self {
+            calls,
+            errors,
+            mean_ms: if calls == 0 { 0.0 } else { ms(total) / calls as f64 },
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: latencies.last().map_or(0.0, |d| ms(*d)),
+            elapsed_secs,
+            throughput: if elapsed_secs > 0.0 { (calls + errors) as f64 / elapsed_secs } else { 0.0 },
+        }
+    }
+}
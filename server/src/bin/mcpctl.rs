@@ -1,5 +1,21 @@
+use anyhow::Context;
 use clap::{Parser, Subcommand};
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::{oneshot, Mutex};
+use tokio::time::Instant;
+
+use roblox_studio_yippieblox_mcp_server::{bench, client_config, render, state_export};
+
+use bench::{Report, Summary};
+use client_config::{ClientKind, ServerEntry};
+use render::OutputFormat;
+use state_export::{Category, Conflict, ImportPolicy, Staged};
 
 #[derive(Parser)]
 #[command(name = "mcpctl", about = "Debug CLI for YippieBlox MCP Server")]
@@ -12,6 +28,10 @@ struct Cli {
     #[arg(long, env = "YIPPIE_TOKEN")]
     token: Option<String>,
 
+    /// Output format for `captures`, `search` and `bench`: json, text (aligned table) or csv
+    #[arg(long, global = true, value_parser = OutputFormat::parse)]
+    format: Option<OutputFormat>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -19,7 +39,11 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Check if the server is running
-    Health,
+    Health {
+        /// Also show build provenance and runtime facts (needs the token)
+        #[arg(long)]
+        full: bool,
+    },
     /// Show connection status
     Status,
     /// List captures in the capture directory
@@ -28,6 +52,114 @@ enum Commands {
         #[arg(long, default_value = ".roblox-captures")]
         dir: String,
     },
+    /// Search the log archive (server must run with YIPPIE_LOG_ARCHIVE=1)
+    Search {
+        /// Words to search for
+        query: String,
+        /// Only entries at or after this time (RFC 3339)
+        #[arg(long)]
+        from: Option<String>,
+        /// Only entries at or before this time (RFC 3339)
+        #[arg(long)]
+        to: Option<String>,
+        /// Comma-separated levels (output,info,warning,error)
+        #[arg(long)]
+        level: Option<String>,
+        /// Max hits to return
+        #[arg(long, default_value = "50")]
+        limit: u32,
+    },
+    /// Export the capture folder (index, captures, recordings, replays, log archive)
+    /// to a .tar.gz with a manifest, for moving to another machine
+    ExportState {
+        /// Archive to write
+        file: String,
+        /// Path to capture directory
+        #[arg(long, default_value = ".roblox-captures", env = "YIPPIE_CAPTURE_DIR")]
+        dir: String,
+    },
+    /// Import an archive made by export-state. Stop the server first, or use its
+    /// POST /admin/import endpoint instead.
+    ImportState {
+        /// Archive to read
+        file: String,
+        /// Path to capture directory
+        #[arg(long, default_value = ".roblox-captures", env = "YIPPIE_CAPTURE_DIR")]
+        dir: String,
+        /// When captures already exist: skip, overwrite or merge
+        #[arg(long, value_parser = Conflict::parse)]
+        captures: Option<Conflict>,
+        /// When replay files already exist: skip, overwrite or merge
+        #[arg(long, value_parser = Conflict::parse)]
+        replays: Option<Conflict>,
+        /// When archived logs already exist: skip, overwrite or merge
+        #[arg(long, value_parser = Conflict::parse)]
+        logs: Option<Conflict>,
+        /// Ask for each category with conflicts that has no flag
+        #[arg(long)]
+        interactive: bool,
+        /// Show the manifest and conflicts without importing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Add or update this server's entry in an MCP client's config file, with the
+    /// absolute binary path, --stdio and the port/token from the environment
+    InstallClient {
+        /// claude-desktop, claude-code or cursor
+        #[arg(long, value_parser = ClientKind::parse)]
+        client: ClientKind,
+        /// Config file to edit instead of the client's default location
+        #[arg(long)]
+        config_path: Option<std::path::PathBuf>,
+        /// Server binary (defaults to the one next to mcpctl)
+        #[arg(long)]
+        binary: Option<std::path::PathBuf>,
+        /// Print the changes without writing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Measure tool call latency (p50/p90/p99, throughput, errors) through a server
+    /// started over stdio, like an MCP client runs it. Without --mock the server takes
+    /// over --port (stopping a server already on it) and waits for the plugin.
+    Bench {
+        /// Tool to call
+        #[arg(long, default_value = "studio-status")]
+        tool: String,
+        /// JSON arguments
+        #[arg(long, default_value = "{}")]
+        args: String,
+        /// Measured calls (iterations per hot path with --state)
+        #[arg(long, default_value = "500")]
+        n: usize,
+        /// Calls in flight at once
+        #[arg(long, default_value = "8")]
+        concurrency: usize,
+        /// Calls made first and left out of the numbers
+        #[arg(long, default_value = "20")]
+        warmup: usize,
+        /// Start calls at this many per second and time each from when it was due, so a
+        /// stall counts against every call it held up (default: as fast as answers come)
+        #[arg(long)]
+        rate: Option<f64>,
+        /// Answer calls with the server's --mock-studio, for numbers without Studio
+        #[arg(long)]
+        mock: bool,
+        /// Benchmark SharedState hot paths (push_log, enqueue/drain, contended log reads) in-process instead
+        #[arg(long, conflicts_with_all = ["mock", "rate", "tool", "args"])]
+        state: bool,
+        /// Server binary (defaults to the one next to mcpctl)
+        #[arg(long)]
+        binary: Option<PathBuf>,
+        /// Write the JSON report to this file
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Report of an earlier run to compare with; fails on regressions
+        #[arg(long)]
+        baseline: Option<PathBuf>,
+        /// Percent worse than the baseline that counts as a regression
+        #[arg(long, default_value = "10")]
+        threshold: f64,
+    },
     /// Send a test tool call through the bridge
     Call {
         /// Tool name (e.g. studio-status)
@@ -45,13 +177,27 @@ async fn main() -> anyhow::Result<()> {
     let client = reqwest::Client::new();
 
     match cli.command {
-        Commands::Health => {
+        Commands::Health { full: false } => {
             let resp = client
                 .get(format!("{base_url}/health"))
                 .send()
                 .await?;
             println!("Server: {}", resp.text().await?);
         }
+        Commands::Health { full: true } => {
+            let token = cli.token.unwrap_or_default();
+            let resp = client
+                .get(format!("{base_url}/health?level=full"))
+                .header("Authorization", format!("Bearer {token}"))
+                .send()
+                .await?;
+            if resp.status().is_success() {
+                let body: Value = resp.json().await?;
+                println!("{}", serde_json::to_string_pretty(&body)?);
+            } else {
+                eprintln!("Error: {} {}", resp.status(), resp.text().await?);
+            }
+        }
         Commands::Status => {
             let token = cli.token.unwrap_or_default();
             let resp = client
@@ -74,7 +220,10 @@ async fn main() -> anyhow::Result<()> {
             }
             let data = std::fs::read_to_string(&index_path)?;
             let entries: Vec<Value> = serde_json::from_str(&data)?;
-            if entries.is_empty() {
+            if let Some(format) = cli.format {
+                let columns = ["capture_type", "timestamp", "tag", "file_path"];
+                print_formatted(format, &columns, &entries)?;
+            } else if entries.is_empty() {
                 println!("No captures recorded.");
             } else {
                 for (i, entry) in entries.iter().enumerate() {
@@ -91,6 +240,167 @@ async fn main() -> anyhow::Result<()> {
                 }
             }
         }
+        Commands::Search { query, from, to, level, limit } => {
+            let token = cli.token.unwrap_or_default();
+            let mut params = vec![("q", query), ("limit", limit.to_string())];
+            params.extend(from.map(|v| ("from", v)));
+            params.extend(to.map(|v| ("to", v)));
+            params.extend(level.map(|v| ("level", v)));
+            let resp = client
+                .get(format!("{base_url}/archive/search"))
+                .header("Authorization", format!("Bearer {token}"))
+                .query(&params)
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                eprintln!("Error: {} {}", resp.status(), resp.text().await?);
+                return Ok(());
+            }
+            let body: Value = resp.json().await?;
+            let hits = body["hits"].as_array().cloned().unwrap_or_default();
+            if let Some(format) = cli.format {
+                let columns = ["time", "level", "score", "message", "sessionId"];
+                return print_formatted(format, &columns, &hits);
+            }
+            if hits.is_empty() {
+                println!("No matches.");
+            }
+            for hit in &hits {
+                println!(
+                    "{} [{}] {}",
+                    hit["time"].as_str().unwrap_or("?"),
+                    hit["level"].as_str().unwrap_or("?"),
+                    hit["message"].as_str().unwrap_or("")
+                );
+            }
+            if body["status"]["indexing"].as_bool() == Some(true) {
+                println!("(archive still indexing; results may be incomplete)");
+            }
+        }
+        Commands::ExportState { file, dir } => {
+            let exported_by = format!("mcpctl {}", env!("CARGO_PKG_VERSION"));
+            let manifest = state_export::export(std::path::Path::new(&dir), std::path::Path::new(&file), &exported_by)?;
+            println!("Exported {dir} to {file}");
+            for (category, stats) in &manifest.categories {
+                println!("  {:<9} {} files, {} bytes", category.name(), stats.files, stats.bytes);
+            }
+        }
+        Commands::ImportState { file, dir, captures, replays, logs, interactive, dry_run } => {
+            let staged = Staged::extract(std::path::Path::new(&dir), std::path::Path::new(&file))?;
+            let conflicts = staged.conflicts()?;
+            println!(
+                "Archive version {} from {} ({}), made from {}",
+                staged.manifest.version, staged.manifest.exported_by, staged.manifest.created_at, staged.manifest.source_dir
+            );
+            for (category, count) in &conflicts {
+                println!("  {:<9} {count} already present", category.name());
+            }
+            if dry_run {
+                return Ok(());
+            }
+
+            let mut policy = ImportPolicy::default();
+            for (category, flag) in [(Category::Captures, captures), (Category::Replays, replays), (Category::Logs, logs)] {
+                let conflict = match flag {
+                    Some(conflict) => conflict,
+                    None if interactive && conflicts.get(&category).copied().unwrap_or(0) > 0 => ask_conflict(category)?,
+                    None => Conflict::default(),
+                };
+                policy.set(category, conflict);
+            }
+            let report = staged.apply(&policy);
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if report.categories.values().any(|c| !c.errors.is_empty()) {
+                anyhow::bail!("Import finished with errors (see above)");
+            }
+        }
+        Commands::InstallClient { client, config_path, binary, dry_run } => {
+            let path = match config_path {
+                Some(path) => path,
+                None => client.default_config_path()?,
+            };
+            let mut env = std::collections::BTreeMap::from([("YIPPIE_PORT".to_string(), cli.port.to_string())]);
+            if let Some(token) = &cli.token {
+                env.insert("YIPPIE_TOKEN".into(), token.clone());
+            }
+            let entry = ServerEntry { command: server_binary(binary)?, env };
+
+            let existing = match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(e) => return Err(anyhow::Error::new(e).context(format!("Failed to read {}", path.display()))),
+            };
+            let merged = client_config::merge(&existing, client, &entry)
+                .with_context(|| format!("Not changing {}", path.display()))?;
+            if !merged.changed {
+                println!("{} already has an up-to-date '{}' entry", path.display(), client_config::SERVER_NAME);
+                return Ok(());
+            }
+            if dry_run {
+                let mut diff = client_config::diff(&merged.before, &merged.after);
+                if let Some(token) = cli.token.as_deref().filter(|t| !t.is_empty()) {
+                    diff = diff.replace(token, "<YIPPIE_TOKEN>");
+                }
+                println!("Would update {} ({}):", path.display(), client.name());
+                print!("{diff}");
+                return Ok(());
+            }
+
+            if !existing.is_empty() {
+                let backup = client_config::backup(&path)?;
+                println!("Backed up {} to {}", path.display(), backup.display());
+            }
+            client_config::write(&path, &merged.after)?;
+            println!("Wrote '{}' to {}; restart {} to load it", client_config::SERVER_NAME, path.display(), client.name());
+            if cli.token.is_none() && !merged.after.contains("\"YIPPIE_TOKEN\"") {
+                println!("No YIPPIE_TOKEN set, so the bridge will run without auth; set it and re-run to add one");
+            }
+        }
+        Commands::Bench { tool, args, n, concurrency, warmup, rate, mock, state, binary, json, baseline, threshold } => {
+            let binary = server_binary(binary)?;
+            let report = if state {
+                bench_state(&binary, n, warmup).await?
+            } else {
+                let args: Value = serde_json::from_str(&args).context("--args is not valid JSON")?;
+                if rate.is_some_and(|r| r.is_nan() || r <= 0.0) {
+                    anyhow::bail!("--rate must be above 0");
+                }
+                let port = if mock { 0 } else { cli.port };
+                let server = Arc::new(StdioServer::start(&binary, port, cli.token.as_deref(), mock).await?);
+                server.wait_for_plugin().await?;
+                let concurrency = concurrency.max(1);
+                run_calls(&server, &tool, &args, warmup, concurrency, None).await;
+                let (summary, first_error) = run_calls(&server, &tool, &args, n, concurrency, rate).await;
+                if let Some(error) = first_error {
+                    eprintln!("First error: {error}");
+                }
+                let mut report = Report::new(if mock { "mock" } else { "studio" }, concurrency, warmup, rate);
+                report.results.insert(tool, summary);
+                report
+            };
+
+            if cli.format == Some(OutputFormat::Json) {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_report(&report);
+            }
+            if let Some(path) = &json {
+                std::fs::write(path, serde_json::to_string_pretty(&report)?)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                println!("Report written to {}", path.display());
+            }
+            if let Some(path) = &baseline {
+                let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+                let base: Report = serde_json::from_str(&text)
+                    .ok()
+                    .filter(|r: &Report| r.format == bench::REPORT_FORMAT)
+                    .with_context(|| format!("{} is not an mcpctl bench report", path.display()))?;
+                let regressions = compare_reports(&base, &report, threshold);
+                if regressions > 0 {
+                    anyhow::bail!("{regressions} regression(s) beyond {threshold}% against {}", path.display());
+                }
+            }
+        }
         Commands::Call { tool, args } => {
             let token = cli.token.unwrap_or_default();
             let args_json: Value = serde_json::from_str(&args)?;
@@ -125,3 +435,325 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Absolute path of the server binary: `binary`, or the one built next to mcpctl.
+fn server_binary(binary: Option<std::path::PathBuf>) -> anyhow::Result<std::path::PathBuf> {
+    let path = match binary {
+        Some(path) => path,
+        None => {
+            let exe = std::env::current_exe()?;
+            let name = format!("roblox-studio-yippieblox-mcp-server{}", std::env::consts::EXE_SUFFIX);
+            exe.with_file_name(name)
+        }
+    };
+    std::fs::canonicalize(&path).map_err(|e| {
+        anyhow::anyhow!("Server binary not found at {} ({e}); build it or pass --binary", path.display())
+    })
+}
+
+/// How long `bench` waits for a plugin to connect to the server it started.
+const BENCH_PLUGIN_WAIT: Duration = Duration::from_secs(60);
+
+/// How long `bench` waits for one call before counting it as failed.
+const BENCH_CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A server child process spoken to over stdio, with requests matched to responses
+/// by id so several can be in flight.
+struct StdioServer {
+    _child: tokio::process::Child,
+    stdin: Mutex<tokio::process::ChildStdin>,
+    pending: Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>>,
+    next_id: AtomicU64,
+}
+
+impl StdioServer {
+    async fn start(binary: &std::path::Path, port: u16, token: Option<&str>, mock: bool) -> anyhow::Result<Self> {
+        let mut command = tokio::process::Command::new(binary);
+        command
+            .arg("--stdio")
+            .env("YIPPIE_PORT", port.to_string())
+            .env("RUST_LOG", "warn")
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::null())
+            .kill_on_drop(true);
+        if mock {
+            command.arg("--mock-studio");
+        }
+        if let Some(token) = token {
+            command.env("YIPPIE_TOKEN", token);
+        }
+        let mut child = command.spawn().with_context(|| format!("Failed to start {}", binary.display()))?;
+        let stdin = child.stdin.take().context("server stdin not piped")?;
+        let stdout = child.stdout.take().context("server stdout not piped")?;
+
+        let pending: Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<Value>>>> = Arc::default();
+        let responses = pending.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let Ok(message) = serde_json::from_str::<Value>(&line) else {
+                    continue;
+                };
+                // Requests and notifications from the server aren't answers
+                if message.get("method").is_some() {
+                    continue;
+                }
+                let Some(id) = message["id"].as_u64() else {
+                    continue;
+                };
+                if let Some(tx) = responses.lock().unwrap().remove(&id) {
+                    let _ = tx.send(message);
+                }
+            }
+        });
+
+        let server = Self { _child: child, stdin: Mutex::new(stdin), pending, next_id: AtomicU64::new(1) };
+        let init = json!({
+            "protocolVersion": "2024-11-05",
+            "capabilities": {},
+            "clientInfo": { "name": "mcpctl-bench", "version": env!("CARGO_PKG_VERSION") },
+        });
+        server.request("initialize", init).await.map_err(anyhow::Error::msg)?;
+        server.send(&json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await?;
+        Ok(server)
+    }
+
+    async fn send(&self, message: &Value) -> anyhow::Result<()> {
+        let mut stdin = self.stdin.lock().await;
+        stdin.write_all(format!("{message}\n").as_bytes()).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    /// The `result` of a request, or its error message.
+    async fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id, tx);
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = self.send(&message).await {
+            self.pending.lock().unwrap().remove(&id);
+            return Err(format!("Server stdin closed: {e}"));
+        }
+        let response = match tokio::time::timeout(BENCH_CALL_TIMEOUT, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err("Server exited".into()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(format!("No answer within {}s", BENCH_CALL_TIMEOUT.as_secs()));
+            }
+        };
+        match response.get("error") {
+            Some(error) => Err(error["message"].as_str().unwrap_or("JSON-RPC error").to_string()),
+            None => Ok(response["result"].clone()),
+        }
+    }
+
+    /// Text of a successful tool result; a result with `isError` is an error.
+    async fn call_tool(&self, tool: &str, args: &Value) -> Result<String, String> {
+        let result = self.request("tools/call", json!({ "name": tool, "arguments": args })).await?;
+        let text = result["content"][0]["text"].as_str().unwrap_or_default().to_string();
+        if result["isError"].as_bool() == Some(true) {
+            return Err(text);
+        }
+        Ok(text)
+    }
+
+    async fn wait_for_plugin(&self) -> anyhow::Result<()> {
+        let deadline = Instant::now() + BENCH_PLUGIN_WAIT;
+        let mut announced = false;
+        loop {
+            let status = self.call_tool("studio-status", &json!({})).await.map_err(anyhow::Error::msg)?;
+            let connected = serde_json::from_str::<Value>(&status).is_ok_and(|s| s["connected"].as_bool() == Some(true));
+            if connected {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                anyhow::bail!("No plugin connected within {}s", BENCH_PLUGIN_WAIT.as_secs());
+            }
+            if !announced {
+                println!("Waiting for the Studio plugin to connect...");
+                announced = true;
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+    }
+}
+
+/// Make `n` calls with `concurrency` in flight. With `rate`, call i is due at
+/// i / rate seconds and timed from then rather than from when a worker got to it,
+/// so slow answers don't hide the calls they held up (coordinated omission).
+/// Returns the summary and the first error seen.
+async fn run_calls(
+    server: &Arc<StdioServer>,
+    tool: &str,
+    args: &Value,
+    n: usize,
+    concurrency: usize,
+    rate: Option<f64>,
+) -> (Summary, Option<String>) {
+    let next = Arc::new(AtomicUsize::new(0));
+    let started = Instant::now();
+    let mut workers = tokio::task::JoinSet::new();
+    for _ in 0..concurrency {
+        let (server, next, tool, args) = (server.clone(), next.clone(), tool.to_string(), args.clone());
+        workers.spawn(async move {
+            let mut latencies = Vec::new();
+            let mut errors = 0;
+            let mut first_error = None;
+            loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= n {
+                    break;
+                }
+                let due = match rate {
+                    Some(rate) => {
+                        let due = started + Duration::from_secs_f64(i as f64 / rate);
+                        tokio::time::sleep_until(due).await;
+                        due
+                    }
+                    None => Instant::now(),
+                };
+                match server.call_tool(&tool, &args).await {
+                    Ok(_) => latencies.push(due.elapsed()),
+                    Err(error) => {
+                        errors += 1;
+                        first_error.get_or_insert(error);
+                    }
+                }
+            }
+            (latencies, errors, first_error)
+        });
+    }
+
+    let mut latencies = Vec::with_capacity(n);
+    let mut errors = 0;
+    let mut first_error = None;
+    while let Some(Ok((worker_latencies, worker_errors, worker_error))) = workers.join_next().await {
+        latencies.extend(worker_latencies);
+        errors += worker_errors;
+        first_error = first_error.or(worker_error);
+    }
+    (Summary::from_latencies(latencies, errors, started.elapsed()), first_error)
+}
+
+/// Run the server's in-process SharedState benchmark and read its report.
+async fn bench_state(binary: &std::path::Path, n: usize, warmup: usize) -> anyhow::Result<Report> {
+    let output = tokio::process::Command::new(binary)
+        .args(["--bench-state", &n.to_string(), "--bench-warmup", &warmup.to_string()])
+        .env("RUST_LOG", "warn")
+        .stderr(std::process::Stdio::null())
+        .output()
+        .await
+        .with_context(|| format!("Failed to start {}", binary.display()))?;
+    if !output.status.success() {
+        anyhow::bail!("{} --bench-state failed ({})", binary.display(), output.status);
+    }
+    serde_json::from_slice(&output.stdout).context("Server printed no benchmark report (is it older than mcpctl?)")
+}
+
+/// Milliseconds, in microseconds below one.
+fn format_ms(ms: f64) -> String {
+    if ms < 1.0 {
+        format!("{:.1} µs", ms * 1000.0)
+    } else {
+        format!("{ms:.2} ms")
+    }
+}
+
+fn print_report(report: &Report) {
+    let driving = match report.rate {
+        Some(rate) => format!("{rate}/s open loop"),
+        None => "closed loop".into(),
+    };
+    for (name, s) in &report.results {
+        println!(
+            "{name} ({}, {} concurrent, {driving}): {} calls, {} errors in {:.2} s",
+            report.mode, report.concurrency, s.calls, s.errors, s.elapsed_secs
+        );
+        println!(
+            "  p50 {}  p90 {}  p99 {}  max {}  mean {}",
+            format_ms(s.p50_ms),
+            format_ms(s.p90_ms),
+            format_ms(s.p99_ms),
+            format_ms(s.max_ms),
+            format_ms(s.mean_ms)
+        );
+        println!("  throughput {:.1} calls/s", s.throughput);
+    }
+}
+
+/// Print how `current` compares with `baseline` and return how many metrics got worse
+/// by more than `threshold` percent.
+fn compare_reports(baseline: &Report, current: &Report, threshold: f64) -> usize {
+    if (&baseline.mode, baseline.concurrency, baseline.rate) != (&current.mode, current.concurrency, current.rate) {
+        println!(
+            "Note: the baseline ran as {} with {} concurrent at rate {:?}; numbers may not be comparable",
+            baseline.mode, baseline.concurrency, baseline.rate
+        );
+    }
+    let limit = threshold / 100.0;
+    let mut regressions = 0;
+    for (name, now) in &current.results {
+        let Some(base) = baseline.results.get(name) else {
+            println!("{name}: not in the baseline");
+            continue;
+        };
+        println!("{name} against the baseline:");
+        // (metric, baseline, current, higher is worse)
+        let metrics = [
+            ("p50", base.p50_ms, now.p50_ms, true),
+            ("p90", base.p90_ms, now.p90_ms, true),
+            ("p99", base.p99_ms, now.p99_ms, true),
+            ("throughput", base.throughput, now.throughput, false),
+        ];
+        for (metric, was, is, higher_is_worse) in metrics {
+            let change = if was > 0.0 { (is - was) / was } else { 0.0 };
+            let worse = if higher_is_worse { change > limit } else { change < -limit };
+            let shown = |v: f64| if metric == "throughput" { format!("{v:.1}/s") } else { format_ms(v) };
+            println!(
+                "  {metric:<10} {:>12} -> {:>12} ({:+.1}%){}",
+                shown(was),
+                shown(is),
+                change * 100.0,
+                if worse { "  REGRESSION" } else { "" }
+            );
+            regressions += usize::from(worse);
+        }
+        let error_rate = |s: &Summary| s.errors as f64 / (s.calls + s.errors).max(1) as f64;
+        if now.errors > 0 && error_rate(now) > error_rate(base) * (1.0 + limit) {
+            println!("  errors     {:>12} -> {:>12}  REGRESSION", base.errors, now.errors);
+            regressions += 1;
+        }
+    }
+    regressions
+}
+
+/// Ask on stdin how to handle a category that already has data.
+fn ask_conflict(category: Category) -> anyhow::Result<Conflict> {
+    use std::io::Write;
+    loop {
+        print!("{} already has data: [s]kip, [o]verwrite or [m]erge? ", category.name());
+        std::io::stdout().flush()?;
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            anyhow::bail!("No answer for {} on stdin", category.name());
+        }
+        match answer.trim().to_ascii_lowercase().as_str() {
+            "s" | "skip" => return Ok(Conflict::Skip),
+            "o" | "overwrite" => return Ok(Conflict::Overwrite),
+            "m" | "merge" | "" => return Ok(Conflict::Merge),
+            _ => continue,
+        }
+    }
+}
+
+/// Print `rows` in the requested format; JSON prints the rows as an array.
+fn print_formatted(format: OutputFormat, columns: &[&str], rows: &[Value]) -> anyhow::Result<()> {
+    match render::render(format, columns, rows) {
+        Some(rendered) => print!("{rendered}"),
+        None => println!("{}", serde_json::to_string_pretty(rows)?),
+    }
+    Ok(())
+}
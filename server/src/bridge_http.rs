@@ -1,17 +1,35 @@
+//! The HTTP bridge the Studio plugin talks to: it registers, long-polls `/pull` for
+//! tool requests and posts results and events to `/push`. Also serves `/health`,
+//! `/status`, `/metrics` and the admin endpoints used by mcpctl.
+
 use axum::{
-    extract::{Query, State},
-    http::{HeaderMap, StatusCode},
+    body::Bytes,
+    extract::{rejection::JsonRejection, Query, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
 use serde::Deserialize;
-use serde_json::json;
-use std::time::Duration;
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use crate::build_info::BuildInfo;
 use crate::config::Config;
+use crate::event_order::{EventOrdering, EVENT_SEQ_FEATURE, GAP_TIMEOUT};
+use crate::log_archive::ArchiveQuery;
+use crate::path_index::{DeltaOutcome, TreeDelta};
+use crate::plugin_settings::PluginSettings;
+use crate::profiles;
+use crate::readiness::Readiness;
+use crate::server::SHUTDOWN_DRAIN_TIMEOUT;
+use crate::state_export::{self, Category, Conflict, ImportPolicy, Staged};
 use crate::state::SharedState;
 use crate::types::*;
+use crate::wire::{self, Casing};
 
 /// Kill any process currently listening on the given port.
 fn kill_port_holder(port: u16) {
@@ -42,12 +60,31 @@ fn kill_port_holder(port: u16) {
 struct AppState {
     shared: SharedState,
     config: Config,
+    /// Unknown bridge fields already warned about in strict mode.
+    warned_fields: Arc<std::sync::Mutex<BTreeSet<String>>>,
+}
+
+/// Bind the bridge listener on 127.0.0.1, killing any stale process holding the port.
+pub async fn bind(config: &Config) -> anyhow::Result<tokio::net::TcpListener> {
+    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], config.port));
+
+    // Kill any existing process holding this port
+    kill_port_holder(config.port);
+
+    Ok(tokio::net::TcpListener::bind(addr).await?)
 }
 
-pub async fn serve(config: Config, state: SharedState) -> anyhow::Result<()> {
+/// Serve the bridge on `listener` until it fails. Requests are checked against
+/// `config.token`.
+pub async fn serve(
+    listener: tokio::net::TcpListener,
+    config: Config,
+    state: SharedState,
+) -> anyhow::Result<()> {
     let app_state = AppState {
         shared: state,
         config: config.clone(),
+        warned_fields: Default::default(),
     };
 
     let app = Router::new()
@@ -56,15 +93,17 @@ pub async fn serve(config: Config, state: SharedState) -> anyhow::Result<()> {
         .route("/push", post(handle_push))
         .route("/health", get(handle_health))
         .route("/status", get(handle_status))
+        .route("/clients", get(handle_clients))
+        .route("/metrics", get(handle_metrics))
+        .route("/archive/search", get(handle_archive_search))
+        .route("/debug/routing", get(handle_debug_routing))
+        .route("/admin/export", post(handle_admin_export))
+        .route("/admin/import", post(handle_admin_import))
+        .route("/admin/profile", get(handle_admin_profile).post(handle_admin_set_profile))
+        .route("/shutdown", post(handle_shutdown))
         .with_state(app_state);
 
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], config.port));
-
-    // Kill any existing process holding this port
-    kill_port_holder(config.port);
-
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-
+    let addr = listener.local_addr()?;
     tracing::info!("HTTP bridge listening on http://{addr}");
     axum::serve(listener, app).await?;
     Ok(())
@@ -72,9 +111,9 @@ pub async fn serve(config: Config, state: SharedState) -> anyhow::Result<()> {
 
 // ─── Auth ─────────────────────────────────────────────────────
 
-fn check_auth(headers: &HeaderMap, config: &Config) -> Result<(), (StatusCode, String)> {
-    let token = match &config.token {
-        Some(t) => t,
+fn check_auth(headers: &HeaderMap, app: &AppState) -> Result<(), (StatusCode, String)> {
+    let token = match &app.config.token {
+        Some(t) => t.expose(),
         None => return Ok(()), // Auth disabled — allow all requests
     };
 
@@ -85,6 +124,9 @@ fn check_auth(headers: &HeaderMap, config: &Config) -> Result<(), (StatusCode, S
 
     let expected = format!("Bearer {token}");
     if auth != expected {
+        // Never log the header itself: a near-miss would leak most of the token
+        tracing::warn!(header_present = !auth.is_empty(), "Rejected bridge request: bad Authorization header");
+        app.shared.record_auth_failure();
         return Err((
             StatusCode::UNAUTHORIZED,
             "Invalid or missing Authorization header".into(),
@@ -93,37 +135,91 @@ fn check_auth(headers: &HeaderMap, config: &Config) -> Result<(), (StatusCode, S
     Ok(())
 }
 
+/// Endpoints that change or stop the server are refused outright while auth is
+/// disabled, since any local process or web page could reach them then.
+fn require_token(app: &AppState, endpoint: &str) -> Result<(), (StatusCode, String)> {
+    if app.config.token.is_none() {
+        return Err((StatusCode::FORBIDDEN, format!("{endpoint} is disabled without a bridge token: set YIPPIE_TOKEN")));
+    }
+    Ok(())
+}
+
+/// Unwrap a JSON body. Requiring `application/json` means a browser has to send a
+/// CORS preflight first, which the bridge doesn't answer.
+fn json_body<T>(body: Result<Json<T>, JsonRejection>, expected: &str) -> Result<T, (StatusCode, String)> {
+    body.map(|Json(body)| body).map_err(|e| (e.status(), format!("Expected a JSON body {expected}: {}", e.body_text())))
+}
+
 // ─── POST /register ───────────────────────────────────────────
 
 async fn handle_register(
     State(app): State<AppState>,
     headers: HeaderMap,
-    Json(body): Json<BridgeRegisterRequest>,
+    Json(raw): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    check_auth(&headers, &app.config)?;
+    check_auth(&headers, &app)?;
+    app.shared.record_bridge_request();
+
+    if app.config.strict_bridge {
+        warn_unknown_fields(&app, "/register", wire::unknown_register_fields(&raw));
+    }
+    let body: BridgeRegisterRequest = serde_json::from_value(raw)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid register payload: {e}")))?;
 
-    let client_id = uuid::Uuid::new_v4().to_string();
+    let client_id = app.shared.next_id();
     let version = if body.plugin_version.is_empty() {
         "unknown".to_string()
     } else {
         body.plugin_version
     };
 
-    tracing::info!(client_id = %client_id, plugin_version = %version, "Plugin registered");
-    app.shared.register_client(client_id.clone(), version).await;
+    let instance_key = body.instance_key.filter(|k| !k.is_empty() && k.len() <= 64);
 
-    Ok(Json(BridgeRegisterResponse {
+    let features: Vec<String> = body
+        .features
+        .into_iter()
+        .filter(|f| f == EVENT_SEQ_FEATURE || f == wire::CAMEL_CASE_FEATURE)
+        .collect();
+    let casing = Casing::negotiate(&features);
+
+    tracing::info!(client_id = %client_id, plugin_version = %version, ?features, "Plugin registered");
+    app.shared
+        .register_client(client_id.clone(), version, instance_key, casing)
+        .await;
+
+    let response = BridgeRegisterResponse {
         client_id,
         server_version: env!("CARGO_PKG_VERSION").to_string(),
-    }))
+        features,
+    };
+    let response = serde_json::to_value(response).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(casing.apply(response)))
+}
+
+/// Strict mode: name fields the server ignored, once per field.
+fn warn_unknown_fields(app: &AppState, endpoint: &str, unknown: BTreeSet<String>) {
+    let mut warned = app.warned_fields.lock().unwrap_or_else(|e| e.into_inner());
+    let new: Vec<String> = unknown
+        .into_iter()
+        .filter(|field| warned.insert(format!("{endpoint} {field}")))
+        .collect();
+    if !new.is_empty() {
+        tracing::warn!(endpoint, fields = %new.join(", "), "Unknown bridge fields ignored (YIPPIE_BRIDGE_STRICT)");
+    }
 }
 
 // ─── GET /pull?clientId=... ───────────────────────────────────
 
+/// `traffic::BackgroundLevel` as a number (3 full .. 0 paused), on every /pull response.
+const BACKGROUND_LEVEL_HEADER: HeaderName = HeaderName::from_static("x-yippie-background-level");
+
 #[derive(Deserialize)]
 struct PullParams {
     #[serde(rename = "clientId")]
     client_id: String,
+    /// The client's clock when it sent the request (see `clock_sync.rs`).
+    #[serde(default)]
+    clock: Option<f64>,
 }
 
 async fn handle_pull(
@@ -131,31 +227,45 @@ async fn handle_pull(
     headers: HeaderMap,
     Query(params): Query<PullParams>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    check_auth(&headers, &app.config)?;
+    check_auth(&headers, &app)?;
+    app.shared.record_bridge_request();
 
     let client_id = &params.client_id;
-
-    // Try immediate drain
-    let requests = app.shared.drain_outbound(client_id).await;
-    if !requests.is_empty() {
-        return Ok(Json(requests));
+    let Some((notify, traffic, casing)) = app.shared.poll_handles(client_id).await else {
+        return Err((StatusCode::NOT_FOUND, "Unknown clientId".into()));
+    };
+    if let Some(clock) = params.clock {
+        app.shared.record_plugin_clock(client_id, clock).await;
     }
 
-    // Long-poll: wait up to 25 seconds for new requests
-    let notify = app.shared.get_notify(client_id).await;
-    if let Some(notify) = notify {
+    // Try immediate drain
+    let mut requests = app.shared.drain_outbound(client_id).await;
+    let mut timed_out = false;
+    if requests.is_empty() {
+        // Long-poll: wait up to 25 seconds for new requests
         match tokio::time::timeout(Duration::from_secs(25), notify.notified()).await {
-            Ok(_) => {
-                let requests = app.shared.drain_outbound(client_id).await;
-                Ok(Json(requests))
-            }
-            Err(_) => {
-                // Timeout — return empty
-                Ok(Json(vec![]))
-            }
+            Ok(_) => requests = app.shared.drain_outbound(client_id).await,
+            // Timeout — return empty
+            Err(_) => timed_out = true,
         }
-    } else {
-        Err((StatusCode::NOT_FOUND, "Unknown clientId".into()))
+    }
+    log_delivered(client_id, &requests);
+
+    let pulled: Vec<_> = requests.iter().map(|request| wire::PulledRequest(request, casing)).collect();
+    let body = serde_json::to_vec(&pulled).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    traffic.record_poll(body.len(), timed_out);
+    let headers = [
+        (header::CONTENT_TYPE, "application/json".to_string()),
+        (BACKGROUND_LEVEL_HEADER, (app.shared.background_level() as u8).to_string()),
+    ];
+    Ok((headers, body))
+}
+
+/// Arguments were already logged (shortened per `YIPPIE_LOG_CODE`) when the call was
+/// queued, so delivery only names the request.
+fn log_delivered(client_id: &str, requests: &[BridgeToolRequest]) {
+    for request in requests {
+        tracing::debug!(client_id = %client_id, request_id = %request.request_id, tool = %request.tool_name, "Delivering tool request");
     }
 }
 
@@ -165,17 +275,46 @@ async fn handle_pull(
 struct PushParams {
     #[serde(rename = "clientId")]
     client_id: String,
+    /// The client's clock when it sent the request (see `clock_sync.rs`).
+    #[serde(default)]
+    clock: Option<f64>,
 }
 
 async fn handle_push(
     State(app): State<AppState>,
     headers: HeaderMap,
     Query(params): Query<PushParams>,
-    Json(body): Json<BridgePushPayload>,
+    raw: Bytes,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    check_auth(&headers, &app.config)?;
+    check_auth(&headers, &app)?;
+    app.shared.record_bridge_request();
 
     let client_id = &params.client_id;
+    if let Some(clock) = params.clock {
+        app.shared.record_plugin_clock(client_id, clock).await;
+    }
+    if let Some(traffic) = app.shared.client_traffic(client_id).await {
+        traffic.record_push(raw.len());
+    }
+    // Invalid UTF-8 (e.g. a Luau string cut mid-character) becomes U+FFFD instead of
+    // failing the whole push
+    let raw: Cow<'_, [u8]> = match String::from_utf8_lossy(&raw) {
+        Cow::Borrowed(_) => Cow::Borrowed(&raw),
+        Cow::Owned(repaired) => {
+            tracing::debug!(client_id = %client_id, "Replaced invalid UTF-8 in push payload");
+            Cow::Owned(repaired.into_bytes())
+        }
+    };
+    // Parsed by hand so the payload size is known
+    let body: BridgePushPayload = if app.config.strict_bridge {
+        let value: Value = serde_json::from_slice(&raw)
+            .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid push payload: {e}")))?;
+        warn_unknown_fields(&app, "/push", wire::unknown_push_fields(&value));
+        serde_json::from_value(value)
+    } else {
+        serde_json::from_slice(&raw)
+    }
+    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Invalid push payload: {e}")))?;
     tracing::debug!(
         client_id = %client_id,
         responses = body.responses.len(),
@@ -197,21 +336,82 @@ async fn handle_push(
         }
     }
 
-    // Process events
-    for event in body.events {
-        handle_event(&app.shared, &event).await;
-    }
+    ingest_events(&app.shared, client_id, body.events).await;
 
     Ok(Json(json!({ "ok": true })))
 }
 
-async fn handle_event(state: &SharedState, event: &BridgeEvent) {
+/// Apply pushed events. Numbered events go through the client's reorder window
+/// and are applied in sequence order; unnumbered ones are applied as they come.
+async fn ingest_events(state: &SharedState, client_id: &str, events: Vec<BridgeEvent>) {
+    if events.iter().all(|e| e.seq.is_none()) {
+        for event in &events {
+            handle_event(state, client_id, event).await;
+        }
+        return;
+    }
+
+    let mut ordering = state.event_order().await;
+    let EventOrdering { clients, stats } = &mut *ordering;
+    let order = clients.entry(client_id.to_string()).or_default();
+    for event in events {
+        let ready = match event.seq {
+            Some(seq) => order.accept(seq, event, stats),
+            None => vec![event],
+        };
+        for event in &ready {
+            handle_event(state, client_id, event).await;
+        }
+    }
+    if order.has_gap() && !order.gap_timer_armed {
+        order.gap_timer_armed = true;
+        tokio::spawn(flush_event_gaps(state.clone(), client_id.to_string()));
+    }
+}
+
+/// Skip each gap once it has been open for `GAP_TIMEOUT`, applying what was waiting
+/// on it, until nothing is held back any more.
+async fn flush_event_gaps(state: SharedState, client_id: String) {
+    let mut deadline = Instant::now() + GAP_TIMEOUT;
+    loop {
+        tokio::time::sleep_until(deadline.into()).await;
+        let mut ordering = state.event_order().await;
+        let EventOrdering { clients, stats } = &mut *ordering;
+        let Some(order) = clients.get_mut(&client_id) else {
+            return;
+        };
+        match order.gap_deadline() {
+            // Filled in the meantime, and a later gap opened: give it its full wait
+            Some(d) if d > Instant::now() => deadline = d,
+            Some(_) => {
+                let ready = order.skip_gap(stats);
+                for event in &ready {
+                    handle_event(&state, &client_id, event).await;
+                }
+                match order.gap_deadline() {
+                    Some(d) => deadline = d,
+                    None => {
+                        order.gap_timer_armed = false;
+                        return;
+                    }
+                }
+            }
+            None => {
+                order.gap_timer_armed = false;
+                return;
+            }
+        }
+    }
+}
+
+/// Apply a bridge event to shared state. Also used by the in-process mock plugin.
+pub(crate) async fn handle_event(state: &SharedState, client_id: &str, event: &BridgeEvent) {
     match event.event_type.as_str() {
         "studio-log" => {
             let level = event.data.get("level").and_then(|v| v.as_str()).unwrap_or("output");
             let message = event.data.get("message").and_then(|v| v.as_str()).unwrap_or("");
             let session_id = event.data.get("sessionId").and_then(|v| v.as_str()).map(String::from);
-            state.push_log(level.to_string(), message.to_string(), session_id).await;
+            state.push_log(level.to_string(), message.to_string(), session_id);
         }
         "studio-playtest_state" => {
             let active = event.data.get("active").and_then(|v| v.as_bool()).unwrap_or(false);
@@ -219,6 +419,36 @@ async fn handle_event(state: &SharedState, event: &BridgeEvent) {
             let mode = event.data.get("mode").and_then(|v| v.as_str()).map(String::from);
             state.update_playtest(active, session_id, mode).await;
         }
+        "studio-playtest_ready" => {
+            let stage = event.data.get("stage").and_then(|v| v.as_str()).unwrap_or("");
+            match Readiness::parse(stage) {
+                Some(readiness) => {
+                    let player = event.data.get("player").and_then(|v| v.as_str()).map(String::from);
+                    state.update_playtest_readiness(Some(readiness), player).await;
+                }
+                None => tracing::warn!(stage, "Malformed studio-playtest_ready event"),
+            }
+        }
+        "studio-input_sample" => {
+            let name = event.data.get("recording").and_then(|v| v.as_str()).unwrap_or("");
+            match serde_json::from_value::<Vec<InputSample>>(event.data["samples"].clone()) {
+                Ok(samples) => state.append_input_samples(name, samples).await,
+                Err(e) => tracing::warn!(error = %e, "Malformed studio-input_sample event"),
+            }
+        }
+        "studio-settings" => match serde_json::from_value::<PluginSettings>(event.data.clone()) {
+            Ok(settings) => state.set_plugin_settings(client_id, settings).await,
+            Err(e) => tracing::warn!(error = %e, "Malformed studio-settings event"),
+        },
+        "studio-tree_delta" => match serde_json::from_value::<TreeDelta>(event.data.clone()) {
+            Ok(delta) => {
+                let outcome = state.path_index().map(|mut index| index.apply_delta(client_id, delta));
+                if outcome == Some(DeltaOutcome::NeedsRefresh) {
+                    state.request_tree_refresh();
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Malformed studio-tree_delta event"),
+        },
         "studio-capture" => {
             tracing::info!(data = ?event.data, "Capture event received");
             // Capture metadata is handled by the captures module when the
@@ -232,8 +462,34 @@ async fn handle_event(state: &SharedState, event: &BridgeEvent) {
 
 // ─── GET /health ──────────────────────────────────────────────
 
-async fn handle_health() -> &'static str {
-    "ok"
+#[derive(Deserialize)]
+struct HealthParams {
+    level: Option<String>,
+}
+
+/// Plain "ok" for liveness checks. `level=full` (authenticated) adds build
+/// provenance and runtime facts.
+async fn handle_health(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HealthParams>,
+) -> Result<axum::response::Response, (StatusCode, String)> {
+    match params.level.as_deref() {
+        None | Some("basic") => Ok("ok".into_response()),
+        Some("full") => {
+            check_auth(&headers, &app)?;
+            Ok(Json(json!({
+                "status": "ok",
+                "build": BuildInfo::get(),
+                "runtime": app.shared.runtime_info(),
+            }))
+            .into_response())
+        }
+        Some(other) => Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown health level '{other}' (expected basic or full)"),
+        )),
+    }
 }
 
 // ─── GET /status ──────────────────────────────────────────────
@@ -242,14 +498,486 @@ async fn handle_status(
     State(app): State<AppState>,
     headers: HeaderMap,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
-    check_auth(&headers, &app.config)?;
+    check_auth(&headers, &app)?;
 
     let status = BridgeStatusResponse {
         connected_clients: app.shared.connected_client_count().await,
         pending_calls: app.shared.pending_call_count().await,
-        log_buffer_size: app.shared.log_buffer_size().await,
+        log_buffer_size: app.shared.log_buffer_size(),
         playtest_active: app.shared.is_playtest_active().await,
+        redactions: app.shared.redaction_counts(),
+        idle: app.shared.is_idle(),
+        idle_secs: app.shared.idle_for().as_secs(),
+        log_archive: app.shared.log_archive().map(|a| a.status()),
+        event_order: app.shared.event_order().await.stats,
+        queue: app.shared.queue_depths().await,
     };
 
     Ok(Json(status))
 }
+
+// ─── GET /clients ─────────────────────────────────────────────
+
+/// Connected clients with their rolling traffic, and the combined HttpService budget.
+async fn handle_clients(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_auth(&headers, &app)?;
+
+    let last_polls: std::collections::HashMap<String, chrono::DateTime<chrono::Utc>> = app
+        .shared
+        .client_info()
+        .await
+        .into_iter()
+        .map(|(id, _, last_poll, _)| (id, last_poll))
+        .collect();
+    let clients: Vec<_> = app
+        .shared
+        .traffic_reports()
+        .await
+        .into_iter()
+        .map(|(id, version, is_bridge, traffic)| {
+            let last_poll_secs_ago = last_polls
+                .get(&id)
+                .map(|t| app.shared.clock().age(*t).as_secs());
+            json!({
+                "clientId": id,
+                "version": version,
+                "isBridge": is_bridge,
+                "lastPollSecsAgo": last_poll_secs_ago,
+                "traffic": traffic,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "clients": clients,
+        "httpBudget": app.shared.http_budget(),
+    })))
+}
+
+// ─── GET /metrics ─────────────────────────────────────────────
+
+/// Bridge traffic in Prometheus text format.
+async fn handle_metrics(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_auth(&headers, &app)?;
+
+    let clients = app.shared.traffic_reports().await;
+    let body = crate::traffic::render_metrics(&app.shared.http_budget(), &clients, &app.shared.tool_calls_by_client());
+    Ok(([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body))
+}
+
+// ─── GET /debug/routing ───────────────────────────────────────
+
+/// Which client each tool would be sent to now, as for `studio-list_tools_with_routing`.
+async fn handle_debug_routing(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_auth(&headers, &app)?;
+
+    if !app.shared.debug_endpoints_enabled() {
+        return Err((StatusCode::NOT_FOUND, "Debug endpoints are disabled (set YIPPIE_DEBUG_ENDPOINTS=1)".into()));
+    }
+    let tools: Vec<String> = crate::mcp_stdio::tool_definitions().into_iter().map(|t| t.name).collect();
+    Ok(Json(app.shared.routing_report(&tools).await))
+}
+
+// ─── GET /archive/search?q=... ────────────────────────────────
+
+#[derive(Deserialize)]
+struct ArchiveSearchParams {
+    q: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    /// Comma-separated levels
+    level: Option<String>,
+    session: Option<String>,
+    limit: Option<u64>,
+}
+
+async fn handle_archive_search(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ArchiveSearchParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_auth(&headers, &app)?;
+
+    let archive = app.shared.log_archive().cloned().ok_or((
+        StatusCode::NOT_FOUND,
+        "Log archive is disabled (set YIPPIE_LOG_ARCHIVE=1)".to_string(),
+    ))?;
+    let args = json!({
+        "query": params.q,
+        "from": params.from,
+        "to": params.to,
+        "levels": params.level.map(|l| l.split(',').map(str::trim).map(String::from).collect::<Vec<_>>()),
+        "sessionId": params.session,
+        "limit": params.limit,
+    });
+    let query = ArchiveQuery::from_args(&args).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let hits = archive
+        .search_blocking(query)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(json!({ "hits": hits, "status": archive.status() })))
+}
+
+// ─── GET/POST /admin/profile ──────────────────────────────────
+
+/// The active configuration profile and the ones that can be selected.
+async fn handle_admin_profile(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    check_auth(&headers, &app)?;
+    Ok(Json(json!({
+        "active": app.shared.settings().to_json(),
+        "profiles": profiles::list(app.shared.profiles()),
+    })))
+}
+
+#[derive(Deserialize)]
+struct AdminProfileBody {
+    name: String,
+}
+
+/// Switch the configuration profile. Takes `{"name": ...}` and a configured token.
+/// Leaving a read-only profile for one that allows writes is refused here: that is
+/// left to studio-set_profile, which the MCP client asks the user to approve, or to a
+/// restart with another YIPPIE_PROFILE.
+async fn handle_admin_set_profile(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<AdminProfileBody>, JsonRejection>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    require_token(&app, "POST /admin/profile")?;
+    check_auth(&headers, &app)?;
+    let params = json_body(body, "{\"name\": ...}")?;
+    let mut left_read_only = false;
+    let (previous, active) = app
+        .shared
+        .switch_profile(&params.name, |current, next| {
+            profiles::keep_read_only(current, next).inspect_err(|_| left_read_only = true)
+        })
+        .map_err(|e| (if left_read_only { StatusCode::FORBIDDEN } else { StatusCode::BAD_REQUEST }, e))?;
+    Ok(Json(json!({ "previous": previous.name(), "active": active.to_json() })))
+}
+
+// ─── POST /admin/export ───────────────────────────────────────
+
+/// No options yet; the body is still required so the export can't be triggered by a
+/// simple cross-origin POST.
+#[derive(Deserialize)]
+struct AdminExportBody {}
+
+/// Export the capture folder to `exports/state-<time>.tar.gz` inside it (see
+/// `state_export.rs`). Takes `{}` and a configured token. Returns the archive path and
+/// its manifest.
+async fn handle_admin_export(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<AdminExportBody>, JsonRejection>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    require_token(&app, "/admin/export")?;
+    check_auth(&headers, &app)?;
+    let AdminExportBody {} = json_body(body, "{}")?;
+
+    let capture_dir = app.config.capture_dir.clone();
+    let name = format!("state-{}.tar.gz", app.shared.clock().now().format("%Y%m%d-%H%M%S"));
+    let exported_by = format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    let (path, manifest) = tokio::task::spawn_blocking(move || {
+        let dir = capture_dir.join(state_export::EXPORTS_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(name);
+        let manifest = state_export::export(&capture_dir, &path, &exported_by)?;
+        anyhow::Ok((path, manifest))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+
+    tracing::info!(path = %path.display(), "State exported");
+    Ok(Json(json!({ "path": path, "manifest": manifest })))
+}
+
+// ─── POST /admin/import ───────────────────────────────────────
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AdminImportBody {
+    /// Archive path relative to the capture folder, e.g. `exports/state-....tar.gz`.
+    path: String,
+    captures: Option<String>,
+    replays: Option<String>,
+    logs: Option<String>,
+    /// Check the archive and report conflicts without importing.
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Import a state export that was copied into the capture folder. Conflict policies
+/// (`skip`, `overwrite`, `merge`) are per category and default to `merge`. Takes the
+/// options as a JSON body and a configured token.
+async fn handle_admin_import(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<AdminImportBody>, JsonRejection>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    require_token(&app, "/admin/import")?;
+    check_auth(&headers, &app)?;
+    let params = json_body(body, "{\"path\": ...}")?;
+
+    let mut policy = ImportPolicy::default();
+    for (category, raw) in [
+        (Category::Captures, &params.captures),
+        (Category::Replays, &params.replays),
+        (Category::Logs, &params.logs),
+    ] {
+        if let Some(raw) = raw {
+            let conflict = Conflict::parse(raw).map_err(|e| (StatusCode::BAD_REQUEST, format!("{}: {e}", category.name())))?;
+            policy.set(category, conflict);
+        }
+    }
+    let capture_dir = app.config.capture_dir.clone();
+    let relative = std::path::Path::new(&params.path);
+    if params.path.is_empty() || !relative.components().all(|c| matches!(c, std::path::Component::Normal(_))) {
+        return Err((StatusCode::BAD_REQUEST, format!("'{}' must be a relative path inside the capture folder", params.path)));
+    }
+    let archive = capture_dir.join(relative);
+    let dry_run = params.dry_run;
+
+    let body = tokio::task::spawn_blocking(move || {
+        let staged = Staged::extract(&capture_dir, &archive)?;
+        if dry_run {
+            return anyhow::Ok(json!({ "manifest": staged.manifest, "conflicts": staged.conflicts()? }));
+        }
+        let report = staged.apply(&policy);
+        anyhow::Ok(serde_json::to_value(report)?)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("{e:#}")))?;
+
+    if !dry_run {
+        tracing::info!(path = %params.path, "State imported");
+    }
+    Ok(Json(body))
+}
+
+// ─── POST /shutdown ───────────────────────────────────────────
+
+/// Value of `confirm` that a shutdown request must carry.
+const SHUTDOWN_CONFIRMATION: &str = "shutdown";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ShutdownBody {
+    confirm: Option<String>,
+    /// Names the supervisor or script in the log; the User-Agent otherwise.
+    requested_by: Option<String>,
+    reason: Option<String>,
+}
+
+/// Ask the server to shut down gracefully: plugin calls in flight get up to
+/// `SHUTDOWN_DRAIN_TIMEOUT` to finish, what the session opened in Studio is closed,
+/// logs are synced and the process exits. Takes `{"confirm": "shutdown"}` as JSON so a
+/// stray POST can't stop it, and only with a token configured. Answers 202 before the
+/// shutdown starts.
+async fn handle_shutdown(
+    State(app): State<AppState>,
+    headers: HeaderMap,
+    body: Result<Json<ShutdownBody>, JsonRejection>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    require_token(&app, "/shutdown")?;
+    check_auth(&headers, &app)?;
+    let body = json_body(body, "{\"confirm\": \"shutdown\"}")?;
+    if body.confirm.as_deref() != Some(SHUTDOWN_CONFIRMATION) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Shutdown not confirmed: send \"confirm\": \"{SHUTDOWN_CONFIRMATION}\""),
+        ));
+    }
+
+    let user_agent = headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok());
+    let requested_by = body
+        .requested_by
+        .as_deref()
+        .or(user_agent)
+        .unwrap_or("unknown")
+        .to_string();
+    let reason = body.reason.unwrap_or_default();
+    let pending = app.shared.pending_call_count().await;
+    let first = app.shared.request_shutdown(requested_by.clone());
+    if first {
+        tracing::warn!(
+            requested_by = %requested_by,
+            user_agent = user_agent.unwrap_or(""),
+            reason = %reason,
+            pending_calls = pending,
+            "Shutdown requested over the bridge"
+        );
+    }
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "shuttingDown": true,
+            "alreadyRequested": !first,
+            "pendingCalls": pending,
+            "drainTimeoutSecs": SHUTDOWN_DRAIN_TIMEOUT.as_secs(),
+        })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AuthToken;
+
+    fn log(seq: u64) -> BridgeEvent {
+        BridgeEvent { event_type: "studio-log".into(), data: json!({ "message": format!("line {seq}") }), seq: Some(seq) }
+    }
+
+    fn messages(state: &SharedState) -> Vec<String> {
+        state.get_logs(0, 100).iter().map(|e| e.message.clone()).collect()
+    }
+
+    #[tokio::test]
+    async fn pushes_handled_out_of_order_are_applied_in_order() {
+        let state = SharedState::for_tests(Config::default());
+        ingest_events(&state, "c1", vec![log(3), log(4)]).await;
+        ingest_events(&state, "c1", vec![log(1), log(1)]).await;
+        assert_eq!(messages(&state), ["line 1"]);
+        ingest_events(&state, "c1", vec![log(2)]).await;
+        assert_eq!(messages(&state), ["line 1", "line 2", "line 3", "line 4"]);
+        let stats = state.event_order().await.stats;
+        assert_eq!((stats.reordered, stats.late, stats.gaps), (2, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn a_gap_that_stays_open_is_flushed_after_the_timeout() {
+        let state = SharedState::for_tests(Config::default());
+        ingest_events(&state, "c1", vec![log(1), log(3), log(4)]).await;
+        // Another client's numbering is its own
+        ingest_events(&state, "c2", vec![log(1)]).await;
+        assert_eq!(messages(&state), ["line 1", "line 1"]);
+
+        tokio::time::sleep(GAP_TIMEOUT / 2).await;
+        assert_eq!(messages(&state).len(), 2, "flushed before the timeout");
+        tokio::time::sleep(GAP_TIMEOUT).await;
+        assert_eq!(messages(&state), ["line 1", "line 1", "line 3", "line 4"]);
+        let ordering = state.event_order().await;
+        assert_eq!((ordering.stats.gaps, ordering.stats.missing), (1, 1));
+        assert!(!ordering.clients["c1"].gap_timer_armed);
+        drop(ordering);
+
+        // The missing event turning up now is dropped
+        ingest_events(&state, "c1", vec![log(2)]).await;
+        assert_eq!(messages(&state).len(), 4);
+        assert_eq!(state.event_order().await.stats.late, 1);
+    }
+
+    fn app_state(config: Config) -> AppState {
+        let shared = SharedState::for_tests(config.clone());
+        AppState { shared, config, warned_fields: Default::default() }
+    }
+
+    #[tokio::test]
+    async fn the_profile_endpoint_needs_a_token_and_cannot_leave_read_only() {
+        let profiles = [
+            ("cautious".to_string(), profiles::Profile { read_only: Some(true), ..Default::default() }),
+            ("strict".to_string(), profiles::Profile { read_only: Some(true), tool_timeout: Some(Duration::from_secs(15)), ..Default::default() }),
+        ];
+        let switch = |app: &AppState, name: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+            let body = Ok(Json(AdminProfileBody { name: name.into() }));
+            handle_admin_set_profile(State(app.clone()), headers, body)
+        };
+
+        let open = app_state(Config { profiles: profiles.clone().into(), ..Config::default() });
+        assert_eq!(switch(&open, "cautious").await.err().unwrap().0, StatusCode::FORBIDDEN);
+        assert_eq!(open.shared.settings().name(), "default");
+
+        let config = Config { token: Some(AuthToken::new("secret")), profiles: profiles.into(), profile: Some("cautious".into()), ..Config::default() };
+        let app = app_state(config);
+        // Read-only to read-only is fine, leaving read-only is not
+        assert!(switch(&app, "strict").await.is_ok());
+        let (status, message) = switch(&app, "default").await.err().unwrap();
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert!(message.contains("studio-set_profile"), "{message}");
+        assert_eq!(app.shared.settings().name(), "strict");
+        assert_eq!(switch(&app, "nonexistent").await.err().unwrap().0, StatusCode::BAD_REQUEST);
+    }
+
+    /// Wait for the refresh task to queue studio-tree_snapshot for `edit`, answer it
+    /// with `entries` at `seq` and return how many snapshot requests there were.
+    async fn answer_snapshots(state: &SharedState, seq: u64, entries: Value) -> usize {
+        let mut requests = state.drain_outbound("edit").await;
+        for _ in 0..100 {
+            if !requests.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            requests = state.drain_outbound("edit").await;
+        }
+        for request in &requests {
+            assert_eq!(request.tool_name, "studio-tree_snapshot");
+            assert_eq!(request.arguments, json!({ "roots": ["Workspace"], "watch": true }));
+            let snapshot = json!({ "clientId": "edit", "seq": seq, "entries": entries });
+            let response = BridgeToolResponse { request_id: request.request_id.clone(), success: true, result: Some(snapshot), error: None };
+            state.resolve_pending(&request.request_id, response).await;
+        }
+        requests.len()
+    }
+
+    async fn index_status(state: &SharedState) -> Value {
+        // The refresh task applies the snapshot after the response is resolved
+        for _ in 0..100 {
+            let status = state.path_index().unwrap().status(state.clock());
+            if status["refreshing"] == false {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        panic!("the path index refresh did not finish");
+    }
+
+    #[tokio::test]
+    async fn a_tree_delta_gap_refreshes_the_path_index() {
+        let config = Config { tree_index: true, tree_roots: vec!["Workspace".into()], ..Config::default() };
+        let state = SharedState::for_tests(config);
+        let tree_delta = |seq: u64, data: Value| {
+            let mut data = data;
+            data["seq"] = json!(seq);
+            BridgeEvent { event_type: "studio-tree_delta".into(), data, seq: None }
+        };
+
+        // Registering starts a snapshot
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        assert_eq!(answer_snapshots(&state, 5, json!([{ "path": "Workspace.Map", "class": "Model" }])).await, 1);
+        assert_eq!(index_status(&state).await["lastSeq"], 5);
+
+        ingest_events(&state, "edit", vec![tree_delta(6, json!({ "added": [{ "path": "Workspace.Map.Wall", "class": "Part" }] }))]).await;
+        assert_eq!(state.path_index().unwrap().class_of("Workspace.Map.Wall"), Some("Part"));
+        assert_eq!(state.drain_outbound("edit").await.len(), 0);
+
+        // Deltas 7 and 8 went missing
+        ingest_events(&state, "edit", vec![tree_delta(9, json!({ "removed": ["Workspace.Map"] }))]).await;
+        assert_eq!(state.path_index().unwrap().class_of("Workspace.Map"), Some("Model"));
+        let entries = json!([{ "path": "Workspace.Level", "class": "Model" }]);
+        assert_eq!(answer_snapshots(&state, 9, entries).await, 1);
+        let status = index_status(&state).await;
+        assert_eq!((status["paths"].clone(), status["lastSeq"].clone(), status["gaps"].clone()), (json!(1), json!(9), json!(1)));
+        assert_eq!(state.path_index().unwrap().class_of("Workspace.Level"), Some("Model"));
+
+        // Malformed deltas are dropped without touching the index
+        ingest_events(&state, "edit", vec![BridgeEvent { event_type: "studio-tree_delta".into(), data: json!({ "added": 1 }), seq: None }]).await;
+        assert_eq!(index_status(&state).await["lastSeq"], 9);
+    }
+}
@@ -0,0 +1,149 @@
+//! Build provenance embedded by `build.rs` and runtime facts about how this server
+//! was launched, for bug reports (`initialize` serverInfo, studio-status,
+//! `GET /health?level=full`).
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::providers::Clock;
+
+fn non_empty(value: &'static str) -> Option<&'static str> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// What was built, and from which source.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: &'static str,
+    /// Short commit hash; `None` when built outside a git checkout.
+    pub git_commit: Option<&'static str>,
+    /// Tracked files had uncommitted changes at build time.
+    pub git_dirty: Option<bool>,
+    /// RFC 3339 time the build script ran (or `SOURCE_DATE_EPOCH`).
+    pub built_at: Option<String>,
+    pub target: Option<&'static str>,
+    pub rustc: Option<&'static str>,
+    pub profile: Option<&'static str>,
+}
+
+impl BuildInfo {
+    pub fn get() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_commit: non_empty(env!("YIPPIE_GIT_COMMIT")),
+            git_dirty: env!("YIPPIE_GIT_DIRTY").parse().ok(),
+            built_at: env!("YIPPIE_BUILT_AT_UNIX")
+                .parse()
+                .ok()
+                .filter(|&secs| secs > 0)
+                .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                .map(|t| t.to_rfc3339()),
+            target: non_empty(env!("YIPPIE_TARGET")),
+            rustc: non_empty(env!("YIPPIE_RUSTC_VERSION")),
+            profile: non_empty(env!("YIPPIE_PROFILE")),
+        }
+    }
+}
+
+/// How this process was started. Fixed at startup.
+#[derive(Debug, Clone, Default)]
+pub struct LaunchInfo {
+    pub mock_studio: bool,
+    /// `--status-fd` / `--status-file` was given, i.e. a supervisor launched us.
+    pub status_report: bool,
+}
+
+/// Launch mode, transports and optional features in effect.
+/// The process id is left out in deterministic mode, since it differs on every run.
+pub fn runtime_info(config: &Config, launch: &LaunchInfo, started_at: std::time::Instant, clock: &dyn Clock) -> Value {
+    json!({
+        "launch": if launch.status_report { "supervised-stdio" } else { "stdio" },
+        "mockStudio": launch.mock_studio,
+        "transports": ["stdio", "http-bridge"],
+        "bridgePort": config.port,
+        "configSource": "environment",
+        "features": {
+            "logArchive": config.log_archive,
+            "treeIndex": config.tree_index,
+            "responseCache": config.cache_ttl.is_some(),
+            "idleCleanup": config.idle_timeout.is_some(),
+            "osCapture": !config.disable_os_capture,
+            "minifyCode": config.minify_code,
+            "auth": config.token.is_some(),
+            "experimentalTools": config.experimental_tools,
+            "alerts": !config.alert_rules.is_empty(),
+            "deterministic": config.deterministic_seed.is_some(),
+        },
+        "logCode": config.log_code.as_str(),
+        "pid": config.deterministic_seed.is_none().then(std::process::id),
+        "uptimeSecs": clock.elapsed(started_at).as_secs(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AuthToken;
+    use crate::providers::LogicalClock;
+
+    #[test]
+    fn build_info_serializes_every_field_in_camel_case() {
+        let build = serde_json::to_value(BuildInfo::get()).unwrap();
+        let keys: Vec<&str> = build.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["builtAt", "gitCommit", "gitDirty", "profile", "rustc", "target", "version"]);
+        assert_eq!(build["version"], env!("CARGO_PKG_VERSION"));
+        // Cargo always tells the build script these
+        assert!(build["target"].as_str().is_some_and(|t| !t.is_empty()), "{build}");
+        assert!(build["profile"].as_str().is_some_and(|p| !p.is_empty()), "{build}");
+        if let Some(built_at) = build["builtAt"].as_str() {
+            chrono::DateTime::parse_from_rfc3339(built_at).unwrap();
+        }
+    }
+
+    #[test]
+    fn runtime_info_of_a_deterministic_stdio_launch() {
+        let config = Config { deterministic_seed: Some(7), ..Config::default() };
+        let launch = LaunchInfo { mock_studio: true, status_report: false };
+        let runtime = runtime_info(&config, &launch, std::time::Instant::now(), &LogicalClock::default());
+        assert_eq!(
+            runtime,
+            json!({
+                "launch": "stdio",
+                "mockStudio": true,
+                "transports": ["stdio", "http-bridge"],
+                "bridgePort": config.port,
+                "configSource": "environment",
+                "features": {
+                    "logArchive": false,
+                    "treeIndex": false,
+                    "responseCache": config.cache_ttl.is_some(),
+                    "idleCleanup": config.idle_timeout.is_some(),
+                    "osCapture": true,
+                    "minifyCode": false,
+                    "auth": false,
+                    "experimentalTools": config.experimental_tools,
+                    "alerts": false,
+                    "deterministic": true,
+                },
+                "logCode": config.log_code.as_str(),
+                "pid": null,
+                "uptimeSecs": 0,
+            })
+        );
+    }
+
+    #[test]
+    fn runtime_info_reports_a_supervised_launch_and_its_pid() {
+        let config = Config { token: Some(AuthToken::new("secret")), tree_index: true, ..Config::default() };
+        let launch = LaunchInfo { mock_studio: false, status_report: true };
+        let runtime = runtime_info(&config, &launch, std::time::Instant::now(), &LogicalClock::default());
+        assert_eq!(runtime["launch"], "supervised-stdio");
+        assert_eq!(runtime["pid"], std::process::id());
+        assert_eq!(runtime["features"]["auth"], true);
+        assert_eq!(runtime["features"]["treeIndex"], true);
+        // The token itself never shows up
+        assert!(!runtime.to_string().contains("secret"));
+    }
+}
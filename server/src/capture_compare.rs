@@ -0,0 +1,442 @@
+//! `studio-capture_compare`: pixel diff of two PNG captures for visual regression checks.
+//!
+//! Both images are decoded to 8-bit RGB. A pixel counts as changed when any channel
+//! differs by more than `threshold`, which absorbs compression noise and anti-aliasing.
+//! Changed pixels are bucketed into `REGION_CELL` squares and touching squares are
+//! merged, so changes a few pixels apart come back as one bounding box instead of
+//! hundreds. The highlighted diff (the second image dimmed to grey, changed pixels
+//! red, region outlines yellow) is saved under `diffs/` and returned inline when it is
+//! small enough.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::types::CaptureMetadata;
+
+pub const DIFF_DIR: &str = "diffs";
+
+/// Stands for the newest image capture other than the one it is compared with.
+pub const LATEST: &str = "latest";
+
+/// Capture types `latest` picks from.
+const IMAGE_CAPTURE_TYPES: [&str; 2] = ["screenshot", "viewport_render"];
+
+pub const DEFAULT_THRESHOLD: u8 = 16;
+
+/// Larger files are refused before reading; larger images after the PNG header.
+const MAX_FILE_BYTES: u64 = 64 * 1024 * 1024;
+const MAX_PIXELS: u64 = 4096 * 4096;
+
+/// Side of the squares changed pixels are clustered by.
+const REGION_CELL: u32 = 8;
+pub const MAX_REGIONS: usize = 50;
+
+/// The diff image is left out of the result above this size; the artifact has it.
+pub const MAX_INLINE_DIFF_BYTES: usize = 1024 * 1024;
+
+const CHANGED_COLOR: [u8; 3] = [255, 0, 0];
+const OUTLINE_COLOR: [u8; 3] = [255, 220, 0];
+
+/// Checked arguments.
+#[derive(Debug, Clone)]
+pub struct CompareRequest {
+    pub before: String,
+    pub after: String,
+    pub threshold: u8,
+    /// Resize the second image to the first's size instead of failing on a mismatch.
+    pub scale: bool,
+    pub tag: Option<String>,
+}
+
+impl CompareRequest {
+    pub fn parse(arguments: &Value) -> Result<Self, String> {
+        let id = |name: &str| arguments.get(name).and_then(|v| v.as_str()).filter(|s| !s.is_empty()).map(String::from);
+        let before = id("before").ok_or("Missing 'before' argument (a capture id or \"latest\")")?;
+        let after = id("after").unwrap_or_else(|| LATEST.to_string());
+        if before == LATEST && after == LATEST {
+            return Err("Only one of 'before' and 'after' can be \"latest\"".into());
+        }
+        if before == after {
+            return Err(format!("'before' and 'after' are the same capture '{before}'"));
+        }
+        let threshold = match arguments.get("threshold").filter(|v| !v.is_null()) {
+            None => DEFAULT_THRESHOLD,
+            Some(v) => v
+                .as_u64()
+                .and_then(|t| u8::try_from(t).ok())
+                .ok_or("'threshold' must be a whole number from 0 to 255")?,
+        };
+        let scale = match arguments.get("scale").filter(|v| !v.is_null()) {
+            None => false,
+            Some(v) => v.as_bool().ok_or("'scale' must be a boolean")?,
+        };
+        Ok(Self {
+            before,
+            after,
+            threshold,
+            scale,
+            tag: arguments.get("tag").and_then(|v| v.as_str()).map(String::from),
+        })
+    }
+
+    /// Find both captures in the index, resolving `latest`.
+    pub fn resolve<'a>(&self, entries: &'a [CaptureMetadata]) -> Result<(&'a CaptureMetadata, &'a CaptureMetadata), String> {
+        let find = |id: &str, other: &str| {
+            if id == LATEST {
+                entries
+                    .iter()
+                    .rev()
+                    .find(|e| e.id != other && IMAGE_CAPTURE_TYPES.contains(&e.capture_type.as_str()))
+                    .ok_or_else(|| "There is no screenshot or viewport render to use as \"latest\"".to_string())
+            } else {
+                entries
+                    .iter()
+                    .find(|e| e.id == id)
+                    .ok_or_else(|| format!("Unknown capture id '{id}'"))
+            }
+        };
+        Ok((find(&self.before, &self.after)?, find(&self.after, &self.before)?))
+    }
+}
+
+/// An 8-bit RGB image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub rgb: Vec<u8>,
+}
+
+impl Image {
+    /// Read the PNG file of a capture.
+    pub fn load(capture: &CaptureMetadata) -> Result<Self, String> {
+        let Some(path) = capture.file_path.as_deref() else {
+            return Err(format!("Capture '{}' has no file", capture.id));
+        };
+        let path = Path::new(path);
+        let size = std::fs::metadata(path)
+            .map_err(|e| format!("Can't read capture '{}' ({}): {e}", capture.id, path.display()))?
+            .len();
+        if size > MAX_FILE_BYTES {
+            return Err(format!("Capture '{}' is larger than {MAX_FILE_BYTES} bytes", capture.id));
+        }
+        let data = std::fs::read(path).map_err(|e| format!("Can't read capture '{}' ({}): {e}", capture.id, path.display()))?;
+        Self::decode(&data).map_err(|e| format!("Capture '{}' is not a readable PNG image: {e}", capture.id))
+    }
+
+    /// Decode a PNG of any colour type and bit depth; alpha is dropped.
+    pub fn decode(data: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(data);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+        let (width, height) = (reader.info().width, reader.info().height);
+        if u64::from(width) * u64::from(height) > MAX_PIXELS {
+            return Err(format!("{width}x{height} is more than {MAX_PIXELS} pixels"));
+        }
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let frame = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+        let pixels = &buffer[..frame.buffer_size()];
+        let rgb = match frame.color_type {
+            png::ColorType::Rgb => pixels.to_vec(),
+            png::ColorType::Rgba => pixels.chunks_exact(4).flat_map(|p| [p[0], p[1], p[2]]).collect(),
+            png::ColorType::Grayscale => pixels.iter().flat_map(|&g| [g, g, g]).collect(),
+            png::ColorType::GrayscaleAlpha => pixels.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0]]).collect(),
+            png::ColorType::Indexed => return Err("palette was not expanded".into()),
+        };
+        Ok(Self { width, height, rgb })
+    }
+
+    /// Nearest-neighbour resize.
+    pub fn scaled(&self, width: u32, height: u32) -> Self {
+        let mut rgb = Vec::with_capacity(width as usize * height as usize * 3);
+        for y in 0..height {
+            let source_y = (u64::from(y) * u64::from(self.height) / u64::from(height)) as usize;
+            for x in 0..width {
+                let source_x = (u64::from(x) * u64::from(self.width) / u64::from(width)) as usize;
+                let i = (source_y * self.width as usize + source_x) * 3;
+                rgb.extend_from_slice(&self.rgb[i..i + 3]);
+            }
+        }
+        Self { width, height, rgb }
+    }
+}
+
+/// Bounding box of one cluster of changed pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub changed_pixels: u64,
+}
+
+/// Result of comparing two images of the same size.
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub width: u32,
+    pub height: u32,
+    pub changed_pixels: u64,
+    /// Largest channel difference seen, changed or not.
+    pub max_channel_delta: u8,
+    /// Largest first, at most `MAX_REGIONS`.
+    pub regions: Vec<Region>,
+    pub region_count: usize,
+    /// Row-major, one entry per pixel.
+    changed: Vec<bool>,
+}
+
+impl Diff {
+    pub fn compute(before: &Image, after: &Image, threshold: u8) -> Self {
+        assert_eq!((before.width, before.height), (after.width, after.height));
+        let mut max_channel_delta = 0;
+        let changed: Vec<bool> = before
+            .rgb
+            .chunks_exact(3)
+            .zip(after.rgb.chunks_exact(3))
+            .map(|(a, b)| {
+                let delta = (0..3).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0);
+                max_channel_delta = max_channel_delta.max(delta);
+                delta > threshold
+            })
+            .collect();
+        let changed_pixels = changed.iter().filter(|&&c| c).count() as u64;
+        let mut regions = cluster(&changed, before.width, before.height);
+        let region_count = regions.len();
+        regions.truncate(MAX_REGIONS);
+        Self {
+            width: before.width,
+            height: before.height,
+            changed_pixels,
+            max_channel_delta,
+            regions,
+            region_count,
+            changed,
+        }
+    }
+
+    pub fn total_pixels(&self) -> u64 {
+        u64::from(self.width) * u64::from(self.height)
+    }
+
+    /// Share of changed pixels in percent, to three decimals.
+    pub fn changed_percent(&self) -> f64 {
+        if self.total_pixels() == 0 {
+            return 0.0;
+        }
+        (self.changed_pixels as f64 * 100_000.0 / self.total_pixels() as f64).round() / 1000.0
+    }
+
+    /// `after` dimmed to grey, with changed pixels in red and the listed regions
+    /// outlined in yellow.
+    pub fn highlight(&self, after: &Image) -> Image {
+        let mut rgb: Vec<u8> = after
+            .rgb
+            .chunks_exact(3)
+            .zip(&self.changed)
+            .flat_map(|(p, &changed)| {
+                if changed {
+                    CHANGED_COLOR
+                } else {
+                    let luma = ((u32::from(p[0]) * 299 + u32::from(p[1]) * 587 + u32::from(p[2]) * 114) / 1000) as u8;
+                    let dimmed = luma / 3 + 96;
+                    [dimmed; 3]
+                }
+            })
+            .collect();
+        let width = self.width as usize;
+        let mut paint = |x: u32, y: u32| {
+            let i = (y as usize * width + x as usize) * 3;
+            rgb[i..i + 3].copy_from_slice(&OUTLINE_COLOR);
+        };
+        // Just outside the box where there is room, so small regions stay visible
+        for region in &self.regions {
+            let (left, top) = (region.x.saturating_sub(1), region.y.saturating_sub(1));
+            let right = (region.x + region.width).min(self.width - 1);
+            let bottom = (region.y + region.height).min(self.height - 1);
+            for x in left..=right {
+                paint(x, top);
+                paint(x, bottom);
+            }
+            for y in top..=bottom {
+                paint(left, y);
+                paint(right, y);
+            }
+        }
+        Image { width: self.width, height: self.height, rgb }
+    }
+}
+
+/// Group changed pixels into regions: cells of `REGION_CELL` pixels with a change are
+/// joined with their eight neighbours, and each group's box is the exact extent of its
+/// changed pixels.
+fn cluster(changed: &[bool], width: u32, height: u32) -> Vec<Region> {
+    #[derive(Clone, Copy)]
+    struct Cell {
+        count: u64,
+        min: (u32, u32),
+        max: (u32, u32),
+    }
+    let cols = width.div_ceil(REGION_CELL) as usize;
+    let rows = height.div_ceil(REGION_CELL) as usize;
+    let mut cells: Vec<Option<Cell>> = vec![None; cols * rows];
+    for (i, _) in changed.iter().enumerate().filter(|(_, &c)| c) {
+        let (x, y) = ((i % width as usize) as u32, (i / width as usize) as u32);
+        let index = (y / REGION_CELL) as usize * cols + (x / REGION_CELL) as usize;
+        let cell = cells[index].get_or_insert(Cell { count: 0, min: (x, y), max: (x, y) });
+        cell.count += 1;
+        cell.min = (cell.min.0.min(x), cell.min.1.min(y));
+        cell.max = (cell.max.0.max(x), cell.max.1.max(y));
+    }
+
+    let mut seen = vec![false; cells.len()];
+    let mut regions = Vec::new();
+    for start in 0..cells.len() {
+        if seen[start] || cells[start].is_none() {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let mut group: Option<Cell> = None;
+        while let Some(index) = stack.pop() {
+            let Some(cell) = cells[index] else { continue };
+            group = Some(match group {
+                None => cell,
+                Some(g) => Cell {
+                    count: g.count + cell.count,
+                    min: (g.min.0.min(cell.min.0), g.min.1.min(cell.min.1)),
+                    max: (g.max.0.max(cell.max.0), g.max.1.max(cell.max.1)),
+                },
+            });
+            let (col, row) = ((index % cols) as isize, (index / cols) as isize);
+            for (dc, dr) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let (c, r) = (col + dc, row + dr);
+                if c < 0 || r < 0 || c >= cols as isize || r >= rows as isize {
+                    continue;
+                }
+                let neighbour = r as usize * cols + c as usize;
+                if !seen[neighbour] && cells[neighbour].is_some() {
+                    seen[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        if let Some(g) = group {
+            regions.push(Region {
+                x: g.min.0,
+                y: g.min.1,
+                width: g.max.0 - g.min.0 + 1,
+                height: g.max.1 - g.min.1 + 1,
+                changed_pixels: g.count,
+            });
+        }
+    }
+    regions.sort_by(|a, b| b.changed_pixels.cmp(&a.changed_pixels).then((a.y, a.x).cmp(&(b.y, b.x))));
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::viewport_render::encode_png;
+
+    fn solid(width: u32, height: u32, color: [u8; 3]) -> Image {
+        Image { width, height, rgb: color.repeat(width as usize * height as usize) }
+    }
+
+    fn set(image: &mut Image, x: u32, y: u32, color: [u8; 3]) {
+        let i = (y * image.width + x) as usize * 3;
+        image.rgb[i..i + 3].copy_from_slice(&color);
+    }
+
+    #[test]
+    fn identical_images_have_no_changes() {
+        let image = solid(32, 16, [40, 80, 120]);
+        let diff = Diff::compute(&image, &image.clone(), 0);
+        assert_eq!((diff.width, diff.height, diff.total_pixels()), (32, 16, 512));
+        assert_eq!(diff.changed_pixels, 0);
+        assert_eq!(diff.changed_percent(), 0.0);
+        assert_eq!(diff.max_channel_delta, 0);
+        assert!(diff.regions.is_empty());
+        assert_eq!(diff.region_count, 0);
+    }
+
+    #[test]
+    fn fully_different_images_change_everywhere() {
+        let diff = Diff::compute(&solid(16, 16, [0, 0, 0]), &solid(16, 16, [255, 255, 255]), DEFAULT_THRESHOLD);
+        assert_eq!(diff.changed_pixels, 256);
+        assert_eq!(diff.changed_percent(), 100.0);
+        assert_eq!(diff.max_channel_delta, 255);
+        assert_eq!(diff.regions, [Region { x: 0, y: 0, width: 16, height: 16, changed_pixels: 256 }]);
+        assert!(diff.highlight(&solid(16, 16, [255, 255, 255])).rgb.chunks_exact(3).all(|p| p == CHANGED_COLOR || p == OUTLINE_COLOR));
+    }
+
+    #[test]
+    fn a_single_changed_pixel_is_one_region() {
+        let before = solid(20, 10, [100, 100, 100]);
+        let mut after = before.clone();
+        set(&mut after, 13, 4, [100, 140, 100]);
+        let diff = Diff::compute(&before, &after, DEFAULT_THRESHOLD);
+        assert_eq!(diff.changed_pixels, 1);
+        assert_eq!(diff.changed_percent(), 0.5);
+        assert_eq!(diff.max_channel_delta, 40);
+        assert_eq!(diff.regions, [Region { x: 13, y: 4, width: 1, height: 1, changed_pixels: 1 }]);
+        let highlighted = diff.highlight(&after);
+        let i = (4 * 20 + 13) * 3;
+        assert_eq!(highlighted.rgb[i..i + 3], CHANGED_COLOR);
+
+        // Within the threshold it doesn't count, though the delta is still reported
+        set(&mut after, 13, 4, [100, 110, 100]);
+        let diff = Diff::compute(&before, &after, DEFAULT_THRESHOLD);
+        assert_eq!((diff.changed_pixels, diff.max_channel_delta), (0, 10));
+        assert!(diff.regions.is_empty());
+    }
+
+    #[test]
+    fn changes_far_apart_are_separate_regions_largest_first() {
+        let before = solid(64, 64, [0, 0, 0]);
+        let mut after = before.clone();
+        set(&mut after, 2, 2, [255, 0, 0]);
+        for (x, y) in [(50, 50), (51, 50), (52, 53)] {
+            set(&mut after, x, y, [0, 255, 0]);
+        }
+        let diff = Diff::compute(&before, &after, DEFAULT_THRESHOLD);
+        assert_eq!(diff.changed_pixels, 4);
+        assert_eq!(
+            diff.regions,
+            [
+                Region { x: 50, y: 50, width: 3, height: 4, changed_pixels: 3 },
+                Region { x: 2, y: 2, width: 1, height: 1, changed_pixels: 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn images_of_another_size_are_compared_after_scaling() {
+        let mut small = solid(8, 4, [10, 10, 10]);
+        set(&mut small, 7, 3, [200, 10, 10]);
+        let large = small.scaled(16, 8);
+        assert_eq!((large.width, large.height, large.rgb.len()), (16, 8, 16 * 8 * 3));
+        // Each source pixel covers a 2x2 block
+        let diff = Diff::compute(&solid(16, 8, [10, 10, 10]), &large, DEFAULT_THRESHOLD);
+        assert_eq!(diff.regions, [Region { x: 14, y: 6, width: 2, height: 2, changed_pixels: 4 }]);
+        assert_eq!(diff.changed_percent(), 3.125);
+    }
+
+    #[test]
+    #[should_panic]
+    fn images_of_another_size_are_not_compared_unscaled() {
+        Diff::compute(&solid(8, 8, [0, 0, 0]), &solid(8, 9, [0, 0, 0]), 0);
+    }
+
+    #[test]
+    fn png_captures_decode_to_what_was_encoded() {
+        let mut image = solid(5, 3, [1, 2, 3]);
+        set(&mut image, 4, 2, [250, 128, 0]);
+        let png = encode_png(image.width, image.height, &image.rgb).unwrap();
+        assert_eq!(Image::decode(&png).unwrap(), image);
+        assert!(Image::decode(b"not a png").is_err());
+    }
+}
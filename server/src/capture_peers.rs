@@ -0,0 +1,127 @@
+//! Other server processes using the same capture folder.
+//!
+//! Running one server per Studio instance with a shared `YIPPIE_CAPTURE_DIR` works,
+//! since index updates are locked (`index_lock.rs`), but it is easy to do by accident.
+//! Each server keeps a heartbeat file `.servers/<pid>-<random>.json` in the capture
+//! folder and rewrites it every `HEARTBEAT_INTERVAL`. Files touched within
+//! `PEER_STALE_AFTER` belong to running servers; older ones were left by servers that
+//! didn't exit cleanly and are removed once they are `PEER_REMOVE_AFTER` old.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::state::SharedState;
+
+/// Also listed in `state_export::RUNTIME_NAMES`, which leaves it out of exports.
+const PEERS_DIR: &str = ".servers";
+
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+const PEER_STALE_AFTER: Duration = Duration::from_secs(30);
+const PEER_REMOVE_AFTER: Duration = Duration::from_secs(600);
+
+/// Other servers seen in the capture folder, for studio-status.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerReport {
+    pub path: String,
+    pub other_processes: usize,
+    pub other_pids: Vec<u32>,
+}
+
+pub struct CapturePeers {
+    dir: PathBuf,
+    capture_dir: PathBuf,
+    name: String,
+    /// Other servers counted by the last heartbeat, to log changes once.
+    last_seen: AtomicUsize,
+}
+
+impl CapturePeers {
+    pub fn new(capture_dir: &Path) -> Self {
+        let random = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            dir: capture_dir.join(PEERS_DIR),
+            capture_dir: capture_dir.to_path_buf(),
+            name: format!("{}-{}.json", std::process::id(), &random[..8]),
+            last_seen: AtomicUsize::new(0),
+        }
+    }
+
+    /// Write this server's heartbeat file.
+    fn beat(&self) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let heartbeat = serde_json::json!({
+            "pid": std::process::id(),
+            "updatedAt": chrono::Utc::now().to_rfc3339(),
+        });
+        std::fs::write(self.dir.join(&self.name), heartbeat.to_string())
+    }
+
+    /// Pids of other live servers, removing heartbeat files long past stale.
+    fn others(&self) -> Vec<u32> {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let now = SystemTime::now();
+        let mut pids = Vec::new();
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str() else { continue };
+            if name == self.name || !name.ends_with(".json") {
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age > PEER_REMOVE_AFTER {
+                let _ = std::fs::remove_file(entry.path());
+            } else if age <= PEER_STALE_AFTER {
+                pids.push(name.split('-').next().and_then(|pid| pid.parse().ok()).unwrap_or(0));
+            }
+        }
+        pids.sort_unstable();
+        pids
+    }
+
+    pub fn report(&self) -> PeerReport {
+        let other_pids = self.others();
+        PeerReport {
+            path: self.capture_dir.to_string_lossy().to_string(),
+            other_processes: other_pids.len(),
+            other_pids,
+        }
+    }
+
+    /// Remove this server's heartbeat file, at shutdown.
+    pub fn leave(&self) {
+        let _ = std::fs::remove_file(self.dir.join(&self.name));
+    }
+}
+
+/// Keep the heartbeat file fresh and log when the number of other servers changes.
+pub async fn run(state: SharedState) {
+    let peers = state.capture_peers();
+    loop {
+        if let Err(e) = peers.beat() {
+            tracing::warn!(path = %peers.dir.display(), "Could not write capture folder heartbeat: {e}");
+        }
+        let others = peers.others();
+        let previous = peers.last_seen.swap(others.len(), Ordering::Relaxed);
+        if others.len() > previous {
+            tracing::warn!(
+                path = %peers.capture_dir.display(),
+                pids = ?others,
+                "Capture folder is shared with {} other server process(es); index.json updates are locked between them",
+                others.len()
+            );
+        } else if others.len() < previous {
+            tracing::info!(pids = ?others, "Capture folder now shared with {} other server process(es)", others.len());
+        }
+        tokio::time::sleep(HEARTBEAT_INTERVAL).await;
+    }
+}
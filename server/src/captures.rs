@@ -1,34 +1,155 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use crate::types::CaptureMetadata;
+use crate::capture_compare::DIFF_DIR;
+use crate::ids::IdGenerator;
+use crate::index_lock::{self, IndexLock};
+use crate::input_recording::{InputRecording, RECORDING_FORMAT_VERSION};
+use crate::model_diff::{MAX_SNAPSHOT_FILE_BYTES, SNAPSHOT_DIR};
+use crate::providers::Clock;
+use crate::replay::MAX_REPLAY_FILE_BYTES;
+use crate::time_report::TIME_REPORT_DIR;
+use crate::types::{CaptureMetadata, ScreenRegion};
+use crate::viewport_render::RENDER_DIR;
 
+#[derive(Clone)]
 pub struct CaptureManager {
     capture_dir: PathBuf,
+    /// Per-call `outputDir` (already checked against the client's roots) for files
+    /// that would otherwise go in the capture directory. The index stays here.
+    output_dir: Option<PathBuf>,
+    os_capture_enabled: bool,
+    ids: Arc<IdGenerator>,
+    clock: Arc<dyn Clock>,
+    /// MCP client of the tool call the manager was made for.
+    mcp_client: Option<String>,
 }
 
 impl CaptureManager {
-    pub fn new(capture_dir: &Path) -> Result<Self> {
+    pub fn new(
+        capture_dir: &Path,
+        os_capture_enabled: bool,
+        ids: Arc<IdGenerator>,
+        clock: Arc<dyn Clock>,
+    ) -> Result<Self> {
         std::fs::create_dir_all(capture_dir)?;
-        tracing::info!(path = %capture_dir.display(), "Capture directory ready");
+        tracing::debug!(path = %capture_dir.display(), "Capture directory ready");
         Ok(Self {
             capture_dir: capture_dir.to_path_buf(),
+            output_dir: None,
+            os_capture_enabled,
+            ids,
+            clock,
+            mcp_client: crate::session::call_client(),
         })
     }
 
+    /// Write files to `output_dir` instead, when given.
+    pub fn with_output_dir(mut self, output_dir: Option<&Path>) -> Self {
+        self.output_dir = output_dir.map(Path::to_path_buf);
+        self
+    }
+
+    /// `output_dir` if set, else `sub` of the capture directory (`""` for the top level).
+    fn artifact_dir(&self, sub: &str) -> Result<PathBuf> {
+        let dir = match &self.output_dir {
+            Some(dir) => dir.clone(),
+            None => self.capture_dir.join(sub),
+        };
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn output_dir_string(&self) -> Option<String> {
+        self.output_dir.as_ref().map(|dir| dir.to_string_lossy().to_string())
+    }
+
+    /// Write `bytes` to `<id>.<ext>` in `artifact_dir(sub)` and add it to the capture
+    /// index as a `kind` entry.
+    fn save_artifact(
+        &self,
+        kind: &str,
+        sub: &str,
+        ext: &str,
+        tag: Option<String>,
+        note: Option<String>,
+        bytes: &[u8],
+    ) -> Result<CaptureMetadata> {
+        let dir = self.artifact_dir(sub)?;
+        let id = self.ids.next();
+        let path = dir.join(format!("{id}.{ext}"));
+        std::fs::write(&path, bytes)?;
+
+        let metadata = CaptureMetadata {
+            id,
+            capture_type: kind.into(),
+            timestamp: self.clock.now().to_rfc3339(),
+            file_path: Some(path.to_string_lossy().to_string()),
+            tag,
+            session_id: None,
+            content_id: None,
+            note,
+            region: None,
+            output_dir: self.output_dir_string(),
+            mcp_client: self.mcp_client.clone(),
+        };
+        self.record_capture(metadata.clone())?;
+        Ok(metadata)
+    }
+
+    /// Add an entry to the index, replacing one with the same id. The index is re-read
+    /// under the lock, so entries another server added since are kept.
     pub fn record_capture(&self, metadata: CaptureMetadata) -> Result<()> {
-        let index_path = self.capture_dir.join("index.json");
+        let _lock = IndexLock::acquire(&self.capture_dir)?;
         let mut entries = self.load_index()?;
-        entries.push(metadata);
-        let json = serde_json::to_string_pretty(&entries)?;
-        std::fs::write(&index_path, json)?;
-        Ok(())
+        match entries.iter_mut().find(|e| e.id == metadata.id) {
+            Some(entry) => *entry = metadata,
+            None => entries.push(metadata),
+        }
+        self.write_index(&entries)
+    }
+
+    /// Update the note and/or tag of an existing capture. `None` leaves a field unchanged.
+    /// Returns the updated entry.
+    pub fn update_capture(
+        &self,
+        id: &str,
+        note: Option<String>,
+        tag: Option<String>,
+    ) -> Result<CaptureMetadata> {
+        let _lock = IndexLock::acquire(&self.capture_dir)?;
+        let mut entries = self.load_index()?;
+        let entry = entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| anyhow::anyhow!("No capture with id '{id}' in index.json"))?;
+        if note.is_some() {
+            entry.note = note;
+        }
+        if tag.is_some() {
+            entry.tag = tag;
+        }
+        let updated = entry.clone();
+        self.write_index(&entries)?;
+        Ok(updated)
     }
 
     pub fn list_captures(&self) -> Result<Vec<CaptureMetadata>> {
         self.load_index()
     }
 
+    /// Write index.json via a temp file + rename so readers never see a partial file.
+    /// Callers hold the `IndexLock`.
+    fn write_index(&self, entries: &[CaptureMetadata]) -> Result<()> {
+        let index_path = self.capture_dir.join("index.json");
+        let tmp_path = index_lock::temp_path(&self.capture_dir);
+        let json = serde_json::to_string_pretty(entries)?;
+        std::fs::write(&tmp_path, json)?;
+        std::fs::rename(&tmp_path, &index_path)?;
+        Ok(())
+    }
+
     fn load_index(&self) -> Result<Vec<CaptureMetadata>> {
         let index_path = self.capture_dir.join("index.json");
         if !index_path.exists() {
@@ -39,22 +160,225 @@ impl CaptureManager {
         Ok(entries)
     }
 
+    /// Save an input recording to `recordings/<name>.json` (replacing any previous
+    /// recording of that name) and add it to the capture index.
+    pub fn save_input_recording(&self, recording: &InputRecording) -> Result<CaptureMetadata> {
+        let dir = self.capture_dir.join("recordings");
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", recording.name));
+        let tmp_path = dir.join(format!("{}.json.tmp", recording.name));
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(recording)?)?;
+        std::fs::rename(&tmp_path, &path)?;
+
+        let metadata = CaptureMetadata {
+            id: self.ids.next(),
+            capture_type: "input_recording".into(),
+            timestamp: recording.recorded_at.clone(),
+            file_path: Some(path.to_string_lossy().to_string()),
+            tag: Some(recording.name.clone()),
+            session_id: None,
+            content_id: None,
+            note: None,
+            region: None,
+            output_dir: None,
+            mcp_client: self.mcp_client.clone(),
+        };
+        self.record_capture(metadata.clone())?;
+        Ok(metadata)
+    }
+
+    /// Save a test matrix report (already serialized and redacted) to
+    /// `test_matrix/<id>.json` and add it to the capture index.
+    pub fn save_test_matrix_report(&self, tag: Option<String>, report: &str) -> Result<CaptureMetadata> {
+        self.save_artifact("test_matrix", "test_matrix", "json", tag, None, report.as_bytes())
+    }
+
+    /// Save the full diff of two sessions' logs to `log_diffs/<id>.diff` and add it
+    /// to the capture index, under the current session's id.
+    pub fn save_log_diff(
+        &self,
+        tag: Option<String>,
+        session_id: &str,
+        diff: &str,
+    ) -> Result<CaptureMetadata> {
+        let dir = self.artifact_dir("log_diffs")?;
+        let id = self.ids.next();
+        let path = dir.join(format!("{id}.diff"));
+        std::fs::write(&path, diff)?;
+
+        let metadata = CaptureMetadata {
+            id,
+            capture_type: "log_diff".into(),
+            timestamp: self.clock.now().to_rfc3339(),
+            file_path: Some(path.to_string_lossy().to_string()),
+            tag,
+            session_id: Some(session_id.to_string()),
+            content_id: None,
+            note: None,
+            region: None,
+            output_dir: self.output_dir_string(),
+            mcp_client: self.mcp_client.clone(),
+        };
+        self.record_capture(metadata.clone())?;
+        Ok(metadata)
+    }
+
+    /// Save a viewport render to `renders/<id>.png` and add it to the capture index.
+    /// `note` describes the camera it was rendered from.
+    pub fn save_viewport_render(&self, tag: Option<String>, note: String, png: &[u8]) -> Result<CaptureMetadata> {
+        self.save_artifact("viewport_render", RENDER_DIR, "png", tag, Some(note), png)
+    }
+
+    /// Save a highlighted capture diff to `diffs/<id>.png` and add it to the capture
+    /// index. `note` names the compared captures.
+    pub fn save_capture_diff(&self, tag: Option<String>, note: String, png: &[u8]) -> Result<CaptureMetadata> {
+        self.save_artifact("capture_diff", DIFF_DIR, "png", tag, Some(note), png)
+    }
+
+    /// Save a studio-time_report CSV to `time_reports/<id>.csv` and add it to the index.
+    pub fn save_time_report(&self, tag: Option<String>, note: String, csv: &str) -> Result<CaptureMetadata> {
+        self.save_artifact("time_report", TIME_REPORT_DIR, "csv", tag, Some(note), csv.as_bytes())
+    }
+
+    /// Save a file written by a result hook to `hooks/<hook>/<id>-<name>` and add it
+    /// to the capture index. Both names are validated by `hooks.rs`.
+    pub fn save_hook_artifact(&self, hook: &str, name: &str, content: &str) -> Result<CaptureMetadata> {
+        let dir = self.capture_dir.join("hooks").join(hook);
+        std::fs::create_dir_all(&dir)?;
+        let id = self.ids.next();
+        let path = dir.join(format!("{id}-{name}"));
+        std::fs::write(&path, content)?;
+
+        let metadata = CaptureMetadata {
+            id,
+            capture_type: "hook_artifact".into(),
+            timestamp: self.clock.now().to_rfc3339(),
+            file_path: Some(path.to_string_lossy().to_string()),
+            tag: Some(hook.to_string()),
+            session_id: None,
+            content_id: None,
+            note: None,
+            region: None,
+            output_dir: None,
+            mcp_client: self.mcp_client.clone(),
+        };
+        self.record_capture(metadata.clone())?;
+        Ok(metadata)
+    }
+
+    pub fn load_input_recording(&self, name: &str) -> Result<InputRecording> {
+        let path = self.capture_dir.join("recordings").join(format!("{name}.json"));
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("No input recording named '{name}' ({})", path.display()))?;
+        let recording: InputRecording = serde_json::from_str(&data)
+            .with_context(|| format!("Input recording '{name}' is not valid JSON"))?;
+        if recording.version != RECORDING_FORMAT_VERSION {
+            anyhow::bail!(
+                "Input recording '{name}' has format version {}, expected {RECORDING_FORMAT_VERSION}",
+                recording.version
+            );
+        }
+        Ok(recording)
+    }
+
+    /// Read a replay file given as a path relative to the capture directory. Absolute
+    /// paths, `..` and symlinks leading outside the capture directory are rejected.
+    pub fn load_replay(&self, relative: &str) -> Result<serde_json::Value> {
+        self.load_relative_json(relative, "Replay file", MAX_REPLAY_FILE_BYTES)
+    }
+
+    /// Save a model snapshot (already serialized and redacted) to
+    /// `snapshots/<id>.json` and add it to the capture index, tagged with its root.
+    /// Returns the entry and the path relative to the capture directory, which is
+    /// what `load_model_snapshot` takes.
+    pub fn save_model_snapshot(&self, root: &str, snapshot: &str) -> Result<(CaptureMetadata, String)> {
+        let dir = self.capture_dir.join(SNAPSHOT_DIR);
+        std::fs::create_dir_all(&dir)?;
+        let id = self.ids.next();
+        let path = dir.join(format!("{id}.json"));
+        std::fs::write(&path, snapshot)?;
+
+        let metadata = CaptureMetadata {
+            id: id.clone(),
+            capture_type: "model_snapshot".into(),
+            timestamp: self.clock.now().to_rfc3339(),
+            file_path: Some(path.to_string_lossy().to_string()),
+            tag: Some(root.to_string()),
+            session_id: None,
+            content_id: None,
+            note: None,
+            region: None,
+            output_dir: None,
+            mcp_client: self.mcp_client.clone(),
+        };
+        self.record_capture(metadata.clone())?;
+        Ok((metadata, format!("{SNAPSHOT_DIR}/{id}.json")))
+    }
+
+    /// Read a model snapshot file given as a path relative to the capture directory,
+    /// with the same checks as `load_replay`.
+    pub fn load_model_snapshot(&self, relative: &str) -> Result<serde_json::Value> {
+        self.load_relative_json(relative, "Snapshot file", MAX_SNAPSHOT_FILE_BYTES)
+    }
+
+    fn load_relative_json(&self, relative: &str, what: &str, max_bytes: u64) -> Result<serde_json::Value> {
+        let rel = Path::new(relative);
+        if relative.is_empty()
+            || !rel
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_) | std::path::Component::CurDir))
+        {
+            anyhow::bail!("{what} must be a relative path inside the capture directory (no '..')");
+        }
+        let root = self.capture_dir.canonicalize()?;
+        let path = root
+            .join(rel)
+            .canonicalize()
+            .with_context(|| format!("No {} at '{relative}' in {}", what.to_lowercase(), root.display()))?;
+        if !path.starts_with(&root) {
+            anyhow::bail!("{what} '{relative}' resolves outside the capture directory");
+        }
+        if std::fs::metadata(&path)?.len() > max_bytes {
+            anyhow::bail!("{what} '{relative}' is larger than {max_bytes} bytes");
+        }
+        let data = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {} {}", what.to_lowercase(), path.display()))?;
+        serde_json::from_str(&data).with_context(|| format!("{what} '{relative}' is not valid JSON"))
+    }
+
     /// Take an OS-level screenshot and save it to the capture directory.
-    /// Returns the absolute path to the saved file.
-    pub async fn os_screenshot(&self, tag: Option<&str>) -> Result<PathBuf> {
-        let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
+    /// If `region` is given, only that rectangle of the primary screen is captured.
+    /// Returns the index entry recorded for the saved file.
+    pub async fn os_screenshot(
+        &self,
+        tag: Option<&str>,
+        region: Option<ScreenRegion>,
+    ) -> Result<CaptureMetadata> {
+        if !self.os_capture_enabled {
+            anyhow::bail!("OS screenshots are disabled by configuration (YIPPIE_DISABLE_OS_CAPTURE)");
+        }
+        if let Some(tag) = tag {
+            validate_filename_tag(tag)?;
+        }
+        if let Some(r) = region {
+            validate_region(r, screen_size().await?)?;
+        }
+
+        let timestamp = self.clock.now().format("%Y%m%d_%H%M%S");
         let tag_suffix = tag
             .map(|t| format!("_{t}"))
             .unwrap_or_default();
         let filename = format!("screenshot_{timestamp}{tag_suffix}.png");
-        let path = self.capture_dir.join(&filename);
+        let path = self.artifact_dir("")?.join(&filename);
 
         #[cfg(target_os = "macos")]
         {
-            let status = tokio::process::Command::new("screencapture")
-                .args(["-x", path.to_str().unwrap()])
-                .status()
-                .await?;
+            let mut cmd = tokio::process::Command::new("screencapture");
+            cmd.arg("-x");
+            if let Some(r) = region {
+                cmd.arg(format!("-R{},{},{},{}", r.x, r.y, r.width, r.height));
+            }
+            let status = cmd.arg(&path).status().await?;
             if !status.success() {
                 anyhow::bail!("screencapture command failed with status {status}");
             }
@@ -63,12 +387,20 @@ impl CaptureManager {
         #[cfg(target_os = "windows")]
         {
             // PowerShell screenshot via .NET
+            let bounds = match region {
+                Some(r) => format!(
+                    "New-Object System.Drawing.Rectangle({}, {}, {}, {})",
+                    r.x, r.y, r.width, r.height
+                ),
+                None => "[System.Windows.Forms.Screen]::PrimaryScreen.Bounds".to_string(),
+            };
+            // The path reaches the script through the environment, never its text
             let ps_script = format!(
-                r#"Add-Type -AssemblyName System.Windows.Forms; [System.Windows.Forms.Screen]::PrimaryScreen | ForEach-Object {{ $bmp = New-Object System.Drawing.Bitmap($_.Bounds.Width, $_.Bounds.Height); $g = [System.Drawing.Graphics]::FromImage($bmp); $g.CopyFromScreen($_.Bounds.Location, [System.Drawing.Point]::Empty, $_.Bounds.Size); $bmp.Save('{}') }}"#,
-                path.display()
+                r#"Add-Type -AssemblyName System.Windows.Forms; Add-Type -AssemblyName System.Drawing; $b = {bounds}; $bmp = New-Object System.Drawing.Bitmap($b.Width, $b.Height); $g = [System.Drawing.Graphics]::FromImage($bmp); $g.CopyFromScreen($b.Location, [System.Drawing.Point]::Empty, $b.Size); $bmp.Save($env:YIPPIE_SCREENSHOT_PATH)"#
             );
             let status = tokio::process::Command::new("powershell")
-                .args(["-Command", &ps_script])
+                .args(["-NoProfile", "-Command", &ps_script])
+                .env("YIPPIE_SCREENSHOT_PATH", &path)
                 .status()
                 .await?;
             if !status.success() {
@@ -78,22 +410,285 @@ impl CaptureManager {
 
         #[cfg(not(any(target_os = "macos", target_os = "windows")))]
         {
-            anyhow::bail!("OS-level screenshots not supported on this platform");
+            // ImageMagick `import` against the X11 root window
+            let mut cmd = tokio::process::Command::new("import");
+            cmd.args(["-window", "root"]);
+            if let Some(r) = region {
+                cmd.arg("-crop")
+                    .arg(format!("{}x{}+{}+{}", r.width, r.height, r.x, r.y));
+            }
+            let status = cmd.arg(&path).status().await?;
+            if !status.success() {
+                anyhow::bail!("import (ImageMagick) screenshot failed with status {status}");
+            }
         }
 
         // Record in index
         let metadata = CaptureMetadata {
-            id: uuid::Uuid::new_v4().to_string(),
+            id: self.ids.next(),
             capture_type: "screenshot".into(),
-            timestamp: chrono::Utc::now().to_rfc3339(),
+            timestamp: self.clock.now().to_rfc3339(),
             file_path: Some(path.to_string_lossy().to_string()),
             tag: tag.map(String::from),
             session_id: None,
             content_id: None,
             note: Some("OS-level screenshot".into()),
+            region,
+            output_dir: self.output_dir_string(),
+            mcp_client: self.mcp_client.clone(),
         };
-        self.record_capture(metadata)?;
+        // The index lock can be held by another process for a while; wait off the runtime
+        let captures = self.clone();
+        let entry = metadata.clone();
+        tokio::task::spawn_blocking(move || captures.record_capture(entry))
+            .await
+            .map_err(|e| anyhow::anyhow!("capture task failed: {e}"))??;
+
+        Ok(metadata)
+    }
+}
+
+/// Longest tag that goes into a screenshot filename.
+const MAX_FILENAME_TAG_LEN: usize = 64;
+
+/// A tag that becomes part of a filename may only use letters, digits, `_` and `-`,
+/// so it can't point outside the capture folder or break out of a command line.
+pub fn validate_filename_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.len() > MAX_FILENAME_TAG_LEN {
+        anyhow::bail!("Tag must be 1 to {MAX_FILENAME_TAG_LEN} characters");
+    }
+    if let Some(c) = tag.chars().find(|c| !(c.is_ascii_alphanumeric() || *c == '_' || *c == '-')) {
+        anyhow::bail!("Tag may only contain letters, digits, '_' and '-' (found {c:?})");
+    }
+    Ok(())
+}
+
+/// Reject regions that are empty or extend past the primary screen.
+fn validate_region(region: ScreenRegion, (screen_w, screen_h): (u32, u32)) -> Result<()> {
+    if region.width == 0 || region.height == 0 {
+        anyhow::bail!("Region width and height must be greater than 0");
+    }
+    let right = u64::from(region.x) + u64::from(region.width);
+    let bottom = u64::from(region.y) + u64::from(region.height);
+    if right > u64::from(screen_w) || bottom > u64::from(screen_h) {
+        anyhow::bail!(
+            "Region {}x{}+{}+{} is outside the primary screen ({screen_w}x{screen_h})",
+            region.width,
+            region.height,
+            region.x,
+            region.y
+        );
+    }
+    Ok(())
+}
+
+/// Query the primary screen size in pixels (points on macOS, matching `screencapture -R`).
+async fn screen_size() -> Result<(u32, u32)> {
+    // The first NSScreen is the main display, the one with the menu bar, which
+    // `screencapture -R` coordinates start from; Finder's desktop spans all displays
+    #[cfg(target_os = "macos")]
+    let output = tokio::process::Command::new("osascript")
+        .args([
+            "-l",
+            "JavaScript",
+            "-e",
+            "ObjC.import('AppKit'); var f = $.NSScreen.screens.objectAtIndex(0).frame; [0, 0, f.size.width, f.size.height].join(', ')",
+        ])
+        .output()
+        .await?;
+
+    #[cfg(target_os = "windows")]
+    let output = tokio::process::Command::new("powershell")
+        .args([
+            "-Command",
+            "Add-Type -AssemblyName System.Windows.Forms; $b = [System.Windows.Forms.Screen]::PrimaryScreen.Bounds; \"$($b.Width)x$($b.Height)\"",
+        ])
+        .output()
+        .await?;
+
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let output = tokio::process::Command::new("xdpyinfo").output().await?;
+
+    if !output.status.success() {
+        anyhow::bail!("Could not determine screen size (status {})", output.status);
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
 
-        Ok(path)
+    // macOS: "0, 0, 1440, 900"; Windows: "1920x1080"; xdpyinfo: "dimensions:    1920x1080 pixels ..."
+    let dims = if cfg!(target_os = "macos") {
+        let nums: Vec<u32> = text
+            .split(',')
+            .filter_map(|n| n.trim().parse().ok())
+            .collect();
+        match nums.as_slice() {
+            [_, _, w, h] => Some((*w, *h)),
+            _ => None,
+        }
+    } else {
+        let line = text
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix("dimensions:"))
+            .unwrap_or(text.trim());
+        line.split_whitespace().next().and_then(|token| {
+            let (w, h) = token.split_once('x')?;
+            Some((w.parse().ok()?, h.parse().ok()?))
+        })
+    };
+    dims.ok_or_else(|| anyhow::anyhow!("Could not parse screen size from: {}", text.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{SeededRng, SystemClock};
+
+    /// A manager as a separate server would have it: its own id sequence and clock.
+    fn manager(dir: &Path, seed: u64) -> CaptureManager {
+        let ids = Arc::new(IdGenerator::new(Arc::new(SeededRng::new(seed))));
+        CaptureManager::new(dir, false, ids, Arc::new(SystemClock)).unwrap()
+    }
+
+    fn entry(id: &str, note: &str) -> CaptureMetadata {
+        CaptureMetadata {
+            id: id.into(),
+            capture_type: "screenshot".into(),
+            timestamp: "2026-01-01T00:00:00Z".into(),
+            file_path: None,
+            tag: None,
+            session_id: None,
+            content_id: None,
+            note: Some(note.into()),
+            region: None,
+            output_dir: None,
+            mcp_client: None,
+        }
+    }
+
+    fn ids(captures: &CaptureManager) -> Vec<String> {
+        let mut ids: Vec<String> = captures.list_captures().unwrap().into_iter().map(|e| e.id).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn filename_tags_are_limited_to_safe_characters() {
+        for tag in ["viewport_after_build", "run-2", "A1"] {
+            assert!(validate_filename_tag(tag).is_ok(), "{tag}");
+        }
+        for tag in ["", "../up", "a/b", "a\\b", "x'); Remove-Item C:\\ -Recurse; ('", "with space", "é", &"x".repeat(65)] {
+            assert!(validate_filename_tag(tag).is_err(), "{tag}");
+        }
+    }
+
+    #[tokio::test]
+    async fn a_screenshot_with_an_unsafe_tag_is_refused_before_capturing() {
+        let dir = tempfile::tempdir().unwrap();
+        let ids = Arc::new(IdGenerator::new(Arc::new(SeededRng::new(1))));
+        let captures = CaptureManager::new(dir.path(), true, ids, Arc::new(SystemClock)).unwrap();
+        let error = captures.os_screenshot(Some("../../escape"), None).await.unwrap_err();
+        assert!(error.to_string().starts_with("Tag may only contain"), "{error}");
+        assert!(captures.list_captures().unwrap().is_empty());
+    }
+
+    #[test]
+    fn two_managers_keep_each_others_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (manager(dir.path(), 1), manager(dir.path(), 2));
+        a.record_capture(entry("a1", "from a")).unwrap();
+        b.record_capture(entry("b1", "from b")).unwrap();
+        a.record_capture(entry("a2", "from a")).unwrap();
+        assert_eq!(ids(&a), ["a1", "a2", "b1"]);
+        assert_eq!(ids(&b), ids(&a));
+    }
+
+    #[test]
+    fn same_id_replaces_the_entry_instead_of_duplicating_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (manager(dir.path(), 1), manager(dir.path(), 2));
+        a.record_capture(entry("shared", "first")).unwrap();
+        b.record_capture(entry("other", "kept")).unwrap();
+        b.record_capture(entry("shared", "second")).unwrap();
+        let entries = a.list_captures().unwrap();
+        assert_eq!(entries.len(), 2);
+        let shared = entries.iter().find(|e| e.id == "shared").unwrap();
+        assert_eq!(shared.note.as_deref(), Some("second"));
+    }
+
+    #[test]
+    fn update_from_one_manager_keeps_entries_from_the_other() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (manager(dir.path(), 1), manager(dir.path(), 2));
+        a.record_capture(entry("a1", "from a")).unwrap();
+        b.record_capture(entry("b1", "from b")).unwrap();
+        let updated = a.update_capture("b1", Some("annotated by a".into()), Some("tagged".into())).unwrap();
+        assert_eq!(updated.note.as_deref(), Some("annotated by a"));
+        let entries = b.list_captures().unwrap();
+        assert_eq!(entries.len(), 2);
+        let b1 = entries.iter().find(|e| e.id == "b1").unwrap();
+        assert_eq!((b1.note.as_deref(), b1.tag.as_deref()), (Some("annotated by a"), Some("tagged")));
+        assert!(a.update_capture("missing", Some("x".into()), None).is_err());
+    }
+
+    #[test]
+    fn concurrent_writers_lose_no_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let writers: Vec<_> = (0..2u64)
+            .map(|seed| {
+                let captures = manager(dir.path(), seed);
+                std::thread::spawn(move || {
+                    for n in 0..40 {
+                        captures.record_capture(entry(&format!("w{seed}-{n:02}"), "concurrent")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(manager(dir.path(), 9).list_captures().unwrap().len(), 80);
+        // No temp file is left behind
+        let leftovers: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn saved_artifacts_from_both_managers_are_indexed() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (manager(dir.path(), 1), manager(dir.path(), 2));
+        let first = a.save_time_report(None, "a".into(), "seq\n1\n").unwrap();
+        let second = b.save_time_report(Some("b".into()), "b".into(), "seq\n2\n").unwrap();
+        assert_ne!(first.id, second.id);
+        let entries = a.list_captures().unwrap();
+        assert_eq!(entries.len(), 2);
+        for saved in [&first, &second] {
+            let path = saved.file_path.as_deref().unwrap();
+            assert!(Path::new(path).exists(), "{path}");
+        }
+    }
+
+    #[test]
+    fn regions_are_checked_against_the_screen() {
+        let region = |x, y, width, height| ScreenRegion { x, y, width, height };
+        let screen = (1920, 1080);
+        assert!(validate_region(region(0, 0, 1920, 1080), screen).is_ok());
+        assert!(validate_region(region(1820, 980, 100, 100), screen).is_ok());
+
+        for (r, message) in [
+            (region(10, 10, 0, 100), "greater than 0"),
+            (region(10, 10, 100, 0), "greater than 0"),
+            (region(1821, 0, 100, 100), "outside the primary screen (1920x1080)"),
+            (region(0, 981, 100, 100), "outside the primary screen (1920x1080)"),
+            (region(1920, 1080, 1, 1), "outside the primary screen (1920x1080)"),
+            // x + width past u32::MAX must not wrap around into range
+            (region(u32::MAX, 0, 2, 2), "outside the primary screen (1920x1080)"),
+        ] {
+            let error = validate_region(r, screen).unwrap_err().to_string();
+            assert!(error.contains(message), "{r:?}: {error}");
+        }
     }
 }
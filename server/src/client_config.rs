@@ -0,0 +1,422 @@
+//! MCP client config entries for `mcpctl install-client`.
+//!
+//! Claude Desktop, Claude Code and Cursor all list servers under `mcpServers` in a
+//! plain JSON file. Installing parses the whole file, replaces only this server's
+//! entry and writes it back, so other servers and settings survive. Files that don't
+//! parse as JSON (comments, trailing commas) are refused rather than rewritten.
+//! Key order inside the file is not preserved.
+//!
+//! Used by mcpctl through the library; the server itself doesn't use it.
+
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Name of the entry under `mcpServers`, as in the README examples.
+pub const SERVER_NAME: &str = "roblox-studio-yippieblox";
+
+/// Above this many line pairs the diff shows the whole file as replaced.
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientKind {
+    ClaudeDesktop,
+    ClaudeCode,
+    Cursor,
+}
+
+impl ClientKind {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "claude-desktop" => Ok(Self::ClaudeDesktop),
+            "claude-code" => Ok(Self::ClaudeCode),
+            "cursor" => Ok(Self::Cursor),
+            _ => Err(format!("unknown client '{s}' (expected claude-desktop, claude-code or cursor)")),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::ClaudeDesktop => "claude-desktop",
+            Self::ClaudeCode => "claude-code",
+            Self::Cursor => "cursor",
+        }
+    }
+
+    /// The client's user-level config file on `os` (`std::env::consts::OS` values).
+    /// `appdata` is `%APPDATA%` and `xdg_config` is `$XDG_CONFIG_HOME`, when set.
+    pub fn config_path(self, os: &str, home: &Path, appdata: Option<&Path>, xdg_config: Option<&Path>) -> PathBuf {
+        match self {
+            Self::ClaudeDesktop => {
+                let dir = match os {
+                    "macos" => home.join("Library").join("Application Support"),
+                    "windows" => appdata.map_or_else(|| home.join("AppData").join("Roaming"), Path::to_path_buf),
+                    _ => xdg_config.map_or_else(|| home.join(".config"), Path::to_path_buf),
+                };
+                dir.join("Claude").join("claude_desktop_config.json")
+            }
+            // User scope; project-scoped `.mcp.json` files are left to `--config-path`
+            Self::ClaudeCode => home.join(".claude.json"),
+            Self::Cursor => home.join(".cursor").join("mcp.json"),
+        }
+    }
+
+    /// `config_path` for the platform mcpctl runs on.
+    pub fn default_config_path(self) -> Result<PathBuf> {
+        let var = |name: &str| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let home = var("HOME")
+            .or_else(|| var("USERPROFILE"))
+            .context("Neither HOME nor USERPROFILE is set; pass --config-path")?;
+        Ok(self.config_path(
+            std::env::consts::OS,
+            &home,
+            var("APPDATA").as_deref(),
+            var("XDG_CONFIG_HOME").as_deref(),
+        ))
+    }
+}
+
+/// What to run for this server.
+#[derive(Debug, Clone)]
+pub struct ServerEntry {
+    /// Absolute path to the server binary.
+    pub command: PathBuf,
+    pub env: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct Merged {
+    pub before: String,
+    pub after: String,
+    /// False when the file already had this exact entry.
+    pub changed: bool,
+}
+
+/// Put `entry` into the config text `existing` (empty for a new file).
+///
+/// Fields of an existing entry that install doesn't set are kept, and so are env
+/// variables it doesn't set, so a token added by hand survives a re-run without one.
+pub fn merge(existing: &str, client: ClientKind, entry: &ServerEntry) -> Result<Merged> {
+    let mut root: Value = if existing.trim().is_empty() {
+        json!({})
+    } else {
+        serde_json::from_str(existing)
+            .context("Config file isn't plain JSON (comments or trailing commas?); edit it by hand or fix it first")?
+    };
+    let before = if existing.trim().is_empty() { String::new() } else { render(&root) };
+
+    let Some(root_map) = root.as_object_mut() else {
+        bail!("Config file is not a JSON object");
+    };
+    let servers = root_map.entry("mcpServers").or_insert_with(|| json!({}));
+    let Some(servers) = servers.as_object_mut() else {
+        bail!("'mcpServers' in the config file is not an object");
+    };
+    let server = servers.entry(SERVER_NAME).or_insert_with(|| json!({}));
+    let Some(server) = server.as_object_mut() else {
+        bail!("'mcpServers.{SERVER_NAME}' in the config file is not an object");
+    };
+
+    let mut env: Map<String, Value> = server.get("env").and_then(Value::as_object).cloned().unwrap_or_default();
+    for (key, value) in &entry.env {
+        env.insert(key.clone(), Value::String(value.clone()));
+    }
+    if client == ClientKind::ClaudeCode {
+        server.insert("type".into(), json!("stdio"));
+    }
+    server.insert("command".into(), json!(entry.command.to_string_lossy()));
+    server.insert("args".into(), json!(["--stdio"]));
+    server.insert("env".into(), Value::Object(env));
+
+    let after = render(&root);
+    Ok(Merged { changed: after != before, before, after })
+}
+
+/// Pretty JSON with a trailing newline, as written to disk.
+pub fn render(value: &Value) -> String {
+    let mut text = serde_json::to_string_pretty(value).unwrap_or_default();
+    text.push('\n');
+    text
+}
+
+/// Copy `path` to `<path>.<timestamp>.bak` next to it.
+pub fn backup(path: &Path) -> Result<PathBuf> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S");
+    let mut name = path.file_name().context("Config path has no file name")?.to_os_string();
+    name.push(format!(".{stamp}.bak"));
+    let backup = path.with_file_name(name);
+    std::fs::copy(path, &backup).with_context(|| format!("Failed to back up {}", path.display()))?;
+    Ok(backup)
+}
+
+/// Write through a temporary file in the same directory, then rename over `path`.
+pub fn write(path: &Path, text: &str) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let mut tmp_name = path.file_name().context("Config path has no file name")?.to_os_string();
+    tmp_name.push(".partial");
+    let tmp = path.with_file_name(tmp_name);
+    std::fs::write(&tmp, text).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    std::fs::rename(&tmp, path).with_context(|| format!("Failed to replace {}", path.display()))?;
+    Ok(())
+}
+
+/// Line diff of `before` and `after`, `-`/`+` for changed lines and up to three
+/// unchanged lines of context around them.
+pub fn diff(before: &str, after: &str) -> String {
+    const CONTEXT: usize = 3;
+
+    let old: Vec<&str> = before.lines().collect();
+    let new: Vec<&str> = after.lines().collect();
+    let ops = if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        old.iter().map(|l| ('-', *l)).chain(new.iter().map(|l| ('+', *l))).collect()
+    } else {
+        line_ops(&old, &new)
+    };
+
+    let near_change = |i: usize| {
+        let from = i.saturating_sub(CONTEXT);
+        let to = (i + CONTEXT + 1).min(ops.len());
+        ops[from..to].iter().any(|(op, _)| *op != ' ')
+    };
+    let mut out = String::new();
+    let mut skipped = false;
+    for (i, (op, line)) in ops.iter().enumerate() {
+        if *op == ' ' && !near_change(i) {
+            if !skipped {
+                out.push_str("  ...\n");
+                skipped = true;
+            }
+            continue;
+        }
+        skipped = false;
+        out.push_str(&format!("{op} {line}\n"));
+    }
+    out
+}
+
+/// Longest-common-subsequence edit script.
+fn line_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(char, &'a str)> {
+    let (n, m) = (old.len(), new.len());
+    // lcs[i][j]: common lines of old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n + m);
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            ops.push((' ', old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(('-', old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', new[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old[i..].iter().map(|l| ('-', *l)));
+    ops.extend(new[j..].iter().map(|l| ('+', *l)));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/client_config").join(name);
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+    }
+
+    fn entry(token: Option<&str>) -> ServerEntry {
+        let mut env = BTreeMap::from([("YIPPIE_PORT".to_string(), "3333".to_string())]);
+        if let Some(token) = token {
+            env.insert("YIPPIE_TOKEN".into(), token.into());
+        }
+        ServerEntry { command: PathBuf::from("/opt/yippie/roblox-studio-yippieblox-mcp-server"), env }
+    }
+
+    fn parsed(merged: &Merged) -> Value {
+        serde_json::from_str(&merged.after).unwrap()
+    }
+
+    #[test]
+    fn config_paths_follow_each_client_and_os() {
+        let home = Path::new("/home/dev");
+        let cases = [
+            (ClientKind::ClaudeDesktop, "macos", "/home/dev/Library/Application Support/Claude/claude_desktop_config.json"),
+            (ClientKind::ClaudeDesktop, "linux", "/home/dev/.config/Claude/claude_desktop_config.json"),
+            (ClientKind::ClaudeDesktop, "windows", "/home/dev/AppData/Roaming/Claude/claude_desktop_config.json"),
+            (ClientKind::ClaudeCode, "macos", "/home/dev/.claude.json"),
+            (ClientKind::ClaudeCode, "windows", "/home/dev/.claude.json"),
+            (ClientKind::Cursor, "linux", "/home/dev/.cursor/mcp.json"),
+            (ClientKind::Cursor, "windows", "/home/dev/.cursor/mcp.json"),
+        ];
+        for (client, os, expected) in cases {
+            assert_eq!(client.config_path(os, home, None, None), Path::new(expected), "{client:?} on {os}");
+        }
+
+        // APPDATA and XDG_CONFIG_HOME win where they apply
+        let appdata = Path::new("/roaming");
+        let xdg = Path::new("/xdg");
+        let desktop = ClientKind::ClaudeDesktop;
+        assert_eq!(desktop.config_path("windows", home, Some(appdata), Some(xdg)), Path::new("/roaming/Claude/claude_desktop_config.json"));
+        assert_eq!(desktop.config_path("linux", home, Some(appdata), Some(xdg)), Path::new("/xdg/Claude/claude_desktop_config.json"));
+        assert_eq!(desktop.config_path("macos", home, Some(appdata), Some(xdg)), desktop.config_path("macos", home, None, None));
+        assert_eq!(ClientKind::Cursor.config_path("linux", home, None, Some(xdg)), Path::new("/home/dev/.cursor/mcp.json"));
+    }
+
+    #[test]
+    fn client_names_round_trip() {
+        for client in [ClientKind::ClaudeDesktop, ClientKind::ClaudeCode, ClientKind::Cursor] {
+            assert_eq!(ClientKind::parse(client.name()), Ok(client));
+        }
+        assert!(ClientKind::parse("vscode").unwrap_err().contains("unknown client 'vscode'"));
+    }
+
+    #[test]
+    fn a_new_file_gets_just_this_server() {
+        let merged = merge("", ClientKind::ClaudeDesktop, &entry(Some("secret"))).unwrap();
+        assert!(merged.changed);
+        assert_eq!(merged.before, "");
+        assert_eq!(
+            parsed(&merged),
+            json!({ "mcpServers": { SERVER_NAME: {
+                "command": "/opt/yippie/roblox-studio-yippieblox-mcp-server",
+                "args": ["--stdio"],
+                "env": { "YIPPIE_PORT": "3333", "YIPPIE_TOKEN": "secret" },
+            } } })
+        );
+        assert!(merged.after.ends_with("}\n"));
+        // Whitespace-only files count as new
+        assert_eq!(merge(" \n", ClientKind::ClaudeDesktop, &entry(Some("secret"))).unwrap().after, merged.after);
+    }
+
+    #[test]
+    fn unrelated_settings_and_servers_are_kept() {
+        let existing = fixture("claude_desktop.json");
+        let merged = merge(&existing, ClientKind::ClaudeDesktop, &entry(None)).unwrap();
+        let mut expected: Value = serde_json::from_str(&existing).unwrap();
+        expected["mcpServers"][SERVER_NAME] = json!({
+            "command": "/opt/yippie/roblox-studio-yippieblox-mcp-server",
+            "args": ["--stdio"],
+            "env": { "YIPPIE_PORT": "3333" },
+        });
+        assert_eq!(parsed(&merged), expected);
+
+        // Claude Code's file holds much more than servers; its entries also name the transport
+        let existing = fixture("claude_code.json");
+        let merged = merge(&existing, ClientKind::ClaudeCode, &entry(Some("t"))).unwrap();
+        let mut expected: Value = serde_json::from_str(&existing).unwrap();
+        expected["mcpServers"][SERVER_NAME] = json!({
+            "type": "stdio",
+            "command": "/opt/yippie/roblox-studio-yippieblox-mcp-server",
+            "args": ["--stdio"],
+            "env": { "YIPPIE_PORT": "3333", "YIPPIE_TOKEN": "t" },
+        });
+        assert_eq!(parsed(&merged), expected);
+    }
+
+    #[test]
+    fn an_existing_entry_is_updated_keeping_what_install_does_not_set() {
+        let merged = merge(&fixture("cursor_stale_entry.json"), ClientKind::Cursor, &entry(None)).unwrap();
+        assert!(merged.changed);
+        let after = parsed(&merged);
+        assert_eq!(
+            after["mcpServers"][SERVER_NAME],
+            json!({
+                "command": "/opt/yippie/roblox-studio-yippieblox-mcp-server",
+                "args": ["--stdio"],
+                // The token added by hand survives a run without one
+                "env": { "YIPPIE_PORT": "3333", "YIPPIE_TOKEN": "added-by-hand", "RUST_LOG": "debug" },
+                "disabled": false,
+            })
+        );
+        assert_eq!(after["mcpServers"]["other"], json!({ "url": "http://localhost:9000/sse" }));
+
+        // A token given now replaces it
+        let merged = merge(&fixture("cursor_stale_entry.json"), ClientKind::Cursor, &entry(Some("new"))).unwrap();
+        assert_eq!(parsed(&merged)["mcpServers"][SERVER_NAME]["env"]["YIPPIE_TOKEN"], "new");
+    }
+
+    #[test]
+    fn re_running_with_the_same_settings_changes_nothing() {
+        for (name, client) in [
+            ("claude_desktop.json", ClientKind::ClaudeDesktop),
+            ("claude_code.json", ClientKind::ClaudeCode),
+            ("cursor_stale_entry.json", ClientKind::Cursor),
+        ] {
+            let first = merge(&fixture(name), client, &entry(Some("t"))).unwrap();
+            assert!(first.changed, "{name}");
+            let second = merge(&first.after, client, &entry(Some("t"))).unwrap();
+            assert!(!second.changed, "{name}");
+            assert_eq!(second.after, first.after, "{name}");
+            // Also when the token is left out the second time
+            assert!(!merge(&first.after, client, &entry(None)).unwrap().changed, "{name}");
+        }
+        // Formatting alone isn't a change
+        let compact = r#"{"mcpServers":{"roblox-studio-yippieblox":{"command":"/opt/yippie/roblox-studio-yippieblox-mcp-server","args":["--stdio"],"env":{"YIPPIE_PORT":"3333"}}}}"#;
+        assert!(!merge(compact, ClientKind::Cursor, &entry(None)).unwrap().changed);
+    }
+
+    #[test]
+    fn files_that_are_not_plain_json_objects_are_refused() {
+        let cases = [
+            (fixture("with_comments.jsonc"), "isn't plain JSON"),
+            ("[]".to_string(), "not a JSON object"),
+            (r#"{"mcpServers": []}"#.to_string(), "'mcpServers' in the config file is not an object"),
+            (r#"{"mcpServers": {"roblox-studio-yippieblox": "x"}}"#.to_string(), "is not an object"),
+        ];
+        for (existing, expected) in cases {
+            let error = format!("{:#}", merge(&existing, ClientKind::ClaudeDesktop, &entry(None)).unwrap_err());
+            assert!(error.contains(expected), "{existing}: {error}");
+        }
+    }
+
+    #[test]
+    fn backups_and_writes_leave_the_old_file_next_to_the_new_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Claude").join("claude_desktop_config.json");
+        write(&path, "{}\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}\n");
+
+        let saved = backup(&path).unwrap();
+        let name = saved.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("claude_desktop_config.json.") && name.ends_with(".bak"), "{name}");
+        assert_eq!(saved.parent(), path.parent());
+        write(&path, "{\"a\": 1}\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&saved).unwrap(), "{}\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"a\": 1}\n");
+        // No temporary file is left behind
+        assert_eq!(std::fs::read_dir(path.parent().unwrap()).unwrap().count(), 2);
+
+        assert!(backup(&dir.path().join("missing.json")).is_err());
+    }
+
+    #[test]
+    fn the_diff_shows_changed_lines_with_context() {
+        let before = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let after = "a\nb\nc\nd\nE\nf\ng\nh\ni\nj\n";
+        assert_eq!(diff(before, after), "  ...\n  b\n  c\n  d\n- e\n+ E\n  f\n  g\n  h\n  i\n+ j\n");
+        assert_eq!(diff("same\n", "same\n"), "  ...\n");
+        assert_eq!(diff("", "new\n"), "+ new\n");
+
+        let merged = merge(&fixture("claude_desktop.json"), ClientKind::ClaudeDesktop, &entry(None)).unwrap();
+        let shown = diff(&merged.before, &merged.after);
+        assert!(shown.contains(&format!("+     \"{SERVER_NAME}\": {{")), "{shown}");
+        assert!(shown.lines().all(|l| !l.starts_with('-')), "{shown}");
+    }
+}
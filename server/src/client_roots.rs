@@ -0,0 +1,173 @@
+//! The MCP client's filesystem roots (`roots/list`), used to check per-call
+//! `outputDir` arguments.
+//!
+//! Roots are asked for on first use and cached until the client sends
+//! `notifications/roots/list_changed`. The request goes out through the stdio writer
+//! and the loop in `mcp_stdio::run` hands the client's response back here, which works
+//! because tool calls run on their own tasks while the loop keeps reading.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+/// How long to wait for the client to answer `roots/list`.
+const ROOTS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Prefix of the ids of requests the server sends, so they can't clash with ids
+/// the client picks.
+const REQUEST_ID_PREFIX: &str = "yippie-";
+
+#[derive(Debug)]
+pub struct ClientRoots {
+    tx: mpsc::Sender<String>,
+    /// Set when `initialize` declared `capabilities.roots`.
+    supported: AtomicBool,
+    next_request: AtomicU64,
+    pending: Mutex<HashMap<String, oneshot::Sender<Result<Value, String>>>>,
+    /// Bumped by `invalidate`; cached roots from an older generation are stale.
+    generation: AtomicU64,
+    cached: tokio::sync::Mutex<Option<(u64, Vec<PathBuf>)>>,
+}
+
+impl ClientRoots {
+    pub fn new(tx: mpsc::Sender<String>) -> Self {
+        Self {
+            tx,
+            supported: AtomicBool::new(false),
+            next_request: AtomicU64::new(1),
+            pending: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            cached: tokio::sync::Mutex::new(None),
+        }
+    }
+
+    /// Record whether the client offers `roots/list`; forgets cached roots.
+    pub fn set_supported(&self, supported: bool) {
+        self.supported.store(supported, Ordering::Relaxed);
+        self.invalidate();
+    }
+
+    /// Drop the cached roots, for `notifications/roots/list_changed`.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Hand a JSON-RPC response from the client to the request waiting for it.
+    /// Returns false when the id isn't one of ours.
+    pub fn resolve_response(&self, message: &Value) -> bool {
+        let Some(id) = message.get("id").and_then(Value::as_str).filter(|id| id.starts_with(REQUEST_ID_PREFIX)) else {
+            return false;
+        };
+        let Some(waiter) = self.pending.lock().unwrap().remove(id) else {
+            return false;
+        };
+        let outcome = match message.get("error") {
+            Some(error) => Err(error.get("message").and_then(Value::as_str).unwrap_or("unknown error").to_string()),
+            None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+        };
+        let _ = waiter.send(outcome);
+        true
+    }
+
+    /// The client's roots as local directories. Non-`file://` roots are skipped.
+    pub async fn get(&self) -> Result<Vec<PathBuf>, String> {
+        if !self.supported.load(Ordering::Relaxed) {
+            return Err("the MCP client did not declare the roots capability".into());
+        }
+        let mut cached = self.cached.lock().await;
+        let generation = self.generation.load(Ordering::Relaxed);
+        if let Some((_, roots)) = cached.as_ref().filter(|(g, _)| *g == generation) {
+            return Ok(roots.clone());
+        }
+
+        let id = format!("{REQUEST_ID_PREFIX}{}", self.next_request.fetch_add(1, Ordering::Relaxed));
+        let (waiter, answer) = oneshot::channel();
+        self.pending.lock().unwrap().insert(id.clone(), waiter);
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": "roots/list" });
+        if self.tx.send(request.to_string()).await.is_err() {
+            self.pending.lock().unwrap().remove(&id);
+            return Err("stdout writer closed".into());
+        }
+        let result = match tokio::time::timeout(ROOTS_TIMEOUT, answer).await {
+            Ok(Ok(outcome)) => outcome.map_err(|e| format!("roots/list failed: {e}"))?,
+            Ok(Err(_)) => return Err("roots/list was abandoned".into()),
+            Err(_) => {
+                self.pending.lock().unwrap().remove(&id);
+                return Err(format!("the client did not answer roots/list within {}s", ROOTS_TIMEOUT.as_secs()));
+            }
+        };
+
+        let roots: Vec<PathBuf> = result
+            .get("roots")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|root| root.get("uri").and_then(Value::as_str))
+            .filter_map(file_uri_to_path)
+            .collect();
+        tracing::info!(roots = ?roots, "Client roots");
+        *cached = Some((generation, roots.clone()));
+        Ok(roots)
+    }
+}
+
+/// `file:///home/me/game` -> `/home/me/game`, `file:///C:/Games/x` -> `C:/Games/x`.
+/// Percent-escapes are decoded. Other schemes and remote hosts give `None`.
+pub fn file_uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?;
+    // Only local files: an empty host or "localhost"
+    let path = match rest.find('/') {
+        Some(0) => rest,
+        Some(i) if rest[..i].eq_ignore_ascii_case("localhost") => &rest[i..],
+        _ => return None,
+    };
+    let decoded = percent_decode(path)?;
+    // "/C:/x" is a Windows drive path
+    let bytes = decoded.as_bytes();
+    let is_drive = bytes.len() >= 3 && bytes[0] == b'/' && bytes[1].is_ascii_alphabetic() && bytes[2] == b':';
+    Some(PathBuf::from(if is_drive { &decoded[1..] } else { decoded.as_str() }))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut out = Vec::with_capacity(s.len());
+    let mut bytes = s.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            out.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            out.push(b);
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unix_and_windows_file_uris() {
+        assert_eq!(file_uri_to_path("file:///home/me/game"), Some(PathBuf::from("/home/me/game")));
+        assert_eq!(file_uri_to_path("file://localhost/home/me"), Some(PathBuf::from("/home/me")));
+        assert_eq!(file_uri_to_path("file:///C:/Games/x"), Some(PathBuf::from("C:/Games/x")));
+        assert_eq!(file_uri_to_path("file:///c%3A/My%20Games"), Some(PathBuf::from("c:/My Games")));
+    }
+
+    #[test]
+    fn remote_and_other_schemes_are_ignored() {
+        assert_eq!(file_uri_to_path("file://server/share/x"), None);
+        assert_eq!(file_uri_to_path("https://example.com/x"), None);
+        assert_eq!(file_uri_to_path("file:///bad%zz"), None);
+        assert_eq!(file_uri_to_path("file:///truncated%2"), None);
+    }
+
+    #[test]
+    fn encoded_parent_segments_decode_to_dot_dot() {
+        // The roots check canonicalizes, so the decoded `..` is resolved there
+        assert_eq!(file_uri_to_path("file:///a/%2e%2e/b"), Some(PathBuf::from("/a/../b")));
+    }
+}
@@ -0,0 +1,179 @@
+//! Plugin clock offset estimation and latency-compensated `studio-virtualuser_sequence`.
+//!
+//! The playtest bridge sends its `os.clock()` with every request (`clock` query
+//! parameter). Each request gives a sample `server time at arrival - plugin clock`,
+//! which is the true offset plus that request's one-way latency. The smallest sample
+//! of a recent window is the estimate: latency only ever adds.
+//!
+//! A sequence then goes out with a `dueClock` per step: the server's time at dispatch,
+//! plus the lead buffer (`YIPPIE_INPUT_LEAD_MS`) and the step's `atMs`, converted to the
+//! plugin's clock. The bridge waits for each `dueClock` on its own clock, so queueing
+//! and delivery delays no longer shift the steps against each other, and reports when
+//! each step actually fired. Without an estimate (no samples yet, an older bridge)
+//! steps keep their relative `atMs` timing.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Offset samples kept per client. Old samples fall out so drift between the two
+/// clocks can't pin the estimate.
+const SAMPLE_WINDOW: usize = 32;
+
+/// Longest sequence (lead buffer plus last step) accepted. Covers a five-minute
+/// recording replayed at 0.25x and keeps the call's timeout well under the stdio
+/// loop's request backstop.
+pub const MAX_SEQUENCE_DURATION: Duration = Duration::from_secs(25 * 60);
+
+/// Steps listed one by one in the timing report; longer sequences only get the summary.
+const MAX_REPORTED_STEPS: usize = 100;
+
+/// Offset of one client's clock from the server's, in seconds.
+pub struct ClockOffset {
+    origin: Instant,
+    samples: Mutex<VecDeque<f64>>,
+}
+
+impl ClockOffset {
+    pub fn new(origin: Instant) -> Self {
+        Self {
+            origin,
+            samples: Mutex::new(VecDeque::with_capacity(SAMPLE_WINDOW)),
+        }
+    }
+
+    /// Seconds since the server's origin; the timebase offsets are measured against.
+    pub fn server_secs(&self) -> f64 {
+        self.origin.elapsed().as_secs_f64()
+    }
+
+    /// Record the plugin clock a request was sent at, on its arrival.
+    pub fn record(&self, plugin_clock: f64) {
+        if !plugin_clock.is_finite() {
+            return;
+        }
+        let server_secs = self.server_secs();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        if samples.len() == SAMPLE_WINDOW {
+            samples.pop_front();
+        }
+        samples.push_back(server_secs - plugin_clock);
+    }
+
+    /// `server time - plugin clock`, or `None` before the first sample.
+    pub fn estimate(&self) -> Option<f64> {
+        let samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.iter().copied().reduce(f64::min)
+    }
+}
+
+/// Check the sequence (lead buffer plus its last step) fits `MAX_SEQUENCE_DURATION`.
+pub fn check_sequence_duration(arguments: &Value, lead: Duration) -> Result<(), String> {
+    let total = lead + Duration::from_millis(last_step_ms(arguments));
+    if total > MAX_SEQUENCE_DURATION {
+        return Err(format!(
+            "Sequence runs for {}s including the {}ms lead buffer; the limit is {}s. Split it into several calls.",
+            total.as_secs(),
+            lead.as_millis(),
+            MAX_SEQUENCE_DURATION.as_secs()
+        ));
+    }
+    Ok(())
+}
+
+/// Largest `atMs` of the sequence's steps.
+pub fn last_step_ms(arguments: &Value) -> u64 {
+    arguments
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .map(|steps| steps.iter().filter_map(|s| s["atMs"].as_u64()).max().unwrap_or(0))
+        .unwrap_or(0)
+}
+
+/// Plugin clock at which the sequence starts: `lead` after `server_secs`, shifted by
+/// the estimated offset.
+pub fn plugin_start_clock(server_secs: f64, offset: f64, lead: Duration) -> f64 {
+    server_secs + lead.as_secs_f64() - offset
+}
+
+/// Give every step an absolute `dueClock` on the plugin's clock.
+pub fn apply_due_clocks(arguments: &mut Value, start_clock: f64) {
+    let Some(steps) = arguments.get_mut("steps").and_then(|v| v.as_array_mut()) else {
+        return;
+    };
+    for step in steps.iter_mut() {
+        let at_ms = step.get("atMs").and_then(|v| v.as_u64()).unwrap_or(0);
+        if let Some(obj) = step.as_object_mut() {
+            obj.insert("dueClock".into(), Value::from(start_clock + at_ms as f64 / 1000.0));
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepTiming {
+    pub at_ms: u64,
+    /// Actual minus scheduled firing time; positive is late.
+    pub error_ms: f64,
+}
+
+/// Timing error statistics over the steps the bridge reported.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingReport {
+    pub lead_ms: u64,
+    pub steps_measured: usize,
+    pub mean_error_ms: f64,
+    pub max_late_ms: f64,
+    pub max_early_ms: f64,
+    pub std_dev_ms: f64,
+    /// The first `MAX_REPORTED_STEPS` steps.
+    pub steps: Vec<StepTiming>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub steps_truncated: bool,
+}
+
+/// Build the timing report from the bridge's `timings` (`atMs`, `dueClock`,
+/// `firedClock` per step). `None` when there is nothing to measure.
+pub fn timing_report(timings: &[Value], lead: Duration) -> Option<TimingReport> {
+    let measured: Vec<StepTiming> = timings
+        .iter()
+        .filter_map(|t| {
+            let due = t.get("dueClock")?.as_f64()?;
+            let fired = t.get("firedClock")?.as_f64()?;
+            Some(StepTiming {
+                at_ms: t.get("atMs").and_then(|v| v.as_u64()).unwrap_or(0),
+                error_ms: round_tenth((fired - due) * 1000.0),
+            })
+        })
+        .collect();
+    if measured.is_empty() {
+        return None;
+    }
+
+    let n = measured.len() as f64;
+    let mean = measured.iter().map(|s| s.error_ms).sum::<f64>() / n;
+    let variance = measured.iter().map(|s| (s.error_ms - mean).powi(2)).sum::<f64>() / n;
+    let max_late = measured.iter().map(|s| s.error_ms).fold(0.0, f64::max);
+    let max_early = measured.iter().map(|s| -s.error_ms).fold(0.0, f64::max);
+    let steps_measured = measured.len();
+    let mut steps = measured;
+    steps.truncate(MAX_REPORTED_STEPS);
+
+    Some(TimingReport {
+        lead_ms: lead.as_millis() as u64,
+        steps_measured,
+        mean_error_ms: round_tenth(mean),
+        max_late_ms: round_tenth(max_late),
+        max_early_ms: round_tenth(max_early),
+        std_dev_ms: round_tenth(variance.sqrt()),
+        steps_truncated: steps_measured > steps.len(),
+        steps,
+    })
+}
+
+fn round_tenth(ms: f64) -> f64 {
+    (ms * 10.0).round() / 10.0
+}
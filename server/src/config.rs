@@ -1,30 +1,193 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::path::PathBuf;
 
+use crate::alerts::{self, AlertRule};
+use crate::hooks::{self, Hook};
+use crate::log_diff::{self, Normalizer};
+use crate::log_args::{self, CodeLogMode};
+use crate::profiles::{self, Profile};
+use crate::redact::{self, RedactPatternSpec};
+use crate::request_limits::{DEFAULT_MAX_REQUEST_BYTES, MIN_MAX_REQUEST_BYTES};
+use crate::tool_history::{DEFAULT_TOOL_HISTORY_SIZE, MAX_TOOL_HISTORY_SIZE};
+
+/// The bridge auth token. Its `Debug` output is redacted so it can't end up in a log
+/// line through `?config`.
+#[derive(Clone)]
+pub struct AuthToken(String);
+
+impl AuthToken {
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// The token itself, for comparing against request headers.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Short, non-reversible identifier for the token, so a supervisor can tell which
+    /// token is in use without seeing it.
+    pub fn fingerprint(&self) -> String {
+        log_args::fingerprint(&self.0)
+    }
+}
+
+impl std::fmt::Debug for AuthToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("AuthToken([redacted])")
+    }
+}
+
+/// Server configuration, normally read from `YIPPIE_*` environment variables.
+///
+/// `Config::default()` is what the server runs with when none of them are set. Fields
+/// may be added in minor releases, so construct it with `default()` or `from_env()`
+/// and change the fields you need.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct Config {
     pub port: u16,
-    pub token: Option<String>,
+    pub token: Option<AuthToken>,
     pub capture_dir: PathBuf,
+    /// Effective redaction patterns (defaults minus disabled, plus custom).
+    pub redact_patterns: Vec<RedactPatternSpec>,
+    /// When true, OS-level screenshots are refused without spawning a subprocess.
+    pub disable_os_capture: bool,
+    /// Persist log entries to `<capture_dir>/logs/` and index them for archive search.
+    pub log_archive: bool,
+    /// Delete archive segments older than this (`YIPPIE_LOG_ARCHIVE_RETENTION_DAYS`).
+    /// `None` keeps them until the segment cap.
+    pub log_archive_retention: Option<std::time::Duration>,
+    /// Prefix for tool names as MCP clients see them. The plugin protocol always
+    /// uses the canonical `studio-` names.
+    pub tool_prefix: String,
+    /// Minify `code` arguments of run_script/test_script unless a call passes `minify: false`.
+    pub minify_code: bool,
+    /// Keep ANSI foreground colours in logs and results as `[red]...[/red]` markers
+    /// instead of dropping them (`YIPPIE_ANSI_MARKERS`).
+    pub ansi_markers: bool,
+    /// Run the idle cleanup after this long without an MCP request. `None` disables it.
+    pub idle_timeout: Option<std::time::Duration>,
+    /// Also stop a running playtest during idle cleanup.
+    pub idle_stop_playtest: bool,
+    /// Keep an instance path index fed by plugin tree deltas.
+    pub tree_index: bool,
+    /// Services the plugin watches for the path index.
+    pub tree_roots: Vec<String>,
+    /// How long cached results of cacheable tools stay valid. `None` disables the cache.
+    pub cache_ttl: Option<std::time::Duration>,
+    /// Most results kept in the response cache.
+    pub cache_size: usize,
+    /// List and accept tools marked experimental. Off with `YIPPIE_DISABLE_EXPERIMENTAL_TOOLS`.
+    pub experimental_tools: bool,
+    /// Offer debug-only tools and bridge endpoints, e.g. the routing table
+    /// (`YIPPIE_DEBUG_ENDPOINTS`).
+    pub debug_endpoints: bool,
+    /// Canonical names of tools switched off by the operator (`YIPPIE_DISABLED_TOOLS`):
+    /// left out of `tools/list` and refused.
+    pub disabled_tools: BTreeSet<String>,
+    /// Canonical tool names sent to another client role than the built-in routing
+    /// picks (`YIPPIE_ROUTING_OVERRIDES`), for plugin builds that handle tools elsewhere.
+    pub routing_overrides: HashMap<String, Role>,
+    /// How `code` arguments appear in debug logs of tool calls (`YIPPIE_LOG_CODE`).
+    pub log_code: CodeLogMode,
+    /// Alerting hooks (`YIPPIE_ALERT_RULES`). Empty disables alerting.
+    pub alert_rules: Vec<AlertRule>,
+    /// Result hooks (`YIPPIE_HOOKS`), compiled. Empty disables hooks.
+    pub hooks: Vec<Hook>,
+    /// Seed for deterministic mode (`YIPPIE_DETERMINISTIC`); `None` runs normally.
+    pub deterministic_seed: Option<u64>,
+    /// Share of the HttpService limit at which bridge traffic raises a warning
+    /// (`YIPPIE_HTTP_BUDGET_WARN`).
+    pub http_budget_warn: f64,
+    /// Lead buffer before the first step of a latency-compensated virtualuser sequence
+    /// (`YIPPIE_INPUT_LEAD_MS`).
+    pub input_lead: std::time::Duration,
+    /// Normalizers `studio-logs_diff_sessions` applies before comparing lines
+    /// (`YIPPIE_LOG_DIFF_NORMALIZERS` merged over the built-ins), compiled.
+    pub log_diff_normalizers: Vec<Normalizer>,
+    /// Named setting overrides (`YIPPIE_PROFILES`), switchable with studio-set_profile.
+    pub profiles: BTreeMap<String, Profile>,
+    /// Profile active at startup (`YIPPIE_PROFILE`, or `--profile`). `None` uses the
+    /// base settings.
+    pub profile: Option<String>,
+    /// Annotate tracebacks in script results with source lines. Off with
+    /// `YIPPIE_DISABLE_TRACEBACK_CONTEXT`.
+    pub traceback_context: bool,
+    /// Warn about unknown fields in /register and /push bodies (`YIPPIE_BRIDGE_STRICT`).
+    pub strict_bridge: bool,
+    /// Longest MCP message read from stdin; longer ones are refused unread
+    /// (`YIPPIE_MAX_REQUEST_BYTES`).
+    pub max_request_bytes: usize,
+    /// Recent tool calls kept for studio-get_tool_history (`YIPPIE_TOOL_HISTORY_SIZE`).
+    /// 0 keeps none.
+    pub tool_history_size: usize,
+    /// Answer requests before the MCP handshake has finished and accept repeated
+    /// `initialize`, for clients that don't follow the lifecycle
+    /// (`YIPPIE_LENIENT_LIFECYCLE`).
+    pub lenient_lifecycle: bool,
 }
 
-pub fn load() -> Result<Config> {
-    let port: u16 = std::env::var("YIPPIE_PORT")
-        .ok()
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 30 * 60;
+
+const DEFAULT_LOG_ARCHIVE_RETENTION_DAYS: u64 = 30;
+
+const DEFAULT_CACHE_TTL_SECS: u64 = 30;
+const DEFAULT_CACHE_SIZE: usize = 64;
+
+const DEFAULT_HTTP_BUDGET_WARN: f64 = 0.8;
+
+const DEFAULT_INPUT_LEAD_MS: u64 = 150;
+const MAX_INPUT_LEAD_MS: u64 = 5_000;
+
+const DEFAULT_TREE_ROOTS: &[&str] = &[
+    "Workspace",
+    "ReplicatedStorage",
+    "ServerScriptService",
+    "ServerStorage",
+    "StarterGui",
+    "StarterPlayer",
+];
+
+/// Canonical tool-name prefix used between the server and the plugin.
+pub const DEFAULT_TOOL_PREFIX: &str = "studio-";
+
+impl Config {
+    /// Read the configuration from `YIPPIE_*` environment variables. Malformed values
+    /// are errors naming the variable.
+    pub fn from_env() -> Result<Self> {
+        from_vars(|name| std::env::var(name).ok())
+    }
+}
+
+impl Default for Config {
+    /// The configuration with no `YIPPIE_*` variables set.
+    fn default() -> Self {
+        from_vars(|_| None).expect("the default configuration is valid")
+    }
+}
+
+/// Parse a boolean flag ("1", "true", "yes", "on" — case-insensitive).
+fn flag(value: Option<String>) -> bool {
+    value
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+        .unwrap_or(false)
+}
+
+/// Build the configuration from variables looked up by name.
+fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Config> {
+    let env_flag = |name: &str| flag(var(name));
+
+    let port: u16 = var("YIPPIE_PORT")
         .and_then(|v| v.parse().ok())
         .unwrap_or(3334);
 
-    let token = std::env::var("YIPPIE_TOKEN").ok();
-    if token.is_none() {
-        eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-        eprintln!("  No YIPPIE_TOKEN set. Auth is DISABLED.");
-        eprintln!("  Set YIPPIE_TOKEN to require a Bearer token.");
-        eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
-    }
+    let token = var("YIPPIE_TOKEN").map(AuthToken);
 
-    let capture_dir = std::env::var("YIPPIE_CAPTURE_DIR")
+    let capture_dir = var("YIPPIE_CAPTURE_DIR")
         .map(PathBuf::from)
-        .unwrap_or_else(|_| {
+        .unwrap_or_else(|| {
             // Default to ~/.roblox-captures/ so it works regardless of cwd
             // (Claude Desktop launches with cwd=/ which is read-only on macOS)
             std::env::var("HOME").map(PathBuf::from)
@@ -32,9 +195,350 @@ pub fn load() -> Result<Config> {
                 .join(".roblox-captures")
         });
 
+    // Custom patterns as a JSON object: {"pattern_name": "regex", ...}
+    let custom_patterns = match var("YIPPIE_REDACT_PATTERNS") {
+        Some(raw) => serde_json::from_str::<BTreeMap<String, String>>(&raw)
+            .context("YIPPIE_REDACT_PATTERNS must be a JSON object of name -> regex")?
+            .into_iter()
+            .map(|(name, regex)| RedactPatternSpec { name, regex })
+            .collect(),
+        None => vec![],
+    };
+    let disabled_patterns: Vec<String> = var("YIPPIE_REDACT_DISABLE")
+        .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    let redact_patterns = redact::pattern_specs(custom_patterns, &disabled_patterns);
+
+    let tool_prefix = var("YIPPIE_TOOL_PREFIX")
+        .unwrap_or_else(|| DEFAULT_TOOL_PREFIX.to_string());
+    // MCP clients accept tool names of letters, digits, '_', '-' and '.'
+    if tool_prefix.is_empty()
+        || tool_prefix.len() > 32
+        || !tool_prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+    {
+        anyhow::bail!(
+            "YIPPIE_TOOL_PREFIX must be 1-32 characters of letters, digits, '_', '-' or '.' (got {tool_prefix:?})"
+        );
+    }
+
+    // 0 disables idle cleanup
+    let idle_timeout_secs: u64 = match var("YIPPIE_IDLE_TIMEOUT_SECS") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .with_context(|| format!("YIPPIE_IDLE_TIMEOUT_SECS must be a number of seconds (got {v:?})"))?,
+        None => DEFAULT_IDLE_TIMEOUT_SECS,
+    };
+    let idle_timeout = (idle_timeout_secs > 0).then(|| std::time::Duration::from_secs(idle_timeout_secs));
+
+    // 0 keeps archive segments until the segment cap
+    let retention_days: u64 = match var("YIPPIE_LOG_ARCHIVE_RETENTION_DAYS") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .with_context(|| format!("YIPPIE_LOG_ARCHIVE_RETENTION_DAYS must be a number of days (got {v:?})"))?,
+        None => DEFAULT_LOG_ARCHIVE_RETENTION_DAYS,
+    };
+    let log_archive_retention =
+        (retention_days > 0).then(|| std::time::Duration::from_secs(retention_days.saturating_mul(24 * 60 * 60)));
+
+    // 0 for either disables the response cache
+    let cache_ttl_secs: u64 = match var("YIPPIE_CACHE_TTL_SECS") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .with_context(|| format!("YIPPIE_CACHE_TTL_SECS must be a number of seconds (got {v:?})"))?,
+        None => DEFAULT_CACHE_TTL_SECS,
+    };
+    let cache_size: usize = match var("YIPPIE_CACHE_SIZE") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .with_context(|| format!("YIPPIE_CACHE_SIZE must be a number of entries (got {v:?})"))?,
+        None => DEFAULT_CACHE_SIZE,
+    };
+    let cache_ttl = (cache_ttl_secs > 0 && cache_size > 0)
+        .then(|| std::time::Duration::from_secs(cache_ttl_secs));
+
+    let log_code = match var("YIPPIE_LOG_CODE") {
+        Some(v) => CodeLogMode::parse(&v)
+            .with_context(|| format!("YIPPIE_LOG_CODE must be full, truncated or omitted (got {v:?})"))?,
+        None => CodeLogMode::default(),
+    };
+
+    let alert_rules = match var("YIPPIE_ALERT_RULES") {
+        Some(raw) => alerts::parse_rules(&raw)?,
+        None => Vec::new(),
+    };
+
+    let hooks = match var("YIPPIE_HOOKS") {
+        Some(raw) => hooks::parse_hooks(&raw)?,
+        None => Vec::new(),
+    };
+
+    let deterministic_seed = if env_flag("YIPPIE_DETERMINISTIC") {
+        match var("YIPPIE_DETERMINISTIC_SEED") {
+            Some(v) => Some(v.trim().parse().with_context(|| {
+                format!("YIPPIE_DETERMINISTIC_SEED must be a non-negative integer (got {v:?})")
+            })?),
+            None => Some(0),
+        }
+    } else {
+        None
+    };
+
+    let http_budget_warn: f64 = match var("YIPPIE_HTTP_BUDGET_WARN") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .ok()
+            .filter(|f: &f64| *f > 0.0 && *f <= 1.0)
+            .with_context(|| format!("YIPPIE_HTTP_BUDGET_WARN must be a fraction above 0 and at most 1 (got {v:?})"))?,
+        None => DEFAULT_HTTP_BUDGET_WARN,
+    };
+
+    let input_lead_ms: u64 = match var("YIPPIE_INPUT_LEAD_MS") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .ok()
+            .filter(|ms| *ms <= MAX_INPUT_LEAD_MS)
+            .with_context(|| format!("YIPPIE_INPUT_LEAD_MS must be 0-{MAX_INPUT_LEAD_MS} milliseconds (got {v:?})"))?,
+        None => DEFAULT_INPUT_LEAD_MS,
+    };
+
+    let max_request_bytes: usize = match var("YIPPIE_MAX_REQUEST_BYTES") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .ok()
+            .filter(|n| *n >= MIN_MAX_REQUEST_BYTES)
+            .with_context(|| {
+                format!("YIPPIE_MAX_REQUEST_BYTES must be a number of bytes, at least {MIN_MAX_REQUEST_BYTES} (got {v:?})")
+            })?,
+        None => DEFAULT_MAX_REQUEST_BYTES,
+    };
+
+    let tool_history_size: usize = match var("YIPPIE_TOOL_HISTORY_SIZE") {
+        Some(v) => v
+            .trim()
+            .parse()
+            .ok()
+            .filter(|n| *n <= MAX_TOOL_HISTORY_SIZE)
+            .with_context(|| format!("YIPPIE_TOOL_HISTORY_SIZE must be 0-{MAX_TOOL_HISTORY_SIZE} calls (got {v:?})"))?,
+        None => DEFAULT_TOOL_HISTORY_SIZE,
+    };
+
+    let log_diff_normalizers = match var("YIPPIE_LOG_DIFF_NORMALIZERS") {
+        Some(raw) => log_diff::parse_normalizers(&raw)?,
+        None => log_diff::default_normalizers(),
+    };
+
+    let profiles = match var("YIPPIE_PROFILES") {
+        Some(raw) => profiles::parse_profiles(&raw)?,
+        None => BTreeMap::new(),
+    };
+    let profile = var("YIPPIE_PROFILE")
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+
+    let disabled_tools = match var("YIPPIE_DISABLED_TOOLS") {
+        Some(raw) => parse_disabled_tools(&raw, &tool_prefix)?,
+        None => BTreeSet::new(),
+    };
+
+    let routing_overrides = match var("YIPPIE_ROUTING_OVERRIDES") {
+        Some(raw) => parse_routing_overrides(&raw, &tool_prefix)?,
+        None => HashMap::new(),
+    };
+
+    let tree_roots: Vec<String> = match var("YIPPIE_TREE_ROOTS") {
+        Some(v) => v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect(),
+        None => DEFAULT_TREE_ROOTS.iter().map(|s| s.to_string()).collect(),
+    };
+
     Ok(Config {
         port,
         token,
         capture_dir,
+        redact_patterns,
+        disable_os_capture: env_flag("YIPPIE_DISABLE_OS_CAPTURE"),
+        log_archive: env_flag("YIPPIE_LOG_ARCHIVE"),
+        log_archive_retention,
+        tool_prefix,
+        minify_code: env_flag("YIPPIE_MINIFY_CODE"),
+        ansi_markers: env_flag("YIPPIE_ANSI_MARKERS"),
+        idle_timeout,
+        idle_stop_playtest: env_flag("YIPPIE_IDLE_STOP_PLAYTEST"),
+        tree_index: env_flag("YIPPIE_TREE_INDEX"),
+        tree_roots,
+        cache_ttl,
+        cache_size,
+        experimental_tools: !env_flag("YIPPIE_DISABLE_EXPERIMENTAL_TOOLS"),
+        debug_endpoints: env_flag("YIPPIE_DEBUG_ENDPOINTS"),
+        disabled_tools,
+        routing_overrides,
+        log_code,
+        alert_rules,
+        hooks,
+        deterministic_seed,
+        http_budget_warn,
+        input_lead: std::time::Duration::from_millis(input_lead_ms),
+        log_diff_normalizers,
+        profiles,
+        profile,
+        traceback_context: !env_flag("YIPPIE_DISABLE_TRACEBACK_CONTEXT"),
+        strict_bridge: env_flag("YIPPIE_BRIDGE_STRICT"),
+        max_request_bytes,
+        tool_history_size,
+        lenient_lifecycle: env_flag("YIPPIE_LENIENT_LIFECYCLE"),
     })
 }
+
+/// Comma-separated tool names, canonical or with the client-facing prefix. An unknown
+/// name is an error rather than ignored, so a typo can't leave a tool exposed.
+fn parse_disabled_tools(raw: &str, prefix: &str) -> Result<BTreeSet<String>> {
+    let mut tools = BTreeSet::new();
+    for name in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let canonical = canonical_tool_name(name, prefix);
+        if !crate::mcp_stdio::is_tool_name(&canonical) {
+            anyhow::bail!("YIPPIE_DISABLED_TOOLS: unknown tool '{name}'");
+        }
+        tools.insert(canonical);
+    }
+    Ok(tools)
+}
+
+/// Client role a tool request is routed to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// The edit-mode plugin.
+    Plugin,
+    /// The playtest bridge running inside the play session.
+    Bridge,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Plugin => "plugin",
+            Role::Bridge => "bridge",
+        }
+    }
+}
+
+/// Comma-separated `tool=role` pairs, e.g. `studio-get_gui_tree=plugin`; the role is
+/// `plugin` or `bridge`. Unknown tools only get a warning: an override for a tool this
+/// build doesn't have routes nothing, unlike a disabled tool left exposed.
+fn parse_routing_overrides(raw: &str, prefix: &str) -> Result<HashMap<String, Role>> {
+    let mut overrides = HashMap::new();
+    for pair in raw.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let Some((name, role)) = pair.split_once('=') else {
+            anyhow::bail!("YIPPIE_ROUTING_OVERRIDES: expected tool=plugin or tool=bridge, got '{pair}'");
+        };
+        let (name, role) = (name.trim(), role.trim());
+        let role = match role.to_ascii_lowercase().as_str() {
+            "plugin" => Role::Plugin,
+            "bridge" => Role::Bridge,
+            _ => anyhow::bail!("YIPPIE_ROUTING_OVERRIDES: role for '{name}' must be plugin or bridge (got '{role}')"),
+        };
+        let canonical = canonical_tool_name(name, prefix);
+        if !crate::mcp_stdio::is_tool_name(&canonical) {
+            tracing::warn!(tool = %name, "YIPPIE_ROUTING_OVERRIDES: unknown tool, override ignored");
+            continue;
+        }
+        overrides.insert(canonical, role);
+    }
+    Ok(overrides)
+}
+
+/// `name` with the default prefix in place of a custom client-facing one.
+fn canonical_tool_name(name: &str, prefix: &str) -> String {
+    match name.strip_prefix(prefix) {
+        Some(rest) if prefix != DEFAULT_TOOL_PREFIX => format!("{DEFAULT_TOOL_PREFIX}{rest}"),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_tools_accept_canonical_and_prefixed_names() {
+        let tools = parse_disabled_tools(" studio-run_script, rbx-test_script ,", "rbx-").unwrap();
+        let expected: BTreeSet<String> = ["studio-run_script", "studio-test_script"].map(String::from).into();
+        assert_eq!(tools, expected);
+    }
+
+    #[test]
+    fn disabled_tools_refuse_an_unknown_name() {
+        let error = parse_disabled_tools("studio-run_script,studio-run_scirpt", DEFAULT_TOOL_PREFIX).unwrap_err();
+        assert_eq!(error.to_string(), "YIPPIE_DISABLED_TOOLS: unknown tool 'studio-run_scirpt'");
+    }
+
+    #[test]
+    fn routing_overrides_accept_either_role_in_any_case() {
+        let overrides = parse_routing_overrides(" rbx-run_script = Bridge, studio-get_output_since=plugin ,", "rbx-").unwrap();
+        let expected: HashMap<String, Role> =
+            [("studio-run_script".to_string(), Role::Bridge), ("studio-get_output_since".to_string(), Role::Plugin)].into();
+        assert_eq!(overrides, expected);
+    }
+
+    #[test]
+    fn routing_overrides_refuse_an_unknown_role() {
+        let error = parse_routing_overrides("studio-run_script=server", DEFAULT_TOOL_PREFIX).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "YIPPIE_ROUTING_OVERRIDES: role for 'studio-run_script' must be plugin or bridge (got 'server')"
+        );
+        let error = parse_routing_overrides("studio-run_script", DEFAULT_TOOL_PREFIX).unwrap_err();
+        assert!(error.to_string().contains("expected tool=plugin or tool=bridge"), "{error}");
+    }
+
+    #[test]
+    fn routing_overrides_warn_about_and_skip_an_unknown_tool() {
+        #[derive(Clone, Default)]
+        struct Captured(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Captured {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt().with_writer(move || writer.clone()).with_ansi(false).finish();
+        let overrides = tracing::subscriber::with_default(subscriber, || {
+            parse_routing_overrides("studio-run_scirpt=bridge,studio-run_script=bridge", DEFAULT_TOOL_PREFIX)
+        })
+        .unwrap();
+
+        assert_eq!(overrides.keys().collect::<Vec<_>>(), ["studio-run_script"]);
+        let logged = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        assert!(logged.contains("WARN"), "{logged}");
+        assert!(logged.contains("unknown tool, override ignored"), "{logged}");
+        assert!(logged.contains("studio-run_scirpt"), "{logged}");
+    }
+
+    #[test]
+    fn max_request_bytes_has_a_floor() {
+        let config = |value: Option<&str>| {
+            let value = value.map(String::from);
+            from_vars(move |name| value.clone().filter(|_| name == "YIPPIE_MAX_REQUEST_BYTES"))
+        };
+        assert_eq!(config(None).unwrap().max_request_bytes, DEFAULT_MAX_REQUEST_BYTES);
+        assert_eq!(config(Some(" 1048576 ")).unwrap().max_request_bytes, 1024 * 1024);
+        assert_eq!(config(Some(&MIN_MAX_REQUEST_BYTES.to_string())).unwrap().max_request_bytes, MIN_MAX_REQUEST_BYTES);
+        for refused in ["1024", "16MB", "-1"] {
+            let error = config(Some(refused)).unwrap_err().to_string();
+            assert!(error.starts_with("YIPPIE_MAX_REQUEST_BYTES must be a number of bytes, at least 65536"), "{error}");
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Per-client reorder window for numbered bridge events.
+//!
+//! Plugins that registered with the `event_seq` feature number their events from 1.
+//! Two /push requests sent back to back can be handled in either order, so events
+//! that arrive ahead of a missing one wait here until it shows up. If it never does
+//! (the push failed), the gap is skipped after `GAP_TIMEOUT` and counted.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::types::BridgeEvent;
+
+/// How long events wait for a missing earlier one.
+pub const GAP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Events held per client at most. Past this the gap is skipped right away.
+const MAX_PENDING_EVENTS: usize = 1000;
+
+/// Bridge event feature names the server accepts at /register.
+pub const EVENT_SEQ_FEATURE: &str = "event_seq";
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+pub struct EventOrderStats {
+    /// Events that arrived ahead of an earlier one and were held back.
+    pub reordered: u64,
+    /// Gaps skipped because the missing events never arrived.
+    pub gaps: u64,
+    /// Events lost in skipped gaps.
+    pub missing: u64,
+    /// Events that arrived after their gap was skipped (or twice), and were dropped.
+    pub late: u64,
+}
+
+pub struct EventOrder {
+    next: u64,
+    pending: BTreeMap<u64, BridgeEvent>,
+    /// When events first started waiting on the current gap.
+    gap_opened: Option<Instant>,
+    /// A gap timer is running for this client.
+    pub gap_timer_armed: bool,
+}
+
+impl Default for EventOrder {
+    fn default() -> Self {
+        Self {
+            next: 1,
+            pending: BTreeMap::new(),
+            gap_opened: None,
+            gap_timer_armed: false,
+        }
+    }
+}
+
+impl EventOrder {
+    /// Take a numbered event. Returns the events that can now be applied, in order.
+    pub fn accept(&mut self, seq: u64, event: BridgeEvent, stats: &mut EventOrderStats) -> Vec<BridgeEvent> {
+        if seq < self.next || self.pending.contains_key(&seq) {
+            stats.late += 1;
+            tracing::warn!(seq, expected = self.next, event_type = %event.event_type, "Dropping late or duplicate bridge event");
+            return Vec::new();
+        }
+        if seq > self.next {
+            stats.reordered += 1;
+        }
+        self.pending.insert(seq, event);
+        if self.pending.len() > MAX_PENDING_EVENTS {
+            return self.skip_gap(stats);
+        }
+        self.drain_ready()
+    }
+
+    /// Events are waiting on a missing earlier one.
+    pub fn has_gap(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// When the current gap will be skipped.
+    pub fn gap_deadline(&self) -> Option<Instant> {
+        self.gap_opened.map(|opened| opened + GAP_TIMEOUT)
+    }
+
+    /// Give up on the missing events and continue from the earliest held one.
+    pub fn skip_gap(&mut self, stats: &mut EventOrderStats) -> Vec<BridgeEvent> {
+        let Some(&first) = self.pending.keys().next() else {
+            return Vec::new();
+        };
+        let missing = first - self.next;
+        tracing::warn!(from = self.next, to = first - 1, missing, "Skipping gap in bridge event sequence");
+        stats.gaps += 1;
+        stats.missing += missing;
+        self.next = first;
+        self.drain_ready()
+    }
+
+    fn drain_ready(&mut self) -> Vec<BridgeEvent> {
+        let mut ready = Vec::new();
+        while let Some(event) = self.pending.remove(&self.next) {
+            ready.push(event);
+            self.next += 1;
+        }
+        if ready.is_empty() && self.gap_opened.is_some() {
+            return ready;
+        }
+        // Either nothing waits any more, or what remains waits on a new gap
+        self.gap_opened = (!self.pending.is_empty()).then(Instant::now);
+        ready
+    }
+}
+
+/// Reorder windows of all clients that number their events, plus totals.
+#[derive(Default)]
+pub struct EventOrdering {
+    pub clients: std::collections::HashMap<String, EventOrder>,
+    pub stats: EventOrderStats,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn event(seq: u64) -> BridgeEvent {
+        BridgeEvent { event_type: "studio-log".into(), data: json!({ "message": format!("event {seq}") }), seq: Some(seq) }
+    }
+
+    fn accept(order: &mut EventOrder, stats: &mut EventOrderStats, seq: u64) -> Vec<u64> {
+        order.accept(seq, event(seq), stats).iter().map(|e| e.seq.unwrap()).collect()
+    }
+
+    #[test]
+    fn events_in_order_pass_straight_through() {
+        let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+        for seq in 1..=3 {
+            assert_eq!(accept(&mut order, &mut stats, seq), [seq]);
+        }
+        assert!(!order.has_gap());
+        assert_eq!(order.gap_deadline(), None);
+        assert_eq!(stats.reordered + stats.gaps + stats.late, 0);
+    }
+
+    #[test]
+    fn events_ahead_of_a_missing_one_wait_for_it() {
+        let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+        assert_eq!(accept(&mut order, &mut stats, 1), [1]);
+        assert!(accept(&mut order, &mut stats, 3).is_empty());
+        assert!(accept(&mut order, &mut stats, 4).is_empty());
+        assert!(order.has_gap());
+        let deadline = order.gap_deadline().unwrap();
+        assert!(deadline > Instant::now() && deadline <= Instant::now() + GAP_TIMEOUT);
+
+        assert_eq!(accept(&mut order, &mut stats, 2), [2, 3, 4]);
+        assert!(!order.has_gap());
+        assert_eq!(order.gap_deadline(), None);
+        assert_eq!((stats.reordered, stats.gaps, stats.missing, stats.late), (2, 0, 0, 0));
+    }
+
+    #[test]
+    fn duplicates_and_events_behind_the_window_are_dropped() {
+        let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+        accept(&mut order, &mut stats, 1);
+        accept(&mut order, &mut stats, 2);
+        assert!(accept(&mut order, &mut stats, 2).is_empty());
+        assert!(accept(&mut order, &mut stats, 1).is_empty());
+        // A duplicate of an event still held back
+        accept(&mut order, &mut stats, 5);
+        assert!(accept(&mut order, &mut stats, 5).is_empty());
+        assert_eq!(stats.late, 3);
+        assert_eq!(accept(&mut order, &mut stats, 3), [3]);
+        assert_eq!(accept(&mut order, &mut stats, 4), [4, 5]);
+    }
+
+    #[test]
+    fn skipping_a_gap_flushes_what_waited_and_counts_the_missing() {
+        let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+        accept(&mut order, &mut stats, 1);
+        accept(&mut order, &mut stats, 4);
+        accept(&mut order, &mut stats, 5);
+        accept(&mut order, &mut stats, 8);
+        let flushed: Vec<u64> = order.skip_gap(&mut stats).iter().map(|e| e.seq.unwrap()).collect();
+        assert_eq!(flushed, [4, 5]);
+        assert_eq!((stats.gaps, stats.missing), (1, 2));
+        // What is left waits on a new gap, with a fresh deadline
+        assert!(order.has_gap());
+        assert!(order.gap_deadline().is_some());
+        let flushed: Vec<u64> = order.skip_gap(&mut stats).iter().map(|e| e.seq.unwrap()).collect();
+        assert_eq!(flushed, [8]);
+        assert_eq!((stats.gaps, stats.missing), (2, 4));
+        assert!(!order.has_gap());
+        // A straggler from a skipped gap is late
+        assert!(accept(&mut order, &mut stats, 3).is_empty());
+        assert_eq!(stats.late, 1);
+        assert!(order.skip_gap(&mut stats).is_empty());
+    }
+
+    #[test]
+    fn too_many_held_events_skip_the_gap_at_once() {
+        let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+        for seq in 2..=MAX_PENDING_EVENTS as u64 + 1 {
+            assert!(accept(&mut order, &mut stats, seq).is_empty());
+        }
+        let flushed = accept(&mut order, &mut stats, MAX_PENDING_EVENTS as u64 + 2);
+        assert_eq!(flushed.len(), MAX_PENDING_EVENTS + 1);
+        assert_eq!(flushed.first(), Some(&2));
+        assert_eq!((stats.gaps, stats.missing), (1, 1));
+    }
+
+    #[test]
+    fn shuffled_delivery_with_gaps_and_duplicates_comes_out_once_and_in_order() {
+        use crate::providers::{Rng, SeededRng};
+
+        for seed in 0..64 {
+            let rng = SeededRng::new(seed);
+            let total = 50 + rng.next_u64() % 200;
+            // Drop about one event in eight and send about one in six twice
+            let sent: Vec<u64> = (1..=total).filter(|_| !rng.next_u64().is_multiple_of(8)).collect();
+            let mut arrivals = sent.clone();
+            for &seq in &sent {
+                if rng.next_u64().is_multiple_of(6) {
+                    arrivals.push(seq);
+                }
+            }
+            let duplicates = (arrivals.len() - sent.len()) as u64;
+            for i in (1..arrivals.len()).rev() {
+                arrivals.swap(i, (rng.next_u64() % (i as u64 + 1)) as usize);
+            }
+
+            let (mut order, mut stats) = (EventOrder::default(), EventOrderStats::default());
+            let mut delivered = Vec::new();
+            for &seq in &arrivals {
+                delivered.extend(accept(&mut order, &mut stats, seq));
+            }
+            while order.has_gap() {
+                delivered.extend(order.skip_gap(&mut stats).iter().map(|e| e.seq.unwrap()));
+            }
+
+            assert!(delivered.windows(2).all(|pair| pair[0] < pair[1]), "seed {seed}: {delivered:?}");
+            assert_eq!(delivered, sent, "seed {seed}");
+            // Dropped events past the last one sent leave no gap
+            let last = sent.last().copied().unwrap_or(0);
+            let dropped: Vec<u64> = (1..last).filter(|seq| sent.binary_search(seq).is_err()).collect();
+            let runs = dropped.iter().filter(|&&seq| seq == 1 || sent.binary_search(&(seq - 1)).is_ok()).count() as u64;
+            assert_eq!((stats.gaps, stats.missing, stats.late), (runs, dropped.len() as u64, duplicates), "seed {seed}");
+        }
+    }
+}
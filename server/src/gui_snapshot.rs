@@ -0,0 +1,909 @@
+//! `studio-gui_tree` and `studio-gui_click`: the player's GUI as snapshots the server
+//! keeps, so a large tree comes back in pages and a click can name its button by text.
+//!
+//! A snapshot is one `studio-get_gui_tree` answer from the bridge (the input probe
+//! walks PlayerGui on the client) at the widest limits. Its shape is checked here and
+//! flattened into elements in tree order, each with its full path. A new call reuses
+//! a snapshot taken less than `SNAPSHOT_REUSE` ago, a few frames, and the pages of a
+//! cursor always read the snapshot they started from.
+//!
+//! Clicks resolve their selector against the player's latest snapshot when it is at
+//! most `maxAgeMs` old, from the running playtest session and taken after the last
+//! click (which likely changed the GUI). Otherwise, or when the selector matches
+//! nothing there, a fresh snapshot is taken.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Limits asked of the probe; it clamps to these anyway.
+pub const SNAPSHOT_MAX_DEPTH: u64 = 16;
+pub const SNAPSHOT_MAX_NODES: u64 = 1000;
+
+/// A snapshot younger than this answers a new studio-gui_tree call without asking
+/// the client again.
+pub const SNAPSHOT_REUSE: Duration = Duration::from_millis(100);
+
+/// Snapshots kept for cursors and clicks; older ones are dropped.
+const MAX_STORED_SNAPSHOTS: usize = 8;
+
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 500;
+
+/// How old a snapshot studio-gui_click may resolve against, by default and at most.
+pub const DEFAULT_CLICK_MAX_AGE_MS: u64 = 2_000;
+pub const MAX_CLICK_MAX_AGE_MS: u64 = 60_000;
+
+/// A click landing further than this from the snapshot's centre means the element
+/// moved in between.
+const MOVED_THRESHOLD_PX: f64 = 2.0;
+
+const BUTTON_CLASSES: &[&str] = &["TextButton", "ImageButton"];
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq)]
+pub struct Vec2 {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// What the probe reports. Field names are the wire contract of `guiTree` in
+/// `input_probe_source.lua`, plus `player` from the bridge.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginTree {
+    root: String,
+    tree: Option<PluginNode>,
+    node_count: usize,
+    truncated: bool,
+    viewport: Vec2,
+    #[serde(default)]
+    player: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginNode {
+    name: String,
+    class_name: String,
+    #[serde(default)]
+    position: Option<Vec2>,
+    #[serde(default)]
+    size: Option<Vec2>,
+    #[serde(default)]
+    visible: Option<bool>,
+    #[serde(default)]
+    interactable: Option<bool>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    z_index: Option<i64>,
+    #[serde(default)]
+    enabled: Option<bool>,
+    #[serde(default)]
+    children: Vec<PluginNode>,
+    #[serde(default)]
+    omitted_children: Option<u64>,
+}
+
+/// One GUI object of a snapshot.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuiElement {
+    /// Position in the snapshot, in tree order.
+    pub index: usize,
+    pub path: String,
+    pub name: String,
+    pub class_name: String,
+    pub depth: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>,
+    /// Screen space, top bar inset included (what studio-click_gui_button clicks in).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<Vec2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<Vec2>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visible: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interactable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z_index: Option<i64>,
+    /// LayerCollectors only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Children past the depth limit, not in the snapshot.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub omitted_children: Option<u64>,
+}
+
+impl GuiElement {
+    pub fn center(&self) -> Option<Vec2> {
+        let (position, size) = (self.position?, self.size?);
+        Some(Vec2 { x: position.x + size.x / 2.0, y: position.y + size.y / 2.0 })
+    }
+
+    fn is_button(&self) -> bool {
+        BUTTON_CLASSES.contains(&self.class_name.as_str())
+    }
+
+    fn summary(&self) -> Value {
+        json!({ "path": self.path, "className": self.class_name, "text": self.text, "center": self.center() })
+    }
+}
+
+pub struct GuiSnapshot {
+    pub id: String,
+    pub player: Option<String>,
+    /// Full name of the walked root.
+    pub root: String,
+    /// The `root` argument it was taken for; `None` is the whole PlayerGui.
+    pub requested_root: Option<String>,
+    pub visible_only: bool,
+    /// Playtest session at the time, so a snapshot from an earlier session isn't used.
+    pub session_id: Option<String>,
+    pub taken: Instant,
+    pub taken_at: String,
+    /// The probe hit its node limit; elements past it are missing.
+    pub truncated: bool,
+    pub viewport: Vec2,
+    pub elements: Vec<GuiElement>,
+}
+
+impl GuiSnapshot {
+    /// Check the probe's answer and flatten its tree.
+    pub fn from_plugin(
+        result: Value,
+        id: String,
+        request: &TreeRequest,
+        session_id: Option<String>,
+        taken_at: String,
+    ) -> Result<Self, String> {
+        let tree: PluginTree =
+            serde_json::from_value(result).map_err(|e| format!("Unexpected GUI tree from the client: {e}"))?;
+        let mut elements = Vec::with_capacity(tree.node_count);
+        if let Some(root) = tree.tree {
+            flatten(root, &tree.root, 0, None, &mut elements)?;
+        }
+        if elements.len() != tree.node_count {
+            return Err(format!(
+                "The client reported {} GUI objects but sent {}",
+                tree.node_count,
+                elements.len()
+            ));
+        }
+        Ok(Self {
+            id,
+            player: tree.player,
+            root: tree.root,
+            requested_root: request.root.clone(),
+            visible_only: request.visible_only,
+            session_id,
+            taken: Instant::now(),
+            taken_at,
+            truncated: tree.truncated,
+            viewport: tree.viewport,
+            elements,
+        })
+    }
+
+    pub fn age_ms(&self) -> u64 {
+        self.taken.elapsed().as_millis() as u64
+    }
+
+    /// The part of `path` below PlayerGui, which a StarterGui path shares.
+    fn gui_relative(path: &str) -> Option<&str> {
+        path.strip_prefix("StarterGui.")
+            .or_else(|| path.split_once(".PlayerGui.").map(|(_, rest)| rest))
+    }
+
+    /// Elements `selector` names, in tree order, before visibility and `nth`.
+    fn candidates(&self, target: &Target) -> Vec<&GuiElement> {
+        match target {
+            Target::Path(path) => {
+                let wanted = Self::gui_relative(path);
+                self.elements
+                    .iter()
+                    .filter(|e| e.path == *path || (wanted.is_some() && Self::gui_relative(&e.path) == wanted))
+                    .collect()
+            }
+            Target::Text { kind, text, mode } => {
+                let mut found: Vec<&GuiElement> = Vec::new();
+                for element in self.elements.iter().filter(|e| mode.matches(e, text)) {
+                    // A button's caption is often a label inside it; the button is the target
+                    let target = match kind {
+                        Kind::Button if !element.is_button() => self.button_ancestor(element),
+                        _ if kind.accepts(&element.class_name) => Some(element),
+                        _ => None,
+                    };
+                    if let Some(target) = target.filter(|t| !found.iter().any(|f| f.index == t.index)) {
+                        found.push(target);
+                    }
+                }
+                found
+            }
+        }
+    }
+
+    fn button_ancestor(&self, element: &GuiElement) -> Option<&GuiElement> {
+        let mut parent = element.parent;
+        while let Some(index) = parent {
+            let ancestor = &self.elements[index];
+            if ancestor.is_button() {
+                return Some(ancestor);
+            }
+            parent = ancestor.parent;
+        }
+        None
+    }
+
+    /// Pick the element a click selector names: visible matches only, the `nth` one
+    /// when given, otherwise the only one.
+    pub fn resolve(&self, selector: &Selector) -> Result<Resolved, Unresolved> {
+        let candidates = self.candidates(&selector.target);
+        if candidates.is_empty() {
+            return Err(Unresolved::NotFound(format!(
+                "Nothing in the GUI matches {}{}",
+                selector.describe(),
+                if self.truncated { " (the snapshot was cut off at its node limit)" } else { "" }
+            )));
+        }
+        let visible: Vec<&GuiElement> = candidates.iter().copied().filter(|e| e.visible == Some(true)).collect();
+        if visible.is_empty() {
+            let hidden: Vec<Value> = candidates.iter().take(5).map(|e| e.summary()).collect();
+            return Err(Unresolved::Refused(format!(
+                "{} matches {} GUI object(s) but none is visible on screen: {}",
+                selector.describe(),
+                candidates.len(),
+                Value::Array(hidden)
+            )));
+        }
+        let element = match selector.nth {
+            Some(nth) => *visible.get(nth - 1).ok_or_else(|| {
+                Unresolved::Refused(format!(
+                    "nth {nth} is past the {} visible match(es) of {}",
+                    visible.len(),
+                    selector.describe()
+                ))
+            })?,
+            None if visible.len() == 1 => visible[0],
+            None => {
+                let listed: Vec<Value> = visible.iter().take(10).map(|e| e.summary()).collect();
+                return Err(Unresolved::Refused(format!(
+                    "{} matches {} visible GUI objects; pass nth (1-{}) to pick one, in tree order: {}",
+                    selector.describe(),
+                    visible.len(),
+                    visible.len(),
+                    Value::Array(listed)
+                )));
+            }
+        };
+        Ok(Resolved {
+            element: element.clone(),
+            center: element.center(),
+            nth: visible.iter().position(|e| e.index == element.index).map_or(1, |i| i + 1),
+            matches: visible.len(),
+        })
+    }
+
+    /// One page of elements, for studio-gui_tree.
+    pub fn page(&self, offset: usize, limit: usize, reused: bool) -> Value {
+        let end = (offset + limit).min(self.elements.len());
+        let elements = self.elements.get(offset..end).unwrap_or_default();
+        json!({
+            "snapshotId": self.id,
+            "player": self.player,
+            "root": self.root,
+            "takenAt": self.taken_at,
+            "ageMs": self.age_ms(),
+            "reused": reused,
+            "viewport": self.viewport,
+            "total": self.elements.len(),
+            "truncated": self.truncated,
+            "offset": offset,
+            "returned": elements.len(),
+            "nextCursor": (end < self.elements.len()).then(|| format!("{}:{end}", self.id)),
+            "elements": elements,
+        })
+    }
+}
+
+fn flatten(
+    node: PluginNode,
+    path: &str,
+    depth: usize,
+    parent: Option<usize>,
+    out: &mut Vec<GuiElement>,
+) -> Result<(), String> {
+    if node.name.is_empty() || node.class_name.is_empty() {
+        return Err(format!("The client sent a GUI object without a name or class under {path}"));
+    }
+    if node.position.is_some() != node.size.is_some() {
+        return Err(format!("The client sent {path} with only one of position and size"));
+    }
+    let index = out.len();
+    out.push(GuiElement {
+        index,
+        path: path.to_string(),
+        name: node.name,
+        class_name: node.class_name,
+        depth,
+        parent,
+        position: node.position,
+        size: node.size,
+        visible: node.visible,
+        interactable: node.interactable,
+        text: node.text,
+        z_index: node.z_index,
+        enabled: node.enabled,
+        omitted_children: node.omitted_children,
+    });
+    for child in node.children {
+        let child_path = format!("{path}.{}", child.name);
+        flatten(child, &child_path, depth + 1, Some(index), out)?;
+    }
+    Ok(())
+}
+
+/// Checked studio-gui_tree arguments.
+#[derive(Debug, Clone, Default)]
+pub struct TreeRequest {
+    pub root: Option<String>,
+    pub visible_only: bool,
+    pub player: Option<String>,
+    pub limit: usize,
+    /// `(snapshot id, offset)` from a previous page's `nextCursor`.
+    pub cursor: Option<(String, usize)>,
+    pub refresh: bool,
+}
+
+impl TreeRequest {
+    pub fn parse(arguments: &Value) -> Result<Self, String> {
+        let limit = match arguments.get("limit").filter(|v| !v.is_null()) {
+            None => DEFAULT_PAGE_SIZE,
+            Some(v) => v
+                .as_u64()
+                .filter(|n| (1..=MAX_PAGE_SIZE as u64).contains(n))
+                .ok_or_else(|| format!("'limit' must be a number of elements from 1 to {MAX_PAGE_SIZE}"))?
+                as usize,
+        };
+        let cursor = match text_arg(arguments, "cursor")? {
+            None => None,
+            Some(raw) => {
+                let parsed = raw
+                    .rsplit_once(':')
+                    .and_then(|(id, offset)| Some((id.to_string(), offset.parse().ok()?)));
+                Some(parsed.ok_or_else(|| format!("'cursor' '{raw}' is not a nextCursor from studio-gui_tree"))?)
+            }
+        };
+        Ok(Self {
+            root: text_arg(arguments, "root")?,
+            visible_only: bool_arg(arguments, "visibleOnly")?,
+            player: text_arg(arguments, "player")?,
+            limit,
+            cursor,
+            refresh: bool_arg(arguments, "refresh")?,
+        })
+    }
+
+    /// Arguments for the bridge's studio-get_gui_tree.
+    pub fn plugin_args(&self) -> Value {
+        json!({
+            "root": self.root,
+            "visibleOnly": self.visible_only,
+            "player": self.player,
+            "maxDepth": SNAPSHOT_MAX_DEPTH,
+            "maxNodes": SNAPSHOT_MAX_NODES,
+        })
+    }
+
+    /// The request for a whole-PlayerGui snapshot of `player`, as clicks use.
+    pub fn full(player: Option<String>) -> Self {
+        Self { player, limit: DEFAULT_PAGE_SIZE, ..Default::default() }
+    }
+}
+
+fn text_arg(arguments: &Value, name: &str) -> Result<Option<String>, String> {
+    match arguments.get(name).filter(|v| !v.is_null()) {
+        None => Ok(None),
+        Some(Value::String(s)) if !s.trim().is_empty() => Ok(Some(s.trim().to_string())),
+        Some(_) => Err(format!("'{name}' must be a non-empty string")),
+    }
+}
+
+fn bool_arg(arguments: &Value, name: &str) -> Result<bool, String> {
+    match arguments.get(name).filter(|v| !v.is_null()) {
+        None => Ok(false),
+        Some(Value::Bool(b)) => Ok(*b),
+        Some(_) => Err(format!("'{name}' must be a boolean")),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Button,
+    Label,
+    TextBox,
+    Image,
+    Any,
+}
+
+impl Kind {
+    fn parse(word: &str) -> Option<Self> {
+        Some(match word.to_ascii_lowercase().as_str() {
+            "button" => Self::Button,
+            "label" => Self::Label,
+            "textbox" => Self::TextBox,
+            "image" => Self::Image,
+            "element" | "any" | "*" => Self::Any,
+            _ => return None,
+        })
+    }
+
+    fn accepts(self, class_name: &str) -> bool {
+        match self {
+            Self::Button => BUTTON_CLASSES.contains(&class_name),
+            Self::Label => class_name == "TextLabel",
+            Self::TextBox => class_name == "TextBox",
+            Self::Image => matches!(class_name, "ImageLabel" | "ImageButton"),
+            Self::Any => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// Text contains the string, ignoring case.
+    Containing,
+    /// Text is the string, ignoring case and surrounding whitespace.
+    WithText,
+    /// The instance Name is the string.
+    Named,
+}
+
+impl TextMode {
+    fn matches(self, element: &GuiElement, wanted: &str) -> bool {
+        match self {
+            Self::Named => element.name == wanted,
+            Self::Containing => element
+                .text
+                .as_deref()
+                .is_some_and(|t| t.to_lowercase().contains(&wanted.to_lowercase())),
+            Self::WithText => element.text.as_deref().is_some_and(|t| t.trim().eq_ignore_ascii_case(wanted.trim())),
+        }
+    }
+
+    fn keyword(self) -> &'static str {
+        match self {
+            Self::Containing => "containing",
+            Self::WithText => "with text",
+            Self::Named => "named",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// `StarterGui.<...>` or `Players.<name>.PlayerGui.<...>`.
+    Path(String),
+    Text { kind: Kind, text: String, mode: TextMode },
+}
+
+/// What studio-gui_click clicks: a path or a text selector, and which visible match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    pub target: Target,
+    /// 1-based, in tree order.
+    pub nth: Option<usize>,
+}
+
+const SELECTOR_HELP: &str = "Use a path ('StarterGui.Menu.PlayButton') or '<kind> containing|with text|named \"<text>\"' with kind button, label, textbox, image or element, e.g. \"button containing 'Play'\"";
+
+impl Selector {
+    pub fn parse(arguments: &Value) -> Result<Self, String> {
+        let nth = match arguments.get("nth").filter(|v| !v.is_null()) {
+            None => None,
+            Some(v) => Some(
+                v.as_u64()
+                    .filter(|n| *n >= 1)
+                    .ok_or("'nth' must be a positive integer (1 is the first match in tree order)")? as usize,
+            ),
+        };
+        let target = match (text_arg(arguments, "selector")?, text_arg(arguments, "path")?) {
+            (Some(_), Some(_)) => return Err("Pass either 'selector' or 'path', not both".into()),
+            (None, None) => return Err(format!("Missing 'selector'. {SELECTOR_HELP}")),
+            (None, Some(path)) => Target::Path(check_path(&path)?),
+            (Some(selector), None) => parse_target(&selector)?,
+        };
+        Ok(Self { target, nth })
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.target {
+            Target::Path(path) => format!("path '{path}'"),
+            Target::Text { kind, text, mode } => {
+                let kind = match kind {
+                    Kind::Button => "button",
+                    Kind::Label => "label",
+                    Kind::TextBox => "textbox",
+                    Kind::Image => "image",
+                    Kind::Any => "element",
+                };
+                format!("{kind} {} '{text}'", mode.keyword())
+            }
+        }
+    }
+}
+
+fn check_path(path: &str) -> Result<String, String> {
+    if path.starts_with("StarterGui.") || (path.starts_with("Players.") && path.contains(".PlayerGui.")) {
+        Ok(path.to_string())
+    } else {
+        Err(format!("'{path}' is not a GUI path. {SELECTOR_HELP}"))
+    }
+}
+
+/// `<kind> containing|with text|named '<text>'`, or a path when there are no quotes.
+fn parse_target(selector: &str) -> Result<Target, String> {
+    let Some(quote_at) = selector.find(['\'', '"']) else {
+        return check_path(selector).map(Target::Path);
+    };
+    let quote = selector[quote_at..].chars().next().unwrap_or('\'');
+    let rest = &selector[quote_at + 1..];
+    let text = rest
+        .strip_suffix(quote)
+        .filter(|t| !t.contains(quote) && !t.is_empty())
+        .ok_or_else(|| format!("Selector '{selector}' must end with one quoted, non-empty text. {SELECTOR_HELP}"))?;
+    let words: Vec<&str> = selector[..quote_at].split_whitespace().collect();
+    let (kind, mode) = match words.as_slice() {
+        [kind, "containing"] => (kind, TextMode::Containing),
+        [kind, "with", "text"] => (kind, TextMode::WithText),
+        [kind, "named"] => (kind, TextMode::Named),
+        _ => return Err(format!("Can't read selector '{selector}'. {SELECTOR_HELP}")),
+    };
+    let kind = Kind::parse(kind).ok_or_else(|| format!("Unknown element kind '{kind}'. {SELECTOR_HELP}"))?;
+    Ok(Target::Text { kind, text: text.to_string(), mode })
+}
+
+/// The element a selector picked, with the point a click aims at.
+pub struct Resolved {
+    pub element: GuiElement,
+    pub center: Option<Vec2>,
+    /// 1-based among `matches` visible matches.
+    pub nth: usize,
+    pub matches: usize,
+}
+
+impl Resolved {
+    /// Whether the client clicked somewhere else than the snapshot's centre.
+    pub fn moved(&self, clicked: Option<Vec2>) -> bool {
+        match (self.center, clicked) {
+            (Some(a), Some(b)) => (a.x - b.x).abs() > MOVED_THRESHOLD_PX || (a.y - b.y).abs() > MOVED_THRESHOLD_PX,
+            _ => false,
+        }
+    }
+}
+
+pub enum Unresolved {
+    /// Nothing matched; a fresher snapshot may have it.
+    NotFound(String),
+    /// Matches exist but none can be picked (hidden, ambiguous, nth too high).
+    Refused(String),
+}
+
+/// Recent snapshots, newest last.
+#[derive(Default)]
+pub struct SnapshotStore {
+    snapshots: VecDeque<Arc<GuiSnapshot>>,
+    last_click: Option<Instant>,
+}
+
+impl SnapshotStore {
+    pub fn insert(&mut self, snapshot: GuiSnapshot) -> Arc<GuiSnapshot> {
+        let snapshot = Arc::new(snapshot);
+        if self.snapshots.len() >= MAX_STORED_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot.clone());
+        snapshot
+    }
+
+    pub fn get(&self, id: &str) -> Option<Arc<GuiSnapshot>> {
+        self.snapshots.iter().find(|s| s.id == id).cloned()
+    }
+
+    /// A snapshot taken for the same arguments within `SNAPSHOT_REUSE`.
+    pub fn reusable(&self, request: &TreeRequest, session_id: Option<&str>) -> Option<Arc<GuiSnapshot>> {
+        self.usable(request.player.as_deref(), session_id, SNAPSHOT_REUSE)
+            .filter(|s| s.requested_root == request.root && s.visible_only == request.visible_only)
+    }
+
+    /// The newest whole-PlayerGui snapshot a click may resolve against.
+    pub fn for_click(&self, player: Option<&str>, session_id: Option<&str>, max_age: Duration) -> Option<Arc<GuiSnapshot>> {
+        self.usable(player, session_id, max_age)
+            .filter(|s| s.requested_root.is_none() && !s.visible_only)
+    }
+
+    fn usable(&self, player: Option<&str>, session_id: Option<&str>, max_age: Duration) -> Option<Arc<GuiSnapshot>> {
+        let snapshot = self.snapshots.back()?;
+        let fresh = snapshot.taken.elapsed() <= max_age
+            && snapshot.session_id.as_deref() == session_id
+            && self.last_click.is_none_or(|clicked| snapshot.taken > clicked)
+            && player.is_none_or(|p| snapshot.player.as_deref() == Some(p));
+        fresh.then(|| snapshot.clone())
+    }
+
+    /// A click probably changed the GUI; later clicks and calls take new snapshots.
+    pub fn clicked(&mut self) {
+        self.last_click = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/gui_tree").join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn shop(id: &str, session_id: Option<&str>) -> GuiSnapshot {
+        let request = TreeRequest::full(None);
+        GuiSnapshot::from_plugin(fixture("shop.json"), id.into(), &request, session_id.map(String::from), "t".into()).unwrap()
+    }
+
+    fn selector(selector: &str) -> Selector {
+        Selector::parse(&json!({ "selector": selector })).unwrap()
+    }
+
+    fn resolve(snapshot: &GuiSnapshot, selector: Selector) -> Result<(String, usize, usize), String> {
+        match snapshot.resolve(&selector) {
+            Ok(resolved) => Ok((resolved.element.path, resolved.nth, resolved.matches)),
+            Err(Unresolved::NotFound(message)) => Err(format!("not found: {message}")),
+            Err(Unresolved::Refused(message)) => Err(format!("refused: {message}")),
+        }
+    }
+
+    fn path(resolved: Result<(String, usize, usize), String>) -> String {
+        resolved.unwrap().0
+    }
+
+    fn ago(duration: Duration) -> Instant {
+        Instant::now().checked_sub(duration).unwrap()
+    }
+
+    #[test]
+    fn the_tree_is_flattened_in_tree_order_with_full_paths() {
+        let snapshot = shop("g1", None);
+        assert_eq!((snapshot.root.as_str(), snapshot.player.as_deref()), ("Players.Alice.PlayerGui", Some("Alice")));
+        assert_eq!(snapshot.elements.len(), 18);
+        let paths: Vec<&str> = snapshot.elements.iter().take(6).map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "Players.Alice.PlayerGui",
+                "Players.Alice.PlayerGui.Menu",
+                "Players.Alice.PlayerGui.Menu.PlayButton",
+                "Players.Alice.PlayerGui.Menu.ShopButton",
+                "Players.Alice.PlayerGui.Menu.ShopButton.Caption",
+                "Players.Alice.PlayerGui.Menu.Title",
+            ]
+        );
+        let caption = &snapshot.elements[4];
+        assert_eq!((caption.depth, caption.parent, caption.index), (3, Some(3), 4));
+        assert_eq!(snapshot.elements[0].parent, None);
+        assert_eq!(snapshot.elements[2].center(), Some(Vec2 { x: 640.0, y: 330.0 }));
+        assert_eq!(snapshot.elements[0].center(), None);
+        assert_eq!(snapshot.elements[1].enabled, Some(true));
+    }
+
+    #[test]
+    fn malformed_trees_are_refused() {
+        let request = TreeRequest::full(None);
+        let refused = |tree: Value| GuiSnapshot::from_plugin(tree, "g1".into(), &request, None, "t".into()).err().unwrap();
+
+        let mut miscounted = fixture("shop.json");
+        miscounted["nodeCount"] = json!(20);
+        assert_eq!(refused(miscounted), "The client reported 20 GUI objects but sent 18");
+        let mut half_placed = fixture("shop.json");
+        half_placed["tree"]["children"][0]["children"][0]["size"] = Value::Null;
+        assert_eq!(refused(half_placed), "The client sent Players.Alice.PlayerGui.Menu.PlayButton with only one of position and size");
+        let mut nameless = fixture("shop.json");
+        nameless["tree"]["children"][1]["name"] = json!("");
+        assert_eq!(refused(nameless), "The client sent a GUI object without a name or class under Players.Alice.PlayerGui.");
+        let mut no_viewport = fixture("shop.json");
+        no_viewport.as_object_mut().unwrap().remove("viewport");
+        assert!(refused(no_viewport).starts_with("Unexpected GUI tree from the client: missing field `viewport`"));
+
+        // An empty PlayerGui has no tree
+        let empty = json!({ "root": "Players.Alice.PlayerGui", "tree": null, "nodeCount": 0, "truncated": false, "viewport": { "x": 1, "y": 1 } });
+        assert!(GuiSnapshot::from_plugin(empty, "g1".into(), &request, None, "t".into()).unwrap().elements.is_empty());
+    }
+
+    #[test]
+    fn selectors_are_parsed() {
+        let text = |kind, text: &str, mode| Target::Text { kind, text: text.into(), mode };
+        let cases = [
+            ("button containing 'Play'", text(Kind::Button, "Play", TextMode::Containing)),
+            ("Button  with text \"Don't\"", text(Kind::Button, "Don't", TextMode::WithText)),
+            ("label named 'Title'", text(Kind::Label, "Title", TextMode::Named)),
+            ("textbox containing 'search'", text(Kind::TextBox, "search", TextMode::Containing)),
+            ("image named 'Logo'", text(Kind::Image, "Logo", TextMode::Named)),
+            ("* containing 'x'", text(Kind::Any, "x", TextMode::Containing)),
+            ("element containing 'a b'", text(Kind::Any, "a b", TextMode::Containing)),
+            ("StarterGui.Menu.PlayButton", Target::Path("StarterGui.Menu.PlayButton".into())),
+            ("Players.Alice.PlayerGui.Menu", Target::Path("Players.Alice.PlayerGui.Menu".into())),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(selector(raw).target, expected, "{raw}");
+        }
+        let by_path = Selector::parse(&json!({ "path": "StarterGui.Menu.PlayButton", "nth": 2 })).unwrap();
+        assert_eq!((by_path.target, by_path.nth), (Target::Path("StarterGui.Menu.PlayButton".into()), Some(2)));
+        assert_eq!(selector("button containing 'Play'").describe(), "button containing 'Play'");
+        assert_eq!(selector("StarterGui.Menu").describe(), "path 'StarterGui.Menu'");
+
+        let refused = |arguments: Value| Selector::parse(&arguments).unwrap_err();
+        let starts = |message: String, prefix: &str| assert!(message.starts_with(prefix), "{message}");
+        starts(refused(json!({})), "Missing 'selector'. Use a path");
+        starts(refused(json!({ "selector": "button containing 'Play'", "path": "StarterGui.Menu" })), "Pass either 'selector' or 'path'");
+        starts(refused(json!({ "selector": "Workspace.Part" })), "'Workspace.Part' is not a GUI path.");
+        starts(refused(json!({ "path": "Players.Alice.Backpack" })), "'Players.Alice.Backpack' is not a GUI path.");
+        starts(refused(json!({ "selector": "button containing 'Play" })), "Selector 'button containing 'Play' must end with one quoted, non-empty text.");
+        starts(refused(json!({ "selector": "button containing ''" })), "Selector 'button containing ''' must end");
+        starts(refused(json!({ "selector": "button containing 'a' 'b'" })), "Selector 'button containing 'a' 'b'' must end");
+        starts(refused(json!({ "selector": "button near 'Play'" })), "Can't read selector 'button near 'Play''.");
+        starts(refused(json!({ "selector": "slider containing 'Volume'" })), "Unknown element kind 'slider'.");
+        starts(refused(json!({ "selector": "button containing 'Play'", "nth": 0 })), "'nth' must be a positive integer");
+    }
+
+    #[test]
+    fn text_selectors_match_by_kind_and_mode() {
+        let snapshot = shop("g1", None);
+        // The title mentions Play too, but it is not a button and has no button around it
+        assert_eq!(path(resolve(&snapshot, selector("button containing 'pLaY'"))), "Players.Alice.PlayerGui.Menu.PlayButton");
+        assert_eq!(path(resolve(&snapshot, selector("label containing 'play'"))), "Players.Alice.PlayerGui.Menu.Title");
+        // A button's caption label finds the button
+        assert_eq!(path(resolve(&snapshot, selector("button with text 'SHOP'"))), "Players.Alice.PlayerGui.Menu.ShopButton");
+        assert_eq!(path(resolve(&snapshot, selector("label with text 'Shop'"))), "Players.Alice.PlayerGui.Menu.ShopButton.Caption");
+        // Exact text ignores surrounding whitespace, containing doesn't need it
+        assert_eq!(path(resolve(&snapshot, selector("button with text 'close'"))), "Players.Alice.PlayerGui.Shop.Close");
+        assert!(resolve(&snapshot, selector("button with text 'clo'")).unwrap_err().starts_with("not found: "));
+        assert_eq!(path(resolve(&snapshot, selector("image named 'Logo'"))), "Players.Alice.PlayerGui.Menu.Logo");
+        assert_eq!(path(resolve(&snapshot, selector("image named 'ShopButton'"))), "Players.Alice.PlayerGui.Menu.ShopButton");
+        assert_eq!(path(resolve(&snapshot, selector("element named 'Items'"))), "Players.Alice.PlayerGui.Shop.Items");
+        assert_eq!(path(resolve(&snapshot, selector("textbox named 'SearchBox'"))), "Players.Alice.PlayerGui.Shop.SearchBox");
+        // Named is exact
+        assert!(resolve(&snapshot, selector("element named 'items'")).is_err());
+    }
+
+    #[test]
+    fn several_matches_need_nth_and_only_visible_ones_count() {
+        let snapshot = shop("g1", None);
+        let message = resolve(&snapshot, selector("button with text 'Buy'")).unwrap_err();
+        assert!(message.starts_with("refused: button with text 'Buy' matches 2 visible GUI objects; pass nth (1-2) to pick one, in tree order: ["), "{message}");
+        assert!(message.contains("Sword.Buy") && message.contains("Shield.Buy") && !message.contains("Bow.Buy"), "{message}");
+
+        let nth = |n: usize| resolve(&snapshot, Selector { nth: Some(n), ..selector("button with text 'Buy'") });
+        assert_eq!(nth(1).unwrap(), ("Players.Alice.PlayerGui.Shop.Items.Sword.Buy".to_string(), 1, 2));
+        assert_eq!(nth(2).unwrap(), ("Players.Alice.PlayerGui.Shop.Items.Shield.Buy".to_string(), 2, 2));
+        assert_eq!(nth(3).unwrap_err(), "refused: nth 3 is past the 2 visible match(es) of button with text 'Buy'");
+        // nth with a single match is fine
+        let single = Selector { nth: Some(1), ..selector("button containing 'Play'") };
+        assert_eq!(resolve(&snapshot, single).unwrap().1, 1);
+
+        let message = resolve(&snapshot, selector("button named 'Hidden'")).unwrap_err();
+        assert!(message.starts_with("refused: button named 'Hidden' matches 1 GUI object(s) but none is visible on screen: ["), "{message}");
+    }
+
+    #[test]
+    fn paths_resolve_through_starter_gui_or_player_gui() {
+        let snapshot = shop("g1", None);
+        let by_path = |p: &str| resolve(&snapshot, Selector::parse(&json!({ "path": p })).unwrap());
+        assert_eq!(path(by_path("StarterGui.Menu.PlayButton")), "Players.Alice.PlayerGui.Menu.PlayButton");
+        assert_eq!(path(by_path("Players.Alice.PlayerGui.Shop.Close")), "Players.Alice.PlayerGui.Shop.Close");
+        assert_eq!(path(resolve(&snapshot, selector("StarterGui.Shop.Items.Shield.Buy"))), "Players.Alice.PlayerGui.Shop.Items.Shield.Buy");
+        // A hidden parent hides the child even when the path names it exactly
+        assert!(by_path("StarterGui.Shop.Items.Bow.Buy").unwrap_err().starts_with("refused: path 'StarterGui.Shop.Items.Bow.Buy' matches 1 GUI object(s) but none is visible"));
+        assert_eq!(by_path("StarterGui.Menu.QuitButton").unwrap_err(), "not found: Nothing in the GUI matches path 'StarterGui.Menu.QuitButton'");
+
+        let mut truncated = shop("g1", None);
+        truncated.truncated = true;
+        let message = resolve(&truncated, selector("button containing 'Quit'")).unwrap_err();
+        assert_eq!(message, "not found: Nothing in the GUI matches button containing 'Quit' (the snapshot was cut off at its node limit)");
+    }
+
+    #[test]
+    fn clicks_away_from_the_centre_mean_the_element_moved() {
+        let snapshot = shop("g1", None);
+        let Ok(resolved) = snapshot.resolve(&selector("button containing 'Play'")) else { panic!("Play did not resolve") };
+        assert_eq!(resolved.center, Some(Vec2 { x: 640.0, y: 330.0 }));
+        assert!(!resolved.moved(Some(Vec2 { x: 641.5, y: 328.0 })));
+        assert!(resolved.moved(Some(Vec2 { x: 640.0, y: 333.0 })));
+        assert!(!resolved.moved(None));
+    }
+
+    #[test]
+    fn tree_requests_are_checked() {
+        let request = TreeRequest::parse(&json!({})).unwrap();
+        assert_eq!((request.limit, request.visible_only, request.refresh, request.cursor), (DEFAULT_PAGE_SIZE, false, false, None));
+        let request = TreeRequest::parse(&json!({ "root": "StarterGui.Menu", "visibleOnly": true, "cursor": "0001-ab:100", "limit": 5 })).unwrap();
+        assert_eq!(request.plugin_args()["maxNodes"], SNAPSHOT_MAX_NODES);
+        assert_eq!(request.plugin_args()["visibleOnly"], true);
+        assert_eq!((request.root.as_deref(), request.cursor, request.limit), (Some("StarterGui.Menu"), Some(("0001-ab".to_string(), 100)), 5));
+
+        let refused = |arguments: Value| TreeRequest::parse(&arguments).unwrap_err();
+        assert_eq!(refused(json!({ "limit": 0 })), "'limit' must be a number of elements from 1 to 500");
+        assert_eq!(refused(json!({ "cursor": "g1" })), "'cursor' 'g1' is not a nextCursor from studio-gui_tree");
+        assert_eq!(refused(json!({ "cursor": "g1:x" })), "'cursor' 'g1:x' is not a nextCursor from studio-gui_tree");
+        assert_eq!(refused(json!({ "refresh": "yes" })), "'refresh' must be a boolean");
+    }
+
+    #[test]
+    fn pages_walk_one_snapshot() {
+        let snapshot = shop("g1", None);
+        let first = snapshot.page(0, 10, false);
+        assert_eq!((&first["total"], &first["returned"], &first["nextCursor"]), (&json!(18), &json!(10), &json!("g1:10")));
+        assert_eq!(first["elements"][2]["path"], "Players.Alice.PlayerGui.Menu.PlayButton");
+        assert_eq!(first["elements"][2]["zIndex"], 2);
+        assert!(first["elements"][0].get("position").is_none());
+        let (_, offset) = TreeRequest::parse(&json!({ "cursor": first["nextCursor"] })).unwrap().cursor.unwrap();
+        let last = snapshot.page(offset, 10, true);
+        assert_eq!((&last["offset"], &last["returned"], &last["nextCursor"], &last["reused"]), (&json!(10), &json!(8), &Value::Null, &json!(true)));
+        assert_eq!(last["elements"][7]["path"], "Players.Alice.PlayerGui.Shop.SearchBox");
+        assert_eq!(snapshot.page(18, 10, true)["returned"], 0);
+    }
+
+    #[test]
+    fn snapshots_are_reused_only_while_fresh_and_unchanged() {
+        let mut store = SnapshotStore::default();
+        let request = TreeRequest::full(None);
+        let fresh = store.insert(shop("g1", Some("session")));
+        assert_eq!(store.reusable(&request, Some("session")).unwrap().id, fresh.id);
+        // Another playtest, or other arguments, need a new snapshot
+        assert!(store.reusable(&request, Some("later")).is_none());
+        let visible = TreeRequest { visible_only: true, ..TreeRequest::full(None) };
+        assert!(store.reusable(&visible, Some("session")).is_none());
+        assert!(store.reusable(&TreeRequest::full(Some("Alice".into())), Some("session")).is_some());
+        assert!(store.reusable(&TreeRequest::full(Some("Bob".into())), Some("session")).is_none());
+
+        // A few frames is the limit for reuse, the click's maxAgeMs for clicks
+        let mut older = shop("g2", Some("session"));
+        older.taken = ago(SNAPSHOT_REUSE * 2);
+        store.insert(older);
+        assert!(store.reusable(&request, Some("session")).is_none());
+        assert_eq!(store.for_click(None, Some("session"), Duration::from_secs(2)).unwrap().id, "g2");
+        assert!(store.for_click(None, Some("session"), Duration::ZERO).is_none());
+
+        // Only the newest snapshot counts, and only a whole-PlayerGui one for clicks
+        let mut menu = shop("g3", Some("session"));
+        menu.requested_root = Some("StarterGui.Menu".into());
+        store.insert(menu);
+        assert!(store.for_click(None, Some("session"), Duration::from_secs(2)).is_none());
+        store.insert(shop("g4", Some("session")));
+        assert_eq!(store.for_click(None, Some("session"), Duration::from_secs(2)).unwrap().id, "g4");
+
+        // A click makes every earlier snapshot stale
+        store.clicked();
+        assert!(store.for_click(None, Some("session"), Duration::from_secs(2)).is_none());
+        assert!(store.reusable(&request, Some("session")).is_none());
+        std::thread::sleep(Duration::from_millis(2));
+        store.insert(shop("g5", Some("session")));
+        assert_eq!(store.for_click(None, Some("session"), Duration::from_secs(2)).unwrap().id, "g5");
+    }
+
+    #[test]
+    fn the_store_keeps_recent_snapshots_for_cursors() {
+        let mut store = SnapshotStore::default();
+        for i in 0..MAX_STORED_SNAPSHOTS + 2 {
+            store.insert(shop(&format!("g{i}"), None));
+        }
+        assert!(store.get("g1").is_none());
+        assert_eq!(store.get("g2").unwrap().id, "g2");
+        assert!(store.get(&format!("g{}", MAX_STORED_SNAPSHOTS + 1)).is_some());
+    }
+}
@@ -0,0 +1,300 @@
+use serde_json::json;
+use std::time::Duration;
+
+use crate::mcp_stdio::{call_plugin, TOOL_CALL_TIMEOUT};
+use crate::session::{SessionActivity, SessionState};
+use crate::state::SharedState;
+use crate::studio_settings;
+
+/// Restoring state must not hold up the request that ended the idle period for long.
+const RESUME_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Watch the MCP sessions for idleness. A session without a request for `timeout`
+/// gives up its log subscription; once every session is idle, close what they left
+/// open in Studio and park until the next request arrives. There is no periodic
+/// wake-up while idle.
+pub async fn run(state: SharedState, timeout: Duration, stop_playtest: bool) {
+    loop {
+        let mut next_check: Option<Duration> = None;
+        let mut went_idle = Vec::new();
+        for (id, session) in state.sessions() {
+            if session.is_idle() {
+                continue;
+            }
+            let idle_for = session.idle_for();
+            if idle_for < timeout {
+                next_check = Some(next_check.map_or(timeout - idle_for, |next| next.min(timeout - idle_for)));
+                continue;
+            }
+            session.enter_idle();
+            tracing::info!(session = %id, idle_secs = idle_for.as_secs(), "MCP session idle");
+            went_idle.push((id, session));
+        }
+        for (id, session) in &went_idle {
+            release_logs(&state, id, session, "Idle cleanup").await;
+        }
+        // Studio-side resources belong to every session, so they wait for the last one
+        if !went_idle.is_empty() && state.is_idle() {
+            tracing::info!("All MCP sessions idle, running cleanup");
+            cleanup(&state, stop_playtest, "Idle cleanup").await;
+        }
+
+        match next_check {
+            Some(wait) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = state.activity().notified() => {}
+                }
+            }
+            None => state.activity().notified().await,
+        }
+    }
+}
+
+/// Close what every session left open, including a running playtest, after the MCP
+/// client went away without closing stdin (see `mcp_stdio::run`).
+pub async fn teardown(state: &SharedState) {
+    for (id, session) in state.sessions() {
+        release_logs(state, &id, &session, "Session teardown").await;
+    }
+    cleanup(state, true, "Session teardown").await;
+}
+
+/// Drop a session's log subscription, keeping it aside for `resume`. The plugin
+/// holds one subscription for every session, so it is only unsubscribed when no
+/// other session still listens.
+async fn release_logs(state: &SharedState, session_id: &str, session: &SessionActivity, label: &str) {
+    if session.log_subscription().is_none() {
+        return;
+    }
+    if !state.logs_subscribed_elsewhere(session_id) {
+        if let Err(e) = call_plugin(state, "studio-logs_unsubscribe", json!({}), TOOL_CALL_TIMEOUT).await {
+            tracing::warn!(session = %session_id, error = %e, "{label}: logs_unsubscribe failed");
+            return;
+        }
+    }
+    session.suspend_log_subscription();
+    tracing::info!(session = %session_id, "{label}: unsubscribed logs");
+}
+
+/// One cleanup pass over the Studio-side resources the sessions share. Each step is
+/// independent, so a failing plugin call only skips that resource. `label` prefixes
+/// the log lines.
+async fn cleanup(state: &SharedState, stop_playtest: bool, label: &str) {
+    let mut summary = Vec::new();
+
+    let cleared = state.clear_log_buffer();
+    if cleared > 0 {
+        summary.push(format!("cleared {cleared} buffered log entries"));
+    }
+
+    let drivers: Vec<String> = state.session_resources().await.npc_drivers.keys().cloned().collect();
+    for driver_id in drivers {
+        let args = json!({ "driverId": driver_id });
+        match call_plugin(state, "studio-npc_driver_stop", args, TOOL_CALL_TIMEOUT).await {
+            Ok(_) => summary.push(format!("stopped NPC driver {driver_id}")),
+            Err(e) => {
+                tracing::warn!(driver_id = %driver_id, error = %e, "{label}: npc_driver_stop failed");
+                state.session_resources().await.npc_drivers.remove(&driver_id);
+            }
+        }
+    }
+
+    for key_code in state.held_keys().await {
+        let args = json!({ "keyCode": key_code, "action": "up" });
+        if let Err(e) = call_plugin(state, "studio-virtualuser_key", args, TOOL_CALL_TIMEOUT).await {
+            tracing::warn!(key = %key_code, error = %e, "{label}: key release failed");
+        }
+    }
+
+    // Ending a checkpoint commits it, which is the user's call to make
+    for (checkpoint_id, checkpoint) in &state.session_resources().await.open_checkpoints {
+        match checkpoint.lost {
+            None => tracing::warn!(
+                checkpoint_id = %checkpoint_id,
+                name = %checkpoint.name,
+                "{label}: checkpoint still open; end it with studio-checkpoint_end"
+            ),
+            Some(loss) => tracing::warn!(
+                checkpoint_id = %checkpoint_id,
+                name = %checkpoint.name,
+                "{label}: checkpoint is unrecoverable ({}); clear it with studio-checkpoint_abandon",
+                loss.describe()
+            ),
+        }
+    }
+
+    let settings = state.studio_setting_originals().await;
+    if !settings.is_empty() {
+        match studio_settings::restore(state, &settings).await {
+            Ok(_) => summary.push(format!("restored {} Studio setting(s)", settings.len())),
+            Err(e) => tracing::warn!(error = %e, "{label}: restoring Studio settings failed"),
+        }
+    }
+
+    if stop_playtest && state.is_playtest_active().await {
+        match call_plugin(state, "studio-playtest_stop", json!({}), TOOL_CALL_TIMEOUT).await {
+            Ok(_) => summary.push("stopped playtest".to_string()),
+            Err(e) => tracing::warn!(error = %e, "{label}: playtest_stop failed"),
+        }
+    }
+
+    if summary.is_empty() {
+        tracing::info!("{label}: nothing to clean up");
+    } else {
+        tracing::info!("{label}: {}", summary.join(", "));
+    }
+}
+
+/// Called before a session's first request after an idle period. Restores the log
+/// subscription the cleanup removed so the client sees no difference.
+pub async fn resume(state: &SharedState, session: &SessionState) {
+    tracing::info!(session = %session.id, "MCP session active again after idle");
+    let Some(subscription) = session.activity().take_suspended_log_subscription() else {
+        return;
+    };
+    match call_plugin(state, "studio-logs_subscribe", subscription.resubscribe_arguments(), RESUME_TIMEOUT).await {
+        Ok(_) => session.activity().set_log_subscription(Some(subscription)),
+        Err(e) => tracing::warn!(error = %e, "Could not restore log subscription after idle"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mcp_stdio::Dispatcher;
+    use crate::mock_studio::scripted;
+    use crate::state::OpenCheckpoint;
+
+    const TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// A connected plugin that answers every call, failing the tools in `failing`.
+    async fn fake_plugin(state: &SharedState, failing: &'static [&'static str]) -> scripted::Calls {
+        scripted::answering(state, move |tool, _| {
+            if failing.contains(&tool) {
+                Err("plugin refused".into())
+            } else {
+                Ok(json!({}))
+            }
+        })
+        .await
+    }
+
+    async fn leave_resources_open(state: &SharedState) {
+        let mut resources = state.session_resources().await;
+        resources.npc_drivers.insert("npc-1".into(), "Workspace.Guard".into());
+        let checkpoint = OpenCheckpoint { name: "move walls".into(), owner: "edit".into(), lost: None };
+        resources.open_checkpoints.insert("cp-1".into(), checkpoint);
+        drop(resources);
+        state.apply_key_transition("W", "down").await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn session_goes_idle_after_the_timeout_and_resumes_on_the_next_request() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = fake_plugin(&state, &[]).await;
+        let mut dispatcher = Dispatcher::new(state.clone());
+        dispatcher.call_tool("studio-logs_subscribe", json!({ "levels": ["error"] })).await.unwrap();
+        tokio::spawn(run(state.clone(), TIMEOUT, false));
+
+        tokio::time::sleep(TIMEOUT - Duration::from_secs(1)).await;
+        assert!(!state.is_idle());
+        assert_eq!(calls.tools(), ["studio-logs_subscribe"]);
+
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert!(state.is_idle());
+        assert_eq!(calls.tools(), ["studio-logs_subscribe", "studio-logs_unsubscribe"]);
+        assert!(!state.logs_subscribed());
+
+        // The next request renews the subscription before it is handled
+        dispatcher.request("ping", json!({})).await.unwrap();
+        assert!(!state.is_idle());
+        let resubscribed = calls.all().last().cloned().unwrap();
+        assert_eq!(resubscribed, ("studio-logs_subscribe".to_string(), json!({ "levels": ["error"] })));
+        assert!(state.logs_subscribed());
+
+        // And the watcher counts the timeout again from there
+        tokio::time::sleep(TIMEOUT + Duration::from_secs(1)).await;
+        assert!(state.is_idle());
+        assert_eq!(calls.tools().last().unwrap(), "studio-logs_unsubscribe");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shared_resources_are_cleaned_up_once_every_session_is_idle() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = fake_plugin(&state, &[]).await;
+        let first = Dispatcher::new(state.clone());
+        let mut second = Dispatcher::new(state.clone());
+        first.call_tool("studio-logs_subscribe", json!({})).await.unwrap();
+        second.call_tool("studio-logs_subscribe", json!({})).await.unwrap();
+        leave_resources_open(&state).await;
+        state.push_log("output".into(), "left over".into(), None);
+        tokio::spawn(run(state.clone(), TIMEOUT, false));
+
+        // The second session stays busy, so only the first one's subscription goes,
+        // and the plugin keeps streaming logs for the second
+        tokio::time::sleep(TIMEOUT / 2).await;
+        second.request("ping", json!({})).await.unwrap();
+        tokio::time::sleep(TIMEOUT / 2 + Duration::from_secs(1)).await;
+        assert!(!state.is_idle());
+        assert_eq!(calls.tools(), ["studio-logs_subscribe", "studio-logs_subscribe"]);
+        assert!(state.logs_subscribed());
+        assert_eq!(state.log_buffer_size(), 1);
+
+        tokio::time::sleep(TIMEOUT / 2).await;
+        assert!(state.is_idle());
+        let called = calls.tools();
+        assert_eq!(
+            called[2..],
+            ["studio-logs_unsubscribe", "studio-npc_driver_stop", "studio-virtualuser_key"]
+        );
+        assert_eq!(calls.all()[4].1, json!({ "keyCode": "W", "action": "up" }));
+        assert_eq!(state.log_buffer_size(), 0);
+        // Ending a checkpoint is left to the user
+        assert!(state.session_resources().await.open_checkpoints.contains_key("cp-1"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idle_cleanup_stops_the_playtest_only_when_configured() {
+        for stop_playtest in [false, true] {
+            let state = SharedState::for_tests(Config::default());
+            let calls = fake_plugin(&state, &[]).await;
+            let _dispatcher = Dispatcher::new(state.clone());
+            state.update_playtest(true, Some("play-1".into()), Some("play".into())).await;
+            let watcher = tokio::spawn(run(state.clone(), TIMEOUT, stop_playtest));
+
+            tokio::time::sleep(TIMEOUT + Duration::from_secs(1)).await;
+            assert!(state.is_idle());
+            assert_eq!(calls.tools().contains(&"studio-playtest_stop".to_string()), stop_playtest);
+            watcher.abort();
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn teardown_closes_everything_and_survives_failing_steps() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = fake_plugin(&state, &["studio-npc_driver_stop"]).await;
+        let dispatcher = Dispatcher::new(state.clone());
+        dispatcher.call_tool("studio-logs_subscribe", json!({})).await.unwrap();
+        leave_resources_open(&state).await;
+        state.update_playtest(true, Some("play-1".into()), Some("play".into())).await;
+
+        teardown(&state).await;
+        assert_eq!(
+            calls.tools(),
+            [
+                "studio-logs_subscribe",
+                "studio-logs_unsubscribe",
+                "studio-npc_driver_stop",
+                "studio-virtualuser_key",
+                "studio-playtest_stop"
+            ]
+        );
+        assert!(!state.logs_subscribed());
+        // A driver the plugin could not stop is forgotten all the same
+        let resources = state.session_resources().await;
+        assert!(resources.npc_drivers.is_empty());
+        assert!(resources.open_checkpoints.contains_key("cp-1"));
+    }
+}
@@ -0,0 +1,98 @@
+//! Identifiers that sort in the order they were issued.
+//!
+//! `<counter>-<random>`: a zero-padded per-process counter, so two ids from this
+//! process compare (as plain strings) in creation order, and 8 random hex digits, so
+//! ids from different runs don't collide. Everything that receives an id treats it
+//! as an opaque string, so ids in the older bare-UUID form are still accepted.
+//!
+//! In deterministic mode the random part comes from the seeded generator, so the
+//! same sequence of calls yields the same ids.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::providers::Rng;
+
+pub struct IdGenerator {
+    counter: AtomicU64,
+    rng: Arc<dyn Rng>,
+}
+
+impl IdGenerator {
+    pub fn new(rng: Arc<dyn Rng>) -> Self {
+        Self {
+            counter: AtomicU64::new(0),
+            rng,
+        }
+    }
+
+    pub fn next(&self) -> String {
+        let seq = self.counter.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{seq:010}-{:08x}", self.rng.next_u64() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{OsRng, SeededRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn ids_are_a_padded_counter_and_a_random_suffix() {
+        let ids = IdGenerator::new(Arc::new(OsRng));
+        let id = ids.next();
+        let (seq, suffix) = id.split_once('-').unwrap();
+        assert_eq!(seq, "0000000001");
+        assert_eq!(suffix.len(), 8);
+        assert!(suffix.chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()), "{id}");
+    }
+
+    #[test]
+    fn ids_sort_as_strings_in_the_order_they_were_issued() {
+        let ids = IdGenerator::new(Arc::new(OsRng));
+        // Past the point where unpadded counters would sort "10" before "9"
+        let issued: Vec<String> = (0..1_000).map(|_| ids.next()).collect();
+        let mut sorted = issued.clone();
+        sorted.sort();
+        assert_eq!(sorted, issued);
+    }
+
+    #[test]
+    fn ids_stay_unique_and_ordered_under_concurrency() {
+        const THREADS: usize = 8;
+        const PER_THREAD: usize = 5_000;
+        let ids = Arc::new(IdGenerator::new(Arc::new(OsRng)));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let ids = ids.clone();
+                std::thread::spawn(move || (0..PER_THREAD).map(|_| ids.next()).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut all = HashSet::new();
+        let mut counters = HashSet::new();
+        for handle in handles {
+            let issued = handle.join().unwrap();
+            // Each thread sees its own ids in order
+            assert!(issued.windows(2).all(|pair| pair[0] < pair[1]));
+            for id in issued {
+                counters.insert(id[..10].to_string());
+                all.insert(id);
+            }
+        }
+        assert_eq!(all.len(), THREADS * PER_THREAD);
+        // No counter value is handed out twice, whatever the random part
+        assert_eq!(counters.len(), THREADS * PER_THREAD);
+    }
+
+    #[test]
+    fn seeded_generators_repeat_their_ids() {
+        let first = IdGenerator::new(Arc::new(SeededRng::new(42)));
+        let second = IdGenerator::new(Arc::new(SeededRng::new(42)));
+        let other = IdGenerator::new(Arc::new(SeededRng::new(43)));
+        let (a, b, c) = (first.next(), second.next(), other.next());
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a[..10], c[..10]);
+    }
+}
@@ -0,0 +1,100 @@
+//! Advisory lock on a capture folder's `index.json`.
+//!
+//! The index is rewritten as a whole, so two processes using one capture folder (two
+//! servers, or a server and `mcpctl import`) must not interleave their
+//! read-modify-write cycles. Each cycle holds an exclusive lock on `index.json.lock`
+//! and re-reads the index under it, so entries the other process added in between are
+//! kept. The lock is advisory (flock / LockFileEx) and goes away with the process that
+//! held it, so a crashed server never leaves it taken.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+pub const LOCK_NAME: &str = "index.json.lock";
+
+/// How long to wait for another process to finish its index update.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+const RETRY_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Exclusive lock on the index of one capture folder, released on drop.
+pub struct IndexLock {
+    _file: File,
+}
+
+impl IndexLock {
+    /// Wait up to `LOCK_TIMEOUT` for the lock. Index updates take milliseconds, so
+    /// running out means the holder is stuck. Blocks the thread while waiting; async
+    /// code reaches it through `SharedState::with_captures`.
+    pub fn acquire(capture_dir: &Path) -> Result<Self> {
+        Self::acquire_within(capture_dir, LOCK_TIMEOUT)
+    }
+
+    fn acquire_within(capture_dir: &Path, timeout: Duration) -> Result<Self> {
+        let path = capture_dir.join(LOCK_NAME);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("can't open {}", path.display()))?;
+        let started = Instant::now();
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if started.elapsed() < timeout => {
+                    std::thread::sleep(RETRY_INTERVAL)
+                }
+                Err(TryLockError::WouldBlock) => bail!(
+                    "index.json in {} is locked by another process (waited {:.1}s)",
+                    capture_dir.display(),
+                    timeout.as_secs_f64()
+                ),
+                Err(TryLockError::Error(e)) => {
+                    return Err(e).with_context(|| format!("can't lock {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+/// Temp file for writing `index.json`, named after this process so two writers never
+/// share one.
+pub fn temp_path(capture_dir: &Path) -> PathBuf {
+    capture_dir.join(format!("index.json.{}.tmp", std::process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn second_holder_times_out_while_the_first_holds_the_lock() {
+        let dir = tempfile::tempdir().unwrap();
+        let held = IndexLock::acquire(dir.path()).unwrap();
+        let started = Instant::now();
+        let err = IndexLock::acquire_within(dir.path(), Duration::from_millis(150)).err().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert!(err.to_string().contains("locked by another process"), "{err}");
+        drop(held);
+        IndexLock::acquire_within(dir.path(), Duration::from_millis(150)).unwrap();
+    }
+
+    #[test]
+    fn waiter_gets_the_lock_once_it_is_released() {
+        let dir = tempfile::tempdir().unwrap();
+        let held = IndexLock::acquire(dir.path()).unwrap();
+        let path = dir.path().to_path_buf();
+        let waiter = std::thread::spawn(move || IndexLock::acquire_within(&path, Duration::from_secs(5)).map(|_| ()));
+        std::thread::sleep(Duration::from_millis(100));
+        drop(held);
+        waiter.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn temp_path_names_this_process() {
+        let path = temp_path(Path::new("/captures"));
+        assert_eq!(path, Path::new("/captures").join(format!("index.json.{}.tmp", std::process::id())));
+    }
+}
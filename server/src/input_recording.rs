@@ -0,0 +1,368 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+
+use crate::types::InputSample;
+
+/// Storage format version written to `recordings/<name>.json`.
+pub const RECORDING_FORMAT_VERSION: u32 = 1;
+
+/// Upper bound on samples kept for one recording (about 10 minutes of busy input).
+pub const MAX_RECORDING_SAMPLES: usize = 20_000;
+
+/// Look updates closer together than this are coalesced on replay; the bridge
+/// can't usefully apply more than one per frame anyway.
+const MIN_LOOK_INTERVAL_MS: u64 = 50;
+
+/// Clock-rate fitting needs at least this many batches spread over at least this
+/// much bridge time; over shorter windows delivery jitter swamps any real drift.
+const MIN_DRIFT_BATCHES: usize = 4;
+const MIN_DRIFT_SPAN_MS: u64 = 5_000;
+
+/// Rate differences below this are indistinguishable from delivery jitter.
+const DRIFT_TOLERANCE: f64 = 0.005;
+
+/// A fitted rate further than this from 1.0 is a stall or a clock jump, not drift.
+const MAX_DRIFT: f64 = 0.1;
+
+pub const MIN_REPLAY_SPEED: f64 = 0.25;
+pub const MAX_REPLAY_SPEED: f64 = 4.0;
+
+/// A named input recording artifact, as stored in the capture directory.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InputRecording {
+    pub version: u32,
+    pub name: String,
+    pub recorded_at: String,
+    pub duration_ms: u64,
+    /// Server milliseconds per bridge millisecond, when the sample timestamps were
+    /// corrected for bridge clock drift.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_rate: Option<f64>,
+    pub samples: Vec<InputSample>,
+}
+
+/// When a batch of samples reached the server, on both clocks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BatchArrival {
+    /// Server clock, milliseconds since the recording started.
+    pub received_ms: u64,
+    /// Newest sample timestamp in the batch (bridge clock).
+    pub sample_ms: u64,
+}
+
+/// Recording names become file names, so keep them to a safe character set.
+pub fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        anyhow::bail!("Recording name must be 1-64 characters");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        anyhow::bail!("Recording name may only contain letters, digits, '_' and '-'");
+    }
+    Ok(())
+}
+
+/// Rebuild a clean timeline from the raw sample stream.
+///
+/// Batches can arrive out of order (the bridge pushes them from separate tasks), the
+/// bridge clock can overshoot the reported duration slightly, and a key can appear to
+/// go down twice if a batch was retried. This sorts by time (stable, so same-tick
+/// transitions keep their order), clamps to `duration_ms`, drops transitions that
+/// don't change key state and appends releases for keys still held at the end so a
+/// replay never leaves the character walking.
+pub fn normalize(mut samples: Vec<InputSample>, duration_ms: u64) -> Vec<InputSample> {
+    samples.sort_by_key(|s| s.t_ms());
+
+    let mut held: BTreeSet<String> = BTreeSet::new();
+    let mut out = Vec::with_capacity(samples.len());
+    for mut sample in samples {
+        match &mut sample {
+            InputSample::Key {
+                t_ms,
+                key_code,
+                action,
+            } => {
+                *t_ms = (*t_ms).min(duration_ms);
+                let changed = match action.as_str() {
+                    "down" => held.insert(key_code.clone()),
+                    "up" => held.remove(key_code.as_str()),
+                    _ => false,
+                };
+                if !changed {
+                    continue;
+                }
+            }
+            InputSample::Look { t_ms, .. } => {
+                *t_ms = (*t_ms).min(duration_ms);
+            }
+        }
+        out.push(sample);
+    }
+
+    for key_code in held {
+        out.push(InputSample::Key {
+            t_ms: duration_ms,
+            key_code,
+            action: "up".into(),
+        });
+    }
+    out
+}
+
+/// Estimate how fast the server clock runs relative to the bridge clock while
+/// recording, as server milliseconds per bridge millisecond.
+///
+/// The bridge stamps samples with its own clock, which can run fast or slow against
+/// the server (a throttled Studio, a sleeping laptop). Each batch arrives a little
+/// after its newest sample, so a least-squares line through (sample time, arrival
+/// time) has the clock rate as its slope; latency only shifts the line. Returns
+/// `None` when there isn't enough data for a reliable fit, when the rate is within
+/// jitter of 1.0, or when it is too far off to be drift.
+pub fn drift_rate(batches: &[BatchArrival]) -> Option<f64> {
+    let first = batches.iter().map(|b| b.sample_ms).min()?;
+    let last = batches.iter().map(|b| b.sample_ms).max()?;
+    if batches.len() < MIN_DRIFT_BATCHES || last - first < MIN_DRIFT_SPAN_MS {
+        return None;
+    }
+
+    let n = batches.len() as f64;
+    let mean_x = batches.iter().map(|b| b.sample_ms as f64).sum::<f64>() / n;
+    let mean_y = batches.iter().map(|b| b.received_ms as f64).sum::<f64>() / n;
+    let (mut cov, mut var) = (0.0, 0.0);
+    for b in batches {
+        let dx = b.sample_ms as f64 - mean_x;
+        cov += dx * (b.received_ms as f64 - mean_y);
+        var += dx * dx;
+    }
+    let rate = cov / var;
+    let off = (rate - 1.0).abs();
+    (DRIFT_TOLERANCE..=MAX_DRIFT).contains(&off).then_some(rate)
+}
+
+/// Move bridge timestamps onto the server timeline by scaling them by `rate`.
+pub fn correct_drift(samples: &mut [InputSample], rate: f64) {
+    for sample in samples {
+        let (InputSample::Key { t_ms, .. } | InputSample::Look { t_ms, .. }) = sample;
+        *t_ms = scale_ms(*t_ms, rate);
+    }
+}
+
+pub fn scale_ms(t_ms: u64, rate: f64) -> u64 {
+    (t_ms as f64 * rate).round() as u64
+}
+
+/// Convert a recording into `studio-virtualuser_sequence` steps, scaling time by
+/// `1 / speed`. Each step carries an absolute offset (`atMs`) from the start of the
+/// sequence, so the bridge schedules against its own start time instead of
+/// accumulating per-step sleep error.
+pub fn replay_steps(recording: &InputRecording, speed: f64) -> Vec<Value> {
+    let scale = |t_ms: u64| scale_ms(t_ms, 1.0 / speed);
+    let mut steps = Vec::with_capacity(recording.samples.len());
+    // (atMs, index into steps) of the last look step
+    let mut last_look: Option<(u64, usize)> = None;
+
+    for sample in &recording.samples {
+        match sample {
+            InputSample::Key {
+                t_ms,
+                key_code,
+                action,
+            } => {
+                // Space is a one-shot jump on the bridge; its release is meaningless
+                if key_code == "Space" && action == "up" {
+                    continue;
+                }
+                steps.push(json!({ "atMs": scale(*t_ms), "keyCode": key_code, "action": action }));
+            }
+            InputSample::Look { t_ms, direction } => {
+                let at_ms = scale(*t_ms);
+                match last_look {
+                    // Keep the newest direction at the earlier step's time
+                    Some((prev_at, idx)) if at_ms < prev_at + MIN_LOOK_INTERVAL_MS => {
+                        steps[idx]["lookDirection"] = json!(direction);
+                    }
+                    _ => {
+                        last_look = Some((at_ms, steps.len()));
+                        steps.push(json!({ "atMs": at_ms, "lookDirection": direction }));
+                    }
+                }
+            }
+        }
+    }
+    steps
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Vec3;
+
+    fn key(t_ms: u64, key_code: &str, action: &str) -> InputSample {
+        InputSample::Key {
+            t_ms,
+            key_code: key_code.into(),
+            action: action.into(),
+        }
+    }
+
+    fn look(t_ms: u64, x: f64) -> InputSample {
+        InputSample::Look {
+            t_ms,
+            direction: Vec3 { x, y: 0.0, z: -1.0 },
+        }
+    }
+
+    fn recording(samples: Vec<InputSample>) -> InputRecording {
+        InputRecording {
+            version: RECORDING_FORMAT_VERSION,
+            name: "test".into(),
+            recorded_at: "2026-01-01T00:00:00Z".into(),
+            duration_ms: samples.iter().map(InputSample::t_ms).max().unwrap_or(0),
+            clock_rate: None,
+            samples,
+        }
+    }
+
+    /// A synthetic batch stream: one batch every `every_ms` of bridge time, arriving
+    /// after 30ms plus up to 40ms of deterministic jitter, with the server clock
+    /// running `rate` times as fast as the bridge's.
+    fn arrivals(span_ms: u64, every_ms: u64, rate: f64) -> Vec<BatchArrival> {
+        (1..=span_ms / every_ms)
+            .map(|i| {
+                let sample_ms = i * every_ms;
+                let jitter = (i * 7919) % 40;
+                BatchArrival {
+                    received_ms: scale_ms(sample_ms, rate) + 30 + jitter,
+                    sample_ms,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn normalize_sorts_out_of_order_batches_and_keeps_same_tick_order() {
+        let samples = vec![key(300, "W", "up"), key(100, "W", "down"), key(300, "A", "down"), key(400, "A", "up")];
+        let out = normalize(samples, 1000);
+        assert_eq!(out, vec![key(100, "W", "down"), key(300, "W", "up"), key(300, "A", "down"), key(400, "A", "up")]);
+    }
+
+    #[test]
+    fn normalize_clamps_to_the_duration() {
+        let out = normalize(vec![key(100, "W", "down"), key(1200, "W", "up"), look(1500, 1.0)], 1000);
+        assert_eq!(out, vec![key(100, "W", "down"), key(1000, "W", "up"), look(1000, 1.0)]);
+    }
+
+    #[test]
+    fn normalize_drops_transitions_that_do_not_change_key_state() {
+        let samples = vec![
+            key(100, "W", "down"),
+            key(150, "W", "down"),
+            key(200, "W", "up"),
+            key(250, "W", "up"),
+            key(260, "S", "up"),
+            key(270, "S", "wiggle"),
+        ];
+        assert_eq!(normalize(samples, 1000), vec![key(100, "W", "down"), key(200, "W", "up")]);
+    }
+
+    #[test]
+    fn normalize_releases_keys_still_held_at_the_end() {
+        let out = normalize(vec![key(100, "W", "down"), key(200, "D", "down"), key(300, "D", "up")], 800);
+        assert_eq!(out.last(), Some(&key(800, "W", "up")));
+        assert_eq!(out.len(), 4);
+    }
+
+    #[test]
+    fn replay_steps_scale_time_by_speed() {
+        let rec = recording(vec![key(100, "W", "down"), key(1000, "W", "up")]);
+        let at = |speed| -> Vec<u64> {
+            replay_steps(&rec, speed).iter().map(|s| s["atMs"].as_u64().unwrap()).collect()
+        };
+        assert_eq!(at(1.0), vec![100, 1000]);
+        assert_eq!(at(2.0), vec![50, 500]);
+        assert_eq!(at(0.5), vec![200, 2000]);
+    }
+
+    #[test]
+    fn replay_steps_coalesce_close_look_updates() {
+        let rec = recording(vec![look(0, 0.1), look(20, 0.2), look(40, 0.3), look(60, 0.4)]);
+        let steps = replay_steps(&rec, 1.0);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0]["atMs"], 0);
+        assert_eq!(steps[0]["lookDirection"]["x"], 0.3);
+        assert_eq!(steps[1]["atMs"], 60);
+
+        // At double speed the same updates land closer together
+        assert_eq!(replay_steps(&rec, 2.0).len(), 1);
+    }
+
+    #[test]
+    fn replay_steps_skip_space_releases() {
+        let rec = recording(vec![key(0, "Space", "down"), key(100, "Space", "up"), key(200, "W", "down")]);
+        let steps = replay_steps(&rec, 1.0);
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0]["keyCode"], "Space");
+        assert_eq!(steps[1]["keyCode"], "W");
+    }
+
+    #[test]
+    fn drift_rate_recovers_a_slow_bridge_clock() {
+        let rate = drift_rate(&arrivals(20_000, 250, 1.02)).unwrap();
+        assert!((rate - 1.02).abs() < 0.002, "{rate}");
+        let rate = drift_rate(&arrivals(20_000, 250, 0.97)).unwrap();
+        assert!((rate - 0.97).abs() < 0.002, "{rate}");
+    }
+
+    #[test]
+    fn drift_rate_ignores_jitter_on_matching_clocks() {
+        assert_eq!(drift_rate(&arrivals(20_000, 250, 1.0)), None);
+    }
+
+    #[test]
+    fn drift_rate_needs_enough_data() {
+        assert_eq!(drift_rate(&[]), None);
+        // Too short a span
+        assert_eq!(drift_rate(&arrivals(3_000, 250, 1.05)), None);
+        // Too few batches
+        assert_eq!(drift_rate(&arrivals(20_000, 6_000, 1.05)), None);
+    }
+
+    #[test]
+    fn drift_rate_rejects_stalls() {
+        let mut batches = arrivals(20_000, 250, 1.0);
+        // The server stopped reading for 10 seconds halfway through
+        let half = batches.len() / 2;
+        for b in &mut batches[half..] {
+            b.received_ms += 10_000;
+        }
+        assert_eq!(drift_rate(&batches), None);
+    }
+
+    #[test]
+    fn corrected_stream_lines_up_with_server_time() {
+        // Bridge clock runs slow: 20s of server time reads as ~19.6s on the bridge
+        let rate = 1.02;
+        let mut samples: Vec<InputSample> = (0..20)
+            .flat_map(|i| [key(i * 980, "W", "down"), key(i * 980 + 490, "W", "up")])
+            .collect();
+        let fitted = drift_rate(&arrivals(19_600, 245, rate)).unwrap();
+        correct_drift(&mut samples, fitted);
+        let last = samples.last().unwrap().t_ms();
+        let expected = scale_ms(19 * 980 + 490, rate);
+        assert!(last.abs_diff(expected) <= 40, "{last} vs {expected}");
+    }
+
+    #[test]
+    fn clock_rate_is_omitted_from_uncorrected_recordings() {
+        let value = serde_json::to_value(recording(vec![key(0, "W", "down")])).unwrap();
+        assert!(value.get("clockRate").is_none());
+        let mut rec: InputRecording = serde_json::from_value(value).unwrap();
+        assert_eq!(rec.clock_rate, None);
+        rec.clock_rate = Some(1.02);
+        assert_eq!(serde_json::to_value(&rec).unwrap()["clockRate"], 1.02);
+    }
+}
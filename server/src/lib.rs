@@ -0,0 +1,116 @@
+//! Core of the YippieBlox MCP server, for running it inside another Rust program
+//! instead of as a subprocess.
+//!
+//! [`ServerBuilder`] starts the server with its HTTP bridge, which the Studio plugin
+//! connects to, and returns a [`ServerHandle`] for calling tools in-process and
+//! stopping the server:
+//!
+//! ```
+//! use roblox_studio_yippieblox_mcp_server::ServerBuilder;
+//! use serde_json::json;
+//!
+//! # tokio::runtime::Runtime::new().unwrap().block_on(async {
+//! let server = ServerBuilder::new()
+//!     .port(0)
+//!     .token("change-me")
+//!     .capture_dir(std::env::temp_dir().join("yippieblox-example"))
+//!     .mock_studio(true)
+//!     .spawn()
+//!     .await?;
+//! println!("Studio plugin connects to http://{}", server.local_addr());
+//!
+//! let status = server.call_tool("studio-status", json!({})).await?;
+//! assert_eq!(status["isError"], false);
+//! server.stop().await;
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! # }).unwrap();
+//! ```
+//!
+//! The pieces the builder assembles can also be driven directly:
+//! [`bridge_http::serve`] on a listener of your own, [`serve_stdio`] for an MCP
+//! client on stdin/stdout, and [`Dispatcher`] for further in-process sessions.
+//!
+//! # Stability
+//!
+//! The items exported here follow semver. Exceptions:
+//!
+//! - [`SharedState`] is exported as a handle to pass between the pieces above. Its
+//!   methods serve the server's own modules and may change in minor releases.
+//! - [`Config`] fields that use types not exported here (redaction patterns, hooks,
+//!   alert rules, profiles, log diff normalizers) can be read but are only meant to be
+//!   set through `YIPPIE_*` variables.
+//! - Hidden modules exist for the `mcpctl` and server binaries and are not API.
+
+mod alerts;
+mod async_calls;
+#[doc(hidden)]
+pub mod bench;
+pub mod bridge_http;
+mod build_info;
+mod capture_compare;
+mod capture_peers;
+mod captures;
+#[doc(hidden)]
+pub mod client_config;
+mod client_roots;
+mod clock_sync;
+mod config;
+mod event_order;
+mod gui_snapshot;
+mod hooks;
+mod idle;
+mod ids;
+mod index_lock;
+mod input_recording;
+mod log_archive;
+mod log_args;
+mod log_diff;
+mod log_store;
+mod luau_minify;
+mod mcp_stdio;
+mod mock_studio;
+mod model_diff;
+mod outbound_queue;
+mod output_dir;
+mod partial_result;
+mod path_index;
+mod place_stats;
+mod plugin_settings;
+mod profiles;
+mod providers;
+mod readiness;
+mod redact;
+#[doc(hidden)]
+pub mod render;
+mod replay;
+mod request_limits;
+mod response_cache;
+mod runtime_flags;
+mod sanitize;
+mod server;
+mod server_events;
+mod session;
+mod state;
+#[doc(hidden)]
+pub mod state_bench;
+#[doc(hidden)]
+pub mod state_export;
+mod studio_settings;
+mod test_matrix;
+mod time_report;
+mod tool_history;
+mod traceback;
+mod traffic;
+mod transaction;
+mod tweenable;
+mod types;
+mod variables;
+mod viewport_render;
+mod wall_clock;
+mod wire;
+
+pub use config::{AuthToken, Config};
+pub use mcp_stdio::{run as serve_stdio, tool_definitions, DispatchError, Dispatcher};
+pub use server::{Server, ServerBuilder, ServerHandle, StartError, SHUTDOWN_DRAIN_TIMEOUT};
+pub use state::SharedState;
+pub use types::{McpToolDef, ToolAnnotations};
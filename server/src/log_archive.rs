@@ -0,0 +1,715 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use crate::types::LogEntry;
+
+/// Entries per JSONL segment before it is closed and handed to the indexer.
+const SEGMENT_MAX_ENTRIES: usize = 5_000;
+
+/// Segments kept on disk. Older ones are deleted, which bounds both the disk used and
+/// index memory.
+const MAX_SEGMENTS: usize = 1_000;
+
+const MAX_SEARCH_LIMIT: usize = 500;
+
+/// Persists every log entry to JSONL segments under `<capture_dir>/logs/` and keeps a
+/// small per-segment summary (time range, levels, sessions, distinct terms) so
+/// searches only open segments that can match.
+///
+/// The summaries are a few sets per segment rather than a full-text index (tantivy,
+/// SQLite FTS): segments are written once and searched rarely, a scan of the few that
+/// can match is fast enough, and it keeps a native dependency and a second on-disk
+/// format out of the server.
+pub struct LogArchive {
+    dir: PathBuf,
+    /// Segments older than this are deleted. `None` keeps them up to `MAX_SEGMENTS`.
+    retention: Option<Duration>,
+    writer: Mutex<SegmentWriter>,
+    /// Lock order: `segments` before `pending`, so a search sees every completed
+    /// segment in exactly one of them.
+    segments: RwLock<Vec<SegmentSummary>>,
+    /// Completed segments waiting for the indexer. Searches scan them whole, like the
+    /// active one.
+    pending: Mutex<Vec<PathBuf>>,
+    entries_indexed: AtomicU64,
+}
+
+struct SegmentWriter {
+    path: PathBuf,
+    file: std::fs::File,
+    entries: usize,
+}
+
+struct SegmentSummary {
+    path: PathBuf,
+    first_ts: f64,
+    last_ts: f64,
+    entries: usize,
+    levels: BTreeSet<String>,
+    sessions: BTreeSet<String>,
+    terms: HashSet<String>,
+}
+
+/// Search parameters. Empty `terms` matches everything in range.
+#[derive(Debug, Default)]
+pub struct ArchiveQuery {
+    pub text: String,
+    pub from_ts: Option<f64>,
+    pub to_ts: Option<f64>,
+    pub levels: Vec<String>,
+    pub session_id: Option<String>,
+    pub limit: usize,
+}
+
+impl ArchiveQuery {
+    /// Build a query from tool-style arguments: `query`, `from`/`to` (RFC 3339 or Unix
+    /// seconds), `levels`, `sessionId`, `limit`.
+    pub fn from_args(args: &serde_json::Value) -> Result<Self, String> {
+        let time = |name: &str| -> Result<Option<f64>, String> {
+            match args.get(name) {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(v) if v.is_number() => Ok(v.as_f64()),
+                Some(serde_json::Value::String(s)) => chrono::DateTime::parse_from_rfc3339(s)
+                    .map(|t| Some(t.timestamp_millis() as f64 / 1000.0))
+                    .map_err(|e| format!("'{name}' must be an RFC 3339 timestamp or Unix seconds: {e}")),
+                Some(_) => Err(format!("'{name}' must be an RFC 3339 timestamp or Unix seconds")),
+            }
+        };
+        Ok(Self {
+            text: args.get("query").and_then(|v| v.as_str()).unwrap_or("").to_string(),
+            from_ts: time("from")?,
+            to_ts: time("to")?,
+            levels: args
+                .get("levels")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().filter_map(|l| l.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+            session_id: args.get("sessionId").and_then(|v| v.as_str()).map(String::from),
+            limit: args.get("limit").and_then(|v| v.as_u64()).unwrap_or(50) as usize,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveHit {
+    pub score: u32,
+    pub ts: f64,
+    pub time: String,
+    pub level: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    pub segment: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArchiveStatus {
+    pub dir: String,
+    pub active_segment: String,
+    pub segments_indexed: usize,
+    pub segments_pending: usize,
+    pub entries_indexed: u64,
+    pub indexing: bool,
+}
+
+impl LogArchive {
+    /// Open the archive directory and start a fresh segment. Segments from earlier
+    /// runs are complete and get indexed by `start_indexing`.
+    pub fn open(dir: &Path, retention: Option<Duration>) -> Result<Arc<Self>> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("creating log archive directory {}", dir.display()))?;
+        let writer = SegmentWriter::create(dir)?;
+        Ok(Arc::new(Self {
+            dir: dir.to_path_buf(),
+            retention,
+            writer: Mutex::new(writer),
+            segments: RwLock::new(Vec::new()),
+            pending: Mutex::new(Vec::new()),
+            entries_indexed: AtomicU64::new(0),
+        }))
+    }
+
+    /// Apply retention, then index existing segments (newest first) on a blocking
+    /// thread.
+    pub fn start_indexing(self: &Arc<Self>) {
+        self.prune();
+        let active = self.writer.lock().unwrap_or_else(|e| e.into_inner()).path.clone();
+        let mut paths = match list_segments(&self.dir) {
+            Ok(p) => p,
+            Err(e) => {
+                tracing::warn!(error = %e, "Could not list log archive segments");
+                return;
+            }
+        };
+        paths.retain(|p| *p != active);
+        paths.reverse();
+        if paths.is_empty() {
+            return;
+        }
+
+        tracing::info!(segments = paths.len(), "Indexing log archive in the background");
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).extend(paths.iter().cloned());
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || {
+            for path in paths {
+                archive.index_segment(&path);
+            }
+        });
+    }
+
+    /// Append an entry to the active segment, rotating when it is full.
+    pub fn append(self: &Arc<Self>, entry: &LogEntry) {
+        let completed = {
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = writer.write(entry) {
+                tracing::warn!(error = %e, "Failed to append to log archive");
+                return;
+            }
+            if writer.entries < SEGMENT_MAX_ENTRIES {
+                return;
+            }
+            let next = match SegmentWriter::create(&self.dir) {
+                Ok(next) => next,
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to rotate log archive segment");
+                    return;
+                }
+            };
+            // Queued before the writer lock is released, so searches never lose sight of it
+            let completed = std::mem::replace(&mut *writer, next).path;
+            self.pending.lock().unwrap_or_else(|e| e.into_inner()).push(completed.clone());
+            completed
+        };
+
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || {
+            archive.index_segment(&completed);
+            archive.prune();
+        });
+    }
+
+    /// Force the active segment to disk, before the process exits.
+    pub fn sync(&self) {
+        let writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = writer.file.sync_data() {
+            tracing::warn!(error = %e, "Failed to sync log archive");
+        }
+    }
+
+    fn index_segment(&self, path: &Path) {
+        let summary = summarize_segment(path);
+        let mut segments = self.segments.write().unwrap_or_else(|e| e.into_inner());
+        match summary {
+            Ok(Some(summary)) => {
+                self.entries_indexed
+                    .fetch_add(summary.entries as u64, Ordering::SeqCst);
+                segments.push(summary);
+                segments.sort_by(|a, b| a.first_ts.total_cmp(&b.first_ts));
+            }
+            // Runs that logged nothing leave an empty segment behind
+            Ok(None) => {
+                if std::fs::metadata(path).is_ok_and(|m| m.len() == 0) {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+            Err(e) => tracing::warn!(path = %path.display(), error = %e, "Failed to index log segment"),
+        }
+        self.pending.lock().unwrap_or_else(|e| e.into_inner()).retain(|p| p != path);
+    }
+
+    /// Delete completed segments past the retention age, then the oldest beyond
+    /// `MAX_SEGMENTS`. Segments still waiting for the indexer are left alone.
+    fn prune(&self) {
+        let active = self.writer.lock().unwrap_or_else(|e| e.into_inner()).path.clone();
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let paths = match list_segments(&self.dir) {
+            Ok(paths) => paths,
+            Err(e) => {
+                tracing::warn!(error = %e, "Could not list log archive segments");
+                return;
+            }
+        };
+        let completed: Vec<PathBuf> = paths.into_iter().filter(|p| *p != active && !pending.contains(p)).collect();
+        let now = SystemTime::now();
+        let expired = |path: &Path| {
+            self.retention.is_some_and(|retention| {
+                std::fs::metadata(path)
+                    .and_then(|m| m.modified())
+                    .is_ok_and(|modified| now.duration_since(modified).unwrap_or_default() > retention)
+            })
+        };
+        // Oldest first; the active and pending segments count towards the cap
+        let over_cap = (completed.len() + pending.len() + 1).saturating_sub(MAX_SEGMENTS);
+        let doomed: Vec<PathBuf> = completed
+            .iter()
+            .enumerate()
+            .filter(|(i, path)| *i < over_cap || expired(path))
+            .map(|(_, path)| path.clone())
+            .collect();
+        if doomed.is_empty() {
+            return;
+        }
+
+        // Forget them first so no new search picks them up; one already scanning
+        // skips a segment that has gone
+        {
+            let mut segments = self.segments.write().unwrap_or_else(|e| e.into_inner());
+            segments.retain(|s| {
+                let keep = !doomed.contains(&s.path);
+                if !keep {
+                    self.entries_indexed.fetch_sub(s.entries as u64, Ordering::SeqCst);
+                }
+                keep
+            });
+        }
+        for path in &doomed {
+            if let Err(e) = std::fs::remove_file(path) {
+                tracing::warn!(path = %path.display(), error = %e, "Failed to delete old log segment");
+            }
+        }
+        tracing::info!(segments = doomed.len(), "Deleted log archive segments past retention");
+    }
+
+    /// Segments a search has to read: indexed ones passing `keep`, every one still
+    /// waiting for the indexer, and the active one (flushed first).
+    fn candidates(&self, keep: impl Fn(&SegmentSummary) -> bool) -> Result<Vec<PathBuf>> {
+        let mut candidates: Vec<PathBuf> = {
+            let segments = self.segments.read().unwrap_or_else(|e| e.into_inner());
+            let mut paths: Vec<PathBuf> = segments.iter().filter(|s| keep(s)).map(|s| s.path.clone()).collect();
+            paths.extend(self.pending.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned());
+            paths
+        };
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.file.flush()?;
+        candidates.push(writer.path.clone());
+        Ok(candidates)
+    }
+
+    /// Ranked search over indexed segments plus the active one. Hits score one point
+    /// per matched query term and a bonus for the whole phrase; ties go to the newest.
+    pub fn search(&self, query: &ArchiveQuery) -> Result<Vec<ArchiveHit>> {
+        let terms = tokenize(&query.text);
+        let phrase = query.text.trim().to_lowercase();
+        let limit = query.limit.clamp(1, MAX_SEARCH_LIMIT);
+
+        let candidates = self.candidates(|s| {
+            query.from_ts.is_none_or(|from| s.last_ts >= from)
+                && query.to_ts.is_none_or(|to| s.first_ts <= to)
+                && (query.levels.is_empty() || query.levels.iter().any(|l| s.levels.contains(l)))
+                && query.session_id.as_ref().is_none_or(|id| s.sessions.contains(id))
+                && (terms.is_empty() || terms.iter().any(|t| s.terms.contains(t)))
+        })?;
+
+        let mut hits = Vec::new();
+        for path in candidates {
+            let segment = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            for (line, entry) in read_segment(&path)? {
+                if query.from_ts.is_some_and(|from| entry.ts < from)
+                    || query.to_ts.is_some_and(|to| entry.ts > to)
+                    || (!query.levels.is_empty() && !query.levels.contains(&entry.level))
+                    || (query.session_id.is_some() && entry.session_id != query.session_id)
+                {
+                    continue;
+                }
+                let score = if terms.is_empty() {
+                    1
+                } else {
+                    let message_terms: HashSet<String> = tokenize(&entry.message).into_iter().collect();
+                    let matched = terms.iter().filter(|t| message_terms.contains(*t)).count() as u32;
+                    let phrase_bonus = terms.len() > 1 && entry.message.to_lowercase().contains(&phrase);
+                    matched + if phrase_bonus { 2 } else { 0 }
+                };
+                if score == 0 {
+                    continue;
+                }
+                hits.push(ArchiveHit {
+                    score,
+                    ts: entry.ts,
+                    time: format_ts(entry.ts),
+                    level: entry.level,
+                    message: entry.message,
+                    session_id: entry.session_id,
+                    segment: segment.clone(),
+                    line,
+                });
+            }
+        }
+
+        hits.sort_by(|a, b| b.score.cmp(&a.score).then(b.ts.total_cmp(&a.ts)));
+        hits.truncate(limit);
+        Ok(hits)
+    }
+
+    /// `search` on a blocking thread, for callers on the async runtime.
+    pub async fn search_blocking(self: &Arc<Self>, query: ArchiveQuery) -> Result<Vec<ArchiveHit>> {
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || archive.search(&query)).await?
+    }
+
+    /// Every archived entry of one playtest session, oldest first, capped at `max`.
+    /// The bool is true when the cap cut entries off.
+    pub fn session_entries(&self, session_id: &str, max: usize) -> Result<(Vec<LogEntry>, bool)> {
+        let candidates = self.candidates(|s| s.sessions.contains(session_id))?;
+
+        let mut entries: Vec<LogEntry> = Vec::new();
+        for path in candidates {
+            entries.extend(
+                read_segment(&path)?
+                    .into_iter()
+                    .map(|(_, entry)| entry)
+                    .filter(|entry| entry.session_id.as_deref() == Some(session_id)),
+            );
+        }
+        entries.sort_by(|a, b| a.ts.total_cmp(&b.ts).then(a.seq.cmp(&b.seq)));
+        let truncated = entries.len() > max;
+        entries.truncate(max);
+        Ok((entries, truncated))
+    }
+
+    /// `session_entries` on a blocking thread.
+    pub async fn session_entries_blocking(
+        self: &Arc<Self>,
+        session_id: String,
+        max: usize,
+    ) -> Result<(Vec<LogEntry>, bool)> {
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || archive.session_entries(&session_id, max)).await?
+    }
+
+    pub fn status(&self) -> ArchiveStatus {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).len();
+        let active_segment = self
+            .writer
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        ArchiveStatus {
+            dir: self.dir.to_string_lossy().to_string(),
+            active_segment,
+            segments_indexed: self.segments.read().unwrap_or_else(|e| e.into_inner()).len(),
+            segments_pending: pending,
+            entries_indexed: self.entries_indexed.load(Ordering::SeqCst),
+            indexing: pending > 0,
+        }
+    }
+}
+
+impl SegmentWriter {
+    fn create(dir: &Path) -> Result<Self> {
+        let stamp = chrono::Utc::now().format("%Y%m%d_%H%M%S%3f").to_string();
+        // A segment rotated within the same millisecond gets a suffix, which sorts after it
+        for attempt in 0.. {
+            let name = match attempt {
+                0 => format!("segment_{stamp}.jsonl"),
+                n => format!("segment_{stamp}_{n}.jsonl"),
+            };
+            let path = dir.join(name);
+            match std::fs::OpenOptions::new().create_new(true).append(true).open(&path) {
+                Ok(file) => {
+                    return Ok(Self {
+                        path,
+                        file,
+                        entries: 0,
+                    })
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => continue,
+                Err(e) => return Err(e).with_context(|| format!("opening log segment {}", path.display())),
+            }
+        }
+        unreachable!()
+    }
+
+    fn write(&mut self, entry: &LogEntry) -> Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.entries += 1;
+        Ok(())
+    }
+}
+
+fn list_segments(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    // Names embed the creation time, so this is oldest first
+    paths.sort();
+    Ok(paths)
+}
+
+/// Parse a segment, skipping lines that aren't valid entries (e.g. a torn final
+/// line after a crash). Line numbers are 1-based. A segment deleted by retention
+/// since it was listed reads as empty.
+fn read_segment(path: &Path) -> Result<Vec<(usize, LogEntry)>> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("opening log segment {}", path.display())),
+    };
+    Ok(std::io::BufReader::new(file)
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let entry = serde_json::from_str::<LogEntry>(&line.ok()?).ok()?;
+            Some((i + 1, entry))
+        })
+        .collect())
+}
+
+fn summarize_segment(path: &Path) -> Result<Option<SegmentSummary>> {
+    let entries = read_segment(path)?;
+    let (Some((_, first)), Some((_, last))) = (entries.first(), entries.last()) else {
+        return Ok(None);
+    };
+    let mut summary = SegmentSummary {
+        path: path.to_path_buf(),
+        first_ts: first.ts,
+        last_ts: last.ts,
+        entries: entries.len(),
+        levels: BTreeSet::new(),
+        sessions: BTreeSet::new(),
+        terms: HashSet::new(),
+    };
+    for (_, entry) in &entries {
+        summary.first_ts = summary.first_ts.min(entry.ts);
+        summary.last_ts = summary.last_ts.max(entry.ts);
+        summary.levels.insert(entry.level.clone());
+        if let Some(session_id) = &entry.session_id {
+            summary.sessions.insert(session_id.clone());
+        }
+        summary.terms.extend(tokenize(&entry.message));
+    }
+    Ok(Some(summary))
+}
+
+/// Lowercased words of two or more letters/digits/underscores.
+fn tokenize(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|t| t.chars().count() >= 2)
+        .map(str::to_lowercase)
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+fn format_ts(ts: f64) -> String {
+    chrono::DateTime::from_timestamp_millis((ts * 1000.0) as i64)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(seq: u64, ts: f64, level: &str, message: &str, session: Option<&str>) -> LogEntry {
+        LogEntry {
+            seq,
+            ts,
+            level: level.into(),
+            message: message.into(),
+            session_id: session.map(String::from),
+        }
+    }
+
+    /// Write a completed segment from an earlier run; names sort by creation time.
+    fn old_segment(dir: &Path, name: &str, entries: &[LogEntry]) -> PathBuf {
+        let path = dir.join(format!("segment_{name}.jsonl"));
+        let lines: String = entries.iter().map(|e| serde_json::to_string(e).unwrap() + "\n").collect();
+        std::fs::write(&path, lines).unwrap();
+        path
+    }
+
+    fn query(text: &str) -> ArchiveQuery {
+        ArchiveQuery { text: text.into(), limit: 50, ..Default::default() }
+    }
+
+    async fn settle(archive: &LogArchive) {
+        for _ in 0..500 {
+            if !archive.status().indexing {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("indexing never finished");
+    }
+
+    #[test]
+    fn pending_segments_are_searched_before_they_are_indexed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = LogArchive::open(tmp.path(), None).unwrap();
+        let path = old_segment(tmp.path(), "20200101_000000000", &[
+            entry(1, 100.0, "error", "boot failed in Loader", Some("s1")),
+            entry(2, 101.0, "info", "retrying", Some("s1")),
+        ]);
+        // Queued for the indexer but not summarized yet
+        archive.pending.lock().unwrap().push(path.clone());
+
+        let hits = archive.search(&query("loader")).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].segment, "segment_20200101_000000000.jsonl");
+        let (entries, truncated) = archive.session_entries("s1", 10).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(!truncated);
+        assert!(archive.status().indexing);
+
+        archive.index_segment(&path);
+        assert!(archive.pending.lock().unwrap().is_empty());
+        assert_eq!(archive.status().segments_indexed, 1);
+        assert!(!archive.status().indexing);
+        // Now found through the summary, still exactly once
+        assert_eq!(archive.search(&query("loader")).unwrap().len(), 1);
+        assert_eq!(archive.session_entries("s1", 10).unwrap().0.len(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn rotated_segment_stays_searchable_while_it_is_indexed() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = LogArchive::open(tmp.path(), None).unwrap();
+        archive.append(&entry(0, 1.0, "error", "needle in the first segment", Some("s1")));
+        for seq in 1..SEGMENT_MAX_ENTRIES as u64 {
+            archive.append(&entry(seq, 1.0 + seq as f64, "info", "filler", Some("s1")));
+        }
+        // The first segment has just been handed to the indexer
+        let hits = archive.search(&query("needle")).unwrap();
+        assert_eq!(hits.len(), 1, "lost the rotated segment");
+        assert_eq!(archive.session_entries("s1", 10).unwrap().0[0].message, "needle in the first segment");
+
+        archive.append(&entry(SEGMENT_MAX_ENTRIES as u64, 1e6, "error", "needle in the second", Some("s2")));
+        settle(&archive).await;
+        let hits = archive.search(&query("needle")).unwrap();
+        assert_eq!(hits.len(), 2, "{hits:?}");
+        assert_eq!(archive.status().segments_indexed, 1);
+        assert_eq!(archive.status().entries_indexed, SEGMENT_MAX_ENTRIES as u64);
+    }
+
+    #[test]
+    fn search_ranks_phrase_and_term_matches_and_filters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = LogArchive::open(tmp.path(), None).unwrap();
+        let path = old_segment(tmp.path(), "20200101_000000000", &[
+            entry(1, 10.0, "error", "Script timeout exceeded", Some("a")),
+            entry(2, 20.0, "warning", "timeout while loading script", Some("b")),
+            entry(3, 30.0, "info", "unrelated", Some("a")),
+        ]);
+        archive.index_segment(&path);
+
+        let hits = archive.search(&query("script timeout")).unwrap();
+        assert_eq!(hits.iter().map(|h| h.score).collect::<Vec<_>>(), [4, 2]);
+        assert_eq!(hits[0].message, "Script timeout exceeded");
+        assert_eq!(hits[0].line, 1);
+
+        let only_warnings = ArchiveQuery { levels: vec!["warning".into()], ..query("timeout") };
+        assert_eq!(archive.search(&only_warnings).unwrap().len(), 1);
+        let in_range = ArchiveQuery { from_ts: Some(15.0), to_ts: Some(25.0), ..query("") };
+        assert_eq!(archive.search(&in_range).unwrap()[0].ts, 20.0);
+        let session = ArchiveQuery { session_id: Some("a".into()), ..query("") };
+        assert_eq!(archive.search(&session).unwrap().len(), 2);
+        assert!(archive.search(&query("nothing")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn segments_past_the_retention_age_are_deleted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let old = old_segment(tmp.path(), "20200101_000000000", &[entry(1, 1.0, "info", "ancient", None)]);
+        let recent = old_segment(tmp.path(), "20200102_000000000", &[entry(2, 2.0, "info", "recent", None)]);
+        let week_ago = SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60);
+        std::fs::File::options().write(true).open(&old).unwrap().set_modified(week_ago).unwrap();
+
+        let archive = LogArchive::open(tmp.path(), Some(Duration::from_secs(24 * 60 * 60))).unwrap();
+        archive.index_segment(&old);
+        archive.index_segment(&recent);
+        assert_eq!(archive.status().entries_indexed, 2);
+        archive.prune();
+
+        assert!(!old.exists());
+        assert!(recent.exists());
+        assert_eq!(archive.status().segments_indexed, 1);
+        assert_eq!(archive.status().entries_indexed, 1);
+        assert!(archive.search(&query("ancient")).unwrap().is_empty());
+        assert_eq!(archive.search(&query("recent")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn no_retention_keeps_old_segments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let old = old_segment(tmp.path(), "20200101_000000000", &[entry(1, 1.0, "info", "ancient", None)]);
+        let year_ago = SystemTime::now() - Duration::from_secs(365 * 24 * 60 * 60);
+        std::fs::File::options().write(true).open(&old).unwrap().set_modified(year_ago).unwrap();
+        LogArchive::open(tmp.path(), None).unwrap().prune();
+        assert!(old.exists());
+    }
+
+    #[test]
+    fn oldest_segments_beyond_the_cap_are_deleted() {
+        let tmp = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = (0..MAX_SEGMENTS + 3)
+            .map(|i| old_segment(tmp.path(), &format!("2020_{i:06}"), &[entry(i as u64, i as f64, "info", "x", None)]))
+            .collect();
+        let archive = LogArchive::open(tmp.path(), None).unwrap();
+        archive.prune();
+
+        // The active segment counts towards the cap
+        assert_eq!(list_segments(tmp.path()).unwrap().len(), MAX_SEGMENTS);
+        assert!(paths[..4].iter().all(|p| !p.exists()));
+        assert!(paths[4..].iter().all(|p| p.exists()));
+    }
+
+    #[test]
+    fn pending_segments_are_not_pruned() {
+        let tmp = tempfile::tempdir().unwrap();
+        let old = old_segment(tmp.path(), "20200101_000000000", &[entry(1, 1.0, "info", "queued", None)]);
+        std::fs::File::options()
+            .write(true)
+            .open(&old)
+            .unwrap()
+            .set_modified(SystemTime::now() - Duration::from_secs(7 * 24 * 60 * 60))
+            .unwrap();
+        let archive = LogArchive::open(tmp.path(), Some(Duration::from_secs(60))).unwrap();
+        archive.pending.lock().unwrap().push(old.clone());
+        archive.prune();
+        assert!(old.exists());
+    }
+
+    #[test]
+    fn segment_deleted_during_a_search_reads_as_empty() {
+        let tmp = tempfile::tempdir().unwrap();
+        assert!(read_segment(&tmp.path().join("segment_gone.jsonl")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn segments_created_in_the_same_millisecond_get_distinct_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let writers: Vec<SegmentWriter> = (0..5).map(|_| SegmentWriter::create(tmp.path()).unwrap()).collect();
+        let paths: BTreeSet<&PathBuf> = writers.iter().map(|w| &w.path).collect();
+        assert_eq!(paths.len(), 5);
+    }
+
+    #[test]
+    fn torn_lines_are_skipped() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = old_segment(tmp.path(), "20200101_000000000", &[entry(1, 1.0, "info", "whole", None)]);
+        let mut file = std::fs::File::options().append(true).open(&path).unwrap();
+        file.write_all(b"{\"seq\":2,\"ts\":2.0,\"lev").unwrap();
+        let entries = read_segment(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].0, 1);
+    }
+}
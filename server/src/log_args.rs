@@ -0,0 +1,98 @@
+//! Tool arguments as they appear in tracing output.
+//!
+//! Luau `code` can embed API keys or cookies, and users attach logs to bug reports,
+//! so code arguments are shortened (or dropped) per `YIPPIE_LOG_CODE` before they
+//! are logged. Everything else still goes through the secret patterns.
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::redact::Redactor;
+
+/// Argument keys that hold Luau source (`setup` is studio-test_matrix's per-variant code).
+const CODE_KEYS: &[&str] = &["code", "setup"];
+
+/// Argument keys whose values are never logged.
+const SECRET_KEYS: &[&str] = &["token", "authorization", "password", "cookie"];
+
+/// Characters of code kept in `truncated` mode.
+const TRUNCATED_CODE_CHARS: usize = 120;
+
+/// How code arguments are written to the log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CodeLogMode {
+    /// Verbatim (secret patterns still apply).
+    Full,
+    /// The first `TRUNCATED_CODE_CHARS` characters, length and hash.
+    #[default]
+    Truncated,
+    /// Only length and hash.
+    Omitted,
+}
+
+impl CodeLogMode {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "full" => Some(Self::Full),
+            "truncated" => Some(Self::Truncated),
+            "omitted" | "none" => Some(Self::Omitted),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Full => "full",
+            Self::Truncated => "truncated",
+            Self::Omitted => "omitted",
+        }
+    }
+}
+
+/// Short, non-reversible identifier for a string, so two log lines can be matched up
+/// without either showing it.
+pub fn fingerprint(text: &str) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    let hex: String = digest.iter().take(6).map(|b| format!("{b:02x}")).collect();
+    format!("sha256:{hex}")
+}
+
+fn shorten_code(code: &str, mode: CodeLogMode) -> String {
+    let summary = format!("{} bytes, {}", code.len(), fingerprint(code));
+    match mode {
+        CodeLogMode::Full => code.to_string(),
+        CodeLogMode::Omitted => format!("[code omitted: {summary}]"),
+        CodeLogMode::Truncated => match code.char_indices().nth(TRUNCATED_CODE_CHARS) {
+            Some((cut, _)) => format!("{}… [{summary}]", &code[..cut]),
+            None => code.to_string(),
+        },
+    }
+}
+
+fn scrub(value: &mut Value, mode: CodeLogMode) {
+    match value {
+        Value::Object(map) => {
+            for (key, inner) in map.iter_mut() {
+                let key = key.to_ascii_lowercase();
+                if SECRET_KEYS.contains(&key.as_str()) {
+                    *inner = Value::String("[redacted]".into());
+                } else if let (true, Value::String(code)) = (CODE_KEYS.contains(&key.as_str()), &*inner) {
+                    *inner = Value::String(shorten_code(code, mode));
+                } else {
+                    scrub(inner, mode);
+                }
+            }
+        }
+        // Replay steps and matrix variants nest arguments in arrays
+        Value::Array(items) => items.iter_mut().for_each(|item| scrub(item, mode)),
+        _ => {}
+    }
+}
+
+/// Arguments rendered for a log line: code per `mode`, secret-named keys dropped,
+/// and the configured secret patterns applied (without counting toward `redactions`).
+pub fn render(arguments: &Value, mode: CodeLogMode, redactor: &Redactor) -> String {
+    let mut arguments = arguments.clone();
+    scrub(&mut arguments, mode);
+    redactor.scrub(&arguments.to_string()).into_owned()
+}
@@ -0,0 +1,685 @@
+//! Line-level comparison of two playtest sessions' logs for `studio-logs_diff_sessions`.
+//!
+//! Each entry becomes `[level] message`. Normalizers replace run-to-run noise
+//! (timestamps, GUIDs, hex ids, numbers) with `<name>` placeholders, and consecutive
+//! repeats collapse into one run with a count, so a loop that logged 40 times instead
+//! of 38 is one line, not 2 inserts. Runs are diffed on their text with Myers'
+//! algorithm. Lines present in both sessions whose total counts differ a lot are
+//! reported separately as count changes. When the sessions differ in more than
+//! `MAX_EDIT_DISTANCE` runs, the ordered diff is skipped and only the line-set
+//! comparison is returned.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::types::LogEntry;
+
+/// Built-in normalizers, applied in this order after any custom ones.
+const DEFAULT_NORMALIZERS: &[(&str, &str)] = &[
+    (
+        "timestamp",
+        r"\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:Z|[+-]\d{2}:?\d{2})?|\b\d{1,2}:\d{2}:\d{2}(?:\.\d+)?\b",
+    ),
+    ("guid", r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b"),
+    ("hex", r"(?i)\b0x[0-9a-f]+\b|\b[0-9a-f]*\d[0-9a-f]*[a-f][0-9a-f]*\b"),
+    // No trailing \b, so numbers with a unit (`0.83s`, `12ms`) are caught too
+    ("number", r"-?\b\d+(?:\.\d+)?"),
+];
+
+/// Entries read per session from the archive.
+pub const MAX_SESSION_ENTRIES: usize = 50_000;
+/// Runs per session that are diffed; the rest is left out (and reported).
+const MAX_RUNS: usize = 5_000;
+/// Largest edit distance (in runs) the ordered diff searches for.
+const MAX_EDIT_DISTANCE: usize = 1_000;
+/// Lines listed under each of `onlyInBaseline`, `onlyInCurrent` and `countChanges`.
+const MAX_LISTED_LINES: usize = 50;
+
+pub const DEFAULT_CONTEXT_LINES: usize = 2;
+pub const MAX_CONTEXT_LINES: usize = 10;
+pub const DEFAULT_MAX_LINES: usize = 200;
+pub const MAX_MAX_LINES: usize = 1_000;
+pub const DEFAULT_MIN_COUNT_DELTA: u64 = 5;
+
+/// A compiled normalizer: matches of `regex` become `<name>`.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    name: String,
+    regex: Regex,
+}
+
+/// The built-in normalizers.
+pub fn default_normalizers() -> Vec<Normalizer> {
+    parse_normalizers("{}").unwrap_or_default()
+}
+
+/// Parse `YIPPIE_LOG_DIFF_NORMALIZERS`: a JSON object of name -> regex. Custom
+/// normalizers run first, in name order. A custom name equal to a built-in replaces
+/// it, and an empty regex removes it.
+pub fn parse_normalizers(raw: &str) -> Result<Vec<Normalizer>> {
+    let custom: BTreeMap<String, String> = serde_json::from_str(raw)
+        .context("YIPPIE_LOG_DIFF_NORMALIZERS must be a JSON object of name -> regex")?;
+    let defaults = DEFAULT_NORMALIZERS
+        .iter()
+        .filter(|(name, _)| !custom.contains_key(*name))
+        .map(|(name, regex)| (name.to_string(), regex.to_string()));
+    custom
+        .clone()
+        .into_iter()
+        .chain(defaults)
+        .filter(|(_, regex)| !regex.is_empty())
+        .map(|(name, regex)| {
+            let compiled = Regex::new(&regex)
+                .with_context(|| format!("Invalid log diff normalizer '{name}': {regex}"))?;
+            Ok(Normalizer { name, regex: compiled })
+        })
+        .collect()
+}
+
+fn normalize(line: &str, normalizers: &[Normalizer]) -> String {
+    let mut out = line.to_string();
+    for normalizer in normalizers {
+        let placeholder = format!("<{}>", normalizer.name);
+        out = normalizer.regex.replace_all(&out, placeholder.as_str()).into_owned();
+    }
+    out
+}
+
+/// Consecutive identical (normalized) lines.
+#[derive(Debug, Clone)]
+struct Run {
+    text: String,
+    count: u64,
+}
+
+/// One session's lines as runs. Returns the runs and how many were cut by `MAX_RUNS`.
+fn collapse(entries: &[LogEntry], normalizers: Option<&[Normalizer]>) -> (Vec<Run>, usize) {
+    let mut runs: Vec<Run> = Vec::new();
+    for entry in entries {
+        let line = format!("[{}] {}", entry.level, entry.message.trim_end());
+        let text = match normalizers {
+            Some(normalizers) => normalize(&line, normalizers),
+            None => line,
+        };
+        match runs.last_mut() {
+            Some(last) if last.text == text => last.count += 1,
+            _ => runs.push(Run { text, count: 1 }),
+        }
+    }
+    let dropped = runs.len().saturating_sub(MAX_RUNS);
+    runs.truncate(MAX_RUNS);
+    (runs, dropped)
+}
+
+/// Total count of each distinct line over all runs.
+fn line_totals(runs: &[Run]) -> BTreeMap<&str, u64> {
+    let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+    for run in runs {
+        *counts.entry(run.text.as_str()).or_default() += run.count;
+    }
+    counts
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Myers' O((N+M)D) diff over run texts, as (op, index in a or b) pairs. `None` when
+/// the edit distance exceeds `max_d`.
+fn myers(a: &[&str], b: &[&str], max_d: usize) -> Option<Vec<(Op, usize)>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (n + m) as usize;
+    let offset = max as isize + 1;
+    let mut v = vec![0isize; 2 * max + 3];
+    // v as it was before each round, for backtracking
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    let mut found = None;
+    'outer: for d in 0..=max.min(max_d) as isize {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let idx = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'outer;
+            }
+            k += 2;
+        }
+    }
+    found?;
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let idx = (k + offset) as usize;
+        let prev_k = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) { k + 1 } else { k - 1 };
+        let prev_x = v[(prev_k + offset) as usize];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            ops.push((Op::Equal, x as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                y -= 1;
+                ops.push((Op::Insert, y as usize));
+            } else {
+                x -= 1;
+                ops.push((Op::Delete, x as usize));
+            }
+        }
+    }
+    ops.reverse();
+    Some(ops)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionSide {
+    pub session_id: String,
+    pub entries: usize,
+    pub distinct_lines: usize,
+    #[serde(skip_serializing_if = "is_zero")]
+    pub runs_dropped: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineCount {
+    pub line: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CountChange {
+    pub line: String,
+    pub baseline: u64,
+    pub current: u64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffReport {
+    pub baseline: SessionSide,
+    pub current: SessionSide,
+    /// `myers`, or `multiset` when the sessions were too different to align.
+    pub algorithm: &'static str,
+    pub identical: bool,
+    pub only_in_baseline_total: usize,
+    pub only_in_current_total: usize,
+    pub count_changes_total: usize,
+    pub only_in_baseline: Vec<LineCount>,
+    pub only_in_current: Vec<LineCount>,
+    pub count_changes: Vec<CountChange>,
+    /// First `maxLines` lines of the unified diff.
+    pub diff: Vec<String>,
+    pub diff_lines_total: usize,
+    pub diff_truncated: bool,
+}
+
+/// Options from the tool arguments.
+pub struct DiffOptions<'a> {
+    pub normalizers: Option<&'a [Normalizer]>,
+    pub context_lines: usize,
+    pub max_lines: usize,
+    pub min_count_delta: u64,
+}
+
+/// Compare two sessions. Returns the report and the full unified diff text.
+pub fn diff_sessions(
+    baseline_id: &str,
+    baseline: &[LogEntry],
+    current_id: &str,
+    current: &[LogEntry],
+    options: &DiffOptions,
+) -> (DiffReport, String) {
+    let (a, a_dropped) = collapse(baseline, options.normalizers);
+    let (b, b_dropped) = collapse(current, options.normalizers);
+
+    let (a_counts, b_counts) = (line_totals(&a), line_totals(&b));
+
+    let mut only_a: Vec<LineCount> = a_counts
+        .iter()
+        .filter(|(line, _)| !b_counts.contains_key(*line))
+        .map(|(line, count)| LineCount { line: line.to_string(), count: *count })
+        .collect();
+    let mut only_b: Vec<LineCount> = b_counts
+        .iter()
+        .filter(|(line, _)| !a_counts.contains_key(*line))
+        .map(|(line, count)| LineCount { line: line.to_string(), count: *count })
+        .collect();
+    // Large count differences: at least min_count_delta apart and at least double
+    let mut count_changes: Vec<CountChange> = a_counts
+        .iter()
+        .filter_map(|(line, &before)| {
+            let after = *b_counts.get(line)?;
+            let (lo, hi) = (before.min(after), before.max(after));
+            (hi - lo >= options.min_count_delta && hi >= 2 * lo).then(|| CountChange {
+                line: line.to_string(),
+                baseline: before,
+                current: after,
+            })
+        })
+        .collect();
+    only_a.sort_by_key(|l| std::cmp::Reverse(l.count));
+    only_b.sort_by_key(|l| std::cmp::Reverse(l.count));
+    count_changes.sort_by_key(|c| std::cmp::Reverse(c.baseline.abs_diff(c.current)));
+
+    let a_text: Vec<&str> = a.iter().map(|r| r.text.as_str()).collect();
+    let b_text: Vec<&str> = b.iter().map(|r| r.text.as_str()).collect();
+    let (algorithm, full) = match myers(&a_text, &b_text, MAX_EDIT_DISTANCE) {
+        Some(ops) => ("myers", render_unified(&ops, &a, &b, options.context_lines, baseline_id, current_id)),
+        None => ("multiset", render_multiset(&only_a, &only_b, &count_changes, baseline_id, current_id)),
+    };
+
+    // Counts too: one more repeat of the same line is a difference
+    let identical = a.len() == b.len() && a.iter().zip(&b).all(|(x, y)| x.text == y.text && x.count == y.count);
+    let lines: Vec<&str> = full.lines().collect();
+    let diff: Vec<String> = lines.iter().take(options.max_lines).map(|l| l.to_string()).collect();
+    let report = DiffReport {
+        baseline: SessionSide {
+            session_id: baseline_id.to_string(),
+            entries: baseline.len(),
+            distinct_lines: a_counts.len(),
+            runs_dropped: a_dropped,
+        },
+        current: SessionSide {
+            session_id: current_id.to_string(),
+            entries: current.len(),
+            distinct_lines: b_counts.len(),
+            runs_dropped: b_dropped,
+        },
+        algorithm,
+        identical,
+        only_in_baseline_total: only_a.len(),
+        only_in_current_total: only_b.len(),
+        count_changes_total: count_changes.len(),
+        only_in_baseline: only_a.into_iter().take(MAX_LISTED_LINES).collect(),
+        only_in_current: only_b.into_iter().take(MAX_LISTED_LINES).collect(),
+        count_changes: count_changes.into_iter().take(MAX_LISTED_LINES).collect(),
+        diff_lines_total: lines.len(),
+        diff_truncated: lines.len() > diff.len(),
+        diff,
+    };
+    (report, full)
+}
+
+fn run_line(prefix: char, run: &Run) -> String {
+    if run.count > 1 {
+        format!("{prefix}{} (x{})", run.text, run.count)
+    } else {
+        format!("{prefix}{}", run.text)
+    }
+}
+
+/// Unified diff of the runs: `@@ -a,n +b,m @@` hunks with `context` runs around changes.
+/// An equal run whose count changed is shown as `~line (xN -> xM)`.
+fn render_unified(ops: &[(Op, usize)], a: &[Run], b: &[Run], context: usize, a_id: &str, b_id: &str) -> String {
+    // Pair each Equal op with its index in b too
+    let mut rows: Vec<(Op, Option<usize>, Option<usize>)> = Vec::with_capacity(ops.len());
+    let mut bi = 0;
+    for &(op, i) in ops {
+        match op {
+            Op::Equal => {
+                rows.push((op, Some(i), Some(bi)));
+                bi += 1;
+            }
+            Op::Delete => rows.push((op, Some(i), None)),
+            Op::Insert => {
+                rows.push((op, None, Some(i)));
+                bi = i + 1;
+            }
+        }
+    }
+    let changed = |row: &(Op, Option<usize>, Option<usize>)| match row {
+        (Op::Equal, Some(i), Some(j)) => a[*i].count != b[*j].count,
+        (Op::Equal, _, _) => false,
+        _ => true,
+    };
+
+    let mut out = format!("--- session {a_id}\n+++ session {b_id}\n");
+    let mut i = 0;
+    while i < rows.len() {
+        if !changed(&rows[i]) {
+            i += 1;
+            continue;
+        }
+        // Grow the hunk while changes are within 2 * context of each other
+        let start = i.saturating_sub(context);
+        let mut end = i;
+        let mut gap = 0;
+        let mut j = i;
+        while j < rows.len() {
+            if changed(&rows[j]) {
+                end = j;
+                gap = 0;
+            } else {
+                gap += 1;
+                if gap > 2 * context {
+                    break;
+                }
+            }
+            j += 1;
+        }
+        let end = (end + context + 1).min(rows.len());
+        let hunk = &rows[start..end];
+        let a_start = hunk.iter().find_map(|r| r.1).map_or(0, |x| x + 1);
+        let b_start = hunk.iter().find_map(|r| r.2).map_or(0, |x| x + 1);
+        let a_len = hunk.iter().filter(|r| r.1.is_some()).count();
+        let b_len = hunk.iter().filter(|r| r.2.is_some()).count();
+        out.push_str(&format!("@@ -{a_start},{a_len} +{b_start},{b_len} @@\n"));
+        for row in hunk {
+            let line = match *row {
+                (Op::Equal, Some(x), Some(y)) if a[x].count != b[y].count => {
+                    format!("~{} (x{} -> x{})", a[x].text, a[x].count, b[y].count)
+                }
+                (Op::Equal, Some(x), _) => run_line(' ', &a[x]),
+                (Op::Delete, Some(x), _) => run_line('-', &a[x]),
+                (_, _, Some(y)) => run_line('+', &b[y]),
+                _ => continue,
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+        i = end;
+    }
+    out
+}
+
+/// Fallback when the sessions are too different to align: lines only in one session
+/// and count changes, without order.
+fn render_multiset(
+    only_a: &[LineCount],
+    only_b: &[LineCount],
+    count_changes: &[CountChange],
+    a_id: &str,
+    b_id: &str,
+) -> String {
+    let mut out = format!("--- session {a_id}\n+++ session {b_id}\n@@ unordered: sessions too different to align @@\n");
+    for line in only_a {
+        out.push_str(&format!("-{} (x{})\n", line.line, line.count));
+    }
+    for line in only_b {
+        out.push_str(&format!("+{} (x{})\n", line.line, line.count));
+    }
+    for change in count_changes {
+        out.push_str(&format!("~{} (x{} -> x{})\n", change.line, change.baseline, change.current));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fixture log: one `<level>\t<message>` entry per line.
+    fn fixture(name: &str) -> Vec<LogEntry> {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/log_diff").join(name);
+        let text = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+        text.lines()
+            .enumerate()
+            .map(|(i, line)| {
+                let (level, message) = line.split_once('\t').unwrap();
+                entry(i, level, message)
+            })
+            .collect()
+    }
+
+    fn entry(seq: usize, level: &str, message: &str) -> LogEntry {
+        LogEntry { seq: seq as u64, ts: seq as f64, level: level.into(), message: message.into(), session_id: None }
+    }
+
+    fn lines(messages: &[&str]) -> Vec<LogEntry> {
+        messages.iter().enumerate().map(|(i, m)| entry(i, "info", m)).collect()
+    }
+
+    fn options(normalizers: Option<&[Normalizer]>) -> DiffOptions<'_> {
+        DiffOptions {
+            normalizers,
+            context_lines: DEFAULT_CONTEXT_LINES,
+            max_lines: DEFAULT_MAX_LINES,
+            min_count_delta: DEFAULT_MIN_COUNT_DELTA,
+        }
+    }
+
+    #[test]
+    fn a_failing_run_diffs_against_the_last_good_one() {
+        let normalizers = default_normalizers();
+        let (report, full) =
+            diff_sessions("good", &fixture("good_run.log"), "bad", &fixture("failing_run.log"), &options(Some(&normalizers)));
+        assert_eq!(
+            full,
+            "--- session good
++++ session bad
+@@ -5,7 +5,10 @@
+ [info] Round <number> starting with <number> players
+ [info] Enemy <guid> spawned (x3)
+~[info] [Combat] Builder hit enemy for <number> damage (x3 -> x1)
++[warning] [Combat] Damage multiplier missing, using <number> (x6)
++[info] [Combat] Builder hit enemy for <number> damage (x8)
+ [info] Round <number> finished in <number>s
+ [info] Saving data for Builder (key player_90210)
+-[info] DataStore save ok (attempt <number>)
++[error] DataStore save failed: <number>: Bad Gateway (attempt <number>)
++[error] ServerScriptService.Data.Save:<number>: attempt to index nil with 'Coins'
+ [info] <timestamp> Shutting down
+"
+        );
+        assert_eq!(report.algorithm, "myers");
+        assert!(!report.identical && !report.diff_truncated);
+        assert_eq!((report.baseline.entries, report.current.entries), (15, 28));
+        let only = |lines: &[LineCount]| lines.iter().map(|l| (l.line.clone(), l.count)).collect::<Vec<_>>();
+        assert_eq!(only(&report.only_in_baseline), [("[info] DataStore save ok (attempt <number>)".to_string(), 1)]);
+        assert_eq!(
+            only(&report.only_in_current)[0],
+            ("[warning] [Combat] Damage multiplier missing, using <number>".to_string(), 6)
+        );
+        assert_eq!(report.only_in_current_total, 3);
+        // 3 hits before, 1 + 8 now
+        let change = &report.count_changes[0];
+        assert_eq!((change.line.as_str(), change.baseline, change.current), ("[info] [Combat] Builder hit enemy for <number> damage", 3, 9));
+        assert_eq!(report.diff, full.lines().collect::<Vec<_>>());
+
+        // Without normalizers every timestamp, id and timing differs
+        let (raw, _) = diff_sessions("good", &fixture("good_run.log"), "bad", &fixture("failing_run.log"), &options(None));
+        assert!(raw.only_in_baseline_total > 5, "{raw:?}");
+        assert!(raw.diff.iter().any(|l| l.starts_with("-[info] 2026-10-16T09:12:03.114Z Server started")));
+    }
+
+    #[test]
+    fn runs_that_only_differ_in_noise_are_identical() {
+        let normalizers = default_normalizers();
+        let a = lines(&[
+            "12:00:01 tick 1 took 16.6ms",
+            "Loaded asset 0x1f3a in 2.5s",
+            "Loaded asset 0x1f3a in 2.5s",
+            "session 3f2a9c1e-77b4-4a0e-9d61-0c5e8f12ab34 ok",
+        ]);
+        let b = lines(&[
+            "12:00:09 tick 7 took 17ms",
+            "Loaded asset 0xbeef in 3s",
+            "Loaded asset 0xbeef in 3s",
+            "session 0b9e4d2a-1c3f-4e5d-8a7b-6c9d0e1f2a3b ok",
+        ]);
+        let (report, full) = diff_sessions("a", &a, "b", &b, &options(Some(&normalizers)));
+        assert!(report.identical, "{full}");
+        assert_eq!(full, "--- session a\n+++ session b\n");
+        assert_eq!((report.baseline.distinct_lines, report.diff_lines_total), (3, 2));
+
+        // Same lines, one repeat more: not identical, but not a large count change either
+        let mut c = b.clone();
+        c.push(entry(4, "info", "session 5d4c3b2a-1f0e-4d9c-8b7a-6f5e4d3c2b1a ok"));
+        let (report, full) = diff_sessions("a", &a, "c", &c, &options(Some(&normalizers)));
+        assert!(!report.identical);
+        assert!(report.count_changes.is_empty());
+        assert!(full.contains("~[info] session <guid> ok (x1 -> x2)"), "{full}");
+    }
+
+    #[test]
+    fn custom_normalizers_run_first_and_can_replace_or_drop_built_ins() {
+        let normalizers = parse_normalizers(r#"{"player": "player_\\d+", "number": "", "guid": "[0-9a-f]{8}-[0-9a-f-]{27}"}"#).unwrap();
+        let names: Vec<&str> = normalizers.iter().map(|n| n.name.as_str()).collect();
+        assert_eq!(names, ["guid", "player", "timestamp", "hex"]);
+        assert_eq!(
+            normalize("12:00:01 saved player_90210 (42 bytes) 3f2a9c1e-77b4-4a0e-9d61-0c5e8f12ab34", &normalizers),
+            "<timestamp> saved <player> (42 bytes) <guid>"
+        );
+        assert_eq!(default_normalizers().len(), DEFAULT_NORMALIZERS.len());
+
+        for (raw, expected) in [
+            ("[]", "JSON object of name -> regex"),
+            (r#"{"x": 1}"#, "JSON object of name -> regex"),
+            (r#"{"bad": "("}"#, "Invalid log diff normalizer 'bad'"),
+        ] {
+            let error = format!("{:#}", parse_normalizers(raw).unwrap_err());
+            assert!(error.contains(expected), "{raw}: {error}");
+        }
+    }
+
+    #[test]
+    fn built_in_normalizers_leave_words_alone() {
+        let normalizers = default_normalizers();
+        for (line, expected) in [
+            ("v2 of Part3 at -4.5, 10", "v2 of Part3 at <number>, <number>"),
+            ("took 0.83s then 12ms", "took <number>s then <number>ms"),
+            ("decade cafe added", "decade cafe added"),
+            ("id a1b2c3 and 0xFF", "id <hex> and <hex>"),
+            ("2026-10-17 14:40:59+02:00 up", "<timestamp> up"),
+        ] {
+            assert_eq!(normalize(line, &normalizers), expected, "{line}");
+        }
+    }
+
+    /// Small deterministic generator, so the property test needs no extra crate.
+    fn xorshift(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    fn lcs_len(a: &[&str], b: &[&str]) -> usize {
+        let mut dp = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                dp[i][j] = if a[i] == b[j] { dp[i + 1][j + 1] + 1 } else { dp[i + 1][j].max(dp[i][j + 1]) };
+            }
+        }
+        dp[0][0]
+    }
+
+    #[test]
+    fn myers_finds_a_shortest_edit_script() {
+        let alphabet = ["a", "b", "c", "d"];
+        let mut seed = 0x9E37_79B9_7F4A_7C15;
+        for _ in 0..300 {
+            let sequence = |seed: &mut u64| -> Vec<&str> {
+                let len = (xorshift(seed) % 12) as usize;
+                (0..len).map(|_| alphabet[(xorshift(seed) % 4) as usize]).collect()
+            };
+            let (a, b) = (sequence(&mut seed), sequence(&mut seed));
+            let ops = myers(&a, &b, usize::MAX).unwrap();
+
+            // Replaying the script turns a into b
+            let mut rebuilt = Vec::new();
+            let mut deleted_or_kept = Vec::new();
+            for &(op, i) in &ops {
+                match op {
+                    Op::Equal => {
+                        rebuilt.push(a[i]);
+                        deleted_or_kept.push(i);
+                    }
+                    Op::Delete => deleted_or_kept.push(i),
+                    Op::Insert => rebuilt.push(b[i]),
+                }
+            }
+            assert_eq!(rebuilt, b, "{a:?} -> {b:?}");
+            assert_eq!(deleted_or_kept, (0..a.len()).collect::<Vec<_>>(), "{a:?} -> {b:?}");
+            // And is as short as an LCS allows
+            let edits = ops.iter().filter(|(op, _)| *op != Op::Equal).count();
+            assert_eq!(edits, a.len() + b.len() - 2 * lcs_len(&a, &b), "{a:?} -> {b:?}");
+        }
+        assert!(myers(&["a", "b", "c"], &["x", "y", "z"], 5).is_none());
+        assert_eq!(myers(&[], &[], 0).unwrap(), []);
+    }
+
+    #[test]
+    fn hunks_keep_their_context_and_merge_when_close() {
+        let a: Vec<String> = (0..20).map(|i| format!("line {i}")).collect();
+        let mut b = a.clone();
+        b[3] = "changed 3".into();
+        b[7] = "changed 7".into();
+        b[16] = "changed 16".into();
+        let (a, b) = (a.iter().map(String::as_str).collect::<Vec<_>>(), b.iter().map(String::as_str).collect::<Vec<_>>());
+        let options = DiffOptions { context_lines: 2, ..options(None) };
+        let (_, full) = diff_sessions("a", &lines(&a), "b", &lines(&b), &options);
+        let headers: Vec<&str> = full.lines().filter(|l| l.starts_with("@@")).collect();
+        // 3 and 7 are four lines apart, within 2 * context; 16 gets its own hunk
+        assert_eq!(headers, ["@@ -2,9 +2,9 @@", "@@ -15,5 +15,5 @@"]);
+        assert!(full.contains("@@\n [info] line 1\n [info] line 2\n-[info] line 3\n+[info] changed 3\n"), "{full}");
+
+        let (_, full) = diff_sessions("a", &lines(&a), "b", &lines(&b), &DiffOptions { context_lines: 0, ..options });
+        assert_eq!(full.lines().filter(|l| l.starts_with("@@")).count(), 3, "{full}");
+    }
+
+    #[test]
+    fn output_is_bounded_and_very_different_sessions_fall_back_to_line_sets() {
+        let many = |prefix: &str, n: usize| -> Vec<LogEntry> {
+            (0..n).map(|i| entry(i, "info", &format!("{prefix} {}", "x".repeat(i % 50 + 1)))).collect()
+        };
+        let bounded = DiffOptions { max_lines: 10, ..options(None) };
+        let (report, full) = diff_sessions("a", &many("old", 100), "b", &many("new", 100), &bounded);
+        assert_eq!(report.algorithm, "myers");
+        assert_eq!(report.diff.len(), 10);
+        assert!(report.diff_truncated);
+        assert_eq!(report.diff_lines_total, full.lines().count());
+        assert_eq!((report.only_in_baseline_total, report.only_in_baseline.len()), (50, 50));
+
+        // More distinct lines than listed
+        let (report, _) = diff_sessions("a", &lines(&[]), "b", &many("only", 60), &bounded);
+        assert_eq!((report.only_in_current_total, report.only_in_current.len()), (50, MAX_LISTED_LINES));
+        let distinct: Vec<LogEntry> = (0..80).map(|i| entry(i, "info", &format!("line {i}"))).collect();
+        let (report, _) = diff_sessions("a", &[], "b", &distinct, &bounded);
+        assert_eq!((report.only_in_current_total, report.only_in_current.len()), (80, MAX_LISTED_LINES));
+
+        // Past MAX_EDIT_DISTANCE, only the unordered comparison is made
+        let old: Vec<LogEntry> = (0..600).map(|i| entry(i, "info", &format!("old {i}"))).collect();
+        let new: Vec<LogEntry> = (0..600).map(|i| entry(i, "info", &format!("new {i}"))).collect();
+        let (report, full) = diff_sessions("a", &old, "b", &new, &options(None));
+        assert_eq!(report.algorithm, "multiset");
+        assert!(full.contains("@@ unordered: sessions too different to align @@\n-[info] old 0 (x1)\n"), "{}", &full[..200]);
+        assert_eq!(report.diff_lines_total, 3 + 1200);
+
+        // Runs past MAX_RUNS are dropped and reported
+        let long: Vec<LogEntry> = (0..MAX_RUNS + 7).map(|i| entry(i, "info", &format!("run {}", i % 2))).collect();
+        let (report, _) = diff_sessions("a", &long, "b", &long, &options(None));
+        assert_eq!(report.baseline.runs_dropped, 7);
+        assert!(report.identical);
+        assert_eq!(serde_json::to_value(&report).unwrap()["current"]["runsDropped"], 7);
+        let (report, _) = diff_sessions("a", &long[..10], "b", &long[..10], &options(None));
+        assert!(serde_json::to_value(&report).unwrap()["current"].get("runsDropped").is_none());
+    }
+}
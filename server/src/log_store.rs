@@ -0,0 +1,228 @@
+//! The in-memory log buffer: the latest `MAX_LOG_BUFFER` entries from Studio, shared
+//! as `Arc<LogEntry>` so readers clone pointers to the entries they keep, not the
+//! entries.
+//!
+//! A push assigns the sequence number, appends and evicts under one short write lock,
+//! so the buffer always holds consecutive numbers in order and a reader never sees a
+//! later entry without the earlier ones. Reads take the read lock and run in parallel.
+//! Live followers subscribe to a broadcast of each entry instead of polling.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+use crate::types::LogEntry;
+
+pub const MAX_LOG_BUFFER: usize = 500;
+
+/// Entries buffered per live follower before it lags.
+const LIVE_CHANNEL_CAPACITY: usize = 1024;
+
+struct Ring {
+    entries: VecDeque<Arc<LogEntry>>,
+    last_seq: u64,
+}
+
+pub struct LogStore {
+    ring: RwLock<Ring>,
+    live: broadcast::Sender<Arc<LogEntry>>,
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        Self {
+            ring: RwLock::new(Ring {
+                entries: VecDeque::with_capacity(MAX_LOG_BUFFER),
+                last_seq: 0,
+            }),
+            live: broadcast::channel(LIVE_CHANNEL_CAPACITY).0,
+        }
+    }
+}
+
+impl LogStore {
+    /// Append the entry `make` builds for the next sequence number. `make` runs under
+    /// the write lock, so it should only assemble fields prepared beforehand.
+    pub fn push(&self, make: impl FnOnce(u64) -> LogEntry) -> Arc<LogEntry> {
+        let mut ring = self.ring.write().unwrap_or_else(|e| e.into_inner());
+        ring.last_seq += 1;
+        let entry = Arc::new(make(ring.last_seq));
+        if ring.entries.len() >= MAX_LOG_BUFFER {
+            ring.entries.pop_front();
+        }
+        ring.entries.push_back(entry.clone());
+        // Sent under the lock so followers get entries in sequence order
+        let _ = self.live.send(entry.clone());
+        entry
+    }
+
+    /// Up to `limit` entries after `since_seq`, oldest first.
+    pub fn since(&self, since_seq: u64, limit: usize) -> Vec<Arc<LogEntry>> {
+        let ring = self.ring.read().unwrap_or_else(|e| e.into_inner());
+        // Sequence numbers in the ring are consecutive, so the start is an offset
+        let first_seq = ring.entries.front().map_or(0, |e| e.seq);
+        let start = (since_seq + 1).saturating_sub(first_seq).min(ring.entries.len() as u64) as usize;
+        ring.entries.range(start..).take(limit).cloned().collect()
+    }
+
+    /// Entries matching `keep`, oldest first.
+    pub fn matching(&self, mut keep: impl FnMut(&LogEntry) -> bool) -> Vec<Arc<LogEntry>> {
+        let ring = self.ring.read().unwrap_or_else(|e| e.into_inner());
+        ring.entries.iter().filter(|e| keep(e)).cloned().collect()
+    }
+
+    /// Run `read` over the buffered entries, oldest first, without copying them.
+    pub fn read<T>(&self, read: impl FnOnce(&mut dyn Iterator<Item = &LogEntry>) -> T) -> T {
+        let ring = self.ring.read().unwrap_or_else(|e| e.into_inner());
+        read(&mut ring.entries.iter().map(|e| &**e))
+    }
+
+    /// Entries pushed from now on. A receiver that falls behind gets `Lagged` and can
+    /// catch up with [`since`](Self::since).
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<LogEntry>> {
+        self.live.subscribe()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.read().unwrap_or_else(|e| e.into_inner()).entries.len()
+    }
+
+    /// Drop every buffered entry. Sequence numbers keep counting.
+    pub fn clear(&self) -> usize {
+        let mut ring = self.ring.write().unwrap_or_else(|e| e.into_inner());
+        let cleared = ring.entries.len();
+        ring.entries.clear();
+        cleared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// An entry whose fields all derive from its sequence number, so a reader can tell
+    /// a torn one from a whole one.
+    fn entry(seq: u64) -> LogEntry {
+        LogEntry {
+            seq,
+            ts: seq as f64,
+            level: if seq.is_multiple_of(2) { "output" } else { "warning" }.into(),
+            message: format!("entry {seq}"),
+            session_id: Some(format!("session {}", seq / 100)),
+        }
+    }
+
+    fn assert_whole(e: &LogEntry) {
+        assert_eq!(e.ts, e.seq as f64);
+        assert_eq!(e.level, if e.seq.is_multiple_of(2) { "output" } else { "warning" });
+        assert_eq!(e.message, format!("entry {}", e.seq));
+        assert_eq!(e.session_id.as_deref(), Some(format!("session {}", e.seq / 100).as_str()));
+    }
+
+    /// Entries in a snapshot follow each other without gaps.
+    fn assert_consecutive<'a>(entries: impl IntoIterator<Item = &'a LogEntry>) -> Option<u64> {
+        let mut last = None;
+        for e in entries {
+            assert_whole(e);
+            if let Some(last) = last {
+                assert_eq!(e.seq, last + 1, "gap or reorder in one snapshot");
+            }
+            last = Some(e.seq);
+        }
+        last
+    }
+
+    #[test]
+    fn readers_see_whole_entries_in_order_across_wraparound() {
+        const PUSHES: u64 = 40 * MAX_LOG_BUFFER as u64;
+        let store = LogStore::default();
+        let mut live = store.subscribe();
+        let done = AtomicBool::new(false);
+
+        std::thread::scope(|scope| {
+            for reader in 0..8 {
+                let (store, done) = (&store, &done);
+                scope.spawn(move || {
+                    let mut seen = 0;
+                    while !done.load(Ordering::Acquire) {
+                        match reader % 3 {
+                            // A follower: each batch starts right after what it has seen,
+                            // unless the ring has wrapped past it
+                            0 => {
+                                let batch = store.since(seen, 64);
+                                if let Some(first) = batch.first() {
+                                    assert!(first.seq > seen, "{} after {seen}", first.seq);
+                                }
+                                if let Some(last) = assert_consecutive(batch.iter().map(|e| &**e)) {
+                                    seen = last;
+                                }
+                            }
+                            1 => {
+                                let matched = store.matching(|e| e.seq % 2 == 1);
+                                let mut last = None;
+                                for e in &matched {
+                                    assert_whole(e);
+                                    assert!(last.is_none_or(|l| e.seq == l + 2), "gap in matched entries");
+                                    last = Some(e.seq);
+                                }
+                            }
+                            _ => {
+                                let last = store.read(|entries| assert_consecutive(entries));
+                                // Later reads never go back in time
+                                if let Some(last) = last {
+                                    assert!(last >= seen);
+                                    seen = last;
+                                }
+                            }
+                        }
+                        assert!(store.len() <= MAX_LOG_BUFFER);
+                    }
+                });
+            }
+
+            for seq in 1..=PUSHES {
+                let pushed = store.push(entry);
+                assert_eq!(pushed.seq, seq);
+            }
+            done.store(true, Ordering::Release);
+        });
+
+        let buffered = store.since(0, usize::MAX);
+        assert_eq!(buffered.len(), MAX_LOG_BUFFER);
+        assert_eq!(buffered.first().unwrap().seq, PUSHES - MAX_LOG_BUFFER as u64 + 1);
+        assert_eq!(assert_consecutive(buffered.iter().map(|e| &**e)), Some(PUSHES));
+
+        // The broadcast holds the last LIVE_CHANNEL_CAPACITY entries, still in order
+        let mut expected = None;
+        loop {
+            match live.try_recv() {
+                Ok(e) => {
+                    assert_whole(&e);
+                    if let Some(expected) = expected {
+                        assert_eq!(e.seq, expected);
+                    }
+                    expected = Some(e.seq + 1);
+                }
+                Err(broadcast::error::TryRecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            }
+        }
+        assert_eq!(expected, Some(PUSHES + 1));
+    }
+
+    #[test]
+    fn since_skips_to_the_oldest_entry_left_after_a_clear_or_wrap() {
+        let store = LogStore::default();
+        for _ in 0..MAX_LOG_BUFFER + 10 {
+            store.push(entry);
+        }
+        assert_eq!(store.since(3, 1)[0].seq, 11);
+        assert_eq!(store.since(MAX_LOG_BUFFER as u64, 1)[0].seq, MAX_LOG_BUFFER as u64 + 1);
+        assert!(store.since(MAX_LOG_BUFFER as u64 + 10, 10).is_empty());
+
+        assert_eq!(store.clear(), MAX_LOG_BUFFER);
+        assert_eq!(store.push(entry).seq, MAX_LOG_BUFFER as u64 + 11);
+        assert_eq!(store.since(0, 10).len(), 1);
+    }
+}
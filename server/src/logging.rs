@@ -0,0 +1,214 @@
+//! Log output: a file layer (`~/.yippieblox-mcp.log`) and a stderr layer, each with
+//! its own level. MCP clients copy the server's stderr into their own logs, so stderr
+//! only gets warnings by default while the file keeps the detail.
+//!
+//! Startup notices (log path, missing token) are written to stderr directly, where a
+//! person launching the server sees them without log formatting, and to the file
+//! through tracing. `--quiet` leaves stderr out entirely.
+
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+
+pub const DEFAULT_FILE_LEVEL: &str = "info";
+pub const DEFAULT_STDERR_LEVEL: &str = "warn";
+
+/// Log file in the home directory, unless `YIPPIE_LOG_FILE` names another.
+const LOG_FILE_NAME: &str = ".yippieblox-mcp.log";
+
+/// A log file bigger than this at startup is moved to `<name>.1`, replacing the
+/// previous one.
+const MAX_LOG_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Target of startup notices. They reach stderr without tracing, so the stderr
+/// layer leaves this target out.
+const BANNER_TARGET: &str = "yippie_banner";
+
+/// Filter directives for both layers; `stderr` is None when it is silenced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Levels {
+    pub file: String,
+    pub stderr: Option<String>,
+}
+
+/// Work out the directives of both layers from the environment, read through `env`.
+/// The file takes `YIPPIE_LOG_LEVEL`; stderr takes `YIPPIE_STDERR_LOG_LEVEL`, or
+/// `RUST_LOG` when that is unset. Both take tracing directives such as
+/// `info,roblox_studio_yippieblox_mcp_server::bridge_http=debug`. `verbose` raises
+/// the default level of both to debug and keeps per-module directives; `quiet` turns
+/// stderr off and leaves the file as it is.
+pub fn levels(env: impl Fn(&str) -> Option<String>, quiet: bool, verbose: bool) -> Result<Levels> {
+    let file_env = env("YIPPIE_LOG_LEVEL");
+    let stderr_env = env("YIPPIE_STDERR_LOG_LEVEL").or_else(|| env("RUST_LOG"));
+    let resolve = |raw: Option<&str>, default: &str, name: &str| -> Result<String> {
+        let directives = raw.map(str::trim).filter(|s| !s.is_empty()).unwrap_or(default);
+        EnvFilter::try_new(directives).with_context(|| format!("{name}: invalid log directives '{directives}'"))?;
+        Ok(if verbose { with_default_level(directives, "debug") } else { directives.to_string() })
+    };
+    let file = resolve(file_env.as_deref(), DEFAULT_FILE_LEVEL, "YIPPIE_LOG_LEVEL")?;
+    let stderr = resolve(stderr_env.as_deref(), DEFAULT_STDERR_LEVEL, "YIPPIE_STDERR_LOG_LEVEL")?;
+    Ok(Levels { file, stderr: (!quiet).then_some(stderr) })
+}
+
+/// Replace the bare level among `directives` (the one without a target) with
+/// `level`, keeping the per-module ones after it.
+fn with_default_level(directives: &str, level: &str) -> String {
+    let targeted = directives.split(',').map(str::trim).filter(|d| !d.is_empty() && d.contains('='));
+    std::iter::once(level).chain(targeted).collect::<Vec<_>>().join(",")
+}
+
+/// Installed logging, for the startup notices.
+pub struct Logging {
+    pub file: Option<PathBuf>,
+    quiet: bool,
+}
+
+impl Logging {
+    /// A notice for whoever launched the server: boxed on stderr unless quiet,
+    /// and one info line in the log file.
+    pub fn notice(&self, lines: &[&str]) {
+        self.boxed(lines);
+        tracing::info!(target: BANNER_TARGET, "{}", lines.join(" "));
+    }
+
+    /// Like `notice`, logged as a warning.
+    pub fn warning(&self, lines: &[&str]) {
+        self.boxed(lines);
+        tracing::warn!(target: BANNER_TARGET, "{}", lines.join(" "));
+    }
+
+    fn boxed(&self, lines: &[&str]) {
+        if self.quiet {
+            return;
+        }
+        eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        for line in lines {
+            eprintln!("  {line}");
+        }
+        eprintln!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+    }
+}
+
+/// Install the global subscriber. A log file that can't be opened is reported on
+/// stderr (unless quiet) and left out; invalid directives fail startup.
+pub fn init(quiet: bool, verbose: bool) -> Result<Logging> {
+    let env = |name: &str| std::env::var(name).ok();
+    let levels = levels(env, quiet, verbose)?;
+
+    let path = env("YIPPIE_LOG_FILE").map(PathBuf::from).or_else(|| {
+        env("HOME").or_else(|| env("USERPROFILE")).map(|home| PathBuf::from(home).join(LOG_FILE_NAME))
+    });
+    let file = match path.as_deref().map(open_log_file) {
+        Some(Ok(file)) => Some(file),
+        Some(Err(e)) => {
+            if !quiet {
+                eprintln!("Not logging to a file: {e:#}");
+            }
+            None
+        }
+        None => None,
+    };
+
+    let file_layer = file.map(|file| {
+        fmt::layer()
+            .with_writer(Mutex::new(file))
+            .with_ansi(false)
+            .with_filter(EnvFilter::new(&levels.file))
+    });
+    // stdout is reserved for MCP JSON-RPC protocol messages
+    let stderr_layer = levels.stderr.as_ref().map(|directives| {
+        fmt::layer()
+            .with_writer(std::io::stderr)
+            .with_filter(EnvFilter::new(format!("{directives},{BANNER_TARGET}=off")))
+    });
+    let has_file = file_layer.is_some();
+    tracing_subscriber::registry().with(file_layer).with(stderr_layer).init();
+
+    Ok(Logging { file: path.filter(|_| has_file), quiet })
+}
+
+fn open_log_file(path: &std::path::Path) -> Result<std::fs::File> {
+    if std::fs::metadata(path).is_ok_and(|m| m.len() > MAX_LOG_FILE_BYTES) {
+        let mut previous = path.as_os_str().to_owned();
+        previous.push(".1");
+        std::fs::rename(path, &previous).with_context(|| format!("can't move {} aside", path.display()))?;
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("can't open {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(vars: &[(&str, &str)], quiet: bool, verbose: bool) -> Result<Levels> {
+        let env = |name: &str| vars.iter().find(|(k, _)| *k == name).map(|(_, v)| v.to_string());
+        levels(env, quiet, verbose)
+    }
+
+    fn both(file: &str, stderr: Option<&str>) -> Levels {
+        Levels { file: file.into(), stderr: stderr.map(String::from) }
+    }
+
+    #[test]
+    fn defaults_split_file_and_stderr() {
+        assert_eq!(resolve(&[], false, false).unwrap(), both("info", Some("warn")));
+    }
+
+    #[test]
+    fn each_layer_reads_its_own_variable() {
+        let vars = [("YIPPIE_LOG_LEVEL", "trace"), ("YIPPIE_STDERR_LOG_LEVEL", "error")];
+        assert_eq!(resolve(&vars, false, false).unwrap(), both("trace", Some("error")));
+    }
+
+    #[test]
+    fn rust_log_sets_stderr_only_without_its_own_variable() {
+        let vars = [("RUST_LOG", "debug")];
+        assert_eq!(resolve(&vars, false, false).unwrap(), both("info", Some("debug")));
+        let vars = [("RUST_LOG", "debug"), ("YIPPIE_STDERR_LOG_LEVEL", "error")];
+        assert_eq!(resolve(&vars, false, false).unwrap(), both("info", Some("error")));
+    }
+
+    #[test]
+    fn blank_variables_fall_back_to_the_defaults() {
+        let vars = [("YIPPIE_LOG_LEVEL", "  "), ("YIPPIE_STDERR_LOG_LEVEL", "")];
+        assert_eq!(resolve(&vars, false, false).unwrap(), both("info", Some("warn")));
+    }
+
+    #[test]
+    fn verbose_raises_both_defaults_and_keeps_module_directives() {
+        let vars = [("YIPPIE_LOG_LEVEL", "warn,roblox_studio_yippieblox_mcp_server::bridge_http=trace")];
+        assert_eq!(
+            resolve(&vars, false, true).unwrap(),
+            both("debug,roblox_studio_yippieblox_mcp_server::bridge_http=trace", Some("debug"))
+        );
+    }
+
+    #[test]
+    fn quiet_silences_stderr_and_wins_over_verbose() {
+        assert_eq!(resolve(&[], true, false).unwrap(), both("info", None));
+        let levels = resolve(&[("YIPPIE_STDERR_LOG_LEVEL", "trace")], true, true).unwrap();
+        assert_eq!(levels.stderr, None);
+    }
+
+    #[test]
+    fn quiet_leaves_the_file_level_alone() {
+        let vars = [("YIPPIE_LOG_LEVEL", "trace")];
+        assert_eq!(resolve(&vars, true, false).unwrap().file, "trace");
+        assert_eq!(resolve(&vars, true, true).unwrap().file, "debug");
+        assert_eq!(resolve(&[], true, true).unwrap().file, "debug");
+    }
+
+    #[test]
+    fn invalid_directives_name_their_variable() {
+        let error = resolve(&[("YIPPIE_STDERR_LOG_LEVEL", "info,=bogus=")], false, false).unwrap_err();
+        assert!(error.to_string().starts_with("YIPPIE_STDERR_LOG_LEVEL: invalid log directives"), "{error}");
+        let error = resolve(&[("YIPPIE_LOG_LEVEL", "info,bridge=loud")], false, false).unwrap_err();
+        assert!(error.to_string().starts_with("YIPPIE_LOG_LEVEL: invalid log directives"), "{error}");
+    }
+}
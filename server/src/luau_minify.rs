@@ -0,0 +1,348 @@
+//! Size reduction for Luau `code` arguments before they are sent to the plugin.
+//!
+//! The code is parsed with full-moon and printed back from its syntax tree with the
+//! trivia rewritten: comments are dropped, indentation and runs of spaces are removed,
+//! and a space is kept only where the two neighbouring tokens would otherwise lex as
+//! something else. Tokens themselves, strings included, print exactly as written.
+//! Line breaks are kept so line numbers in Luau errors still match the submitted code.
+//! `--!` directives such as `--!strict` are kept when they start a line.
+//!
+//! The output is parsed again and its syntax tree compared with the input's, ignoring
+//! trivia, and every token must sit on the line it came from. Any difference is
+//! reported as an error and the caller sends the original code.
+
+use full_moon::ast::Ast;
+use full_moon::node::Node;
+use full_moon::tokenizer::{Lexer, LexerResult, Token, TokenReference, TokenType};
+use full_moon::visitors::VisitorMut;
+use full_moon::LuaVersion;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MinifyReport {
+    pub applied: bool,
+    pub original_bytes: usize,
+    pub minified_bytes: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_reason: Option<String>,
+}
+
+/// Minify `code`, falling back to the original text when minification fails or saves
+/// nothing. The report says which happened.
+pub fn minify_or_original(code: &str) -> (String, MinifyReport) {
+    match minify(code) {
+        Ok(minified) if minified.len() < code.len() => {
+            let report = MinifyReport {
+                applied: true,
+                original_bytes: code.len(),
+                minified_bytes: minified.len(),
+                skipped_reason: None,
+            };
+            (minified, report)
+        }
+        result => {
+            let reason = match result {
+                Err(e) => e,
+                Ok(_) => "no size reduction".to_string(),
+            };
+            let report = MinifyReport {
+                applied: false,
+                original_bytes: code.len(),
+                minified_bytes: code.len(),
+                skipped_reason: Some(reason),
+            };
+            (code.to_string(), report)
+        }
+    }
+}
+
+pub fn minify(code: &str) -> Result<String, String> {
+    let ast = parse(code)?;
+    let minified = Printer::default().visit_ast(ast.clone()).to_string();
+    verify(code, &ast, &minified)?;
+    Ok(minified)
+}
+
+fn parse(code: &str) -> Result<Ast, String> {
+    full_moon::parse_fallible(code, LuaVersion::luau())
+        .into_result()
+        .map_err(|errors| match errors.first() {
+            Some(e) => format!("code does not parse as Luau: {e}"),
+            None => "code does not parse as Luau".to_string(),
+        })
+}
+
+/// Parse the output again and check it is the same program, line for line.
+fn verify(original: &str, original_ast: &Ast, minified: &str) -> Result<(), String> {
+    let minified_ast =
+        parse(minified).map_err(|e| format!("minified output failed to parse: {e}"))?;
+    if !original_ast.nodes().similar(minified_ast.nodes()) {
+        return Err("minified output parses to a different syntax tree".to_string());
+    }
+    if token_lines(original)? != token_lines(minified)? {
+        return Err("minified output moved tokens to other lines".to_string());
+    }
+    Ok(())
+}
+
+/// The line each token starts on.
+fn token_lines(code: &str) -> Result<Vec<usize>, String> {
+    Ok(lex(code)?
+        .iter()
+        .map(|t| t.start_position().line())
+        .collect())
+}
+
+/// Tokens of `code`, without trivia or the end of file.
+fn lex(code: &str) -> Result<Vec<Token>, String> {
+    match Lexer::new(code, LuaVersion::luau()).collect() {
+        LexerResult::Ok(tokens) => Ok(tokens
+            .into_iter()
+            .filter(|t| !t.token_type().is_trivia() && !matches!(t.token_type(), TokenType::Eof))
+            .collect()),
+        LexerResult::Recovered(_, errors) | LexerResult::Fatal(errors) => {
+            Err(match errors.first() {
+                Some(e) => format!("code does not tokenize as Luau: {e}"),
+                None => "code does not tokenize as Luau".to_string(),
+            })
+        }
+    }
+}
+
+/// Rewrites the trivia around every token. Full-moon visits tokens in source order,
+/// and the trivia between two tokens is the first one's trailing trivia followed by
+/// the second one's leading trivia, so each token takes over the gap before it.
+#[derive(Default)]
+struct Printer {
+    /// Trailing trivia of the previous token, not yet printed.
+    pending: Vec<Token>,
+    previous: Option<Token>,
+}
+
+impl Printer {
+    fn rewrite(&mut self, token: TokenReference) -> TokenReference {
+        let mut trivia = std::mem::take(&mut self.pending);
+        trivia.extend(token.leading_trivia().cloned());
+        let leading = self.gap(&trivia, token.token());
+        self.pending = token.trailing_trivia().cloned().collect();
+        self.previous = Some(token.token().clone());
+        TokenReference::new(leading, token.token().clone(), Vec::new())
+    }
+
+    /// What to print in place of `trivia`: its line breaks, `--!` directives that
+    /// start a line, and a space if `next` would otherwise join the previous token.
+    fn gap(&self, trivia: &[Token], next: &Token) -> Vec<Token> {
+        let mut out = Vec::new();
+        let mut line_start = self.previous.is_none();
+        for t in trivia {
+            let newlines = match t.token_type() {
+                TokenType::Whitespace { characters } => characters.matches('\n').count(),
+                TokenType::MultiLineComment { comment, .. } => comment.matches('\n').count(),
+                TokenType::SingleLineComment { comment } => {
+                    if line_start && comment.starts_with('!') {
+                        out.push(t.clone());
+                        line_start = false;
+                    }
+                    0
+                }
+                TokenType::Shebang { .. } => {
+                    out.push(t.clone());
+                    0
+                }
+                _ => 0,
+            };
+            if newlines > 0 {
+                out.push(whitespace(&"\n".repeat(newlines)));
+                line_start = true;
+            }
+        }
+        if out.is_empty() {
+            if let Some(previous) = &self.previous {
+                if needs_space(previous, next) {
+                    out.push(whitespace(" "));
+                }
+            }
+        }
+        out
+    }
+}
+
+impl VisitorMut for Printer {
+    fn visit_token_reference(&mut self, token: TokenReference) -> TokenReference {
+        // End of file is visited on its own, through `visit_eof`.
+        match token.token_type() {
+            TokenType::Eof => token,
+            _ => self.rewrite(token),
+        }
+    }
+
+    fn visit_eof(&mut self, token: TokenReference) -> TokenReference {
+        self.rewrite(token)
+    }
+}
+
+fn whitespace(characters: &str) -> Token {
+    Token::new(TokenType::Whitespace {
+        characters: characters.into(),
+    })
+}
+
+/// Whether `a` and `b` printed back to back would lex as something other than the
+/// two tokens, e.g. `local x`, `a - -b` or `1 ..x`.
+fn needs_space(a: &Token, b: &Token) -> bool {
+    if matches!(b.token_type(), TokenType::Eof) {
+        return false;
+    }
+    // Luau reads letters, digits and `_` after a number into it (`2do` is one malformed
+    // number there), though full-moon's lexer stops at the digits.
+    if matches!(a.token_type(), TokenType::Number { .. })
+        && b.to_string()
+            .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return true;
+    }
+    let joined = format!("{a}{b}");
+    // Interpolated string pieces end in `{` or a backtick and start with `}` or one, so
+    // nothing joins them, but Luau refuses `{{` as an escape mistake.
+    if matches!(a.token_type(), TokenType::InterpolatedString { .. })
+        || matches!(b.token_type(), TokenType::InterpolatedString { .. })
+    {
+        return joined.contains("{{");
+    }
+    let Ok(tokens) = lex(&joined) else {
+        return true;
+    };
+    let mut tokens = tokens.iter();
+    !(tokens.next().map(Token::token_type) == Some(a.token_type())
+        && tokens.next().map(Token::token_type) == Some(b.token_type())
+        && tokens.next().is_none())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixtures() -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/luau");
+        let mut fixtures: Vec<(String, String)> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "luau"))
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read_to_string(&path).unwrap())
+            })
+            .collect();
+        fixtures.sort();
+        assert!(!fixtures.is_empty(), "no fixtures in {}", dir.display());
+        fixtures
+    }
+
+    /// Every fixture minifies to a smaller program whose syntax tree, parsed on its
+    /// own, matches the original's and whose tokens keep their lines.
+    #[test]
+    fn fixture_corpus_keeps_the_syntax_tree() {
+        for (name, code) in fixtures() {
+            let minified = minify(&code).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert!(minified.len() < code.len(), "{name}: no size reduction");
+
+            let original = full_moon::parse_fallible(&code, LuaVersion::luau())
+                .into_result()
+                .unwrap();
+            let reparsed = full_moon::parse_fallible(&minified, LuaVersion::luau())
+                .into_result()
+                .unwrap_or_else(|e| panic!("{name}: {e:?}\n{minified}"));
+            assert!(
+                original.nodes().similar(reparsed.nodes()),
+                "{name}: syntax trees differ\n{minified}"
+            );
+            assert_eq!(code.lines().count(), minified.lines().count(), "{name}");
+            assert_eq!(
+                minify(&minified).unwrap(),
+                minified,
+                "{name}: not idempotent"
+            );
+        }
+    }
+
+    #[test]
+    fn drops_comments_and_indentation_but_keeps_lines() {
+        let code = "-- header\nlocal x = 1 -- one\n\tif x then\n\t\tprint( x )\n\tend\n";
+        assert_eq!(
+            minify(code).unwrap(),
+            "\nlocal x=1\nif x then\nprint(x)\nend\n"
+        );
+    }
+
+    #[test]
+    fn keeps_directives_at_line_start() {
+        let code = "--!strict\n--!optimize 2\nlocal x = 1 --!not a directive\n";
+        assert_eq!(
+            minify(code).unwrap(),
+            "--!strict\n--!optimize 2\nlocal x=1\n"
+        );
+    }
+
+    #[test]
+    fn block_comments_keep_their_line_breaks() {
+        let code = "local a = 1 --[[ one\ntwo\nthree ]] local b = 2\nprint(a, b)";
+        assert_eq!(minify(code).unwrap(), "local a=1\n\nlocal b=2\nprint(a,b)");
+    }
+
+    #[test]
+    fn strings_print_verbatim() {
+        let code = "local s = [[ -- a  b ]] .. \"--[[ c ]]\" .. `x { 1 + 2 } y`";
+        assert_eq!(
+            minify(code).unwrap(),
+            "local s=[[ -- a  b ]]..\"--[[ c ]]\"..`x {1+2} y`"
+        );
+    }
+
+    #[test]
+    fn spaces_only_where_tokens_would_join() {
+        assert_eq!(minify("local a = b - -c").unwrap(), "local a=b- -c");
+        assert_eq!(minify("local a = 1 .. x").unwrap(), "local a=1 ..x");
+        assert_eq!(minify("local a = b .. .5").unwrap(), "local a=b.. .5");
+        assert_eq!(
+            minify("local a = t [ [[s]] ]").unwrap(),
+            "local a=t[ [[s]]]"
+        );
+        assert_eq!(minify("local a = `{ {1} }`").unwrap(), "local a=`{ {1}}`");
+        assert_eq!(
+            minify("for i = 1, 2 do if i > 0 then x = 1 end end").unwrap(),
+            "for i=1,2 do if i>0 then x=1 end end"
+        );
+        assert_eq!(
+            minify("return not a and b or c").unwrap(),
+            "return not a and b or c"
+        );
+    }
+
+    #[test]
+    fn unparsable_code_is_sent_unchanged() {
+        let code = "local x = -- nothing\n";
+        let (sent, report) = minify_or_original(code);
+        assert_eq!(sent, code);
+        assert!(!report.applied);
+        assert!(report
+            .skipped_reason
+            .unwrap()
+            .starts_with("code does not parse as Luau"));
+    }
+
+    #[test]
+    fn report_counts_bytes() {
+        let code = "local   x   =   1   -- comment\n";
+        let (sent, report) = minify_or_original(code);
+        assert_eq!(sent, "local x=1\n");
+        assert!(report.applied);
+        assert_eq!(report.original_bytes, code.len());
+        assert_eq!(report.minified_bytes, sent.len());
+
+        let (sent, report) = minify_or_original("x=1");
+        assert_eq!(sent, "x=1");
+        assert_eq!(report.skipped_reason.as_deref(), Some("no size reduction"));
+    }
+}
@@ -1,12 +1,28 @@
-mod bridge_http;
-mod captures;
-mod config;
-mod mcp_stdio;
-mod state;
-mod types;
+mod logging;
+mod startup_status;
 
 use anyhow::Result;
 use clap::Parser;
+use std::sync::Arc;
+
+use roblox_studio_yippieblox_mcp_server::{
+    bridge_http, serve_stdio, state_bench, Config, ServerBuilder, SHUTDOWN_DRAIN_TIMEOUT,
+};
+
+/// How long to wait for the bridge to bind and the stdio loop to start before reporting a startup failure
+/// on --status-fd / --status-file. The bridge keeps retrying afterwards.
+const STARTUP_BIND_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(15);
+
+/// Time for the bridge to answer `POST /shutdown` before draining starts.
+const SHUTDOWN_REPLY_GRACE: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Progress of the HTTP bridge retry loop, watched by the startup reporter.
+#[derive(Debug, Clone)]
+enum BridgeBind {
+    Pending,
+    Failed(String),
+    Bound(std::net::SocketAddr),
+}
 
 #[derive(Parser)]
 #[command(name = "roblox-studio-yippieblox-mcp-server")]
@@ -15,40 +31,114 @@ struct Cli {
     /// Run in STDIO mode (required for MCP clients like Claude Code / Claude Desktop)
     #[arg(long)]
     stdio: bool,
+
+    /// Simulate a connected Studio plugin in-process (no Roblox required)
+    #[arg(long)]
+    mock_studio: bool,
+
+    /// JSON scenario file with canned responses for --mock-studio
+    #[arg(long, requires = "mock_studio")]
+    mock_scenario: Option<std::path::PathBuf>,
+
+    /// Start with this configuration profile from YIPPIE_PROFILES (overrides YIPPIE_PROFILE)
+    #[arg(long, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Write a JSON startup summary (or startup error) to this inherited file descriptor, then close it
+    #[arg(long, value_name = "N", value_parser = startup_status::parse_status_fd)]
+    status_fd: Option<i32>,
+
+    /// Write a JSON startup summary (or startup error) to this file, atomically
+    #[arg(long, value_name = "PATH")]
+    status_file: Option<std::path::PathBuf>,
+
+    /// Benchmark SharedState hot paths with this many iterations each, print a JSON
+    /// report to stdout and exit (what `mcpctl bench --state` runs)
+    #[arg(long, value_name = "N", hide = true)]
+    bench_state: Option<usize>,
+
+    /// Write nothing to stderr; logs and startup notices go only to the log file
+    #[arg(long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log at debug level, both to stderr and to the log file
+    #[arg(long)]
+    verbose: bool,
+
+    /// Untimed iterations before --bench-state measures
+    #[arg(long, value_name = "N", default_value = "100", hide = true, requires = "bench_state")]
+    bench_warmup: usize,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let _cli = Cli::parse();
+fn main() -> Result<()> {
+    // Parsed before the runtime starts: --status-fd is checked against the descriptors
+    // the process inherited, before tokio opens its own
+    let cli = Cli::parse();
+    let reporter = Arc::new(startup_status::StatusReporter::new(
+        cli.status_fd,
+        cli.status_file.clone(),
+    ));
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?
+        .block_on(run(cli, reporter))
+}
 
-    // Log to stderr (visible in Claude Desktop logs and terminal).
-    // stdout is reserved for MCP JSON-RPC protocol messages.
-    tracing_subscriber::fmt()
-        .with_writer(std::io::stderr)
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
-        )
-        .init();
+async fn run(cli: Cli, reporter: Arc<startup_status::StatusReporter>) -> Result<()> {
 
-    let config = config::load()?;
-    tracing::info!(
-        port = config.port,
-        capture_dir = %config.capture_dir.display(),
-        "YippieBlox MCP Server starting"
-    );
+    let logging = reporter.stage("logging", logging::init(cli.quiet, cli.verbose))?;
+    if let Some(path) = &logging.file {
+        logging.notice(&[&format!("Logging to {}", path.display())]);
+    }
 
-    let state = state::SharedState::new(config.capture_dir.clone());
+    let mut config = reporter.stage("config", Config::from_env())?;
+    if config.token.is_none() {
+        logging.warning(&["No YIPPIE_TOKEN set. Auth is DISABLED.", "Set YIPPIE_TOKEN to require a Bearer token."]);
+    }
+    if let Some(profile) = cli.profile.clone() {
+        config.profile = Some(profile);
+    }
+    if let Some(iterations) = cli.bench_state {
+        let report = state_bench::run(config, iterations, cli.bench_warmup).await?;
+        println!("{}", serde_json::to_string(&report)?);
+        return Ok(());
+    }
 
-    // Ensure capture directory exists
-    captures::CaptureManager::new(&config.capture_dir)?;
+    let mut builder = ServerBuilder::from_config(config)
+        .mock_studio(cli.mock_studio)
+        .status_report(cli.status_fd.is_some() || cli.status_file.is_some());
+    if let Some(path) = &cli.mock_scenario {
+        builder = builder.mock_scenario(path);
+    }
+    let server = match builder.build() {
+        Ok(server) => server,
+        Err(e) => {
+            let stage = e.stage();
+            return reporter.stage(stage, Err(e.into_error()));
+        }
+    };
+    let config = server.config().clone();
+    let state = server.state().clone();
 
+    let (bind_tx, mut bind_rx) = tokio::sync::watch::channel(BridgeBind::Pending);
     let http_config = config.clone();
     let http_state = state.clone();
     let http_handle = tokio::spawn(async move {
         // Retry binding the HTTP bridge with backoff
         loop {
-            match bridge_http::serve(http_config.clone(), http_state.clone()).await {
+            let listener = match bridge_http::bind(&http_config).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    bind_tx.send_replace(BridgeBind::Failed(e.to_string()));
+                    tracing::warn!("HTTP bridge failed: {e}. Retrying in 3s...");
+                    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+                    continue;
+                }
+            };
+            if let Ok(addr) = listener.local_addr() {
+                bind_tx.send_replace(BridgeBind::Bound(addr));
+            }
+            match bridge_http::serve(listener, http_config.clone(), http_state.clone()).await {
                 Ok(()) => break,
                 Err(e) => {
                     tracing::warn!("HTTP bridge failed: {e}. Retrying in 3s...");
@@ -60,10 +150,38 @@ async fn main() -> Result<()> {
 
     let stdio_state = state.clone();
     let stdio_handle = tokio::spawn(async move {
-        mcp_stdio::run(stdio_state).await
+        serve_stdio(stdio_state).await
+    });
+
+    // Report the bound address once the retry loop has actually bound, not the
+    // configured port, so supervisors see what the plugin must connect to. Ready also
+    // waits for the stdio loop, so a client may send requests as soon as it's reported.
+    let mock_studio = cli.mock_studio;
+    let ready_state = state.clone();
+    tokio::spawn(async move {
+        let deadline = tokio::time::Instant::now() + STARTUP_BIND_TIMEOUT;
+        let bound = tokio::time::timeout_at(deadline, bind_rx.wait_for(|b| matches!(b, BridgeBind::Bound(_)))).await;
+        let bound = bound.ok().and_then(|r| r.ok()).map(|b| b.clone());
+        if let Some(BridgeBind::Bound(_)) = bound {
+            if tokio::time::timeout_at(deadline, ready_state.stdio_running()).await.is_err() {
+                reporter.failed("stdio", &format!("MCP stdio loop not running after {STARTUP_BIND_TIMEOUT:?}"));
+                return;
+            }
+        }
+        match bound {
+            Some(BridgeBind::Bound(addr)) => reporter.ready(&config, addr, mock_studio),
+            _ => {
+                let error = match &*bind_rx.borrow() {
+                    BridgeBind::Failed(e) => e.clone(),
+                    _ => format!("bridge not bound after {STARTUP_BIND_TIMEOUT:?}"),
+                };
+                reporter.failed("bridge_bind", &error);
+            }
+        }
     });
 
-    // Exit when STDIO closes (client disconnected). HTTP bridge runs in background.
+    // Exit when STDIO closes (client disconnected) or POST /shutdown asks to. HTTP
+    // bridge runs in background.
     tokio::select! {
         _ = http_handle => {
             tracing::info!("HTTP bridge task ended");
@@ -74,7 +192,18 @@ async fn main() -> Result<()> {
                 tracing::error!("STDIO task error: {e}");
             }
         }
+        requested_by = state.shutdown_requested() => {
+            tokio::time::sleep(SHUTDOWN_REPLY_GRACE).await;
+            tracing::info!(requested_by = %requested_by, "Shutting down");
+            server.drain(SHUTDOWN_DRAIN_TIMEOUT).await;
+            server.shutdown();
+            tracing::info!("Shutdown complete");
+            // The stdio task may be blocked reading stdin, which would hold up
+            // dropping the runtime
+            std::process::exit(0);
+        }
     }
+    server.shutdown();
 
     Ok(())
 }
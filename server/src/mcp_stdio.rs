@@ -1,83 +1,398 @@
 use anyhow::Result;
+use base64::Engine;
+use regex::Regex;
 use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::sync::mpsc;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::JoinSet;
 
-use crate::state::SharedState;
+use crate::async_calls;
+use crate::capture_compare::{self, CompareRequest, Diff, Image};
+use crate::captures;
+use crate::config::DEFAULT_TOOL_PREFIX;
+use crate::gui_snapshot::{self, GuiSnapshot, Selector, Target, TreeRequest, Unresolved};
+use crate::idle;
+use crate::input_recording::{self, InputRecording};
+use crate::log_archive::ArchiveQuery;
+use crate::log_diff;
+use crate::luau_minify;
+use crate::model_diff::{self, Snapshot, SnapshotSource};
+use crate::outbound_queue::Priority;
+use crate::render::{self, OutputFormat};
+use crate::replay;
+use crate::request_limits::{self, BoundedLines, Line};
+use crate::runtime_flags::{self, RuntimeFlag};
+use crate::sanitize::Sanitizer;
+use crate::studio_settings;
+use crate::state::{OpenCheckpoint, SharedState};
+use crate::build_info::BuildInfo;
+use crate::client_roots::ClientRoots;
+use crate::clock_sync;
+use crate::output_dir::{self, OUTPUT_DIR_TOOLS};
+use crate::partial_result;
+use crate::place_stats::{self, PlaceStats, StatsRequest};
+use crate::profiles::{self, Settings};
+use crate::readiness::{self, Readiness};
+use crate::session::{self, Lifecycle, LogSubscription, SessionState};
+use tracing::Instrument;
+use crate::test_matrix;
+use crate::time_report;
+use crate::traceback;
+use crate::tool_history::{self, HistoryQuery, ToolCallRecord};
+use crate::tweenable;
+use crate::traffic::TrafficReport;
+use crate::transaction;
+use crate::variables::{self, Scope};
+use crate::viewport_render::{self, Render, RenderRequest};
 use crate::types::*;
 
 const SERVER_NAME: &str = "roblox-studio-yippieblox-mcp-server";
 const SERVER_VERSION: &str = env!("CARGO_PKG_VERSION");
 const PROTOCOL_VERSION: &str = "2025-11-25";
-const TOOL_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+pub(crate) const TOOL_CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+tokio::task_local! {
+    /// Queue priority of plugin calls made while handling one MCP tool call (including
+    /// replay steps and test_matrix variants). Calls outside any tool call are the
+    /// server's own automation and queue as background.
+    static CALL_PRIORITY: Priority;
+}
+/// How long a playtest-only tool waits for playtest state while a playtest_play/run
+/// call is still in flight, before failing fast.
+const PLAYTEST_START_GRACE: Duration = Duration::from_secs(15);
+/// The Studio version is a cheap lookup; don't make get_version wait the full tool timeout.
+const GET_VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Group list lookup before studio-set_collision_group.
+const COLLISION_GROUPS_TIMEOUT: Duration = Duration::from_secs(5);
+/// Bounds for run_script `scriptTimeoutMs`. The upper bound stays below
+/// TOOL_CALL_TIMEOUT so the plugin's timeout error arrives before ours.
+const MIN_SCRIPT_TIMEOUT_MS: u64 = 100;
+const MAX_SCRIPT_TIMEOUT_MS: u64 = 25_000;
+/// How long trigger_proximity_prompt waits for `Triggered`, hold time included.
+const DEFAULT_PROMPT_TIMEOUT_MS: u64 = 10_000;
+const MIN_PROMPT_TIMEOUT_MS: u64 = 1_000;
+const MAX_PROMPT_TIMEOUT_MS: u64 = 60_000;
+/// How long studio-get_replication_stats samples the network counters.
+const DEFAULT_REPLICATION_WINDOW_MS: u64 = 2_000;
+const MIN_REPLICATION_WINDOW_MS: u64 = 500;
+const MAX_REPLICATION_WINDOW_MS: u64 = 10_000;
+/// How long studio-cancel waits for the plugin to confirm it stopped a call.
+const CANCEL_ACK_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often studio-wait_operation checks whether the call it waits for has finished.
+const OPERATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Timing bounds for studio-wait_for_condition. The poll interval is capped at the
+/// timeout, so a wait always evaluates the predicate at least twice when it fails.
+const DEFAULT_CONDITION_TIMEOUT_MS: u64 = 10_000;
+const MIN_CONDITION_TIMEOUT_MS: u64 = 100;
+const MAX_CONDITION_TIMEOUT_MS: u64 = 120_000;
+const DEFAULT_CONDITION_INTERVAL_MS: u64 = 100;
+const MIN_CONDITION_INTERVAL_MS: u64 = 10;
+const MAX_CONDITION_INTERVAL_MS: u64 = 10_000;
+/// Limits of one studio-batch_get_properties call. Paths whose values would push the
+/// result past BATCH_PROPERTIES_MAX_BYTES aren't read; `resumeAt` says where to go on.
+const MAX_BATCH_PATHS: usize = 500;
+const MAX_BATCH_PROPERTIES: usize = 50;
+const BATCH_PROPERTIES_MAX_BYTES: usize = 256 * 1024;
+
+/// Backstop for a request whose handler never returns (e.g. a pending plugin call
+/// nothing resolves). Well above the longest bounded flow: test_matrix allows 30
+/// minutes of variant time plus setup and stop calls.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3600);
+/// Tool calls running at once. Further calls wait for a slot on their own task, so
+/// the loop keeps reading (`ping`, `roots/list` answers) meanwhile.
+const MAX_CONCURRENT_TOOL_CALLS: usize = 32;
+/// Tool calls running or waiting to run. Calls past this are refused, so a client
+/// that floods the server can't grow the queue without bound.
+const MAX_QUEUED_TOOL_CALLS: usize = 1024;
+/// How long tool calls still running when stdin closes get to answer before they are
+/// stopped, so a hung call can't hold the exit for `REQUEST_TIMEOUT`.
+const STDIN_CLOSE_GRACE: Duration = Duration::from_secs(10);
 
 /// Run the MCP STDIO loop: read JSON-RPC from stdin, write responses to stdout.
+///
+/// `tools/call` requests run on their own tasks and answer when done, so a slow tool
+/// doesn't hold up `ping` or `studio-status` behind it; responses carry the request id,
+/// so they may arrive out of order. At most `MAX_CONCURRENT_TOOL_CALLS` run at once;
+/// the rest wait their turn in arrival order.
+/// Everything else, `initialize` included, is answered inline and in order.
+/// Requests other than `ping` are refused until the handshake has finished (see
+/// `session::Lifecycle`) and once a shutdown has been requested.
 pub async fn run(state: SharedState) -> Result<()> {
-    let stdin = tokio::io::stdin();
-    let reader = BufReader::new(stdin);
-    let mut lines = reader.lines();
+    serve(state, tokio::io::stdin(), tokio::io::stdout()).await
+}
+
+/// The loop behind `run`, on any input and output.
+async fn serve<R, W>(state: SharedState, input: R, mut output: W) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let limit = state.max_request_bytes();
+    let mut lines = BoundedLines::new(BufReader::new(input), limit);
 
-    // All stdout writes go through this channel to prevent interleaving
+    // All stdout writes go through this channel to prevent interleaving. The writer
+    // only ends early on a write error (the client is gone even if stdin is still
+    // open); the loop below watches for that.
     let (tx, mut rx) = mpsc::channel::<String>(64);
-    tokio::spawn(async move {
-        let mut stdout = tokio::io::stdout();
+    let mut writer = tokio::spawn(async move {
         while let Some(line) = rx.recv().await {
-            if stdout.write_all(line.as_bytes()).await.is_err() {
-                break;
+            let written = async {
+                output.write_all(line.as_bytes()).await?;
+                output.write_all(b"\n").await?;
+                output.flush().await
+            };
+            if let Err(e) = written.await {
+                tracing::error!(error = %e, "Writing to stdout failed");
+                return;
             }
-            if stdout.write_all(b"\n").await.is_err() {
-                break;
-            }
-            let _ = stdout.flush().await;
         }
     });
 
-    while let Some(line) = lines.next_line().await? {
-        let line = line.trim().to_string();
-        if line.is_empty() {
-            continue;
-        }
+    // In-flight tool calls keep the session as it was when they started; `initialize`
+    // swaps in an updated copy.
+    let roots = Arc::new(ClientRoots::new(tx.clone()));
+    let mut session = Arc::new(SessionState::new(state.next_id(), roots));
+    state.register_session(&session);
+    let mut in_flight = ToolCallTasks::new(MAX_CONCURRENT_TOOL_CALLS, MAX_QUEUED_TOOL_CALLS);
+    let mut writer_failed = false;
+    state.mark_stdio_running();
 
-        let msg: JsonRpcMessage = match serde_json::from_str(&line) {
-            Ok(m) => m,
+    loop {
+        // A line cut short when the writer ends is not missed: the loop stops either way
+        let line = tokio::select! {
+            line = lines.next_line() => match line? {
+                Some(Line::Complete(line)) => line,
+                Some(Line::Oversized { bytes, id }) => {
+                    tracing::warn!(id = ?id, bytes, limit, "Refusing oversized MCP message");
+                    let resp = JsonRpcResponse::error(id, -32602, request_limits::oversized_message(bytes, limit));
+                    if !send_response(&tx, &resp).await {
+                        writer_failed = true;
+                        break;
+                    }
+                    continue;
+                }
+                None => break,
+            },
+            _ = &mut writer => {
+                writer_failed = true;
+                break;
+            }
+        };
+        let parsed = match std::str::from_utf8(&line) {
+            Ok(text) if text.trim().is_empty() => continue,
+            Ok(text) => serde_json::from_str::<Value>(text.trim()),
+            Err(e) => Err(serde::de::Error::custom(format!("message is not UTF-8: {e}"))),
+        };
+        let parsed = parsed.and_then(|value| {
+            // Answers to requests the server sent (roots/list) have no method
+            if value.get("method").is_none() && value.get("id").is_some() {
+                return Ok(Err(value));
+            }
+            serde_json::from_value::<JsonRpcMessage>(value).map(Ok)
+        });
+        let msg = match parsed {
+            Ok(Ok(m)) => m,
+            Ok(Err(response)) => {
+                if !session.roots().resolve_response(&response) {
+                    tracing::warn!(id = ?response.get("id"), "Response from the client to no pending request");
+                }
+                continue;
+            }
             Err(e) => {
                 tracing::warn!("Failed to parse JSON-RPC message: {e}");
                 let resp = JsonRpcResponse::error(Value::Null, -32700, format!("Parse error: {e}"));
-                let _ = tx.send(serde_json::to_string(&resp)?).await;
+                if !send_response(&tx, &resp).await {
+                    writer_failed = true;
+                    break;
+                }
                 continue;
             }
         };
 
-        tracing::info!(method = %msg.method, id = ?msg.id, "Received MCP message");
+        tracing::info!(session = %session.id, method = %msg.method, id = ?msg.id, "Received MCP message");
+        // The logical clock of deterministic mode moves once per message
+        state.clock().tick();
 
         // Notifications (no id) don't get a response
         if msg.id.is_none() {
-            handle_notification(&msg.method).await;
+            handle_notification(&mut session, &msg.method).await;
             continue;
         }
 
         let id = msg.id.unwrap();
-        let response = handle_request(&state, id.clone(), &msg.method, msg.params).await;
-        let serialized = serde_json::to_string(&response)?;
-        if tx.send(serialized).await.is_err() {
-            tracing::error!("stdout writer closed");
+        if state.shutdown_pending() {
+            Arc::make_mut(&mut session).set_lifecycle(Lifecycle::ShuttingDown);
+        }
+        if let Err((code, message)) = session.lifecycle().admit(&msg.method, state.lenient_lifecycle()) {
+            tracing::warn!(id = ?id, method = %msg.method, lifecycle = ?session.lifecycle(), "Refusing MCP request: {message}");
+            if !send_response(&tx, &JsonRpcResponse::error(id, code, message)).await {
+                writer_failed = true;
+                break;
+            }
+            continue;
+        }
+        if state.record_activity(session.activity()) {
+            idle::resume(&state, &session).await;
+        }
+        if msg.method == "tools/call" {
+            let queued = {
+                let (state, mut session, tx, id) = (state.clone(), session.clone(), tx.clone(), id.clone());
+                in_flight.spawn(async move {
+                    let handling = handle_request(&state, &mut session, id.clone(), &msg.method, msg.params);
+                    let response = match tokio::time::timeout(REQUEST_TIMEOUT, handling).await {
+                        Ok(response) => response,
+                        Err(_) => {
+                            tracing::error!(id = ?id, "Request did not finish within {}s", REQUEST_TIMEOUT.as_secs());
+                            let message = format!("Internal error: request did not finish within {}s", REQUEST_TIMEOUT.as_secs());
+                            JsonRpcResponse::error(id, -32603, message)
+                        }
+                    };
+                    send_response(&tx, &response).await;
+                })
+            };
+            if !queued {
+                tracing::warn!(id = ?id, "Refusing tool call: {MAX_QUEUED_TOOL_CALLS} already running or queued");
+                let message = format!(
+                    "Server busy: {MAX_QUEUED_TOOL_CALLS} tool calls are already running or queued. Retry when some have finished."
+                );
+                let response = JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+                if !send_response(&tx, &response).await {
+                    writer_failed = true;
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let response = handle_request(&state, &mut session, id, &msg.method, msg.params).await;
+        if !send_response(&tx, &response).await {
+            writer_failed = true;
             break;
         }
     }
 
+    if writer_failed {
+        // Nobody reads the results any more: fail the plugin calls being awaited,
+        // drop the calls still running and close what the session left open
+        tracing::error!("stdout is broken, MCP session ending");
+        let failed = state.fail_pending_calls("MCP client disconnected").await;
+        in_flight.abort_all().await;
+        tracing::info!(failed_calls = failed, "Aborted in-flight tool calls");
+        idle::teardown(&state).await;
+        return Ok(());
+    }
+
     tracing::info!("stdin closed, MCP session ending");
+    // Answer what is still running or queued before the writer shuts down, but don't
+    // wait on a hung call for long
+    if tokio::time::timeout(STDIN_CLOSE_GRACE, in_flight.join_all()).await.is_err() {
+        let failed = state.fail_pending_calls("MCP session ended").await;
+        in_flight.abort_all().await;
+        tracing::warn!(
+            failed_calls = failed,
+            "Stopped tool calls still running {}s after stdin closed",
+            STDIN_CLOSE_GRACE.as_secs()
+        );
+    }
+    // The session's roots requests hold a sender too
+    drop(session);
+    drop(tx);
+    let _ = writer.await;
     Ok(())
 }
 
-async fn handle_notification(method: &str) {
+/// Tool call tasks of the stdio loop. Each call gets its task right away and waits
+/// there for one of `running` slots, so a full house never blocks the loop; at most
+/// `capacity` calls are running or waiting.
+struct ToolCallTasks {
+    tasks: JoinSet<()>,
+    /// Fair, so waiting calls start in the order they arrived.
+    slots: Arc<Semaphore>,
+    capacity: usize,
+}
+
+impl ToolCallTasks {
+    fn new(running: usize, capacity: usize) -> Self {
+        Self {
+            tasks: JoinSet::new(),
+            slots: Arc::new(Semaphore::new(running)),
+            capacity,
+        }
+    }
+
+    /// Queue `call`. False, with nothing spawned, when `capacity` calls are already
+    /// running or waiting.
+    fn spawn<F>(&mut self, call: F) -> bool
+    where
+        F: std::future::Future<Output = ()> + Send + 'static,
+    {
+        while self.tasks.try_join_next().is_some() {}
+        if self.tasks.len() >= self.capacity {
+            return false;
+        }
+        let slots = self.slots.clone();
+        self.tasks.spawn(async move {
+            // The semaphore is never closed
+            let Ok(_slot) = slots.acquire_owned().await else {
+                return;
+            };
+            call.await;
+        });
+        true
+    }
+
+    /// Wait for every call, queued ones included.
+    async fn join_all(&mut self) {
+        while self.tasks.join_next().await.is_some() {}
+    }
+
+    /// Drop every call, running or queued, and wait until they are gone.
+    async fn abort_all(&mut self) {
+        self.tasks.abort_all();
+        self.join_all().await;
+    }
+}
+
+/// Queue `response` for stdout. False once the writer has stopped.
+async fn send_response(tx: &mpsc::Sender<String>, response: &JsonRpcResponse) -> bool {
+    let serialized = match serde_json::to_string(response) {
+        Ok(serialized) => serialized,
+        Err(e) => {
+            tracing::error!("Failed to serialize JSON-RPC response: {e}");
+            return true;
+        }
+    };
+    if tx.send(serialized).await.is_err() {
+        tracing::error!("stdout writer closed");
+        return false;
+    }
+    true
+}
+
+async fn handle_notification(session: &mut Arc<SessionState>, method: &str) {
     match method {
         "notifications/initialized" => {
-            tracing::info!("MCP client initialized");
+            if session.lifecycle() == Lifecycle::Uninitialized {
+                tracing::warn!("MCP client sent notifications/initialized before initialize");
+            } else {
+                tracing::info!("MCP client initialized");
+            }
+            let ready = session.lifecycle().ready();
+            Arc::make_mut(session).set_lifecycle(ready);
         }
         "notifications/cancelled" => {
             tracing::info!("MCP client cancelled a request");
         }
+        "notifications/roots/list_changed" => {
+            tracing::info!("MCP client roots changed");
+            session.roots().invalidate();
+        }
         other => {
             tracing::debug!("Unknown notification: {other}");
         }
@@ -86,570 +401,7082 @@ async fn handle_notification(method: &str) {
 
 async fn handle_request(
     state: &SharedState,
+    session: &mut Arc<SessionState>,
     id: Value,
     method: &str,
     params: Value,
 ) -> JsonRpcResponse {
     match method {
-        "initialize" => handle_initialize(id),
+        "initialize" => {
+            Arc::make_mut(session).initialize(&params);
+            if let Some(client) = session.client() {
+                state.match_client_profile(&client.name);
+            }
+            handle_initialize(state, id)
+        }
         "ping" => JsonRpcResponse::success(id, json!({})),
-        "tools/list" => handle_tools_list(id),
-        "tools/call" => handle_tools_call(state, id, params).await,
+        "tools/list" => handle_tools_list(state, id),
+        "tools/call" => handle_tools_call(state, session, id, params).await,
         _ => JsonRpcResponse::error(id, -32601, format!("Method not found: {method}")),
     }
 }
 
-fn handle_initialize(id: Value) -> JsonRpcResponse {
-    JsonRpcResponse::success(
-        id,
-        json!({
-            "protocolVersion": PROTOCOL_VERSION,
-            "capabilities": {
-                "tools": {}
-            },
-            "serverInfo": {
-                "name": SERVER_NAME,
-                "version": SERVER_VERSION
+/// Answers MCP requests without the stdio loop, for programs embedding the server.
+///
+/// Requests go through the same handling as stdin messages: tool name prefix,
+/// policy, response cache and `format`. The dispatcher is one MCP session; clones
+/// share it. There is no client to ask for roots, so `outputDir` arguments are
+/// refused and tools write to the capture folder.
+#[derive(Clone)]
+pub struct Dispatcher {
+    state: SharedState,
+    session: Arc<SessionState>,
+}
+
+/// Why [`Dispatcher::request`] or [`Dispatcher::call_tool`] failed.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum DispatchError {
+    /// The request was rejected as a JSON-RPC error: unknown method or tool, malformed
+    /// params, or a handler that ran past its backstop timeout.
+    Protocol { code: i64, message: String },
+    /// The tool ran and reported failure (`isError`). `message` joins the text content;
+    /// `result` is the whole MCP tool result.
+    Tool { message: String, result: Value },
+}
+
+impl std::fmt::Display for DispatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DispatchError::Protocol { code, message } => write!(f, "{message} (JSON-RPC error {code})"),
+            DispatchError::Tool { message, .. } => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DispatchError {}
+
+impl Dispatcher {
+    pub fn new(state: SharedState) -> Self {
+        // There is no client to answer `roots/list`, so a roots request fails at once
+        let (tx, _) = mpsc::channel(1);
+        let mut session = SessionState::new(state.next_id(), Arc::new(ClientRoots::new(tx)));
+        // In-process callers have no handshake to wait for
+        session.set_lifecycle(Lifecycle::Ready);
+        state.register_session(&session);
+        Self {
+            state,
+            session: Arc::new(session),
+        }
+    }
+
+    /// Handle one MCP request (`initialize`, `ping`, `tools/list`, `tools/call`) and
+    /// return its `result`. `initialize` updates the session of this dispatcher only.
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Value, DispatchError> {
+        self.state.clock().tick();
+        if self.state.record_activity(self.session.activity()) {
+            idle::resume(&self.state, &self.session).await;
+        }
+        let handling = handle_request(&self.state, &mut self.session, Value::Null, method, params);
+        let response = tokio::time::timeout(REQUEST_TIMEOUT, handling).await.map_err(|_| DispatchError::Protocol {
+            code: -32603,
+            message: format!("Internal error: request did not finish within {}s", REQUEST_TIMEOUT.as_secs()),
+        })?;
+        if let Some(error) = response.error {
+            return Err(DispatchError::Protocol {
+                code: error.code,
+                message: error.message,
+            });
+        }
+        Ok(response.result.unwrap_or(Value::Null))
+    }
+
+    /// Call a tool by the name clients see (with the configured prefix). Returns the
+    /// MCP tool result, `{ content, isError: false }`.
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, DispatchError> {
+        let params = json!({ "name": name, "arguments": arguments });
+        let result = self.clone().request("tools/call", params).await?;
+        if result.get("isError").and_then(Value::as_bool) == Some(true) {
+            let message = result["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|c| c.get("text").and_then(Value::as_str))
+                .collect::<Vec<_>>()
+                .join("\n");
+            return Err(DispatchError::Tool { message, result });
+        }
+        Ok(result)
+    }
+
+    /// The tools as `tools/list` shows them: prefixed, without disabled tools, and
+    /// without experimental ones when those are off.
+    pub fn tools(&self) -> Vec<McpToolDef> {
+        listed_tools(&self.state)
+    }
+}
+
+fn handle_initialize(state: &SharedState, id: Value) -> JsonRpcResponse {
+    let prefix = state.tool_prefix();
+    let experimental: Vec<String> = tool_definitions()
+        .into_iter()
+        .filter(|t| t.experimental)
+        .map(|t| apply_tool_prefix(&t.name, prefix).into_owned())
+        .collect();
+    let mut result = json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "capabilities": {
+            "tools": {},
+            "experimental": {
+                "yippieblox/experimentalTools": {
+                    "enabled": state.experimental_tools_enabled(),
+                    "tools": experimental,
+                }
             }
-        }),
-    )
+        },
+        "serverInfo": {
+            "name": SERVER_NAME,
+            "version": SERVER_VERSION,
+            "build": BuildInfo::get(),
+        }
+    });
+    // Only worth a line of context when there is something to switch
+    if !state.profiles().is_empty() {
+        let settings = state.settings();
+        result["instructions"] = json!(apply_tool_prefix(
+            &format!(
+                "Configuration profile '{}' is active ({}). Switch with studio-set_profile; studio-status shows the current one.",
+                settings.name(),
+                profiles::describe(&settings)
+            ),
+            prefix
+        ));
+    }
+    JsonRpcResponse::success(id, result)
 }
 
-fn handle_tools_list(id: Value) -> JsonRpcResponse {
-    let tools = tool_definitions();
-    let tools_json: Vec<Value> = tools
+fn handle_tools_list(state: &SharedState, id: Value) -> JsonRpcResponse {
+    let tools_json: Vec<Value> = listed_tools(state)
         .into_iter()
         .map(|t| serde_json::to_value(t).unwrap())
         .collect();
     JsonRpcResponse::success(id, json!({ "tools": tools_json }))
 }
 
-async fn handle_tools_call(state: &SharedState, id: Value, params: Value) -> JsonRpcResponse {
-    let tool_name = match params.get("name").and_then(|v| v.as_str()) {
-        Some(n) => n.to_string(),
+/// Tool definitions as clients see them.
+fn listed_tools(state: &SharedState) -> Vec<McpToolDef> {
+    let prefix = state.tool_prefix();
+    let include_experimental = state.experimental_tools_enabled();
+    tool_definitions()
+        .into_iter()
+        .filter(|t| include_experimental || !t.experimental)
+        .filter(|t| !state.tool_disabled_by_policy(&t.name))
+        .filter(|t| state.debug_endpoints_enabled() || !DEBUG_TOOLS.contains(&t.name.as_str()))
+        .map(|mut t| {
+            t.name = apply_tool_prefix(&t.name, prefix).into_owned();
+            t.description = t.description.map(|d| apply_tool_prefix(&d, prefix).into_owned());
+            t
+        })
+        .collect()
+}
+
+/// Check the shape of `tools/call` params so a malformed request gets a message naming
+/// the actual problem. A null or missing `arguments` becomes `{}`.
+fn check_tools_call_params(params: &mut Value) -> Result<(), String> {
+    let Some(params) = params.as_object_mut() else {
+        return Err(format!("tools/call params must be an object, got {}", json_type(params)));
+    };
+    match params.get("name") {
+        None | Some(Value::Null) => return Err("Missing 'name' in tools/call params".into()),
+        Some(Value::String(name)) if name.is_empty() => return Err("'name' in tools/call params is empty".into()),
+        Some(Value::String(_)) => {}
+        Some(other) => return Err(format!("'name' in tools/call params must be a string, got {}", json_type(other))),
+    }
+    match params.get("arguments") {
+        None | Some(Value::Null) => {
+            params.insert("arguments".into(), json!({}));
+        }
+        Some(Value::Object(_)) => {}
+        Some(other) => return Err(format!("'arguments' in tools/call params must be an object, got {}", json_type(other))),
+    }
+    Ok(())
+}
+
+/// JSON type name for error messages.
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+async fn handle_tools_call(
+    state: &SharedState,
+    session: &SessionState,
+    id: Value,
+    mut params: Value,
+) -> JsonRpcResponse {
+    if let Err(message) = check_tools_call_params(&mut params) {
+        return JsonRpcResponse::error(id, -32602, message);
+    }
+    let prefix = state.tool_prefix();
+    if prefix != DEFAULT_TOOL_PREFIX {
+        // Map the client-facing name back to the canonical `studio-` name used for
+        // dispatch, routing and the plugin protocol.
+        if let Some(name) = params.get("name").and_then(|v| v.as_str()) {
+            let Some(rest) = name.strip_prefix(prefix) else {
+                return JsonRpcResponse::error(id, -32602, format!("Unknown tool: {name}"));
+            };
+            params["name"] = json!(format!("{DEFAULT_TOOL_PREFIX}{rest}"));
+        }
+    }
+
+    // Before anything else reads the arguments: a pasted dataset fails here, cheaply
+    let name = params["name"].as_str().unwrap_or_default();
+    if let Err(message) = request_limits::check_code_size(name, &params["arguments"]) {
+        tracing::warn!(tool = %name, "Refusing oversized code argument");
+        return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+    }
+
+    // `format` is handled here for tabular tools and not forwarded
+    let tabular = params
+        .get("name")
+        .and_then(|v| v.as_str())
+        .and_then(tabular_columns);
+    let mut format = None;
+    if tabular.is_some() {
+        if let Some(requested) = params
+            .get_mut("arguments")
+            .and_then(|a| a.as_object_mut())
+            .and_then(|a| a.remove("format"))
+        {
+            match requested.as_str().map(OutputFormat::parse) {
+                Some(Ok(f)) => format = Some(f),
+                Some(Err(e)) => return JsonRpcResponse::success(id, McpToolResult::error_text(e).to_value()),
+                None => {
+                    let result = McpToolResult::error_text("'format' must be a string: json, text or csv");
+                    return JsonRpcResponse::success(id, result.to_value());
+                }
+            }
+        }
+    }
+
+    // Results are cached after redaction; the prefix rewrite below runs on every call
+    let tool_name = params.get("name").and_then(|v| v.as_str()).unwrap_or_default().to_string();
+    let raw_text = params.pointer(RAW_TEXT_META).and_then(|v| v.as_bool()) == Some(true);
+    // Raw results differ from the cleaned ones cached for the same arguments
+    let cacheable = tool_annotations(&tool_name).cacheable && !raw_text;
+    // The cache key and hooks need the arguments after dispatch has consumed them;
+    // other calls skip the copy, which matters for multi-megabyte `code`
+    let arguments = (cacheable || state.hooks().is_some()).then(|| params["arguments"].clone());
+    let cached = cacheable
+        .then(|| state.response_cache()?.get(&tool_name, arguments.as_ref()?))
+        .flatten();
+
+    let started = std::time::Instant::now();
+    let ts = state.clock().now().to_rfc3339();
+    let from_cache = cached.is_some();
+    let mut response = match cached {
+        Some(result) => {
+            tracing::debug!(tool = %tool_name, "Served from response cache");
+            JsonRpcResponse::success(id, result)
+        }
         None => {
-            return JsonRpcResponse::error(id, -32602, "Missing 'name' in tools/call params");
+            let priority = session.priority();
+            let client = session.client_label();
+            state.count_tool_call(client.as_deref());
+            // Every log line of the call, plugin routing included, names the client
+            let span = tracing::info_span!("tool_call", mcp_client = client.as_deref().unwrap_or("unknown"));
+            let call = CALL_PRIORITY.scope(priority, dispatch_tool_call(state, session, id, params));
+            let mut response = session::with_call_client(client, call).instrument(span).await;
+            if !raw_text {
+                sanitize_tool_result(state.sanitizer(), &mut response);
+            }
+            redact_tool_result(state, &mut response);
+            if let Some(result) = response.result.as_ref().filter(|r| cacheable && r["isError"] != true) {
+                if let (Some(mut cache), Some(arguments)) = (state.response_cache(), &arguments) {
+                    cache.insert(&tool_name, arguments, result.clone());
+                }
+            }
+            if let (Some((rows_key, columns)), Some(format)) = (tabular, format) {
+                apply_output_format(&mut response, format, rows_key, columns);
+            }
+            response
         }
     };
-    let arguments = params
-        .get("arguments")
-        .cloned()
-        .unwrap_or(json!({}));
-
-    // studio-status can be answered directly by the server
-    if tool_name == "studio-status" {
-        return handle_status_tool(state, id).await;
+    // Hooks run on every call, cached or not, after redaction
+    if let (Some(hooks), Some(arguments)) = (state.hooks(), &arguments) {
+        if let Some(result) = response.result.as_mut() {
+            hooks.apply(state, &tool_name, arguments, result).await;
+        }
+    }
+    deliver_settings_warnings(state, session, &mut response).await;
+    if tool_name != "studio-get_tool_history" {
+        record_tool_call(state, session, &tool_name, ts, started, from_cache, &response);
     }
+    if prefix != DEFAULT_TOOL_PREFIX {
+        rewrite_result_text(&mut response, |text| apply_tool_prefix(text, prefix));
+    }
+    response
+}
 
-    // Disabled tools — return unsupported immediately
-    let disabled_reason = match tool_name.as_str() {
-        "studio-capture_screenshot" => {
-            Some("Unsupported: CaptureService returns rbxtemp:// content IDs that cannot be extracted as files from a plugin.")
+/// Append the plugin settings warnings this session hasn't been shown to a tool
+/// result, once each. Some depend on the session (log forwarding is only a problem
+/// for a session subscribed to logs), so every session keeps its own record.
+async fn deliver_settings_warnings(state: &SharedState, session: &SessionState, response: &mut JsonRpcResponse) {
+    if response.result.is_none() {
+        return;
+    }
+    let warnings = state
+        .plugin_settings_warnings(session.activity().log_subscription().is_some())
+        .await;
+    let keys: Vec<String> = warnings
+        .iter()
+        .map(|(client_id, warning)| format!("{client_id}/{}", warning.code))
+        .collect();
+    let undelivered = session.activity().undelivered_warnings(&keys);
+    let Some(content) = response
+        .result
+        .as_mut()
+        .and_then(|r| r.get_mut("content"))
+        .and_then(Value::as_array_mut)
+    else {
+        return;
+    };
+    for (key, (client_id, warning)) in keys.iter().zip(&warnings) {
+        if undelivered.contains(key) {
+            let text = format!("Warning from plugin {client_id} ({}): {}", warning.code, warning.message);
+            content.push(json!(McpContent::Text { text }));
         }
-        "studio-capture_video_start" | "studio-capture_video_stop" => {
-            Some("Unsupported: CaptureService does not expose a video recording API.")
+    }
+}
+
+/// Add a finished call to the tool history. Its error is the JSON-RPC error or the
+/// text of an error result, already redacted.
+fn record_tool_call(
+    state: &SharedState,
+    session: &SessionState,
+    tool_name: &str,
+    ts: String,
+    started: std::time::Instant,
+    cached: bool,
+    response: &JsonRpcResponse,
+) {
+    let error = match (&response.error, &response.result) {
+        (Some(error), _) => Some(error.message.clone()),
+        (None, Some(result)) if result["isError"] == true => {
+            Some(result.pointer("/content/0/text").and_then(|t| t.as_str()).unwrap_or_default().to_string())
         }
         _ => None,
     };
-    if let Some(reason) = disabled_reason {
-        let result = McpToolResult::error_text(reason);
-        return JsonRpcResponse::success(id, result.to_value());
+    state.tool_history().push(ToolCallRecord {
+        seq: 0,
+        ts,
+        tool: tool_name.to_string(),
+        success: error.is_none(),
+        duration_ms: state.clock().elapsed(started).as_millis() as u64,
+        cached,
+        client: session.client_label(),
+        session: session.id.clone(),
+        error,
+    });
+}
+
+/// Row array key and columns for tools whose results are tabular and accept `format`.
+fn tabular_columns(tool_name: &str) -> Option<(&'static str, &'static [&'static str])> {
+    match tool_name {
+        "studio-logs_get" | "studio-get_output_since" => Some(("entries", &["seq", "ts", "level", "message"])),
+        "studio-logs_search_archive" => Some(("hits", &["time", "level", "score", "message", "sessionId"])),
+        "studio-get_errors_grouped" => Some(("groups", &["count", "level", "firstTs", "lastTs", "message"])),
+        "studio-get_tool_history" => Some(("calls", &["seq", "ts", "tool", "success", "durationMs", "client"])),
+        _ => None,
     }
+}
 
-    // All other tools require a connected plugin
-    if !state.has_connected_client().await {
-        let result = McpToolResult::error_text(
-            "No Roblox Studio plugin connected. Install the plugin and click Connect.",
-        );
-        return JsonRpcResponse::success(id, result.to_value());
+/// Put the JSON result in `structuredContent` and, for text/csv, replace the text block
+/// with a rendering of its row array. Error results are left alone.
+fn apply_output_format(
+    response: &mut JsonRpcResponse,
+    format: OutputFormat,
+    rows_key: &str,
+    columns: &[&str],
+) {
+    let Some(result) = response.result.as_mut() else {
+        return;
+    };
+    if result["isError"] == true {
+        return;
     }
+    let Some(Value::String(text)) = result.pointer_mut("/content/0/text") else {
+        return;
+    };
+    let Ok(structured) = serde_json::from_str::<Value>(text) else {
+        return;
+    };
+    if let Some(rows) = structured.get(rows_key).and_then(|r| r.as_array()) {
+        if let Some(rendered) = render::render(format, columns, rows) {
+            *text = rendered;
+        }
+    }
+    result["structuredContent"] = structured;
+}
 
-    // Create oneshot channel for the response
-    let request_id = uuid::Uuid::new_v4().to_string();
-    let (tx, rx) = tokio::sync::oneshot::channel();
+/// Canonical names of every tool the server exposes.
+static TOOL_NAMES: LazyLock<HashSet<String>> =
+    LazyLock::new(|| tool_definitions().into_iter().map(|t| t.name).collect());
 
-    let bridge_request = BridgeToolRequest {
-        request_id: request_id.clone(),
-        tool_name: tool_name.clone(),
-        arguments,
-    };
+/// Annotations of every tool that has them, by canonical name.
+static TOOL_ANNOTATIONS: LazyLock<HashMap<String, ToolAnnotations>> = LazyLock::new(|| {
+    tool_definitions()
+        .into_iter()
+        .filter_map(|t| Some((t.name, t.annotations?)))
+        .collect()
+});
 
-    state.register_pending(request_id.clone(), tx).await;
+/// Whether `name` is the canonical name of a tool the server exposes.
+pub fn is_tool_name(name: &str) -> bool {
+    TOOL_NAMES.contains(name)
+}
 
-    if !state.enqueue_tool_request(bridge_request).await {
-        let result = McpToolResult::error_text("Failed to enqueue tool request to plugin");
-        return JsonRpcResponse::success(id, result.to_value());
-    }
+fn tool_annotations(tool_name: &str) -> ToolAnnotations {
+    TOOL_ANNOTATIONS.get(tool_name).copied().unwrap_or_default()
+}
 
-    tracing::info!(tool = %tool_name, request_id = %request_id, "Forwarding tool call to plugin");
+static EXPERIMENTAL_TOOLS: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    tool_definitions()
+        .into_iter()
+        .filter(|t| t.experimental)
+        .map(|t| t.name)
+        .collect()
+});
 
-    // Await plugin response with timeout
-    let start = std::time::Instant::now();
-    match tokio::time::timeout(TOOL_CALL_TIMEOUT, rx).await {
-        Ok(Ok(response)) => {
-            let elapsed = start.elapsed();
-            if response.success {
-                tracing::info!(tool = %tool_name, elapsed_ms = elapsed.as_millis(), "Tool call succeeded");
-                let text = response
-                    .result
-                    .map(|v| {
-                        if v.is_string() {
-                            v.as_str().unwrap().to_string()
-                        } else {
-                            serde_json::to_string_pretty(&v).unwrap_or_default()
-                        }
-                    })
-                    .unwrap_or_else(|| "ok".to_string());
-                let result = McpToolResult::text(text);
-                JsonRpcResponse::success(id, result.to_value())
-            } else {
-                let error_msg = response
-                    .error
-                    .unwrap_or_else(|| "Unknown plugin error".to_string());
-                tracing::warn!(tool = %tool_name, elapsed_ms = elapsed.as_millis(), error = %error_msg, "Tool call failed");
-                let result = McpToolResult::error_text(error_msg);
-                JsonRpcResponse::success(id, result.to_value())
-            }
-        }
-        Ok(Err(_)) => {
-            tracing::error!(tool = %tool_name, "Plugin disconnected while processing tool call");
-            let result = McpToolResult::error_text("Plugin disconnected while processing tool call");
-            JsonRpcResponse::success(id, result.to_value())
+/// Tools for diagnosing the server itself, only offered with `YIPPIE_DEBUG_ENDPOINTS`.
+const DEBUG_TOOLS: &[&str] = &["studio-list_tools_with_routing"];
+
+/// Why `tool_name` can't be called on this server: switched off by the operator, a
+/// debug tool while those are off, or listed but unsupported in Studio.
+fn disabled_reason(state: &SharedState, tool_name: &str) -> Option<String> {
+    if state.tool_disabled_by_policy(tool_name) {
+        return Some(format!("{tool_name} is disabled by server policy (YIPPIE_DISABLED_TOOLS)"));
+    }
+    if !state.debug_endpoints_enabled() && DEBUG_TOOLS.contains(&tool_name) {
+        return Some(format!("{tool_name} is a debug tool; set YIPPIE_DEBUG_ENDPOINTS=1 to enable it"));
+    }
+    let reason = match tool_name {
+        "studio-capture_screenshot" => {
+            "Unsupported: CaptureService returns rbxtemp:// content IDs that cannot be extracted as files from a plugin."
         }
-        Err(_) => {
-            tracing::warn!(tool = %tool_name, "Tool call timed out after {TOOL_CALL_TIMEOUT:?}");
-            let result = McpToolResult::error_text(format!(
-                "Tool call '{tool_name}' timed out after {}s. Is the Studio plugin running?",
-                TOOL_CALL_TIMEOUT.as_secs()
-            ));
-            JsonRpcResponse::success(id, result.to_value())
+        "studio-capture_video_start" | "studio-capture_video_stop" => {
+            "Unsupported: CaptureService does not expose a video recording API."
         }
+        _ => return None,
+    };
+    Some(reason.into())
+}
+
+static TOOL_NAME_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"studio-[a-z_]+").expect("static regex"));
+
+/// Swap the canonical prefix for `prefix` in every known tool name in `text`, so names
+/// in descriptions and messages match what the client calls. Event names such as
+/// `studio-log` are not tools and stay as they are.
+fn apply_tool_prefix<'a>(text: &'a str, prefix: &str) -> Cow<'a, str> {
+    if prefix == DEFAULT_TOOL_PREFIX {
+        return Cow::Borrowed(text);
     }
+    TOOL_NAME_RE.replace_all(text, |caps: &regex::Captures| {
+        let name = &caps[0];
+        if TOOL_NAMES.contains(name) {
+            format!("{prefix}{}", &name[DEFAULT_TOOL_PREFIX.len()..])
+        } else {
+            name.to_string()
+        }
+    })
 }
 
-async fn handle_status_tool(state: &SharedState, id: Value) -> JsonRpcResponse {
-    let connected = state.has_connected_client().await;
-    let client_id = state.first_client_id().await;
-    let (playtest_active, session_id, mode) = state.playtest_info().await;
-    let clients: Vec<Value> = state
-        .client_info()
-        .await
-        .into_iter()
-        .map(|(id, version, last_poll, is_bridge)| {
-            let age_secs = (chrono::Utc::now() - last_poll).num_seconds();
-            json!({
-                "clientId": id,
-                "version": version,
-                "isBridge": is_bridge,
-                "lastPollSecsAgo": age_secs,
-            })
-        })
-        .collect();
+/// `tools/call` `params._meta` flag that keeps escape sequences and control characters
+/// in the result, for a call that needs the exact text.
+const RAW_TEXT_META: &str = "/_meta/yippieblox~1rawText";
 
-    let result = json!({
-        "connected": connected,
-        "clientId": client_id,
-        "clients": clients,
-        "playtest": {
-            "active": playtest_active,
-            "sessionId": session_id,
-            "mode": mode,
+/// Strip ANSI escapes and control characters from a tool result: its text blocks and
+/// `structuredContent`. Text blocks holding JSON carry them as `\u001b`-style escapes,
+/// so those are parsed, cleaned and written back in the same layout.
+fn sanitize_tool_result(sanitizer: Sanitizer, response: &mut JsonRpcResponse) {
+    rewrite_result_text(response, |text| {
+        let text = sanitizer.text(text);
+        let escaped = ["\\u00", "\\r", "\\b", "\\f"].iter().any(|e| text.contains(e));
+        if !escaped || !(text.starts_with('{') || text.starts_with('[')) {
+            return text;
         }
+        let Ok(mut value) = serde_json::from_str::<Value>(&text) else {
+            return text;
+        };
+        if !sanitizer.value(&mut value) {
+            return text;
+        }
+        let rewritten = if text.contains('\n') {
+            serde_json::to_string_pretty(&value)
+        } else {
+            serde_json::to_string(&value)
+        };
+        rewritten.map(Cow::Owned).unwrap_or(text)
     });
+    if let Some(structured) = response.result.as_mut().and_then(|r| r.get_mut("structuredContent")) {
+        sanitizer.value(structured);
+    }
+}
 
-    JsonRpcResponse::success(id, McpToolResult {
-        content: vec![McpContent::Text {
-            text: serde_json::to_string_pretty(&result).unwrap(),
-        }],
-        is_error: false,
-    }.to_value())
+/// Apply secret redaction to a tool result before it leaves the server.
+fn redact_tool_result(state: &SharedState, response: &mut JsonRpcResponse) {
+    if let Some(result) = response.result.as_mut() {
+        redact_result(state, result);
+    }
 }
 
-// ─── Tool Definitions ─────────────────────────────────────────
+/// Redact every text block of a tool result and every string in its `structuredContent`.
+fn redact_result(state: &SharedState, result: &mut Value) {
+    rewrite_text_blocks(result, |text| state.redact(text));
+    if let Some(structured) = result.get_mut("structuredContent") {
+        state.redact_value(structured);
+    }
+}
 
-fn tool_definitions() -> Vec<McpToolDef> {
-    vec![
-        McpToolDef {
-            name: "studio-status".into(),
-            description: Some("Get current Studio connection state and playtest status. Use this to verify the plugin is connected before executing other tools, or to check if a playtest is currently active. Returns connection status, playtest mode (none/play/run), and server URL.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {},
-                "additionalProperties": false
-            }),
-        },
-        McpToolDef {
-            name: "studio-run_script".into(),
-            description: Some("Execute Luau code in Studio's edit mode to modify the place structure, inspect the DataModel, or create/modify instances. Only works when NO playtest is active - this is for editing the place file itself. Returns the script's return value and any print() output. Use studio-test_script instead if you need to test runtime behavior, game logic, or anything involving Players.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "code": {
-                        "type": "string",
-                        "description": "Luau code to execute in edit mode. Can include print() statements for debugging. Use 'return <value>' to return data. Multi-line scripts are supported. Example: 'local part = Instance.new(\"Part\", workspace); part.Size = Vector3.new(4,1,2); return part.Name'"
-                    },
-                    "mode": {
-                        "type": "string",
-                        "enum": ["module", "command"],
-                        "description": "Execution mode (default: module)"
-                    },
-                    "allowInPlay": {
-                        "type": "boolean",
-                        "description": "Allow execution during a playtest session (default: false). Usually you should use studio-test_script instead."
-                    },
-                    "captureLogsMs": {
-                        "type": "number",
-                        "description": "Milliseconds to capture log output after execution (default: 0). Set to e.g. 500 to capture async print() output."
-                    }
-                },
-                "required": ["code"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-checkpoint_begin".into(),
-            description: Some("Start a named ChangeHistoryService checkpoint to track modifications you're about to make. Always call this BEFORE making changes you might want to undo later. Returns a checkpointId that you MUST save and pass to studio-checkpoint_end to commit the changes. Typical workflow: checkpoint_begin → run_script (make changes) → checkpoint_end.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "name": {
-                        "type": "string",
-                        "description": "Descriptive name for this checkpoint. Will appear in Studio's undo history. Be specific about what changes you're making. Example: 'Create 10 test parts' or 'Modify lighting settings'"
-                    }
-                },
-                "required": ["name"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-checkpoint_end".into(),
-            description: Some("Commit and finalize a checkpoint started with studio-checkpoint_begin. This makes the recorded changes available for undo in Studio's history. You MUST provide the checkpointId returned from the begin call. Always call this after completing your modifications - uncommitted checkpoints cannot be undone.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "checkpointId": {
-                        "type": "string",
-                        "description": "The unique checkpoint ID returned from studio-checkpoint_begin. Required to commit the correct checkpoint."
-                    },
-                    "commitMessage": {
-                        "type": "string",
-                        "description": "Optional commit description for the undo history"
-                    }
-                },
-                "required": ["checkpointId"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-checkpoint_undo".into(),
-            description: Some("Undo the most recent committed checkpoint in Studio's ChangeHistory. Reverts all changes made in the last checkpoint operation. Works the same as Edit → Undo in Studio. Multiple calls will undo multiple checkpoints sequentially. Cannot undo past the current session start.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "checkpointId": {
-                        "type": "string",
-                        "description": "Optional: specific checkpoint to undo to. If omitted, undoes the most recent checkpoint."
-                    }
+/// Run `f` over every text block of a tool result, replacing the text when it changes.
+fn rewrite_result_text<F>(response: &mut JsonRpcResponse, f: F)
+where
+    F: for<'a> Fn(&'a str) -> Cow<'a, str>,
+{
+    if let Some(result) = response.result.as_mut() {
+        rewrite_text_blocks(result, f);
+    }
+}
+
+fn rewrite_text_blocks<F>(result: &mut Value, f: F)
+where
+    F: for<'a> Fn(&'a str) -> Cow<'a, str>,
+{
+    let Some(content) = result.get_mut("content").and_then(|c| c.as_array_mut()) else {
+        return;
+    };
+    for block in content {
+        if let Some(Value::String(text)) = block.get_mut("text") {
+            if let Cow::Owned(rewritten) = f(text) {
+                *text = rewritten;
+            }
+        }
+    }
+}
+
+async fn dispatch_tool_call(
+    state: &SharedState,
+    session: &SessionState,
+    id: Value,
+    mut params: Value,
+) -> JsonRpcResponse {
+    let tool_name = match params.get("name").and_then(|v| v.as_str()) {
+        Some(n) => n.to_string(),
+        None => {
+            return JsonRpcResponse::error(id, -32602, "Missing 'name' in tools/call params");
+        }
+    };
+    let mut arguments = params
+        .get_mut("arguments")
+        .map(Value::take)
+        .unwrap_or(json!({}));
+
+    if !state.experimental_tools_enabled() && EXPERIMENTAL_TOOLS.contains(&tool_name) {
+        let result = McpToolResult::error_text(format!(
+            "{tool_name} is experimental and disabled on this server (YIPPIE_DISABLE_EXPERIMENTAL_TOOLS)"
+        ));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    if let Some(reason) = disabled_reason(state, &tool_name) {
+        return JsonRpcResponse::success(id, McpToolResult::error_text(reason).to_value());
+    }
+
+    // Read once: a profile switch during the call doesn't change how it runs
+    let settings = state.settings();
+    if settings.read_only
+        && !tool_annotations(&tool_name).read_only_hint
+        && !matches!(tool_name.as_str(), "studio-status" | "studio-set_profile" | "studio-cancel")
+    {
+        let result = McpToolResult::error_text(format!(
+            "{tool_name} is refused: the active profile '{}' is read-only. Only read-only tools are allowed until the profile is switched (studio-set_profile).",
+            settings.name()
+        ));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    // Anything that may change Studio makes cached results stale
+    if !tool_annotations(&tool_name).read_only_hint {
+        state.invalidate_response_cache();
+        state.script_sources().clear();
+    }
+
+    // studio-status can be answered directly by the server
+    if tool_name == "studio-status" {
+        return handle_status_tool(state, session, id).await;
+    }
+
+    // Tools that write files can put them in a directory inside the client's roots
+    let mut output_dir = None;
+    if OUTPUT_DIR_TOOLS.contains(&tool_name.as_str()) {
+        if let Some(requested) = arguments.as_object_mut().and_then(|a| a.remove("outputDir")) {
+            let resolved = match (requested.as_str(), session.roots().get().await) {
+                (None, _) => Err("'outputDir' must be a string".to_string()),
+                (Some(requested), Ok(roots)) => output_dir::resolve(requested, &roots),
+                (Some(requested), Err(e)) => Err(format!(
+                    "{}: outputDir '{requested}' can't be checked: {e}",
+                    output_dir::PERMISSION_DENIED
+                )),
+            };
+            match resolved {
+                Ok(dir) => output_dir = Some(dir),
+                Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+            }
+        }
+    }
+
+    // OS screenshots are taken by the server itself, no plugin round-trip
+    if tool_name == "studio-screenshot_region" {
+        return handle_screenshot_region_tool(state, id, &arguments, output_dir.as_deref()).await;
+    }
+
+    if tool_name == "studio-capture_viewport_via_render" {
+        return handle_viewport_render_tool(state, id, &arguments, output_dir.as_deref(), settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-annotate_capture" {
+        return handle_annotate_capture_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-list_tools_with_routing" {
+        return handle_list_tools_with_routing(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-capture_compare" {
+        return handle_capture_compare_tool(state, id, &arguments, output_dir.as_deref()).await;
+    }
+
+    if tool_name == "studio-time_report" {
+        return handle_time_report_tool(state, id, &arguments, output_dir.as_deref()).await;
+    }
+
+    // Checkpoints whose recording Studio already dropped can't be ended; say so
+    // instead of letting the plugin answer "Unknown checkpointId"
+    if tool_name == "studio-checkpoint_end" {
+        if let Some(message) = lost_checkpoint_error(state, &arguments).await {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+
+    if tool_name == "studio-checkpoint_abandon" {
+        return handle_checkpoint_abandon_tool(state, id, &arguments).await;
+    }
+
+    // Variables are kept by the server
+    match tool_name.as_str() {
+        "studio-var_set" => return handle_var_set_tool(state, session, id, &arguments),
+        "studio-var_get" => return handle_var_get_tool(state, session, id, &arguments),
+        "studio-var_list" => return handle_var_list_tool(state, session, id, &arguments),
+        _ => {}
+    }
+
+    // The plugin keeps one log subscription for every session, so a session leaving
+    // while others still listen only drops its own
+    if tool_name == "studio-logs_unsubscribe" && state.logs_subscribed_elsewhere(&session.id) {
+        session.activity().set_log_subscription(None);
+        tracing::info!(session = %session.id, "Log subscription dropped for this session only");
+        let result = json!({ "ok": true, "subscribedElsewhere": true });
+        let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+        return JsonRpcResponse::success(id, McpToolResult::text(text).to_value());
+    }
+
+    // Replays run each step back through this dispatcher
+    if tool_name == "studio-replay" {
+        return handle_tool_replay_tool(state, session, id, &arguments).await;
+    }
+
+    // Transactions also re-enter the dispatcher, inside one checkpoint
+    if tool_name == "studio-transaction" {
+        return handle_transaction_tool(state, session, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-test_matrix" {
+        return handle_test_matrix_tool(state, id, &arguments, output_dir.as_deref()).await;
+    }
+
+    if let Err(message) = check_requirements(state, &tool_name).await {
+        tracing::info!(tool = %tool_name, "Rejected without a plugin round-trip: {message}");
+        return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+    }
+
+    // Input recordings are stored and replayed by the server around bridge calls
+    match tool_name.as_str() {
+        "studio-virtualuser_record_start" => return handle_record_start_tool(state, id, &arguments).await,
+        "studio-virtualuser_record_stop" => return handle_record_stop_tool(state, id).await,
+        "studio-virtualuser_replay" => return handle_replay_tool(state, id, &arguments).await,
+        "studio-virtualuser_sequence" => {
+            let result = match call_sequence(state, arguments).await {
+                Ok(value) => McpToolResult::text(plugin_result_text(value)),
+                Err(message) => McpToolResult::error_text(message),
+            };
+            return JsonRpcResponse::success(id, result.to_value());
+        }
+        _ => {}
+    }
+
+    if tool_name == "studio-get_output_since" {
+        return handle_get_output_since_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-get_errors_grouped" {
+        return handle_get_errors_grouped_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-get_tool_history" {
+        return handle_get_tool_history_tool(state, id, &arguments);
+    }
+
+    // Async calls are tracked by the server
+    match tool_name.as_str() {
+        "studio-cancel" => return handle_cancel_tool(state, id, &arguments).await,
+        "studio-get_operation" | "studio-wait_operation" => {
+            return handle_operation_tool(state, id, &tool_name, &arguments).await
+        }
+        _ => {}
+    }
+
+    if tool_name == "studio-logs_search_archive" {
+        return handle_logs_search_archive_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-logs_diff_sessions" {
+        return handle_logs_diff_sessions_tool(state, id, &arguments, output_dir.as_deref()).await;
+    }
+
+    if tool_name == "studio-get_version" {
+        return handle_get_version_tool(state, id).await;
+    }
+
+    if tool_name == "studio-get_keybinds" {
+        return handle_get_keybinds_tool(state, id).await;
+    }
+
+    if tool_name == "studio-set_collision_group" {
+        return handle_set_collision_group_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-set_studio_setting" {
+        return handle_set_studio_setting_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-set_runtime_flag" {
+        return handle_set_runtime_flag_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-is_tweenable" {
+        return handle_is_tweenable_tool(state, id, &arguments).await;
+    }
+
+    if tool_name == "studio-batch_get_properties" {
+        return handle_batch_get_properties_tool(state, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-diff_places" {
+        return handle_diff_places_tool(state, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-place_stats" {
+        return handle_place_stats_tool(state, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-gui_tree" {
+        return handle_gui_tree_tool(state, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-gui_click" {
+        return handle_gui_click_tool(state, id, &arguments, settings.tool_timeout).await;
+    }
+
+    if tool_name == "studio-set_profile" {
+        return handle_set_profile_tool(state, id, &arguments);
+    }
+
+    if tool_name == "studio-playtest_wait_ready" {
+        return handle_playtest_wait_ready_tool(state, id, &arguments).await;
+    }
+
+    let _starting = matches!(tool_name.as_str(), "studio-playtest_play" | "studio-playtest_run")
+        .then(|| state.begin_playtest_start());
+    let playtest_wait = match take_playtest_wait(&tool_name, &mut arguments) {
+        Ok(wait) => wait,
+        Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+    };
+    if tool_name == "studio-run_script" {
+        if let Err(message) = clamp_script_timeout(&mut arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-wait_for_condition" {
+        if let Err(message) = clamp_condition_timing(&mut arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-trigger_proximity_prompt" {
+        if let Err(message) = check_prompt_target(state, &mut arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-get_replication_stats" {
+        if let Err(message) = clamp_replication_window(&mut arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-click_gui_button" {
+        if let Err(message) = check_gui_button_target(state, &arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-open_script" {
+        if let Err(message) = check_script_target(state, &arguments) {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+    if tool_name == "studio-get_gui_tree" {
+        if let Some(root) = arguments.get("root").and_then(|v| v.as_str()) {
+            if let Err(message) = starter_gui_path(root) {
+                return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+            }
+        }
+    }
+    let expanded = match expand_code_variables(state, session, &tool_name, &mut arguments) {
+        Ok(expanded) => expanded,
+        Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+    };
+    let run_async = match take_async_flag(&tool_name, &mut arguments) {
+        Ok(run_async) => run_async,
+        Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+    };
+    let minify_report = minify_code_argument(&settings, &tool_name, &mut arguments);
+    let timeout = tool_timeout(&tool_name, &arguments, settings.tool_timeout);
+    let window = match tool_name.as_str() {
+        "studio-test_script" => Some(partial_result::CallWindow::open(state).await),
+        _ => None,
+    };
+    let started = tokio::time::Instant::now();
+    let with_tracebacks = state.traceback_context_enabled() && traceback::TRACEBACK_TOOLS.contains(&tool_name.as_str());
+    // Applied to the session once the plugin has confirmed the change
+    let log_subscription = match tool_name.as_str() {
+        "studio-logs_subscribe" => Some(Some(LogSubscription { levels: arguments.get("levels").cloned() })),
+        "studio-logs_unsubscribe" => Some(None),
+        _ => None,
+    };
+    let call = match start_plugin_call(state, &tool_name, arguments).await {
+        Ok(call) => call,
+        Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+    };
+    let completion = Completion {
+        tool_name,
+        timeout,
+        started,
+        window,
+        minify_report,
+        expanded,
+        with_tracebacks,
+        playtest_wait,
+    };
+    if run_async {
+        return start_async_call(state, id, call, completion);
+    }
+    let result = completion.finish(state, call).await;
+    if let Some(subscription) = log_subscription.filter(|_| result["isError"] != true) {
+        session.activity().set_log_subscription(subscription);
+    }
+    JsonRpcResponse::success(id, result)
+}
+
+/// What turns a plugin response into the tool result, kept while the call runs.
+struct Completion {
+    tool_name: String,
+    timeout: Duration,
+    /// Tokio's clock, like the wait it is compared against.
+    started: tokio::time::Instant,
+    window: Option<partial_result::CallWindow>,
+    minify_report: Option<luau_minify::MinifyReport>,
+    expanded: Option<Vec<String>>,
+    with_tracebacks: bool,
+    playtest_wait: Option<(Readiness, Duration)>,
+}
+
+impl Completion {
+    /// Wait for the plugin and build the tool result from its response.
+    async fn finish(self, state: &SharedState, call: PluginCall) -> Value {
+        let Completion { tool_name, timeout, started, window, minify_report, expanded, with_tracebacks, playtest_wait } = self;
+        let result = match call.wait(state, timeout).await {
+            Ok(mut value) => {
+                if let (Some(report), Some(obj)) = (minify_report, value.as_object_mut()) {
+                    obj.insert("minify".into(), json!(report));
                 }
-            }),
-        },
-        McpToolDef {
-            name: "studio-playtest_play".into(),
-            description: Some("Start a Play mode playtest session - simulates both client and server like pressing F5 in Studio. Use this when you need to test player-facing features: character movement, UI, camera controls, localscripts, or anything requiring a player character. The local player spawns and can be controlled with studio-virtualuser_* tools. Use studio-playtest_run instead for server-only testing without a player character, or studio-test_script for quick one-off tests.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {}
-            }),
-        },
-        McpToolDef {
-            name: "studio-playtest_run".into(),
-            description: Some("Start a Run mode playtest session - server-only simulation like pressing F8 in Studio. Use this for testing server scripts, game logic, and systems that don't require a player character. No local player spawns, making it faster than Play mode. Use studio-playtest_play if you need to test player interactions or client-side features, or studio-test_script for quick one-off tests.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {}
-            }),
-        },
-        McpToolDef {
-            name: "studio-playtest_stop".into(),
-            description: Some("Stop the currently active playtest and return Studio to edit mode. Works for both Play mode (F5) and Run mode (F8) playtests. Always call this when you're done testing to free up resources and allow edit-mode script execution again. Automatically called by studio-test_script, but required when manually starting playtests with studio-playtest_play or studio-playtest_run.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "sessionId": {
-                        "type": "string",
-                        "description": "Optional session ID to stop. If omitted, stops the current active playtest."
-                    }
+                if let (Some(names), Some(obj)) = (expanded, value.as_object_mut()) {
+                    obj.insert("variables".into(), json!(names));
                 }
-            }),
-        },
-        McpToolDef {
-            name: "studio-test_script".into(),
-            description: Some("Execute Luau code inside a live playtest environment to test game logic, physics, character movement, Players service, or any runtime behavior. Automatically starts a playtest, runs your code in the game server, captures all logs and errors, stops the playtest, and returns results. Use this instead of studio-run_script when testing gameplay features, server scripts, or anything requiring a running game. Cannot modify the place structure - use studio-run_script for that. Returns: success (bool), value (return value), error (if failed), logs (all captured output), errors (warnings/errors only), duration (seconds).".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "code": {
-                        "type": "string",
-                        "description": "Luau code to execute during playtest. Runs in server context. Can access running game services like Players, RunService, ReplicatedStorage. Use print() for debugging output. Example: 'local players = game.Players:GetPlayers(); print(#players .. \" players in game\"); return workspace.Gravity'"
-                    },
-                    "mode": {
-                        "type": "string",
-                        "enum": ["run", "play"],
-                        "description": "Playtest mode: 'run' (server only, faster, no player character) or 'play' (client+server, player spawns). Default: 'run'"
-                    },
-                    "timeout": {
-                        "type": "number",
-                        "description": "Max seconds to wait for the test to complete before force-stopping. Default: 30. Increase for long-running tests."
-                    }
-                },
-                "required": ["code"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-logs_subscribe".into(),
-            description: Some("Subscribe to real-time Studio log output to capture print() statements, errors, and warnings from scripts. Must be called before studio-logs_get will return any data. Logs are buffered in memory until you unsubscribe. Use includeHistory: true to receive logs from before subscription. Essential for debugging script execution. Always unsubscribe when finished to prevent memory buildup.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "channels": {
-                        "type": "array",
-                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
-                        "description": "Log levels to subscribe to (default: all). Filter to specific levels to reduce noise."
-                    },
-                    "includeHistory": {
-                        "type": "boolean",
-                        "description": "Whether to include logs generated before subscribing (default: true). Set to true if you need to see output from scripts that ran earlier in the session."
-                    },
-                    "maxHistory": {
-                        "type": "number",
-                        "description": "Max history entries to return (default: 200). Historical buffer is limited."
+                if with_tracebacks {
+                    traceback::enrich_result(state, &mut value).await;
+                }
+                // Held until the player or character is there (playtest_play waitFor)
+                if let Some((level, wait)) = playtest_wait {
+                    match wait_for_readiness(state, level, wait).await {
+                        Ok(report) => {
+                            if let Some(obj) = value.as_object_mut() {
+                                obj.insert("ready".into(), report);
+                            }
+                        }
+                        Err(message) => {
+                            let message = format!("{message} The playtest did start; wait again with studio-playtest_wait_ready or stop it with studio-playtest_stop.");
+                            return McpToolResult::error_text(message).to_value();
+                        }
                     }
                 }
-            }),
-        },
-        McpToolDef {
-            name: "studio-logs_unsubscribe".into(),
-            description: Some("Stop receiving log output and clear the log buffer. Call this when you're done monitoring logs to free up memory. After unsubscribing, studio-logs_get will fail until you subscribe again. Safe to call even if not subscribed.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {},
-                "additionalProperties": false
-            }),
-        },
-        McpToolDef {
-            name: "studio-logs_get".into(),
-            description: Some("Fetch buffered log entries that have accumulated since subscribing with studio-logs_subscribe. Returns all captured print() output, errors, and warnings. Requires an active subscription - call studio-logs_subscribe first. Logs are cleared from the buffer after retrieval.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "sinceSeq": {
-                        "type": "number",
-                        "description": "Return only logs after this sequence number. Use to paginate or avoid re-reading old entries."
-                    },
-                    "limit": {
-                        "type": "number",
-                        "description": "Max entries to return (default: 200)"
-                    },
-                    "levels": {
-                        "type": "array",
-                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
-                        "description": "Filter by log level. Omit to get all levels."
-                    }
+                McpToolResult::text(plugin_result_text(value))
+            }
+            // A test that outran the wait still left its logs behind; return those
+            Err(message) => match window.filter(|_| started.elapsed() >= timeout) {
+                Some(window) => {
+                    tracing::warn!(tool = %tool_name, "Returning a partial result after the timeout");
+                    let partial = partial_result::timed_out_test(state, &window, timeout).await;
+                    let text = serde_json::to_string_pretty(&partial).unwrap_or_default();
+                    let mut result = McpToolResult::text(text).to_value();
+                    result["structuredContent"] = partial;
+                    return result;
                 }
-            }),
-        },
-        McpToolDef {
-            name: "studio-virtualuser_key".into(),
-            description: Some("Simulate keyboard input for the player character during Play mode playtest (F5). Control character movement (W/A/S/D), jumping (Space), and sprinting (LeftShift/RightShift). Keys stay held until explicitly released with action 'up'. Use 'down' to start holding a key, do other things, then 'up' to release. Space triggers a single jump. Only works during Play mode with a spawned character. Requires studio-playtest_play to be called first.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "keyCode": {
-                        "type": "string",
-                        "enum": ["W", "A", "S", "D", "Space", "LeftShift", "RightShift"],
-                        "description": "Keyboard key to simulate. W=forward, A=left, S=backward, D=right, Space=jump, LeftShift/RightShift=sprint."
-                    },
-                    "action": {
-                        "type": "string",
-                        "enum": ["down", "up"],
-                        "description": "'down' = start holding key (default), 'up' = release key. Keys stay held until released. For jumping, just send 'down' once."
-                    }
-                },
-                "required": ["keyCode"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-virtualuser_mouse_button".into(),
-            description: Some("Simulate mouse click at the player's position during Play mode. Performs a raycast from the character's head toward a world position or named instance to detect and interact with world objects. Reports what was hit (instance name, class, position, distance, material) and detects interactive elements (ClickDetectors, ProximityPrompts). Only works during Play mode (F5) with a spawned character.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "button": {
-                        "type": "integer",
-                        "enum": [1, 2],
-                        "description": "Mouse button number. 1=left click (primary), 2=right click (secondary). Most interactions use button 1."
-                    },
-                    "action": {
-                        "type": "string",
-                        "enum": ["click"],
-                        "description": "Action type. Currently only 'click' is supported."
-                    },
-                    "worldPosition": {
-                        "type": "object",
-                        "properties": {
-                            "x": { "type": "number" },
-                            "y": { "type": "number" },
-                            "z": { "type": "number" }
-                        },
-                        "required": ["x", "y", "z"],
-                        "description": "World-space position to raycast toward from the character's head. Provide this OR target."
-                    },
-                    "target": {
-                        "type": "string",
-                        "description": "Instance path to target (e.g. 'Workspace.MyPart'). If it's a BasePart and no worldPosition given, its position is used. Provide this OR worldPosition."
-                    }
-                },
-                "required": ["button", "action"]
-            }),
+                None if with_tracebacks => McpToolResult::error_text(traceback::enrich_error(state, message).await),
+                None => McpToolResult::error_text(message),
+            },
+        };
+        result.to_value()
+    }
+}
+
+/// Take the `async` argument of a long tool out of the call, since the plugin
+/// doesn't know it.
+fn take_async_flag(tool_name: &str, arguments: &mut Value) -> Result<bool, String> {
+    if !async_calls::ASYNC_TOOLS.contains(&tool_name) {
+        return Ok(false);
+    }
+    match arguments.as_object_mut().and_then(|a| a.remove("async")) {
+        None | Some(Value::Null) => Ok(false),
+        Some(Value::Bool(run_async)) => Ok(run_async),
+        Some(_) => Err("'async' must be a boolean".into()),
+    }
+}
+
+/// Answer an `async: true` call with its operation id and finish it in the background,
+/// storing the result for studio-get_operation and studio-wait_operation.
+fn start_async_call(state: &SharedState, id: Value, call: PluginCall, completion: Completion) -> JsonRpcResponse {
+    let request_id = call.request_id.clone();
+    let report = json!({
+        "id": request_id,
+        "tool": completion.tool_name,
+        "status": async_calls::CallStatus::Running,
+        "timeoutSecs": completion.timeout.as_secs(),
+        "hint": "Poll or wait for the result with studio-get_operation / studio-wait_operation, or stop the call with studio-cancel; all three take this id.",
+    });
+    let started_at = state.clock().now().to_rfc3339();
+    state
+        .async_calls()
+        .start(request_id.clone(), &completion.tool_name, call.client_id.clone(), started_at);
+    tracing::info!(tool = %completion.tool_name, request_id = %request_id, "Running tool call in the background");
+    let state = state.clone();
+    tokio::spawn(async move {
+        let mut result = completion.finish(&state, call).await;
+        // Stored results are read back whole by studio-get_operation and friends, so
+        // they are redacted once here rather than wherever they are shown
+        redact_result(&state, &mut result);
+        state.async_calls().finish(&request_id, result);
+    });
+    let text = serde_json::to_string_pretty(&report).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+/// Stop waiting for an async call, take it out of the queue if the plugin hasn't
+/// pulled it yet, and otherwise ask the plugin to stop it.
+async fn handle_cancel_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let Some(request_id) = arguments.get("id").and_then(|v| v.as_str()) else {
+        return error("Missing 'id' argument (the id a call with async: true returned)".into());
+    };
+    let cancelled = match state.async_calls().cancel(request_id) {
+        Ok(cancelled) => cancelled,
+        Err(message) => return error(message),
+    };
+    tracing::info!(tool = %cancelled.tool, request_id = %request_id, "Cancelling tool call");
+    // The task waiting for the call finishes now; a late plugin response is dropped
+    state
+        .resolve_pending(
+            request_id,
+            BridgeToolResponse {
+                request_id: request_id.to_string(),
+                success: false,
+                result: None,
+                error: Some(format!("Cancelled by studio-cancel after {} ms", cancelled.elapsed_ms)),
+            },
+        )
+        .await;
+    let plugin = if state.withdraw_request(&cancelled.client_id, request_id).await {
+        json!({ "stopped": true, "note": "Studio hadn't picked the call up yet; it was taken out of the queue" })
+    } else {
+        cancel_in_plugin(state, &cancelled.client_id, request_id).await
+    };
+    let report = json!({
+        "id": request_id,
+        "tool": cancelled.tool,
+        "cancelled": true,
+        "elapsedMs": cancelled.elapsed_ms,
+        "plugin": plugin,
+    });
+    let text = serde_json::to_string_pretty(&report).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+/// Ask the client that runs a call to cancel its thread, and report what it said.
+async fn cancel_in_plugin(state: &SharedState, client_id: &str, request_id: &str) -> Value {
+    let cancel_id = state.next_id();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    state.register_pending(cancel_id.clone(), tx).await;
+    let request = BridgeToolRequest {
+        request_id: cancel_id.clone(),
+        tool_name: "studio-cancel".into(),
+        arguments: json!({ "requestId": request_id }),
+    };
+    if !state.enqueue_for_client(client_id, request, Priority::Interactive).await {
+        state.remove_pending(&cancel_id).await;
+        return json!({ "stopped": false, "note": "The client that ran the call has disconnected" });
+    }
+    match tokio::time::timeout(CANCEL_ACK_TIMEOUT, rx).await {
+        Ok(Ok(response)) if response.success => response.result.unwrap_or(Value::Null),
+        Ok(Ok(response)) => json!({
+            "stopped": false,
+            "note": response.error.unwrap_or_else(|| "Unknown plugin error".into()),
+        }),
+        _ => {
+            state.remove_pending(&cancel_id).await;
+            json!({
+                "stopped": false,
+                "note": format!("Studio didn't confirm within {}s; the call may still run there, but its result is discarded", CANCEL_ACK_TIMEOUT.as_secs()),
+            })
+        }
+    }
+}
+
+/// studio-get_operation and studio-wait_operation: an async call's state, with its
+/// result once finished. wait_operation first waits for it up to `timeoutMs`.
+async fn handle_operation_tool(state: &SharedState, id: Value, tool_name: &str, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let Some(request_id) = arguments.get("id").and_then(|v| v.as_str()) else {
+        return error("Missing 'id' argument (the id a call with async: true returned)".into());
+    };
+    if tool_name == "studio-wait_operation" {
+        let wait_ms = match async_wait_ms(arguments, "timeoutMs", async_calls::DEFAULT_OPERATION_WAIT_MS) {
+            Ok(wait_ms) => wait_ms,
+            Err(message) => return error(message),
+        };
+        wait_for_async_call(state, request_id, wait_ms).await;
+    }
+    let mut operation = match state.async_calls().operation(request_id) {
+        Ok(operation) => operation,
+        Err(message) => return error(message),
+    };
+    if tool_name == "studio-wait_operation" {
+        operation["timedOut"] = json!(operation["status"] == json!(async_calls::CallStatus::Running));
+    }
+    let mut result = McpToolResult::text(serde_json::to_string_pretty(&operation).unwrap_or_default()).to_value();
+    result["structuredContent"] = operation;
+    JsonRpcResponse::success(id, result)
+}
+
+/// A wait in milliseconds for an async call, capped at `MAX_RESULT_WAIT_MS`.
+fn async_wait_ms(arguments: &Value, name: &str, default: u64) -> Result<u64, String> {
+    match arguments.get(name) {
+        None | Some(Value::Null) => Ok(default),
+        Some(raw) => raw
+            .as_f64()
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+            .map(|ms| (ms.round() as u64).min(async_calls::MAX_RESULT_WAIT_MS))
+            .ok_or_else(|| format!("'{name}' must be a non-negative number of milliseconds")),
+    }
+}
+
+/// Wait until an async call is no longer running, or `wait_ms` has passed.
+async fn wait_for_async_call(state: &SharedState, request_id: &str, wait_ms: u64) {
+    let deadline = tokio::time::Instant::now() + Duration::from_millis(wait_ms);
+    while state.async_calls().status(request_id) == Some(async_calls::CallStatus::Running)
+        && tokio::time::Instant::now() < deadline
+    {
+        tokio::time::sleep(OPERATION_POLL_INTERVAL).await;
+    }
+}
+
+/// Validate run_script `scriptTimeoutMs` and clamp it to the supported range in place.
+fn clamp_script_timeout(arguments: &mut Value) -> Result<(), String> {
+    let Some(raw) = arguments.get("scriptTimeoutMs") else {
+        return Ok(());
+    };
+    if raw.is_null() {
+        return Ok(());
+    }
+    let ms = raw
+        .as_f64()
+        .filter(|ms| ms.is_finite() && *ms > 0.0)
+        .ok_or("'scriptTimeoutMs' must be a positive number of milliseconds")?;
+    let clamped = (ms.round() as u64).clamp(MIN_SCRIPT_TIMEOUT_MS, MAX_SCRIPT_TIMEOUT_MS);
+    if clamped as f64 != ms {
+        tracing::debug!(requested = ms, clamped, "Clamped scriptTimeoutMs");
+    }
+    arguments["scriptTimeoutMs"] = json!(clamped);
+    Ok(())
+}
+
+/// Validate wait_for_condition `predicate`, `timeoutMs` and `intervalMs`, filling in
+/// the defaults and clamping both durations in place.
+fn clamp_condition_timing(arguments: &mut Value) -> Result<(), String> {
+    let predicate = arguments.get("predicate").and_then(|v| v.as_str()).unwrap_or("");
+    if predicate.trim().is_empty() {
+        return Err("Missing 'predicate' argument (a Luau expression, e.g. 'workspace:FindFirstChild(\"Door\") == nil')".into());
+    }
+    let read_ms = |key: &str, default: u64, min: u64, max: u64| -> Result<u64, String> {
+        let ms = match arguments.get(key) {
+            None | Some(Value::Null) => return Ok(default),
+            Some(raw) => raw
+                .as_f64()
+                .filter(|ms| ms.is_finite() && *ms >= 0.0)
+                .ok_or(format!("'{key}' must be a non-negative number of milliseconds"))?,
+        };
+        let clamped = (ms.round() as u64).clamp(min, max);
+        if clamped as f64 != ms {
+            tracing::debug!(key, requested = ms, clamped, "Clamped wait_for_condition timing");
+        }
+        Ok(clamped)
+    };
+    let timeout_ms = read_ms("timeoutMs", DEFAULT_CONDITION_TIMEOUT_MS, MIN_CONDITION_TIMEOUT_MS, MAX_CONDITION_TIMEOUT_MS)?;
+    let interval_ms = read_ms(
+        "intervalMs",
+        DEFAULT_CONDITION_INTERVAL_MS,
+        MIN_CONDITION_INTERVAL_MS,
+        MAX_CONDITION_INTERVAL_MS,
+    )?
+    .min(timeout_ms);
+    arguments["timeoutMs"] = json!(timeout_ms);
+    arguments["intervalMs"] = json!(interval_ms);
+    Ok(())
+}
+
+/// Check a trigger_proximity_prompt call before it reaches the bridge: the path must
+/// be under Workspace and, when the path index knows it, name a ProximityPrompt.
+/// Paths the index doesn't have (created at runtime, or outside the indexed roots)
+/// are left to the bridge. Clamps `timeoutMs` in place.
+fn check_prompt_target(state: &SharedState, arguments: &mut Value) -> Result<(), String> {
+    let path = arguments
+        .get("path")
+        .and_then(|v| v.as_str())
+        .filter(|p| !p.is_empty())
+        .ok_or("Missing 'path' argument (the prompt's full name, as listed by studio-get_proximity_prompts)")?;
+    if !path.starts_with("Workspace.") {
+        return Err(format!(
+            "'{path}' is not under Workspace. ProximityPrompts only work in Workspace; use the path studio-get_proximity_prompts reports."
+        ));
+    }
+    let indexed_class = state
+        .path_index()
+        .and_then(|index| index.class_of(path).map(String::from));
+    if let Some(class_name) = indexed_class.filter(|c| c != "ProximityPrompt") {
+        return Err(format!(
+            "not_a_proximity_prompt: {path} is a {class_name}. Use studio-get_proximity_prompts to find the prompt under it."
+        ));
+    }
+
+    let timeout_ms = match arguments.get("timeoutMs").filter(|v| !v.is_null()) {
+        None => DEFAULT_PROMPT_TIMEOUT_MS,
+        Some(raw) => raw
+            .as_f64()
+            .filter(|ms| ms.is_finite() && *ms > 0.0)
+            .ok_or("'timeoutMs' must be a positive number of milliseconds")?
+            .round() as u64,
+    };
+    arguments["timeoutMs"] = json!(timeout_ms.clamp(MIN_PROMPT_TIMEOUT_MS, MAX_PROMPT_TIMEOUT_MS));
+    Ok(())
+}
+
+/// Fill in get_replication_stats `windowMs` and clamp it in place.
+fn clamp_replication_window(arguments: &mut Value) -> Result<(), String> {
+    let window_ms = match arguments.get("windowMs").filter(|v| !v.is_null()) {
+        None => DEFAULT_REPLICATION_WINDOW_MS,
+        Some(raw) => raw
+            .as_f64()
+            .filter(|ms| ms.is_finite() && *ms > 0.0)
+            .ok_or("'windowMs' must be a positive number of milliseconds")?
+            .round() as u64,
+    };
+    arguments["windowMs"] = json!(window_ms.clamp(MIN_REPLICATION_WINDOW_MS, MAX_REPLICATION_WINDOW_MS));
+    Ok(())
+}
+
+/// Concrete GuiButton classes.
+const GUI_BUTTON_CLASSES: &[&str] = &["TextButton", "ImageButton"];
+
+/// Check a click_gui_button call before it reaches the bridge: the path must be in
+/// StarterGui or a player's PlayerGui and, when the path index knows the StarterGui
+/// original, name a GuiButton. GUI built by scripts at runtime is left to the client.
+fn check_gui_button_target(state: &SharedState, arguments: &Value) -> Result<(), String> {
+    let path = arguments
+        .get("path")
+        .and_then(|v| v.as_str())
+        .filter(|p| !p.is_empty())
+        .ok_or("Missing 'path' argument (e.g. 'StarterGui.MainMenu.PlayButton')")?;
+    let starter_path = starter_gui_path(path)?;
+    let indexed_class = state
+        .path_index()
+        .and_then(|index| index.class_of(&starter_path).map(String::from));
+    if let Some(class_name) = indexed_class.filter(|c| !GUI_BUTTON_CLASSES.contains(&c.as_str())) {
+        return Err(format!(
+            "not_a_gui_button: {starter_path} is a {class_name}; only TextButton and ImageButton can be clicked"
+        ));
+    }
+    Ok(())
+}
+
+/// Classes the script editor opens.
+const SCRIPT_CLASSES: &[&str] = &["Script", "LocalScript", "ModuleScript"];
+
+/// Check an open_script call before it reaches the plugin: `line` must be a
+/// positive integer and, when the path index knows the path, it must name a script.
+fn check_script_target(state: &SharedState, arguments: &Value) -> Result<(), String> {
+    let path = arguments
+        .get("path")
+        .and_then(|v| v.as_str())
+        .filter(|p| !p.is_empty())
+        .ok_or("Missing 'path' argument (e.g. 'ServerScriptService.GameManager')")?;
+    if let Some(line) = arguments.get("line").filter(|v| !v.is_null()) {
+        if line.as_u64().is_none_or(|l| l < 1) {
+            return Err("'line' must be a positive integer".into());
+        }
+    }
+    let indexed_class = state
+        .path_index()
+        .and_then(|index| index.class_of(path).map(String::from));
+    if let Some(class_name) = indexed_class.filter(|c| !SCRIPT_CLASSES.contains(&c.as_str())) {
+        return Err(format!(
+            "not_a_script: {path} is a {class_name}, not a Script, LocalScript or ModuleScript"
+        ));
+    }
+    Ok(())
+}
+
+/// The StarterGui original of a player GUI path: `Players.<name>.PlayerGui.<rest>` is
+/// a copy of `StarterGui.<rest>`. Errors for paths outside both.
+fn starter_gui_path(path: &str) -> Result<String, String> {
+    match path.strip_prefix("Players.") {
+        Some(rest) => match rest.split_once(".PlayerGui.") {
+            Some((_, gui_path)) => Ok(format!("StarterGui.{gui_path}")),
+            None => Err(format!("'{path}' is not inside a PlayerGui")),
         },
-        McpToolDef {
-            name: "studio-virtualuser_move_mouse".into(),
-            description: Some("Set the player character's facing direction during Play mode by rotating the HumanoidRootPart to face toward a world position (horizontal rotation only). Use for controlling where the character looks, affecting camera angle and character rotation. Only works during Play mode (F5) with a spawned character. Requires studio-playtest_play to be called first.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "lookAt": {
-                        "type": "object",
-                        "properties": {
-                            "x": { "type": "number" },
-                            "y": { "type": "number" },
-                            "z": { "type": "number" }
-                        },
-                        "required": ["x", "y", "z"],
-                        "description": "World-space position to face toward. The character rotates horizontally to look at this point."
-                    }
-                },
-                "required": ["lookAt"]
-            }),
+        None if path.starts_with("StarterGui.") => Ok(path.to_string()),
+        None => Err(format!(
+            "'{path}' is not a GUI path. Use 'StarterGui.<ScreenGui>.<...>' or 'Players.<name>.PlayerGui.<ScreenGui>.<...>'."
+        )),
+    }
+}
+
+/// Expand `${var:name}` placeholders in the `code` argument of script tools. Returns
+/// the names used, if there were any placeholders.
+fn expand_code_variables(
+    state: &SharedState,
+    session: &SessionState,
+    tool_name: &str,
+    arguments: &mut Value,
+) -> Result<Option<Vec<String>>, String> {
+    if !variables::SCRIPT_TOOLS.contains(&tool_name) {
+        return Ok(None);
+    }
+    let Some(code) = arguments.get("code").and_then(|v| v.as_str()) else {
+        return Ok(None);
+    };
+    let now = std::time::Instant::now();
+    let store = state.variables();
+    let lookup = |name: &str| store.get(&session.id, None, name, now).map(|(_, value)| value.clone());
+    let Some(expansion) = variables::expand(code, lookup)? else {
+        return Ok(None);
+    };
+    tracing::debug!(tool = %tool_name, variables = ?expansion.variables, "Expanded variables in code argument");
+    arguments["code"] = Value::String(expansion.code);
+    Ok(Some(expansion.variables))
+}
+
+/// Minify the `code` argument of run_script/test_script when the call's `minify` flag
+/// (or the profile's `minifyCode`, `YIPPIE_MINIFY_CODE` by default) asks for it. The
+/// flag is removed before forwarding.
+fn minify_code_argument(
+    settings: &Settings,
+    tool_name: &str,
+    arguments: &mut Value,
+) -> Option<luau_minify::MinifyReport> {
+    if !matches!(tool_name, "studio-run_script" | "studio-test_script") {
+        return None;
+    }
+    let args = arguments.as_object_mut()?;
+    let requested = args
+        .remove("minify")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(settings.minify_code);
+    if !requested {
+        return None;
+    }
+    let code = args.get("code")?.as_str()?;
+    let (code, report) = luau_minify::minify_or_original(code);
+    match &report.skipped_reason {
+        None => tracing::debug!(
+            tool = %tool_name,
+            original = report.original_bytes,
+            minified = report.minified_bytes,
+            "Minified code argument"
+        ),
+        Some(reason) => tracing::info!(tool = %tool_name, "Sending code unminified: {reason}"),
+    }
+    args.insert("code".into(), Value::String(code));
+    Some(report)
+}
+
+/// What a tool needs from the Studio session to do anything useful.
+#[derive(Debug, Clone, Copy, Default)]
+struct ToolRequirements {
+    requires_playtest: bool,
+    /// Play mode (F5) specifically; Run mode (F8) has no player.
+    requires_play_mode: bool,
+    /// A spawned player character. Only the bridge can check this.
+    requires_character: bool,
+}
+
+fn tool_requirements(tool_name: &str) -> ToolRequirements {
+    match tool_name {
+        "studio-virtualuser_key"
+        | "studio-virtualuser_mouse_button"
+        | "studio-virtualuser_move_mouse"
+        | "studio-virtualuser_sequence"
+        | "studio-virtualuser_record_start"
+        | "studio-virtualuser_replay"
+        | "studio-get_humanoid_description"
+        | "studio-get_proximity_prompts"
+        | "studio-trigger_proximity_prompt" => ToolRequirements {
+            requires_playtest: true,
+            requires_play_mode: true,
+            requires_character: true,
         },
-        McpToolDef {
-            name: "studio-npc_driver_start".into(),
-            description: Some("Start controlling any NPC character (any Model with a Humanoid) during Play mode playtest. Enables AI-style control for testing NPC movement, pathfinding, and behavior. Returns a driverId you MUST use for subsequent studio-npc_driver_command and studio-npc_driver_stop calls. Multiple NPCs can be controlled simultaneously. Stop control with studio-npc_driver_stop when finished.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "target": {
-                        "type": "string",
-                        "description": "Full instance path to the NPC character model. Must contain a Humanoid. Example: 'Workspace.NPCModel' or 'Workspace.Enemies.Zombie1'. Case-sensitive."
-                    }
-                },
-                "required": ["target"]
-            }),
+        // Bindings and GUI live on the client, so Run mode (no player) can't answer
+        "studio-get_bindable_actions"
+        | "studio-click_gui_button"
+        | "studio-get_gui_tree"
+        | "studio-gui_tree"
+        | "studio-gui_click" => ToolRequirements {
+            requires_playtest: true,
+            requires_play_mode: true,
+            ..Default::default()
         },
-        McpToolDef {
-            name: "studio-npc_driver_command".into(),
-            description: Some("Send movement and behavior commands to an NPC being controlled by studio-npc_driver_start. Available commands: 'move_to' (navigate to world position), 'jump', 'wait' (pause for duration), 'set_walkspeed' (change movement speed), and 'look_at' (face a position). Commands execute synchronously - move_to blocks until the NPC arrives or times out. Only works during Play mode with an active driver.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "driverId": {
-                        "type": "string",
-                        "description": "Driver ID returned from studio-npc_driver_start. Required to identify which NPC to command."
-                    },
-                    "command": {
-                        "type": "object",
-                        "description": "Command to execute on the NPC.",
-                        "properties": {
-                            "type": {
-                                "type": "string",
-                                "enum": ["move_to", "jump", "wait", "set_walkspeed", "look_at"],
-                                "description": "Command type. 'move_to' navigates to position, 'jump' makes NPC jump, 'wait' pauses for ms duration, 'set_walkspeed' changes speed, 'look_at' rotates to face position."
-                            },
-                            "position": {
-                                "type": "object",
-                                "properties": {
-                                    "x": { "type": "number" },
-                                    "y": { "type": "number" },
-                                    "z": { "type": "number" }
-                                },
-                                "description": "Target world position for 'move_to' and 'look_at'. Example: {x: 10, y: 0, z: 20}"
-                            },
-                            "ms": {
-                                "type": "number",
-                                "description": "Duration in milliseconds for 'wait' command. Example: 2000 for 2 seconds."
-                            },
-                            "value": {
-                                "type": "number",
-                                "description": "Value for 'set_walkspeed'. Default Roblox character WalkSpeed is 16. Range: 0-100+."
-                            },
-                            "timeout": {
-                                "type": "number",
-                                "description": "Max seconds to wait for 'move_to' to complete before giving up (default: 15)."
-                            }
-                        },
-                        "required": ["type"]
-                    }
-                },
-                "required": ["driverId", "command"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-npc_driver_stop".into(),
-            description: Some("Stop controlling an NPC that was started with studio-npc_driver_start. Releases control, stops all movement, and clears any queued commands. The NPC will return to idle. Always call this when finished controlling an NPC to free up resources. Safe to call even if the NPC isn't being controlled.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "driverId": {
-                        "type": "string",
-                        "description": "Driver ID returned from studio-npc_driver_start. Identifies which NPC to stop controlling."
-                    }
-                },
-                "required": ["driverId"]
-            }),
-        },
-        McpToolDef {
-            name: "studio-capture_screenshot".into(),
-            description: Some("DISABLED - DO NOT USE. Capture a screenshot of the Studio viewport. Non-functional due to Roblox API limitations - CaptureService returns inaccessible rbxtemp:// URIs that cannot be extracted as files. Will return an error if called.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "tag": {
-                        "type": "string",
-                        "description": "Tag for this capture (e.g. 'after_jump', 'menu_open')"
-                    },
-                    "includeUI": {
-                        "type": "boolean",
-                        "description": "Include UI elements if supported"
-                    }
-                }
-            }),
-        },
-        McpToolDef {
-            name: "studio-capture_video_start".into(),
-            description: Some("DISABLED - DO NOT USE. Start recording video of Studio viewport. Non-functional - Roblox's CaptureService does not expose video recording APIs to plugins. Will return an error if called.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "tag": {
-                        "type": "string",
-                        "description": "Tag for this recording"
-                    },
-                    "maxSeconds": {
-                        "type": "number",
-                        "description": "Maximum recording duration in seconds (default: 10)"
+        "studio-npc_driver_start"
+        | "studio-npc_driver_command"
+        | "studio-npc_driver_stop"
+        | "studio-get_sound_playing"
+        | "studio-get_network_ownership"
+        | "studio-set_network_ownership"
+        | "studio-get_replication_stats" => {
+            ToolRequirements {
+                requires_playtest: true,
+                ..Default::default()
+            }
+        }
+        _ => ToolRequirements::default(),
+    }
+}
+
+/// Enforce the requirements the server can verify locally (playtest active, mode,
+/// character spawned) so calls that can't succeed fail without a plugin round-trip.
+/// While a playtest_play/run call is in flight the state event may not have arrived
+/// yet, and a character the bridge reports as not yet spawned is usually seconds
+/// away, so wait for either (up to `PLAYTEST_START_GRACE`) instead of failing.
+async fn check_requirements(state: &SharedState, tool_name: &str) -> Result<(), String> {
+    let requirements = tool_requirements(tool_name);
+    if !requirements.requires_playtest {
+        return Ok(());
+    }
+    let deadline = tokio::time::Instant::now() + PLAYTEST_START_GRACE;
+    loop {
+        // Register for wakeups before reading state so a change in between isn't missed
+        let changed = state.playtest_changed().notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+
+        let (active, _, mode) = state.playtest_info().await;
+        // A connected bridge means a playtest is running even if the state event was lost
+        let active = active || state.has_playtest_bridge().await;
+        let verdict = if !active {
+            let target = if requirements.requires_play_mode {
+                "a Play mode playtest"
+            } else {
+                "an active playtest"
+            };
+            Err(format!(
+                "playtest_required: {tool_name} needs {target}, and none is running. Start one with studio-playtest_play first, then retry."
+            ))
+        } else if requirements.requires_play_mode && mode.as_deref() == Some("run") {
+            let character = if requirements.requires_character {
+                " with a spawned character"
+            } else {
+                ""
+            };
+            Err(format!(
+                "play_mode_required: {tool_name} needs Play mode (F5){character}, but the running playtest is in Run mode (F8). Stop it with studio-playtest_stop and start one with studio-playtest_play."
+            ))
+        } else {
+            Ok(())
+        };
+        // Bridges that don't report readiness leave this to the bridge itself. Only
+        // worth waiting for when nothing else rules the call out (Run mode never spawns one)
+        let (readiness, _) = state.playtest_readiness().await;
+        let spawning = readiness
+            .filter(|r| verdict.is_ok() && requirements.requires_character && *r < Readiness::CharacterSpawned);
+        let verdict = match (verdict, spawning) {
+            (Ok(()), Some(readiness)) => Err(format!(
+                "character_not_spawned: {tool_name} needs the player's character, which hasn't spawned yet (readiness: {}). Wait for it with studio-playtest_wait_ready, then retry.",
+                readiness.as_str()
+            )),
+            (verdict, _) => verdict,
+        };
+
+        let waiting = state.playtest_start_in_flight() || spawning.is_some();
+        match verdict {
+            Err(_) if waiting && tokio::time::Instant::now() < deadline => {
+                let _ = tokio::time::timeout_at(deadline, changed).await;
+            }
+            other => return other,
+        }
+    }
+}
+
+/// Wait until the running playtest reaches `level`, for at most `timeout`. A
+/// playtest_play/run call still in flight counts as a playtest about to run.
+/// Returns the readiness report, or why the stage won't be reached in time.
+async fn wait_for_readiness(state: &SharedState, level: Readiness, timeout: Duration) -> Result<Value, String> {
+    let started = std::time::Instant::now();
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        // Register for wakeups before reading state so a change in between isn't missed
+        let changed = state.playtest_changed().notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+
+        let (active, session_id, mode) = state.playtest_info().await;
+        let (readiness, player) = state.playtest_readiness().await;
+        let reached = readiness.map(Readiness::as_str);
+        if readiness.is_some_and(|r| r >= level) {
+            return Ok(json!({
+                "ready": true,
+                "level": level.as_str(),
+                "reached": reached,
+                "sessionId": session_id,
+                "mode": mode,
+                "player": player,
+                "waitedMs": started.elapsed().as_millis() as u64,
+            }));
+        }
+        let active = active || state.has_playtest_bridge().await;
+        if !active && !state.playtest_start_in_flight() {
+            return Err(format!(
+                "playtest_required: no playtest is running to reach {}. Start one with studio-playtest_play first.",
+                level.as_str()
+            ));
+        }
+        if level.needs_player() && mode.as_deref() == Some("run") {
+            return Err(format!(
+                "play_mode_required: {} needs a player, and the running playtest is in Run mode (F8), which has none. Wait for serverStarted instead, or use studio-playtest_play.",
+                level.as_str()
+            ));
+        }
+        if tokio::time::timeout_at(deadline, changed).await.is_err() {
+            return Err(format!(
+                "playtest_not_ready: the playtest didn't reach {} within {}ms (reached: {}). Check the logs (studio-logs_get) for errors during startup.",
+                level.as_str(),
+                timeout.as_millis(),
+                reached.unwrap_or("nothing reported yet")
+            ));
+        }
+    }
+}
+
+async fn handle_playtest_wait_ready_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let level = match readiness::parse_level(arguments.get("level"), "level") {
+        Ok(level) => level,
+        Err(message) => return error(message),
+    };
+    let timeout_ms = match readiness::parse_timeout(arguments.get("timeoutMs"), "timeoutMs") {
+        Ok(ms) => ms,
+        Err(message) => return error(message),
+    };
+    match wait_for_readiness(state, level, Duration::from_millis(timeout_ms)).await {
+        Ok(report) => JsonRpcResponse::success(id, McpToolResult::text(plugin_result_text(report)).to_value()),
+        Err(message) => error(message),
+    }
+}
+
+/// Take the `waitFor` and `waitTimeoutMs` arguments of playtest_play out of the
+/// call, since the plugin doesn't know them.
+fn take_playtest_wait(tool_name: &str, arguments: &mut Value) -> Result<Option<(Readiness, Duration)>, String> {
+    if tool_name != "studio-playtest_play" {
+        return Ok(None);
+    }
+    let Some(obj) = arguments.as_object_mut() else {
+        return Ok(None);
+    };
+    let wait_for = obj.remove("waitFor");
+    let timeout = obj.remove("waitTimeoutMs");
+    if wait_for.as_ref().is_none_or(Value::is_null) {
+        return match timeout.filter(|t| !t.is_null()) {
+            Some(_) => Err("'waitTimeoutMs' needs 'waitFor'".into()),
+            None => Ok(None),
+        };
+    }
+    let level = readiness::parse_level(wait_for.as_ref(), "waitFor")?;
+    let timeout_ms = readiness::parse_timeout(timeout.as_ref(), "waitTimeoutMs")?;
+    Ok(Some((level, Duration::from_millis(timeout_ms))))
+}
+
+/// Forward a tool call to the plugin (or playtest bridge) and wait for its response.
+/// On failure, returns the message to show the MCP client.
+pub(crate) async fn call_plugin(
+    state: &SharedState,
+    tool_name: &str,
+    arguments: Value,
+    timeout: Duration,
+) -> Result<Value, String> {
+    start_plugin_call(state, tool_name, arguments).await?.wait(state, timeout).await
+}
+
+/// A tool request queued for the plugin, with the pending call its response resolves.
+struct PluginCall {
+    request_id: String,
+    client_id: String,
+    tool_name: String,
+    rx: tokio::sync::oneshot::Receiver<BridgeToolResponse>,
+    tracked_arguments: Option<Value>,
+}
+
+/// Queue a tool call for the plugin without waiting for it.
+async fn start_plugin_call(state: &SharedState, tool_name: &str, arguments: Value) -> Result<PluginCall, String> {
+    // All plugin tools require a connected plugin
+    if !state.has_connected_client().await {
+        return Err("No Roblox Studio plugin connected. Install the plugin and click Connect.".into());
+    }
+
+    // Create oneshot channel for the response
+    let request_id = state.next_id();
+    let (tx, rx) = tokio::sync::oneshot::channel();
+
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        tracing::debug!(
+            tool = %tool_name,
+            request_id = %request_id,
+            arguments = %state.arguments_for_log(&arguments),
+            "Tool call arguments"
+        );
+    }
+
+    let tracked_arguments =
+        (is_input_tool(tool_name) || is_resource_tool(tool_name)).then(|| arguments.clone());
+    let bridge_request = BridgeToolRequest {
+        request_id: request_id.clone(),
+        tool_name: tool_name.to_string(),
+        arguments,
+    };
+
+    state.register_pending(request_id.clone(), tx).await;
+
+    if tool_name == "studio-test_script" {
+        let mode = bridge_request.arguments.get("mode").and_then(|m| m.as_str()).map(String::from);
+        let started_at = state.clock().now().to_rfc3339();
+        state.test_timings().open(request_id.clone(), std::time::Instant::now(), started_at, mode);
+    }
+
+    let priority = CALL_PRIORITY.try_with(|p| *p).unwrap_or(Priority::Background);
+    let Some(client_id) = state.enqueue_tool_request(bridge_request, priority).await else {
+        state.test_timings().discard(&request_id);
+        return Err("Failed to enqueue tool request to plugin".into());
+    };
+
+    tracing::info!(tool = %tool_name, request_id = %request_id, "Forwarding tool call to plugin");
+    Ok(PluginCall {
+        request_id,
+        client_id,
+        tool_name: tool_name.to_string(),
+        rx,
+        tracked_arguments,
+    })
+}
+
+impl PluginCall {
+    /// Await the plugin's response for up to `timeout`.
+    async fn wait(self, state: &SharedState, timeout: Duration) -> Result<Value, String> {
+        let PluginCall { request_id, client_id, tool_name, rx, tracked_arguments } = self;
+        let tool_name = tool_name.as_str();
+        let start = std::time::Instant::now();
+        let outcome = tokio::time::timeout(timeout, rx).await;
+        if tool_name == "studio-test_script" {
+            finish_test_timing(state, &request_id, &outcome);
+        }
+        match outcome {
+            Ok(Ok(response)) => {
+                let elapsed = start.elapsed();
+                if response.success {
+                    tracing::info!(tool = %tool_name, elapsed_ms = elapsed.as_millis(), "Tool call succeeded");
+                    let result = response.result.unwrap_or(Value::Null);
+                    if let Some(arguments) = tracked_arguments {
+                        if is_input_tool(tool_name) {
+                            track_held_keys(state, tool_name, &arguments).await;
+                        }
+                        track_session_resources(state, &client_id, tool_name, &arguments, &result).await;
                     }
+                    Ok(result)
+                } else {
+                    let error_msg = response
+                        .error
+                        .unwrap_or_else(|| "Unknown plugin error".to_string());
+                    tracing::warn!(tool = %tool_name, elapsed_ms = elapsed.as_millis(), error = %error_msg, "Tool call failed");
+                    Err(error_msg)
                 }
-            }),
+            }
+            Ok(Err(_)) => {
+                tracing::error!(tool = %tool_name, "Plugin disconnected while processing tool call");
+                Err("Plugin disconnected while processing tool call".into())
+            }
+            Err(_) => {
+                tracing::warn!(tool = %tool_name, "Tool call timed out after {timeout:?}");
+                // Nobody waits for it now; a late answer finds nothing to resolve
+                state.remove_pending(&request_id).await;
+                Err(format!(
+                    "Tool call '{tool_name}' timed out after {}s. Is the Studio plugin running?",
+                    timeout.as_secs()
+                ))
+            }
+        }
+    }
+}
+
+/// Close the phase timeline of a test_script call for studio-time_report.
+fn finish_test_timing(
+    state: &SharedState,
+    request_id: &str,
+    outcome: &Result<Result<BridgeToolResponse, tokio::sync::oneshot::error::RecvError>, tokio::time::error::Elapsed>,
+) {
+    let result = match outcome {
+        Ok(Ok(response)) if response.success => response.result.as_ref(),
+        _ => None,
+    };
+    let label = match (outcome, result) {
+        (Err(_), _) => "timedOut",
+        (_, Some(result)) if result.get("success").and_then(|v| v.as_bool()) == Some(true) => "passed",
+        (_, Some(_)) => "failed",
+        (Ok(_), None) => "error",
+    };
+    let session_id = result.and_then(|r| r.get("sessionId")).and_then(|v| v.as_str()).map(String::from);
+    state
+        .test_timings()
+        .finish(request_id, std::time::Instant::now(), state.clock(), label, session_id);
+}
+
+fn is_input_tool(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "studio-virtualuser_key" | "studio-virtualuser_sequence" | "studio-playtest_stop"
+    )
+}
+
+/// Tools that open or close plugin-side resources the idle cleanup cares about.
+fn is_resource_tool(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "studio-checkpoint_begin"
+            | "studio-checkpoint_end"
+            | "studio-checkpoint_undo"
+            | "studio-npc_driver_start"
+            | "studio-npc_driver_stop"
+    )
+}
+
+async fn track_session_resources(
+    state: &SharedState,
+    client_id: &str,
+    tool_name: &str,
+    arguments: &Value,
+    result: &Value,
+) {
+    let str_field = |v: &Value, key: &str| v.get(key).and_then(|s| s.as_str()).map(String::from);
+    let owner = if tool_name == "studio-checkpoint_begin" {
+        state.client_owner_key(client_id).await
+    } else {
+        None
+    };
+    let mut resources = state.session_resources().await;
+    match tool_name {
+        "studio-checkpoint_begin" => {
+            if let Some(checkpoint_id) = str_field(result, "checkpointId") {
+                // A reloaded plugin numbers its checkpoints from 1 again, so this may
+                // replace a lost entry with the same id.
+                let checkpoint = OpenCheckpoint {
+                    name: str_field(arguments, "name").unwrap_or_default(),
+                    owner: owner.unwrap_or_else(|| client_id.to_string()),
+                    lost: None,
+                };
+                resources.open_checkpoints.insert(checkpoint_id, checkpoint);
+            }
+        }
+        "studio-checkpoint_end" => {
+            if let Some(checkpoint_id) = str_field(arguments, "checkpointId") {
+                resources.open_checkpoints.remove(&checkpoint_id);
+            }
+        }
+        // Undo with the id of an open recording cancels it
+        "studio-checkpoint_undo" if str_field(result, "action").as_deref() == Some("cancelled_recording") => {
+            if let Some(checkpoint_id) = str_field(arguments, "checkpointId") {
+                resources.open_checkpoints.remove(&checkpoint_id);
+            }
+        }
+        "studio-npc_driver_start" => {
+            if let Some(driver_id) = str_field(result, "driverId") {
+                let target = str_field(arguments, "target").unwrap_or_default();
+                resources.npc_drivers.insert(driver_id, target);
+            }
+        }
+        "studio-npc_driver_stop" => {
+            if let Some(driver_id) = str_field(arguments, "driverId") {
+                resources.npc_drivers.remove(&driver_id);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Mirror a successful key, sequence or stop call into the server's held-key set.
+async fn track_held_keys(state: &SharedState, tool_name: &str, arguments: &Value) {
+    if tool_name == "studio-playtest_stop" {
+        // The bridge's stop handler releases its own input before ending the test
+        state.clear_held_keys("playtest stopped").await;
+        return;
+    }
+    let transition = |v: &Value| {
+        let key_code = v.get("keyCode").and_then(|k| k.as_str())?.to_string();
+        let action = v.get("action").and_then(|a| a.as_str()).unwrap_or("down").to_string();
+        Some((key_code, action))
+    };
+    if tool_name == "studio-virtualuser_key" {
+        if let Some((key_code, action)) = transition(arguments) {
+            state.apply_key_transition(&key_code, &action).await;
+        }
+        return;
+    }
+
+    let mut steps: Vec<&Value> = arguments
+        .get("steps")
+        .and_then(|v| v.as_array())
+        .map(|s| s.iter().collect())
+        .unwrap_or_default();
+    steps.sort_by_key(|s| s.get("atMs").and_then(|v| v.as_u64()).unwrap_or(0));
+    for (key_code, action) in steps.into_iter().filter_map(transition) {
+        state.apply_key_transition(&key_code, &action).await;
+    }
+    // The bridge releases movement keys when a sequence ends
+    state.release_held_keys(&["W", "A", "S", "D"]).await;
+}
+
+/// Text shown to the MCP client for a successful plugin result.
+fn plugin_result_text(value: Value) -> String {
+    match value {
+        Value::Null => "ok".to_string(),
+        Value::String(s) => s,
+        other => serde_json::to_string_pretty(&other).unwrap_or_default(),
+    }
+}
+
+/// Timed sequences run for as long as their last step, so they get that on top of
+/// the normal timeout.
+fn tool_timeout(tool_name: &str, arguments: &Value, base: Duration) -> Duration {
+    match tool_name {
+        "studio-virtualuser_sequence" => {
+            base + Duration::from_millis(clock_sync::last_step_ms(arguments))
+        }
+        // The bridge gives up after timeoutMs (clamped by check_prompt_target)
+        "studio-trigger_proximity_prompt" => {
+            base + Duration::from_millis(arguments["timeoutMs"].as_u64().unwrap_or(0))
+        }
+        // The plugin polls for timeoutMs (clamped by clamp_condition_timing)
+        "studio-wait_for_condition" => {
+            base + Duration::from_millis(arguments["timeoutMs"].as_u64().unwrap_or(0))
+        }
+        // The bridge samples for windowMs (clamped by clamp_replication_window)
+        "studio-get_replication_stats" => {
+            base + Duration::from_millis(arguments["windowMs"].as_u64().unwrap_or(0))
+        }
+        // The plugin stops the test after `timeout` seconds, then needs time to answer
+        "studio-test_script" => {
+            let secs = arguments["timeout"].as_f64().filter(|s| s.is_finite() && *s > 0.0).unwrap_or(30.0);
+            Duration::from_secs_f64(secs.min(REQUEST_TIMEOUT.as_secs_f64())) + test_matrix::STOP_GRACE
+        }
+        _ => base,
+    }
+}
+
+async fn handle_status_tool(state: &SharedState, session: &SessionState, id: Value) -> JsonRpcResponse {
+    let connected = state.has_connected_client().await;
+    let client_id = state.first_client_id().await;
+    let (playtest_active, session_id, mode) = state.playtest_info().await;
+    let (readiness, player) = state.playtest_readiness().await;
+    // Traffic follows real polling, so deterministic mode leaves it out
+    let mut traffic: HashMap<String, TrafficReport> = if state.is_deterministic() {
+        HashMap::new()
+    } else {
+        state
+            .traffic_reports()
+            .await
+            .into_iter()
+            .map(|(id, _, _, report)| (id, report))
+            .collect()
+    };
+    let clients: Vec<Value> = state
+        .client_info()
+        .await
+        .into_iter()
+        .map(|(id, version, last_poll, is_bridge)| {
+            let age_secs = state.clock().age(last_poll).as_secs();
+            let traffic = traffic.remove(&id);
+            json!({
+                "clientId": id,
+                "version": version,
+                "isBridge": is_bridge,
+                "lastPollSecsAgo": age_secs,
+                "traffic": traffic,
+            })
+        })
+        .collect();
+
+    let result = json!({
+        "connected": connected,
+        "clientId": client_id,
+        "clients": clients,
+        "playtest": {
+            "active": playtest_active,
+            "sessionId": session_id,
+            "mode": mode,
+            "readiness": readiness.map(Readiness::as_str),
+            "player": player,
         },
-        McpToolDef {
-            name: "studio-capture_video_stop".into(),
-            description: Some("DISABLED - DO NOT USE. Stop video recording. Non-functional - Roblox's CaptureService does not expose video recording APIs to plugins. Will return an error if called.".into()),
-            input_schema: json!({
-                "type": "object",
-                "properties": {
-                    "recordingId": {
-                        "type": "string",
-                        "description": "Recording ID to stop"
-                    }
-                }
-            }),
+        "heldKeys": state.held_keys().await,
+        "runtimeFlags": state.runtime_flag_originals().await,
+        "studioSettings": state.studio_setting_originals().await,
+        "profile": state.settings().to_json(),
+        "session": session_summary(state, session).await,
+        "mcpSession": session.status(PROTOCOL_VERSION, state.clock()),
+        "build": BuildInfo::get(),
+        "runtime": state.runtime_info(),
+        "redactions": state.redaction_counts(),
+        "logArchive": state.log_archive().map(|a| a.status()),
+        "captureDir": (!state.is_deterministic()).then(|| state.capture_peers().report()),
+        "treeIndex": state.path_index().map(|index| index.status(state.clock())),
+        "responseCache": state.response_cache().map(|cache| cache.status()),
+        "pluginSettings": state.plugin_settings_report(session.activity().log_subscription().is_some()).await,
+        "queue": state.queue_depths().await,
+        "asyncCalls": state.async_calls().running(),
+        "httpBudget": (!state.is_deterministic()).then(|| state.http_budget()),
+        "alerts": state.alerting().map(|a| a.report()),
+        "hooks": state.hooks().map(|h| h.report()),
+        "eventOrder": state.event_order().await.stats,
+    });
+
+    JsonRpcResponse::success(id, McpToolResult {
+        content: vec![McpContent::Text {
+            text: serde_json::to_string_pretty(&result).unwrap(),
+        }],
+        is_error: false,
+    }.to_value())
+}
+
+async fn lost_checkpoint_error(state: &SharedState, arguments: &Value) -> Option<String> {
+    let checkpoint_id = arguments.get("checkpointId")?.as_str()?;
+    let resources = state.session_resources().await;
+    let checkpoint = resources.open_checkpoints.get(checkpoint_id)?;
+    let loss = checkpoint.lost?;
+    Some(format!(
+        "Checkpoint '{}' ({checkpoint_id}) can no longer be ended: {}. Changes made since studio-checkpoint_begin are still in the place but not grouped for undo. Start a new checkpoint with studio-checkpoint_begin, and clear this one with studio-checkpoint_abandon.",
+        checkpoint.name,
+        loss.describe()
+    ))
+}
+
+/// Drop tracked checkpoints the plugin can no longer finish: one by id, or all of
+/// them. Live recordings are refused, since forgetting them would leave the Studio
+/// recording open.
+async fn handle_checkpoint_abandon_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let requested = arguments.get("checkpointId").and_then(|v| v.as_str());
+    let mut resources = state.session_resources().await;
+
+    if let Some(checkpoint_id) = requested {
+        let message = match resources.open_checkpoints.get(checkpoint_id) {
+            None => Some(format!("No tracked checkpoint '{checkpoint_id}'")),
+            Some(c) if c.lost.is_none() => Some(format!(
+                "Checkpoint '{checkpoint_id}' is still open in Studio. Commit it with studio-checkpoint_end or cancel it with studio-checkpoint_undo and its checkpointId."
+            )),
+            Some(_) => None,
+        };
+        if let Some(message) = message {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    }
+
+    let abandoned: Vec<String> = resources
+        .open_checkpoints
+        .iter()
+        .filter(|(cid, c)| c.lost.is_some() && requested.is_none_or(|r| r == cid.as_str()))
+        .map(|(cid, _)| cid.clone())
+        .collect();
+    for checkpoint_id in &abandoned {
+        resources.open_checkpoints.remove(checkpoint_id);
+    }
+    let still_open = resources.open_checkpoints.len();
+    drop(resources);
+
+    tracing::info!(abandoned = ?abandoned, "Abandoned unrecoverable checkpoints");
+    let result = json!({ "abandoned": abandoned, "stillOpen": still_open });
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+/// Plugin-side resources opened through tool calls, for studio-status.
+async fn session_summary(state: &SharedState, session: &SessionState) -> Value {
+    let resources = state.session_resources().await;
+    json!({
+        "mcpClient": session.client_label(),
+        "logsSubscribed": session.activity().log_subscription().is_some(),
+        "openCheckpoints": resources
+            .open_checkpoints
+            .iter()
+            .filter(|(_, c)| c.lost.is_none())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>(),
+        "unrecoverableCheckpoints": resources
+            .open_checkpoints
+            .iter()
+            .filter_map(|(id, c)| {
+                let loss = c.lost?;
+                Some(json!({ "checkpointId": id, "name": c.name, "reason": loss.describe() }))
+            })
+            .collect::<Vec<_>>(),
+        "npcDrivers": resources.npc_drivers.keys().collect::<Vec<_>>(),
+    })
+}
+
+async fn handle_screenshot_region_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    output_dir: Option<&Path>,
+) -> JsonRpcResponse {
+    let field = |name: &str| {
+        arguments
+            .get(name)
+            .and_then(|v| v.as_u64())
+            .and_then(|v| u32::try_from(v).ok())
+    };
+    let region = match (field("x"), field("y"), field("width"), field("height")) {
+        (Some(x), Some(y), Some(width), Some(height)) => ScreenRegion {
+            x,
+            y,
+            width,
+            height,
         },
-    ]
+        _ => {
+            let result = McpToolResult::error_text(
+                "x, y, width and height are required and must be non-negative integers (pixels)",
+            );
+            return JsonRpcResponse::success(id, result.to_value());
+        }
+    };
+    let tag = arguments.get("tag").and_then(|v| v.as_str());
+    if let Some(Err(e)) = tag.map(captures::validate_filename_tag) {
+        return JsonRpcResponse::success(id, McpToolResult::error_text(format!("Invalid tag: {e}")).to_value());
+    }
+
+    let captures = match state.capture_manager() {
+        Ok(c) => c.with_output_dir(output_dir),
+        Err(e) => {
+            let result = McpToolResult::error_text(format!("Capture directory unavailable: {e}"));
+            return JsonRpcResponse::success(id, result.to_value());
+        }
+    };
+
+    match captures.os_screenshot(tag, Some(region)).await {
+        Ok(capture) => {
+            tracing::info!(path = ?capture.file_path, ?region, "Region screenshot saved");
+            let result = json!({
+                "id": capture.id,
+                "path": capture.file_path,
+                "region": region,
+                "tag": tag,
+            });
+            let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+            JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Region screenshot failed");
+            let result = McpToolResult::error_text(format!("Screenshot failed: {e}"));
+            JsonRpcResponse::success(id, result.to_value())
+        }
+    }
+}
+
+/// Have the plugin ray-trace the edit camera's view, then save and return it as a PNG.
+async fn handle_viewport_render_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    output_dir: Option<&Path>,
+    timeout: Duration,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let request = match RenderRequest::parse(arguments) {
+        Ok(request) => request,
+        Err(message) => return error(message),
+    };
+    let tag = arguments.get("tag").and_then(|v| v.as_str()).map(String::from);
+
+    let result = match call_plugin(state, "studio-capture_viewport_via_render", request.plugin_args(), timeout).await {
+        Ok(result) => result,
+        Err(e) => return error(format!("Render failed: {e}")),
+    };
+    let render = match Render::from_plugin(result, &request) {
+        Ok(render) => render,
+        Err(message) => return error(message),
+    };
+
+    let note = match render.details.get("camera") {
+        Some(camera) => format!("Ray-traced viewport render from camera {camera}"),
+        None => "Ray-traced viewport render".to_string(),
+    };
+    let png = render.png.clone();
+    let saved = state
+        .with_captures(output_dir, move |captures| captures.save_viewport_render(tag, note, &png))
+        .await;
+    let capture = match saved {
+        Ok(capture) => capture,
+        Err(e) => return error(format!("Failed to save the render: {e}")),
+    };
+    tracing::info!(path = ?capture.file_path, width = render.width, height = render.height, "Viewport render saved");
+
+    let mut summary = json!({
+        "id": capture.id,
+        "path": capture.file_path,
+        "width": render.width,
+        "height": render.height,
+        "bytes": render.png.len(),
+        "tag": capture.tag,
+    });
+    if let (Some(summary), Some(details)) = (summary.as_object_mut(), render.details.as_object()) {
+        for (key, value) in details {
+            summary.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+    }
+    let text = serde_json::to_string_pretty(&summary).unwrap_or_default();
+    let result = McpToolResult::text(text).with_image(render.png_base64(), "image/png");
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+/// Run every listed tool name through the plugin routing, as `enqueue_tool_request`
+/// would route it right now.
+async fn handle_list_tools_with_routing(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let prefix = state.tool_prefix();
+    let filter = arguments.get("filter").and_then(|v| v.as_str()).unwrap_or("");
+    // Routing works on canonical names; the filter matches what the client sees
+    let tools: Vec<String> = listed_tools(state)
+        .into_iter()
+        .filter(|t| t.name.contains(filter))
+        .map(|t| canonical_tool_name(state, &t.name))
+        .collect();
+    let mut report = state.routing_report(&tools).await;
+    if let Some(routes) = report["tools"].as_array_mut() {
+        for route in routes {
+            if let Some(tool) = route["tool"].as_str() {
+                route["tool"] = json!(apply_tool_prefix(tool, prefix));
+            }
+        }
+    }
+    report["rule"] = json!("A tool goes to the first client of the role it prefers (clients in clientId order), else to the most recently polled client. The preferred role is built in unless YIPPIE_ROUTING_OVERRIDES sets it (overridden: true). Tools the server answers itself never reach this routing.");
+    let mut result = McpToolResult::text(serde_json::to_string_pretty(&report).unwrap_or_default()).to_value();
+    result["structuredContent"] = report;
+    JsonRpcResponse::success(id, result)
+}
+
+/// Diff two PNG captures, save the highlighted diff and return the metrics with it.
+async fn handle_capture_compare_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    output_dir: Option<&Path>,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let request = match CompareRequest::parse(arguments) {
+        Ok(request) => request,
+        Err(message) => return error(message),
+    };
+    let entries = match state.capture_manager().and_then(|captures| captures.list_captures()) {
+        Ok(entries) => entries,
+        Err(e) => return error(format!("Failed to read the capture index: {e}")),
+    };
+    let (before, after) = match request.resolve(&entries) {
+        Ok((before, after)) => (before.clone(), after.clone()),
+        Err(message) => return error(message),
+    };
+
+    // Decoding and diffing a full-screen pair takes a while; keep it off the runtime
+    let (threshold, scale) = (request.threshold, request.scale);
+    let (before_capture, after_capture) = (before.clone(), after.clone());
+    let compared = tokio::task::spawn_blocking(move || {
+        let before = Image::load(&before_capture)?;
+        let mut after = Image::load(&after_capture)?;
+        let original_size = (after.width, after.height);
+        if original_size != (before.width, before.height) {
+            if !scale {
+                return Err(format!(
+                    "The captures differ in size: '{}' is {}x{}, '{}' is {}x{}. Pass scale: true to resize the second to the first's size, or compare captures of the same region.",
+                    before_capture.id, before.width, before.height, after_capture.id, after.width, after.height
+                ));
+            }
+            after = after.scaled(before.width, before.height);
+        }
+        let diff = Diff::compute(&before, &after, threshold);
+        let highlighted = diff.highlight(&after);
+        let png = viewport_render::encode_png(highlighted.width, highlighted.height, &highlighted.rgb)
+            .map_err(|e| format!("PNG encoding failed: {e:#}"))?;
+        Ok((diff, original_size, png))
+    })
+    .await;
+    let (diff, after_size, png) = match compared {
+        Ok(Ok(compared)) => compared,
+        Ok(Err(message)) => return error(message),
+        Err(e) => return error(format!("Comparison failed: {e}")),
+    };
+
+    let note = format!("Diff of {} against {}: {}% changed", before.id, after.id, diff.changed_percent());
+    let diff_png = png.clone();
+    let saved = state
+        .with_captures(output_dir, move |captures| captures.save_capture_diff(request.tag, note, &diff_png))
+        .await;
+    let capture = match saved {
+        Ok(capture) => capture,
+        Err(e) => return error(format!("Failed to save the diff image: {e}")),
+    };
+    tracing::info!(path = ?capture.file_path, changed = diff.changed_pixels, "Capture diff saved");
+
+    let mut summary = json!({
+        "before": { "id": before.id, "path": before.file_path },
+        "after": { "id": after.id, "path": after.file_path },
+        "width": diff.width,
+        "height": diff.height,
+        "threshold": threshold,
+        "identical": diff.changed_pixels == 0,
+        "changedPixels": diff.changed_pixels,
+        "totalPixels": diff.total_pixels(),
+        "changedPercent": diff.changed_percent(),
+        "maxChannelDelta": diff.max_channel_delta,
+        "regionCount": diff.region_count,
+        "regions": diff.regions,
+        "regionsTruncated": diff.region_count > diff.regions.len(),
+        "diff": { "id": capture.id, "path": capture.file_path, "bytes": png.len() },
+    });
+    if after_size != (diff.width, diff.height) {
+        summary["scaled"] = json!({ "from": [after_size.0, after_size.1], "to": [diff.width, diff.height] });
+    }
+    let inline = png.len() <= capture_compare::MAX_INLINE_DIFF_BYTES;
+    if !inline {
+        summary["imageOmitted"] = json!(format!(
+            "The diff image is {} bytes, over the {} byte limit for inline images; read it from diff.path",
+            png.len(),
+            capture_compare::MAX_INLINE_DIFF_BYTES
+        ));
+    }
+    let mut result = McpToolResult::text(serde_json::to_string_pretty(&summary).unwrap_or_default());
+    if inline {
+        result = result.with_image(base64::engine::general_purpose::STANDARD.encode(&png), "image/png");
+    }
+    let mut value = result.to_value();
+    value["structuredContent"] = summary;
+    JsonRpcResponse::success(id, value)
+}
+
+/// Summarize where recent test_script calls spent their time and save the rows as CSV.
+async fn handle_time_report_tool(state: &SharedState, id: Value, arguments: &Value, output_dir: Option<&Path>) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let count = |key: &str, default: usize, max: usize| match arguments.get(key) {
+        None | Some(Value::Null) => Ok(default),
+        Some(v) => match v.as_u64() {
+            Some(n) if (1..=max as u64).contains(&n) => Ok(n as usize),
+            _ => Err(format!("'{key}' must be an integer from 1 to {max}")),
+        },
+    };
+    let (last, slowest) = match (
+        count("last", time_report::DEFAULT_LAST, time_report::MAX_RECORDS),
+        count("slowest", time_report::DEFAULT_SLOWEST, time_report::MAX_SLOWEST),
+    ) {
+        (Ok(last), Ok(slowest)) => (last, slowest),
+        (Err(message), _) | (_, Err(message)) => return error(message),
+    };
+    let tag = arguments.get("tag").and_then(|v| v.as_str()).map(String::from);
+
+    let records = state.test_timings().last(last);
+    if records.is_empty() {
+        let summary = json!({
+            "sessions": 0,
+            "note": "No studio-test_script calls have finished since the server started",
+        });
+        let mut result = McpToolResult::text(serde_json::to_string_pretty(&summary).unwrap_or_default()).to_value();
+        result["structuredContent"] = summary;
+        return JsonRpcResponse::success(id, result);
+    }
+
+    let mut summary = time_report::summarize(&records, slowest);
+    let csv = render::to_csv(&time_report::CSV_COLUMNS, &time_report::csv_rows(&records));
+    let note = format!("Phase timings of the last {} test_script calls", records.len());
+    let saved = state
+        .with_captures(output_dir, move |captures| captures.save_time_report(tag, note, &csv))
+        .await;
+    match saved {
+        Ok(capture) => summary["csv"] = json!({ "id": capture.id, "path": capture.file_path }),
+        Err(e) => return error(format!("Failed to save the time report: {e}")),
+    }
+    let mut result = McpToolResult::text(serde_json::to_string_pretty(&summary).unwrap_or_default()).to_value();
+    result["structuredContent"] = summary;
+    JsonRpcResponse::success(id, result)
+}
+
+async fn handle_annotate_capture_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let Some(capture_id) = arguments.get("id").and_then(|v| v.as_str()) else {
+        let result = McpToolResult::error_text("Missing required argument: id (capture id from index.json)");
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+    let note = arguments.get("note").and_then(|v| v.as_str()).map(String::from);
+    let tag = arguments.get("tag").and_then(|v| v.as_str()).map(String::from);
+    if note.is_none() && tag.is_none() {
+        let result = McpToolResult::error_text("Provide 'note' and/or 'tag' to attach to the capture");
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    let target = capture_id.to_string();
+    let updated = state
+        .with_captures(None, move |captures| captures.update_capture(&target, note, tag))
+        .await;
+    match updated {
+        Ok(entry) => {
+            tracing::info!(capture_id = %capture_id, "Capture annotated");
+            let text = serde_json::to_string_pretty(&entry).unwrap_or_default();
+            JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+        }
+        Err(e) => {
+            let result = McpToolResult::error_text(format!("Failed to annotate capture: {e}"));
+            JsonRpcResponse::success(id, result.to_value())
+        }
+    }
+}
+
+async fn handle_get_output_since_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let seconds = match arguments.get("seconds").and_then(|v| v.as_f64()) {
+        Some(s) if s > 0.0 && s.is_finite() => s,
+        _ => {
+            let result = McpToolResult::error_text("'seconds' is required and must be a positive number");
+            return JsonRpcResponse::success(id, result.to_value());
+        }
+    };
+    let limit = arguments.get("limit").and_then(|v| v.as_u64()).unwrap_or(200) as usize;
+    let levels: Option<Vec<&str>> = arguments
+        .get("levels")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|l| l.as_str()).collect());
+
+    let now = state.clock().now().timestamp_millis() as f64 / 1000.0;
+    let mut entries: Vec<Arc<LogEntry>> = state
+        .get_logs_since_ts(now - seconds)
+        .into_iter()
+        .filter(|e| levels.as_ref().is_none_or(|l| l.contains(&e.level.as_str())))
+        .collect();
+    // Keep the most recent entries when the window holds more than `limit`
+    let truncated = entries.len() > limit;
+    if truncated {
+        entries.drain(..entries.len() - limit);
+    }
+
+    let result = json!({
+        "entries": entries,
+        "windowSeconds": seconds,
+        "from": now - seconds,
+        "to": now,
+        "truncated": truncated,
+        "nextSeq": entries.last().map(|e| e.seq),
+    });
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+async fn handle_get_errors_grouped_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let limit = match arguments.get("limit").filter(|v| !v.is_null()) {
+        None => 20,
+        Some(v) => match v.as_u64().filter(|n| *n > 0) {
+            Some(n) => n as usize,
+            None => {
+                let result = McpToolResult::error_text("'limit' must be a positive number");
+                return JsonRpcResponse::success(id, result.to_value());
+            }
+        },
+    };
+    let levels: Vec<&str> = match arguments.get("levels").and_then(|v| v.as_array()) {
+        Some(a) => a.iter().filter_map(|l| l.as_str()).collect(),
+        None => vec!["error"],
+    };
+
+    let mut grouped = state.log_groups(&levels);
+    let distinct = grouped.groups.len();
+    grouped.groups.truncate(limit);
+    let result = json!({
+        "groups": grouped.groups,
+        "distinct": distinct,
+        "entries": grouped.entries,
+        "ungrouped": grouped.ungrouped,
+        "truncated": distinct > limit,
+        "bufferFull": state.log_buffer_full(),
+    });
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+fn handle_get_tool_history_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let history = state.tool_history();
+    if history.capacity() == 0 {
+        let result = McpToolResult::error_text("Tool history is disabled (YIPPIE_TOOL_HISTORY_SIZE=0)");
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+    let limit = match arguments.get("limit").filter(|v| !v.is_null()) {
+        None => tool_history::DEFAULT_LIMIT,
+        Some(v) => match v.as_u64().filter(|n| *n > 0) {
+            Some(n) => n as usize,
+            None => {
+                let result = McpToolResult::error_text("'limit' must be a positive number");
+                return JsonRpcResponse::success(id, result.to_value());
+            }
+        },
+    };
+    let since_seq = match arguments.get("sinceSeq").filter(|v| !v.is_null()) {
+        None => None,
+        Some(v) => match v.as_u64() {
+            Some(n) => Some(n),
+            None => {
+                let result = McpToolResult::error_text("'sinceSeq' must be a non-negative integer");
+                return JsonRpcResponse::success(id, result.to_value());
+            }
+        },
+    };
+    // Names may come with the client-facing prefix, as clients see them
+    let tool = arguments.get("tool").and_then(|v| v.as_str()).map(|name| {
+        match name.strip_prefix(state.tool_prefix()) {
+            Some(rest) if state.tool_prefix() != DEFAULT_TOOL_PREFIX => format!("{DEFAULT_TOOL_PREFIX}{rest}"),
+            _ => name.to_string(),
+        }
+    });
+    let query = HistoryQuery {
+        limit,
+        tool,
+        since_seq,
+        failed_only: arguments.get("failedOnly").and_then(|v| v.as_bool()).unwrap_or(false),
+    };
+
+    let (calls, truncated) = history.query(&query);
+    let result = json!({
+        "calls": calls,
+        "truncated": truncated,
+        "recorded": history.recorded(),
+        "oldestSeq": history.oldest_seq(),
+        "capacity": history.capacity(),
+    });
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+async fn handle_logs_search_archive_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let Some(archive) = state.log_archive() else {
+        let result = McpToolResult::error_text("Log archive is disabled (set YIPPIE_LOG_ARCHIVE=1 and restart the server)");
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+    let query = match ArchiveQuery::from_args(arguments) {
+        Ok(q) => q,
+        Err(e) => return JsonRpcResponse::success(id, McpToolResult::error_text(e).to_value()),
+    };
+
+    let result = match archive.search_blocking(query).await {
+        Ok(hits) => {
+            let status = archive.status();
+            let text = serde_json::to_string_pretty(&json!({
+                "hits": hits,
+                "indexing": status.indexing,
+                "segmentsPending": status.segments_pending,
+            }))
+            .unwrap_or_default();
+            McpToolResult::text(text)
+        }
+        Err(e) => McpToolResult::error_text(format!("Archive search failed: {e}")),
+    };
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+/// One session's entries: from the archive when it is enabled, else from the log
+/// buffer. Returns the entries, their source and a warning when entries may be missing.
+async fn session_log_entries(state: &SharedState, session_id: &str) -> Result<(Vec<LogEntry>, &'static str, Option<String>), String> {
+    if let Some(archive) = state.log_archive() {
+        let (entries, truncated) = archive
+            .session_entries_blocking(session_id.to_string(), log_diff::MAX_SESSION_ENTRIES)
+            .await
+            .map_err(|e| format!("Reading session '{session_id}' from the log archive failed: {e}"))?;
+        let warning = truncated.then(|| {
+            format!("Session '{session_id}' has more than {} entries; only the first were compared", log_diff::MAX_SESSION_ENTRIES)
+        });
+        return Ok((entries, "archive", warning));
+    }
+    let entries = state.session_logs(session_id).into_iter().map(Arc::unwrap_or_clone).collect();
+    let warning = state.log_buffer_full().then(|| {
+        format!("The log buffer is full, so early entries of session '{session_id}' may have been evicted. Set YIPPIE_LOG_ARCHIVE=1 to compare whole sessions.")
+    });
+    Ok((entries, "buffer", warning))
+}
+
+/// Diff the logs of two sessions, save the full diff as a capture and return the
+/// summary with a bounded excerpt.
+async fn handle_logs_diff_sessions_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    output_dir: Option<&Path>,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let session_arg = |key: &str| arguments.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+    let (Some(baseline_id), Some(current_id)) = (session_arg("baselineSessionId"), session_arg("currentSessionId")) else {
+        return error("Missing required arguments: baselineSessionId and currentSessionId".into());
+    };
+
+    let (baseline, source, baseline_warning) = match session_log_entries(state, baseline_id).await {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    let (current, _, current_warning) = match session_log_entries(state, current_id).await {
+        Ok(found) => found,
+        Err(e) => return error(e),
+    };
+    for (session_id, entries) in [(baseline_id, &baseline), (current_id, &current)] {
+        if entries.is_empty() {
+            let where_ = if source == "archive" { "the log archive" } else { "the log buffer" };
+            return error(format!("No log entries for session '{session_id}' in {where_}"));
+        }
+    }
+
+    let normalize = arguments.get("normalize").and_then(|v| v.as_bool()).unwrap_or(true);
+    let options = log_diff::DiffOptions {
+        normalizers: normalize.then(|| state.log_diff_normalizers()),
+        context_lines: arguments
+            .get("contextLines")
+            .and_then(|v| v.as_u64())
+            .map_or(log_diff::DEFAULT_CONTEXT_LINES, |n| (n as usize).min(log_diff::MAX_CONTEXT_LINES)),
+        max_lines: arguments
+            .get("maxLines")
+            .and_then(|v| v.as_u64())
+            .map_or(log_diff::DEFAULT_MAX_LINES, |n| (n as usize).clamp(1, log_diff::MAX_MAX_LINES)),
+        min_count_delta: arguments
+            .get("minCountDelta")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(log_diff::DEFAULT_MIN_COUNT_DELTA)
+            .max(1),
+    };
+    let (report, full) = log_diff::diff_sessions(baseline_id, &baseline, current_id, &current, &options);
+
+    let tag = arguments.get("tag").and_then(|v| v.as_str()).map(String::from);
+    let session = current_id.to_string();
+    let artifact = state
+        .with_captures(output_dir, move |captures| captures.save_log_diff(tag, &session, &full))
+        .await;
+
+    let mut result = serde_json::to_value(&report).unwrap_or_default();
+    result["source"] = json!(source);
+    let warnings: Vec<String> = baseline_warning.into_iter().chain(current_warning).collect();
+    if !warnings.is_empty() {
+        result["warnings"] = json!(warnings);
+    }
+    match artifact {
+        Ok(capture) => result["artifact"] = json!({ "id": capture.id, "path": capture.file_path }),
+        Err(e) => result["artifactError"] = json!(format!("Failed to save the full diff: {e}")),
+    }
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+/// Server and plugin versions are known locally; only the Studio version needs a
+/// plugin round-trip. If that fails, the rest is still returned.
+async fn handle_get_version_tool(state: &SharedState, id: Value) -> JsonRpcResponse {
+    let plugins: Vec<Value> = state
+        .client_info()
+        .await
+        .into_iter()
+        .map(|(client_id, version, _, is_bridge)| {
+            json!({ "clientId": client_id, "version": version, "isBridge": is_bridge })
+        })
+        .collect();
+    let mut result = json!({
+        "serverVersion": SERVER_VERSION,
+        "protocolVersion": PROTOCOL_VERSION,
+        "pluginVersion": plugins.iter().find(|p| p["isBridge"] == false).map(|p| p["version"].clone()),
+        "plugins": plugins,
+        "studioVersion": null,
+    });
+    match call_plugin(state, "studio-get_version", json!({}), GET_VERSION_TIMEOUT).await {
+        Ok(Value::Object(studio)) => {
+            for (key, value) in studio {
+                result[key] = value;
+            }
+        }
+        Ok(other) => result["studioVersion"] = other,
+        Err(e) => result["studioVersionError"] = json!(e),
+    }
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+async fn handle_get_keybinds_tool(state: &SharedState, id: Value) -> JsonRpcResponse {
+    let held_keys = state.held_keys().await;
+    let result = json!({
+        "heldKeys": held_keys,
+        "playtestActive": state.is_playtest_active().await,
+        "hint": if held_keys.is_empty() {
+            None
+        } else {
+            Some("Release keys with studio-virtualuser_key action \"up\" when done")
+        },
+    });
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+/// Check the group against the registered ones before the plugin assigns it, so a
+/// typo comes back with the list of valid names instead of a bare plugin error.
+async fn handle_set_collision_group_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    if arguments.get("path").and_then(|v| v.as_str()).is_none_or(str::is_empty) {
+        return error("Missing 'path' argument".into());
+    }
+    let Some(group) = arguments.get("group").and_then(|v| v.as_str()).filter(|g| !g.is_empty()) else {
+        return error("Missing 'group' argument".into());
+    };
+    if state.is_playtest_active().await {
+        return error(
+            "Stop the playtest first: studio-set_collision_group edits the edit DataModel, which the running session doesn't see".into(),
+        );
+    }
+
+    // Best effort: if the group list can't be read, the plugin still checks the name
+    match call_plugin(state, "studio-get_collision_groups", json!({ "groupsOnly": true }), COLLISION_GROUPS_TIMEOUT).await {
+        Ok(value) => {
+            let names: Vec<&str> = value["groups"]
+                .as_array()
+                .map(|groups| groups.iter().filter_map(|g| g["name"].as_str()).collect())
+                .unwrap_or_default();
+            if !names.is_empty() && !names.contains(&group) {
+                return error(format!(
+                    "Unknown collision group '{group}'. Registered groups: {}. Register new groups with PhysicsService:RegisterCollisionGroup (e.g. via studio-run_script).",
+                    names.join(", ")
+                ));
+            }
+        }
+        Err(e) => tracing::debug!(error = %e, "Could not list collision groups; leaving the check to the plugin"),
+    }
+
+    let result = match call_plugin(state, "studio-set_collision_group", arguments.clone(), TOOL_CALL_TIMEOUT).await {
+        Ok(value) => McpToolResult::text(plugin_result_text(value)),
+        Err(message) => McpToolResult::error_text(message),
+    };
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+/// Ask the plugin for the property's value type and decide from it whether
+/// TweenService can animate the property.
+async fn handle_is_tweenable_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let non_empty = |key: &str| arguments.get(key).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+    let Some(property) = non_empty("property") else {
+        return error("Missing 'property' argument".into());
+    };
+    let args = match (non_empty("path"), non_empty("className")) {
+        (Some(path), None) => json!({ "path": path, "property": property }),
+        (None, Some(class_name)) => json!({ "className": class_name, "property": property }),
+        (Some(_), Some(_)) => return error("Pass either 'path' or 'className', not both".into()),
+        (None, None) => return error("Missing 'path' or 'className' argument".into()),
+    };
+
+    let info = match call_plugin(state, "studio-get_property_type", args, TOOL_CALL_TIMEOUT).await {
+        Ok(info) => info,
+        Err(message) => return error(message),
+    };
+    let value_type = info["valueType"].as_str().unwrap_or("nil");
+    let mut result = json!({
+        "className": info["className"],
+        "property": property,
+        "valueType": value_type,
+    });
+    if let Some(enum_type) = info["enumType"].as_str() {
+        result["enumType"] = json!(enum_type);
+    }
+    if let Some(verdict) = tweenable::check(value_type).as_object() {
+        for (key, value) in verdict {
+            if !value.is_null() {
+                result[key] = value.clone();
+            }
+        }
+    }
+    JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+}
+
+/// A non-empty list of distinct, non-empty strings from `arguments[key]`, at most `max`.
+fn string_list(arguments: &Value, key: &str, max: usize) -> Result<Vec<String>, String> {
+    let items = arguments
+        .get(key)
+        .and_then(|v| v.as_array())
+        .filter(|items| !items.is_empty())
+        .ok_or(format!("Missing '{key}' argument (a non-empty array of strings)"))?;
+    let mut out: Vec<String> = Vec::with_capacity(items.len());
+    for item in items {
+        let item = item
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .ok_or(format!("'{key}' must only contain non-empty strings"))?;
+        if !out.iter().any(|seen| seen == item) {
+            out.push(item.to_string());
+        }
+    }
+    if out.len() > max {
+        return Err(format!("'{key}' has {} entries; at most {max} per call", out.len()));
+    }
+    Ok(out)
+}
+
+/// Read `properties` of every instance in `paths` with one plugin request. Properties
+/// the plugin read as nil come back as null rather than missing.
+async fn handle_batch_get_properties_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    timeout: Duration,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let paths = match string_list(arguments, "paths", MAX_BATCH_PATHS) {
+        Ok(paths) => paths,
+        Err(message) => return error(message),
+    };
+    let properties = match string_list(arguments, "properties", MAX_BATCH_PROPERTIES) {
+        Ok(properties) => properties,
+        Err(message) => return error(message),
+    };
+
+    let args = json!({ "paths": paths, "properties": properties, "maxBytes": BATCH_PROPERTIES_MAX_BYTES });
+    let mut result = match call_plugin(state, "studio-batch_get_properties", args, timeout).await {
+        Ok(result) => result,
+        Err(message) => return error(message),
+    };
+
+    // Luau tables drop nil values, and an empty table arrives as []
+    let mut values = serde_json::Map::new();
+    for path in &paths {
+        let Some(read) = result["values"].get(path) else {
+            continue;
+        };
+        let failed = result["errors"].get(path);
+        let mut row = serde_json::Map::new();
+        for property in &properties {
+            if failed.and_then(|f| f.get(property)).is_some() {
+                continue;
+            }
+            row.insert(property.clone(), read.get(property).cloned().unwrap_or(Value::Null));
+        }
+        values.insert(path.clone(), Value::Object(row));
+    }
+    result["values"] = Value::Object(values);
+    if result["errors"].as_object().is_none_or(|errors| errors.is_empty()) {
+        if let Some(obj) = result.as_object_mut() {
+            obj.remove("errors");
+        }
+    }
+    result["count"] = json!(result["values"].as_object().map_or(0, |v| v.len()));
+    JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+}
+
+/// One side of studio-diff_places: the snapshot and how it is described in the
+/// result. Live sides are saved as snapshot files when `save` is set.
+async fn diff_places_side(
+    state: &SharedState,
+    source: &SnapshotSource,
+    max_instances: usize,
+    save: bool,
+    timeout: Duration,
+) -> Result<(Snapshot, Value), String> {
+    match source {
+        SnapshotSource::Live(path) => {
+            let args = json!({ "path": path, "maxInstances": max_instances });
+            let result = call_plugin(state, "studio-snapshot_subtree", args, timeout).await?;
+            let snapshot = Snapshot::from_plugin(result, state.clock().now().to_rfc3339())?;
+            let mut side = json!({
+                "source": "live",
+                "root": snapshot.root,
+                "takenAt": snapshot.taken_at,
+                "instances": snapshot.instances.len(),
+                "truncated": snapshot.truncated,
+            });
+            if save {
+                let text = serde_json::to_string(&snapshot).map_err(|e| e.to_string())?;
+                let (root, text) = (snapshot.root.clone(), state.redact(&text).into_owned());
+                let saved = state
+                    .with_captures(None, move |captures| captures.save_model_snapshot(&root, &text))
+                    .await;
+                match saved {
+                    Ok((capture, file)) => {
+                        side["snapshot"] = json!(file);
+                        side["captureId"] = json!(capture.id);
+                    }
+                    Err(e) => side["saveError"] = json!(format!("Failed to save the snapshot: {e}")),
+                }
+            }
+            Ok((snapshot, side))
+        }
+        SnapshotSource::File(file) => {
+            let value = state
+                .capture_manager()
+                .and_then(|captures| captures.load_model_snapshot(file))
+                .map_err(|e| e.to_string())?;
+            let snapshot = Snapshot::from_file(value, file)?;
+            let side = json!({
+                "source": "snapshot",
+                "snapshot": file,
+                "root": snapshot.root,
+                "takenAt": snapshot.taken_at,
+                "instances": snapshot.instances.len(),
+                "truncated": snapshot.truncated,
+            });
+            Ok((snapshot, side))
+        }
+    }
+}
+
+/// Compare two subtrees, live or from snapshot files. Without `compare`, only takes
+/// (and saves) the `base` snapshot for a later comparison.
+async fn handle_diff_places_tool(state: &SharedState, id: Value, arguments: &Value, timeout: Duration) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let Some(base_arg) = arguments.get("base") else {
+        return error("Missing required argument: base".into());
+    };
+    let base_source = match SnapshotSource::parse(base_arg, "base") {
+        Ok(source) => source,
+        Err(message) => return error(message),
+    };
+    let compare_source = match arguments.get("compare").map(|v| SnapshotSource::parse(v, "compare")).transpose() {
+        Ok(source) => source,
+        Err(message) => return error(message),
+    };
+    let save_only = compare_source.is_none();
+    if save_only && !matches!(base_source, SnapshotSource::Live(_)) {
+        return error("Without 'compare', 'base' must be a live 'instance' to take a snapshot of".into());
+    }
+    let save = save_only || arguments.get("save").and_then(|v| v.as_bool()).unwrap_or(false);
+    let max_instances = arguments
+        .get("maxInstances")
+        .and_then(|v| v.as_u64())
+        .map_or(model_diff::DEFAULT_MAX_INSTANCES, |n| (n as usize).clamp(1, model_diff::MAX_INSTANCES));
+    let max_changes = arguments
+        .get("maxChanges")
+        .and_then(|v| v.as_u64())
+        .map_or(model_diff::DEFAULT_MAX_CHANGES, |n| (n as usize).clamp(1, model_diff::MAX_MAX_CHANGES));
+
+    let (base, base_side) = match diff_places_side(state, &base_source, max_instances, save, timeout).await {
+        Ok(found) => found,
+        Err(message) => return error(format!("base: {message}")),
+    };
+    let Some(compare_source) = compare_source else {
+        let text = serde_json::to_string_pretty(&json!({ "base": base_side })).unwrap_or_default();
+        return JsonRpcResponse::success(id, McpToolResult::text(text).to_value());
+    };
+    let (compare, compare_side) = match diff_places_side(state, &compare_source, max_instances, save, timeout).await {
+        Ok(found) => found,
+        Err(message) => return error(format!("compare: {message}")),
+    };
+
+    let report = model_diff::diff(&base, &compare, max_changes);
+    let mut result = serde_json::to_value(&report).unwrap_or_default();
+    result["base"] = base_side;
+    result["compare"] = compare_side;
+    if base.truncated || compare.truncated {
+        result["warning"] = json!(format!(
+            "A snapshot stopped at {max_instances} instances, so instances past the cut show up as added or removed. Narrow the subtree or raise maxInstances (at most {}).",
+            model_diff::MAX_INSTANCES
+        ));
+    }
+    JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+}
+
+/// Count the place through the plugin, keep the snapshot and compare it with an earlier
+/// one. The text is tables; structuredContent has the same data as JSON.
+async fn handle_place_stats_tool(state: &SharedState, id: Value, arguments: &Value, timeout: Duration) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let request = match StatsRequest::parse(arguments) {
+        Ok(request) => request,
+        Err(message) => return error(message),
+    };
+    let unknown = |previous: &str, store: &place_stats::StatsStore| {
+        let kept = store.ids();
+        if kept.is_empty() {
+            format!("No place stats snapshot '{previous}': none are kept yet. Call studio-place_stats without compareTo first.")
+        } else {
+            format!(
+                "No place stats snapshot '{previous}'. Kept (last {}): {}",
+                place_stats::MAX_STORED_SNAPSHOTS,
+                kept.join(", ")
+            )
+        }
+    };
+    // Before the walk, which takes a while on a large place
+    if let Some(previous) = &request.compare_to {
+        let store = state.place_stats();
+        if store.get(previous).is_none() {
+            return error(unknown(previous, &store));
+        }
+    }
+
+    let result = match call_plugin(state, "studio-place_stats", request.plugin_args(), timeout).await {
+        Ok(result) => result,
+        Err(e) => return error(format!("Counting the place failed: {e}")),
+    };
+    let stats = match PlaceStats::from_plugin(result, state.next_id(), state.clock().now().to_rfc3339()) {
+        Ok(stats) => stats,
+        Err(message) => return error(message),
+    };
+
+    let mut store = state.place_stats();
+    let comparison = match &request.compare_to {
+        Some(previous) => match store.get(previous) {
+            Some(before) => Some(place_stats::compare(before, &stats, request.top)),
+            None => return error(unknown(previous, &store)),
+        },
+        None => None,
+    };
+    let text = place_stats::render_text(&stats, comparison.as_ref(), request.top);
+    let structured = place_stats::structured(&stats, comparison.as_ref(), request.top);
+    store.insert(stats);
+
+    let mut result = McpToolResult::text(text).to_value();
+    result["structuredContent"] = structured;
+    JsonRpcResponse::success(id, result)
+}
+
+/// Take a GUI snapshot through the bridge's studio-get_gui_tree and keep it.
+async fn take_gui_snapshot(
+    state: &SharedState,
+    request: &TreeRequest,
+    timeout: Duration,
+) -> Result<Arc<GuiSnapshot>, String> {
+    let (_, session_id, _) = state.playtest_info().await;
+    let result = call_plugin(state, "studio-get_gui_tree", request.plugin_args(), timeout)
+        .await
+        .map_err(|e| format!("Reading the GUI failed: {e}"))?;
+    let snapshot = GuiSnapshot::from_plugin(result, state.next_id(), request, session_id, state.clock().now().to_rfc3339())?;
+    Ok(state.gui_snapshots().insert(snapshot))
+}
+
+/// A page of the player's GUI, from a snapshot taken now, one a few frames old with
+/// the same arguments, or the one a cursor points into.
+async fn handle_gui_tree_tool(state: &SharedState, id: Value, arguments: &Value, timeout: Duration) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let request = match TreeRequest::parse(arguments) {
+        Ok(request) => request,
+        Err(message) => return error(message),
+    };
+    if let Some(root) = &request.root {
+        if let Err(message) = starter_gui_path(root) {
+            return error(message);
+        }
+    }
+
+    let page = if let Some((snapshot_id, offset)) = &request.cursor {
+        let Some(snapshot) = state.gui_snapshots().get(snapshot_id) else {
+            return error(format!(
+                "Cursor snapshot '{snapshot_id}' is no longer kept. Call studio-gui_tree without cursor for a new snapshot."
+            ));
+        };
+        if *offset > snapshot.elements.len() {
+            return error(format!("Cursor offset {offset} is past the {} elements of the snapshot", snapshot.elements.len()));
+        }
+        snapshot.page(*offset, request.limit, true)
+    } else {
+        let (_, session_id, _) = state.playtest_info().await;
+        let cached = (!request.refresh)
+            .then(|| state.gui_snapshots().reusable(&request, session_id.as_deref()))
+            .flatten();
+        match cached {
+            Some(snapshot) => snapshot.page(0, request.limit, true),
+            None => match take_gui_snapshot(state, &request, timeout).await {
+                Ok(snapshot) => snapshot.page(0, request.limit, false),
+                Err(message) => return error(message),
+            },
+        }
+    };
+
+    let mut result = McpToolResult::text(serde_json::to_string_pretty(&page).unwrap_or_default()).to_value();
+    result["structuredContent"] = page;
+    JsonRpcResponse::success(id, result)
+}
+
+/// Resolve a path or text selector against a GUI snapshot and click what it names
+/// through the bridge's studio-click_gui_button.
+async fn handle_gui_click_tool(state: &SharedState, id: Value, arguments: &Value, timeout: Duration) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let selector = match Selector::parse(arguments) {
+        Ok(selector) => selector,
+        Err(message) => return error(message),
+    };
+    let player = arguments.get("player").and_then(|v| v.as_str()).map(String::from);
+    let max_age_ms = match arguments.get("maxAgeMs").filter(|v| !v.is_null()) {
+        None => gui_snapshot::DEFAULT_CLICK_MAX_AGE_MS,
+        Some(v) => match v.as_u64().filter(|ms| *ms <= gui_snapshot::MAX_CLICK_MAX_AGE_MS) {
+            Some(ms) => ms,
+            None => {
+                return error(format!(
+                    "'maxAgeMs' must be a number of milliseconds from 0 to {}",
+                    gui_snapshot::MAX_CLICK_MAX_AGE_MS
+                ))
+            }
+        },
+    };
+
+    let (_, session_id, _) = state.playtest_info().await;
+    let cached = state
+        .gui_snapshots()
+        .for_click(player.as_deref(), session_id.as_deref(), Duration::from_millis(max_age_ms));
+    let request = TreeRequest::full(player.clone());
+    let (mut snapshot, mut refreshed) = match cached {
+        Some(snapshot) => (snapshot, false),
+        None => match take_gui_snapshot(state, &request, timeout).await {
+            Ok(snapshot) => (snapshot, true),
+            Err(message) => return error(message),
+        },
+    };
+    let mut resolution = snapshot.resolve(&selector);
+    // The element may have appeared since the cached snapshot
+    if matches!(resolution, Err(Unresolved::NotFound(_))) && !refreshed {
+        snapshot = match take_gui_snapshot(state, &request, timeout).await {
+            Ok(snapshot) => snapshot,
+            Err(message) => return error(message),
+        };
+        refreshed = true;
+        resolution = snapshot.resolve(&selector);
+    }
+    let resolved = match resolution {
+        Ok(resolved) => Some(resolved),
+        // Past the snapshot's limits, the client can still find a path
+        Err(Unresolved::NotFound(_)) if snapshot.truncated && matches!(selector.target, Target::Path(_)) => None,
+        Err(Unresolved::NotFound(message) | Unresolved::Refused(message)) => return error(message),
+    };
+    if let Some(resolved) = resolved.as_ref().filter(|r| !GUI_BUTTON_CLASSES.contains(&r.element.class_name.as_str())) {
+        return error(format!(
+            "not_a_gui_button: {} ({}) is a {}; only TextButton and ImageButton can be clicked",
+            selector.describe(),
+            resolved.element.path,
+            resolved.element.class_name
+        ));
+    }
+
+    let path = match (&resolved, &selector.target) {
+        (Some(resolved), _) => resolved.element.path.clone(),
+        (None, Target::Path(path)) => path.clone(),
+        (None, Target::Text { .. }) => unreachable!("text selectors that match nothing return above"),
+    };
+    let clicked = match call_plugin(state, "studio-click_gui_button", json!({ "path": path, "player": player }), timeout).await {
+        Ok(clicked) => clicked,
+        Err(e) => {
+            return error(format!(
+                "Clicking {path} failed: {e}. It was resolved from a GUI snapshot {} ms old; pass maxAgeMs: 0 to resolve against a fresh one.",
+                snapshot.age_ms()
+            ))
+        }
+    };
+    state.gui_snapshots().clicked();
+
+    let clicked_at = clicked.get("position").and_then(|p| serde_json::from_value(p.clone()).ok());
+    let result = json!({
+        "clicked": clicked,
+        "target": resolved.as_ref().map(|r| json!({
+            "path": r.element.path,
+            "className": r.element.class_name,
+            "text": r.element.text,
+            "center": r.center,
+            "nth": r.nth,
+            "matches": r.matches,
+        })),
+        "moved": resolved.as_ref().is_some_and(|r| r.moved(clicked_at)),
+        "snapshot": {
+            "snapshotId": snapshot.id,
+            "ageMs": snapshot.age_ms(),
+            "refreshed": refreshed,
+            "truncated": snapshot.truncated,
+        },
+    });
+    let mut result_value = McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value();
+    result_value["structuredContent"] = result;
+    JsonRpcResponse::success(id, result_value)
+}
+
+/// Set a whitelisted flag, or put changed flags back, through the plugin's property
+/// override handler. The first value a flag had is kept for `restore`.
+async fn handle_set_runtime_flag_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let action = arguments.get("action").and_then(|v| v.as_str()).unwrap_or("set");
+    if action == "list" {
+        let result = json!({ "flags": runtime_flags::list(), "changed": state.runtime_flag_originals().await });
+        return JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value());
+    }
+    if action != "set" && action != "restore" {
+        return error(format!("Unknown action '{action}': expected set, restore or list"));
+    }
+    let flag = match arguments.get("flag").and_then(|v| v.as_str()) {
+        Some(name) => match runtime_flags::find(name) {
+            Some(flag) => Some(flag),
+            None => return error(runtime_flags::not_allowed(name)),
+        },
+        None if action == "set" => return error("Missing 'flag' argument".into()),
+        None => None,
+    };
+    if state.is_playtest_active().await {
+        return error(
+            "Stop the playtest first: studio-set_runtime_flag edits the edit DataModel, which the running session doesn't see".into(),
+        );
+    }
+
+    if action == "restore" {
+        let originals = state.runtime_flag_originals().await;
+        let targets: Vec<(&RuntimeFlag, Value)> = match flag {
+            Some(flag) => match originals.get(flag.name) {
+                Some(value) => vec![(flag, value.clone())],
+                None => return error(format!("{} was not changed by studio-set_runtime_flag; nothing to restore", flag.name)),
+            },
+            None => originals
+                .iter()
+                .filter_map(|(name, value)| Some((runtime_flags::find(name)?, value.clone())))
+                .collect(),
+        };
+        let names: Vec<&str> = targets.iter().map(|(flag, _)| flag.name).collect();
+        let restored: serde_json::Map<String, Value> =
+            targets.iter().map(|(flag, value)| (flag.name.to_string(), value.clone())).collect();
+        if !targets.is_empty() {
+            if let Err(message) = call_plugin(state, "studio-set_properties", runtime_flags::overrides(targets), TOOL_CALL_TIMEOUT).await {
+                return error(message);
+            }
+        }
+        for name in names {
+            state.forget_runtime_flag(name).await;
+        }
+        let result = json!({ "restored": restored });
+        return JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value());
+    }
+
+    let Some(flag) = flag else {
+        return error("Missing 'flag' argument".into());
+    };
+    let Some(value) = arguments.get("value").filter(|v| !v.is_null()) else {
+        return error(format!("Missing 'value' argument for {}", flag.name));
+    };
+    if let Err(message) = flag.validate(value) {
+        return error(message);
+    }
+    let previous = match call_plugin(state, "studio-set_properties", runtime_flags::overrides([(flag, value.clone())]), TOOL_CALL_TIMEOUT).await {
+        Ok(result) => result["previous"][flag.service()][flag.property()].clone(),
+        Err(message) => return error(message),
+    };
+    state.record_runtime_flag_original(flag.name, previous.clone()).await;
+    let result = json!({
+        "flag": flag.name,
+        "value": value,
+        "previous": previous,
+        "restoreTo": state.runtime_flag_original(flag.name).await,
+        "hint": "Put it back with action \"restore\" (or restore every changed flag by leaving out 'flag')",
+    });
+    JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+}
+
+/// Toggle a whitelisted Studio setting. The value before the first change is put back
+/// when the playtest running now ends, or at session cleanup (`studio_settings.rs`).
+async fn handle_set_studio_setting_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let action = arguments.get("action").and_then(|v| v.as_str()).unwrap_or("set");
+    if action == "list" {
+        let result = json!({ "settings": studio_settings::list(), "changed": state.studio_setting_originals().await });
+        return JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value());
+    }
+    if action != "set" && action != "restore" {
+        return error(format!("Unknown action '{action}': expected set, restore or list"));
+    }
+    let setting = match arguments.get("setting").and_then(|v| v.as_str()) {
+        Some(name) => match studio_settings::find(name) {
+            Some(setting) => Some(setting),
+            None => return error(studio_settings::not_allowed(name)),
+        },
+        None if action == "set" => return error("Missing 'setting' argument".into()),
+        None => None,
+    };
+
+    if action == "restore" {
+        let mut originals = state.studio_setting_originals().await;
+        if let Some(setting) = setting {
+            if !originals.contains_key(setting.name) {
+                return error(format!("{} was not changed by studio-set_studio_setting; nothing to restore", setting.name));
+            }
+            originals.retain(|name, _| name == setting.name);
+        }
+        let result = match studio_settings::restore(state, &originals).await {
+            Ok(restored) => json!({ "restored": restored }),
+            Err(message) => return error(message),
+        };
+        return JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value());
+    }
+
+    let Some(setting) = setting else {
+        return error("Missing 'setting' argument".into());
+    };
+    let Some(value) = arguments.get("value").filter(|v| !v.is_null()) else {
+        return error(format!("Missing 'value' argument for {}", setting.name));
+    };
+    if let Err(message) = setting.validate(value) {
+        return error(message);
+    }
+    let (active, session_id, _) = state.playtest_info().await;
+    let previous = match call_plugin(state, "studio-set_studio_settings", studio_settings::changes([(setting, value.clone())]), TOOL_CALL_TIMEOUT).await {
+        Ok(result) => result["previous"][setting.service()][setting.property()].clone(),
+        Err(message) => return error(message),
+    };
+    state.record_studio_setting_original(setting.name, previous.clone(), session_id.filter(|_| active)).await;
+    let original = state.studio_setting_originals().await.remove(setting.name);
+    let restore_when = match original.as_ref().and_then(|o| o.playtest.as_deref()) {
+        Some(session) => format!("when playtest {session} ends"),
+        None => "when the MCP session is cleaned up (idle timeout or disconnect)".to_string(),
+    };
+    let result = json!({
+        "setting": setting.name,
+        "value": value,
+        "previous": previous,
+        "restoreTo": original.map(|o| o.value),
+        "restoreWhen": restore_when,
+        "hint": "Restored automatically; put it back sooner with action \"restore\"",
+    });
+    JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+}
+
+/// Show or switch the configuration profile. This may leave a read-only profile: the
+/// tool isn't marked read-only, so the MCP client asks the user before calling it.
+/// `POST /admin/profile` can't.
+fn handle_set_profile_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    if state.profiles().is_empty() {
+        return error("No configuration profiles are defined. Set YIPPIE_PROFILES to a JSON object of name -> overrides.".into());
+    }
+    let Some(name) = arguments.get("profile").and_then(|v| v.as_str()) else {
+        let result = json!({ "active": state.settings().to_json(), "profiles": profiles::list(state.profiles()) });
+        return JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value());
+    };
+    let switched = state.switch_profile(name, |_, _| Ok(()));
+    match switched {
+        Ok((previous, active)) => {
+            let result = json!({ "previous": previous.name(), "active": active.to_json() });
+            JsonRpcResponse::success(id, McpToolResult::text(serde_json::to_string_pretty(&result).unwrap_or_default()).to_value())
+        }
+        Err(message) => error(message),
+    }
+}
+
+/// Run the variants, save the full report (all logs) as a capture and return the
+/// matrix summary. A variant failing is a result, not a tool error.
+async fn handle_test_matrix_tool(
+    state: &SharedState,
+    id: Value,
+    arguments: &Value,
+    output_dir: Option<&Path>,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let matrix = match test_matrix::Matrix::parse(arguments) {
+        Ok(matrix) => matrix,
+        Err(e) => return error(format!("{e:#}")),
+    };
+    if state.is_playtest_active().await {
+        return error("A playtest is running. Stop it with studio-playtest_stop before running a test matrix.".into());
+    }
+
+    let outcomes = test_matrix::run(state, &matrix).await;
+    let totals = test_matrix::totals(&outcomes);
+    let tag = arguments.get("tag").and_then(|v| v.as_str()).map(String::from);
+    let report = json!({
+        "code": matrix.code,
+        "failFast": matrix.fail_fast,
+        "totals": totals,
+        "variants": outcomes,
+    });
+    let report_text = serde_json::to_string_pretty(&report).unwrap_or_default();
+    let report_text = state.redact(&report_text).into_owned();
+    let artifact = state
+        .with_captures(output_dir, move |captures| captures.save_test_matrix_report(tag, &report_text))
+        .await;
+
+    let mut result = json!({
+        "totals": totals,
+        "variants": outcomes.iter().map(|o| o.summary()).collect::<Vec<_>>(),
+    });
+    match artifact {
+        Ok(capture) => result["artifact"] = json!({ "id": capture.id, "path": capture.file_path }),
+        Err(e) => result["artifactError"] = json!(format!("Failed to save the full report: {e}")),
+    }
+    let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+}
+
+async fn handle_record_start_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let Some(name) = arguments.get("name").and_then(|v| v.as_str()) else {
+        let result = McpToolResult::error_text("Missing required argument: name");
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+    if let Err(e) = input_recording::validate_name(name) {
+        let result = McpToolResult::error_text(e.to_string());
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+    let max_seconds = arguments
+        .get("maxSeconds")
+        .and_then(|v| v.as_f64())
+        .unwrap_or(60.0)
+        .clamp(1.0, 300.0);
+
+    if !state.start_input_recording(name.to_string()).await {
+        let open = state.input_recording_name().await.unwrap_or_default();
+        let result = McpToolResult::error_text(format!(
+            "Recording '{open}' is already in progress. Stop it with studio-virtualuser_record_stop first."
+        ));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    let bridge_args = json!({ "name": name, "maxSeconds": max_seconds });
+    let result = match call_plugin(state, "studio-virtualuser_record_start", bridge_args, TOOL_CALL_TIMEOUT).await {
+        Ok(value) => {
+            tracing::info!(recording = %name, "Input recording started");
+            McpToolResult::text(plugin_result_text(value))
+        }
+        Err(message) => {
+            state.take_input_recording().await;
+            McpToolResult::error_text(message)
+        }
+    };
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+async fn handle_record_stop_tool(state: &SharedState, id: Value) -> JsonRpcResponse {
+    let Some(name) = state.input_recording_name().await else {
+        let result = McpToolResult::error_text(
+            "No input recording in progress. Start one with studio-virtualuser_record_start.",
+        );
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+
+    // The bridge flushes its last batch of samples before it answers
+    let bridge_result = call_plugin(
+        state,
+        "studio-virtualuser_record_stop",
+        json!({ "name": name }),
+        TOOL_CALL_TIMEOUT,
+    )
+    .await;
+
+    let Some(active) = state.take_input_recording().await else {
+        let result = McpToolResult::error_text("Recording was already stopped");
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+    let warning = bridge_result.as_ref().err().map(|e| {
+        format!("Bridge did not confirm the stop ({e}); saved the samples received so far")
+    });
+    if warning.is_some() && active.samples.is_empty() {
+        let result = McpToolResult::error_text(format!(
+            "Recording '{name}' failed: {}",
+            bridge_result.err().unwrap_or_default()
+        ));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    // Sample times are on the bridge clock; if it ran at a different rate from ours,
+    // move them (and the bridge-reported duration) onto the server timeline
+    let clock_rate = input_recording::drift_rate(&active.batches);
+    let mut samples = active.samples;
+    if let Some(rate) = clock_rate {
+        tracing::info!(recording = %name, rate, "Correcting bridge clock drift");
+        input_recording::correct_drift(&mut samples, rate);
+    }
+    let duration_ms = match bridge_result.ok().and_then(|v| v.get("durationMs").and_then(|d| d.as_u64())) {
+        Some(bridge_ms) => input_recording::scale_ms(bridge_ms, clock_rate.unwrap_or(1.0)),
+        None => (state.clock().now() - active.started_at).num_milliseconds().max(0) as u64,
+    };
+    let recording = InputRecording {
+        version: input_recording::RECORDING_FORMAT_VERSION,
+        name: active.name,
+        recorded_at: active.started_at.to_rfc3339(),
+        duration_ms,
+        clock_rate,
+        samples: input_recording::normalize(samples, duration_ms),
+    };
+
+    let to_save = recording.clone();
+    let saved = state
+        .with_captures(None, move |captures| captures.save_input_recording(&to_save))
+        .await;
+    match saved {
+        Ok(capture) => {
+            let key_transitions = recording
+                .samples
+                .iter()
+                .filter(|s| matches!(s, InputSample::Key { .. }))
+                .count();
+            tracing::info!(recording = %recording.name, samples = recording.samples.len(), "Input recording saved");
+            let result = json!({
+                "name": recording.name,
+                "id": capture.id,
+                "path": capture.file_path,
+                "durationMs": recording.duration_ms,
+                "keyTransitions": key_transitions,
+                "lookSamples": recording.samples.len() - key_transitions,
+                "droppedSamples": active.dropped,
+                "clockRate": recording.clock_rate,
+                "warning": warning,
+            });
+            let text = serde_json::to_string_pretty(&result).unwrap_or_default();
+            JsonRpcResponse::success(id, McpToolResult::text(text).to_value())
+        }
+        Err(e) => {
+            let result = McpToolResult::error_text(format!("Failed to save recording: {e}"));
+            JsonRpcResponse::success(id, result.to_value())
+        }
+    }
+}
+
+async fn handle_replay_tool(state: &SharedState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let Some(name) = arguments.get("name").and_then(|v| v.as_str()) else {
+        let result = McpToolResult::error_text("Missing required argument: name");
+        return JsonRpcResponse::success(id, result.to_value());
+    };
+    if let Err(e) = input_recording::validate_name(name) {
+        let result = McpToolResult::error_text(e.to_string());
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+    let speed = arguments.get("speed").and_then(|v| v.as_f64()).unwrap_or(1.0);
+    if !(input_recording::MIN_REPLAY_SPEED..=input_recording::MAX_REPLAY_SPEED).contains(&speed) {
+        let result = McpToolResult::error_text(format!(
+            "speed must be between {} and {}",
+            input_recording::MIN_REPLAY_SPEED,
+            input_recording::MAX_REPLAY_SPEED
+        ));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    let recording = match state
+        .capture_manager()
+        .and_then(|captures| captures.load_input_recording(name))
+    {
+        Ok(r) => r,
+        Err(e) => {
+            let result = McpToolResult::error_text(format!("{e:#}"));
+            return JsonRpcResponse::success(id, result.to_value());
+        }
+    };
+    let steps = input_recording::replay_steps(&recording, speed);
+    if steps.is_empty() {
+        let result = McpToolResult::error_text(format!("Recording '{name}' has no input to replay"));
+        return JsonRpcResponse::success(id, result.to_value());
+    }
+
+    let step_count = steps.len();
+    let sequence_args = json!({ "steps": steps });
+    tracing::info!(recording = %name, speed, steps = step_count, "Replaying input recording");
+    let result = match call_sequence(state, sequence_args).await {
+        Ok(value) => {
+            let summary = json!({
+                "name": name,
+                "speed": speed,
+                "steps": step_count,
+                "recordedDurationMs": recording.duration_ms,
+                "replay": value,
+            });
+            McpToolResult::text(serde_json::to_string_pretty(&summary).unwrap_or_default())
+        }
+        Err(message) => McpToolResult::error_text(message),
+    };
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+/// Run a virtualuser sequence. When the clock offset of the client it goes to is
+/// known, steps get absolute `dueClock`s (see `clock_sync.rs`) and the result reports
+/// how far each step fired from its schedule under `timing`.
+async fn call_sequence(state: &SharedState, mut arguments: Value) -> Result<Value, String> {
+    let lead = state.input_lead();
+    clock_sync::check_sequence_duration(&arguments, lead)?;
+    let offset = state.routed_clock_offset("studio-virtualuser_sequence").await;
+    let compensated = match offset.and_then(|o| Some((o.server_secs(), o.estimate()?))) {
+        Some((now, offset)) => {
+            clock_sync::apply_due_clocks(&mut arguments, clock_sync::plugin_start_clock(now, offset, lead));
+            true
+        }
+        None => {
+            tracing::debug!("No clock offset for the sequence's client yet; steps keep relative timing");
+            false
+        }
+    };
+
+    let timeout = tool_timeout("studio-virtualuser_sequence", &arguments, state.settings().tool_timeout) + lead;
+    let mut value = call_plugin(state, "studio-virtualuser_sequence", arguments, timeout).await?;
+    let Some(obj) = value.as_object_mut() else {
+        return Ok(value);
+    };
+    let timings = obj.remove("timings");
+    // Measured jitter differs run to run, so deterministic mode leaves it out
+    if !compensated || state.is_deterministic() {
+        return Ok(value);
+    }
+    let report = timings
+        .as_ref()
+        .and_then(|t| t.as_array())
+        .and_then(|t| clock_sync::timing_report(t, lead));
+    if let Some(report) = report {
+        tracing::info!(
+            steps = report.steps_measured,
+            mean_error_ms = report.mean_error_ms,
+            max_late_ms = report.max_late_ms,
+            "Sequence timing"
+        );
+        obj.insert("timing".into(), json!(report));
+    }
+    Ok(value)
+}
+
+async fn handle_tool_replay_tool(
+    state: &SharedState,
+    session: &SessionState,
+    id: Value,
+    arguments: &Value,
+) -> JsonRpcResponse {
+    let source = match (arguments.get("steps"), arguments.get("file").and_then(|v| v.as_str())) {
+        (Some(steps), None) => Ok(steps.clone()),
+        (None, Some(file)) => state
+            .capture_manager()
+            .and_then(|captures| captures.load_replay(file))
+            .map_err(|e| format!("{e:#}")),
+        _ => Err("Pass exactly one of 'steps' or 'file'".to_string()),
+    };
+    let steps = match source.and_then(|v| replay::parse_steps(&v).map_err(|e| format!("{e:#}"))) {
+        Ok(steps) => steps,
+        Err(message) => {
+            return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value());
+        }
+    };
+    let stop_on_error = arguments
+        .get("stopOnError")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true);
+
+    tracing::info!(steps = steps.len(), "Replaying tool calls");
+    let mut results = Vec::with_capacity(steps.len());
+    let mut failed = 0;
+    for (index, step) in steps.iter().enumerate() {
+        let tool = canonical_tool_name(state, &step.tool);
+        if step.delay_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(step.delay_ms)).await;
+        }
+
+        let (ok, output) = if tool == "studio-replay" {
+            (false, json!("studio-replay cannot be nested"))
+        } else {
+            let params = json!({ "name": tool, "arguments": step.arguments });
+            let response = Box::pin(dispatch_tool_call(state, session, Value::Null, params)).await;
+            step_outcome(response)
+        };
+        if !ok {
+            failed += 1;
+        }
+        let output_key = if ok { "result" } else { "error" };
+        let mut entry = json!({ "index": index, "tool": step.tool, "ok": ok });
+        entry[output_key] = output;
+        results.push(entry);
+        if !ok && stop_on_error {
+            break;
+        }
+    }
+
+    let summary = json!({
+        "steps": steps.len(),
+        "completed": results.len(),
+        "failed": failed,
+        "stoppedEarly": results.len() < steps.len(),
+        "results": results,
+    });
+    let text = serde_json::to_string_pretty(&summary).unwrap_or_default();
+    let result = if failed > 0 {
+        McpToolResult::error_text(text)
+    } else {
+        McpToolResult::text(text)
+    };
+    JsonRpcResponse::success(id, result.to_value())
+}
+
+/// Success flag and output of one replayed call. Text results that are JSON are
+/// embedded as JSON; image blocks are summarized by their MIME type.
+fn step_outcome(response: JsonRpcResponse) -> (bool, Value) {
+    if let Some(error) = response.error {
+        return (false, json!(error.message));
+    }
+    let result = response.result.unwrap_or_default();
+    let ok = result["isError"] != true;
+    let blocks: Vec<Value> = result["content"]
+        .as_array()
+        .map(|content| {
+            content
+                .iter()
+                .map(|block| match block["text"].as_str() {
+                    Some(text) => serde_json::from_str(text).unwrap_or_else(|_| json!(text)),
+                    None => json!({ "image": block["mimeType"] }),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let output = match <[Value; 1]>::try_from(blocks) {
+        Ok([single]) => single,
+        Err(blocks) => Value::Array(blocks),
+    };
+    (ok, output)
+}
+
+/// Steps of replays and transactions may use the client-facing prefix or the
+/// canonical name.
+fn canonical_tool_name(state: &SharedState, tool: &str) -> String {
+    let prefix = state.tool_prefix();
+    match tool.strip_prefix(prefix) {
+        Some(rest) if prefix != DEFAULT_TOOL_PREFIX => format!("{DEFAULT_TOOL_PREFIX}{rest}"),
+        _ => tool.to_string(),
+    }
+}
+
+async fn handle_transaction_tool(
+    state: &SharedState,
+    session: &SessionState,
+    id: Value,
+    arguments: &Value,
+    tool_timeout: Duration,
+) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+
+    let steps = match arguments.get("steps") {
+        Some(value) => match transaction::parse_steps(value) {
+            Ok(steps) => steps,
+            Err(e) => return error(format!("{e:#}")),
+        },
+        None => return error("Missing 'steps' argument".into()),
+    };
+    let budget = match transaction::budget(arguments.get("timeoutMs").and_then(|v| v.as_u64()), steps.len(), tool_timeout) {
+        Ok(budget) => budget,
+        Err(e) => return error(format!("{e:#}")),
+    };
+
+    // Every step is checked before the checkpoint opens, so a bad list changes nothing
+    let mut tools = Vec::with_capacity(steps.len());
+    for (index, step) in steps.iter().enumerate() {
+        let tool = canonical_tool_name(state, &step.tool);
+        if tool == "studio-transaction" {
+            return error(format!("Step {index}: studio-transaction cannot be nested"));
+        }
+        if step.arguments.get("async").and_then(|v| v.as_bool()) == Some(true) {
+            return error(format!("Step {index}: async calls can't run in a transaction, which has to wait for each step"));
+        }
+        if !is_tool_name(&tool) {
+            return error(format!("Step {index}: unknown tool '{}'", step.tool));
+        }
+        if let Some(reason) = disabled_reason(state, &tool) {
+            return error(format!("Step {index}: {reason}"));
+        }
+        if !tool_annotations(&tool).transaction_safe {
+            return error(format!(
+                "Step {index}: {tool} can't run in a transaction. Only tools whose edits a checkpoint can take back are allowed: {}",
+                transaction_safe_tools().join(", ")
+            ));
+        }
+        tools.push(tool);
+    }
+
+    let name = arguments
+        .get("name")
+        .and_then(|v| v.as_str())
+        .filter(|n| !n.is_empty())
+        .unwrap_or(transaction::DEFAULT_CHECKPOINT_NAME);
+    let begin = json!({ "name": "studio-checkpoint_begin", "arguments": { "name": name } });
+    let (begun, output) = step_outcome(Box::pin(dispatch_tool_call(state, session, Value::Null, begin)).await);
+    let checkpoint_id = match output.get("checkpointId").and_then(|v| v.as_str()) {
+        Some(checkpoint_id) if begun => checkpoint_id.to_string(),
+        _ => return error(format!("Could not open a checkpoint for the transaction, no step was run: {}", plugin_result_text(output))),
+    };
+
+    tracing::info!(steps = steps.len(), checkpoint = %checkpoint_id, "Running transaction");
+    // Tokio's clock, like the timeouts the remaining budget is spent on
+    let deadline = tokio::time::Instant::now() + budget;
+    let mut results = Vec::with_capacity(steps.len());
+    let mut failure = None;
+    for (index, (step, tool)) in steps.iter().zip(&tools).enumerate() {
+        let started = tokio::time::Instant::now();
+        let remaining = deadline.saturating_duration_since(started);
+        let params = json!({ "name": tool, "arguments": step.arguments });
+        let (ok, output) = match tokio::time::timeout(remaining, Box::pin(dispatch_tool_call(state, session, Value::Null, params))).await {
+            Ok(response) => step_outcome(response),
+            Err(_) => {
+                failure = Some("timedOut");
+                (false, json!(format!("Ran out of the transaction's {} ms budget", budget.as_millis())))
+            }
+        };
+        let output_key = if ok { "result" } else { "error" };
+        let mut entry = json!({ "index": index, "tool": step.tool, "ok": ok, "elapsedMs": started.elapsed().as_millis() as u64 });
+        entry[output_key] = output;
+        results.push(entry);
+        if !ok {
+            failure.get_or_insert("failed");
+            break;
+        }
+    }
+
+    let mut summary = json!({
+        "checkpointId": checkpoint_id,
+        "steps": steps.len(),
+        "completed": results.len(),
+    });
+    if failure.is_none() {
+        let end = json!({ "name": "studio-checkpoint_end", "arguments": { "checkpointId": checkpoint_id } });
+        let (ended, output) = step_outcome(Box::pin(dispatch_tool_call(state, session, Value::Null, end)).await);
+        if ended {
+            summary["committed"] = json!(true);
+            summary["results"] = json!(results);
+            let text = serde_json::to_string_pretty(&summary).unwrap_or_default();
+            return JsonRpcResponse::success(id, McpToolResult::text(text).to_value());
+        }
+        summary["commitError"] = output;
+        failure = Some("commitFailed");
+    } else {
+        summary["failedStep"] = json!(results.len() - 1);
+    }
+
+    // Cancelling the still-open recording reverts everything the steps changed
+    tracing::warn!(checkpoint = %checkpoint_id, reason = ?failure, "Transaction failed, rolling back");
+    let undo = json!({ "name": "studio-checkpoint_undo", "arguments": { "checkpointId": checkpoint_id } });
+    let (undone, output) = step_outcome(Box::pin(dispatch_tool_call(state, session, Value::Null, undo)).await);
+    let mut rollback = json!({ "ok": undone });
+    if undone {
+        rollback["action"] = output.get("action").cloned().unwrap_or(Value::Null);
+    } else {
+        rollback["error"] = output;
+    }
+    if failure == Some("timedOut") {
+        rollback["warning"] = json!("The step that ran out of time may still be running in Studio; changes it makes after the rollback are not reverted.");
+    }
+    summary["committed"] = json!(false);
+    summary["reason"] = json!(failure);
+    summary["rollback"] = rollback;
+    summary["results"] = json!(results);
+    let text = serde_json::to_string_pretty(&summary).unwrap_or_default();
+    JsonRpcResponse::success(id, McpToolResult::error_text(text).to_value())
+}
+
+/// Tools carrying `ToolAnnotations::TRANSACTION_SAFE`, for error messages.
+fn transaction_safe_tools() -> Vec<String> {
+    tool_definitions()
+        .into_iter()
+        .filter(|t| t.annotations.is_some_and(|a| a.transaction_safe))
+        .map(|t| t.name)
+        .collect()
+}
+
+fn variable_scope(arguments: &Value) -> Result<Option<Scope>, String> {
+    match arguments.get("scope") {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(raw)) => Scope::parse(raw)
+            .map(Some)
+            .ok_or_else(|| format!("Unknown scope '{raw}' (use 'session' or 'global')")),
+        Some(_) => Err("'scope' must be 'session' or 'global'".into()),
+    }
+}
+
+fn handle_var_set_tool(state: &SharedState, session: &SessionState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let Some(name) = arguments.get("name").and_then(|v| v.as_str()) else {
+        return error("Missing 'name' argument".into());
+    };
+    let scope = match variable_scope(arguments) {
+        Ok(scope) => scope.unwrap_or(Scope::Session),
+        Err(message) => return error(message),
+    };
+    let now = std::time::Instant::now();
+    let delete = arguments.get("delete").and_then(|v| v.as_bool()).unwrap_or(false);
+    let result = match (delete, arguments.get("value")) {
+        (true, Some(_)) => return error("Pass either 'value' or delete=true, not both".into()),
+        (true, None) => {
+            let removed = state.variables().remove(&session.id, scope, name, now);
+            json!({ "name": name, "scope": scope, "deleted": removed })
+        }
+        (false, None) => return error("Missing 'value' argument (any JSON value; delete=true removes the variable)".into()),
+        (false, Some(value)) => {
+            let ttl = match arguments.get("ttlSecs").map(|v| v.as_u64()) {
+                None => None,
+                Some(Some(secs)) if (1..=variables::MAX_TTL_SECS).contains(&secs) => Some(Duration::from_secs(secs)),
+                Some(_) => return error(format!("'ttlSecs' must be a whole number from 1 to {}", variables::MAX_TTL_SECS)),
+            };
+            match state.variables().set(&session.id, scope, name, value.clone(), ttl, now) {
+                Ok(description) => description,
+                Err(message) => return error(message),
+            }
+        }
+    };
+    JsonRpcResponse::success(id, McpToolResult::text(plugin_result_text(result)).to_value())
+}
+
+fn handle_var_get_tool(state: &SharedState, session: &SessionState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let error = |message: String| JsonRpcResponse::success(id.clone(), McpToolResult::error_text(message).to_value());
+    let Some(name) = arguments.get("name").and_then(|v| v.as_str()) else {
+        return error("Missing 'name' argument".into());
+    };
+    let scope = match variable_scope(arguments) {
+        Ok(scope) => scope,
+        Err(message) => return error(message),
+    };
+    let now = std::time::Instant::now();
+    let store = state.variables();
+    let (Some((_, value)), Some(mut description)) =
+        (store.get(&session.id, scope, name, now), store.describe(&session.id, scope, name, now))
+    else {
+        let place = scope.map_or("", |s| if s == Scope::Session { " in the session scope" } else { " in the global scope" });
+        return error(format!("No variable '{name}'{place}. studio-var_list shows what is set."));
+    };
+    description["value"] = value.clone();
+    JsonRpcResponse::success(id, McpToolResult::text(plugin_result_text(description)).to_value())
+}
+
+fn handle_var_list_tool(state: &SharedState, session: &SessionState, id: Value, arguments: &Value) -> JsonRpcResponse {
+    let scope = match variable_scope(arguments) {
+        Ok(scope) => scope,
+        Err(message) => return JsonRpcResponse::success(id, McpToolResult::error_text(message).to_value()),
+    };
+    let listed = state.variables().list(&session.id, scope, std::time::Instant::now());
+    let result = json!({
+        "count": listed.len(),
+        "variables": listed,
+        "limits": {
+            "variablesPerScope": variables::MAX_VARIABLES,
+            "bytesPerScope": variables::MAX_SCOPE_BYTES,
+            "bytesPerValue": variables::MAX_VALUE_BYTES,
+        },
+    });
+    JsonRpcResponse::success(id, McpToolResult::text(plugin_result_text(result)).to_value())
+}
+
+// ─── Tool Definitions ─────────────────────────────────────────
+
+/// Every tool the server implements, under its canonical `studio-` name, whatever the
+/// configuration. See [`Dispatcher::tools`] for the list clients get.
+pub fn tool_definitions() -> Vec<McpToolDef> {
+    vec![
+        McpToolDef {
+            name: "studio-status".into(),
+            description: Some("Get current Studio connection state and playtest status. Use this to verify the plugin is connected before executing other tools, or to check if a playtest is currently active. Returns connection status, playtest mode (none/play/run), server URL, any virtual keys still held, what the session has open in Studio (log subscription, checkpoints, NPC drivers), the settings each plugin reported with any mismatch warnings (pluginSettings), tool requests waiting per priority (queue), and each client's bridge traffic with the combined HttpService request rate against Roblox's 500/minute limit (traffic, httpBudget).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-run_script".into(),
+            description: Some("Execute Luau code in Studio's edit mode to modify the place structure, inspect the DataModel, or create/modify instances. Only works when NO playtest is active - this is for editing the place file itself. Returns the script's return value and any print() output. Use studio-test_script instead if you need to test runtime behavior, game logic, or anything involving Players.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "code": {
+                        "type": "string",
+                        "description": "Luau code to execute in edit mode. Can include print() statements for debugging. Use 'return <value>' to return data. Multi-line scripts are supported. Example: 'local part = Instance.new(\"Part\", workspace); part.Size = Vector3.new(4,1,2); return part.Name'. ${var:name} is replaced by a variable stored with studio-var_set."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["module", "command"],
+                        "description": "Execution mode (default: module)"
+                    },
+                    "allowInPlay": {
+                        "type": "boolean",
+                        "description": "Allow execution during a playtest session (default: false). Usually you should use studio-test_script instead."
+                    },
+                    "captureLogsMs": {
+                        "type": "number",
+                        "description": "Milliseconds to capture log output after execution (default: 0). Set to e.g. 500 to capture async print() output."
+                    },
+                    "scriptTimeoutMs": {
+                        "type": "number",
+                        "description": "Abort the script if it is still running after this many milliseconds and return a timeout error (clamped to 100-25000). Protects against accidental infinite loops; code must yield (e.g. task.wait) to be cancellable. Default: no limit besides the 30s tool timeout."
+                    },
+                    "async": {
+                        "type": "boolean",
+                        "description": "Return at once with an operation id instead of waiting for the result (default: false). Poll or wait for the result with studio-get_operation / studio-wait_operation; stop the call with studio-cancel."
+                    },
+                    "minify": {
+                        "type": "boolean",
+                        "description": "Strip comments and indentation from code before sending it to Studio (default: server setting, off unless YIPPIE_MINIFY_CODE=1). Strings and line numbers are preserved. The result includes a 'minify' object with original and minified sizes."
+                    }
+                },
+                "required": ["code"]
+            }),
+            annotations: Some(ToolAnnotations::TRANSACTION_SAFE),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-checkpoint_begin".into(),
+            description: Some("Start a named ChangeHistoryService checkpoint to track modifications you're about to make. Always call this BEFORE making changes you might want to undo later. Returns a checkpointId that you MUST save and pass to studio-checkpoint_end to commit the changes. Typical workflow: checkpoint_begin → run_script (make changes) → checkpoint_end.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Descriptive name for this checkpoint. Will appear in Studio's undo history. Be specific about what changes you're making. Example: 'Create 10 test parts' or 'Modify lighting settings'"
+                    }
+                },
+                "required": ["name"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-checkpoint_end".into(),
+            description: Some("Commit and finalize a checkpoint started with studio-checkpoint_begin. This makes the recorded changes available for undo in Studio's history. You MUST provide the checkpointId returned from the begin call. Always call this after completing your modifications - uncommitted checkpoints cannot be undone. Fails fast if the plugin reloaded since the checkpoint began, because Studio discards open recordings on reload.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "checkpointId": {
+                        "type": "string",
+                        "description": "The unique checkpoint ID returned from studio-checkpoint_begin. Required to commit the correct checkpoint."
+                    },
+                    "commitMessage": {
+                        "type": "string",
+                        "description": "Optional commit description for the undo history"
+                    }
+                },
+                "required": ["checkpointId"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-checkpoint_undo".into(),
+            description: Some("Undo the most recent committed checkpoint in Studio's ChangeHistory. Reverts all changes made in the last checkpoint operation. Works the same as Edit → Undo in Studio. Multiple calls will undo multiple checkpoints sequentially. Cannot undo past the current session start.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "checkpointId": {
+                        "type": "string",
+                        "description": "Optional: specific checkpoint to undo to. If omitted, undoes the most recent checkpoint."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-set_waypoint".into(),
+            description: Some("Set a named waypoint in Studio's undo history (ChangeHistoryService:SetWaypoint), so everything changed since the previous waypoint becomes one undoable step. Use this after changes made without studio-checkpoint_begin, e.g. by several studio-run_script calls, to make them undoable as a unit; it shows up in studio-get_recent_changes and studio-checkpoint_undo reverts it. Refused while a playtest is running or a checkpoint is still recording.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of the waypoint, shown in Studio's undo history. Example: 'Add spawn points'"
+                    }
+                },
+                "required": ["name"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_recent_changes".into(),
+            description: Some("List the recent waypoints in Studio's undo history, newest first, with the current position: entries marked 'applied' can be undone, 'undone' ones can be redone, and 'current' is the one the next undo reverts. Use this before studio-checkpoint_undo to see what an undo will revert, or to confirm a checkpoint was committed. Studio has no API that lists waypoints, so the plugin rebuilds the history from ChangeHistoryService events since it loaded (trackedSince); inSync is false once an undo or redo didn't match the list, and undoName/redoName come straight from Studio either way. Works in Edit mode.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 100,
+                        "description": "Most waypoints to return, newest first (default 20)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-checkpoint_abandon".into(),
+            description: Some("Stop tracking checkpoints whose Studio recording was lost because the plugin reloaded or disconnected (listed under unrecoverableCheckpoints in studio-status). Use this after studio-checkpoint_end reports a checkpoint can no longer be ended, then start over with studio-checkpoint_begin. Only clears the server's bookkeeping; it does not change the place. Refuses checkpoints that are still open in Studio. Answered by the server without a plugin round-trip.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "checkpointId": {
+                        "type": "string",
+                        "description": "Checkpoint to clear. Omit to clear every unrecoverable checkpoint."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-playtest_play".into(),
+            description: Some("Start a Play mode playtest session - simulates both client and server like pressing F5 in Studio. Use this when you need to test player-facing features: character movement, UI, camera controls, localscripts, or anything requiring a player character. The local player spawns and can be controlled with studio-virtualuser_* tools. The call returns once the playtest starts, a few seconds before the character spawns; pass waitFor 'characterSpawned' to return only when the character is ready for input (the result then has 'ready'). Use studio-playtest_run instead for server-only testing without a player character, or studio-test_script for quick one-off tests.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "waitFor": {
+                        "type": "string",
+                        "enum": ["serverStarted", "playerAdded", "characterSpawned"],
+                        "description": "Return only once the playtest reaches this stage, as with studio-playtest_wait_ready. If it isn't reached in time the call fails, but the playtest keeps running."
+                    },
+                    "waitTimeoutMs": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": readiness::MAX_READY_TIMEOUT_MS,
+                        "description": "How long to wait for waitFor (default 30000)."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-playtest_run".into(),
+            description: Some("Start a Run mode playtest session - server-only simulation like pressing F8 in Studio. Use this for testing server scripts, game logic, and systems that don't require a player character. No local player spawns, making it faster than Play mode. Use studio-playtest_play if you need to test player interactions or client-side features, or studio-test_script for quick one-off tests.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-playtest_stop".into(),
+            description: Some("Stop the currently active playtest and return Studio to edit mode. Works for both Play mode (F5) and Run mode (F8) playtests. Always call this when you're done testing to free up resources and allow edit-mode script execution again. Automatically called by studio-test_script, but required when manually starting playtests with studio-playtest_play or studio-playtest_run.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "sessionId": {
+                        "type": "string",
+                        "description": "Optional session ID to stop. If omitted, stops the current active playtest."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-playtest_wait_ready".into(),
+            description: Some("Wait until the running playtest reaches a readiness stage: serverStarted (the playtest bridge is up on the game server), playerAdded (the local player joined) or characterSpawned (the character has its Humanoid and root part, so studio-virtualuser_* and other character tools work). Stages after serverStarted need Play mode. Returns ready, the level asked for, the stage reached, sessionId, mode, player and waitedMs; fails with playtest_not_ready and the stage reached if the timeout passes first, or right away if no playtest is running. A playtest_play/run call still in progress counts as a playtest about to run. Current readiness is also in studio-status under playtest.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "level": {
+                        "type": "string",
+                        "enum": ["serverStarted", "playerAdded", "characterSpawned"],
+                        "description": "Stage to wait for (default characterSpawned)."
+                    },
+                    "timeoutMs": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": readiness::MAX_READY_TIMEOUT_MS,
+                        "description": "Longest wait in milliseconds (default 30000)."
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-test_script".into(),
+            description: Some("Execute Luau code inside a live playtest environment to test game logic, physics, character movement, Players service, or any runtime behavior. Automatically starts a playtest, runs your code in the game server, captures all logs and errors, stops the playtest, and returns results. Use this instead of studio-run_script when testing gameplay features, server scripts, or anything requiring a running game. Cannot modify the place structure - use studio-run_script for that. Returns: success (bool), value (return value), error (if failed), logs (all captured output), errors (warnings/errors only), duration (seconds). If Studio does not answer within timeout plus 15 seconds, returns a partial result instead of an error: timedOut true, errorCode 'TIMEOUT', the logs received so far for the test's session, and the last known playtest state.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "code": {
+                        "type": "string",
+                        "description": "Luau code to execute during playtest. Runs in server context. Can access running game services like Players, RunService, ReplicatedStorage. Use print() for debugging output. Example: 'local players = game.Players:GetPlayers(); print(#players .. \" players in game\"); return workspace.Gravity'. ${var:name} is replaced by a variable stored with studio-var_set."
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["run", "play"],
+                        "description": "Playtest mode: 'run' (server only, faster, no player character) or 'play' (client+server, player spawns). Default: 'run'"
+                    },
+                    "timeout": {
+                        "type": "number",
+                        "description": "Max seconds to wait for the test to complete before force-stopping. Default: 30. Increase for long-running tests."
+                    },
+                    "minify": {
+                        "type": "boolean",
+                        "description": "Strip comments and indentation from code before sending it to Studio (default: server setting, off unless YIPPIE_MINIFY_CODE=1). Strings and line numbers are preserved. The result includes a 'minify' object with original and minified sizes."
+                    },
+                    "async": {
+                        "type": "boolean",
+                        "description": "Return at once with an operation id instead of waiting for the result (default: false). Poll or wait for the result with studio-get_operation / studio-wait_operation; stop the call with studio-cancel."
+                    }
+                },
+                "required": ["code"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-logs_subscribe".into(),
+            description: Some("Subscribe to real-time Studio log output to capture print() statements, errors, and warnings from scripts. Must be called before studio-logs_get will return any data. Logs are buffered in memory until you unsubscribe. Use includeHistory: true to receive logs from before subscription. Essential for debugging script execution. Always unsubscribe when finished to prevent memory buildup.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "channels": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
+                        "description": "Log levels to subscribe to (default: all). Filter to specific levels to reduce noise."
+                    },
+                    "includeHistory": {
+                        "type": "boolean",
+                        "description": "Whether to include logs generated before subscribing (default: true). Set to true if you need to see output from scripts that ran earlier in the session."
+                    },
+                    "maxHistory": {
+                        "type": "number",
+                        "description": "Max history entries to return (default: 200). Historical buffer is limited."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-logs_unsubscribe".into(),
+            description: Some("Stop receiving log output and clear the log buffer. Call this when you're done monitoring logs to free up memory. After unsubscribing, studio-logs_get will fail until you subscribe again. Safe to call even if not subscribed.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-logs_get".into(),
+            description: Some("Fetch buffered log entries that have accumulated since subscribing with studio-logs_subscribe. Returns all captured print() output, errors, and warnings. Requires an active subscription - call studio-logs_subscribe first. Logs are cleared from the buffer after retrieval.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "sinceSeq": {
+                        "type": "number",
+                        "description": "Return only logs after this sequence number. Use to paginate or avoid re-reading old entries."
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Max entries to return (default: 200)"
+                    },
+                    "levels": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
+                        "description": "Filter by log level. Omit to get all levels."
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["json", "text", "csv"],
+                        "description": "Rendering of the text result (default: json). 'text' is an aligned table, 'csv' is RFC 4180 CSV. When set, the full JSON result is also returned in structuredContent."
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_output_since".into(),
+            description: Some("Fetch log entries received in the last N seconds, e.g. 'what was printed in the last 5 seconds'. Use when you don't have a sequence number to pass to studio-logs_get. Reads the server's log buffer (last 500 entries), so logs must be flowing via studio-logs_subscribe. Answered by the server without a plugin round-trip. Returns entries plus nextSeq for follow-up studio-logs_get calls.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "seconds": {
+                        "type": "number",
+                        "description": "Window size: return entries received within this many seconds before now."
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Max entries to return, keeping the most recent (default: 200)"
+                    },
+                    "levels": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
+                        "description": "Filter by log level. Omit to get all levels."
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["json", "text", "csv"],
+                        "description": "Rendering of the text result (default: json). 'text' is an aligned table, 'csv' is RFC 4180 CSV. When set, the full JSON result is also returned in structuredContent."
+                    }
+                },
+                "required": ["seconds"]
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_errors_grouped".into(),
+            description: Some("Summarize errors in the log buffer as distinct messages with counts, e.g. '3 distinct errors, one repeated 400 times', instead of reading every repeated line. Groups entries with the same level and message and returns them most frequent first, each with count and first/last receive time (firstTs/lastTs, Unix seconds) and sequence number. Reads the server's log buffer (last 500 entries), so logs must be flowing via studio-logs_subscribe; bufferFull means older entries were dropped, so counts cover only the buffer. Answered by the server without a plugin round-trip. Use studio-logs_get with the sequence numbers to see the lines around an error.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "levels": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
+                        "description": "Levels to group (default: [\"error\"])."
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Max groups to return, most frequent first (default: 20). 'distinct' still counts all of them."
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["json", "text", "csv"],
+                        "description": "Rendering of the text result (default: json). 'text' is an aligned table, 'csv' is RFC 4180 CSV. When set, the full JSON result is also returned in structuredContent."
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-list_tools_with_routing".into(),
+            description: Some("Debug tool: show which connected client each tool would be sent to right now, to diagnose a call landing on the wrong one (e.g. a virtualuser command reaching the edit plugin instead of the playtest bridge). Lists the connected clients with their role ('plugin' or 'bridge'), last poll and queue depths, and for every tool the role it prefers (overridden: true when YIPPIE_ROUTING_OVERRIDES sets it), the client and role it routes to, and fallback: true when no client of the preferred role is connected. Tools the server answers itself are listed too but never reach this routing. Only offered when the server runs with YIPPIE_DEBUG_ENDPOINTS=1.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "filter": {
+                        "type": "string",
+                        "description": "Only list tools whose name contains this text. Example: 'virtualuser'"
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_tool_history".into(),
+            description: Some("List recent tool calls on this server: tool name, start time (ts), success, durationMs, whether the response cache answered, the MCP client and session, and for failures the first line of the error. Use it to recover context after a reconnect or to see what another client connected to the same server just did. Arguments and results aren't kept. History is shared by all MCP sessions, holds the last 200 calls by default (YIPPIE_TOOL_HISTORY_SIZE) and is lost on restart; calls to this tool are not recorded. Returns the latest matching calls oldest first, with seq numbers for sinceSeq; 'truncated' means older matches were left out. Answered by the server without a plugin round-trip.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "number",
+                        "description": "Max calls to return, the most recent ones (default: 20)."
+                    },
+                    "tool": {
+                        "type": "string",
+                        "description": "Only calls to this tool."
+                    },
+                    "sinceSeq": {
+                        "type": "number",
+                        "description": "Only calls after this seq, e.g. the last one seen in a previous answer."
+                    },
+                    "failedOnly": {
+                        "type": "boolean",
+                        "description": "Only calls that failed (default: false)."
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["json", "text", "csv"],
+                        "description": "Rendering of the text result (default: json). 'text' is an aligned table, 'csv' is RFC 4180 CSV. When set, the full JSON result is also returned in structuredContent."
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_operation".into(),
+            description: Some(format!("Poll a call started with async: true (studio-run_script, studio-test_script, studio-wait_for_condition) by the id it returned. Returns the operation: id, tool, status ('running', 'succeeded', 'failed' or 'cancelled'), startedAt and elapsedMs, and once it has finished, result (what the call would have returned without async) and expiresInSecs. Finished operations are kept for {} minutes (the last 50 at most), in memory only. Answers at once, without a plugin round-trip; studio-wait_operation blocks instead.", async_calls::FINISHED_CALL_TTL.as_secs() / 60)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The id the async call returned."
+                    }
+                },
+                "required": ["id"]
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-wait_operation".into(),
+            description: Some(format!("Block until a call started with async: true finishes, or timeoutMs (default {}, max {}) passes, then return the operation as studio-get_operation does, with timedOut set when it is still running. Waiting doesn't affect the call; wait again or cancel it with studio-cancel.", async_calls::DEFAULT_OPERATION_WAIT_MS, async_calls::MAX_RESULT_WAIT_MS)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The id the async call returned."
+                    },
+                    "timeoutMs": {
+                        "type": "number",
+                        "description": "Wait at most this many milliseconds (default 30000, max 60000)."
+                    }
+                },
+                "required": ["id"]
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-cancel".into(),
+            description: Some("Cancel a call started with async: true, by the id it returned (the one studio-get_operation and studio-wait_operation take). The call ends at once with status 'cancelled' and a result arriving later is discarded. If Studio hasn't picked the call up yet it is taken out of the queue; otherwise the plugin cancels the thread running it, like run_script's scriptTimeoutMs does, so code must yield (e.g. task.wait) to be stopped, and work it already started (a playtest begun by studio-test_script, threads it spawned) keeps going. Returns cancelled, elapsedMs and plugin: whether Studio stopped the call (stopped) with a note when it couldn't confirm. Only async calls can be cancelled; an MCP notifications/cancelled for a normal call is just logged.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "The id the async call returned."
+                    }
+                },
+                "required": ["id"]
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-logs_search_archive".into(),
+            description: Some("Search logs from past and current sessions in the on-disk log archive, e.g. 'when did this error first appear?'. Requires the server to run with YIPPIE_LOG_ARCHIVE=1; the in-memory buffer used by studio-logs_get only holds the last 500 entries. Matches whole words (case-insensitive); hits are ranked by how many query words match, with exact phrase matches first, then newest first. Answered by the server without a plugin round-trip. While older sessions are still being indexed after startup, results may be incomplete (see 'indexing').".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "Words to search for. Omit to list entries matching the other filters."
+                    },
+                    "from": {
+                        "type": "string",
+                        "description": "Only entries at or after this time (RFC 3339, e.g. 2025-01-31T14:00:00Z)"
+                    },
+                    "to": {
+                        "type": "string",
+                        "description": "Only entries at or before this time (RFC 3339)"
+                    },
+                    "levels": {
+                        "type": "array",
+                        "items": { "type": "string", "enum": ["output", "info", "warning", "error"] },
+                        "description": "Filter by log level. Omit to get all levels."
+                    },
+                    "sessionId": {
+                        "type": "string",
+                        "description": "Only entries from this playtest session"
+                    },
+                    "limit": {
+                        "type": "number",
+                        "description": "Max hits to return (default: 50, max: 500)"
+                    },
+                    "format": {
+                        "type": "string",
+                        "enum": ["json", "text", "csv"],
+                        "description": "Rendering of the text result (default: json). 'text' is an aligned table, 'csv' is RFC 4180 CSV. When set, the full JSON result is also returned in structuredContent."
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-logs_diff_sessions".into(),
+            description: Some("Compare the logs of two playtest sessions, e.g. a known-good run against the current one, to see what changed. Lines are '[level] message'; timestamps, GUIDs, hex ids and numbers are replaced by placeholders first (normalize: false compares raw text; extra patterns via YIPPIE_LOG_DIFF_NORMALIZERS), and consecutive repeats collapse into one line with a count. Reads the log archive when YIPPIE_LOG_ARCHIVE=1, else the in-memory buffer (last 500 entries). Answered by the server without a plugin round-trip. Returns per-session counts, onlyInBaseline / onlyInCurrent (distinct lines with counts, most frequent first), countChanges (lines in both whose count changed by at least minCountDelta and 2x), a unified diff excerpt (diff, diffTruncated) and an artifact capture holding the full diff, saved in the capture folder or in outputDir. Sessions too different to align are compared as line sets (algorithm: 'multiset').".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "baselineSessionId": {
+                        "type": "string",
+                        "description": "Session to compare against, e.g. a run that behaved correctly. Session ids appear in studio-playtest_status and in log entries (sessionId)."
+                    },
+                    "currentSessionId": {
+                        "type": "string",
+                        "description": "Session to compare."
+                    },
+                    "normalize": {
+                        "type": "boolean",
+                        "description": "Replace timestamps, GUIDs, hex ids and numbers with placeholders before comparing. Default: true."
+                    },
+                    "contextLines": {
+                        "type": "number",
+                        "description": "Unchanged lines shown around each change in the diff (default 2, max 10)."
+                    },
+                    "maxLines": {
+                        "type": "number",
+                        "description": "Diff lines returned inline (default 200, max 1000). The artifact always holds the full diff."
+                    },
+                    "minCountDelta": {
+                        "type": "number",
+                        "description": "Smallest count difference reported in countChanges (default 5). The larger count must also be at least twice the smaller."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag stored with the diff capture in index.json."
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to write the full diff to instead of log_diffs/ in the capture folder. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: the capture folder."
+                    }
+                },
+                "required": ["baselineSessionId", "currentSessionId"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_key".into(),
+            description: Some("Simulate keyboard input for the player character during Play mode playtest (F5). Control character movement (W/A/S/D), jumping (Space), and sprinting (LeftShift/RightShift). Keys stay held until explicitly released with action 'up'. Use 'down' to start holding a key, do other things, then 'up' to release. Space triggers a single jump. Only works during Play mode with a spawned character. Requires studio-playtest_play to be called first.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "keyCode": {
+                        "type": "string",
+                        "enum": ["W", "A", "S", "D", "Space", "LeftShift", "RightShift"],
+                        "description": "Keyboard key to simulate. W=forward, A=left, S=backward, D=right, Space=jump, LeftShift/RightShift=sprint."
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["down", "up"],
+                        "description": "'down' = start holding key (default), 'up' = release key. Keys stay held until released. For jumping, just send 'down' once."
+                    }
+                },
+                "required": ["keyCode"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_mouse_button".into(),
+            description: Some("Simulate mouse click at the player's position during Play mode. Performs a raycast from the character's head toward a world position or named instance to detect and interact with world objects. Reports what was hit (instance name, class, position, distance, material) and detects interactive elements (ClickDetectors, ProximityPrompts). Only works during Play mode (F5) with a spawned character.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "button": {
+                        "type": "integer",
+                        "enum": [1, 2],
+                        "description": "Mouse button number. 1=left click (primary), 2=right click (secondary). Most interactions use button 1."
+                    },
+                    "action": {
+                        "type": "string",
+                        "enum": ["click"],
+                        "description": "Action type. Currently only 'click' is supported."
+                    },
+                    "worldPosition": {
+                        "type": "object",
+                        "properties": {
+                            "x": { "type": "number" },
+                            "y": { "type": "number" },
+                            "z": { "type": "number" }
+                        },
+                        "required": ["x", "y", "z"],
+                        "description": "World-space position to raycast toward from the character's head. Provide this OR target."
+                    },
+                    "target": {
+                        "type": "string",
+                        "description": "Instance path to target (e.g. 'Workspace.MyPart'). If it's a BasePart and no worldPosition given, its position is used. Provide this OR worldPosition."
+                    }
+                },
+                "required": ["button", "action"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_move_mouse".into(),
+            description: Some("Set the player character's facing direction during Play mode by rotating the HumanoidRootPart to face toward a world position (horizontal rotation only). Use for controlling where the character looks, affecting camera angle and character rotation. Only works during Play mode (F5) with a spawned character. Requires studio-playtest_play to be called first.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "lookAt": {
+                        "type": "object",
+                        "properties": {
+                            "x": { "type": "number" },
+                            "y": { "type": "number" },
+                            "z": { "type": "number" }
+                        },
+                        "required": ["x", "y", "z"],
+                        "description": "World-space position to face toward. The character rotates horizontally to look at this point."
+                    }
+                },
+                "required": ["lookAt"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_version".into(),
+            description: Some("Report the Roblox Studio version, the YippieBlox plugin version(s), and the MCP server version in one call. Use this to record the exact environment in bug reports or when behavior differs between machines. Server and plugin versions are answered by the server; the Studio version comes from the plugin via version(). If the plugin is not connected or doesn't answer, studioVersion is null and studioVersionError explains why. Repeat calls within YIPPIE_CACHE_TTL_SECS are answered from the server's response cache.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::CACHEABLE),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_current_tool".into(),
+            description: Some("Report which Studio edit tool the user has selected (Select, Move, Scale, Rotate, Transform, ...) and the current grid size in studs. Use this in collaborative sessions to match suggestions to how the user is working, e.g. phrasing placement advice in grid steps when they are using Move with a 1-stud grid. Works in Edit mode. Values Studio doesn't expose to plugins are omitted and listed in 'unavailable'; the rotation snap increment is not exposed at all.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-get_bindable_actions".into(),
+            description: Some("List the actions the game has bound with ContextActionService during a Play mode playtest, with the inputs each is bound to (e.g. KeyCode.E, UserInputType.MouseButton1), priority and touch-button settings. Use this before simulating input to learn the game's real controls instead of guessing key bindings. Requires an active Play mode playtest (F5) with a player; bindings are read on the client. Returns { player, count, actions: [{ name, inputs, priority, stackOrder, createTouchButton, title, description }] } plus a note when nothing is bound (the game may read input through UserInputService instead).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_humanoid_description".into(),
+            description: Some("Snapshot the HumanoidDescription applied to a player's character during a Play mode playtest: rig type, body colors (hex), body part and clothing asset ids, scales, animation ids and accessories (assetId, type, layered), plus the accessories the character actually wears (equipped), which also covers ones added outside the description. Use this to verify avatar features such as outfit changers, scaling or team colors after triggering them. Requires Play mode (F5) with a spawned character; returns an error while the character is missing or respawning.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_proximity_prompts".into(),
+            description: Some("List the ProximityPrompts in Workspace near the player's character during a Play mode playtest, nearest first: path, actionText, objectText, holdDuration, enabled, keyboardKeyCode, maxActivationDistance, requiresLineOfSight, distance (studs from the character's root part) and inRange (within the prompt's own activation distance). Use this to discover what the player can interact with before walking there with studio-virtualuser_key, instead of raycasting blindly. Requires Play mode (F5) with a spawned character.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "radius": {
+                        "type": "number",
+                        "description": "Search radius in studs around the character (default 100, max 2000)"
+                    },
+                    "includeDisabled": {
+                        "type": "boolean",
+                        "description": "Include prompts with Enabled = false (default true)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most prompts to return (default 50, max 200); 'count' reports how many matched"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-trigger_proximity_prompt".into(),
+            description: Some("Hold and complete a ProximityPrompt as the player during a Play mode playtest, the way pressing its key would, and wait for its Triggered event. The hold runs on the player's client (for the prompt's HoldDuration), so the prompt must be enabled and shown to the player: in range, and in line of sight if it requires that. Returns triggered, the hold time and how long Triggered took; if Triggered doesn't fire within timeoutMs, returns an error with the prompt's distance and state. Find paths with studio-get_proximity_prompts first. Requires Play mode (F5) with a spawned character.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full name of the ProximityPrompt under Workspace, e.g. 'Workspace.Door.Handle.ProximityPrompt'"
+                    },
+                    "timeoutMs": {
+                        "type": "number",
+                        "description": "How long to wait for Triggered, including the hold (default 10000, 1000-60000)"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-click_gui_button".into(),
+            description: Some("Click a GUI button (TextButton or ImageButton) as the player during a Play mode playtest and wait until its Activated or MouseButton1Click handlers have run. The click happens at the button's centre on the player's client, so the button must be visible, Active, in an enabled ScreenGui and not covered by another GUI object. Returns which events fired and the topmost GUI object at the click point; if neither fires within 3 seconds, returns an error naming that object. Use for menus and UI flows that studio-virtualuser_mouse_button can't reach, since it only raycasts into the 3D world. Requires Play mode (F5).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "The button's path: 'StarterGui.<ScreenGui>.<...>.<Button>' (looked up in the player's PlayerGui) or 'Players.<name>.PlayerGui.<...>'"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_gui_tree".into(),
+            description: Some("Map the player's on-screen GUI during a Play mode playtest: the PlayerGui hierarchy as a tree of ScreenGuis, frames, buttons, labels and text boxes, read on the player's client. Each GUI object has className, position and size (pixels, screen space including the top bar inset, the same space studio-click_gui_button clicks in), zIndex, text for text objects, visible (it and its ancestors are Visible in an enabled ScreenGui, and it overlaps the screen), and for buttons and text boxes interactable (visible and Active/TextEditable; not checked for being covered). Scripts, layouts and constraints are left out. Bounded by maxDepth and maxNodes: cut-off branches report omittedChildren, and truncated is set when the node limit was hit. Use it to find what to click before studio-click_gui_button. Requires Play mode (F5).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Start here instead of the whole PlayerGui: 'StarterGui.<ScreenGui>.<...>' (looked up in the player's PlayerGui) or 'Players.<name>.PlayerGui.<...>'"
+                    },
+                    "maxDepth": {
+                        "type": "integer",
+                        "description": "Levels below the root to include (default 8, max 16)"
+                    },
+                    "maxNodes": {
+                        "type": "integer",
+                        "description": "Most objects to return (default 200, max 1000)"
+                    },
+                    "visibleOnly": {
+                        "type": "boolean",
+                        "description": "Leave out hidden objects and everything under them: not Visible, or in a disabled ScreenGui (default false)"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-gui_tree".into(),
+            description: Some("Page through the player's on-screen GUI during a Play mode playtest, as a flat list in tree order. Each element has its full path, name, className, depth, parent (index of its parent element), position and size (pixels, screen space including the top bar inset), visible, interactable for buttons and text boxes, text for text objects and zIndex. The server keeps the snapshot it reads (up to 1000 objects, 16 levels deep): a call within a few frames of another with the same arguments reuses it, and nextCursor continues the same snapshot, so pages don't shift under you. Pass refresh: true to read the GUI again. studio-gui_click resolves its selectors against these snapshots. Requires Play mode (F5).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Start here instead of the whole PlayerGui: 'StarterGui.<ScreenGui>.<...>' (looked up in the player's PlayerGui) or 'Players.<name>.PlayerGui.<...>'"
+                    },
+                    "visibleOnly": {
+                        "type": "boolean",
+                        "description": "Leave out hidden objects and everything under them (default false)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Elements per page (default 100, max 500)"
+                    },
+                    "cursor": {
+                        "type": "string",
+                        "description": "nextCursor from a previous page, to continue its snapshot"
+                    },
+                    "refresh": {
+                        "type": "boolean",
+                        "description": "Read the GUI again even if a snapshot from a few frames ago would do (default false)"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-gui_click".into(),
+            description: Some("Click a GUI button during a Play mode playtest, named by path or by what it says: \"button containing 'Play'\" (text contains, ignoring case), \"button with text 'Play'\" (exact text), \"button named 'PlayButton'\" (instance Name); label, textbox, image and element select other kinds. A button whose caption is a label inside it is found by the label's text. The server resolves the selector against the latest GUI snapshot when it is at most maxAgeMs old, from this playtest and taken since the last click; otherwise, or when nothing matches there, it reads the GUI again. Only visible matches count, in tree order; more than one is an error listing them unless nth picks one. The resolved path is clicked through studio-click_gui_button. Returns the click, the target (path, className, text, center, nth of matches), moved when the client clicked away from the snapshot's centre, and the snapshot's age. Requires Play mode (F5).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "selector": {
+                        "type": "string",
+                        "description": "\"<kind> containing|with text|named '<text>'\" (kind: button, label, textbox, image, element), or a GUI path"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "GUI path instead of a selector: 'StarterGui.<ScreenGui>.<...>' or 'Players.<name>.PlayerGui.<...>'"
+                    },
+                    "nth": {
+                        "type": "integer",
+                        "description": "Which visible match to click, 1-based in tree order (needed when more than one matches)"
+                    },
+                    "maxAgeMs": {
+                        "type": "integer",
+                        "description": "Oldest snapshot to resolve against, in milliseconds (default 2000, max 60000; 0 always reads the GUI again)"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player name. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_sound_playing".into(),
+            description: Some("List the Sounds playing right now in a running playtest, to check that audio triggers fired: path, side ('server', or 'client' for sounds only the player's client plays, e.g. from LocalScripts), soundId, volume, timePosition and timeLength (seconds), looped and playbackSpeed. Server sounds are searched in Workspace and SoundService; with a player (Play mode), the client's Workspace, SoundService and PlayerGui are searched too. Sorted by path and bounded by limit; 'count' reports how many are playing. If the client can't be asked, server sounds are still returned with 'clientError'. Requires a running playtest (Play or Run).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "includeClient": {
+                        "type": "boolean",
+                        "description": "Also list sounds playing on the player's client (default true; needs Play mode)"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most sounds to return (default 50, max 200); 'count' reports how many are playing"
+                    },
+                    "player": {
+                        "type": "string",
+                        "description": "Player whose client to ask. Defaults to the first player (the only one in a solo Play session)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_network_ownership".into(),
+            description: Some("Report who simulates a part's physics during a playtest: the network owner (a player's name or 'server'), whether the engine assigns it automatically, and whether it can be set at all (anchored parts and parts welded to anchored ones can't; cannotSetReason says why). Ownership belongs to the part's whole assembly, so assemblyRoot names the root part whose ownership the part shares. Use this when server-side physics changes (velocity, position) don't stick because a client owns the part. Requires an active playtest; the path must name a BasePart.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the part, e.g. 'Workspace.Ball'"
+                    }
+                },
+                "required": ["path"]
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_replication_stats".into(),
+            description: Some(format!("Measure network replication during a playtest: the server's data and physics send/receive rates (KB/s, from the Stats service) averaged over a short sampling window with their peaks, the server heartbeat time, and each player's ping. sendPerPlayerKbps divides the data send rate by the number of players; notes flag rates near what a player's connection sustains. Use it after adding RemoteEvents, replicated values or many moving parts to catch replication that is more than the game needs; compare a run with and without the change. Samples for windowMs ({}-{} ms, default {}). Metrics the Studio build doesn't expose are listed under unavailable. Requires an active playtest.", MIN_REPLICATION_WINDOW_MS, MAX_REPLICATION_WINDOW_MS, DEFAULT_REPLICATION_WINDOW_MS)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "windowMs": {
+                        "type": "number",
+                        "description": format!("Sampling window in milliseconds ({MIN_REPLICATION_WINDOW_MS}-{MAX_REPLICATION_WINDOW_MS}). Default: {DEFAULT_REPLICATION_WINDOW_MS}.")
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_network_ownership".into(),
+            description: Some("Set who simulates a part's physics during a playtest: a player by name, 'server', or back to automatic assignment with auto=true. Applies to the part's whole assembly and lasts until the playtest stops (or the engine reassigns it, for auto). Fails for parts whose ownership can't be set, such as anchored ones. Returns the ownership before and after, in the shape of studio-get_network_ownership. Requires an active playtest; the path must name a BasePart.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the part, e.g. 'Workspace.Ball'"
+                    },
+                    "owner": {
+                        "type": "string",
+                        "description": "Player name, or 'server'. Omit when auto is true."
+                    },
+                    "auto": {
+                        "type": "boolean",
+                        "description": "Hand ownership back to the engine's automatic assignment (SetNetworkOwnershipAuto)"
+                    }
+                },
+                "required": ["path"]
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_keybinds".into(),
+            description: Some("Report which virtual keys the server believes are currently held on the player character (from successful studio-virtualuser_key and studio-virtualuser_sequence calls). Use after a series of inputs to check nothing is left stuck held, then release stragglers with studio-virtualuser_key action 'up'. Answered by the server; works without a playtest.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {},
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_sequence".into(),
+            description: Some("Play a timed sequence of key presses and look directions on the player character during Play mode. Each step fires at its 'atMs' offset from the start of the sequence, scheduled against one start time so delays don't accumulate. Once the server has estimated the bridge's clock offset, the schedule is sent as absolute times on the bridge's clock (after a short lead buffer), so bridge latency doesn't skew it, and the result's 'timing' reports each step's error from its schedule. Any movement keys still held when the sequence ends are released. Blocks until the last step has run. Only works during Play mode (F5) with a spawned character.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "steps": {
+                        "type": "array",
+                        "description": "Steps in any order; they are run sorted by atMs. Each step sets 'keyCode' (+ optional 'action', default 'down'), 'lookDirection' ({x,y,z} facing vector) or 'lookAt' ({x,y,z} world position).",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "atMs": { "type": "integer", "minimum": 0, "description": "Milliseconds from the start of the sequence." },
+                                "keyCode": { "type": "string", "enum": ["W", "A", "S", "D", "Space", "LeftShift", "RightShift"] },
+                                "action": { "type": "string", "enum": ["down", "up"] },
+                                "lookDirection": {
+                                    "type": "object",
+                                    "properties": { "x": { "type": "number" }, "y": { "type": "number" }, "z": { "type": "number" } }
+                                },
+                                "lookAt": {
+                                    "type": "object",
+                                    "properties": { "x": { "type": "number" }, "y": { "type": "number" }, "z": { "type": "number" } }
+                                }
+                            },
+                            "required": ["atMs"]
+                        }
+                    }
+                },
+                "required": ["steps"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_record_start".into(),
+            description: Some("Start recording a human play session for later replay. While recording, the playtest bridge samples the player character's movement (as W/A/S/D/Space transitions) and facing direction and streams them to the server. Play the game yourself, then call studio-virtualuser_record_stop to save the recording. Only works during Play mode (F5) with a spawned character. One recording at a time.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Recording name (letters, digits, '_' and '-'; max 64). Saved as recordings/<name>.json in the capture folder, replacing an older recording of the same name."
+                    },
+                    "maxSeconds": {
+                        "type": "number",
+                        "description": "Stop sampling automatically after this many seconds (default 60, max 300)."
+                    }
+                },
+                "required": ["name"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_record_stop".into(),
+            description: Some("Stop the input recording started with studio-virtualuser_record_start and save it to the capture folder. Returns the file path, duration and sample counts. Keys still held at the end are recorded as released.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {}
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-virtualuser_replay".into(),
+            description: Some("Replay a saved input recording on the player character as a timed sequence (see studio-virtualuser_sequence). Use for regression checks: record once with studio-virtualuser_record_start/stop, then replay after changes and compare logs or screenshots. Blocks until the replay finishes. Only works during Play mode (F5) with a spawned character.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Name of a recording saved by studio-virtualuser_record_stop."
+                    },
+                    "speed": {
+                        "type": "number",
+                        "description": "Playback speed factor, 0.25 to 4 (default 1). 2 replays twice as fast."
+                    }
+                },
+                "required": ["name"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-npc_driver_start".into(),
+            description: Some("Start controlling any NPC character (any Model with a Humanoid) during Play mode playtest. Enables AI-style control for testing NPC movement, pathfinding, and behavior. Returns a driverId you MUST use for subsequent studio-npc_driver_command and studio-npc_driver_stop calls. Multiple NPCs can be controlled simultaneously. Stop control with studio-npc_driver_stop when finished.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "target": {
+                        "type": "string",
+                        "description": "Full instance path to the NPC character model. Must contain a Humanoid. Example: 'Workspace.NPCModel' or 'Workspace.Enemies.Zombie1'. Case-sensitive."
+                    }
+                },
+                "required": ["target"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-npc_driver_command".into(),
+            description: Some("Send movement and behavior commands to an NPC being controlled by studio-npc_driver_start. Available commands: 'move_to' (navigate to world position), 'jump', 'wait' (pause for duration), 'set_walkspeed' (change movement speed), and 'look_at' (face a position). Commands execute synchronously - move_to blocks until the NPC arrives or times out. Only works during Play mode with an active driver.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "driverId": {
+                        "type": "string",
+                        "description": "Driver ID returned from studio-npc_driver_start. Required to identify which NPC to command."
+                    },
+                    "command": {
+                        "type": "object",
+                        "description": "Command to execute on the NPC.",
+                        "properties": {
+                            "type": {
+                                "type": "string",
+                                "enum": ["move_to", "jump", "wait", "set_walkspeed", "look_at"],
+                                "description": "Command type. 'move_to' navigates to position, 'jump' makes NPC jump, 'wait' pauses for ms duration, 'set_walkspeed' changes speed, 'look_at' rotates to face position."
+                            },
+                            "position": {
+                                "type": "object",
+                                "properties": {
+                                    "x": { "type": "number" },
+                                    "y": { "type": "number" },
+                                    "z": { "type": "number" }
+                                },
+                                "description": "Target world position for 'move_to' and 'look_at'. Example: {x: 10, y: 0, z: 20}"
+                            },
+                            "ms": {
+                                "type": "number",
+                                "description": "Duration in milliseconds for 'wait' command. Example: 2000 for 2 seconds."
+                            },
+                            "value": {
+                                "type": "number",
+                                "description": "Value for 'set_walkspeed'. Default Roblox character WalkSpeed is 16. Range: 0-100+."
+                            },
+                            "timeout": {
+                                "type": "number",
+                                "description": "Max seconds to wait for 'move_to' to complete before giving up (default: 15)."
+                            }
+                        },
+                        "required": ["type"]
+                    }
+                },
+                "required": ["driverId", "command"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-npc_driver_stop".into(),
+            description: Some("Stop controlling an NPC that was started with studio-npc_driver_start. Releases control, stops all movement, and clears any queued commands. The NPC will return to idle. Always call this when finished controlling an NPC to free up resources. Safe to call even if the NPC isn't being controlled.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "driverId": {
+                        "type": "string",
+                        "description": "Driver ID returned from studio-npc_driver_start. Identifies which NPC to stop controlling."
+                    }
+                },
+                "required": ["driverId"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-test_matrix".into(),
+            description: Some("Run the same test code (like studio-test_script) under several variants in one call, e.g. Run and Play mode, with and without a Lighting or Workspace tweak. Variants run one after another: optional edit-mode setup code (via studio-run_script), property overrides applied, the test playtest started and stopped, overrides reverted. A failing variant does not stop the rest unless failFast is set. Setup code changes the edit DataModel and is not undone. Returns totals (passed/failed/skipped), a per-variant summary (status, error, value, duration, log and error counts) and an artifact capture holding every variant's full logs, saved in the capture folder or in outputDir.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "code": {
+                        "type": "string",
+                        "description": "Luau test code run in the game server of each variant's playtest, exactly as in studio-test_script."
+                    },
+                    "variants": {
+                        "type": "array",
+                        "minItems": 1,
+                        "maxItems": 16,
+                        "description": "Configurations to run the code under, in order.",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "name": {
+                                    "type": "string",
+                                    "description": "Label for the summary. Default: 'variant <index>'."
+                                },
+                                "mode": {
+                                    "type": "string",
+                                    "enum": ["run", "play"],
+                                    "description": "Playtest mode. Default: 'run'."
+                                },
+                                "setup": {
+                                    "type": "string",
+                                    "description": "Edit-mode Luau run with studio-run_script before the test. Its changes stay in the place."
+                                },
+                                "properties": {
+                                    "type": "object",
+                                    "description": "Property overrides applied before the test and reverted after, e.g. {\"Lighting\": {\"ClockTime\": 0}, \"Workspace\": {\"Gravity\": 50}}. Only Lighting and Workspace. Vector3/Color3 values are [x, y, z] arrays, enums are item names."
+                                },
+                                "timeout": {
+                                    "type": "number",
+                                    "description": "Seconds before this variant's test is force-stopped. Overrides the top-level timeout."
+                                }
+                            },
+                            "additionalProperties": false
+                        }
+                    },
+                    "timeout": {
+                        "type": "number",
+                        "description": "Per-variant test timeout in seconds (default 30, max 300). All variant timeouts together may not exceed 1800."
+                    },
+                    "failFast": {
+                        "type": "boolean",
+                        "description": "Skip the remaining variants after the first one that does not pass. Default: false."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag stored with the report capture in index.json."
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to write the report to instead of test_matrix/ in the capture folder. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: the capture folder."
+                    }
+                },
+                "required": ["code", "variants"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_collision_groups".into(),
+            description: Some("List the collision groups registered with PhysicsService, which groups collide with which, and the collision group of the parts under a subtree. Use it to debug parts that pass through each other or block things they shouldn't. By default only parts outside the 'Default' group are listed (countsByGroup still covers every part); pass includeDefault to list them all. Reads the edit DataModel. Returns groups [{name, id, collidesWith}], root, countsByGroup, parts [{path, group}] and truncated.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Subtree to scan, e.g. 'Workspace.Map'. Case-sensitive. Defaults to 'Workspace'."
+                    },
+                    "includeDefault": {
+                        "type": "boolean",
+                        "description": "Also list parts in the 'Default' group. Default: false."
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 1000,
+                        "description": "Maximum parts to list (default 200). truncated is true when more matched."
+                    },
+                    "groupsOnly": {
+                        "type": "boolean",
+                        "description": "Return only the registered groups and their collision pairs, without scanning parts."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_collision_group".into(),
+            description: Some("Assign a BasePart, or with recursive every BasePart under a model or folder, to a registered collision group. The group must already exist (create it with PhysicsService:RegisterCollisionGroup via studio-run_script); unknown names are rejected with the list of registered groups. Edits the edit DataModel as one undo step, so it is refused while a playtest is running. Returns target, group, parts and changed (parts that were in another group).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full instance path, e.g. 'Workspace.Door.Frame'. Case-sensitive."
+                    },
+                    "group": {
+                        "type": "string",
+                        "description": "Registered collision group name, e.g. 'Players' or 'Default'. Case-sensitive."
+                    },
+                    "recursive": {
+                        "type": "boolean",
+                        "description": "Assign every BasePart under 'path' (including itself). Required when 'path' is not a BasePart. Default: false."
+                    }
+                },
+                "required": ["path", "group"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::TRANSACTION_SAFE),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_pivot".into(),
+            description: Some("Get the pivot of a Model or BasePart (PVInstance:GetPivot): the point and rotation studio-set_pivot and Studio's move tools use. Use it before placing objects relative to each other; reading CFrame or Position directly gives a model's PrimaryPart or nothing at all. Reads the edit DataModel. Returns path, className and pivot (position, orientation in degrees as Studio shows it, and the 12 CFrame components). Models also return primaryPart (path or null) and boundingBox (center, size); parts return size and pivotOffset.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full instance path of a Model or BasePart, e.g. 'Workspace.House'. Case-sensitive."
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_pivot".into(),
+            description: Some("Move a Model or BasePart so its pivot is at a position and/or orientation (PVInstance:PivotTo), carrying every part of a model along. Pass position, orientation or both; the one left out keeps its current value. Edits the edit DataModel as one undo step (part of an open checkpoint if there is one), so it is refused while a playtest is running. Returns path, previous and the new pivot in the same shape as studio-get_pivot.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full instance path of a Model or BasePart, e.g. 'Workspace.House'. Case-sensitive."
+                    },
+                    "position": {
+                        "type": "array",
+                        "items": { "type": "number" },
+                        "minItems": 3,
+                        "maxItems": 3,
+                        "description": "World position for the pivot, [x, y, z] in studs."
+                    },
+                    "orientation": {
+                        "type": "array",
+                        "items": { "type": "number" },
+                        "minItems": 3,
+                        "maxItems": 3,
+                        "description": "Rotation in degrees, [x, y, z], as in Studio's Orientation property."
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::TRANSACTION_SAFE),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_profile".into(),
+            description: Some("Show or switch the server's configuration profile. Profiles are named sets of overrides from YIPPIE_PROFILES, e.g. a cautious one (readOnly, short toolTimeoutSecs) and a fast iteration one (long timeouts, minifyCode); 'default' is the base settings. A profile can set minifyCode (default for run_script/test_script minify), logCode (how code appears in debug logs), toolTimeoutSecs (how long the server waits for Studio, 10-600) and readOnly (refuse every tool not marked read-only). Without 'profile', returns the active settings and the available profiles. With it, switches and returns previous and active. Calls already running keep the settings they started with. It can leave a readOnly profile for one that allows writes; ask the user before doing that. studio-status shows the active profile.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "profile": {
+                        "type": "string",
+                        "description": "Profile to switch to, or 'default' for the base settings. Omit to list profiles."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-is_tweenable".into(),
+            description: Some(format!("Check whether TweenService can animate a property before building a tween, e.g. Part.Transparency (yes) or Part.BrickColor (no, tween Color instead). Pass the property and either the path of an instance or a className; a className is inspected on a fresh, unparented instance, so nothing in the place changes. Tweenable value types: {}. boolean and EnumItem are accepted by TweenService but jump to the goal at the end instead of blending (interpolated: false). Returns className, property, valueType (the Luau typeof, plus enumType for enums), tweenable, interpolated and a note suggesting an alternative when it isn't tweenable. A property that doesn't exist is an error. Repeat calls within YIPPIE_CACHE_TTL_SECS are answered from the server's response cache.", tweenable::supported())),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "property": {
+                        "type": "string",
+                        "description": "Property name, e.g. 'Transparency', 'CFrame', 'BackgroundColor3'. Case-sensitive."
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Instance whose property to check, e.g. 'Workspace.Door'. Use this or className."
+                    },
+                    "className": {
+                        "type": "string",
+                        "description": "Class to check, e.g. 'Part', 'Frame'. Must be creatable with Instance.new; use path for services and other non-creatable classes."
+                    }
+                },
+                "required": ["property"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::CACHEABLE),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-batch_get_properties".into(),
+            description: Some(format!("Read the same properties from many instances in one call, e.g. Position and Anchored of every part in a model, instead of one run_script per instance. Returns values (path -> property -> value; a nil value such as an empty PrimaryPart is null), errors (path -> 'Instance not found', or property -> reason for properties the instance doesn't have) and count. Vector3, Color3, Vector2 and UDim/UDim2 are arrays of their components, CFrame the 12 GetComponents numbers, enums the item name, Instance references the full name; other types are tostring'd. At most {MAX_BATCH_PATHS} paths and {MAX_BATCH_PROPERTIES} properties per call. When the values would exceed {} KB the remaining paths are not read: truncated is true and resumeAt is the 1-based index in paths to continue from.", BATCH_PROPERTIES_MAX_BYTES / 1024)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "paths": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Full names of the instances, e.g. ['Workspace.Map.Door', 'Workspace.Map.Window']. Duplicates are read once."
+                    },
+                    "properties": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Property names to read from each instance, e.g. ['Position', 'Anchored']. Case-sensitive."
+                    }
+                },
+                "required": ["paths", "properties"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-diff_places".into(),
+            description: Some(format!("Compare two versions of a subtree and list what changed: instances added or removed (a whole missing subtree is listed once at its top, with its descendant count) and, for instances at the same path, changed properties with before and after values. Each side is either a live instance path ({{ instance: 'Workspace.Map' }}) or a snapshot file saved earlier ({{ snapshot: 'snapshots/<id>.json' }}), so you can verify edits across sessions: snapshot before, change, then diff the snapshot against the live tree. Without compare, only takes the base snapshot and saves it; save: true also saves the live sides of a comparison. Instances are matched by their path below each root, so two different roots (e.g. Workspace.MapA and Workspace.MapB) can be compared. Recorded per instance: class, common properties for its class (BasePart position/size/color/material/anchoring, GUI layout, Value, Text, lights, sounds, ...), attributes as '@name', CollectionService Tags and script sources as '<length>:<hash>'; numbers within 0.0001 count as equal. Snapshots stop at maxInstances (default {}, max {}); each list stops at maxChanges (default {}, max {}) with truncated set and the totals still counting everything.", model_diff::DEFAULT_MAX_INSTANCES, model_diff::MAX_INSTANCES, model_diff::DEFAULT_MAX_CHANGES, model_diff::MAX_MAX_CHANGES)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "base": {
+                        "type": "object",
+                        "description": "The side to compare against: { instance: 'Workspace.Map' } for the live subtree, or { snapshot: 'snapshots/<id>.json' } for a saved snapshot (path relative to the capture folder, as returned when it was saved).",
+                        "properties": {
+                            "instance": { "type": "string" },
+                            "snapshot": { "type": "string" }
+                        }
+                    },
+                    "compare": {
+                        "type": "object",
+                        "description": "The side to compare, in the same form as base. Leave it out to only take and save a snapshot of base.",
+                        "properties": {
+                            "instance": { "type": "string" },
+                            "snapshot": { "type": "string" }
+                        }
+                    },
+                    "save": {
+                        "type": "boolean",
+                        "description": "Also save the live sides of a comparison as snapshot files (default false)."
+                    },
+                    "maxInstances": {
+                        "type": "integer",
+                        "description": "Most instances recorded per live snapshot (default 2000, max 5000)."
+                    },
+                    "maxChanges": {
+                        "type": "integer",
+                        "description": "Most entries in each of added, removed and changed (default 200, max 1000)."
+                    }
+                },
+                "required": ["base"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-place_stats".into(),
+            description: Some(format!("Count what is in the place, for numbers before and after an optimization pass: instances per class, scripts and their total source length, unions (UnionOperation/NegateOperation/IntersectOperation), MeshParts, texture references and distinct texture ids with a memory estimate (512x512 RGBA with mipmaps per distinct texture; Studio doesn't expose real sizes). Counts the whole edit DataModel, or only the descendants of root. Each call is kept as a snapshot with an id (the last {} are kept, until the server restarts); pass compareTo with an earlier id to also get the change in each total, classes added and removed, and count changes sorted by size. Returns tables as text and the same data as JSON in structuredContent. Services the plugin can't read are listed under skipped.", place_stats::MAX_STORED_SNAPSHOTS)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Only count descendants of this instance, e.g. 'Workspace.Map' (default: the whole place)."
+                    },
+                    "compareTo": {
+                        "type": "string",
+                        "description": "Id of an earlier studio-place_stats snapshot to compare with."
+                    },
+                    "top": {
+                        "type": "number",
+                        "description": format!("Rows in the class table and in the count changes, largest first (default: {}, max {}).", place_stats::DEFAULT_TOP, place_stats::MAX_TOP)
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_runtime_flag".into(),
+            description: Some(format!("Flip a whitelisted, reversible testing setting in the edit DataModel and put it back later. Allowed flags: {}. Anything else is refused. action 'set' (default) changes 'flag' to 'value' and returns previous and restoreTo (the value before the first change this server made); 'restore' puts 'flag' back, or every changed flag when 'flag' is left out; 'list' returns the whitelist with allowed values and the changed flags. Refused while a playtest is running; change flags before studio-playtest_play. Changes are not undo steps: restore them with this tool.", runtime_flags::FLAGS.iter().map(|f| f.name).collect::<Vec<_>>().join(", "))),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["set", "restore", "list"],
+                        "description": "Default: 'set'."
+                    },
+                    "flag": {
+                        "type": "string",
+                        "description": "Flag as Service.Property, e.g. 'Workspace.StreamingEnabled'. Required for 'set'."
+                    },
+                    "value": {
+                        "description": "New value for 'set': a boolean, a number in the flag's range, or an enum item name (e.g. 'Deferred')."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-set_studio_setting".into(),
+            description: Some(format!("Temporarily change a whitelisted Studio setting a test depends on, such as physics visualization or simulated replication lag. Allowed settings: {}. Anything else is refused, so the user's other preferences are never touched. Studio settings persist across places, so every change is undone automatically: a change made during a playtest is restored when that playtest ends, one made outside a playtest when the MCP session is cleaned up (idle timeout or disconnect). action 'set' (default) changes 'setting' to 'value' and returns previous, restoreTo (the value before the first change) and restoreWhen; 'restore' puts 'setting' back now, or every changed setting when 'setting' is left out; 'list' returns the whitelist with allowed values and the changed settings.", studio_settings::SETTINGS.iter().map(|s| s.name).collect::<Vec<_>>().join(", "))),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "action": {
+                        "type": "string",
+                        "enum": ["set", "restore", "list"],
+                        "description": "Default: 'set'."
+                    },
+                    "setting": {
+                        "type": "string",
+                        "description": "Setting as Group.Property, e.g. 'Physics.AreOwnersShown'. Required for 'set'."
+                    },
+                    "value": {
+                        "description": "New value for 'set': a boolean, a number in the setting's range, or an enum item name (e.g. 'Disabled')."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-wait_for_condition".into(),
+            description: Some("Wait until a Luau predicate holds, polling it in Studio every intervalMs until it returns a truthy value or timeoutMs passes. Use it to synchronize with async game state instead of sleeping, e.g. 'workspace:FindFirstChild(\"Boss\") ~= nil' or 'workspace.Door.Transparency == 1'. The predicate is an expression (or a chunk with its own return) run in the plugin context like studio-run_script; an evaluation that errors counts as not yet met, so indexing something that doesn't exist yet is fine. It sees the edit DataModel, which is the running game in Run mode (F8); in Play mode (F5) the server and client run in separate DataModels it can't see, so use studio-test_script for state inside the session. Timing is clamped: timeoutMs 100-120000 (default 10000), intervalMs 10-10000 (default 100, at most timeoutMs). Returns met, waitedMs and attempts, plus the predicate's value when met, or timedOut and the last evaluation error when not. A timeout is a normal result, not a tool error; a predicate that doesn't compile is.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "predicate": {
+                        "type": "string",
+                        "description": "Luau expression evaluated each poll, e.g. 'game.Players.NumPlayers > 0'. A truthy value ends the wait."
+                    },
+                    "timeoutMs": {
+                        "type": "integer",
+                        "description": "How long to keep polling, in milliseconds (100-120000). Default: 10000."
+                    },
+                    "intervalMs": {
+                        "type": "integer",
+                        "description": "Delay between evaluations, in milliseconds (10-10000, at most timeoutMs). Default: 100."
+                    },
+                    "async": {
+                        "type": "boolean",
+                        "description": "Return at once with an operation id instead of waiting for the result (default: false). Poll or wait for the result with studio-get_operation / studio-wait_operation; stop the call with studio-cancel."
+                    }
+                },
+                "required": ["predicate"],
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_lua_globals".into(),
+            description: Some("List the globals studio-run_script code shares between calls, with their Luau types, to see what earlier scripts left behind instead of probing by trial and error. Returns three scopes, each sorted by name and bounded by limit with count and truncated: env (variables earlier run_script, wait_for_condition or test setup code assigned without 'local'; they persist until the plugin reloads), _G and shared (tables visible to every script in the plugin context). Engine globals such as game, workspace, task or Vector3 don't show up in env; set includeBuiltins to check which of the common ones are available. Only names and types are returned, never values. Reads the edit-mode plugin context.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 500,
+                        "description": "Most globals to return per scope, sorted by name (default 100)."
+                    },
+                    "includeBuiltins": {
+                        "type": "boolean",
+                        "description": "Also report which common engine globals (game, workspace, plugin, task, Vector3, ...) are available, with their types. Default false."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-select_matching".into(),
+            description: Some("Find instances under a root and select them in Studio in one call, e.g. 'select all the SpawnLocations' or 'select every part tagged Lava'. Filters combine (all must match): className (IsA, so 'BasePart' matches every part type), exact name, case-insensitive nameContains and a CollectionService tag; at least one is required. Replaces the selection unless add is set. Searches the edit DataModel. Returns matched (total), selected (how many were selected, capped by limit), truncated, selectionSize (the whole selection afterwards) and the first 20 selected paths as sample. Pairs with studio-focus_camera_on to frame the result.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "root": {
+                        "type": "string",
+                        "description": "Subtree to search, e.g. 'Workspace.Map'. Case-sensitive. The root itself is not a candidate. Defaults to 'Workspace'."
+                    },
+                    "className": {
+                        "type": "string",
+                        "description": "Match instances that are this class or inherit from it (IsA), e.g. 'BasePart', 'Script'."
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Match instances with exactly this Name (case-sensitive)."
+                    },
+                    "nameContains": {
+                        "type": "string",
+                        "description": "Match instances whose Name contains this text (case-insensitive)."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Match instances with this CollectionService tag."
+                    },
+                    "add": {
+                        "type": "boolean",
+                        "description": "Add the matches to the current selection instead of replacing it. Default: false."
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Most instances to select (default 500, max 5000). 'truncated' is true when more matched."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-focus_camera_on".into(),
+            description: Some("Frame the Studio edit camera on an instance or on the current selection, like pressing F in Studio. Pass 'path' to target one instance; omit it to frame everything selected. The camera keeps its viewing angle and moves so the combined bounds of all BaseParts in the target fill the view. Pairs well with studio-screenshot_region to capture what you just built. Acts on the edit camera (not a running playtest's camera). Returns the resulting camera CFrame (position, lookVector and the 12 CFrame components) and the framed bounds.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full instance path, e.g. 'Workspace.Castle.Gate'. Case-sensitive. Omit to use the current Studio selection."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-open_script".into(),
+            description: Some("Open a Script, LocalScript or ModuleScript in Studio's script editor and make its tab active, to point the person working in Studio at a script you just changed or want them to review. Optionally put the cursor on 'line'. Does not change the script. Fails with not_a_script if the path names another kind of instance, and with an error if Studio can't open the editor. Returns the script's path, className, line and the method used (OpenScriptDocumentAsync, or plugin:OpenScript when a line is given or as a fallback).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Full path of the script, e.g. 'ServerScriptService.GameManager'. Case-sensitive."
+                    },
+                    "line": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Line to put the cursor on (1-based). Omit to open the script where it was."
+                    }
+                },
+                "required": ["path"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-get_open_scripts".into(),
+            description: Some("List the scripts open in the script editor of the person working in Studio, to see what they are working on. Each has path, className, lineCount, cursor (line and character, 1-based), selectionAnchor when text is selected (the other end of the selection), and visibleLines (first and last line in view) when Studio reports them. The command bar is left out. Sorted by path and bounded by limit; count is the number open and truncated is set when more are open. With none open, scripts is empty and note says so. Studio doesn't report which tab is active. Read-only.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Most scripts to list (default 20, max 100)."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-screenshot_region".into(),
+            description: Some("Take an OS-level screenshot of a rectangle of the primary screen and save it as a PNG in the capture folder, or in outputDir inside one of the client's roots. Use this to capture just the Studio viewport (or any panel) instead of the whole desktop, producing tighter images for review. The region is validated against the screen size before capturing. Returns the capture id and saved file path. Runs on the server machine itself - does not need the plugin, but Studio must be visible on screen. Returns an error if OS captures are disabled by server configuration.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "x": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Left edge of the region in screen pixels (points on macOS), measured from the top-left of the primary screen."
+                    },
+                    "y": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Top edge of the region in screen pixels (points on macOS)."
+                    },
+                    "width": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Region width. x + width must not exceed the screen width."
+                    },
+                    "height": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Region height. y + height must not exceed the screen height."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "pattern": "^[A-Za-z0-9_-]{1,64}$",
+                        "description": "Optional tag appended to the filename and stored in index.json (e.g. 'viewport_after_build'). Letters, digits, '_' and '-' only, at most 64."
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to save the PNG in, e.g. next to the project it belongs to. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: the capture folder."
+                    }
+                },
+                "required": ["x", "y", "width", "height"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-capture_viewport_via_render".into(),
+            description: Some(format!("Render what the Studio edit camera sees and return it as an image you can look at, saved as a PNG in the capture folder (or outputDir) and indexed in index.json. Works without screen access, unlike studio-screenshot_region, and unlike the disabled studio-capture_screenshot it produces a real file. The plugin can't read Studio's own frame, so it ray-traces the scene: one ray per pixel, each hit shaded by the part's color (terrain by material color) and the sun angle, misses drawn as sky. Expect a flat-shaded, low-resolution picture: no textures, decals, meshes' surface detail, shadows, reflections, particles, GUIs or transparency effects (parts more than 95% transparent are seen through). Good for checking layout, placement, colors and what is in view; not for visual polish. Pass 'path' to aim at an instance from the current viewing angle without moving the edit camera. Size is at most {}x{} pixels (default {}x{}); render time grows with the pixel count. Renders the edit DataModel, also during a playtest. Returns the capture id, path, size, the camera used and how many pixels hit geometry, plus the image.", viewport_render::MAX_WIDTH, viewport_render::MAX_HEIGHT, viewport_render::DEFAULT_WIDTH, viewport_render::DEFAULT_HEIGHT)),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "width": {
+                        "type": "integer",
+                        "minimum": viewport_render::MIN_SIZE,
+                        "maximum": viewport_render::MAX_WIDTH,
+                        "description": format!("Image width in pixels. Default {}.", viewport_render::DEFAULT_WIDTH)
+                    },
+                    "height": {
+                        "type": "integer",
+                        "minimum": viewport_render::MIN_SIZE,
+                        "maximum": viewport_render::MAX_HEIGHT,
+                        "description": format!("Image height in pixels. Default {}. The field of view is the camera's vertical one, so the aspect ratio sets how much is seen sideways.", viewport_render::DEFAULT_HEIGHT)
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Instance to frame, e.g. 'Workspace.House'. The render camera keeps the edit camera's viewing angle and moves back until the instance's parts fit. Default: render from the edit camera as it is."
+                    },
+                    "maxDistance": {
+                        "type": "number",
+                        "description": format!("How far rays travel in studs before counting as sky. Default {}, at most {}.", viewport_render::DEFAULT_MAX_DISTANCE, viewport_render::MAX_MAX_DISTANCE)
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Optional tag stored with the capture in index.json (e.g. 'after_build')"
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to save the PNG in. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: renders/ in the capture folder."
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-annotate_capture".into(),
+            description: Some("Attach a note and/or tag to an existing capture in the capture folder's index.json. Use this right after taking a screenshot to record what it shows (e.g. 'door opens after prompt trigger'), building an annotated trail of captures the user can review later. Requires the capture id from index.json or a capture tool result. Overwrites the previous note/tag; omitted fields are left unchanged.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Capture id as stored in index.json."
+                    },
+                    "note": {
+                        "type": "string",
+                        "description": "Free-form note describing what the capture shows. Example: 'Spawn area after lighting change - shadows look correct'"
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Short label for grouping captures. Example: 'lighting_pass'"
+                    }
+                },
+                "required": ["id"]
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-capture_compare".into(),
+            description: Some("Compare two PNG captures (OS screenshots or viewport renders) pixel by pixel for visual regression checks, e.g. before and after a lighting or UI change. Pass capture ids from index.json or earlier capture results; 'latest' stands for the newest screenshot or render other than the other side (after defaults to 'latest'). A pixel counts as changed when any RGB channel differs by more than threshold. Returns changedPercent, changedPixels, identical, the bounding boxes of changed regions (largest first; changes a few pixels apart are merged) and a diff image: the second capture dimmed, changed pixels red, regions outlined yellow. The diff is saved under diffs/ in the capture folder (or in outputDir) and also returned as an image when under 1 MB. Captures of different sizes fail unless scale is true, which resizes the second to the first's size. Answered by the server without a plugin round-trip.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "before": {
+                        "type": "string",
+                        "description": "Capture id of the reference image, or 'latest'."
+                    },
+                    "after": {
+                        "type": "string",
+                        "description": "Capture id of the image to check, or 'latest' (default)."
+                    },
+                    "threshold": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "maximum": 255,
+                        "description": "Largest per-channel difference (0-255) still treated as unchanged, to ignore compression noise and anti-aliasing. Default 16; 0 counts every difference."
+                    },
+                    "scale": {
+                        "type": "boolean",
+                        "description": "Resize 'after' to the size of 'before' when they differ, instead of failing. Default false."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag stored with the diff capture in index.json."
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to write the diff image to instead of diffs/ in the capture folder. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: the capture folder."
+                    }
+                },
+                "required": ["before"],
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-time_report".into(),
+            description: Some("Show where recent studio-test_script calls spent their time, to find what slows a test suite down. Each call is split into phases: queueWait (queued until the plugin picked it up), startup (until the playtest was running), execution (while it ran) and teardown (from the playtest stopping to the result). Returns totals, averages, maxima and share of time per phase over the last N calls, pass/fail counts, and the slowest calls with their dominant phase. A phase whose boundary the server didn't see is left at zero and listed under missing. Also saves one CSV row per call under time_reports/ in the capture folder (or in outputDir). Timings are kept in memory since the server started; deterministic mode reports zeros. Answered by the server without a plugin round-trip.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "last": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 200,
+                        "description": "Number of most recent test_script calls to include. Default 20."
+                    },
+                    "slowest": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 20,
+                        "description": "Number of slowest calls to list. Default 3."
+                    },
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag stored with the CSV in index.json."
+                    },
+                    "outputDir": {
+                        "type": "string",
+                        "description": "Directory to write the CSV to instead of time_reports/ in the capture folder. Must be inside one of the client's roots (MCP roots capability); relative paths start at the first root. Created if missing. Default: the capture folder."
+                    }
+                },
+                "additionalProperties": false
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-replay".into(),
+            description: Some("Run a saved sequence of tool calls in order, waiting delayMs before each step, and return every step's result. Use this to re-run a reproducible scenario (e.g. start a playtest, walk forward, check output) for regression checks or demos. Pass the steps inline or load them from a JSON file inside the capture folder, e.g. 'replays/door_test.json'. Stops at the first failing step unless stopOnError is false. Other requests wait while a replay runs, so keep delays short (max 60s per step, 10 minutes total).".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "steps": {
+                        "type": "array",
+                        "description": "Tool calls to run, in order. Omit when using 'file'.",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "tool": {
+                                    "type": "string",
+                                    "description": "Tool name, e.g. studio-run_script"
+                                },
+                                "arguments": {
+                                    "type": "object",
+                                    "description": "Arguments for the tool (default: {})"
+                                },
+                                "delayMs": {
+                                    "type": "number",
+                                    "description": "Wait this long before the step (default: 0, max: 60000)"
+                                }
+                            },
+                            "required": ["tool"]
+                        }
+                    },
+                    "file": {
+                        "type": "string",
+                        "description": "Path of a JSON replay file relative to the capture folder, holding a steps array or {\"steps\": [...]}. Omit when passing 'steps'."
+                    },
+                    "stopOnError": {
+                        "type": "boolean",
+                        "description": "Stop at the first failing step (default: true)"
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-transaction".into(),
+            description: Some("Run several editing tool calls as one all-or-nothing change. The server opens a checkpoint, runs the steps in order and commits the checkpoint as one undo step when all of them succeed. If a step fails or the time budget runs out, the remaining steps are skipped and the checkpoint is cancelled, which reverts what the earlier steps changed. Only tools whose edits a checkpoint can take back are allowed as steps: studio-run_script, studio-set_pivot and studio-set_collision_group; other steps, and nested transactions, are refused before anything runs. Returns committed, every step's result or error, and on failure the failed step, the reason (failed, timedOut or commitFailed) and the rollback outcome. Edit mode only, since checkpoints can't be opened during a playtest.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "steps": {
+                        "type": "array",
+                        "description": "Tool calls to run, in order (max 50)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "tool": {
+                                    "type": "string",
+                                    "description": "Tool name, e.g. studio-set_pivot"
+                                },
+                                "arguments": {
+                                    "type": "object",
+                                    "description": "Arguments for the tool (default: {})"
+                                }
+                            },
+                            "required": ["tool"]
+                        }
+                    },
+                    "name": {
+                        "type": "string",
+                        "description": "Checkpoint name shown in Studio's undo history (default: 'MCP Transaction')"
+                    },
+                    "timeoutMs": {
+                        "type": "number",
+                        "description": "Time budget for all steps together (default: the tool timeout per step, max: 600000). A step that would run past it fails the transaction."
+                    }
+                },
+                "required": ["steps"]
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-var_set".into(),
+            description: Some("Store a JSON value under a name on the server, so a later call can use it without passing it through the conversation, e.g. an instance path or position computed by studio-run_script and needed by studio-test_script. The code of studio-run_script and studio-test_script can refer to it as ${var:name}: in code it becomes a Luau literal (\"text\", 42, true, nil, or a table for arrays and objects), inside a quoted or backtick string the value's text, escaped; write $${var:name} for a literal ${var:name}. Session variables (default) belong to this MCP session and shadow global ones of the same name; global variables are shared by every session of the server. Variables live in memory until the server exits or ttlSecs runs out. Limits: 64 KB per value, 256 variables and 1 MB per scope. Pass delete=true instead of value to remove one.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Variable name: letters, digits and '_', not starting with a digit (max 64)"
+                    },
+                    "value": {
+                        "description": "Any JSON value"
+                    },
+                    "scope": {
+                        "type": "string",
+                        "enum": ["session", "global"],
+                        "description": "Where to store it (default: session)"
+                    },
+                    "ttlSecs": {
+                        "type": "number",
+                        "description": "Forget the variable after this many seconds (1-604800, default: never)"
+                    },
+                    "delete": {
+                        "type": "boolean",
+                        "description": "Remove the variable from the scope instead of setting it"
+                    }
+                },
+                "required": ["name"]
+            }),
+            annotations: None,
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-var_get".into(),
+            description: Some("Read a variable stored with studio-var_set. Without scope, the session's variable is returned if there is one, otherwise the global one. Returns name, scope, type, value, bytes, setSecsAgo and expiresInSecs (null when it doesn't expire). Fails for names that are not set or have expired.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "name": {
+                        "type": "string",
+                        "description": "Variable name"
+                    },
+                    "scope": {
+                        "type": "string",
+                        "enum": ["session", "global"],
+                        "description": "Only look in this scope (default: session, then global)"
+                    }
+                },
+                "required": ["name"]
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-var_list".into(),
+            description: Some("List the variables stored with studio-var_set, session ones first, by name, with their scope, type, size, age and time left, but not their values (use studio-var_get). Also returns the per-scope limits.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "scope": {
+                        "type": "string",
+                        "enum": ["session", "global"],
+                        "description": "Only list this scope (default: both)"
+                    }
+                }
+            }),
+            annotations: Some(ToolAnnotations::READ_ONLY),
+            experimental: true,
+        },
+        McpToolDef {
+            name: "studio-capture_screenshot".into(),
+            description: Some("DISABLED - DO NOT USE. Capture a screenshot of the Studio viewport. Non-functional due to Roblox API limitations - CaptureService returns inaccessible rbxtemp:// URIs that cannot be extracted as files. Will return an error if called.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag for this capture (e.g. 'after_jump', 'menu_open')"
+                    },
+                    "includeUI": {
+                        "type": "boolean",
+                        "description": "Include UI elements if supported"
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-capture_video_start".into(),
+            description: Some("DISABLED - DO NOT USE. Start recording video of Studio viewport. Non-functional - Roblox's CaptureService does not expose video recording APIs to plugins. Will return an error if called.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "tag": {
+                        "type": "string",
+                        "description": "Tag for this recording"
+                    },
+                    "maxSeconds": {
+                        "type": "number",
+                        "description": "Maximum recording duration in seconds (default: 10)"
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+        McpToolDef {
+            name: "studio-capture_video_stop".into(),
+            description: Some("DISABLED - DO NOT USE. Stop video recording. Non-functional - Roblox's CaptureService does not expose video recording APIs to plugins. Will return an error if called.".into()),
+            input_schema: json!({
+                "type": "object",
+                "properties": {
+                    "recordingId": {
+                        "type": "string",
+                        "description": "Recording ID to stop"
+                    }
+                }
+            }),
+            annotations: None,
+            experimental: false,
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock_studio;
+    use crate::plugin_settings::PluginSettings;
+    use crate::state::CheckpointLoss;
+    use crate::wire::Casing;
+
+    const CHARACTER_TOOL: &str = "studio-virtualuser_key";
+    const PLAY_MODE_TOOL: &str = "studio-get_gui_tree";
+    const PLAYTEST_TOOL: &str = "studio-npc_driver_start";
+    const UNRESTRICTED_TOOL: &str = "studio-status";
+
+    fn test_state() -> SharedState {
+        SharedState::for_tests(Config::default())
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Playtest {
+        None,
+        Play,
+        Run,
+        PlaySpawning,
+        PlaySpawned,
+        RunWithReadiness,
+        /// A playtest bridge is connected but its state event never arrived.
+        BridgeOnly,
+    }
+
+    async fn set_up(playtest: Playtest) -> SharedState {
+        let state = test_state();
+        let (mode, readiness) = match playtest {
+            Playtest::None => return state,
+            Playtest::BridgeOnly => {
+                state.register_client("bridge".into(), "playtest-1.0".into(), None, Casing::Snake).await;
+                return state;
+            }
+            Playtest::Play => ("play", None),
+            Playtest::Run => ("run", None),
+            Playtest::PlaySpawning => ("play", Some(Readiness::PlayerAdded)),
+            Playtest::PlaySpawned => ("play", Some(Readiness::CharacterSpawned)),
+            Playtest::RunWithReadiness => ("run", Some(Readiness::ServerStarted)),
+        };
+        state.update_playtest(true, Some("session".into()), Some(mode.into())).await;
+        state.update_playtest_readiness(readiness, None).await;
+        state
+    }
+
+    /// "ok", the error's taxonomy code, or "waits" if the check is still holding the
+    /// call back for a playtest or character that might turn up.
+    async fn outcome(state: &SharedState, tool: &str) -> String {
+        match tokio::time::timeout(Duration::from_millis(200), check_requirements(state, tool)).await {
+            Err(_) => "waits".into(),
+            Ok(Ok(())) => "ok".into(),
+            Ok(Err(message)) => message.split(':').next().unwrap_or_default().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn requirements_matrix() {
+        use Playtest::*;
+        let tools = [CHARACTER_TOOL, PLAY_MODE_TOOL, PLAYTEST_TOOL, UNRESTRICTED_TOOL];
+        let expected: [(Playtest, [&str; 4]); 7] = [
+            (None, ["playtest_required", "playtest_required", "playtest_required", "ok"]),
+            (Play, ["ok", "ok", "ok", "ok"]),
+            (Run, ["play_mode_required", "play_mode_required", "ok", "ok"]),
+            (PlaySpawning, ["waits", "ok", "ok", "ok"]),
+            (PlaySpawned, ["ok", "ok", "ok", "ok"]),
+            (RunWithReadiness, ["play_mode_required", "play_mode_required", "ok", "ok"]),
+            (BridgeOnly, ["ok", "ok", "ok", "ok"]),
+        ];
+        for (playtest, row) in expected {
+            let state = set_up(playtest).await;
+            for (tool, want) in tools.iter().zip(row) {
+                assert_eq!(outcome(&state, tool).await, want, "{tool} with {playtest:?}");
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn waits_for_a_playtest_that_is_starting() {
+        let state = test_state();
+        let guard = state.begin_playtest_start();
+        assert_eq!(outcome(&state, PLAYTEST_TOOL).await, "waits");
+
+        let starter = state.clone();
+        tokio::spawn(async move {
+            starter.update_playtest(true, Some("session".into()), Some("play".into())).await;
+            drop(guard);
+        });
+        let result = tokio::time::timeout(Duration::from_secs(5), check_requirements(&state, CHARACTER_TOOL)).await;
+        assert_eq!(result.expect("check should finish once the playtest runs"), Ok(()));
+    }
+
+    #[tokio::test]
+    async fn fails_once_a_starting_playtest_gives_up() {
+        let state = test_state();
+        let guard = state.begin_playtest_start();
+        let check = tokio::spawn({
+            let state = state.clone();
+            async move { check_requirements(&state, PLAYTEST_TOOL).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(guard);
+        let result = tokio::time::timeout(Duration::from_secs(5), check).await.unwrap().unwrap();
+        assert!(result.unwrap_err().starts_with("playtest_required:"));
+    }
+
+    #[tokio::test]
+    async fn start_in_flight_does_not_hold_back_a_run_mode_error() {
+        let state = set_up(Playtest::Run).await;
+        let _guard = state.begin_playtest_start();
+        // A restart into Play mode may be on its way, so the check holds on
+        assert_eq!(outcome(&state, CHARACTER_TOOL).await, "waits");
+        state.update_playtest(true, Some("next".into()), Some("play".into())).await;
+        assert_eq!(outcome(&state, CHARACTER_TOOL).await, "ok");
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_character_to_spawn() {
+        let state = set_up(Playtest::PlaySpawning).await;
+        let check = tokio::spawn({
+            let state = state.clone();
+            async move { check_requirements(&state, CHARACTER_TOOL).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        state.update_playtest_readiness(Some(Readiness::CharacterSpawned), Some("Player1".into())).await;
+        let result = tokio::time::timeout(Duration::from_secs(5), check).await.unwrap().unwrap();
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn tool_calls_past_the_running_limit_wait_their_turn() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Mutex;
+
+        let mut tasks = ToolCallTasks::new(2, 10);
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let started = Arc::new(Mutex::new(Vec::new()));
+        for i in 0..6 {
+            let (running, peak, started) = (running.clone(), peak.clone(), started.clone());
+            let queued = tasks.spawn(async move {
+                started.lock().unwrap().push(i);
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+            assert!(queued, "call {i} was refused");
+        }
+        tasks.join_all().await;
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(*started.lock().unwrap(), vec![0, 1, 2, 3, 4, 5]);
+    }
+
+    #[tokio::test]
+    async fn tool_calls_past_the_queue_capacity_are_refused() {
+        let mut tasks = ToolCallTasks::new(1, 3);
+        let release = Arc::new(tokio::sync::Notify::new());
+        for _ in 0..3 {
+            let release = release.clone();
+            assert!(tasks.spawn(async move { release.notified().await }));
+        }
+        assert!(!tasks.spawn(async {}));
+
+        // Finished calls free their place in the queue
+        for _ in 0..3 {
+            release.notify_one();
+            tokio::task::yield_now().await;
+        }
+        tasks.join_all().await;
+        assert!(tasks.spawn(async {}));
+        tasks.join_all().await;
+    }
+
+    #[tokio::test]
+    async fn aborting_drops_queued_tool_calls() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let mut tasks = ToolCallTasks::new(1, 10);
+        let ran = Arc::new(AtomicBool::new(false));
+        assert!(tasks.spawn(std::future::pending()));
+        let flag = ran.clone();
+        assert!(tasks.spawn(async move { flag.store(true, Ordering::SeqCst) }));
+        tasks.abort_all().await;
+        assert!(!ran.load(Ordering::SeqCst));
+        assert!(tasks.spawn(async {}));
+    }
+
+    /// A server with the in-process mock plugin, once its settings report is in.
+    async fn with_mock_studio() -> SharedState {
+        let state = test_state();
+        tokio::spawn(mock_studio::run(state.clone(), mock_studio::Scenario::default()));
+        while state.plugin_settings_report(false).await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        state
+    }
+
+    fn texts(result: &Value) -> Vec<&str> {
+        result["content"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c["text"].as_str())
+            .collect()
+    }
+
+    async fn logs_subscribed(dispatcher: &Dispatcher) -> bool {
+        let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        status["session"]["logsSubscribed"].as_bool().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sessions_keep_their_own_log_subscriptions_and_warnings() {
+        let state = with_mock_studio().await;
+        let first = Dispatcher::new(state.clone());
+        let second = Dispatcher::new(state.clone());
+
+        // The mock doesn't forward logs, which only matters to a subscribed session
+        let subscribed = first.call_tool("studio-logs_subscribe", json!({ "levels": ["error"] })).await.unwrap();
+        assert!(texts(&subscribed).iter().any(|t| t.contains("log_forwarding_off")), "{subscribed}");
+        let again = first.call_tool("studio-var_list", json!({})).await.unwrap();
+        assert!(!texts(&again).iter().any(|t| t.contains("log_forwarding_off")), "warning repeated: {again}");
+        let other = second.call_tool("studio-var_list", json!({})).await.unwrap();
+        assert!(!texts(&other).iter().any(|t| t.contains("log_forwarding_off")), "{other}");
+
+        assert!(logs_subscribed(&first).await);
+        assert!(!logs_subscribed(&second).await);
+
+        // Leaving while the first session listens keeps the plugin subscribed
+        let left = second.call_tool("studio-logs_unsubscribe", json!({})).await.unwrap();
+        assert!(texts(&left)[0].contains("subscribedElsewhere"), "{left}");
+        assert!(logs_subscribed(&first).await);
+        assert!(state.logs_subscribed());
+
+        first.call_tool("studio-logs_unsubscribe", json!({})).await.unwrap();
+        assert!(!logs_subscribed(&first).await);
+        assert!(!state.logs_subscribed());
+    }
+
+    #[tokio::test]
+    async fn sessions_await_their_tool_calls_concurrently() {
+        let state = with_mock_studio().await;
+        let first = Dispatcher::new(state.clone());
+        let second = Dispatcher::new(state.clone());
+
+        // The mock holds an endless loop until scriptTimeoutMs runs out
+        let slow = async {
+            let args = json!({ "code": "while true do end", "scriptTimeoutMs": 500 });
+            let outcome = first.call_tool("studio-run_script", args).await;
+            (outcome, std::time::Instant::now())
+        };
+        let quick = async {
+            let outcome = second.call_tool("studio-run_script", json!({ "code": "print('hi')" })).await;
+            (outcome, std::time::Instant::now())
+        };
+        let ((slow, slow_done), (quick, quick_done)) = tokio::join!(slow, quick);
+        assert!(slow.is_err());
+        assert!(quick.is_ok(), "{quick:?}");
+        assert!(quick_done < slow_done);
+
+        let ids: Vec<String> = state.sessions().into_iter().map(|(id, _)| id).collect();
+        assert!(ids.contains(&first.session.id) && ids.contains(&second.session.id));
+    }
+
+    /// The JSON-RPC error a `tools/call` request line gets.
+    async fn tools_call_error(line: &str) -> (i64, String) {
+        let msg: JsonRpcMessage = serde_json::from_str(line).unwrap();
+        let mut dispatcher = Dispatcher::new(test_state());
+        match dispatcher.request(&msg.method, msg.params).await {
+            Err(DispatchError::Protocol { code, message }) => (code, message),
+            other => panic!("{line} was not refused: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn tools_call_without_params_is_refused() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call"}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "tools/call params must be an object, got null".into()));
+    }
+
+    #[tokio::test]
+    async fn tools_call_with_non_object_params_is_refused() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":["studio-status"]}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "tools/call params must be an object, got an array".into()));
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":"studio-status"}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "tools/call params must be an object, got a string".into()));
+    }
+
+    #[tokio::test]
+    async fn tools_call_without_a_name_is_refused() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"arguments":{}}}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "Missing 'name' in tools/call params".into()));
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":null}}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "Missing 'name' in tools/call params".into()));
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":""}}"#;
+        assert_eq!(tools_call_error(line).await, (-32602, "'name' in tools/call params is empty".into()));
+    }
+
+    #[tokio::test]
+    async fn tools_call_with_a_non_string_name_is_refused() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":7}}"#;
+        assert_eq!(
+            tools_call_error(line).await,
+            (-32602, "'name' in tools/call params must be a string, got a number".into())
+        );
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":{"tool":"studio-status"}}}"#;
+        assert_eq!(
+            tools_call_error(line).await,
+            (-32602, "'name' in tools/call params must be a string, got an object".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn tools_call_with_non_object_arguments_is_refused() {
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"studio-status","arguments":"{}"}}"#;
+        assert_eq!(
+            tools_call_error(line).await,
+            (-32602, "'arguments' in tools/call params must be an object, got a string".into())
+        );
+        let line = r#"{"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"studio-status","arguments":[1]}}"#;
+        assert_eq!(
+            tools_call_error(line).await,
+            (-32602, "'arguments' in tools/call params must be an object, got an array".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn tools_call_with_missing_or_null_arguments_runs() {
+        let mut dispatcher = Dispatcher::new(test_state());
+        let result = dispatcher.request("tools/call", json!({ "name": "studio-var_list" })).await.unwrap();
+        assert_eq!(result["isError"], false);
+        let params = json!({ "name": "studio-var_list", "arguments": null });
+        let result = dispatcher.request("tools/call", params).await.unwrap();
+        assert_eq!(result["isError"], false);
+    }
+
+    #[tokio::test]
+    async fn disabled_tools_are_hidden_from_tools_list() {
+        let config = Config {
+            disabled_tools: ["studio-run_script".to_string()].into(),
+            ..Config::default()
+        };
+        let mut dispatcher = Dispatcher::new(SharedState::for_tests(config));
+        let listed = dispatcher.request("tools/list", json!({})).await.unwrap();
+        let names: Vec<&str> = listed["tools"].as_array().unwrap().iter().filter_map(|t| t["name"].as_str()).collect();
+        assert!(!names.contains(&"studio-run_script"));
+        assert!(names.contains(&"studio-test_script"));
+    }
+
+    #[tokio::test]
+    async fn disabled_tools_are_refused_on_tools_call() {
+        let config = Config {
+            disabled_tools: ["studio-run_script".to_string()].into(),
+            ..Config::default()
+        };
+        let dispatcher = Dispatcher::new(SharedState::for_tests(config));
+        match dispatcher.call_tool("studio-run_script", json!({ "code": "print(1)" })).await {
+            Err(DispatchError::Tool { message, .. }) => {
+                assert_eq!(message, "studio-run_script is disabled by server policy (YIPPIE_DISABLED_TOOLS)");
+            }
+            other => panic!("disabled tool ran: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn screenshot_region_refuses_tags_unsafe_for_a_filename() {
+        let dispatcher = Dispatcher::new(test_state());
+        let region = |tag: &str| json!({ "x": 0, "y": 0, "width": 10, "height": 10, "tag": tag });
+        for tag in ["../../outside", "x'); Remove-Item -Recurse ~; ('"] {
+            let message = tool_error(dispatcher.call_tool("studio-screenshot_region", region(tag)).await);
+            assert!(message.starts_with("Invalid tag: Tag may only contain letters, digits"), "{message}");
+        }
+    }
+
+    /// An MCP client on in-memory pipes, with a plugin that never polls so tool calls
+    /// stay pending.
+    struct PipeClient {
+        input: Option<tokio::io::DuplexStream>,
+        output: Option<tokio::io::BufReader<tokio::io::DuplexStream>>,
+        session: tokio::task::JoinHandle<Result<()>>,
+    }
+
+    impl PipeClient {
+        async fn start(state: &SharedState) -> Self {
+            state.register_client("plugin".into(), "1.0".into(), None, Casing::Snake).await;
+            let (input, server_input) = tokio::io::duplex(64 * 1024);
+            let (output, server_output) = tokio::io::duplex(64 * 1024);
+            let session = tokio::spawn(serve(state.clone(), server_input, server_output));
+            Self {
+                input: Some(input),
+                output: Some(tokio::io::BufReader::new(output)),
+                session,
+            }
+        }
+
+        async fn send(&mut self, message: Value) {
+            let input = self.input.as_mut().unwrap();
+            input.write_all(format!("{message}\n").as_bytes()).await.unwrap();
+        }
+
+        async fn read(&mut self) -> Value {
+            use tokio::io::AsyncBufReadExt;
+            let mut line = String::new();
+            self.output.as_mut().unwrap().read_line(&mut line).await.unwrap();
+            serde_json::from_str(&line).unwrap()
+        }
+
+        /// Handshake, then a tool call the plugin never answers.
+        async fn start_hung_call(&mut self, state: &SharedState) {
+            self.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })).await;
+            assert!(self.read().await.get("result").is_some());
+            self.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+            let call = json!({ "name": "studio-run_script", "arguments": { "code": "print(1)" } });
+            self.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": call })).await;
+            while state.pending_call_count().await == 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn closed_output_ends_the_session_while_stdin_stays_open() {
+        let state = test_state();
+        let mut client = PipeClient::start(&state).await;
+        client.start_hung_call(&state).await;
+
+        // The client stops reading; the next response can't be written
+        client.output = None;
+        client.send(json!({ "jsonrpc": "2.0", "id": 3, "method": "ping" })).await;
+        let ended = tokio::time::timeout(Duration::from_secs(5), &mut client.session).await;
+        assert!(client.input.is_some(), "stdin must still be open");
+        ended.expect("session ended").unwrap().unwrap();
+        assert_eq!(state.pending_call_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn malformed_input_to_a_closed_output_ends_the_session() {
+        let state = test_state();
+        let mut client = PipeClient::start(&state).await;
+        client.start_hung_call(&state).await;
+
+        // The parse error can't be written either
+        client.output = None;
+        client.input.as_mut().unwrap().write_all(b"{not json\n").await.unwrap();
+        let ended = tokio::time::timeout(Duration::from_secs(5), &mut client.session).await;
+        assert!(client.input.is_some(), "stdin must still be open");
+        ended.expect("session ended").unwrap().unwrap();
+        assert_eq!(state.pending_call_count().await, 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closing_stdin_stops_hung_calls_after_the_grace_period() {
+        let state = test_state();
+        let mut client = PipeClient::start(&state).await;
+        client.start_hung_call(&state).await;
+
+        let closed = tokio::time::Instant::now();
+        client.input = None;
+        tokio::time::timeout(REQUEST_TIMEOUT, &mut client.session)
+            .await
+            .expect("session ended before REQUEST_TIMEOUT")
+            .unwrap()
+            .unwrap();
+        assert!(closed.elapsed() >= STDIN_CLOSE_GRACE);
+        assert!(closed.elapsed() < STDIN_CLOSE_GRACE + Duration::from_secs(5));
+        assert_eq!(state.pending_call_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn closing_stdin_answers_finished_calls_first() {
+        let state = test_state();
+        let mut client = PipeClient::start(&state).await;
+        client.send(json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} })).await;
+        client.read().await;
+        client.send(json!({ "jsonrpc": "2.0", "method": "notifications/initialized" })).await;
+        let call = json!({ "name": "studio-var_list", "arguments": {} });
+        client.send(json!({ "jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": call })).await;
+        client.input = None;
+        assert_eq!(client.read().await["id"], 2);
+        tokio::time::timeout(Duration::from_secs(5), &mut client.session).await.unwrap().unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn async_calls_are_cancelled_and_read_by_the_same_operation_id() {
+        let state = with_mock_studio().await;
+        let dispatcher = Dispatcher::new(state);
+        let started = dispatcher
+            .call_tool(
+                "studio-run_script",
+                json!({ "code": "while true do end", "scriptTimeoutMs": 25_000, "async": true }),
+            )
+            .await
+            .unwrap();
+        let started: Value = serde_json::from_str(texts(&started)[0]).unwrap();
+        let operation_id = started["id"].as_str().unwrap().to_string();
+        assert!(started.get("requestId").is_none(), "{started}");
+
+        let Err(DispatchError::Tool { message, .. }) =
+            dispatcher.call_tool("studio-cancel", json!({ "requestId": operation_id })).await
+        else {
+            panic!("studio-cancel took requestId");
+        };
+        assert!(message.contains("Missing 'id'"), "{message}");
+
+        let cancelled = dispatcher.call_tool("studio-cancel", json!({ "id": operation_id })).await.unwrap();
+        let cancelled: Value = serde_json::from_str(texts(&cancelled)[0]).unwrap();
+        assert_eq!(cancelled["id"], json!(operation_id));
+        assert_eq!(cancelled["cancelled"], true);
+
+        let operation = dispatcher.call_tool("studio-get_operation", json!({ "id": operation_id })).await.unwrap();
+        assert_eq!(operation["structuredContent"]["status"], "cancelled", "{operation}");
+
+        let Err(DispatchError::Tool { message, .. }) =
+            dispatcher.call_tool("studio-call_result", json!({ "requestId": operation_id })).await
+        else {
+            panic!("studio-call_result is still served");
+        };
+        assert!(message.contains("Unknown tool"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn read_only_profile_refuses_mutating_tools() {
+        let config = Config {
+            profiles: [("cautious".to_string(), profiles::Profile { read_only: Some(true), ..Default::default() })].into(),
+            profile: Some("cautious".into()),
+            ..Config::default()
+        };
+        let dispatcher = Dispatcher::new(SharedState::for_tests(config));
+
+        let Err(DispatchError::Tool { message, .. }) =
+            dispatcher.call_tool("studio-var_set", json!({ "name": "score", "value": 1 })).await
+        else {
+            panic!("studio-var_set ran under a read-only profile");
+        };
+        assert!(message.contains("profile 'cautious' is read-only"), "{message}");
+        let Err(DispatchError::Tool { message, .. }) = dispatcher.call_tool("studio-var_get", json!({ "name": "score" })).await
+        else {
+            panic!("studio-var_get found a variable that was refused");
+        };
+        assert!(!message.contains("read-only"), "{message}");
+
+        assert!(dispatcher.call_tool("studio-status", json!({})).await.is_ok());
+
+        // The tool call, which the client asks the user about, may leave it
+        dispatcher.call_tool("studio-set_profile", json!({ "profile": "default" })).await.unwrap();
+        assert!(dispatcher.call_tool("studio-var_set", json!({ "name": "score", "value": 1 })).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn capture_compare_refuses_captures_of_another_size_unless_scaled() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = SharedState::for_tests(Config { capture_dir: dir.path().to_path_buf(), ..Config::default() });
+        let captures = state.capture_manager().unwrap();
+        let png = |width: u32, height: u32| viewport_render::encode_png(width, height, &vec![90; (width * height * 3) as usize]).unwrap();
+        let before = captures.save_viewport_render(None, "before".into(), &png(8, 4)).unwrap();
+        let after = captures.save_viewport_render(None, "after".into(), &png(16, 8)).unwrap();
+        let dispatcher = Dispatcher::new(state);
+
+        let Err(DispatchError::Tool { message, .. }) = dispatcher
+            .call_tool("studio-capture_compare", json!({ "before": before.id, "after": after.id }))
+            .await
+        else {
+            panic!("captures of different sizes were compared");
+        };
+        assert!(message.contains("is 8x4") && message.contains("is 16x8"), "{message}");
+
+        let compared = dispatcher
+            .call_tool("studio-capture_compare", json!({ "before": before.id, "after": after.id, "scale": true }))
+            .await
+            .unwrap();
+        let summary: Value = serde_json::from_str(texts(&compared)[0]).unwrap();
+        assert_eq!((summary["width"].as_u64(), summary["height"].as_u64()), (Some(8), Some(4)));
+        assert_eq!(summary["identical"], true, "{summary}");
+    }
+
+    #[tokio::test]
+    async fn tabular_results_render_as_csv_or_text_and_keep_the_json() {
+        let state = test_state();
+        state.push_log("output".into(), "Loaded 3 items, \"fast\"".into(), None);
+        state.push_log("error".into(), "Stack:\n\tScript.Main".into(), None);
+        let dispatcher = Dispatcher::new(state);
+
+        let csv = dispatcher.call_tool("studio-get_output_since", json!({ "seconds": 60, "format": "csv" })).await.unwrap();
+        let rows: Vec<&str> = texts(&csv)[0].split("\r\n").collect();
+        assert_eq!(rows[0], "seq,ts,level,message");
+        assert!(rows[1].ends_with(",output,\"Loaded 3 items, \"\"fast\"\"\""), "{}", rows[1]);
+        assert!(rows[2].ends_with(",error,\"Stack:\n\tScript.Main\""), "{}", rows[2]);
+        assert_eq!(csv["structuredContent"]["entries"][1]["message"], "Stack:\n\tScript.Main");
+
+        let text = dispatcher.call_tool("studio-get_output_since", json!({ "seconds": 60, "format": "TEXT" })).await.unwrap();
+        let lines: Vec<&str> = texts(&text)[0].lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[3].ends_with("error   Stack:\\n\\tScript.Main"), "{}", lines[3]);
+
+        let json = dispatcher.call_tool("studio-get_output_since", json!({ "seconds": 60 })).await.unwrap();
+        let parsed: Value = serde_json::from_str(texts(&json)[0]).unwrap();
+        assert_eq!(parsed["entries"], csv["structuredContent"]["entries"]);
+
+        let Err(DispatchError::Tool { message, .. }) =
+            dispatcher.call_tool("studio-get_output_since", json!({ "seconds": 60, "format": "xml" })).await
+        else {
+            panic!("an unknown format was accepted");
+        };
+        assert_eq!(message, "Unknown format 'xml' (expected json, text or csv)");
+    }
+
+    #[tokio::test]
+    async fn lost_checkpoints_fail_fast_and_can_be_abandoned() {
+        let state = test_state();
+        let checkpoint = |lost| OpenCheckpoint { name: "walls".into(), owner: "studio-a".into(), lost };
+        {
+            let mut resources = state.session_resources().await;
+            resources.open_checkpoints.insert("1".into(), checkpoint(None));
+            resources.open_checkpoints.insert("2".into(), checkpoint(Some(CheckpointLoss::Reloaded)));
+            resources.open_checkpoints.insert("3".into(), checkpoint(Some(CheckpointLoss::Disconnected)));
+        }
+        let dispatcher = Dispatcher::new(state.clone());
+        // Answered without a plugin round trip, with what to do instead
+        let message = tool_error(dispatcher.call_tool("studio-checkpoint_end", json!({ "checkpointId": "2" })).await);
+        assert!(message.starts_with("Checkpoint 'walls' (2) can no longer be ended: the Studio plugin reloaded"), "{message}");
+        assert!(message.contains("studio-checkpoint_begin") && message.contains("studio-checkpoint_abandon"), "{message}");
+
+        let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        assert_eq!(status["session"]["openCheckpoints"], json!(["1"]));
+        let unrecoverable = &status["session"]["unrecoverableCheckpoints"];
+        assert_eq!(unrecoverable[1]["checkpointId"], "3");
+        assert_eq!(unrecoverable[1]["reason"], "the Studio plugin that opened it disconnected");
+
+        let message = tool_error(dispatcher.call_tool("studio-checkpoint_abandon", json!({ "checkpointId": "1" })).await);
+        assert!(message.starts_with("Checkpoint '1' is still open in Studio."), "{message}");
+        let message = tool_error(dispatcher.call_tool("studio-checkpoint_abandon", json!({ "checkpointId": "9" })).await);
+        assert_eq!(message, "No tracked checkpoint '9'");
+
+        let one = dispatcher.call_tool("studio-checkpoint_abandon", json!({ "checkpointId": "3" })).await.unwrap();
+        let one: Value = serde_json::from_str(texts(&one)[0]).unwrap();
+        assert_eq!(one, json!({ "abandoned": ["3"], "stillOpen": 2 }));
+        let rest = dispatcher.call_tool("studio-checkpoint_abandon", json!({})).await.unwrap();
+        let rest: Value = serde_json::from_str(texts(&rest)[0]).unwrap();
+        assert_eq!(rest, json!({ "abandoned": ["2"], "stillOpen": 1 }));
+        assert_eq!(state.session_resources().await.open_checkpoints.keys().collect::<Vec<_>>(), ["1"]);
+    }
+
+    #[tokio::test]
+    async fn initialize_and_status_report_the_same_build() {
+        let state = test_state();
+        let mut dispatcher = Dispatcher::new(state.clone());
+        let initialized = dispatcher.request("initialize", json!({ "protocolVersion": PROTOCOL_VERSION })).await.unwrap();
+        let server_info = &initialized["serverInfo"];
+        assert_eq!(server_info["version"], SERVER_VERSION);
+        assert_eq!(server_info["build"], serde_json::to_value(BuildInfo::get()).unwrap());
+
+        let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        assert_eq!(status["build"], server_info["build"]);
+        let mut runtime = status["runtime"].clone();
+        assert_eq!(runtime["pid"], std::process::id());
+        // Uptime may have ticked over between the two readings
+        runtime["uptimeSecs"] = state.runtime_info()["uptimeSecs"].clone();
+        assert_eq!(runtime, state.runtime_info());
+    }
+
+    #[tokio::test]
+    async fn test_matrix_runs_variants_against_the_mock_and_saves_the_full_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = SharedState::for_tests(Config { capture_dir: dir.path().to_path_buf(), ..Config::default() });
+        tokio::spawn(mock_studio::run(state.clone(), mock_studio::Scenario::default()));
+        while !state.has_connected_client().await {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let dispatcher = Dispatcher::new(state.clone());
+        let arguments = json!({
+            "code": "return workspace.Gravity",
+            "variants": [
+                { "name": "plain", "mode": "run" },
+                { "name": "low gravity", "mode": "play", "properties": { "Workspace": { "Gravity": 50 } } },
+                { "name": "after", "mode": "run" },
+                { "name": "broken", "setup": "   ", "mode": "run", "timeout": 5 }
+            ],
+            "tag": "gravity",
+        });
+        let mut broken = arguments.clone();
+        broken["code"] = json!("error('nope')");
+        broken["variants"] = json!([{ "name": "broken" }]);
+
+        let result = dispatcher.call_tool("studio-test_matrix", arguments).await.unwrap();
+        let summary: Value = serde_json::from_str(texts(&result)[0]).unwrap();
+        assert_eq!(summary["totals"]["total"], 4);
+        assert_eq!(summary["totals"]["passed"], 4);
+        let gravity = |index: usize| {
+            let value: Value = serde_json::from_str(summary["variants"][index]["value"].as_str().unwrap()).unwrap();
+            (value["mode"].clone(), value["properties"]["Workspace.Gravity"].clone())
+        };
+        assert_eq!(gravity(1), (json!("play"), json!(50)));
+        // The override was reverted before the next variant
+        assert_eq!(gravity(2), (json!("run"), json!(196.2)));
+        assert_eq!(summary["variants"][0]["logCount"], 1);
+
+        let report_path = summary["artifact"]["path"].as_str().unwrap();
+        let report: Value = serde_json::from_str(&std::fs::read_to_string(report_path).unwrap()).unwrap();
+        assert_eq!(report["variants"][1]["logs"][0]["message"], "[mock] test_script running");
+        assert_eq!(report["totals"], summary["totals"]);
+
+        // A failing variant is a result, not a tool error
+        let result = dispatcher.call_tool("studio-test_matrix", broken).await.unwrap();
+        let summary: Value = serde_json::from_str(texts(&result)[0]).unwrap();
+        assert_eq!(summary["variants"][0]["status"], "failed");
+
+        state.update_playtest(true, Some("play-1".into()), Some("play".into())).await;
+        let Err(DispatchError::Tool { message, .. }) = dispatcher
+            .call_tool("studio-test_matrix", json!({ "code": "return 1", "variants": [{}] }))
+            .await
+        else {
+            panic!("a matrix started during a playtest");
+        };
+        assert!(message.starts_with("A playtest is running."), "{message}");
+    }
+
+    #[tokio::test]
+    async fn settings_warnings_are_shown_once_and_again_after_they_clear() {
+        let state = test_state();
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let settings = |port: u16| PluginSettings {
+            server_url: format!("http://localhost:{port}"),
+            poll_interval_secs: 0.0,
+            log_forwarding: "all".into(),
+            playtest_bridge: true,
+        };
+        let warnings = |result: &Value| -> Vec<String> {
+            texts(result).into_iter().filter(|t| t.starts_with("Warning from plugin")).map(String::from).collect()
+        };
+        let port = Config::default().port;
+
+        state.set_plugin_settings("edit", settings(port + 1)).await;
+        let first = dispatcher.call_tool("studio-var_list", json!({})).await.unwrap();
+        assert_eq!(
+            warnings(&first),
+            [format!(
+                "Warning from plugin edit (port_mismatch): Plugin server URL http://localhost:{} uses port {}, but this server listens on {port}",
+                port + 1,
+                port + 1
+            )]
+        );
+        let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        assert!(warnings(&status).is_empty(), "warning repeated: {status}");
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        assert_eq!(status["pluginSettings"][0]["warnings"][0]["code"], "port_mismatch");
+
+        // Fixed settings clear the warning, everywhere
+        state.set_plugin_settings("edit", settings(port)).await;
+        let fixed = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        assert!(warnings(&fixed).is_empty());
+        let fixed: Value = serde_json::from_str(texts(&fixed)[0]).unwrap();
+        assert_eq!(fixed["pluginSettings"][0]["warnings"], json!([]));
+        assert_eq!(fixed["pluginSettings"][0]["settings"]["serverUrl"], format!("http://localhost:{port}"));
+
+        // A warning that comes back is news again
+        state.set_plugin_settings("edit", settings(port + 1)).await;
+        let back = dispatcher.call_tool("studio-var_list", json!({})).await.unwrap();
+        assert_eq!(warnings(&back).len(), 1);
+
+        // Settings of unknown clients are ignored
+        state.set_plugin_settings("gone", settings(port + 2)).await;
+        assert_eq!(state.plugin_settings_report(false).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn hooks_post_process_results_and_report_their_failures_in_status() {
+        let raw = json!([
+            { "name": "tag", "tools": ["studio-status"], "script": r#"emit(`hooked ${result.tool}, ${result.json.hooks.len()} hooks`);"# },
+            { "name": "slow", "tools": ["*"], "script": "loop {}" },
+        ]);
+        let config = Config { hooks: crate::hooks::parse_hooks(&raw.to_string()).unwrap(), ..Config::default() };
+        let dispatcher = Dispatcher::new(SharedState::for_tests(config));
+
+        let result = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        let blocks = texts(&result);
+        let status: Value = serde_json::from_str(blocks[0]).unwrap();
+        assert_eq!(blocks[1], "hooked studio-status, 2 hooks");
+        assert!(blocks[2].starts_with("Hook warnings:\n- hook 'slow' failed: "), "{}", blocks[2]);
+        assert_eq!(blocks.len(), 3);
+        assert_ne!(result["isError"], true);
+        // The status was taken before this call's hooks ran
+        assert_eq!(status["hooks"][1]["runs"], 0);
+
+        let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        assert_eq!((&status["hooks"][0]["runs"], &status["hooks"][0]["failed"]), (&json!(1), &json!(0)));
+        assert_eq!((&status["hooks"][1]["runs"], &status["hooks"][1]["failed"]), (&json!(1), &json!(1)));
+        assert!(status["hooks"][1]["lastError"].as_str().unwrap().contains("operations"), "{status}");
+    }
+
+    #[tokio::test]
+    async fn logs_diff_sessions_returns_a_bounded_diff_and_saves_the_full_one() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = SharedState::for_tests(Config { capture_dir: dir.path().to_path_buf(), ..Config::default() });
+        for (session, messages) in [
+            ("s-good", ["12:00:01 Round 1 started", "Saved in 0.8s", "Round 1 finished"]),
+            ("s-bad", ["12:30:44 Round 1 started", "Save failed: 502", "Round 1 finished"]),
+        ] {
+            for message in messages {
+                state.push_log("info".into(), message.into(), Some(session.into()));
+            }
+        }
+        let dispatcher = Dispatcher::new(state);
+        let call = |arguments: Value| dispatcher.call_tool("studio-logs_diff_sessions", arguments);
+
+        let result = call(json!({ "baselineSessionId": "s-good", "currentSessionId": "s-bad", "maxLines": 4 })).await.unwrap();
+        let report: Value = serde_json::from_str(texts(&result)[0]).unwrap();
+        assert_eq!(report["source"], "buffer");
+        assert_eq!(report["identical"], false);
+        assert_eq!(
+            report["diff"],
+            json!(["--- session s-good", "+++ session s-bad", "@@ -1,3 +1,3 @@", " [info] <timestamp> Round <number> started"])
+        );
+        assert_eq!((&report["diffTruncated"], &report["diffLinesTotal"]), (&json!(true), &json!(7)));
+        let saved = std::fs::read_to_string(report["artifact"]["path"].as_str().unwrap()).unwrap();
+        assert!(saved.contains("-[info] Saved in <number>s\n+[info] Save failed: <number>\n"), "{saved}");
+
+        // normalize: false compares the raw lines
+        let result = call(json!({ "baselineSessionId": "s-good", "currentSessionId": "s-bad", "normalize": false })).await.unwrap();
+        let report: Value = serde_json::from_str(texts(&result)[0]).unwrap();
+        assert_eq!(report["onlyInBaselineTotal"], 2);
+
+        for (arguments, expected) in [
+            (json!({ "baselineSessionId": "s-good" }), "Missing required arguments"),
+            (json!({ "baselineSessionId": "s-good", "currentSessionId": "s-none" }), "No log entries for session 's-none' in the log buffer"),
+        ] {
+            let Err(DispatchError::Tool { message, .. }) = call(arguments.clone()).await else {
+                panic!("{arguments} succeeded");
+            };
+            assert!(message.contains(expected), "{message}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn a_timed_out_test_script_returns_its_session_logs_as_a_partial_result() {
+        let state = test_state();
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        state.push_log("info".into(), "an earlier session".into(), Some("t0".into()));
+        let dispatcher = Dispatcher::new(state.clone());
+        let started = tokio::time::Instant::now();
+        let call = tokio::spawn(async move { dispatcher.call_tool("studio-test_script", json!({ "code": "wait(999)", "timeout": 5 })).await });
+
+        // The plugin starts the playtest and the test logs, but no answer comes
+        let request = loop {
+            match state.drain_outbound("edit").await.pop() {
+                Some(request) => break request,
+                None => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        assert_eq!(request.tool_name, "studio-test_script");
+        state.update_playtest(true, Some("t1".into()), Some("run".into())).await;
+        state.push_log("info".into(), "test started".into(), Some("t1".into()));
+        state.push_log("error".into(), "Workspace.Test:3: still waiting".into(), Some("t1".into()));
+
+        let result = call.await.unwrap().unwrap();
+        assert!(started.elapsed() >= Duration::from_secs(5) + test_matrix::STOP_GRACE);
+        assert_ne!(result["isError"], true);
+        let partial = &result["structuredContent"];
+        assert_eq!(serde_json::from_str::<Value>(texts(&result)[0]).unwrap(), *partial);
+        assert_eq!((&partial["timedOut"], &partial["errorCode"], &partial["sessionId"]), (&json!(true), &json!("TIMEOUT"), &json!("t1")));
+        let logs: Vec<&str> = partial["logs"].as_array().unwrap().iter().map(|l| l["message"].as_str().unwrap()).collect();
+        assert_eq!(logs, ["test started", "Workspace.Test:3: still waiting"]);
+        assert_eq!(partial["playtest"]["mode"], "run");
+        // The late answer finds nothing waiting for it
+        assert_eq!(state.pending_call_count().await, 0);
+        let late = BridgeToolResponse { request_id: request.request_id.clone(), success: true, result: Some(json!({})), error: None };
+        assert!(!state.resolve_pending(&request.request_id, late).await);
+    }
+
+    /// Log output of the test, for checking what the tracing spans carry.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for CapturedLogs {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn the_mcp_client_identity_reaches_history_captures_logs_metrics_and_profiles() {
+        let logs = CapturedLogs::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer({
+                let logs = logs.clone();
+                move || logs.clone()
+            })
+            .with_ansi(false)
+            .finish();
+        let _logging = tracing::subscriber::set_default(subscriber);
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            capture_dir: dir.path().to_path_buf(),
+            profiles: profiles::parse_profiles(r#"{"slow": {"toolTimeoutSecs": 90, "clients": ["harness"]}}"#).unwrap(),
+            ..Config::default()
+        };
+        let state = SharedState::for_tests(config);
+        tokio::spawn(mock_studio::run(state.clone(), mock_studio::Scenario::default()));
+        while state.plugin_settings_report(false).await.is_empty() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for session in ["s1", "s2"] {
+            state.push_log("info".into(), format!("ran {session}"), Some(session.into()));
+        }
+
+        let mut harness = Dispatcher::new(state.clone());
+        harness.request("initialize", json!({ "clientInfo": { "name": "Harness", "version": "2.1" } })).await.unwrap();
+        let mut other = Dispatcher::new(state.clone());
+        other.request("initialize", json!({ "clientInfo": { "name": "Other" } })).await.unwrap();
+
+        // Matched case-insensitively by name, for every session from then on
+        assert_eq!(state.settings().name(), "slow");
+
+        harness.call_tool("studio-run_script", json!({ "code": "return 1" })).await.unwrap();
+        let diff = harness
+            .call_tool("studio-logs_diff_sessions", json!({ "baselineSessionId": "s1", "currentSessionId": "s2" }))
+            .await
+            .unwrap();
+        let diff: Value = serde_json::from_str(texts(&diff)[0]).unwrap();
+        let status = other.call_tool("studio-status", json!({})).await.unwrap();
+        let status: Value = serde_json::from_str(texts(&status)[0]).unwrap();
+        assert_eq!(status["session"]["mcpClient"], "Other");
+
+        // History
+        let history = harness.call_tool("studio-get_tool_history", json!({})).await.unwrap();
+        let history: Value = serde_json::from_str(texts(&history)[0]).unwrap();
+        let calls: Vec<(&str, &str)> = history["calls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["tool"].as_str().unwrap(), c["client"].as_str().unwrap()))
+            .collect();
+        assert_eq!(
+            calls,
+            [
+                ("studio-run_script", "Harness/2.1"),
+                ("studio-logs_diff_sessions", "Harness/2.1"),
+                ("studio-status", "Other")
+            ]
+        );
+
+        // The capture index
+        let captures = state.capture_manager().unwrap().list_captures().unwrap();
+        let capture = captures.iter().find(|c| diff["artifact"]["id"] == c.id.as_str()).unwrap();
+        assert_eq!(capture.mcp_client.as_deref(), Some("Harness/2.1"));
+
+        // Metrics
+        let counts = state.tool_calls_by_client();
+        assert_eq!((counts["Harness/2.1"], counts["Other"]), (3, 1));
+
+        // Log lines of the call, plugin routing included
+        let logged = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        let routed = logged.lines().find(|l| l.contains("Forwarding tool call to plugin")).expect(&logged);
+        assert!(routed.contains(r#"tool_call{mcp_client="Harness/2.1"}"#), "{routed}");
+    }
+
+    fn tool_error(result: Result<Value, DispatchError>) -> String {
+        match result {
+            Err(DispatchError::Tool { message, .. }) => message,
+            other => panic!("expected a tool error, got {other:?}"),
+        }
+    }
+
+    fn transaction_summary(result: &Value) -> Value {
+        serde_json::from_str(texts(result)[0]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn transactions_commit_or_roll_back_in_the_mock_plugin() {
+        let state = with_mock_studio().await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let script = |code: &str| json!({ "tool": "studio-run_script", "arguments": { "code": code } });
+
+        let result = dispatcher
+            .call_tool(
+                "studio-transaction",
+                json!({
+                    "name": "Build walls",
+                    "steps": [script("return 1"), { "tool": "studio-set_pivot", "arguments": { "path": "Workspace.Wall", "position": [1, 2, 3] } }],
+                }),
+            )
+            .await
+            .unwrap();
+        assert_ne!(result["isError"], true, "{result}");
+        let committed = transaction_summary(&result);
+        assert_eq!((&committed["committed"], &committed["steps"], &committed["completed"]), (&json!(true), &json!(2), &json!(2)));
+        assert_eq!(committed["results"][1]["result"]["pivot"]["position"], json!({ "x": 1.0, "y": 2.0, "z": 3.0 }), "{committed}");
+        assert!(committed["results"].as_array().unwrap().iter().all(|r| r["ok"] == true && r["elapsedMs"].is_u64()));
+        assert!(committed.get("rollback").is_none());
+        assert!(state.session_resources().await.open_checkpoints.is_empty());
+
+        // The second step fails, so the third never runs and the recording is cancelled
+        let result = dispatcher
+            .call_tool("studio-transaction", json!({ "steps": [script("return 1"), { "tool": "studio-set_pivot" }, script("return 3")] }))
+            .await;
+        let failed: Value = serde_json::from_str(&tool_error(result)).unwrap();
+        assert_eq!((&failed["committed"], &failed["reason"], &failed["failedStep"]), (&json!(false), &json!("failed"), &json!(1)), "{failed}");
+        assert_eq!((&failed["steps"], &failed["completed"]), (&json!(3), &json!(2)));
+        let results = failed["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!((&results[0]["ok"], &results[1]["ok"]), (&json!(true), &json!(false)));
+        assert!(results[0]["result"].is_object() && results[1].get("result").is_none());
+        assert!(results[1]["error"].as_str().unwrap().contains("Missing 'path' argument"), "{failed}");
+        assert_eq!(failed["rollback"], json!({ "ok": true, "action": "cancelled_recording" }));
+        assert!(state.session_resources().await.open_checkpoints.is_empty());
+
+        // Checkpoints can't open during a playtest, and then no step runs
+        dispatcher.call_tool("studio-playtest_run", json!({})).await.unwrap();
+        let message = tool_error(dispatcher.call_tool("studio-transaction", json!({ "steps": [script("return 1")] })).await);
+        assert!(message.starts_with("Could not open a checkpoint for the transaction, no step was run: "), "{message}");
+        assert!(message.contains("playtest mode"), "{message}");
+    }
+
+    #[tokio::test]
+    async fn invalid_transactions_are_refused_before_a_checkpoint_opens() {
+        let state = test_state();
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let refused = |steps: Value| {
+            let dispatcher = dispatcher.clone();
+            async move { tool_error(dispatcher.call_tool("studio-transaction", json!({ "steps": steps })).await) }
+        };
+        let script = json!({ "tool": "studio-run_script", "arguments": { "code": "return 1" } });
+
+        let nested = json!({ "tool": "studio-transaction", "arguments": { "steps": [script] } });
+        assert_eq!(refused(json!([script, nested])).await, "Step 1: studio-transaction cannot be nested");
+        let unsafe_step = json!({ "tool": "studio-set_studio_setting", "arguments": {} });
+        let message = refused(json!([script, unsafe_step])).await;
+        assert!(message.starts_with("Step 1: studio-set_studio_setting can't run in a transaction."), "{message}");
+        assert!(message.ends_with("allowed: studio-run_script, studio-set_collision_group, studio-set_pivot"), "{message}");
+        let async_step = json!({ "tool": "studio-run_script", "arguments": { "code": "return 1", "async": true } });
+        assert!(refused(json!([async_step])).await.starts_with("Step 0: async calls can't run in a transaction"));
+        assert_eq!(refused(json!([{ "tool": "studio-nope" }])).await, "Step 0: unknown tool 'studio-nope'");
+        assert_eq!(refused(json!([])).await, "Transaction has no steps");
+        let message = tool_error(dispatcher.call_tool("studio-transaction", json!({ "steps": [script], "timeoutMs": 0 })).await);
+        assert_eq!(message, "'timeoutMs' must be positive");
+
+        // Nothing reached the plugin
+        assert!(state.drain_outbound("edit").await.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn transaction_steps_share_one_time_budget() {
+        let state = test_state();
+        // Each script takes as many seconds as it says; "hang" never answers
+        let called = mock_studio::scripted::start(&state, |request| match request.tool_name.as_str() {
+            "studio-checkpoint_begin" => Some((Duration::ZERO, Ok(json!({ "checkpointId": "cp_1", "name": "MCP Transaction" })))),
+            "studio-checkpoint_undo" => Some((Duration::ZERO, Ok(json!({ "ok": true, "action": "cancelled_recording" })))),
+            _ => match request.arguments["code"].as_str()? {
+                "hang" => None,
+                seconds => Some((Duration::from_secs(seconds.parse().unwrap()), Ok(json!({ "value": seconds })))),
+            },
+        })
+        .await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let script = |code: &str| json!({ "tool": "studio-run_script", "arguments": { "code": code } });
+
+        let started = tokio::time::Instant::now();
+        let result = dispatcher
+            .call_tool("studio-transaction", json!({ "steps": [script("3"), script("4"), script("hang"), script("1")], "timeoutMs": 10_000 }))
+            .await;
+        // The third step gets what the first two left of the budget, not a full timeout
+        assert_eq!(started.elapsed(), Duration::from_secs(10));
+        let failed: Value = serde_json::from_str(&tool_error(result)).unwrap();
+        assert_eq!((&failed["reason"], &failed["failedStep"], &failed["completed"]), (&json!("timedOut"), &json!(2), &json!(3)), "{failed}");
+        let elapsed: Vec<u64> = failed["results"].as_array().unwrap().iter().map(|r| r["elapsedMs"].as_u64().unwrap()).collect();
+        assert_eq!(elapsed, [3_000, 4_000, 3_000]);
+        assert_eq!(failed["results"][2]["error"], "Ran out of the transaction's 10000 ms budget");
+        assert_eq!(failed["rollback"]["ok"], true);
+        assert!(failed["rollback"]["warning"].as_str().unwrap().contains("may still be running"), "{failed}");
+        assert_eq!(
+            called.tools(),
+            ["studio-checkpoint_begin", "studio-run_script", "studio-run_script", "studio-run_script", "studio-checkpoint_undo"]
+        );
+        assert!(state.session_resources().await.open_checkpoints.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_commits_and_rollbacks_are_reported() {
+        let state = test_state();
+        let called = mock_studio::scripted::answering(&state, |tool, _| match tool {
+            "studio-checkpoint_begin" => Ok(json!({ "checkpointId": "cp_1", "name": "MCP Transaction" })),
+            "studio-checkpoint_end" => Err("Studio lost the recording".to_string()),
+            "studio-checkpoint_undo" => Err("Nothing to undo".to_string()),
+            _ => Ok(json!({ "value": "1" })),
+        })
+        .await;
+        let dispatcher = Dispatcher::new(state.clone());
+
+        let result = dispatcher
+            .call_tool("studio-transaction", json!({ "steps": [{ "tool": "studio-run_script", "arguments": { "code": "return 1" } }] }))
+            .await;
+        let failed: Value = serde_json::from_str(&tool_error(result)).unwrap();
+        assert_eq!((&failed["committed"], &failed["reason"]), (&json!(false), &json!("commitFailed")), "{failed}");
+        assert!(failed.get("failedStep").is_none());
+        assert!(failed["commitError"].as_str().unwrap().contains("Studio lost the recording"), "{failed}");
+        assert_eq!(failed["results"][0]["ok"], true);
+        assert_eq!(failed["rollback"]["ok"], false);
+        assert!(failed["rollback"]["error"].as_str().unwrap().contains("Nothing to undo"), "{failed}");
+        assert!(failed["rollback"].get("warning").is_none());
+        assert_eq!(
+            called.tools(),
+            ["studio-checkpoint_begin", "studio-run_script", "studio-checkpoint_end", "studio-checkpoint_undo"]
+        );
+    }
+
+    #[tokio::test]
+    async fn cached_results_last_until_a_call_that_may_change_studio() {
+        let state = test_state();
+        let called = mock_studio::scripted::answering(&state, |tool, args| match tool {
+            "studio-get_property_type" => Ok(json!({ "className": args["className"], "valueType": "number" })),
+            _ => Ok(json!({ "value": "1" })),
+        })
+        .await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let tweenable = json!({ "className": "Part", "property": "Transparency" });
+
+        let first = dispatcher.call_tool("studio-is_tweenable", tweenable.clone()).await.unwrap();
+        let second = dispatcher.call_tool("studio-is_tweenable", tweenable.clone()).await.unwrap();
+        assert_eq!(first, second);
+        assert_eq!(called.tools(), ["studio-get_property_type"]);
+
+        // Read-only calls keep the cache
+        dispatcher.call_tool("studio-get_keybinds", json!({})).await.unwrap();
+        dispatcher.call_tool("studio-is_tweenable", tweenable.clone()).await.unwrap();
+        assert_eq!(called.tools(), ["studio-get_property_type"]);
+
+        dispatcher.call_tool("studio-run_script", json!({ "code": "workspace.Part:Destroy()" })).await.unwrap();
+        dispatcher.call_tool("studio-is_tweenable", tweenable).await.unwrap();
+        assert_eq!(called.tools(), ["studio-get_property_type", "studio-run_script", "studio-get_property_type"]);
+        let status = state.response_cache().unwrap().status();
+        assert_eq!((&status["hits"], &status["invalidations"]), (&json!(2), &json!(1)), "{status}");
+    }
+
+    #[tokio::test]
+    async fn oversized_code_is_refused_before_it_reaches_the_plugin() {
+        let state = test_state();
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let code = format!("local data = {{}} -- {}", "x".repeat(5 * 1024 * 1024));
+        let message = tool_error(dispatcher.call_tool("studio-run_script", json!({ "code": code })).await);
+        assert!(message.starts_with(&format!("'code' is {} bytes; studio-run_script accepts at most", code.len())), "{message}");
+        assert!(state.drain_outbound("edit").await.is_empty());
+        assert_eq!(state.pending_call_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn place_stats_snapshots_are_kept_and_compared() {
+        let state = with_mock_studio().await;
+        let dispatcher = Dispatcher::new(state.clone());
+
+        let message = tool_error(dispatcher.call_tool("studio-place_stats", json!({ "compareTo": "nope" })).await);
+        assert!(message.contains("none are kept yet"), "{message}");
+        let message = tool_error(dispatcher.call_tool("studio-place_stats", json!({ "root": "Workspace.Missing" })).await);
+        assert_eq!(message, "Counting the place failed: Instance not found: Workspace.Missing");
+
+        let first = dispatcher.call_tool("studio-place_stats", json!({})).await.unwrap();
+        let first_id = first["structuredContent"]["id"].as_str().unwrap().to_string();
+        assert_eq!(first["structuredContent"]["totals"]["instances"], 168, "{first}");
+        assert!(first["structuredContent"].get("comparison").is_none());
+        assert!(texts(&first)[0].starts_with(&format!("Place stats {first_id} of game at ")));
+
+        // The mock place gained parts and a MeshPart and lost its Sparkles and a union
+        let second = dispatcher.call_tool("studio-place_stats", json!({ "compareTo": first_id, "top": 1 })).await.unwrap();
+        let comparison = &second["structuredContent"]["comparison"];
+        assert_eq!(comparison["comparedTo"], first_id.as_str());
+        assert_eq!(comparison["classesAdded"][0]["class"], "MeshPart");
+        assert_eq!(comparison["classesRemoved"][0]["class"], "Sparkles");
+        assert_eq!(comparison["changes"], json!([{ "class": "Part", "before": 120, "after": 160, "delta": 40 }]));
+        assert_eq!(comparison["changedClasses"], 3, "{comparison}");
+        assert!(texts(&second)[0].contains("\nCount changes (1 of 3, largest first)\n"));
+
+        let message = tool_error(dispatcher.call_tool("studio-place_stats", json!({ "compareTo": "nope" })).await);
+        let second_id = second["structuredContent"]["id"].as_str().unwrap();
+        assert_eq!(message, format!("No place stats snapshot 'nope'. Kept (last 20): {first_id}, {second_id}"));
+        // Refused before the plugin walked the place, so nothing new was kept
+        assert_eq!(state.place_stats().ids(), [first_id.as_str(), second_id]);
+    }
+
+    #[tokio::test]
+    async fn gui_clicks_resolve_selectors_against_recent_gui_tree_snapshots() {
+        let state = with_mock_studio().await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let message = tool_error(dispatcher.call_tool("studio-gui_tree", json!({})).await);
+        assert!(message.contains("Play mode"), "{message}");
+        dispatcher.call_tool("studio-playtest_play", json!({})).await.unwrap();
+
+        let first = dispatcher.call_tool("studio-gui_tree", json!({ "limit": 3 })).await.unwrap();
+        let page = &first["structuredContent"];
+        assert_eq!((&page["total"], &page["returned"], &page["reused"]), (&json!(5), &json!(3), &json!(false)), "{page}");
+        let snapshot_id = page["snapshotId"].as_str().unwrap();
+        assert_eq!(page["nextCursor"], format!("{snapshot_id}:3"));
+        let rest = dispatcher.call_tool("studio-gui_tree", json!({ "cursor": page["nextCursor"] })).await.unwrap();
+        let rest = &rest["structuredContent"];
+        assert_eq!((&rest["snapshotId"], &rest["offset"], &rest["reused"]), (&json!(snapshot_id), &json!(3), &json!(true)));
+        assert_eq!(rest["elements"][1]["path"], "Players.MockPlayer.PlayerGui.MainMenu.Title");
+        let message = tool_error(dispatcher.call_tool("studio-gui_tree", json!({ "cursor": "gone:0" })).await);
+        assert!(message.starts_with("Cursor snapshot 'gone' is no longer kept."), "{message}");
+
+        // The tree's snapshot answers the first click...
+        let clicked = dispatcher.call_tool("studio-gui_click", json!({ "selector": "button containing 'play'" })).await.unwrap();
+        let clicked = &clicked["structuredContent"];
+        assert_eq!(clicked["target"]["path"], "Players.MockPlayer.PlayerGui.MainMenu.PlayButton", "{clicked}");
+        assert_eq!(clicked["target"]["center"], json!({ "x": 640.0, "y": 330.0 }));
+        assert_eq!((&clicked["moved"], &clicked["clicked"]["activated"]), (&json!(false), &json!(true)));
+        assert_eq!((&clicked["snapshot"]["snapshotId"], &clicked["snapshot"]["refreshed"]), (&json!(snapshot_id), &json!(false)));
+
+        // ...and the click makes it stale for the next one
+        let clicked = dispatcher.call_tool("studio-gui_click", json!({ "path": "StarterGui.MainMenu.SettingsButton" })).await.unwrap();
+        let clicked = &clicked["structuredContent"];
+        assert_eq!(clicked["snapshot"]["refreshed"], true);
+        assert_ne!(clicked["snapshot"]["snapshotId"], snapshot_id);
+        assert_eq!(clicked["target"]["className"], "ImageButton");
+
+        let message = tool_error(dispatcher.call_tool("studio-gui_click", json!({ "selector": "label containing 'Title'" })).await);
+        assert!(message.starts_with("not_a_gui_button: label containing 'Title' (Players.MockPlayer.PlayerGui.MainMenu.Title) is a TextLabel"), "{message}");
+        let message = tool_error(dispatcher.call_tool("studio-gui_click", json!({ "selector": "button containing 'Quit'", "maxAgeMs": 0 })).await);
+        assert_eq!(message, "Nothing in the GUI matches button containing 'Quit'");
+        let message = tool_error(dispatcher.call_tool("studio-gui_click", json!({ "selector": "button containing 'Play'", "maxAgeMs": 60_001 })).await);
+        assert_eq!(message, "'maxAgeMs' must be a number of milliseconds from 0 to 60000");
+    }
+
+    #[tokio::test]
+    async fn test_script_phases_reach_the_time_report_and_its_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = SharedState::for_tests(Config { capture_dir: dir.path().to_path_buf(), ..Config::default() });
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        let dispatcher = Dispatcher::new(state.clone());
+        let empty = dispatcher.call_tool("studio-time_report", json!({})).await.unwrap();
+        assert_eq!(empty["structuredContent"]["sessions"], 0);
+
+        let call = tokio::spawn({
+            let dispatcher = dispatcher.clone();
+            async move { dispatcher.call_tool("studio-test_script", json!({ "code": "print(1)", "mode": "run" })).await }
+        });
+        // Pulled, then the playtest runs and stops before the result comes back
+        let request = loop {
+            match state.drain_outbound("edit").await.pop() {
+                Some(request) => break request,
+                None => tokio::time::sleep(Duration::from_millis(5)).await,
+            }
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        state.update_playtest(true, Some("s1".into()), Some("run".into())).await;
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        state.update_playtest(false, None, None).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let result = json!({ "success": true, "sessionId": "s1", "logs": [] });
+        let response = BridgeToolResponse { request_id: request.request_id.clone(), success: true, result: Some(result), error: None };
+        assert!(state.resolve_pending(&request.request_id, response).await);
+        call.await.unwrap().unwrap();
+
+        let report = dispatcher.call_tool("studio-time_report", json!({ "tag": "suite" })).await.unwrap();
+        let summary = &report["structuredContent"];
+        assert_eq!((&summary["sessions"], &summary["outcomes"], &summary["missingMarks"]), (&json!(1), &json!({ "passed": 1 }), &json!({})), "{summary}");
+        let phase = |name: &str| summary["phases"][name]["totalMs"].as_u64().unwrap();
+        assert!(phase("startup") >= 20 && phase("execution") >= 40 && phase("teardown") >= 20, "{summary}");
+        let phases: u64 = time_report::Phase::ALL.iter().map(|p| phase(p.as_str())).sum();
+        assert_eq!(phases, summary["totalMs"].as_u64().unwrap());
+        assert_eq!((&summary["slowest"][0]["sessionId"], &summary["slowest"][0]["mode"]), (&json!("s1"), &json!("run")));
+
+        let csv = std::fs::read_to_string(summary["csv"]["path"].as_str().unwrap()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with(&format!("1,{},s1,run,passed,", request.request_id)), "{csv}");
+        let message = tool_error(dispatcher.call_tool("studio-time_report", json!({ "last": 0 })).await);
+        assert_eq!(message, "'last' must be an integer from 1 to 200");
+    }
 }
@@ -0,0 +1,1502 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::bridge_http::handle_event;
+use crate::state::SharedState;
+use crate::types::*;
+use crate::wire::Casing;
+
+/// Plugin version reported by the mock client (shows up in studio-status).
+const MOCK_PLUGIN_VERSION: &str = "mock-studio";
+
+/// Readiness stages a mock Play mode playtest reports after it starts, each after
+/// the delay from the previous one.
+const MOCK_SPAWN_STAGES: [(Duration, &str); 2] =
+    [(Duration::from_millis(300), "playerAdded"), (Duration::from_millis(900), "characterSpawned")];
+
+/// Collision groups the mock reports, with the groups each one collides with.
+const MOCK_COLLISION_GROUPS: &[(&str, &[&str])] =
+    &[("Default", &["Default", "Players"]), ("Players", &["Default"]), ("Ghosts", &[])];
+
+/// Default of a service property studio-set_properties can change in the mock.
+fn mock_property_default(key: &str) -> Option<Value> {
+    Some(match key {
+        "Lighting.ClockTime" => json!(14.0),
+        "Lighting.Brightness" => json!(2.0),
+        "Lighting.GlobalShadows" => json!(true),
+        "Workspace.Gravity" => json!(196.2),
+        "Workspace.FallenPartsDestroyHeight" => json!(-500.0),
+        "Workspace.StreamingEnabled" => json!(false),
+        "Workspace.SignalBehavior" => json!("Default"),
+        "Players.CharacterAutoLoads" => json!(true),
+        "Players.RespawnTime" => json!(5.0),
+        // Studio settings, for studio-set_studio_settings
+        "Physics.AreOwnersShown"
+        | "Physics.AreAssembliesShown"
+        | "Physics.AreContactPointsShown"
+        | "Physics.AreAwakePartsHighlighted"
+        | "Physics.ShowDecompositionGeometry" => json!(false),
+        "Physics.AllowSleep" => json!(true),
+        "Physics.PhysicsEnvironmentalThrottle" => json!("DefaultAuto"),
+        "Network.IncomingReplicationLag" => json!(0.0),
+        _ => return None,
+    })
+}
+
+/// Source of the script a failing mock test_script reports in its traceback.
+const MOCK_MODULE_SOURCE: &str = "local MockModule = {}\n\nfunction MockModule.fail()\n\terror(\"[mock] test code raised an error\")\nend\n\nfunction MockModule.run()\n\tMockModule.fail()\nend\n\nreturn MockModule\n";
+
+/// Origin of the mock's own clock, the counterpart of the bridge's `os.clock()`.
+static MOCK_CLOCK_ORIGIN: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+fn mock_clock() -> f64 {
+    MOCK_CLOCK_ORIGIN.elapsed().as_secs_f64()
+}
+
+/// Simulated travel time for npc_driver move_to commands.
+const MOCK_MOVE_DURATION: Duration = Duration::from_millis(750);
+
+/// Optional scenario file for `--mock-scenario`.
+///
+/// ```json
+/// {
+///   "startupLogs": [{ "level": "output", "message": "Server booted" }],
+///   "responses": {
+///     "studio-run_script": { "success": true, "result": { "value": "42" }, "delayMs": 200 }
+///   },
+///   "pluginSettings": { "pollIntervalSecs": 90 }
+/// }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Scenario {
+    #[serde(default)]
+    startup_logs: Vec<ScenarioLog>,
+    /// Fields that replace the mock's default `studio-settings` report.
+    #[serde(default)]
+    plugin_settings: serde_json::Map<String, Value>,
+    #[serde(default)]
+    responses: HashMap<String, ScenarioResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioLog {
+    #[serde(default = "default_level")]
+    level: String,
+    message: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ScenarioResponse {
+    #[serde(default = "default_success")]
+    success: bool,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    delay_ms: u64,
+}
+
+fn default_level() -> String {
+    "output".into()
+}
+
+fn default_success() -> bool {
+    true
+}
+
+pub fn load_scenario(path: &Path) -> Result<Scenario> {
+    let data = std::fs::read_to_string(path)
+        .with_context(|| format!("reading mock scenario {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("parsing mock scenario {}", path.display()))
+}
+
+/// Simulated Studio-side state, the equivalent of the plugin's module-level locals.
+#[derive(Default)]
+struct MockStudio {
+    playtest: Option<(String, String)>,
+    next_session: u64,
+    /// Open checkpoint id -> name
+    checkpoints: HashMap<String, String>,
+    next_checkpoint: u64,
+    /// Committed checkpoint names, oldest first; the first `history_position` are applied
+    history: Vec<String>,
+    history_position: usize,
+    drivers: HashMap<String, String>,
+    next_driver: u64,
+    held_keys: HashSet<String>,
+    recording: Option<(String, std::time::Instant)>,
+    log_seq: u64,
+    /// "Service.Property" -> value, for properties changed by studio-set_properties
+    properties: HashMap<String, Value>,
+    /// Part path -> owner set by studio-set_network_ownership; None is automatic
+    network_owners: HashMap<String, Option<String>>,
+    /// studio-place_stats calls so far; each one finds the place a little changed
+    place_stats_taken: u64,
+}
+
+/// Run the in-process fake plugin.
+///
+/// Registers a regular client in `SharedState` and services it the same way the
+/// Luau plugin does: drain the outbound queue, long-wait on the notify handle,
+/// resolve pending calls and feed events through the bridge event handler.
+pub async fn run(state: SharedState, scenario: Scenario) {
+    let client_id = state.next_id();
+    state
+        .register_client(client_id.clone(), MOCK_PLUGIN_VERSION.to_string(), None, Casing::Snake)
+        .await;
+    tracing::info!(client_id = %client_id, "Mock Studio plugin registered");
+
+    let scenario = Arc::new(scenario);
+    let mock = Arc::new(Mutex::new(MockStudio::default()));
+    // Requests being answered, by request id, so studio-cancel can abort them
+    let running: Arc<std::sync::Mutex<HashMap<String, (tokio::task::AbortHandle, String)>>> = Default::default();
+
+    // Like the plugin, report settings right after registering
+    let mut settings = json!({
+        "serverUrl": format!("http://localhost:{}", state.bridge_port()),
+        "pollIntervalSecs": 0,
+        "logForwarding": "off",
+        "playtestBridge": true,
+    });
+    for (key, value) in &scenario.plugin_settings {
+        settings[key] = value.clone();
+    }
+    let event = BridgeEvent {
+        event_type: "studio-settings".into(),
+        data: settings,
+        seq: None,
+    };
+    handle_event(&state, &client_id, &event).await;
+
+    for log in &scenario.startup_logs {
+        push_log(&state, &mock, &log.level, &log.message).await;
+    }
+
+    loop {
+        // Like the bridge's `clock` parameter on each pull
+        state.record_plugin_clock(&client_id, mock_clock()).await;
+        let mut requests = state.drain_outbound(&client_id).await;
+        if requests.is_empty() {
+            let Some(notify) = state.get_notify(&client_id).await else {
+                tracing::warn!("Mock Studio client was removed, stopping");
+                return;
+            };
+            let _ = tokio::time::timeout(Duration::from_secs(25), notify.notified()).await;
+            requests = state.drain_outbound(&client_id).await;
+        }
+
+        for request in requests {
+            if request.tool_name == "studio-cancel" {
+                let target = request.arguments["requestId"].as_str().unwrap_or_default();
+                let result = match running.lock().unwrap_or_else(|e| e.into_inner()).remove(target) {
+                    Some((task, tool)) => {
+                        task.abort();
+                        json!({ "stopped": true, "tool": tool })
+                    }
+                    None => json!({ "stopped": false, "note": "Not running in Studio; it already finished" }),
+                };
+                let response = BridgeToolResponse {
+                    request_id: request.request_id.clone(),
+                    success: true,
+                    result: Some(result),
+                    error: None,
+                };
+                state.resolve_pending(&request.request_id, response).await;
+                continue;
+            }
+            let state = state.clone();
+            let scenario = scenario.clone();
+            let mock = mock.clone();
+            let calls = running.clone();
+            let request_id = request.request_id.clone();
+            let tool = request.tool_name.clone();
+            // Held until the task is listed, so it can't finish and unlist itself first
+            let mut listed = running.lock().unwrap_or_else(|e| e.into_inner());
+            let task = tokio::spawn(async move {
+                let response = respond(&state, &scenario, &mock, request).await;
+                calls.lock().unwrap_or_else(|e| e.into_inner()).remove(&response.request_id);
+                if !state.resolve_pending(&response.request_id, response.clone()).await {
+                    tracing::warn!(request_id = %response.request_id, "Mock response had no pending call");
+                }
+            });
+            listed.insert(request_id, (task.abort_handle(), tool));
+        }
+    }
+}
+
+async fn respond(
+    state: &SharedState,
+    scenario: &Scenario,
+    mock: &Mutex<MockStudio>,
+    request: BridgeToolRequest,
+) -> BridgeToolResponse {
+    tracing::debug!(tool = %request.tool_name, "Mock Studio handling request");
+
+    let outcome = match scenario.responses.get(&request.tool_name) {
+        Some(canned) => {
+            tokio::time::sleep(Duration::from_millis(canned.delay_ms)).await;
+            if canned.success {
+                Ok(canned.result.clone().unwrap_or(json!({ "ok": true })))
+            } else {
+                Err(canned.error.clone().unwrap_or_else(|| "Scenario error".into()))
+            }
+        }
+        None => simulate(state, mock, &request.tool_name, &request.arguments).await,
+    };
+
+    match outcome {
+        Ok(result) => BridgeToolResponse {
+            request_id: request.request_id,
+            success: true,
+            result: Some(result),
+            error: None,
+        },
+        Err(error) => BridgeToolResponse {
+            request_id: request.request_id,
+            success: false,
+            result: None,
+            error: Some(error),
+        },
+    }
+}
+
+/// Built-in behaviour for each tool when the scenario doesn't override it.
+async fn simulate(
+    state: &SharedState,
+    mock: &Mutex<MockStudio>,
+    tool_name: &str,
+    args: &Value,
+) -> Result<Value, String> {
+    let arg_str = |name: &str| args.get(name).and_then(|v| v.as_str()).map(String::from);
+
+    match tool_name {
+        "studio-get_version" => Ok(json!({ "studioVersion": "0.0.0.mock", "luauVersion": "Luau" })),
+        "studio-get_current_tool" => Ok(json!({ "activeTool": "Select", "gridSize": 1, "unavailable": [] })),
+        "studio-focus_camera_on" => Ok(json!({
+            "targets": 1,
+            "method": "ZoomToExtents",
+            "boundsCenter": { "x": 0, "y": 5, "z": 0 },
+            "boundsSize": { "x": 4, "y": 10, "z": 4 },
+            "camera": {
+                "position": { "x": 0, "y": 12, "z": 18 },
+                "lookVector": { "x": 0, "y": -0.36, "z": -0.93 },
+                "components": [0, 12, 18, 1, 0, 0, 0, 0.93, -0.36, 0, 0.36, 0.93],
+            },
+        })),
+        "studio-capture_viewport_via_render" => {
+            let width = args.get("width").and_then(Value::as_u64).unwrap_or(256) as usize;
+            let height = args.get("height").and_then(Value::as_u64).unwrap_or(144) as usize;
+            // Sky above the horizon, baseplate below, a red part in the middle
+            let mut rgb = Vec::with_capacity(width * height * 3);
+            for y in 0..height {
+                for x in 0..width {
+                    let in_part = (width * 2 / 5..width * 3 / 5).contains(&x) && (height * 3 / 10..height * 7 / 10).contains(&y);
+                    let pixel = if in_part {
+                        [196, 40, 28]
+                    } else if y < height / 2 {
+                        [120, 170, 230]
+                    } else {
+                        [99, 95, 98]
+                    };
+                    rgb.extend_from_slice(&pixel);
+                }
+            }
+            use base64::Engine;
+            Ok(json!({
+                "pixels": base64::engine::general_purpose::STANDARD.encode(&rgb),
+                "camera": {
+                    "position": { "x": 0, "y": 12, "z": 18 },
+                    "lookVector": { "x": 0, "y": -0.36, "z": -0.93 },
+                    "fieldOfView": 70,
+                },
+                "hitPixels": width * height / 2,
+                "elapsedMs": 12,
+            }))
+        }
+        "studio-open_script" => {
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            if path != "ServerScriptService.MockModule" {
+                return Err(format!("Instance not found: {path}"));
+            }
+            let line = args.get("line").and_then(Value::as_u64);
+            let lines = MOCK_MODULE_SOURCE.lines().count() as u64;
+            if let Some(line) = line.filter(|l| *l > lines) {
+                return Err(format!("'line' {line} is past the end of {path} ({lines} lines)"));
+            }
+            Ok(json!({
+                "path": path,
+                "className": "ModuleScript",
+                "line": line,
+                "method": if line.is_some() { "OpenScript" } else { "OpenScriptDocumentAsync" },
+            }))
+        }
+        "studio-get_open_scripts" => {
+            let limit = args["limit"].as_u64().unwrap_or(20).clamp(1, 100) as usize;
+            let open = [json!({
+                "path": "ServerScriptService.MockModule",
+                "className": "ModuleScript",
+                "lineCount": MOCK_MODULE_SOURCE.lines().count(),
+                "cursor": { "line": 3, "character": 5 },
+                "visibleLines": { "first": 1, "last": MOCK_MODULE_SOURCE.lines().count() },
+            })];
+            Ok(json!({
+                "scripts": open.iter().take(limit).collect::<Vec<_>>(),
+                "count": open.len(),
+                "truncated": open.len() > limit,
+            }))
+        }
+        "studio-select_matching" => {
+            if ["className", "name", "nameContains", "tag"].iter().all(|key| args.get(*key).is_none()) {
+                return Err("Pass at least one filter: className, name, nameContains or tag".into());
+            }
+            Ok(json!({
+                "root": arg_str("root").unwrap_or_else(|| "Workspace".into()),
+                "matched": 2,
+                "selected": 2,
+                "selectionSize": if args.get("add").and_then(|v| v.as_bool()) == Some(true) { 3 } else { 2 },
+                "truncated": false,
+                "sample": ["Workspace.MockPart1", "Workspace.MockPart2"],
+            }))
+        }
+        "studio-set_properties" | "studio-set_studio_settings" => {
+            let key = if tool_name == "studio-set_properties" { "overrides" } else { "settings" };
+            let overrides = args
+                .get(key)
+                .and_then(|v| v.as_object())
+                .ok_or(format!("Missing '{key}' argument"))?;
+            let mut m = mock.lock().await;
+            let mut changes = Vec::new();
+            for (service, props) in overrides {
+                for (prop, value) in props.as_object().into_iter().flatten() {
+                    let key = format!("{service}.{prop}");
+                    let Some(default) = mock_property_default(&key) else {
+                        return Err(format!("{key} is not a readable property"));
+                    };
+                    let old = m.properties.get(&key).cloned().unwrap_or(default);
+                    changes.push((service.clone(), prop.clone(), key, old, value.clone()));
+                }
+            }
+            let applied = changes.len();
+            let mut previous = json!({});
+            for (service, prop, key, old, new) in changes {
+                previous[service][prop] = old;
+                m.properties.insert(key, new);
+            }
+            Ok(json!({ "previous": previous, "applied": applied }))
+        }
+        "studio-batch_get_properties" => {
+            // Every mock instance is a Part; paths containing "Missing" don't exist
+            let mut values = serde_json::Map::new();
+            let mut errors = serde_json::Map::new();
+            let properties: Vec<&str> = args["properties"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+            for path in args["paths"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if path.contains("Missing") {
+                    errors.insert(path.into(), json!("Instance not found"));
+                    continue;
+                }
+                let mut read = serde_json::Map::new();
+                let mut failed = serde_json::Map::new();
+                for &property in &properties {
+                    let value = match property {
+                        "Name" => json!(path.rsplit('.').next().unwrap_or(path)),
+                        "ClassName" => json!("Part"),
+                        "Anchored" | "CanCollide" => json!(true),
+                        "Transparency" | "Reflectance" => json!(0),
+                        "Position" | "Orientation" => json!([0, 0, 0]),
+                        "Size" => json!([4, 1, 2]),
+                        "Color" => json!([0.64, 0.64, 0.64]),
+                        "Material" => json!("Plastic"),
+                        "Parent" => json!(path.rsplit_once('.').map_or("Workspace", |(parent, _)| parent)),
+                        // An empty reference, left out like the plugin does
+                        "PrimaryPart" => continue,
+                        _ => {
+                            failed.insert(property.into(), json!("not a readable property of Part"));
+                            continue;
+                        }
+                    };
+                    read.insert(property.into(), value);
+                }
+                values.insert(path.into(), Value::Object(read));
+                if !failed.is_empty() {
+                    errors.insert(path.into(), Value::Object(failed));
+                }
+            }
+            Ok(json!({ "values": values, "errors": errors, "truncated": false, "bytes": 0 }))
+        }
+        "studio-snapshot_subtree" => {
+            // A small fixed model; roots containing "MapB" have the door moved, the
+            // window gone and a lamp added, so every kind of difference shows up
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            if path.contains("Missing") {
+                return Err(format!("Instance not found: {path}"));
+            }
+            let variant = path.contains("MapB");
+            let mut instances = serde_json::Map::new();
+            instances.insert("".into(), json!({ "className": "Model", "properties": [] }));
+            instances.insert(
+                "Door".into(),
+                json!({
+                    "className": "Part",
+                    "properties": {
+                        "Position": if variant { json!([4, 3, 0]) } else { json!([0, 3, 0]) },
+                        "Size": [4, 6, 1],
+                        "Anchored": true,
+                        "Tags": ["Interactive"],
+                    },
+                }),
+            );
+            instances.insert("Door.Hinge".into(), json!({ "className": "HingeConstraint", "properties": [] }));
+            if variant {
+                instances.insert(
+                    "Lamp".into(),
+                    json!({ "className": "Part", "properties": { "Position": [0, 8, 0], "Anchored": true } }),
+                );
+                instances.insert("Lamp.PointLight".into(), json!({ "className": "PointLight", "properties": { "Brightness": 2 } }));
+            } else {
+                instances.insert(
+                    "Window".into(),
+                    json!({ "className": "Part", "properties": { "Transparency": 0.5, "@Breakable": true } }),
+                );
+            }
+            let count = instances.len();
+            Ok(json!({ "root": path, "instances": instances, "count": count, "truncated": false }))
+        }
+        "studio-get_script_sources" => {
+            let mut sources = serde_json::Map::new();
+            let mut skipped = serde_json::Map::new();
+            for path in args["paths"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+                if path == "ServerScriptService.MockModule" {
+                    sources.insert(path.into(), json!(MOCK_MODULE_SOURCE));
+                } else {
+                    skipped.insert(path.into(), json!("not found"));
+                }
+            }
+            Ok(json!({ "sources": sources, "skipped": skipped }))
+        }
+        "studio-get_property_type" => {
+            let property = arg_str("property").ok_or("Missing 'property' argument")?;
+            // Every mock instance is a Part
+            let class_name = match arg_str("path") {
+                Some(_) => "Part".to_string(),
+                None => arg_str("className").ok_or("Pass 'path' or 'className'")?,
+            };
+            let (value_type, enum_type) = match property.as_str() {
+                "Transparency" | "Reflectance" | "BackgroundTransparency" | "Volume" => ("number", None),
+                "Position" | "Size" | "Orientation" => ("Vector3", None),
+                "CFrame" | "PivotOffset" => ("CFrame", None),
+                "Color" | "BackgroundColor3" | "TextColor3" => ("Color3", None),
+                "BrickColor" => ("BrickColor", None),
+                "Anchored" | "CanCollide" | "Visible" => ("boolean", None),
+                "Material" => ("EnumItem", Some("Material")),
+                "Name" | "Text" => ("string", None),
+                "Parent" => ("Instance", None),
+                _ => return Err(format!("{property} is not a readable property of {class_name}")),
+            };
+            Ok(json!({
+                "className": class_name,
+                "property": property,
+                "valueType": value_type,
+                "enumType": enum_type,
+                "source": "class",
+            }))
+        }
+        "studio-get_collision_groups" => {
+            let groups: Vec<Value> = MOCK_COLLISION_GROUPS
+                .iter()
+                .enumerate()
+                .map(|(id, (name, collides))| json!({ "name": name, "id": id, "collidesWith": collides }))
+                .collect();
+            if args.get("groupsOnly").and_then(|v| v.as_bool()) == Some(true) {
+                return Ok(json!({ "groups": groups }));
+            }
+            Ok(json!({
+                "groups": groups,
+                "root": arg_str("path").unwrap_or_else(|| "Workspace".into()),
+                "countsByGroup": { "Default": 12, "Players": 1 },
+                "parts": [{ "path": "Workspace.MockPlayerHitbox", "group": "Players" }],
+                "truncated": false,
+            }))
+        }
+        "studio-set_collision_group" => {
+            let group = arg_str("group").unwrap_or_default();
+            if !MOCK_COLLISION_GROUPS.iter().any(|(name, _)| *name == group) {
+                return Err(format!("Collision group is not registered: {group}"));
+            }
+            Ok(json!({
+                "target": arg_str("path").unwrap_or_default(),
+                "group": group,
+                "parts": 1,
+                "changed": 1,
+            }))
+        }
+        "studio-get_pivot" => {
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            Ok(json!({
+                "path": path,
+                "className": "Model",
+                "pivot": mock_pivot([0.0, 5.0, 0.0]),
+                "primaryPart": format!("{path}.Root"),
+                "boundingBox": { "center": { "x": 0, "y": 5, "z": 0 }, "size": { "x": 4, "y": 10, "z": 4 } },
+            }))
+        }
+        "studio-set_pivot" => {
+            if mock.lock().await.playtest.is_some() {
+                return Err("Stop the playtest first: studio-set_pivot edits the edit DataModel, which the running session doesn't see".into());
+            }
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            let position = args
+                .get("position")
+                .and_then(|v| v.as_array())
+                .map(|p| [0, 1, 2].map(|i| p.get(i).and_then(|c| c.as_f64()).unwrap_or(0.0)))
+                .unwrap_or([0.0, 5.0, 0.0]);
+            Ok(json!({ "path": path, "previous": mock_pivot([0.0, 5.0, 0.0]), "pivot": mock_pivot(position) }))
+        }
+        "studio-get_bindable_actions" => {
+            if mock.lock().await.playtest.is_none() {
+                return Err("studio-get_bindable_actions requires an active Play mode playtest (F5)".into());
+            }
+            Ok(json!({
+                "player": "MockPlayer",
+                "count": 1,
+                "actions": [{
+                    "name": "Interact",
+                    "inputs": ["KeyCode.E", "KeyCode.ButtonX"],
+                    "priority": 2000,
+                    "stackOrder": 1,
+                    "createTouchButton": true,
+                    "title": null,
+                    "description": null,
+                }],
+            }))
+        }
+        "studio-get_humanoid_description" => {
+            require_play_mode(mock).await?;
+            if let Some(player) = arg_str("player").filter(|p| p != "MockPlayer") {
+                return Err(format!("No player named '{player}' in the playtest"));
+            }
+            Ok(json!({
+                "player": "MockPlayer",
+                "character": "Workspace.MockPlayer",
+                "rigType": "R15",
+                "bodyColors": {
+                    "Head": "#eab892", "Torso": "#0d69ac", "LeftArm": "#eab892",
+                    "RightArm": "#eab892", "LeftLeg": "#27462d", "RightLeg": "#27462d",
+                },
+                "bodyParts": { "Head": 0, "Torso": 0, "LeftArm": 0, "RightArm": 0, "LeftLeg": 0, "RightLeg": 0 },
+                "scales": {
+                    "HeightScale": 1.0, "WidthScale": 1.0, "DepthScale": 1.0,
+                    "HeadScale": 1.0, "BodyTypeScale": 0.3, "ProportionScale": 1.0,
+                },
+                "clothing": { "Shirt": 0, "Pants": 0, "GraphicTShirt": 0, "Face": 0 },
+                "animations": {
+                    "IdleAnimation": 0, "WalkAnimation": 0, "RunAnimation": 0, "JumpAnimation": 0,
+                    "FallAnimation": 0, "ClimbAnimation": 0, "SwimAnimation": 0,
+                },
+                "accessories": [{ "assetId": 48474313, "type": "Hat", "layered": false, "order": null }],
+                "equipped": [{ "name": "MockHat", "type": "Hat" }],
+            }))
+        }
+        "studio-get_proximity_prompts" => {
+            require_play_mode(mock).await?;
+            let radius = args["radius"].as_f64().unwrap_or(100.0).clamp(1.0, 2000.0);
+            let include_disabled = args["includeDisabled"].as_bool().unwrap_or(true);
+            let prompts: Vec<Value> = MOCK_PROMPTS
+                .iter()
+                .copied()
+                .filter(|(_, _, _, enabled, distance, _)| *distance <= radius && (include_disabled || *enabled))
+                .map(|(path, action, hold, enabled, distance, max)| {
+                    json!({
+                        "path": path,
+                        "actionText": action,
+                        "objectText": "",
+                        "holdDuration": hold,
+                        "enabled": enabled,
+                        "keyboardKeyCode": "E",
+                        "maxActivationDistance": max,
+                        "requiresLineOfSight": true,
+                        "distance": distance,
+                        "inRange": distance <= max,
+                    })
+                })
+                .collect();
+            Ok(json!({
+                "player": "MockPlayer",
+                "origin": [0, 3, 0],
+                "radius": radius,
+                "count": prompts.len(),
+                "prompts": prompts,
+                "truncated": false,
+            }))
+        }
+        "studio-trigger_proximity_prompt" => {
+            require_play_mode(mock).await?;
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            let (_, _, hold, enabled, distance, max) = MOCK_PROMPTS
+                .iter()
+                .copied()
+                .find(|(p, ..)| *p == path)
+                .ok_or_else(|| format!("No instance at '{path}'"))?;
+            if !enabled || distance > max {
+                return Err(format!(
+                    "prompt_not_triggered: {path} did not fire Triggered (enabled: {enabled}, distance {distance} studs, maxActivationDistance {max})"
+                ));
+            }
+            tokio::time::sleep(Duration::from_secs_f64(hold)).await;
+            Ok(json!({
+                "path": path,
+                "player": "MockPlayer",
+                "triggered": true,
+                "holdDuration": hold,
+                "elapsedMs": (hold * 1000.0).round() as u64,
+                "distance": distance,
+            }))
+        }
+        "studio-click_gui_button" => {
+            require_play_mode(mock).await?;
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            let gui_path = path
+                .strip_prefix("StarterGui.")
+                .or_else(|| path.split_once(".PlayerGui.").map(|(_, rest)| rest))
+                .unwrap_or(&path);
+            let (index, (_, class_name)) = MOCK_GUI
+                .iter()
+                .enumerate()
+                .find(|(_, (p, _))| *p == gui_path)
+                .ok_or_else(|| format!("No GUI object at '{path}' on the client"))?;
+            if !matches!(*class_name, "TextButton" | "ImageButton") {
+                return Err(format!("not_a_gui_button: {path} is a {class_name}"));
+            }
+            Ok(json!({
+                "path": format!("Players.MockPlayer.PlayerGui.{gui_path}"),
+                "className": class_name,
+                "activated": true,
+                "mouseButton1Click": true,
+                // The centre of the element in the mock studio-get_gui_tree
+                "position": { "x": 640, "y": 330 + 80 * index },
+                "topObject": format!("Players.MockPlayer.PlayerGui.{gui_path}"),
+                "player": "MockPlayer",
+            }))
+        }
+        "studio-get_sound_playing" => {
+            require_playtest(mock).await?;
+            let play_mode = matches!(&mock.lock().await.playtest, Some((_, mode)) if mode == "play");
+            let include_client = play_mode && args["includeClient"].as_bool().unwrap_or(true);
+            let limit = args["limit"].as_u64().unwrap_or(50).clamp(1, 200) as usize;
+            let found: Vec<Value> = MOCK_SOUNDS
+                .iter()
+                .filter(|(_, side, _, _)| *side == "server" || include_client)
+                .map(|(path, side, looped, position)| {
+                    json!({
+                        "path": path,
+                        "side": side,
+                        "soundId": "rbxassetid://0",
+                        "volume": 0.5,
+                        "timePosition": position,
+                        "timeLength": if *looped { 90.0 } else { 0.3 },
+                        "looped": looped,
+                        "playbackSpeed": 1.0,
+                    })
+                })
+                .collect();
+            let mut result = json!({
+                "count": found.len(),
+                "truncated": found.len() > limit,
+                "sounds": found.into_iter().take(limit).collect::<Vec<_>>(),
+            });
+            // The bridge leaves player out in Run mode
+            if play_mode {
+                result["player"] = "MockPlayer".into();
+            }
+            Ok(result)
+        }
+        "studio-place_stats" => {
+            if arg_str("root").is_some_and(|root| root.contains("Missing")) {
+                return Err(format!("Instance not found: {}", arg_str("root").unwrap_or_default()));
+            }
+            // Every call has 40 more parts and one union fewer; the Sparkles go after the
+            // first call and a MeshPart shows up
+            let n = {
+                let mut m = mock.lock().await;
+                m.place_stats_taken += 1;
+                m.place_stats_taken - 1
+            };
+            let mut classes = serde_json::Map::new();
+            classes.insert("Part".into(), json!(120 + 40 * n));
+            classes.insert("Model".into(), json!(14));
+            classes.insert("UnionOperation".into(), json!(6u64.saturating_sub(n)));
+            classes.insert("Decal".into(), json!(9));
+            classes.insert("Script".into(), json!(3));
+            classes.insert("LocalScript".into(), json!(2));
+            classes.insert("ModuleScript".into(), json!(5 + n));
+            classes.insert("Folder".into(), json!(7));
+            if n == 0 {
+                classes.insert("Sparkles".into(), json!(2));
+            } else {
+                classes.insert("MeshPart".into(), json!(n));
+            }
+            classes.retain(|_, count| count.as_u64() != Some(0));
+            let instances: u64 = classes.values().filter_map(Value::as_u64).sum();
+            Ok(json!({
+                "root": arg_str("root").unwrap_or_else(|| "game".into()),
+                "instances": instances,
+                "classes": classes,
+                "scripts": { "count": 10 + n, "sourceBytes": 18_400 + 1_200 * n, "unreadable": 0 },
+                "textures": { "references": 9 + n, "unique": 4 + n },
+                "skipped": [],
+            }))
+        }
+        "studio-get_network_ownership" => {
+            require_playtest(mock).await?;
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            mock_ownership(&*mock.lock().await, &path)
+        }
+        "studio-get_replication_stats" => {
+            require_playtest(mock).await?;
+            let window_ms = args["windowMs"].as_u64().unwrap_or(2_000);
+            let players: Vec<Value> = match &mock.lock().await.playtest {
+                Some((_, mode)) if mode == "play" => vec![json!({ "name": "MockPlayer", "pingMs": 12 })],
+                _ => Vec::new(),
+            };
+            let player_count = players.len();
+            Ok(json!({
+                "windowMs": window_ms,
+                "samples": window_ms / 250 + 1,
+                "metrics": {
+                    "dataSendKbps": { "avg": 3.2, "peak": 5.8 },
+                    "dataReceiveKbps": { "avg": 0.9, "peak": 1.4 },
+                    "physicsSendKbps": { "avg": 1.1, "peak": 2.0 },
+                    "physicsReceiveKbps": { "avg": 0.4, "peak": 0.7 },
+                    "heartbeatTimeMs": { "avg": 16.7, "peak": 18.1 },
+                },
+                "unavailable": [],
+                "players": players,
+                "playerCount": player_count,
+                "sendPerPlayerKbps": if player_count > 0 { json!(3.2) } else { Value::Null },
+                "notes": [],
+            }))
+        }
+        "studio-set_network_ownership" => {
+            require_playtest(mock).await?;
+            let path = arg_str("path").ok_or("Missing 'path' argument")?;
+            let mut m = mock.lock().await;
+            let previous = mock_ownership(&m, &path)?;
+            if previous["canSetOwner"] != true {
+                return Err(format!("Cannot set network ownership of {path}: {}", previous["cannotSetReason"].as_str().unwrap_or_default()));
+            }
+            let owner = match (args["auto"].as_bool() == Some(true), arg_str("owner")) {
+                (true, _) => None,
+                (false, Some(owner)) if owner == "server" => Some(owner),
+                (false, Some(owner)) if owner == "MockPlayer" && matches!(&m.playtest, Some((_, mode)) if mode == "play") => Some(owner),
+                (false, Some(owner)) => {
+                    return Err(format!("No player named '{owner}' in the playtest. Pass 'server' for the server."));
+                }
+                (false, None) => return Err("Pass 'owner' (a player name or 'server') or auto = true".into()),
+            };
+            m.network_owners.insert(path.clone(), owner);
+            Ok(json!({ "path": path, "previous": previous, "current": mock_ownership(&m, &path)? }))
+        }
+        "studio-get_gui_tree" => {
+            require_play_mode(mock).await?;
+            let max_depth = args["maxDepth"].as_u64().unwrap_or(8).clamp(1, 16);
+            let max_nodes = args["maxNodes"].as_u64().unwrap_or(200).clamp(1, 1000) as usize;
+            let elements: Vec<Value> = MOCK_GUI
+                .iter()
+                .enumerate()
+                .map(|(i, (path, class_name))| {
+                    let name = path.rsplit('.').next().unwrap_or(path);
+                    let mut node = json!({
+                        "name": name,
+                        "className": class_name,
+                        "position": { "x": 540, "y": 300 + 80 * i },
+                        "size": { "x": 200, "y": 60 },
+                        "visible": true,
+                        "zIndex": 1,
+                    });
+                    if class_name.ends_with("Button") {
+                        node["interactable"] = true.into();
+                    }
+                    if class_name.starts_with("Text") {
+                        node["text"] = name.into();
+                    }
+                    node
+                })
+                .collect();
+            let menu = json!({
+                "name": "MainMenu",
+                "className": "ScreenGui",
+                "enabled": true,
+                "displayOrder": 0,
+                "ignoreGuiInset": false,
+                "children": elements,
+            });
+            let (root_path, mut tree) = match arg_str("root") {
+                None => (
+                    "Players.MockPlayer.PlayerGui".to_string(),
+                    json!({ "name": "PlayerGui", "className": "PlayerGui", "children": [menu] }),
+                ),
+                Some(root) => {
+                    let gui_path = root
+                        .strip_prefix("StarterGui.")
+                        .or_else(|| root.split_once(".PlayerGui.").map(|(_, rest)| rest))
+                        .unwrap_or(&root);
+                    let node = if gui_path == "MainMenu" {
+                        menu
+                    } else {
+                        menu["children"]
+                            .as_array()
+                            .and_then(|c| c.iter().find(|n| format!("MainMenu.{}", n["name"].as_str().unwrap_or("")) == gui_path))
+                            .cloned()
+                            .ok_or_else(|| format!("No GUI object at '{root}' on the client"))?
+                    };
+                    (format!("Players.MockPlayer.PlayerGui.{gui_path}"), node)
+                }
+            };
+            let mut budget = max_nodes;
+            let mut truncated = false;
+            prune_gui_tree(&mut tree, 0, max_depth, &mut budget, &mut truncated);
+            Ok(json!({
+                "root": root_path,
+                "tree": tree,
+                "nodeCount": max_nodes - budget,
+                "truncated": truncated,
+                "maxDepth": max_depth,
+                "maxNodes": max_nodes,
+                "viewport": { "x": 1280, "y": 720 },
+                "player": "MockPlayer",
+            }))
+        }
+        "studio-run_script" => {
+            let code = arg_str("code").ok_or("Missing or invalid 'code' argument (must be a string)")?;
+            if mock.lock().await.playtest.is_some() && !args["allowInPlay"].as_bool().unwrap_or(false) {
+                return Err("Cannot run scripts during playtest. Set allowInPlay=true to override.".into());
+            }
+            // Simulate an endless loop being cancelled by scriptTimeoutMs
+            if let Some(ms) = args["scriptTimeoutMs"].as_u64().filter(|_| code.contains("while true do")) {
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                return Err(format!("Script timed out after {ms} ms (scriptTimeoutMs) and was cancelled"));
+            }
+            push_log(state, mock, "output", "[mock] run_script executed").await;
+            let value = json!({ "mock": true, "echo": code }).to_string();
+            Ok(json!({ "value": value, "logs": [] }))
+        }
+        "studio-get_lua_globals" => {
+            // A fixed environment, as if an earlier run_script had left a helper and a counter
+            let limit = args.get("limit").and_then(Value::as_u64).unwrap_or(100).clamp(1, 500) as usize;
+            let scope = |entries: &[(&str, &str)]| {
+                let globals: Vec<Value> = entries.iter().take(limit).map(|(name, kind)| json!({ "name": name, "type": kind })).collect();
+                json!({ "count": entries.len(), "truncated": entries.len() > limit, "globals": globals })
+            };
+            let mut result = json!({
+                "env": scope(&[("counter", "number"), ("helper", "function")]),
+                "_G": scope(&[]),
+                "shared": scope(&[("MockConfig", "table")]),
+            });
+            if args["includeBuiltins"].as_bool() == Some(true) {
+                result["builtins"] = json!([
+                    { "name": "game", "type": "Instance" },
+                    { "name": "workspace", "type": "Instance" },
+                    { "name": "plugin", "type": "Instance" },
+                    { "name": "task", "type": "table" },
+                ]);
+            }
+            Ok(result)
+        }
+        "studio-wait_for_condition" => {
+            let predicate = arg_str("predicate").ok_or("Missing or invalid 'predicate' argument (must be a Luau expression)")?;
+            // A literal `false` never holds, so the timeout path can be exercised
+            if predicate.trim() == "false" {
+                let timeout_ms = args["timeoutMs"].as_u64().unwrap_or(10_000);
+                let interval_ms = args["intervalMs"].as_u64().unwrap_or(100).max(1);
+                tokio::time::sleep(Duration::from_millis(timeout_ms)).await;
+                return Ok(json!({
+                    "met": false,
+                    "timedOut": true,
+                    "waitedMs": timeout_ms,
+                    "attempts": timeout_ms / interval_ms + 1,
+                }));
+            }
+            Ok(json!({ "met": true, "waitedMs": 0, "attempts": 1, "value": true }))
+        }
+        "studio-test_script" => {
+            let code = arg_str("code").ok_or("Missing required argument: code")?;
+            let mode = arg_str("mode").unwrap_or_else(|| "run".into());
+            let session_id = start_playtest(state, mock, &mode).await?;
+            push_log(state, mock, "output", "[mock] test_script running").await;
+            tokio::time::sleep(Duration::from_millis(250)).await;
+            let properties = mock.lock().await.properties.clone();
+            stop_playtest(state, mock).await;
+            // Code that calls error() fails, so failure paths can be exercised; the
+            // error comes from MockModule, whose source studio-get_script_sources serves
+            let failed = code.contains("error(");
+            let mut logs = vec![json!({ "level": "output", "message": "[mock] test_script running" })];
+            if failed {
+                logs.push(json!({
+                    "level": "error",
+                    "message": "ServerScriptService.MockModule:4: [mock] test code raised an error\nStack Begin\nScript 'ServerScriptService.MockModule', Line 4 - function fail\nScript 'ServerScriptService.MockModule', Line 8\nStack End",
+                }));
+            }
+            Ok(json!({
+                "success": !failed,
+                "value": json!({ "mock": true, "echo": code, "mode": mode, "properties": properties }).to_string(),
+                "error": failed.then_some("ServerScriptService.MockModule:4: [mock] test code raised an error"),
+                "logs": logs,
+                "errors": [],
+                "duration": 0.25,
+                "timedOut": false,
+                "sessionId": session_id,
+            }))
+        }
+        "studio-checkpoint_begin" => {
+            let mut m = mock.lock().await;
+            if m.playtest.is_some() {
+                return Err("Failed to begin recording. A recording may already be in progress, or Studio is in playtest mode.".into());
+            }
+            m.next_checkpoint += 1;
+            let checkpoint_id = format!("cp_{}", m.next_checkpoint);
+            let name = arg_str("name").unwrap_or_else(|| "MCP Checkpoint".into());
+            m.checkpoints.insert(checkpoint_id.clone(), name.clone());
+            Ok(json!({ "checkpointId": checkpoint_id, "name": name }))
+        }
+        "studio-checkpoint_end" => {
+            let checkpoint_id = arg_str("checkpointId").ok_or("Missing 'checkpointId' argument")?;
+            let mut m = mock.lock().await;
+            let Some(name) = m.checkpoints.remove(&checkpoint_id) else {
+                return Err(format!("Unknown checkpointId: {checkpoint_id}"));
+            };
+            let position = m.history_position;
+            m.history.truncate(position);
+            m.history.push(name);
+            m.history_position = m.history.len();
+            Ok(json!({
+                "ok": true,
+                "checkpointId": checkpoint_id,
+                "commitMessage": args.get("commitMessage"),
+            }))
+        }
+        "studio-checkpoint_undo" => {
+            let mut m = mock.lock().await;
+            // Cancelling an open recording reverts it without touching the history
+            if let Some(checkpoint_id) = arg_str("checkpointId").filter(|id| m.checkpoints.contains_key(id)) {
+                m.checkpoints.remove(&checkpoint_id);
+                return Ok(json!({ "ok": true, "undoneCheckpointId": checkpoint_id, "action": "cancelled_recording" }));
+            }
+            m.history_position = m.history_position.saturating_sub(1);
+            Ok(json!({ "ok": true, "action": "undo" }))
+        }
+        "studio-set_waypoint" => {
+            let name = arg_str("name").filter(|n| !n.is_empty()).ok_or("Missing 'name' argument")?;
+            let mut m = mock.lock().await;
+            if m.playtest.is_some() {
+                return Err("Stop the playtest first: studio-set_waypoint marks the edit DataModel's undo history, which the running session doesn't use".into());
+            }
+            if let Some(checkpoint_id) = m.checkpoints.keys().next() {
+                return Err(format!(
+                    "Checkpoint {checkpoint_id} is still recording; end it with studio-checkpoint_end before setting a waypoint"
+                ));
+            }
+            let position = m.history_position;
+            m.history.truncate(position);
+            m.history.push(name.clone());
+            m.history_position = m.history.len();
+            Ok(json!({ "ok": true, "name": name, "seq": m.history_position }))
+        }
+        "studio-get_recent_changes" => {
+            let m = mock.lock().await;
+            let limit = args.get("limit").and_then(Value::as_u64).unwrap_or(20).clamp(1, 100) as usize;
+            let waypoints: Vec<Value> = m
+                .history
+                .iter()
+                .enumerate()
+                .rev()
+                .take(limit)
+                .map(|(i, name)| {
+                    json!({
+                        "seq": i + 1,
+                        "name": name,
+                        "state": if i < m.history_position { "applied" } else { "undone" },
+                        "current": i + 1 == m.history_position,
+                    })
+                })
+                .collect();
+            let undo_name = m.history_position.checked_sub(1).map(|i| m.history[i].clone());
+            let redo_name = m.history.get(m.history_position).cloned();
+            Ok(json!({
+                "waypoints": waypoints,
+                "position": m.history_position,
+                "total": m.history.len(),
+                "truncated": m.history.len() > limit,
+                "inSync": true,
+                "trackedSince": 0,
+                "unavailable": [],
+                "canUndo": undo_name.is_some(),
+                "undoName": undo_name,
+                "canRedo": redo_name.is_some(),
+                "redoName": redo_name,
+            }))
+        }
+        "studio-playtest_play" | "studio-playtest_run" => {
+            let mode = if tool_name == "studio-playtest_play" { "play" } else { "run" };
+            let session_id = start_playtest(state, mock, mode).await?;
+            Ok(json!({
+                "sessionId": session_id,
+                "status": "started",
+                "mode": mode,
+                "service": "MockStudio",
+            }))
+        }
+        "studio-playtest_stop" => {
+            let stopped = stop_playtest(state, mock).await;
+            Ok(json!({ "ok": true, "stoppedSessionId": stopped }))
+        }
+        "studio-logs_subscribe" => {
+            let history = state.get_logs(0, 200);
+            Ok(json!({ "ok": true, "history": history }))
+        }
+        "studio-logs_unsubscribe" => Ok(json!({ "ok": true })),
+        "studio-logs_get" => {
+            let since = args["sinceSeq"].as_u64().unwrap_or(0);
+            let limit = args["limit"].as_u64().unwrap_or(200) as usize;
+            let entries = state.get_logs(since, limit);
+            let next_seq = entries.last().map(|e| e.seq).unwrap_or(since);
+            Ok(json!({ "entries": entries, "nextSeq": next_seq, "subscribed": true }))
+        }
+        "studio-virtualuser_key" => {
+            require_play_mode(mock).await?;
+            let key = arg_str("keyCode").ok_or("Missing required argument: keyCode")?;
+            let action = arg_str("action").unwrap_or_else(|| "down".into());
+            if key == "Space" {
+                return Ok(json!({ "key": "Space", "action": "jump", "state": "Jumping" }));
+            }
+            let mut m = mock.lock().await;
+            if action == "up" {
+                m.held_keys.remove(&key);
+            } else {
+                m.held_keys.insert(key.clone());
+            }
+            let mut held: Vec<&String> = m.held_keys.iter().collect();
+            held.sort();
+            Ok(json!({ "key": key, "action": action, "heldKeys": held }))
+        }
+        "studio-virtualuser_mouse_button" => {
+            require_play_mode(mock).await?;
+            Ok(json!({
+                "button": args["button"].as_u64().unwrap_or(1),
+                "action": "click",
+                "miss": true,
+                "note": "Mock Studio has no world to raycast into",
+            }))
+        }
+        "studio-virtualuser_move_mouse" => {
+            require_play_mode(mock).await?;
+            Ok(json!({
+                "lookVector": { "x": 0.0, "y": 0.0, "z": -1.0 },
+                "position": { "x": 0.0, "y": 3.0, "z": 0.0 },
+            }))
+        }
+        "studio-virtualuser_sequence" => {
+            require_play_mode(mock).await?;
+            let mut steps: Vec<Value> = args["steps"].as_array().cloned().unwrap_or_default();
+            if steps.is_empty() {
+                return Err("Missing required argument: steps (non-empty array)".into());
+            }
+            steps.sort_by_key(|s| s["atMs"].as_u64().unwrap_or(0));
+            let started = tokio::time::Instant::now();
+            let started_clock = mock_clock();
+            let mut timings = Vec::new();
+            for step in &steps {
+                let at_ms = step["atMs"].as_u64().unwrap_or(0);
+                let due_clock = step["dueClock"].as_f64();
+                let due_secs = due_clock.unwrap_or(started_clock + at_ms as f64 / 1000.0);
+                tokio::time::sleep(Duration::from_secs_f64((due_secs - mock_clock()).max(0.0))).await;
+                if let Some(due_clock) = due_clock {
+                    timings.push(json!({ "atMs": at_ms, "dueClock": due_clock, "firedClock": mock_clock() }));
+                }
+                if let Some(key) = step["keyCode"].as_str() {
+                    let mut m = mock.lock().await;
+                    if step["action"].as_str() == Some("up") {
+                        m.held_keys.remove(key);
+                    } else if key != "Space" {
+                        m.held_keys.insert(key.to_string());
+                    }
+                }
+            }
+            let mut released: Vec<String> = mock.lock().await.held_keys.drain().collect();
+            released.sort();
+            Ok(json!({
+                "steps": steps.len(),
+                "elapsedMs": state.clock().elapsed(started.into_std()).as_millis() as u64,
+                "maxLateMs": 0,
+                "releasedKeys": released,
+                "timings": (!timings.is_empty()).then_some(timings),
+            }))
+        }
+        "studio-virtualuser_record_start" => {
+            require_play_mode(mock).await?;
+            let name = arg_str("name").ok_or("Missing required argument: name")?;
+            let mut m = mock.lock().await;
+            if let Some((open, _)) = &m.recording {
+                return Err(format!("Already recording '{open}'"));
+            }
+            m.recording = Some((name.clone(), std::time::Instant::now()));
+            Ok(json!({ "recording": name, "maxSeconds": args["maxSeconds"].as_f64().unwrap_or(60.0) }))
+        }
+        "studio-virtualuser_record_stop" => {
+            let (name, started) = mock
+                .lock()
+                .await
+                .recording
+                .take()
+                .ok_or("No input recording in progress")?;
+            let duration_ms = state.clock().elapsed(started).as_millis() as u64;
+            // Nobody is playing, so stream a fixed walk instead. The later batch is sent
+            // first and repeats a transition, like a retried push would.
+            push_event(
+                state,
+                "studio-input_sample",
+                json!({ "recording": name, "samples": [
+                    { "kind": "key", "tMs": 600, "keyCode": "D", "action": "down" },
+                    { "kind": "key", "tMs": 900, "keyCode": "D", "action": "up" },
+                    { "kind": "look", "tMs": 920, "direction": { "x": 1.0, "y": 0.0, "z": 0.0 } },
+                ]}),
+            )
+            .await;
+            push_event(
+                state,
+                "studio-input_sample",
+                json!({ "recording": name, "samples": [
+                    { "kind": "key", "tMs": 0, "keyCode": "W", "action": "down" },
+                    { "kind": "look", "tMs": 0, "direction": { "x": 0.0, "y": 0.0, "z": -1.0 } },
+                    { "kind": "key", "tMs": 400, "keyCode": "W", "action": "down" },
+                ]}),
+            )
+            .await;
+            Ok(json!({ "recording": name, "durationMs": duration_ms.max(1000) }))
+        }
+        "studio-npc_driver_start" => {
+            require_playtest(mock).await?;
+            let target = arg_str("target")
+                .ok_or("Missing required argument: target (instance path like 'Workspace.NPCModel')")?;
+            let mut m = mock.lock().await;
+            m.next_driver += 1;
+            let driver_id = format!("drv_{}", m.next_driver);
+            m.drivers.insert(driver_id.clone(), target.clone());
+            Ok(json!({
+                "driverId": driver_id,
+                "target": target,
+                "className": "Model",
+                "walkSpeed": 16,
+                "health": 100,
+                "maxHealth": 100,
+            }))
+        }
+        "studio-npc_driver_command" => {
+            let driver_id = arg_str("driverId").ok_or("Missing required argument: driverId")?;
+            if !mock.lock().await.drivers.contains_key(&driver_id) {
+                return Err(format!("Unknown driverId: {driver_id}"));
+            }
+            let command = &args["command"];
+            match command["type"].as_str() {
+                Some("move_to") => {
+                    tokio::time::sleep(MOCK_MOVE_DURATION).await;
+                    Ok(json!({
+                        "type": "move_to",
+                        "reached": true,
+                        "timedOut": false,
+                        "elapsed": MOCK_MOVE_DURATION.as_secs_f64(),
+                        "position": command["position"],
+                    }))
+                }
+                Some("wait") => {
+                    let ms = command["ms"].as_u64().unwrap_or(1000);
+                    tokio::time::sleep(Duration::from_millis(ms)).await;
+                    Ok(json!({ "type": "wait", "waited": ms as f64 / 1000.0 }))
+                }
+                Some(other @ ("jump" | "set_walkspeed" | "look_at")) => Ok(json!({ "type": other })),
+                other => Err(format!(
+                    "Unknown command type: {}. Supported: move_to, jump, wait, set_walkspeed, look_at",
+                    other.unwrap_or("nil")
+                )),
+            }
+        }
+        "studio-npc_driver_stop" => {
+            let driver_id = arg_str("driverId").ok_or("Missing required argument: driverId")?;
+            if mock.lock().await.drivers.remove(&driver_id).is_none() {
+                return Err(format!("Unknown driverId: {driver_id}"));
+            }
+            Ok(json!({ "driverId": driver_id, "stopped": true }))
+        }
+        other => Err(format!("Unknown tool: {other}")),
+    }
+}
+
+async fn require_playtest(mock: &Mutex<MockStudio>) -> Result<(), String> {
+    if mock.lock().await.playtest.is_none() {
+        return Err("This tool only works during a Play mode playtest (F5). Start one with studio-playtest_play first, then retry.".into());
+    }
+    Ok(())
+}
+
+/// Prompts the mock's workspace has: path, action text, hold duration, enabled,
+/// distance from the character, max activation distance.
+const MOCK_PROMPTS: [(&str, &str, f64, bool, f64, f64); 3] = [
+    ("Workspace.Shop.Counter.ProximityPrompt", "Buy", 0.5, true, 8.4, 10.0),
+    ("Workspace.Door.Handle.ProximityPrompt", "Open", 0.0, true, 14.2, 10.0),
+    ("Workspace.Vault.Lock.ProximityPrompt", "Unlock", 2.0, false, 42.0, 6.0),
+];
+
+/// Sounds playing in the mock's game: path, side, looped, time position. Client sounds
+/// need a player, so only Play mode has them.
+const MOCK_SOUNDS: [(&str, &str, bool, f64); 2] = [
+    ("Players.MockPlayer.PlayerGui.MainMenu.ClickSound", "client", false, 0.12),
+    ("Workspace.Music.BackgroundLoop", "server", true, 12.5),
+];
+
+/// Parts in the mock's game: path, anchored.
+const MOCK_PARTS: [(&str, bool); 2] = [("Workspace.Baseplate", true), ("Workspace.Ball", false)];
+
+/// Network ownership of a mock part, shaped like the bridge's. Unanchored parts are
+/// owned by the server unless studio-set_network_ownership said otherwise.
+fn mock_ownership(m: &MockStudio, path: &str) -> Result<Value, String> {
+    let Some((_, anchored)) = MOCK_PARTS.iter().find(|(p, _)| *p == path) else {
+        return match path {
+            "Workspace.Shop" => Err(format!("{path} is a Model, not a BasePart. Network ownership belongs to parts (their assembly).")),
+            _ => Err(format!("Instance not found: {path}")),
+        };
+    };
+    let set = m.network_owners.get(path).cloned();
+    let mut result = json!({
+        "path": path,
+        "className": "Part",
+        "owner": set.clone().flatten().unwrap_or_else(|| "server".into()),
+        "auto": !matches!(set, Some(Some(_))),
+        "anchored": anchored,
+        "assemblyRoot": path,
+        "canSetOwner": !anchored,
+    });
+    if *anchored {
+        result["cannotSetReason"] = "Cannot change NetworkOwnership of an anchored part.".into();
+    }
+    Ok(result)
+}
+
+/// A pivot at `position` with no rotation, shaped like the plugin's.
+fn mock_pivot(position: [f64; 3]) -> Value {
+    let [x, y, z] = position;
+    json!({
+        "position": { "x": x, "y": y, "z": z },
+        "orientation": { "x": 0, "y": 0, "z": 0 },
+        "components": [x, y, z, 1, 0, 0, 0, 1, 0, 0, 0, 1],
+    })
+}
+
+/// The mock player's GUI, relative to PlayerGui.
+const MOCK_GUI: [(&str, &str); 3] = [
+    ("MainMenu.PlayButton", "TextButton"),
+    ("MainMenu.SettingsButton", "ImageButton"),
+    ("MainMenu.Title", "TextLabel"),
+];
+
+/// Apply get_gui_tree's depth and node limits to a mock tree, the way the input probe
+/// does: cut-off branches get `omittedChildren`, the node limit sets `truncated`.
+fn prune_gui_tree(node: &mut Value, depth: u64, max_depth: u64, budget: &mut usize, truncated: &mut bool) {
+    *budget -= 1;
+    let Some(children) = node.get_mut("children").and_then(Value::as_array_mut) else {
+        return;
+    };
+    if depth >= max_depth {
+        let omitted = children.len();
+        node["omittedChildren"] = omitted.into();
+        if let Some(obj) = node.as_object_mut() {
+            obj.remove("children");
+        }
+        return;
+    }
+    let mut kept = Vec::new();
+    for mut child in std::mem::take(children) {
+        if *budget == 0 {
+            *truncated = true;
+            break;
+        }
+        prune_gui_tree(&mut child, depth + 1, max_depth, budget, truncated);
+        kept.push(child);
+    }
+    *children = kept;
+}
+
+async fn require_play_mode(mock: &Mutex<MockStudio>) -> Result<(), String> {
+    match &mock.lock().await.playtest {
+        Some((_, mode)) if mode == "play" => Ok(()),
+        _ => Err("No player character found. Requires Play mode playtest (F5) with a spawned character.".into()),
+    }
+}
+
+async fn start_playtest(
+    state: &SharedState,
+    mock: &Mutex<MockStudio>,
+    mode: &str,
+) -> Result<String, String> {
+    let session_id = {
+        let mut m = mock.lock().await;
+        if let Some((_, running)) = &m.playtest {
+            return Err(format!(
+                "A playtest session is already running (mode: {running}). Stop it first."
+            ));
+        }
+        m.next_session += 1;
+        let session_id = format!("session_{}", m.next_session);
+        m.playtest = Some((session_id.clone(), mode.to_string()));
+        session_id
+    };
+    push_event(
+        state,
+        "studio-playtest_state",
+        json!({ "active": true, "sessionId": session_id, "mode": mode }),
+    )
+    .await;
+    push_log(state, mock, "info", &format!("[mock] {mode} mode started ({session_id})")).await;
+    push_event(state, "studio-playtest_ready", json!({ "stage": "serverStarted" })).await;
+    if mode == "play" {
+        // The player joins and spawns a little after the call returns, as in Studio
+        let state = state.clone();
+        let session = session_id.clone();
+        tokio::spawn(async move {
+            for (delay, stage) in MOCK_SPAWN_STAGES {
+                tokio::time::sleep(delay).await;
+                if state.playtest_info().await.1.as_deref() != Some(session.as_str()) {
+                    return;
+                }
+                push_event(&state, "studio-playtest_ready", json!({ "stage": stage, "player": "MockPlayer" })).await;
+            }
+        });
+    }
+    Ok(session_id)
+}
+
+async fn stop_playtest(state: &SharedState, mock: &Mutex<MockStudio>) -> Option<String> {
+    let stopped = {
+        let mut m = mock.lock().await;
+        m.held_keys.clear();
+        m.drivers.clear();
+        m.recording = None;
+        m.network_owners.clear();
+        m.playtest.take().map(|(session_id, _)| session_id)
+    };
+    push_event(
+        state,
+        "studio-playtest_state",
+        json!({ "active": false, "sessionId": stopped }),
+    )
+    .await;
+    if let Some(session_id) = &stopped {
+        push_log(state, mock, "info", &format!("[mock] playtest stopped ({session_id})")).await;
+    }
+    stopped
+}
+
+async fn push_log(state: &SharedState, mock: &Mutex<MockStudio>, level: &str, message: &str) {
+    let (seq, session_id) = {
+        let mut m = mock.lock().await;
+        m.log_seq += 1;
+        (m.log_seq, m.playtest.as_ref().map(|(id, _)| id.clone()))
+    };
+    push_event(
+        state,
+        "studio-log",
+        json!({ "seq": seq, "level": level, "message": message, "sessionId": session_id }),
+    )
+    .await;
+}
+
+async fn push_event(state: &SharedState, event_type: &str, data: Value) {
+    let event = BridgeEvent {
+        event_type: event_type.to_string(),
+        data,
+        seq: None,
+    };
+    // The mock never sends tree deltas, so its events don't need the real client id
+    handle_event(state, "mock-studio", &event).await;
+}
+
+/// A fake plugin for unit tests whose answers the test scripts, where the full mock
+/// would get in the way: failing a given tool, holding a call back, or checking
+/// exactly which calls the server made.
+#[cfg(test)]
+pub(crate) mod scripted {
+    use super::*;
+
+    /// Calls the scripted plugin got, in the order it pulled them.
+    #[derive(Clone, Default)]
+    pub(crate) struct Calls(Arc<std::sync::Mutex<Vec<(String, Value)>>>);
+
+    impl Calls {
+        /// Every call so far, as (tool, arguments).
+        pub(crate) fn all(&self) -> Vec<(String, Value)> {
+            self.0.lock().unwrap().clone()
+        }
+
+        /// The tools called so far.
+        pub(crate) fn tools(&self) -> Vec<String> {
+            self.0.lock().unwrap().iter().map(|(tool, _)| tool.clone()).collect()
+        }
+    }
+
+    /// Register plugin "edit" and answer what the server sends it: `answer` gives a
+    /// delay and the outcome, or `None` to leave the call unanswered.
+    pub(crate) async fn start(
+        state: &SharedState,
+        answer: impl Fn(&BridgeToolRequest) -> Option<(Duration, Result<Value, String>)> + Send + 'static,
+    ) -> Calls {
+        state.register_client("edit".into(), "0.4.0".into(), None, Casing::Snake).await;
+        let calls = Calls::default();
+        let notify = state.get_notify("edit").await.unwrap();
+        tokio::spawn({
+            let (state, calls) = (state.clone(), calls.clone());
+            async move {
+                loop {
+                    let pulled = notify.notified();
+                    let requests = state.drain_outbound("edit").await;
+                    if requests.is_empty() {
+                        pulled.await;
+                        continue;
+                    }
+                    for request in requests {
+                        calls.0.lock().unwrap().push((request.tool_name.clone(), request.arguments.clone()));
+                        let Some((delay, outcome)) = answer(&request) else { continue };
+                        let (success, result, error) = match outcome {
+                            Ok(result) => (true, Some(result), None),
+                            Err(error) => (false, None, Some(error)),
+                        };
+                        let response = BridgeToolResponse { request_id: request.request_id.clone(), success, result, error };
+                        if delay.is_zero() {
+                            state.resolve_pending(&request.request_id, response).await;
+                        } else {
+                            let state = state.clone();
+                            tokio::spawn(async move {
+                                tokio::time::sleep(delay).await;
+                                state.resolve_pending(&request.request_id, response).await;
+                            });
+                        }
+                    }
+                }
+            }
+        });
+        calls
+    }
+
+    /// Like `start`, answering every call right away with `answer(tool, arguments)`.
+    pub(crate) async fn answering(
+        state: &SharedState,
+        answer: impl Fn(&str, &Value) -> Result<Value, String> + Send + 'static,
+    ) -> Calls {
+        start(state, move |request| Some((Duration::ZERO, answer(&request.tool_name, &request.arguments)))).await
+    }
+}
@@ -0,0 +1,273 @@
+//! Structural and property comparison of two subtree snapshots for `studio-diff_places`.
+//!
+//! A snapshot is taken by the plugin (internal `studio-snapshot_subtree`,
+//! `tools/model_snapshot.lua`): every instance under a root, keyed by its path below
+//! the root (`""` for the root itself, `#2` for a repeated sibling name), with its
+//! class and a per-class set of properties, attributes (`@name`) and tags. Snapshots
+//! can be saved under `snapshots/` in the capture folder, so a later session can
+//! compare the live tree with one taken earlier.
+//!
+//! Instances are matched by path. A subtree that only exists on one side is listed
+//! once, at its top, with the number of descendants it holds.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+
+pub const SNAPSHOT_FORMAT: &str = "yippieblox-model-snapshot";
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot files live here, relative to the capture folder.
+pub const SNAPSHOT_DIR: &str = "snapshots";
+pub const MAX_SNAPSHOT_FILE_BYTES: u64 = 16 * 1024 * 1024;
+
+pub const DEFAULT_MAX_INSTANCES: usize = 2_000;
+pub const MAX_INSTANCES: usize = 5_000;
+pub const DEFAULT_MAX_CHANGES: usize = 200;
+pub const MAX_MAX_CHANGES: usize = 1_000;
+
+/// Numbers closer than this are equal, so float noise in positions and colors
+/// doesn't show up as a change.
+const NUMBER_TOLERANCE: f64 = 1e-4;
+
+/// Shown instead of the empty path of the root itself.
+const ROOT_LABEL: &str = "(root)";
+
+/// Where one side of a comparison comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotSource {
+    /// The live subtree at this instance path.
+    Live(String),
+    /// A snapshot file, relative to the capture folder.
+    File(String),
+}
+
+impl SnapshotSource {
+    /// `{ "instance": path }` or `{ "snapshot": file }`.
+    pub fn parse(value: &Value, key: &str) -> Result<Self, String> {
+        let text = |field: &str| value.get(field).and_then(|v| v.as_str()).filter(|s| !s.is_empty());
+        match (text("instance"), text("snapshot")) {
+            (Some(path), None) => Ok(Self::Live(path.to_string())),
+            (None, Some(file)) => Ok(Self::File(file.to_string())),
+            _ => Err(format!(
+                "'{key}' must be an object with either 'instance' (a live path, e.g. 'Workspace.Map') or 'snapshot' (a snapshot file)"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Snapshot {
+    pub format: String,
+    pub version: u32,
+    /// Full name of the root instance when the snapshot was taken.
+    pub root: String,
+    pub taken_at: String,
+    /// Set when the walk stopped at maxInstances.
+    pub truncated: bool,
+    pub instances: BTreeMap<String, InstanceRecord>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceRecord {
+    pub class_name: String,
+    #[serde(default, deserialize_with = "object_or_empty_array")]
+    pub properties: BTreeMap<String, Value>,
+}
+
+/// Luau encodes an empty table as `[]`.
+fn object_or_empty_array<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, Value>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Object(map) => Ok(map.into_iter().collect()),
+        Value::Array(items) if items.is_empty() => Ok(BTreeMap::new()),
+        Value::Null => Ok(BTreeMap::new()),
+        other => Err(serde::de::Error::custom(format!("expected an object of properties, got {other}"))),
+    }
+}
+
+impl Snapshot {
+    /// A snapshot from the plugin's `studio-snapshot_subtree` result.
+    pub fn from_plugin(result: Value, taken_at: String) -> Result<Self, String> {
+        #[derive(Deserialize)]
+        struct PluginSnapshot {
+            root: String,
+            #[serde(default)]
+            truncated: bool,
+            instances: BTreeMap<String, InstanceRecord>,
+        }
+        let taken: PluginSnapshot =
+            serde_json::from_value(result).map_err(|e| format!("Unexpected snapshot from the plugin: {e}"))?;
+        Ok(Self {
+            format: SNAPSHOT_FORMAT.into(),
+            version: SNAPSHOT_VERSION,
+            root: taken.root,
+            taken_at,
+            truncated: taken.truncated,
+            instances: taken.instances,
+        })
+    }
+
+    /// A snapshot read back from a file.
+    pub fn from_file(value: Value, file: &str) -> Result<Self, String> {
+        let snapshot: Self =
+            serde_json::from_value(value).map_err(|e| format!("'{file}' is not a model snapshot: {e}"))?;
+        if snapshot.format != SNAPSHOT_FORMAT {
+            return Err(format!("'{file}' is not a model snapshot (format '{}')", snapshot.format));
+        }
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(format!(
+                "'{file}' has snapshot version {}; this server reads up to {SNAPSHOT_VERSION}",
+                snapshot.version
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceRef {
+    pub path: String,
+    pub class_name: String,
+    /// Instances below this one, which are added or removed along with it.
+    #[serde(skip_serializing_if = "is_zero")]
+    pub descendants: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
+}
+
+#[derive(Debug, Serialize)]
+pub struct PropertyChange {
+    pub before: Value,
+    pub after: Value,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InstanceChange {
+    pub path: String,
+    pub class_name: String,
+    /// Set when the instance at this path has another class now; properties aren't
+    /// compared then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class_before: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, PropertyChange>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelDiff {
+    pub identical: bool,
+    pub added_total: usize,
+    pub removed_total: usize,
+    pub changed_total: usize,
+    pub added: Vec<InstanceRef>,
+    pub removed: Vec<InstanceRef>,
+    pub changed: Vec<InstanceChange>,
+    /// Set when a list was cut at maxChanges; the totals still count everything.
+    pub truncated: bool,
+}
+
+/// Compare `compare` against `base`. Each list holds at most `max_changes` entries.
+pub fn diff(base: &Snapshot, compare: &Snapshot, max_changes: usize) -> ModelDiff {
+    let removed = one_sided(&base.instances, &compare.instances);
+    let added = one_sided(&compare.instances, &base.instances);
+
+    let mut changed = Vec::new();
+    for (key, before) in &base.instances {
+        let Some(after) = compare.instances.get(key) else {
+            continue;
+        };
+        if before.class_name != after.class_name {
+            changed.push(InstanceChange {
+                path: label(key),
+                class_name: after.class_name.clone(),
+                class_before: Some(before.class_name.clone()),
+                properties: BTreeMap::new(),
+            });
+            continue;
+        }
+        let names: std::collections::BTreeSet<&String> =
+            before.properties.keys().chain(after.properties.keys()).collect();
+        let mut properties = BTreeMap::new();
+        for name in names {
+            let was = before.properties.get(name).unwrap_or(&Value::Null);
+            let is = after.properties.get(name).unwrap_or(&Value::Null);
+            if !values_equal(was, is) {
+                properties.insert(name.clone(), PropertyChange { before: was.clone(), after: is.clone() });
+            }
+        }
+        if !properties.is_empty() {
+            changed.push(InstanceChange { path: label(key), class_name: after.class_name.clone(), class_before: None, properties });
+        }
+    }
+
+    let (added_total, removed_total, changed_total) = (added.len(), removed.len(), changed.len());
+    let truncated = added_total > max_changes || removed_total > max_changes || changed_total > max_changes;
+    let cut = |mut list: Vec<InstanceRef>| {
+        list.truncate(max_changes);
+        list
+    };
+    changed.truncate(max_changes);
+    ModelDiff {
+        identical: added_total == 0 && removed_total == 0 && changed_total == 0,
+        added_total,
+        removed_total,
+        changed_total,
+        added: cut(added),
+        removed: cut(removed),
+        changed,
+        truncated,
+    }
+}
+
+/// Instances in `side` without a counterpart in `other`, each listed at the top of
+/// its missing subtree with the count of missing descendants below it.
+fn one_sided(side: &BTreeMap<String, InstanceRecord>, other: &BTreeMap<String, InstanceRecord>) -> Vec<InstanceRef> {
+    let missing: HashSet<&str> = side.keys().filter(|k| !other.contains_key(*k)).map(String::as_str).collect();
+    let mut tops: BTreeMap<&str, InstanceRef> = BTreeMap::new();
+    for (key, record) in side.iter().filter(|(k, _)| missing.contains(k.as_str())) {
+        match missing_ancestor(key, &missing) {
+            Some(top) => {
+                if let Some(entry) = tops.get_mut(top) {
+                    entry.descendants += 1;
+                }
+            }
+            None => {
+                tops.insert(key, InstanceRef { path: label(key), class_name: record.class_name.clone(), descendants: 0 });
+            }
+        }
+    }
+    tops.into_values().collect()
+}
+
+/// The outermost ancestor of `key` that is also missing.
+fn missing_ancestor<'a>(key: &str, missing: &HashSet<&'a str>) -> Option<&'a str> {
+    key.match_indices('.').find_map(|(i, _)| missing.get(&key[..i]).copied())
+}
+
+fn label(key: &str) -> String {
+    if key.is_empty() {
+        ROOT_LABEL.into()
+    } else {
+        key.to_string()
+    }
+}
+
+/// Equal JSON values, with numbers (also inside arrays) compared within
+/// `NUMBER_TOLERANCE`.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => match (x.as_f64(), y.as_f64()) {
+            (Some(x), Some(y)) => (x - y).abs() <= NUMBER_TOLERANCE,
+            _ => x == y,
+        },
+        (Value::Array(xs), Value::Array(ys)) => xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| values_equal(x, y)),
+        _ => a == b,
+    }
+}
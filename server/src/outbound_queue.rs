@@ -0,0 +1,293 @@
+//! Per-client queue of tool requests waiting for the plugin's next /pull, in two
+//! priority lanes.
+//!
+//! Interactive calls (an MCP session that didn't ask for background priority) go
+//! ahead of background ones: sessions that declared `background` in `initialize`, and
+//! the server's own automation (path index snapshots, idle cleanup). Each pull hands
+//! over a bounded batch with only a few background requests in it, so a long
+//! background run reaches the plugin a little at a time and interactive calls queued
+//! meanwhile overtake the rest. Background requests still progress: after
+//! `STARVATION_LIMIT` interactive requests in a row while one waits, the oldest
+//! background request goes next.
+//!
+//! How many background requests a pull may carry follows the HttpService budget
+//! (`traffic::BackgroundLevel`); while it is paused they stay queued.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::traffic::BackgroundLevel;
+use crate::types::BridgeToolRequest;
+
+/// Most requests handed to the plugin per pull.
+const MAX_DRAIN_BATCH: usize = 8;
+
+/// Interactive requests taken in a row while a background one waits, before the
+/// background one goes next.
+const STARVATION_LIMIT: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    #[default]
+    Interactive,
+    Background,
+}
+
+impl Priority {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "interactive" | "high" => Some(Self::Interactive),
+            "background" | "low" => Some(Self::Background),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct OutboundQueue {
+    interactive: VecDeque<BridgeToolRequest>,
+    background: VecDeque<BridgeToolRequest>,
+    /// Interactive requests taken in a row while background ones waited.
+    streak: u32,
+    /// Times a background request went ahead of waiting interactive ones.
+    starvation_boosts: u64,
+}
+
+impl OutboundQueue {
+    pub fn push(&mut self, request: BridgeToolRequest, priority: Priority) {
+        match priority {
+            Priority::Interactive => self.interactive.push_back(request),
+            Priority::Background => self.background.push_back(request),
+        }
+    }
+
+    /// The next batch for the plugin, in the order it should start them, with at most
+    /// as many background requests as `level` allows.
+    pub fn drain_batch(&mut self, level: BackgroundLevel) -> Vec<BridgeToolRequest> {
+        let mut batch = Vec::new();
+        let mut background_taken = 0;
+        while batch.len() < MAX_DRAIN_BATCH {
+            let background_ready = !self.background.is_empty() && background_taken < level.background_batch();
+            let take_background = match (self.interactive.is_empty(), background_ready) {
+                (true, true) => true,
+                (false, true) if self.streak >= STARVATION_LIMIT => {
+                    self.starvation_boosts += 1;
+                    true
+                }
+                (false, _) => false,
+                (true, false) => break,
+            };
+            if take_background {
+                batch.extend(self.background.pop_front());
+                background_taken += 1;
+                self.streak = 0;
+            } else {
+                batch.extend(self.interactive.pop_front());
+                if !self.background.is_empty() {
+                    self.streak += 1;
+                }
+            }
+        }
+        if self.background.is_empty() {
+            self.streak = 0;
+        }
+        batch
+    }
+
+    /// Drop a queued request. Returns false if it isn't queued (any more).
+    pub fn remove(&mut self, request_id: &str) -> bool {
+        for lane in [&mut self.interactive, &mut self.background] {
+            if let Some(index) = lane.iter().position(|r| r.request_id == request_id) {
+                lane.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Queued requests per priority.
+    pub fn depths(&self) -> QueueDepths {
+        QueueDepths {
+            interactive: self.interactive.len(),
+            background: self.background.len(),
+            starvation_boosts: self.starvation_boosts,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueueDepths {
+    pub interactive: usize,
+    pub background: usize,
+    pub starvation_boosts: u64,
+}
+
+impl std::ops::AddAssign for QueueDepths {
+    fn add_assign(&mut self, other: Self) {
+        self.interactive += other.interactive;
+        self.background += other.background;
+        self.starvation_boosts += other.starvation_boosts;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(id: &str) -> BridgeToolRequest {
+        BridgeToolRequest { request_id: id.into(), tool_name: "studio-status".into(), arguments: json!({}) }
+    }
+
+    fn queue(interactive: usize, background: usize) -> OutboundQueue {
+        let mut queue = OutboundQueue::default();
+        for i in 1..=background {
+            queue.push(request(&format!("b{i}")), Priority::Background);
+        }
+        for i in 1..=interactive {
+            queue.push(request(&format!("i{i}")), Priority::Interactive);
+        }
+        queue
+    }
+
+    fn ids(batch: Vec<BridgeToolRequest>) -> Vec<String> {
+        batch.into_iter().map(|r| r.request_id).collect()
+    }
+
+    #[test]
+    fn interactive_requests_overtake_queued_background_ones() {
+        let mut queue = queue(3, 6);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Full)), ["i1", "i2", "i3", "b1", "b2"]);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Full)), ["b3", "b4"]);
+        queue.push(request("i4"), Priority::Interactive);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Full)), ["i4", "b5", "b6"]);
+        assert_eq!(queue.depths().starvation_boosts, 0);
+    }
+
+    #[test]
+    fn a_waiting_background_request_goes_after_a_streak_of_interactive_ones() {
+        let mut queue = queue(10, 1);
+        assert_eq!(
+            ids(queue.drain_batch(BackgroundLevel::Full)),
+            ["i1", "i2", "i3", "i4", "b1", "i5", "i6", "i7"]
+        );
+        assert_eq!(queue.depths().starvation_boosts, 1);
+    }
+
+    #[test]
+    fn background_requests_progress_under_constant_interactive_load() {
+        let mut queue = queue(0, 3);
+        let mut delivered = Vec::new();
+        for pull in 0..4 {
+            for i in 0..MAX_DRAIN_BATCH {
+                queue.push(request(&format!("p{pull}-{i}")), Priority::Interactive);
+            }
+            delivered.extend(ids(queue.drain_batch(BackgroundLevel::Minimal)));
+        }
+        let background: Vec<&String> = delivered.iter().filter(|id| id.starts_with('b')).collect();
+        assert_eq!(background, ["b1", "b2", "b3"]);
+        assert_eq!(queue.depths().starvation_boosts, 3);
+    }
+
+    #[test]
+    fn the_background_level_caps_background_requests_per_pull() {
+        let mut queue = queue(0, 5);
+        assert!(queue.drain_batch(BackgroundLevel::Paused).is_empty());
+        assert_eq!(queue.depths().background, 5);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Minimal)), ["b1"]);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Reduced)), ["b2", "b3"]);
+        queue.push(request("i1"), Priority::Interactive);
+        // Interactive requests don't wait for the budget
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Paused)), ["i1"]);
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Full)), ["b4", "b5"]);
+    }
+
+    #[test]
+    fn queued_requests_can_be_withdrawn_from_either_lane() {
+        let mut queue = queue(2, 2);
+        assert!(queue.remove("i2"));
+        assert!(queue.remove("b1"));
+        assert!(!queue.remove("b1"));
+        let depths = queue.depths();
+        assert_eq!((depths.interactive, depths.background), (1, 1));
+        assert_eq!(ids(queue.drain_batch(BackgroundLevel::Full)), ["i1", "b2"]);
+    }
+
+    #[test]
+    fn randomized_arrivals_keep_lane_order_priority_and_progress() {
+        use crate::providers::{Rng, SeededRng};
+        const LEVELS: [BackgroundLevel; 4] =
+            [BackgroundLevel::Paused, BackgroundLevel::Minimal, BackgroundLevel::Reduced, BackgroundLevel::Full];
+
+        for seed in 0..64 {
+            let rng = SeededRng::new(seed);
+            let mut queue = OutboundQueue::default();
+            let (mut pushed_interactive, mut pushed_background) = (0u64, 0u64);
+            let (mut next_interactive, mut next_background) = (1u64, 1u64);
+            // Pulls that could have carried the oldest background request but didn't
+            let mut head_waited = 0u32;
+            for step in 0..400 {
+                // Mostly interactive bursts, so the interactive lane rarely runs dry
+                if !rng.next_u64().is_multiple_of(3) {
+                    for _ in 0..rng.next_u64() % 6 {
+                        if rng.next_u64().is_multiple_of(4) {
+                            pushed_background += 1;
+                            queue.push(request(&format!("b{pushed_background}")), Priority::Background);
+                        } else {
+                            pushed_interactive += 1;
+                            queue.push(request(&format!("i{pushed_interactive}")), Priority::Interactive);
+                        }
+                    }
+                    continue;
+                }
+
+                let level = LEVELS[(rng.next_u64() % 4) as usize];
+                let before = queue.depths();
+                let batch = ids(queue.drain_batch(level));
+                let after = queue.depths();
+                let context = format!("seed {seed}, step {step}, {level:?}: {batch:?}");
+                assert!(batch.len() <= MAX_DRAIN_BATCH, "{context}");
+
+                let (mut background_taken, mut boosted) = (0, 0);
+                for (index, id) in batch.iter().enumerate() {
+                    let n: u64 = id[1..].parse().unwrap();
+                    if id.starts_with('i') {
+                        assert_eq!(n, next_interactive, "interactive lane out of order, {context}");
+                        next_interactive += 1;
+                    } else {
+                        assert_eq!(n, next_background, "background lane out of order, {context}");
+                        next_background += 1;
+                        background_taken += 1;
+                        // Taken while interactive requests were still waiting
+                        if after.interactive > 0 || batch[index + 1..].iter().any(|id| id.starts_with('i')) {
+                            boosted += 1;
+                        }
+                    }
+                }
+                assert!(background_taken <= level.background_batch(), "{context}");
+                // Interactive requests only stay queued behind a full batch
+                if after.interactive > 0 {
+                    assert_eq!(batch.len(), MAX_DRAIN_BATCH, "{context}");
+                }
+                // Every background request ahead of interactive ones was a starvation boost
+                assert_eq!(after.starvation_boosts - before.starvation_boosts, boosted, "{context}");
+
+                if before.background > 0 && level != BackgroundLevel::Paused {
+                    head_waited = if background_taken > 0 { 0 } else { head_waited + 1 };
+                    assert!(head_waited <= STARVATION_LIMIT, "background request starved, {context}");
+                }
+            }
+
+            while queue.depths().interactive + queue.depths().background > 0 {
+                for id in ids(queue.drain_batch(BackgroundLevel::Full)) {
+                    let next = if id.starts_with('i') { &mut next_interactive } else { &mut next_background };
+                    assert_eq!(id[1..].parse::<u64>().unwrap(), *next, "seed {seed}");
+                    *next += 1;
+                }
+            }
+            assert_eq!((next_interactive - 1, next_background - 1), (pushed_interactive, pushed_background), "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,242 @@
+//! Per-call `outputDir`: where a tool writes its files instead of the capture folder.
+//!
+//! Only directories inside one of the MCP client's roots are allowed. The check runs on
+//! canonical paths, so `..` and symlinks can't leave a root, and again after the
+//! directory is created in case a component was swapped for a symlink meanwhile. The
+//! index entry for the file stays in the capture folder's `index.json`.
+
+use std::path::{Component, Path, PathBuf};
+
+/// Tools that take `outputDir`.
+pub const OUTPUT_DIR_TOOLS: &[&str] = &[
+    "studio-screenshot_region",
+    "studio-capture_viewport_via_render",
+    "studio-capture_compare",
+    "studio-test_matrix",
+    "studio-logs_diff_sessions",
+    "studio-time_report",
+];
+
+/// Error prefix for paths outside the roots, so callers can tell it from I/O errors.
+pub const PERMISSION_DENIED: &str = "PERMISSION_DENIED";
+
+/// Resolve `requested` against `roots`, create it, and return its canonical path.
+///
+/// Absolute paths must lie inside a root; relative ones are taken from the first root.
+/// `..` components are refused outright, as are Windows drive-relative paths (`C:x`).
+pub fn resolve(requested: &str, roots: &[PathBuf]) -> Result<PathBuf, String> {
+    let denied = |why: String| format!("{PERMISSION_DENIED}: outputDir '{requested}' {why}");
+
+    let path = Path::new(requested);
+    if requested.trim().is_empty() {
+        return Err("outputDir must not be empty".into());
+    }
+    if path.components().any(|c| matches!(c, Component::ParentDir)) {
+        return Err(denied("contains '..'".into()));
+    }
+    if !path.is_absolute() && path.components().any(|c| matches!(c, Component::Prefix(_) | Component::RootDir)) {
+        return Err(denied("is relative to a drive or the current drive's root; give a full path".into()));
+    }
+
+    // Roots that don't exist (or can't be read) can't contain anything
+    let roots: Vec<PathBuf> = roots.iter().filter_map(|root| root.canonicalize().ok()).collect();
+    let Some(first_root) = roots.first() else {
+        return Err(denied("can't be checked: the client reported no local roots".into()));
+    };
+    let candidate = if path.is_absolute() { path.to_path_buf() } else { first_root.join(path) };
+
+    let inside = |canonical: &Path| roots.iter().any(|root| canonical.starts_with(root));
+    let outside = || denied(format!("is outside the client's roots ({})", display_roots(&roots)));
+
+    if !inside(&canonicalize_existing_prefix(&candidate)?) {
+        return Err(outside());
+    }
+    std::fs::create_dir_all(&candidate).map_err(|e| format!("Failed to create outputDir '{requested}': {e}"))?;
+    let created = candidate
+        .canonicalize()
+        .map_err(|e| format!("Failed to resolve outputDir '{requested}': {e}"))?;
+    if !inside(&created) {
+        return Err(outside());
+    }
+    if !created.is_dir() {
+        return Err(format!("outputDir '{requested}' is not a directory"));
+    }
+    Ok(created)
+}
+
+/// Canonicalize the longest existing ancestor of `path` (resolving its symlinks) and
+/// append the components that don't exist yet.
+fn canonicalize_existing_prefix(path: &Path) -> Result<PathBuf, String> {
+    let mut existing = path;
+    let mut missing = Vec::new();
+    loop {
+        match existing.canonicalize() {
+            Ok(mut canonical) => {
+                canonical.extend(missing.iter().rev());
+                return Ok(canonical);
+            }
+            Err(_) => {
+                let (Some(parent), Some(name)) = (existing.parent(), existing.file_name()) else {
+                    return Err(format!("Failed to resolve outputDir '{}'", path.display()));
+                };
+                missing.push(name);
+                existing = parent;
+            }
+        }
+    }
+}
+
+fn display_roots(roots: &[PathBuf]) -> String {
+    roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A temp dir holding a `root` folder; returns (tempdir, canonical root).
+    fn setup() -> (tempfile::TempDir, PathBuf) {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("root");
+        std::fs::create_dir(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+        (tmp, root)
+    }
+
+    fn assert_denied(result: Result<PathBuf, String>) {
+        let err = result.expect_err("path should be refused");
+        assert!(err.starts_with(PERMISSION_DENIED), "{err}");
+    }
+
+    #[test]
+    fn relative_path_resolves_under_the_first_root() {
+        let (_tmp, root) = setup();
+        let dir = resolve("shots/today", std::slice::from_ref(&root)).unwrap();
+        assert_eq!(dir, root.join("shots").join("today"));
+        assert!(dir.is_dir());
+    }
+
+    #[test]
+    fn absolute_path_inside_any_root_is_allowed() {
+        let (tmp, root) = setup();
+        let other = tmp.path().join("other");
+        std::fs::create_dir(&other).unwrap();
+        let target = other.join("out");
+        let dir = resolve(target.to_str().unwrap(), &[root, other.clone()]).unwrap();
+        assert_eq!(dir, other.canonicalize().unwrap().join("out"));
+    }
+
+    #[test]
+    fn parent_segments_are_refused() {
+        let (_tmp, root) = setup();
+        let roots = [root.clone()];
+        assert_denied(resolve("..", &roots));
+        assert_denied(resolve("../escape", &roots));
+        assert_denied(resolve("a/../../escape", &roots));
+        // Even when it would land back inside the root
+        assert_denied(resolve("a/../b", &roots));
+        let absolute = format!("{}/a/../../escape", root.display());
+        assert_denied(resolve(&absolute, &roots));
+        assert!(!root.parent().unwrap().join("escape").exists());
+    }
+
+    #[test]
+    fn absolute_path_outside_the_roots_is_refused() {
+        let (tmp, root) = setup();
+        let outside = tmp.path().join("elsewhere");
+        assert_denied(resolve(outside.to_str().unwrap(), &[root]));
+        assert!(!outside.exists(), "refused directory must not be created");
+    }
+
+    #[test]
+    fn sibling_sharing_the_root_prefix_is_refused() {
+        let (tmp, root) = setup();
+        // `/tmp/x/root2` starts with the string `/tmp/x/root` but isn't inside it
+        let sibling = tmp.path().join("root2");
+        std::fs::create_dir(&sibling).unwrap();
+        assert_denied(resolve(sibling.join("out").to_str().unwrap(), std::slice::from_ref(&root)));
+        let prefixed = format!("{}2/out", root.display());
+        assert_denied(resolve(&prefixed, &[root]));
+        assert!(!sibling.join("out").exists());
+    }
+
+    #[test]
+    fn differently_cased_root_never_escapes() {
+        let (tmp, root) = setup();
+        let upper = tmp.path().canonicalize().unwrap().join("ROOT").join("out");
+        // Case-insensitive file systems resolve `ROOT` to the root itself; case-sensitive
+        // ones treat it as another directory. Either way nothing lands outside.
+        match resolve(upper.to_str().unwrap(), std::slice::from_ref(&root)) {
+            Ok(dir) => assert!(dir.starts_with(&root), "{}", dir.display()),
+            Err(err) => assert!(err.starts_with(PERMISSION_DENIED), "{err}"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_escaping_the_root_is_refused() {
+        let (tmp, root) = setup();
+        let outside = tmp.path().join("outside");
+        std::fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        assert_denied(resolve("link", std::slice::from_ref(&root)));
+        assert_denied(resolve("link/nested/deeper", std::slice::from_ref(&root)));
+        assert_denied(resolve(root.join("link").to_str().unwrap(), &[root]));
+        assert!(!outside.join("nested").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_staying_inside_the_root_is_allowed() {
+        let (_tmp, root) = setup();
+        std::fs::create_dir(root.join("real")).unwrap();
+        std::os::unix::fs::symlink(root.join("real"), root.join("alias")).unwrap();
+        assert_eq!(resolve("alias/out", std::slice::from_ref(&root)).unwrap(), root.join("real").join("out"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_root_is_compared_canonically() {
+        let (tmp, root) = setup();
+        let alias = tmp.path().join("alias");
+        std::os::unix::fs::symlink(&root, &alias).unwrap();
+        // The root given through a symlink still contains paths spelled the real way
+        let dir = resolve(root.join("out").to_str().unwrap(), &[alias]).unwrap();
+        assert_eq!(dir, root.join("out"));
+    }
+
+    #[test]
+    fn existing_file_is_not_a_directory() {
+        let (_tmp, root) = setup();
+        std::fs::write(root.join("file"), b"x").unwrap();
+        let err = resolve("file", &[root]).unwrap_err();
+        assert!(err.contains("outputDir 'file'"), "{err}");
+    }
+
+    #[test]
+    fn empty_path_and_missing_roots_are_refused() {
+        let (tmp, root) = setup();
+        assert_eq!(resolve("  ", &[root]).unwrap_err(), "outputDir must not be empty");
+        assert_denied(resolve("out", &[]));
+        assert_denied(resolve("out", &[tmp.path().join("does-not-exist")]));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn drive_relative_and_rooted_windows_paths_are_refused() {
+        let (_tmp, root) = setup();
+        assert_denied(resolve("C:out", std::slice::from_ref(&root)));
+        assert_denied(resolve(r"\out", std::slice::from_ref(&root)));
+        assert_denied(resolve(r"..\escape", &[root]));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths_compare_case_insensitively() {
+        let (_tmp, root) = setup();
+        let upper = root.to_str().unwrap().to_uppercase();
+        let dir = resolve(&format!(r"{upper}\out"), std::slice::from_ref(&root)).unwrap();
+        assert!(dir.starts_with(&root));
+    }
+}
@@ -0,0 +1,247 @@
+//! Best-effort result for a `studio-test_script` call the plugin didn't answer in time.
+//!
+//! The logs of the test are usually already in the server's buffer, so instead of a
+//! bare timeout error the call returns what is known: the entries of the playtest
+//! session the call started, the last playtest state and a `TIMEOUT` error code.
+//! The session is the one that became active after the call began, taken from the
+//! playtest state or, once that has moved on, from the first new session id in the
+//! logs. Without one (logs from an older plugin carry no session id) every entry
+//! received since the call began is used. Stopping the playtest is left to the
+//! plugin's own test timeout.
+
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::state::SharedState;
+use crate::types::LogEntry;
+
+/// Log entries returned; the newest are kept, since the end shows where it got stuck.
+const MAX_PARTIAL_LOGS: usize = 200;
+
+/// What the server knew when a call started.
+pub struct CallWindow {
+    /// Server receive time (Unix seconds, as in `LogEntry::ts`) at the start.
+    pub started_ts: f64,
+    /// Playtest session active before the call, which is not the call's own.
+    pub session_before: Option<String>,
+}
+
+impl CallWindow {
+    pub async fn open(state: &SharedState) -> Self {
+        let (active, session_id, _) = state.playtest_info().await;
+        Self {
+            started_ts: state.clock().now().timestamp_millis() as f64 / 1000.0,
+            session_before: session_id.filter(|_| active),
+        }
+    }
+}
+
+/// Log entries attributed to the call.
+pub struct Correlated {
+    /// The session the call started, if one could be identified.
+    pub session_id: Option<String>,
+    /// `session` (tagged entries of that session plus untagged ones in the window)
+    /// or `window` (everything received since the call began).
+    pub correlation: &'static str,
+    pub entries: Vec<Arc<LogEntry>>,
+}
+
+/// Pick the entries belonging to the call from `buffer` (oldest first).
+/// `current_session` is the session active now, if any.
+pub fn correlate(buffer: &[Arc<LogEntry>], window: &CallWindow, current_session: Option<&str>) -> Correlated {
+    let in_window = |entry: &&Arc<LogEntry>| entry.ts >= window.started_ts;
+    let is_new = |id: &str| window.session_before.as_deref() != Some(id);
+    let session_id = current_session
+        .filter(|id| is_new(id))
+        .map(String::from)
+        .or_else(|| {
+            buffer
+                .iter()
+                .filter(in_window)
+                .filter_map(|entry| entry.session_id.as_deref())
+                .find(|id| is_new(id))
+                .map(String::from)
+        });
+
+    let entries: Vec<Arc<LogEntry>> = match &session_id {
+        Some(id) => buffer
+            .iter()
+            .filter(|entry| match &entry.session_id {
+                Some(tagged) => tagged == id,
+                None => entry.ts >= window.started_ts,
+            })
+            .cloned()
+            .collect(),
+        None => buffer.iter().filter(in_window).cloned().collect(),
+    };
+    Correlated {
+        correlation: if session_id.is_some() { "session" } else { "window" },
+        session_id,
+        entries,
+    }
+}
+
+/// The structured partial result for a test_script call that got no answer within
+/// `timeout`. Shaped like a plugin result with `success: false` and `timedOut: true`.
+pub async fn timed_out_test(state: &SharedState, window: &CallWindow, timeout: Duration) -> Value {
+    let (active, current_session, mode) = state.playtest_info().await;
+    // Wall-clock durations would break deterministic transcripts
+    let running_for = if state.is_deterministic() {
+        None
+    } else {
+        state.playtest_running_for().await.map(|(_, _, running_for)| running_for.as_secs_f64())
+    };
+    let buffer = state.get_logs(0, usize::MAX);
+    let Correlated { session_id, correlation, mut entries } =
+        correlate(&buffer, window, current_session.as_deref().filter(|_| active));
+
+    let logs_omitted = entries.len().saturating_sub(MAX_PARTIAL_LOGS);
+    entries.drain(..logs_omitted);
+    let errors: Vec<&Arc<LogEntry>> = entries
+        .iter()
+        .filter(|entry| matches!(entry.level.as_str(), "warning" | "error"))
+        .collect();
+
+    let mut result = json!({
+        "success": false,
+        "timedOut": true,
+        "errorCode": "TIMEOUT",
+        "error": format!(
+            "The plugin did not answer within {}s. This is a partial result from the server's log buffer; the test may still be running until its own timeout stops the playtest.",
+            timeout.as_secs()
+        ),
+        "sessionId": session_id,
+        "correlation": correlation,
+        "logs": entries,
+        "errors": errors,
+        "playtest": {
+            "active": active,
+            "sessionId": current_session,
+            "mode": mode,
+            "runningForSecs": running_for,
+        },
+    });
+    if logs_omitted > 0 {
+        result["logsOmitted"] = json!(logs_omitted);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    fn entry(seq: u64, ts: f64, session: Option<&str>) -> Arc<LogEntry> {
+        Arc::new(LogEntry {
+            seq,
+            ts,
+            level: "info".into(),
+            message: format!("line {seq}"),
+            session_id: session.map(String::from),
+        })
+    }
+
+    fn window(started_ts: f64, session_before: Option<&str>) -> CallWindow {
+        CallWindow { started_ts, session_before: session_before.map(String::from) }
+    }
+
+    fn seqs(correlated: &Correlated) -> Vec<u64> {
+        correlated.entries.iter().map(|e| e.seq).collect()
+    }
+
+    /// A previous session still logging as the call starts, then the call's own.
+    fn two_sessions() -> Vec<Arc<LogEntry>> {
+        vec![
+            entry(1, 90.0, Some("old")),
+            entry(2, 95.0, None),
+            entry(3, 100.0, Some("old")),
+            entry(4, 101.0, None),
+            entry(5, 102.0, Some("new")),
+            entry(6, 103.0, None),
+            entry(7, 104.0, Some("new")),
+            entry(8, 105.0, Some("old")),
+        ]
+    }
+
+    #[test]
+    fn tagged_logs_follow_the_session_the_call_started() {
+        let window = window(100.0, Some("old"));
+        let by_state = correlate(&two_sessions(), &window, Some("new"));
+        assert_eq!((by_state.session_id.as_deref(), by_state.correlation), (Some("new"), "session"));
+        // The new session's entries, untagged ones from the window, nothing of "old"
+        assert_eq!(seqs(&by_state), [4, 5, 6, 7]);
+
+        // Once the playtest has ended (or still shows the old session) the logs name it
+        for current in [None, Some("old")] {
+            let by_logs = correlate(&two_sessions(), &window, current);
+            assert_eq!(by_logs.session_id.as_deref(), Some("new"), "{current:?}");
+            assert_eq!(seqs(&by_logs), [4, 5, 6, 7], "{current:?}");
+        }
+    }
+
+    #[test]
+    fn a_session_tagged_only_before_the_window_is_not_the_calls() {
+        // No playtest was running before the call, and "old" only logged before it began
+        let buffer = vec![entry(1, 90.0, Some("old")), entry(2, 101.0, None), entry(3, 102.0, Some("new"))];
+        let correlated = correlate(&buffer, &window(100.0, None), None);
+        assert_eq!(correlated.session_id.as_deref(), Some("new"));
+        assert_eq!(seqs(&correlated), [2, 3]);
+    }
+
+    #[test]
+    fn untagged_logs_fall_back_to_the_call_window() {
+        // An older plugin: no session ids at all
+        let buffer = vec![entry(1, 99.9, None), entry(2, 100.0, None), entry(3, 120.0, None)];
+        let correlated = correlate(&buffer, &window(100.0, None), None);
+        assert_eq!((correlated.session_id.as_deref(), correlated.correlation), (None, "window"));
+        assert_eq!(seqs(&correlated), [2, 3]);
+
+        // Only the session that was already running logged: everything in the window
+        let correlated = correlate(&two_sessions(), &window(104.5, Some("old")), Some("old"));
+        assert_eq!((correlated.session_id.as_deref(), correlated.correlation), (None, "window"));
+        assert_eq!(seqs(&correlated), [8]);
+
+        let correlated = correlate(&[], &window(100.0, None), None);
+        assert!(correlated.entries.is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_partial_result_keeps_the_newest_logs_and_the_playtest_state() {
+        let state = SharedState::for_tests(Config { deterministic_seed: Some(7), ..Config::default() });
+        state.update_playtest(true, Some("new".into()), Some("play".into())).await;
+        state.push_log("info".into(), "from an earlier test".into(), Some("old".into()));
+        for i in 0..MAX_PARTIAL_LOGS + 5 {
+            let level = if i % 100 == 0 { "warning" } else { "info" };
+            state.push_log(level.into(), format!("step {i}"), Some("new".into()));
+        }
+        state.push_log("error".into(), "stuck waiting for the round".into(), Some("new".into()));
+
+        let result = timed_out_test(&state, &window(0.0, Some("old")), Duration::from_secs(45)).await;
+        assert_eq!((&result["success"], &result["timedOut"], &result["errorCode"]), (&json!(false), &json!(true), &json!("TIMEOUT")));
+        assert!(result["error"].as_str().unwrap().starts_with("The plugin did not answer within 45s."));
+        assert_eq!((&result["sessionId"], &result["correlation"]), (&json!("new"), &json!("session")));
+        assert_eq!(result["playtest"], json!({ "active": true, "sessionId": "new", "mode": "play", "runningForSecs": null }));
+
+        let logs = result["logs"].as_array().unwrap();
+        assert_eq!(logs.len(), MAX_PARTIAL_LOGS);
+        assert_eq!(result["logsOmitted"], 6);
+        assert_eq!(logs[0]["message"], "step 6");
+        assert_eq!(logs[MAX_PARTIAL_LOGS - 1]["message"], "stuck waiting for the round");
+        let errors: Vec<&str> = result["errors"].as_array().unwrap().iter().map(|e| e["message"].as_str().unwrap()).collect();
+        assert_eq!(errors, ["step 100", "step 200", "stuck waiting for the round"]);
+    }
+
+    #[tokio::test]
+    async fn without_a_playtest_or_logs_the_result_says_so() {
+        let state = SharedState::for_tests(Config::default());
+        let window = CallWindow::open(&state).await;
+        assert_eq!(window.session_before, None);
+        let result = timed_out_test(&state, &window, Duration::from_secs(30)).await;
+        assert_eq!((&result["sessionId"], &result["correlation"]), (&Value::Null, &json!("window")));
+        assert_eq!((&result["logs"], &result["errors"]), (&json!([]), &json!([])));
+        assert_eq!(result["playtest"]["active"], false);
+        assert!(result.get("logsOmitted").is_none());
+    }
+}
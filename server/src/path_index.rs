@@ -0,0 +1,372 @@
+//! Index of instance paths under the watched roots (`YIPPIE_TREE_ROOTS`), kept current
+//! by `studio-tree_delta` events from the plugin.
+//!
+//! The plugin numbers its deltas per client. A delta that doesn't directly follow the
+//! last one applied (a gap, a delta from a client the index wasn't built from, or an
+//! `overflow` flag from the plugin's storm guard) triggers a full `studio-tree_snapshot`.
+//! Deltas arriving while the snapshot is in flight are dropped; the snapshot's
+//! sequence number says where to continue.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::mcp_stdio::call_plugin;
+use crate::providers::Clock;
+use crate::state::SharedState;
+
+/// Paths kept at most. The plugin caps its snapshot at the same size.
+pub const MAX_INDEXED_PATHS: usize = 20_000;
+
+/// A delta with more changes than this is treated like an overflow.
+const MAX_DELTA_CHANGES: usize = 2_000;
+
+/// Walking a large place can take a while; deltas are dropped until it finishes.
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+pub struct TreeDelta {
+    pub seq: u64,
+    #[serde(default)]
+    pub added: Vec<IndexedPath>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub renamed: Vec<RenamedPath>,
+    /// The plugin dropped changes because too many arrived at once.
+    #[serde(default)]
+    pub overflow: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexedPath {
+    pub path: String,
+    #[serde(rename = "class")]
+    pub class_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RenamedPath {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TreeSnapshot {
+    client_id: String,
+    seq: u64,
+    entries: Vec<IndexedPath>,
+    #[serde(default)]
+    truncated: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeltaOutcome {
+    Applied,
+    /// Already covered by the index (or dropped while a refresh is in flight).
+    Ignored,
+    /// The index can't be brought up to date from this delta; start a refresh.
+    NeedsRefresh,
+}
+
+#[derive(Debug, Default)]
+pub struct PathIndex {
+    /// Full name -> ClassName
+    paths: BTreeMap<String, String>,
+    /// Client the index follows and the last sequence number applied from it.
+    source: Option<(String, u64)>,
+    refreshing: bool,
+    /// Paths were left out because the index is full.
+    truncated: bool,
+    updated_at: Option<Instant>,
+    deltas_applied: u64,
+    gaps: u64,
+}
+
+impl PathIndex {
+    /// Mark a refresh as started. Returns false if one is already in flight.
+    pub fn begin_refresh(&mut self) -> bool {
+        !std::mem::replace(&mut self.refreshing, true)
+    }
+
+    pub fn apply_delta(&mut self, client_id: &str, delta: TreeDelta) -> DeltaOutcome {
+        if self.refreshing {
+            return DeltaOutcome::Ignored;
+        }
+        let expected = match &self.source {
+            Some((source, seq)) if source == client_id => Some(seq + 1),
+            _ => None,
+        };
+        if expected.is_some_and(|e| delta.seq < e) {
+            return DeltaOutcome::Ignored;
+        }
+        let changes = delta.added.len() + delta.removed.len() + delta.renamed.len();
+        if expected != Some(delta.seq) || delta.overflow || changes > MAX_DELTA_CHANGES {
+            tracing::info!(
+                client_id,
+                seq = delta.seq,
+                expected = ?expected,
+                overflow = delta.overflow,
+                changes,
+                "Tree delta can't be applied; refreshing path index"
+            );
+            self.gaps += 1;
+            return DeltaOutcome::NeedsRefresh;
+        }
+
+        for path in &delta.removed {
+            self.remove_subtree(path);
+        }
+        for rename in &delta.renamed {
+            self.rename_subtree(&rename.from, &rename.to);
+        }
+        for added in delta.added {
+            self.insert(added.path, added.class_name);
+        }
+        self.source = Some((client_id.to_string(), delta.seq));
+        self.deltas_applied += 1;
+        self.updated_at = Some(Instant::now());
+        DeltaOutcome::Applied
+    }
+
+    fn finish_refresh(&mut self, snapshot: TreeSnapshot) {
+        self.paths.clear();
+        self.truncated = snapshot.truncated;
+        for entry in snapshot.entries {
+            self.insert(entry.path, entry.class_name);
+        }
+        self.source = Some((snapshot.client_id, snapshot.seq));
+        self.refreshing = false;
+        self.updated_at = Some(Instant::now());
+    }
+
+    fn fail_refresh(&mut self) {
+        // The next delta finds no matching source and asks again
+        self.source = None;
+        self.refreshing = false;
+    }
+
+    fn insert(&mut self, path: String, class_name: String) {
+        if self.paths.len() >= MAX_INDEXED_PATHS && !self.paths.contains_key(&path) {
+            self.truncated = true;
+            return;
+        }
+        self.paths.insert(path, class_name);
+    }
+
+    /// Keys of `path` and everything below it. Children are `path.<name>`, and '/'
+    /// is the character right after '.', so the range covers exactly the subtree.
+    fn subtree_keys(&self, path: &str) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .paths
+            .range(format!("{path}.")..format!("{path}/"))
+            .map(|(k, _)| k.clone())
+            .collect();
+        if self.paths.contains_key(path) {
+            keys.push(path.to_string());
+        }
+        keys
+    }
+
+    fn remove_subtree(&mut self, path: &str) {
+        for key in self.subtree_keys(path) {
+            self.paths.remove(&key);
+        }
+    }
+
+    fn rename_subtree(&mut self, from: &str, to: &str) {
+        for key in self.subtree_keys(from) {
+            if let Some(class_name) = self.paths.remove(&key) {
+                let moved = format!("{to}{}", &key[from.len()..]);
+                self.insert(moved, class_name);
+            }
+        }
+    }
+
+    /// ClassName of an indexed path, if the index has it.
+    pub fn class_of(&self, path: &str) -> Option<&str> {
+        self.paths.get(path).map(String::as_str)
+    }
+
+    /// Size and freshness, for studio-status.
+    pub fn status(&self, clock: &dyn Clock) -> Value {
+        json!({
+            "paths": self.paths.len(),
+            "truncated": self.truncated,
+            "refreshing": self.refreshing,
+            "lastSeq": self.source.as_ref().map(|(_, seq)| seq),
+            "updatedSecsAgo": self.updated_at.map(|t| clock.elapsed(t).as_secs()),
+            "deltasApplied": self.deltas_applied,
+            "gaps": self.gaps,
+        })
+    }
+}
+
+/// Replace the index with a full snapshot from the plugin. The snapshot call also
+/// (re)starts the plugin's delta stream for the configured roots.
+pub async fn refresh(state: SharedState) {
+    let args = json!({ "roots": state.tree_roots(), "watch": true });
+    let result = call_plugin(&state, "studio-tree_snapshot", args, SNAPSHOT_TIMEOUT)
+        .await
+        .and_then(|v| serde_json::from_value::<TreeSnapshot>(v).map_err(|e| e.to_string()));
+    let Some(mut index) = state.path_index() else {
+        return;
+    };
+    match result {
+        Ok(snapshot) => {
+            tracing::info!(
+                paths = snapshot.entries.len(),
+                seq = snapshot.seq,
+                truncated = snapshot.truncated,
+                "Path index refreshed"
+            );
+            index.finish_refresh(snapshot);
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Path index refresh failed");
+            index.fail_refresh();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::SystemClock;
+
+    fn added(path: &str, class_name: &str) -> IndexedPath {
+        IndexedPath { path: path.into(), class_name: class_name.into() }
+    }
+
+    fn delta(seq: u64) -> TreeDelta {
+        TreeDelta { seq, added: Vec::new(), removed: Vec::new(), renamed: Vec::new(), overflow: false }
+    }
+
+    fn snapshot(client_id: &str, seq: u64, entries: Vec<IndexedPath>) -> TreeSnapshot {
+        TreeSnapshot { client_id: client_id.into(), seq, entries, truncated: false }
+    }
+
+    /// An index built from a snapshot of `edit` at sequence 10.
+    fn indexed() -> PathIndex {
+        let mut index = PathIndex::default();
+        assert!(index.begin_refresh());
+        index.finish_refresh(snapshot(
+            "edit",
+            10,
+            vec![
+                added("Workspace", "Workspace"),
+                added("Workspace.Map", "Model"),
+                added("Workspace.Map.Wall", "Part"),
+                added("Workspace.Map.Wall.Decal", "Decal"),
+                added("Workspace.Map2", "Model"),
+                added("Workspace.Map2.Floor", "Part"),
+            ],
+        ));
+        index
+    }
+
+    fn paths(index: &PathIndex) -> Vec<&str> {
+        index.paths.keys().map(String::as_str).collect()
+    }
+
+    #[test]
+    fn deltas_add_remove_and_rename_whole_subtrees() {
+        let mut index = indexed();
+        let mut first = delta(11);
+        first.added = vec![added("Workspace.Map.Door", "Part")];
+        first.removed = vec!["Workspace.Map.Wall".into()];
+        assert_eq!(index.apply_delta("edit", first), DeltaOutcome::Applied);
+        // A sibling whose name starts with the removed one's is left alone
+        assert_eq!(paths(&index), ["Workspace", "Workspace.Map", "Workspace.Map.Door", "Workspace.Map2", "Workspace.Map2.Floor"]);
+
+        let mut second = delta(12);
+        second.renamed = vec![RenamedPath { from: "Workspace.Map".into(), to: "Workspace.Level".into() }];
+        assert_eq!(index.apply_delta("edit", second), DeltaOutcome::Applied);
+        assert_eq!(paths(&index), ["Workspace", "Workspace.Level", "Workspace.Level.Door", "Workspace.Map2", "Workspace.Map2.Floor"]);
+        assert_eq!(index.class_of("Workspace.Level.Door"), Some("Part"));
+        assert_eq!(index.class_of("Workspace.Map.Door"), None);
+    }
+
+    #[test]
+    fn removals_then_renames_then_additions_within_a_delta() {
+        let mut index = indexed();
+        // The plugin batched "Wall removed, Map2 renamed to Map3, a new Wall added"
+        let mut batched = delta(11);
+        batched.added = vec![added("Workspace.Map.Wall", "WedgePart")];
+        batched.removed = vec!["Workspace.Map.Wall".into()];
+        batched.renamed = vec![RenamedPath { from: "Workspace.Map2".into(), to: "Workspace.Map3".into() }];
+        assert_eq!(index.apply_delta("edit", batched), DeltaOutcome::Applied);
+        assert_eq!(index.class_of("Workspace.Map.Wall"), Some("WedgePart"));
+        assert_eq!(index.class_of("Workspace.Map.Wall.Decal"), None);
+        assert_eq!(index.class_of("Workspace.Map3.Floor"), Some("Part"));
+    }
+
+    #[test]
+    fn old_deltas_are_ignored_and_gaps_ask_for_a_refresh() {
+        let mut index = indexed();
+        assert_eq!(index.apply_delta("edit", delta(10)), DeltaOutcome::Ignored);
+        assert_eq!(index.apply_delta("edit", delta(3)), DeltaOutcome::Ignored);
+        assert_eq!(index.apply_delta("edit", delta(11)), DeltaOutcome::Applied);
+        assert_eq!(index.apply_delta("edit", delta(13)), DeltaOutcome::NeedsRefresh);
+        // Another client's stream can't continue this index either
+        assert_eq!(index.apply_delta("edit-2", delta(12)), DeltaOutcome::NeedsRefresh);
+        assert_eq!(index.status(&SystemClock)["gaps"], 2);
+        assert_eq!(index.status(&SystemClock)["lastSeq"], 11);
+    }
+
+    #[test]
+    fn storms_ask_for_a_refresh_instead_of_applying() {
+        let mut index = indexed();
+        let mut overflowed = delta(11);
+        overflowed.overflow = true;
+        overflowed.added = vec![added("Workspace.Spark", "Part")];
+        assert_eq!(index.apply_delta("edit", overflowed), DeltaOutcome::NeedsRefresh);
+        assert_eq!(index.class_of("Workspace.Spark"), None);
+
+        let mut huge = delta(11);
+        huge.added = (0..=MAX_DELTA_CHANGES).map(|i| added(&format!("Workspace.Spark{i}"), "Part")).collect();
+        assert_eq!(index.apply_delta("edit", huge), DeltaOutcome::NeedsRefresh);
+        assert_eq!(index.paths.len(), 6);
+    }
+
+    #[test]
+    fn a_refresh_drops_deltas_until_the_snapshot_says_where_to_continue() {
+        let mut index = indexed();
+        assert!(index.begin_refresh());
+        assert!(!index.begin_refresh());
+        assert_eq!(index.apply_delta("edit", delta(11)), DeltaOutcome::Ignored);
+        assert_eq!(index.status(&SystemClock)["refreshing"], true);
+
+        index.finish_refresh(snapshot("edit-2", 40, vec![added("Workspace", "Workspace")]));
+        assert_eq!(paths(&index), ["Workspace"]);
+        assert_eq!(index.apply_delta("edit-2", delta(40)), DeltaOutcome::Ignored);
+        assert_eq!(index.apply_delta("edit-2", delta(41)), DeltaOutcome::Applied);
+    }
+
+    #[test]
+    fn a_failed_refresh_makes_the_next_delta_ask_again() {
+        let mut index = indexed();
+        assert!(index.begin_refresh());
+        index.fail_refresh();
+        assert_eq!(index.apply_delta("edit", delta(11)), DeltaOutcome::NeedsRefresh);
+        assert!(index.begin_refresh());
+    }
+
+    #[test]
+    fn the_index_stops_growing_at_its_cap() {
+        let mut index = PathIndex::default();
+        let entries = (0..MAX_INDEXED_PATHS + 5).map(|i| added(&format!("Workspace.P{i:05}"), "Part")).collect();
+        index.finish_refresh(snapshot("edit", 1, entries));
+        let status = index.status(&SystemClock);
+        assert_eq!(status["paths"], MAX_INDEXED_PATHS);
+        assert_eq!(status["truncated"], true);
+        // Renaming within a full index moves paths rather than dropping them
+        let mut rename = delta(2);
+        rename.renamed = vec![RenamedPath { from: "Workspace.P00000".into(), to: "Workspace.First".into() }];
+        assert_eq!(index.apply_delta("edit", rename), DeltaOutcome::Applied);
+        assert_eq!(index.class_of("Workspace.First"), Some("Part"));
+        assert_eq!(index.status(&SystemClock)["paths"], MAX_INDEXED_PATHS);
+    }
+}
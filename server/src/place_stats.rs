@@ -0,0 +1,576 @@
+//! `studio-place_stats`: instance counts and sizes across the DataModel, kept so a
+//! later call can show what an optimization pass changed.
+//!
+//! The plugin (`tools/place_stats.lua`) only walks the tree and reports raw numbers:
+//! a class histogram, script count and source length, and texture references. What
+//! those numbers must satisfy and everything derived from them (unions, mesh parts,
+//! the texture memory estimate) is defined here. Snapshots are kept in memory, the
+//! last `MAX_STORED_SNAPSHOTS`, and `compareTo` diffs against one of them.
+
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::render;
+
+pub const DEFAULT_TOP: usize = 25;
+pub const MAX_TOP: usize = 500;
+
+/// Snapshots kept for `compareTo`; older ones are dropped.
+pub const MAX_STORED_SNAPSHOTS: usize = 20;
+
+/// Assumed size of one texture: 512×512 RGBA with mipmaps. The plugin can't read
+/// texture resolutions, so memory is estimated from the number of distinct ids.
+const ESTIMATED_TEXTURE_BYTES: u64 = 512 * 512 * 4 * 4 / 3;
+
+const SCRIPT_CLASSES: &[&str] = &["Script", "LocalScript", "ModuleScript"];
+const UNION_CLASSES: &[&str] = &["UnionOperation", "NegateOperation", "IntersectOperation"];
+
+/// Checked arguments, passed on to the plugin.
+#[derive(Debug, Clone)]
+pub struct StatsRequest {
+    /// Instance path to count under; `None` is the whole place.
+    pub root: Option<String>,
+    pub compare_to: Option<String>,
+    pub top: usize,
+}
+
+impl StatsRequest {
+    pub fn parse(arguments: &Value) -> Result<Self, String> {
+        let top = match arguments.get("top").filter(|v| !v.is_null()) {
+            None => DEFAULT_TOP,
+            Some(v) => v
+                .as_u64()
+                .filter(|n| (1..=MAX_TOP as u64).contains(n))
+                .ok_or_else(|| format!("'top' must be a number of rows from 1 to {MAX_TOP}"))? as usize,
+        };
+        let text = |name: &str| -> Result<Option<String>, String> {
+            match arguments.get(name).filter(|v| !v.is_null()) {
+                None => Ok(None),
+                Some(Value::String(s)) if !s.trim().is_empty() => Ok(Some(s.trim().to_string())),
+                Some(_) => Err(format!("'{name}' must be a non-empty string")),
+            }
+        };
+        Ok(Self {
+            root: text("root")?,
+            compare_to: text("compareTo")?,
+            top,
+        })
+    }
+
+    pub fn plugin_args(&self) -> Value {
+        json!({ "root": self.root })
+    }
+}
+
+/// What the plugin reports. Field names are the wire contract of `tools/place_stats.lua`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginStats {
+    root: String,
+    instances: u64,
+    #[serde(deserialize_with = "histogram")]
+    classes: BTreeMap<String, u64>,
+    scripts: PluginScripts,
+    textures: PluginTextures,
+    /// Subtrees the plugin could not walk (locked services).
+    #[serde(default)]
+    skipped: Vec<String>,
+}
+
+/// Luau encodes an empty table as `[]`.
+fn histogram<'de, D: Deserializer<'de>>(deserializer: D) -> Result<BTreeMap<String, u64>, D::Error> {
+    match Value::deserialize(deserializer)? {
+        Value::Array(items) if items.is_empty() => Ok(BTreeMap::new()),
+        other => serde_json::from_value(other).map_err(serde::de::Error::custom),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginScripts {
+    count: u64,
+    source_bytes: u64,
+    /// Scripts whose Source could not be read; not in `source_bytes`.
+    #[serde(default)]
+    unreadable: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PluginTextures {
+    references: u64,
+    unique: u64,
+}
+
+/// Figures derived from a snapshot, compared one to one in a delta.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Totals {
+    pub instances: u64,
+    pub distinct_classes: u64,
+    pub scripts: u64,
+    pub script_source_bytes: u64,
+    pub unions: u64,
+    pub mesh_parts: u64,
+    pub texture_references: u64,
+    pub unique_textures: u64,
+    pub estimated_texture_bytes: u64,
+}
+
+impl Totals {
+    /// (name, value) in display order.
+    fn rows(&self) -> [(&'static str, u64); 9] {
+        [
+            ("instances", self.instances),
+            ("distinctClasses", self.distinct_classes),
+            ("scripts", self.scripts),
+            ("scriptSourceBytes", self.script_source_bytes),
+            ("unions", self.unions),
+            ("meshParts", self.mesh_parts),
+            ("textureReferences", self.texture_references),
+            ("uniqueTextures", self.unique_textures),
+            ("estimatedTextureBytes", self.estimated_texture_bytes),
+        ]
+    }
+}
+
+/// One stored result of `studio-place_stats`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceStats {
+    pub id: String,
+    pub root: String,
+    pub taken_at: String,
+    pub totals: Totals,
+    pub unreadable_scripts: u64,
+    pub skipped: Vec<String>,
+    pub classes: BTreeMap<String, u64>,
+}
+
+impl PlaceStats {
+    /// Check the plugin's numbers against each other and derive the totals.
+    pub fn from_plugin(result: Value, id: String, taken_at: String) -> Result<Self, String> {
+        let stats: PluginStats =
+            serde_json::from_value(result).map_err(|e| format!("Unexpected place stats from the plugin: {e}"))?;
+        if let Some(name) = stats.classes.keys().find(|name| name.is_empty()) {
+            return Err(format!("The plugin's class histogram has an empty class name ({name:?})"));
+        }
+        let counted: u64 = stats.classes.values().sum();
+        if counted != stats.instances {
+            return Err(format!(
+                "The plugin's class histogram adds up to {counted} instances but it reported {}",
+                stats.instances
+            ));
+        }
+        let count = |classes: &[&str]| classes.iter().filter_map(|c| stats.classes.get(*c)).sum::<u64>();
+        let script_classes = count(SCRIPT_CLASSES);
+        if stats.scripts.count != script_classes {
+            return Err(format!(
+                "The plugin reported {} scripts but its histogram has {script_classes} Script, LocalScript and ModuleScript instances",
+                stats.scripts.count
+            ));
+        }
+        if stats.scripts.unreadable > stats.scripts.count {
+            return Err("The plugin reported more unreadable scripts than scripts".into());
+        }
+        if stats.textures.unique > stats.textures.references {
+            return Err(format!(
+                "The plugin reported {} distinct textures from only {} references",
+                stats.textures.unique, stats.textures.references
+            ));
+        }
+        let totals = Totals {
+            instances: stats.instances,
+            distinct_classes: stats.classes.len() as u64,
+            scripts: stats.scripts.count,
+            script_source_bytes: stats.scripts.source_bytes,
+            unions: count(UNION_CLASSES),
+            mesh_parts: count(&["MeshPart"]),
+            texture_references: stats.textures.references,
+            unique_textures: stats.textures.unique,
+            estimated_texture_bytes: stats.textures.unique * ESTIMATED_TEXTURE_BYTES,
+        };
+        Ok(Self {
+            id,
+            root: stats.root,
+            taken_at,
+            totals,
+            unreadable_scripts: stats.scripts.unreadable,
+            skipped: stats.skipped,
+            classes: stats.classes,
+        })
+    }
+
+    /// Classes by count, largest first, ties by name.
+    fn ranked_classes(&self) -> Vec<(&str, u64)> {
+        let mut ranked: Vec<(&str, u64)> = self.classes.iter().map(|(c, n)| (c.as_str(), *n)).collect();
+        ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        ranked
+    }
+}
+
+/// Snapshots of earlier calls, oldest first.
+#[derive(Default)]
+pub struct StatsStore {
+    snapshots: VecDeque<PlaceStats>,
+}
+
+impl StatsStore {
+    pub fn insert(&mut self, stats: PlaceStats) {
+        if self.snapshots.len() == MAX_STORED_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(stats);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PlaceStats> {
+        self.snapshots.iter().find(|s| s.id == id)
+    }
+
+    pub fn ids(&self) -> Vec<&str> {
+        self.snapshots.iter().map(|s| s.id.as_str()).collect()
+    }
+}
+
+/// One class whose count differs between two snapshots.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClassChange {
+    pub class: String,
+    pub before: u64,
+    pub after: u64,
+    pub delta: i64,
+}
+
+/// A total that may differ between two snapshots.
+#[derive(Debug, Clone, Serialize)]
+pub struct TotalChange {
+    pub total: &'static str,
+    pub before: u64,
+    pub after: u64,
+    pub delta: i64,
+}
+
+/// How `after` differs from `before`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Comparison {
+    pub compared_to: String,
+    pub compared_to_taken_at: String,
+    /// Set when the snapshots counted different roots.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub root_mismatch: Option<String>,
+    pub totals: Vec<TotalChange>,
+    pub classes_added: Vec<ClassChange>,
+    pub classes_removed: Vec<ClassChange>,
+    /// Classes on both sides whose count changed, largest change first, at most `top`.
+    pub changes: Vec<ClassChange>,
+    pub changed_classes: usize,
+}
+
+fn change(name: &str, before: u64, after: u64) -> ClassChange {
+    ClassChange {
+        class: name.to_string(),
+        before,
+        after,
+        delta: after as i64 - before as i64,
+    }
+}
+
+/// Largest change first, ties by name.
+fn by_magnitude(changes: &mut [ClassChange]) {
+    changes.sort_by(|a, b| b.delta.unsigned_abs().cmp(&a.delta.unsigned_abs()).then(a.class.cmp(&b.class)));
+}
+
+pub fn compare(before: &PlaceStats, after: &PlaceStats, top: usize) -> Comparison {
+    let totals = before
+        .totals
+        .rows()
+        .into_iter()
+        .zip(after.totals.rows())
+        .map(|((total, before), (_, after))| TotalChange {
+            total,
+            before,
+            after,
+            delta: after as i64 - before as i64,
+        })
+        .collect();
+
+    let mut classes_added = Vec::new();
+    let mut classes_removed = Vec::new();
+    let mut changes = Vec::new();
+    for (class, &count) in &after.classes {
+        match before.classes.get(class) {
+            None => classes_added.push(change(class, 0, count)),
+            Some(&old) if old != count => changes.push(change(class, old, count)),
+            Some(_) => {}
+        }
+    }
+    for (class, &count) in &before.classes {
+        if !after.classes.contains_key(class) {
+            classes_removed.push(change(class, count, 0));
+        }
+    }
+    by_magnitude(&mut classes_added);
+    by_magnitude(&mut classes_removed);
+    by_magnitude(&mut changes);
+    let changed_classes = changes.len();
+    changes.truncate(top);
+
+    Comparison {
+        compared_to: before.id.clone(),
+        compared_to_taken_at: before.taken_at.clone(),
+        root_mismatch: (before.root != after.root)
+            .then(|| format!("{} counted {}, this snapshot counted {}", before.id, before.root, after.root)),
+        totals,
+        classes_added,
+        classes_removed,
+        changes,
+        changed_classes,
+    }
+}
+
+/// The result as structuredContent: the snapshot with its classes ranked (top `top`),
+/// and the comparison if there is one.
+pub fn structured(stats: &PlaceStats, comparison: Option<&Comparison>, top: usize) -> Value {
+    let ranked = stats.ranked_classes();
+    let classes: Vec<Value> = ranked.iter().take(top).map(|(c, n)| json!({ "class": c, "count": n })).collect();
+    let mut value = json!({
+        "id": stats.id,
+        "root": stats.root,
+        "takenAt": stats.taken_at,
+        "totals": stats.totals,
+        "classes": classes,
+        "classesTruncated": ranked.len() > top,
+    });
+    if stats.unreadable_scripts > 0 {
+        value["unreadableScripts"] = json!(stats.unreadable_scripts);
+    }
+    if !stats.skipped.is_empty() {
+        value["skipped"] = json!(stats.skipped);
+    }
+    if let Some(comparison) = comparison {
+        value["comparison"] = json!(comparison);
+    }
+    value
+}
+
+/// Readable tables for the text content.
+pub fn render_text(stats: &PlaceStats, comparison: Option<&Comparison>, top: usize) -> String {
+    let table = |columns: &[&str], rows: Vec<Value>| render::to_text_table(columns, &rows);
+    let mut out = format!("Place stats {} of {} at {}\n\n", stats.id, stats.root, stats.taken_at);
+    let totals = stats.totals.rows().into_iter().map(|(name, value)| json!({ "total": name, "value": value }));
+    out += &table(&["total", "value"], totals.collect());
+    out += "(estimatedTextureBytes assumes 512x512 RGBA with mipmaps per distinct texture)\n";
+    if stats.unreadable_scripts > 0 {
+        out += &format!("{} scripts could not be read and are not in scriptSourceBytes\n", stats.unreadable_scripts);
+    }
+    if !stats.skipped.is_empty() {
+        out += &format!("Not counted (not accessible): {}\n", stats.skipped.join(", "));
+    }
+
+    let ranked = stats.ranked_classes();
+    out += &format!("\nTop {} of {} classes\n", top.min(ranked.len()), ranked.len());
+    let rows = ranked.iter().take(top).map(|(c, n)| json!({ "class": c, "count": n }));
+    out += &table(&["class", "count"], rows.collect());
+
+    let Some(comparison) = comparison else {
+        return out;
+    };
+    let signed = |name: &str, before: u64, after: u64, delta: i64| {
+        json!({ "name": name, "before": before, "after": after, "delta": format!("{delta:+}") })
+    };
+    let class_rows = |list: &[ClassChange]| list.iter().map(|c| signed(&c.class, c.before, c.after, c.delta)).collect();
+    let columns = ["name", "before", "after", "delta"];
+    out += &format!("\nCompared with {} at {}\n", comparison.compared_to, comparison.compared_to_taken_at);
+    if let Some(mismatch) = &comparison.root_mismatch {
+        out += &format!("Warning: {mismatch}\n");
+    }
+    let totals = comparison.totals.iter().map(|t| signed(t.total, t.before, t.after, t.delta));
+    out += &table(&columns, totals.collect());
+    for (title, list) in [("Classes added", &comparison.classes_added), ("Classes removed", &comparison.classes_removed)] {
+        if !list.is_empty() {
+            out += &format!("\n{title} ({})\n", list.len());
+            out += &table(&columns, class_rows(list));
+        }
+    }
+    if comparison.changed_classes == 0 {
+        out += "\nNo class counts changed\n";
+    } else {
+        out += &format!("\nCount changes ({} of {}, largest first)\n", comparison.changes.len(), comparison.changed_classes);
+        out += &table(&columns, class_rows(&comparison.changes));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn fixture(name: &str) -> Value {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/place_stats").join(name);
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+    }
+
+    fn snapshot(name: &str, id: &str) -> PlaceStats {
+        PlaceStats::from_plugin(fixture(name), id.into(), format!("2026-10-17T10:00:0{}Z", id.len())).unwrap()
+    }
+
+    /// Cells of the table row whose first cell is `first`.
+    fn row<'a>(text: &'a str, first: &str) -> Vec<&'a str> {
+        text.lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .find(|cells| cells.first() == Some(&first))
+            .unwrap_or_else(|| panic!("no row {first} in\n{text}"))
+    }
+
+    #[test]
+    fn arguments_are_checked() {
+        let request = StatsRequest::parse(&json!({})).unwrap();
+        assert_eq!((request.root, request.compare_to, request.top), (None, None, DEFAULT_TOP));
+        let request = StatsRequest::parse(&json!({ "root": " Workspace.Map ", "compareTo": "s1", "top": 3 })).unwrap();
+        assert_eq!((request.root.as_deref(), request.compare_to.as_deref(), request.top), (Some("Workspace.Map"), Some("s1"), 3));
+        assert_eq!(request.plugin_args(), json!({ "root": "Workspace.Map" }));
+
+        let refused = |arguments: Value| StatsRequest::parse(&arguments).unwrap_err();
+        assert_eq!(refused(json!({ "top": 0 })), "'top' must be a number of rows from 1 to 500");
+        assert_eq!(refused(json!({ "top": MAX_TOP + 1 })), "'top' must be a number of rows from 1 to 500");
+        assert_eq!(refused(json!({ "root": "  " })), "'root' must be a non-empty string");
+        assert_eq!(refused(json!({ "compareTo": 4 })), "'compareTo' must be a non-empty string");
+    }
+
+    #[test]
+    fn totals_are_derived_from_the_histogram() {
+        let stats = snapshot("before.json", "s1");
+        let totals = &stats.totals;
+        assert_eq!((totals.instances, totals.distinct_classes, totals.scripts, totals.script_source_bytes), (1354, 11, 150, 412_000));
+        // UnionOperation and NegateOperation
+        assert_eq!((totals.unions, totals.mesh_parts), (64, 0));
+        assert_eq!((totals.texture_references, totals.unique_textures), (120, 48));
+        assert_eq!(totals.estimated_texture_bytes, 48 * 1_398_101);
+        assert_eq!((stats.unreadable_scripts, stats.skipped.as_slice()), (2, ["CoreGui".to_string()].as_slice()));
+        assert_eq!(stats.ranked_classes()[..3], [("Part", 800), ("Model", 120), ("Decal", 90)]);
+    }
+
+    #[test]
+    fn an_empty_place_comes_as_an_empty_luau_table() {
+        let empty = json!({
+            "root": "Workspace.Empty", "instances": 0, "classes": [],
+            "scripts": { "count": 0, "sourceBytes": 0 }, "textures": { "references": 0, "unique": 0 },
+        });
+        let stats = PlaceStats::from_plugin(empty, "s1".into(), "t".into()).unwrap();
+        assert!(stats.classes.is_empty());
+        assert_eq!(stats.totals.distinct_classes, 0);
+        assert!(render_text(&stats, None, DEFAULT_TOP).contains("\nTop 0 of 0 classes\nclass  count\n-----  -----\n(no rows)\n"));
+    }
+
+    #[test]
+    fn inconsistent_plugin_numbers_are_refused() {
+        let Value::Object(cases) = fixture("invalid.json") else { panic!("invalid.json is not an object") };
+        for (case, fixture) in cases {
+            let error = PlaceStats::from_plugin(fixture["result"].clone(), "s1".into(), "t".into()).unwrap_err();
+            let expected = fixture["error"].as_str().unwrap();
+            assert!(error.starts_with(expected), "{case}: {error}");
+        }
+    }
+
+    #[test]
+    fn comparisons_list_added_removed_and_changed_classes_by_magnitude() {
+        let (before, after) = (snapshot("before.json", "s1"), snapshot("after.json", "s2"));
+        let comparison = compare(&before, &after, DEFAULT_TOP);
+        assert_eq!((comparison.compared_to.as_str(), comparison.root_mismatch.as_deref()), ("s1", None));
+        let list = |changes: &[ClassChange]| changes.iter().map(|c| (c.class.clone(), c.before, c.after, c.delta)).collect::<Vec<_>>();
+        let expected = |rows: &[(&str, u64, u64, i64)]| rows.iter().map(|(c, b, a, d)| (c.to_string(), *b, *a, *d)).collect::<Vec<_>>();
+        assert_eq!(list(&comparison.classes_added), expected(&[("MeshPart", 0, 64, 64)]));
+        assert_eq!(
+            list(&comparison.classes_removed),
+            expected(&[("UnionOperation", 60, 0, -60), ("Sparkles", 30, 0, -30), ("NegateOperation", 4, 0, -4)])
+        );
+        assert_eq!(list(&comparison.changes), expected(&[("Part", 800, 640, -160), ("Script", 40, 12, -28), ("ModuleScript", 85, 109, 24)]));
+        assert_eq!(comparison.changed_classes, 3);
+
+        let totals: Vec<(&str, i64)> = comparison.totals.iter().map(|t| (t.total, t.delta)).collect();
+        assert_eq!(
+            totals,
+            [
+                ("instances", -194),
+                ("distinctClasses", -2),
+                ("scripts", -4),
+                ("scriptSourceBytes", -13_500),
+                ("unions", -64),
+                ("meshParts", 64),
+                ("textureReferences", 0),
+                ("uniqueTextures", -18),
+                ("estimatedTextureBytes", -18 * 1_398_101),
+            ]
+        );
+
+        // `top` bounds the count changes only; the count says how many there were
+        let comparison = compare(&before, &after, 1);
+        assert_eq!((comparison.changes.len(), comparison.changed_classes, comparison.classes_removed.len()), (1, 3, 3));
+        // Comparing with itself changes nothing
+        let same = compare(&after, &after, DEFAULT_TOP);
+        assert!(same.classes_added.is_empty() && same.classes_removed.is_empty() && same.changes.is_empty());
+        assert!(same.totals.iter().all(|t| t.delta == 0));
+    }
+
+    #[test]
+    fn comparing_different_roots_warns() {
+        let before = snapshot("before.json", "s1");
+        let mut map = fixture("after.json");
+        map["root"] = json!("Workspace.Map");
+        let after = PlaceStats::from_plugin(map, "s2".into(), "t".into()).unwrap();
+        let comparison = compare(&before, &after, DEFAULT_TOP);
+        assert_eq!(comparison.root_mismatch.as_deref(), Some("s1 counted game, this snapshot counted Workspace.Map"));
+        assert!(render_text(&after, Some(&comparison), DEFAULT_TOP).contains("\nWarning: s1 counted game, this snapshot counted Workspace.Map\n"));
+    }
+
+    #[test]
+    fn text_and_structured_content_carry_the_same_tables() {
+        let (before, after) = (snapshot("before.json", "s1"), snapshot("after.json", "s2"));
+        let comparison = compare(&before, &after, 2);
+        let text = render_text(&after, Some(&comparison), 2);
+        assert!(text.starts_with("Place stats s2 of game at 2026-10-17T10:00:02Z\n\ntotal "), "{text}");
+        assert_eq!(row(&text, "scriptSourceBytes"), ["scriptSourceBytes", "398500"]);
+        assert!(text.contains("\nNot counted (not accessible): CoreGui\n"));
+        assert!(!text.contains("could not be read"));
+        assert!(text.contains("\nTop 2 of 9 classes\n"));
+        assert_eq!(row(&text, "Part"), ["Part", "640"]);
+        let delta = &text[text.find("\nCompared with s1 at 2026-10-17T10:00:02Z\n").unwrap()..];
+        assert_eq!(row(delta, "unions"), ["unions", "64", "0", "-64"]);
+        assert_eq!(row(delta, "meshParts"), ["meshParts", "0", "64", "+64"]);
+        assert!(text.contains("\nClasses added (1)\n") && text.contains("\nClasses removed (3)\n"));
+        assert_eq!(row(&text, "Sparkles"), ["Sparkles", "30", "0", "-30"]);
+        assert!(text.ends_with("\nCount changes (2 of 3, largest first)\nname    before  after  delta\n------  ------  -----  -----\nPart    800     640    -160\nScript  40      12     -28\n"), "{text}");
+
+        let value = structured(&after, Some(&comparison), 2);
+        assert_eq!(value["classes"], json!([{ "class": "Part", "count": 640 }, { "class": "Model", "count": 120 }]));
+        assert_eq!((&value["classesTruncated"], &value["totals"]["meshParts"]), (&json!(true), &json!(64)));
+        assert!(value.get("unreadableScripts").is_none());
+        assert_eq!(value["skipped"], json!(["CoreGui"]));
+        assert_eq!(value["comparison"]["changedClasses"], 3);
+        assert_eq!(value["comparison"]["changes"][0], json!({ "class": "Part", "before": 800, "after": 640, "delta": -160 }));
+        assert_eq!(value["comparison"]["totals"][0], json!({ "total": "instances", "before": 1354, "after": 1160, "delta": -194 }));
+        assert!(value["comparison"].get("rootMismatch").is_none());
+
+        let value = structured(&before, None, DEFAULT_TOP);
+        assert_eq!((&value["classesTruncated"], &value["unreadableScripts"]), (&json!(false), &json!(2)));
+        assert!(value.get("comparison").is_none());
+        assert!(render_text(&before, None, DEFAULT_TOP).contains("\n2 scripts could not be read and are not in scriptSourceBytes\n"));
+    }
+
+    #[test]
+    fn the_store_keeps_the_latest_snapshots() {
+        let mut store = StatsStore::default();
+        for i in 0..MAX_STORED_SNAPSHOTS + 3 {
+            store.insert(snapshot("before.json", &format!("s{i}")));
+        }
+        let ids = store.ids();
+        assert_eq!(ids.len(), MAX_STORED_SNAPSHOTS);
+        assert_eq!((ids[0], *ids.last().unwrap()), ("s3", format!("s{}", MAX_STORED_SNAPSHOTS + 2).as_str()));
+        assert!(store.get("s2").is_none());
+        assert_eq!(store.get("s3").unwrap().id, "s3");
+    }
+}
@@ -0,0 +1,175 @@
+//! Plugin-side configuration reported with the `studio-settings` bridge event, and
+//! the checks the server runs against it.
+//!
+//! Warnings are not stored: they are computed from the latest settings and the
+//! server's current state, so they clear as soon as either changes.
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Effective settings of a plugin client, as it sends them.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PluginSettings {
+    /// Server URL the plugin connects to.
+    pub server_url: String,
+    /// Seconds the plugin waits between polls; 0 means it re-polls as soon as a
+    /// long poll returns.
+    #[serde(default)]
+    pub poll_interval_secs: f64,
+    /// Which log levels the plugin streams as `studio-log` events, or "off".
+    pub log_forwarding: String,
+    /// The playtest bridge Script is injected into ServerScriptService.
+    pub playtest_bridge: bool,
+}
+
+/// What the server expects of a well-configured plugin right now.
+pub struct Expectations {
+    pub port: u16,
+    /// Clients that don't poll for this long are removed.
+    pub stale_after: Duration,
+    /// An MCP client holds a log subscription.
+    pub logs_subscribed: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SettingsWarning {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Host and port of an `http://host:port/...` URL (port 80 when none is given).
+fn host_port(url: &str) -> Option<(&str, u16)> {
+    let rest = url.strip_prefix("http://")?;
+    let authority = rest.split('/').next()?;
+    match authority.rsplit_once(':') {
+        Some((host, port)) if !port.contains(']') => Some((host, port.parse().ok()?)),
+        _ => Some((authority, 80)),
+    }
+}
+
+pub fn validate(settings: &PluginSettings, expect: &Expectations) -> Vec<SettingsWarning> {
+    let mut warnings = Vec::new();
+
+    match host_port(&settings.server_url) {
+        Some((host, port)) => {
+            if port != expect.port {
+                warnings.push(SettingsWarning {
+                    code: "port_mismatch",
+                    message: format!(
+                        "Plugin server URL {} uses port {port}, but this server listens on {}",
+                        settings.server_url, expect.port
+                    ),
+                });
+            }
+            if !matches!(host, "localhost" | "127.0.0.1" | "[::1]") {
+                warnings.push(SettingsWarning {
+                    code: "non_local_host",
+                    message: format!(
+                        "Plugin server URL host '{host}' is not local; the bridge only listens on 127.0.0.1"
+                    ),
+                });
+            }
+        }
+        None => warnings.push(SettingsWarning {
+            code: "invalid_server_url",
+            message: format!("Plugin server URL '{}' is not an http://host:port URL", settings.server_url),
+        }),
+    }
+
+    if settings.poll_interval_secs >= expect.stale_after.as_secs_f64() {
+        warnings.push(SettingsWarning {
+            code: "poll_interval_too_long",
+            message: format!(
+                "Plugin polls every {}s, but clients that don't poll for {}s are dropped as stale",
+                settings.poll_interval_secs,
+                expect.stale_after.as_secs()
+            ),
+        });
+    }
+
+    if expect.logs_subscribed && settings.log_forwarding == "off" {
+        warnings.push(SettingsWarning {
+            code: "log_forwarding_off",
+            message: "A log subscription is active, but the plugin is not forwarding logs (it may have reloaded); call studio-logs_subscribe again".into(),
+        });
+    }
+
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(server_url: &str, poll_interval_secs: f64, log_forwarding: &str) -> PluginSettings {
+        PluginSettings {
+            server_url: server_url.into(),
+            poll_interval_secs,
+            log_forwarding: log_forwarding.into(),
+            playtest_bridge: true,
+        }
+    }
+
+    fn expect(logs_subscribed: bool) -> Expectations {
+        Expectations { port: 3334, stale_after: Duration::from_secs(60), logs_subscribed }
+    }
+
+    fn codes(settings: &PluginSettings, expect: &Expectations) -> Vec<&'static str> {
+        validate(settings, expect).into_iter().map(|w| w.code).collect()
+    }
+
+    #[test]
+    fn a_well_configured_plugin_has_no_warnings() {
+        for url in ["http://localhost:3334", "http://127.0.0.1:3334/", "http://[::1]:3334/bridge"] {
+            assert_eq!(codes(&settings(url, 0.0, "all"), &expect(true)), Vec::<&str>::new(), "{url}");
+        }
+    }
+
+    #[test]
+    fn server_urls_are_checked_for_port_and_host() {
+        let check = |url: &str| codes(&settings(url, 0.0, "all"), &expect(false));
+        assert_eq!(check("http://localhost:3335"), ["port_mismatch"]);
+        // No port is port 80
+        assert_eq!(check("http://localhost"), ["port_mismatch"]);
+        assert_eq!(check("http://192.168.1.20:3334"), ["non_local_host"]);
+        assert_eq!(check("http://studio.example:8080/x"), ["port_mismatch", "non_local_host"]);
+        assert_eq!(check("https://localhost:3334"), ["invalid_server_url"]);
+        assert_eq!(check("localhost:3334"), ["invalid_server_url"]);
+        assert_eq!(check("http://localhost:port"), ["invalid_server_url"]);
+
+        let warning = &validate(&settings("http://localhost:4000", 0.0, "all"), &expect(false))[0];
+        assert_eq!(warning.message, "Plugin server URL http://localhost:4000 uses port 4000, but this server listens on 3334");
+    }
+
+    #[test]
+    fn poll_intervals_at_the_stale_threshold_are_too_long() {
+        let check = |secs: f64| codes(&settings("http://localhost:3334", secs, "all"), &expect(false));
+        assert!(check(59.5).is_empty());
+        assert_eq!(check(60.0), ["poll_interval_too_long"]);
+        assert_eq!(check(90.0), ["poll_interval_too_long"]);
+    }
+
+    #[test]
+    fn log_forwarding_off_only_matters_with_a_subscription() {
+        let off = settings("http://localhost:3334", 0.0, "off");
+        assert!(codes(&off, &expect(false)).is_empty());
+        assert_eq!(codes(&off, &expect(true)), ["log_forwarding_off"]);
+        assert!(codes(&settings("http://localhost:3334", 0.0, "errors"), &expect(true)).is_empty());
+    }
+
+    #[test]
+    fn settings_events_parse_in_the_plugin_shape() {
+        let parsed: PluginSettings = serde_json::from_value(json!({
+            "serverUrl": "http://localhost:3334",
+            "logForwarding": "all",
+            "playtestBridge": false,
+        }))
+        .unwrap();
+        assert_eq!(parsed.poll_interval_secs, 0.0);
+        assert!(!parsed.playtest_bridge);
+        assert_eq!(serde_json::to_value(&parsed).unwrap()["pollIntervalSecs"], 0.0);
+        assert!(serde_json::from_value::<PluginSettings>(json!({ "serverUrl": "http://localhost:3334" })).is_err());
+    }
+}
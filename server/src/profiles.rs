@@ -0,0 +1,333 @@
+//! Named configuration profiles (`YIPPIE_PROFILES`), e.g. a cautious setup and a fast
+//! iteration one, switchable at runtime with `studio-set_profile` or
+//! `POST /admin/profile`. Only the tool, which the MCP client asks the user to
+//! approve, can leave a read-only profile for one that allows writes.
+//!
+//! A profile overrides some of the base settings from the environment; a field the
+//! profile leaves out keeps its base value, not the value of the previous profile.
+//! The active settings are swapped as a whole, and a tool call reads them once when it
+//! is dispatched, so calls already in flight finish with the settings they started with.
+//!
+//! A profile can name MCP clients (`clients`, matched against `clientInfo.name` from
+//! `initialize`). Without a profile chosen at startup, the first profile listing the
+//! client that initializes becomes active.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::log_args::CodeLogMode;
+use crate::mcp_stdio::TOOL_CALL_TIMEOUT;
+
+/// Bounds for a profile's `toolTimeoutSecs`.
+const MIN_TOOL_TIMEOUT_SECS: u64 = 10;
+const MAX_TOOL_TIMEOUT_SECS: u64 = 600;
+
+/// Name that selects the base settings without a profile.
+pub const BASE_PROFILE: &str = "default";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawProfile {
+    minify_code: Option<bool>,
+    log_code: Option<String>,
+    tool_timeout_secs: Option<u64>,
+    read_only: Option<bool>,
+    #[serde(default)]
+    clients: Vec<String>,
+}
+
+/// Overrides of one profile, validated. `None` keeps the base setting.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub minify_code: Option<bool>,
+    pub log_code: Option<CodeLogMode>,
+    pub tool_timeout: Option<Duration>,
+    pub read_only: Option<bool>,
+    /// MCP client names (case-insensitive) this profile is selected for.
+    pub clients: Vec<String>,
+}
+
+impl Profile {
+    /// The overrides as set in `YIPPIE_PROFILES`, for listings.
+    pub fn to_json(&self) -> Value {
+        let mut out = serde_json::Map::new();
+        if let Some(minify_code) = self.minify_code {
+            out.insert("minifyCode".into(), json!(minify_code));
+        }
+        if let Some(log_code) = self.log_code {
+            out.insert("logCode".into(), json!(log_code.as_str()));
+        }
+        if let Some(timeout) = self.tool_timeout {
+            out.insert("toolTimeoutSecs".into(), json!(timeout.as_secs()));
+        }
+        if let Some(read_only) = self.read_only {
+            out.insert("readOnly".into(), json!(read_only));
+        }
+        if !self.clients.is_empty() {
+            out.insert("clients".into(), json!(self.clients));
+        }
+        Value::Object(out)
+    }
+}
+
+/// Parse `YIPPIE_PROFILES`: a JSON object of profile name -> overrides
+/// (`minifyCode`, `logCode`, `toolTimeoutSecs`, `readOnly`, `clients`).
+pub fn parse_profiles(raw: &str) -> Result<BTreeMap<String, Profile>> {
+    let raw: BTreeMap<String, RawProfile> = serde_json::from_str(raw).context(
+        "YIPPIE_PROFILES must be a JSON object of name -> { minifyCode, logCode, toolTimeoutSecs, readOnly, clients }",
+    )?;
+    raw.into_iter()
+        .map(|(name, profile)| {
+            if name.trim().is_empty() || name == BASE_PROFILE {
+                anyhow::bail!("Invalid profile name {name:?} in YIPPIE_PROFILES ('{BASE_PROFILE}' is reserved for the base settings)");
+            }
+            let log_code = match &profile.log_code {
+                Some(v) => Some(CodeLogMode::parse(v).with_context(|| {
+                    format!("Profile '{name}': logCode must be full, truncated or omitted (got {v:?})")
+                })?),
+                None => None,
+            };
+            let tool_timeout = match profile.tool_timeout_secs {
+                Some(secs) if !(MIN_TOOL_TIMEOUT_SECS..=MAX_TOOL_TIMEOUT_SECS).contains(&secs) => anyhow::bail!(
+                    "Profile '{name}': toolTimeoutSecs must be between {MIN_TOOL_TIMEOUT_SECS} and {MAX_TOOL_TIMEOUT_SECS} (got {secs})"
+                ),
+                secs => secs.map(Duration::from_secs),
+            };
+            let parsed = Profile {
+                minify_code: profile.minify_code,
+                log_code,
+                tool_timeout,
+                read_only: profile.read_only,
+                clients: profile.clients,
+            };
+            Ok((name, parsed))
+        })
+        .collect()
+}
+
+/// The settings a profile can change, as currently in effect.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// Active profile; `None` for the base settings.
+    pub profile: Option<String>,
+    /// Default for the per-call `minify` argument of run_script/test_script.
+    pub minify_code: bool,
+    /// How `code` arguments appear in debug logs of tool calls.
+    pub log_code: CodeLogMode,
+    /// Wait for a plugin answer; per-tool waits that extend it build on this.
+    pub tool_timeout: Duration,
+    /// Refuse tools that aren't annotated read-only (studio-status and
+    /// studio-set_profile stay available).
+    pub read_only: bool,
+}
+
+impl Settings {
+    /// The settings from the environment, without a profile.
+    pub fn base(config: &Config) -> Self {
+        Self {
+            profile: None,
+            minify_code: config.minify_code,
+            log_code: config.log_code,
+            tool_timeout: TOOL_CALL_TIMEOUT,
+            read_only: false,
+        }
+    }
+
+    /// The settings with profile `name` (or the base settings for `default`) applied.
+    pub fn resolve(config: &Config, name: &str) -> Result<Self, String> {
+        let base = Self::base(config);
+        if name == BASE_PROFILE {
+            return Ok(base);
+        }
+        let Some(profile) = config.profiles.get(name) else {
+            return Err(unknown_profile(config, name));
+        };
+        Ok(Self {
+            profile: Some(name.to_string()),
+            minify_code: profile.minify_code.unwrap_or(base.minify_code),
+            log_code: profile.log_code.unwrap_or(base.log_code),
+            tool_timeout: profile.tool_timeout.unwrap_or(base.tool_timeout),
+            read_only: profile.read_only.unwrap_or(base.read_only),
+        })
+    }
+
+    /// The settings a server starts with: the profile `YIPPIE_PROFILE` / `--profile`
+    /// chose, or the base settings.
+    pub fn startup(config: &Config) -> Result<Self, String> {
+        Self::resolve(config, config.profile.as_deref().unwrap_or(BASE_PROFILE))
+    }
+
+    pub fn name(&self) -> &str {
+        self.profile.as_deref().unwrap_or(BASE_PROFILE)
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name(),
+            "minifyCode": self.minify_code,
+            "logCode": self.log_code.as_str(),
+            "toolTimeoutSecs": self.tool_timeout.as_secs(),
+            "readOnly": self.read_only,
+        })
+    }
+}
+
+/// The first profile (by name) that lists `client_name`.
+pub fn for_client<'a>(profiles: &'a BTreeMap<String, Profile>, client_name: &str) -> Option<&'a str> {
+    profiles
+        .iter()
+        .find(|(_, profile)| profile.clients.iter().any(|c| c.eq_ignore_ascii_case(client_name)))
+        .map(|(name, _)| name.as_str())
+}
+
+/// `switch_profile` check for switches the user didn't approve (`POST /admin/profile`,
+/// a client's `initialize`): they may not leave a read-only profile for one that
+/// allows writes. Only studio-set_profile, which the MCP client asks about, can.
+pub fn keep_read_only(current: &Settings, next: &Settings) -> Result<(), String> {
+    if current.read_only && !next.read_only {
+        return Err(format!(
+            "The active profile '{}' is read-only and '{}' is not. Leave it with studio-set_profile or restart the server with another YIPPIE_PROFILE.",
+            current.name(),
+            next.name()
+        ));
+    }
+    Ok(())
+}
+
+/// One-line summary for the `initialize` instructions.
+pub fn describe(settings: &Settings) -> String {
+    format!(
+        "{}tool timeout {}s, minify code {}, code in logs {}",
+        if settings.read_only { "read-only, " } else { "" },
+        settings.tool_timeout.as_secs(),
+        if settings.minify_code { "on" } else { "off" },
+        settings.log_code.as_str()
+    )
+}
+
+/// Every selectable profile with its overrides, `default` (the base settings) included.
+pub fn list(profiles: &BTreeMap<String, Profile>) -> Value {
+    let mut out = serde_json::Map::new();
+    out.insert(BASE_PROFILE.into(), json!({}));
+    for (name, profile) in profiles {
+        out.insert(name.clone(), profile.to_json());
+    }
+    Value::Object(out)
+}
+
+fn unknown_profile(config: &Config, name: &str) -> String {
+    let mut names: Vec<&str> = vec![BASE_PROFILE];
+    names.extend(config.profiles.keys().map(String::as_str));
+    format!("Unknown profile '{name}'. Available: {}", names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::SharedState;
+
+    /// Base settings as if set through `YIPPIE_MINIFY_CODE` and `YIPPIE_LOG_CODE`, and
+    /// two profiles over them.
+    fn config() -> Config {
+        let profiles = parse_profiles(
+            r#"{
+                "cautious": { "readOnly": true, "toolTimeoutSecs": 120 },
+                "fast": { "minifyCode": false, "logCode": "full", "toolTimeoutSecs": 10, "clients": ["Cursor"] }
+            }"#,
+        )
+        .unwrap();
+        Config { minify_code: true, log_code: CodeLogMode::Omitted, profiles, ..Config::default() }
+    }
+
+    #[test]
+    fn a_profile_overrides_only_the_fields_it_sets() {
+        let config = config();
+        let base = Settings::resolve(&config, BASE_PROFILE).unwrap();
+        assert_eq!(
+            base.to_json(),
+            json!({
+                "name": "default",
+                "minifyCode": true,
+                "logCode": "omitted",
+                "toolTimeoutSecs": TOOL_CALL_TIMEOUT.as_secs(),
+                "readOnly": false,
+            })
+        );
+        // Fields the profile leaves out keep the environment's value
+        let cautious = Settings::resolve(&config, "cautious").unwrap();
+        assert_eq!(
+            cautious.to_json(),
+            json!({ "name": "cautious", "minifyCode": true, "logCode": "omitted", "toolTimeoutSecs": 120, "readOnly": true })
+        );
+        let fast = Settings::resolve(&config, "fast").unwrap();
+        assert_eq!(
+            fast.to_json(),
+            json!({ "name": "fast", "minifyCode": false, "logCode": "full", "toolTimeoutSecs": 10, "readOnly": false })
+        );
+    }
+
+    #[test]
+    fn unknown_profiles_are_errors() {
+        let mut config = config();
+        let err = Settings::resolve(&config, "turbo").unwrap_err();
+        assert_eq!(err, "Unknown profile 'turbo'. Available: default, cautious, fast");
+        config.profile = Some("turbo".into());
+        assert_eq!(Settings::startup(&config).unwrap_err(), err);
+        config.profile = Some("fast".into());
+        assert_eq!(Settings::startup(&config).unwrap().name(), "fast");
+    }
+
+    #[test]
+    fn malformed_profiles_are_refused() {
+        let err = parse_profiles(r#"{ "default": {} }"#).unwrap_err().to_string();
+        assert!(err.contains("'default' is reserved"), "{err}");
+        let err = parse_profiles(r#"{ "slow": { "toolTimeoutSecs": 3600 } }"#).unwrap_err().to_string();
+        assert!(err.contains("toolTimeoutSecs must be between 10 and 600 (got 3600)"), "{err}");
+        let err = parse_profiles(r#"{ "quiet": { "logCode": "silent" } }"#).unwrap_err().to_string();
+        assert!(err.contains("logCode must be full, truncated or omitted"), "{err}");
+        assert!(parse_profiles(r#"{ "typo": { "readonly": true } }"#).is_err());
+    }
+
+    #[test]
+    fn clients_are_matched_without_case() {
+        let config = config();
+        assert_eq!(for_client(&config.profiles, "cursor"), Some("fast"));
+        assert_eq!(for_client(&config.profiles, "claude-code"), None);
+    }
+
+    #[test]
+    fn a_switch_is_never_seen_half_applied() {
+        let state = SharedState::for_tests(config());
+        let config = config();
+        let whole = [
+            Settings::resolve(&config, "cautious").unwrap().to_json(),
+            Settings::resolve(&config, "fast").unwrap().to_json(),
+        ];
+
+        // A call in flight keeps the settings it was dispatched with
+        state.switch_profile("fast", |_, _| Ok(())).unwrap();
+        let in_flight = state.settings();
+        state.switch_profile("cautious", |_, _| Ok(())).unwrap();
+        assert_eq!(in_flight.to_json(), whole[1]);
+        assert_eq!(state.settings().to_json(), whole[0]);
+
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..2_000 {
+                        let seen = state.settings().to_json();
+                        assert!(whole.contains(&seen), "half-applied settings: {seen}");
+                    }
+                });
+            }
+            for i in 0..1_000 {
+                let name = if i % 2 == 0 { "fast" } else { "cautious" };
+                state.switch_profile(name, |_, _| Ok(())).unwrap();
+            }
+        });
+    }
+}
@@ -0,0 +1,132 @@
+//! Sources of time and randomness behind what the server reports, so deterministic
+//! mode (`YIPPIE_DETERMINISTIC`) can swap them for reproducible ones.
+//!
+//! In deterministic mode the clock is logical: it starts at 2000-01-01T00:00:00Z and
+//! moves one second per MCP message, and measured durations and ages read as zero.
+//! Random bits come from a generator seeded with `YIPPIE_DETERMINISTIC_SEED`.
+//! Liveness checks (stale clients, call timeouts, idle cleanup) keep using real time:
+//! they decide what the server does, not what it reports.
+
+use chrono::{DateTime, Utc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 2000-01-01T00:00:00Z, where the logical clock starts.
+const LOGICAL_EPOCH_SECS: i64 = 946_684_800;
+
+pub trait Clock: Send + Sync {
+    /// Current time, for timestamps shown to clients.
+    fn now(&self) -> DateTime<Utc>;
+    /// Time since `start`, for reported durations.
+    fn elapsed(&self, start: Instant) -> Duration;
+    /// Time since a wall-clock moment the server recorded, for reported ages.
+    fn age(&self, since: DateTime<Utc>) -> Duration;
+    /// Advance a logical clock by one step. Real clocks ignore it.
+    fn tick(&self) {}
+    /// The system clock, read to notice it jumping (`wall_clock.rs`). That is a
+    /// liveness check, so logical clocks keep the real one.
+    fn wall_now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn elapsed(&self, start: Instant) -> Duration {
+        start.elapsed()
+    }
+
+    fn age(&self, since: DateTime<Utc>) -> Duration {
+        (Utc::now() - since).to_std().unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+pub struct LogicalClock {
+    steps: AtomicU64,
+}
+
+impl Clock for LogicalClock {
+    fn now(&self) -> DateTime<Utc> {
+        let steps = self.steps.load(Ordering::Relaxed) as i64;
+        DateTime::from_timestamp(LOGICAL_EPOCH_SECS + steps, 0).unwrap_or_default()
+    }
+
+    fn elapsed(&self, _start: Instant) -> Duration {
+        Duration::ZERO
+    }
+
+    fn age(&self, _since: DateTime<Utc>) -> Duration {
+        Duration::ZERO
+    }
+
+    fn tick(&self) {
+        self.steps.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub trait Rng: Send + Sync {
+    fn next_u64(&self) -> u64;
+}
+
+/// Random bits from the OS, via UUIDv4.
+pub struct OsRng;
+
+impl Rng for OsRng {
+    fn next_u64(&self) -> u64 {
+        uuid::Uuid::new_v4().as_u64_pair().0
+    }
+}
+
+/// SplitMix64: the same seed gives the same sequence.
+pub struct SeededRng {
+    state: AtomicU64,
+}
+
+impl SeededRng {
+    const GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&self) -> u64 {
+        let mut z = self.state.fetch_add(Self::GAMMA, Ordering::Relaxed).wrapping_add(Self::GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+/// The clock and random source a server runs with.
+#[derive(Clone)]
+pub struct Providers {
+    pub clock: Arc<dyn Clock>,
+    pub rng: Arc<dyn Rng>,
+}
+
+impl Providers {
+    /// Real time and OS randomness, or the deterministic pair when a seed is given.
+    pub fn new(deterministic_seed: Option<u64>) -> Self {
+        match deterministic_seed {
+            None => Self {
+                clock: Arc::new(SystemClock),
+                rng: Arc::new(OsRng),
+            },
+            Some(seed) => Self {
+                clock: Arc::new(LogicalClock::default()),
+                rng: Arc::new(SeededRng::new(seed)),
+            },
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//! How far a running playtest has come up, as reported by the playtest bridge in
+//! `studio-playtest_ready` events.
+//!
+//! `playtest_play` returns once Studio has started the test, but the player joins
+//! and the character spawns a few seconds later. The bridge reports each stage;
+//! `studio-playtest_wait_ready` and `playtest_play` with `waitFor` wait for one, and
+//! character tools hold back until the character is there.
+
+use serde_json::Value;
+
+/// Default wait for a readiness stage.
+pub const DEFAULT_READY_TIMEOUT_MS: u64 = 30_000;
+pub const MAX_READY_TIMEOUT_MS: u64 = 120_000;
+
+/// Stages in the order they are reached. The bridge may report an earlier stage
+/// again, e.g. `PlayerAdded` while the character respawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Readiness {
+    ServerStarted,
+    PlayerAdded,
+    CharacterSpawned,
+}
+
+impl Readiness {
+    pub const ALL: [Readiness; 3] = [Readiness::ServerStarted, Readiness::PlayerAdded, Readiness::CharacterSpawned];
+
+    pub fn parse(stage: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == stage)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Readiness::ServerStarted => "serverStarted",
+            Readiness::PlayerAdded => "playerAdded",
+            Readiness::CharacterSpawned => "characterSpawned",
+        }
+    }
+
+    /// Stages past the server need a player, which Run mode doesn't have.
+    pub fn needs_player(self) -> bool {
+        self > Readiness::ServerStarted
+    }
+}
+
+/// Read a stage argument (`level`, `waitFor`), defaulting to `CharacterSpawned`.
+pub fn parse_level(value: Option<&Value>, key: &str) -> Result<Readiness, String> {
+    match value.filter(|v| !v.is_null()) {
+        None => Ok(Readiness::CharacterSpawned),
+        Some(raw) => raw.as_str().and_then(Readiness::parse).ok_or_else(|| {
+            let names: Vec<&str> = Readiness::ALL.iter().map(|r| r.as_str()).collect();
+            format!("'{key}' must be one of: {}", names.join(", "))
+        }),
+    }
+}
+
+/// Read a wait timeout in milliseconds, defaulting to `DEFAULT_READY_TIMEOUT_MS`.
+pub fn parse_timeout(value: Option<&Value>, key: &str) -> Result<u64, String> {
+    match value.filter(|v| !v.is_null()) {
+        None => Ok(DEFAULT_READY_TIMEOUT_MS),
+        Some(raw) => {
+            let ms = raw
+                .as_u64()
+                .filter(|ms| *ms > 0)
+                .ok_or(format!("'{key}' must be a positive number of milliseconds"))?;
+            if ms > MAX_READY_TIMEOUT_MS {
+                return Err(format!("'{key}' {ms} exceeds {MAX_READY_TIMEOUT_MS}"));
+            }
+            Ok(ms)
+        }
+    }
+}
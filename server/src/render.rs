@@ -0,0 +1,190 @@
+//! Flat renderings (aligned text table, CSV) of tabular tool results.
+//!
+//! Also used by the `mcpctl` binary, through the library.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Json,
+    Text,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "csv" => Ok(Self::Csv),
+            other => Err(format!("Unknown format '{other}' (expected json, text or csv)")),
+        }
+    }
+}
+
+/// Render `rows` (JSON objects) as the given columns. `None` for JSON, which callers
+/// print themselves.
+pub fn render(format: OutputFormat, columns: &[&str], rows: &[Value]) -> Option<String> {
+    match format {
+        OutputFormat::Json => None,
+        OutputFormat::Text => Some(to_text_table(columns, rows)),
+        OutputFormat::Csv => Some(to_csv(columns, rows)),
+    }
+}
+
+/// Plain string form of a cell: strings unquoted, null empty, anything else as JSON.
+fn cell(row: &Value, column: &str) -> String {
+    match row.get(column) {
+        None | Some(Value::Null) => String::new(),
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// RFC 4180 CSV: fields with commas, quotes or line breaks are quoted and embedded
+/// quotes doubled. Lines end with CRLF.
+pub fn to_csv(columns: &[&str], rows: &[Value]) -> String {
+    let line = |fields: Vec<String>| {
+        let quoted: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        quoted.join(",") + "\r\n"
+    };
+    let mut out = line(columns.iter().map(|c| c.to_string()).collect());
+    for row in rows {
+        out.push_str(&line(columns.iter().map(|c| cell(row, c)).collect()));
+    }
+    out
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Space-aligned table with a header rule. Line breaks and tabs inside cells are
+/// shown as `\n` / `\t` so every row stays on one line. Widths count characters,
+/// which lines up for most text but not for wide (CJK, emoji) glyphs.
+pub fn to_text_table(columns: &[&str], rows: &[Value]) -> String {
+    let escape = |s: String| s.replace('\r', "\\r").replace('\n', "\\n").replace('\t', "\\t");
+    let header: Vec<String> = columns.iter().map(|c| c.to_string()).collect();
+    let body: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| columns.iter().map(|c| escape(cell(row, c))).collect())
+        .collect();
+
+    let widths: Vec<usize> = (0..columns.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&body)
+                .map(|r| r[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |fields: &[String]| {
+        let mut s = String::new();
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                s.push_str("  ");
+            }
+            s.push_str(field);
+            if i + 1 < fields.len() {
+                s.extend(std::iter::repeat_n(' ', widths[i] - field.chars().count()));
+            }
+        }
+        s.trim_end().to_string() + "\n"
+    };
+
+    let mut out = line(&header);
+    let rule: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+    out.push_str(&line(&rule));
+    for row in &body {
+        out.push_str(&line(row));
+    }
+    if body.is_empty() {
+        out.push_str("(no rows)\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const COLUMNS: [&str; 3] = ["seq", "level", "message"];
+
+    fn rows() -> Vec<Value> {
+        vec![
+            json!({ "seq": 1, "level": "output", "message": "plain" }),
+            json!({ "seq": 2, "level": "warning", "message": "a, b and \"c\"" }),
+            json!({ "seq": 3, "level": "error", "message": "line one\nline two\r\n\tindented" }),
+            json!({ "seq": 4, "level": null, "message": "héllo wörld ✓" }),
+            json!({ "seq": 5.5, "extra": "ignored" }),
+        ]
+    }
+
+    #[test]
+    fn formats_parse_in_any_case() {
+        assert_eq!(OutputFormat::parse("CSV"), Ok(OutputFormat::Csv));
+        assert_eq!(OutputFormat::parse("Text"), Ok(OutputFormat::Text));
+        assert_eq!(OutputFormat::parse("json"), Ok(OutputFormat::Json));
+        assert_eq!(
+            OutputFormat::parse("yaml"),
+            Err("Unknown format 'yaml' (expected json, text or csv)".to_string())
+        );
+        assert_eq!(render(OutputFormat::Json, &COLUMNS, &rows()), None);
+    }
+
+    #[test]
+    fn csv_quotes_commas_quotes_and_line_breaks() {
+        let csv = to_csv(&COLUMNS, &rows());
+        assert_eq!(
+            csv,
+            "seq,level,message\r\n\
+             1,output,plain\r\n\
+             2,warning,\"a, b and \"\"c\"\"\"\r\n\
+             3,error,\"line one\nline two\r\n\tindented\"\r\n\
+             4,,héllo wörld ✓\r\n\
+             5.5,,\r\n"
+        );
+    }
+
+    #[test]
+    fn csv_of_no_rows_is_the_header() {
+        assert_eq!(to_csv(&COLUMNS, &[]), "seq,level,message\r\n");
+        assert_eq!(to_csv(&["a,b"], &[json!({ "a,b": "x" })]), "\"a,b\"\r\nx\r\n");
+    }
+
+    #[test]
+    fn text_table_aligns_columns_and_keeps_rows_on_one_line() {
+        let table = to_text_table(&COLUMNS, &rows());
+        assert_eq!(
+            table,
+            "seq  level    message\n\
+             ---  -------  --------------------------------\n\
+             1    output   plain\n\
+             2    warning  a, b and \"c\"\n\
+             3    error    line one\\nline two\\r\\n\\tindented\n\
+             4             héllo wörld ✓\n\
+             5.5\n"
+        );
+        // Widths count characters, not bytes
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[5].chars().position(|c| c == 'h'), lines[0].find('m'));
+    }
+
+    #[test]
+    fn text_table_of_no_rows_says_so() {
+        assert_eq!(to_text_table(&["a", "bb"], &[]), "a  bb\n-  --\n(no rows)\n");
+    }
+
+    #[test]
+    fn nested_values_render_as_json() {
+        let row = json!({ "seq": [1, 2], "level": { "a": true }, "message": false });
+        assert_eq!(to_csv(&COLUMNS, &[row]), "seq,level,message\r\n\"[1,2]\",\"{\"\"a\"\":true}\",false\r\n");
+    }
+}
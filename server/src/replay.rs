@@ -0,0 +1,65 @@
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Upper bound on steps in one replay.
+pub const MAX_REPLAY_STEPS: usize = 200;
+
+/// Longest wait before a single step.
+pub const MAX_STEP_DELAY_MS: u64 = 60_000;
+
+/// Longest total wait across all steps. The stdio loop handles one request at a time,
+/// so a replay holds up everything else while it runs.
+pub const MAX_TOTAL_DELAY_MS: u64 = 600_000;
+
+/// Largest replay file read from the capture directory.
+pub const MAX_REPLAY_FILE_BYTES: u64 = 1024 * 1024;
+
+/// One recorded tool call. Stored files use the same shape as the `steps` argument.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ReplayStep {
+    pub tool: String,
+    #[serde(default = "empty_arguments")]
+    pub arguments: Value,
+    /// Wait this long before running the step.
+    #[serde(default)]
+    pub delay_ms: u64,
+}
+
+fn empty_arguments() -> Value {
+    Value::Object(Default::default())
+}
+
+/// Parse and bound-check a step list. Accepts either an array of steps or a
+/// `{ "steps": [...] }` object, so a saved file can carry other fields (a description,
+/// say) alongside the steps.
+pub fn parse_steps(value: &Value) -> Result<Vec<ReplayStep>> {
+    let list = match value {
+        Value::Array(_) => value,
+        Value::Object(obj) => obj.get("steps").context("Replay must have a 'steps' array")?,
+        _ => bail!("Replay must be an array of steps or an object with a 'steps' array"),
+    };
+    let steps: Vec<ReplayStep> = serde_json::from_value(list.clone())
+        .context("Each step needs 'tool' and may have 'arguments' (object) and 'delayMs'")?;
+
+    if steps.is_empty() {
+        bail!("Replay has no steps");
+    }
+    if steps.len() > MAX_REPLAY_STEPS {
+        bail!("Replay has {} steps (max {MAX_REPLAY_STEPS})", steps.len());
+    }
+    for (i, step) in steps.iter().enumerate() {
+        if !step.arguments.is_object() {
+            bail!("Step {i}: 'arguments' must be an object");
+        }
+        if step.delay_ms > MAX_STEP_DELAY_MS {
+            bail!("Step {i}: delayMs {} exceeds {MAX_STEP_DELAY_MS}", step.delay_ms);
+        }
+    }
+    let total: u64 = steps.iter().map(|s| s.delay_ms).sum();
+    if total > MAX_TOTAL_DELAY_MS {
+        bail!("Total delay {total} ms exceeds {MAX_TOTAL_DELAY_MS} ms");
+    }
+    Ok(steps)
+}
@@ -0,0 +1,230 @@
+//! Early rejection of oversized MCP requests.
+//!
+//! Agents sometimes paste whole datasets into a `code` argument. Without a bound the
+//! stdio loop would buffer the line, parse it and pass the tree through the dispatcher
+//! before anything noticed the size. Here a line over `YIPPIE_MAX_REQUEST_BYTES` is
+//! skipped while it is being read, keeping only enough of it to find the request id,
+//! and `code` arguments are checked against a per-tool limit before any other
+//! validation.
+
+use serde_json::Value;
+use std::sync::LazyLock;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+pub const DEFAULT_MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+pub const MIN_MAX_REQUEST_BYTES: usize = 64 * 1024;
+
+/// Kept from the start and the end of an oversized line to look for its id.
+const SNIFF_HEAD_BYTES: usize = 64 * 1024;
+const SNIFF_TAIL_BYTES: usize = 256;
+
+/// Largest `code` argument a tool accepts. Luau this large is data, not a script, and
+/// would not make it through the plugin's HTTP request to Studio anyway.
+const SCRIPT_CODE_LIMIT: usize = 4 * 1024 * 1024;
+
+/// One line of stdin.
+pub enum Line {
+    Complete(Vec<u8>),
+    /// Over the limit and discarded. `id` is the request id, if it could be found.
+    Oversized { bytes: usize, id: Value },
+}
+
+/// Newline-delimited reader that stops buffering a line once it passes `limit` bytes.
+pub struct BoundedLines<R> {
+    reader: R,
+    limit: usize,
+}
+
+impl<R: AsyncBufRead + Unpin> BoundedLines<R> {
+    pub fn new(reader: R, limit: usize) -> Self {
+        Self { reader, limit }
+    }
+
+    /// The next line without its newline; `None` at end of input. Not cancel safe: a
+    /// line being read when the future is dropped is lost.
+    pub async fn next_line(&mut self) -> std::io::Result<Option<Line>> {
+        let mut line = Vec::new();
+        let mut tail = Vec::new();
+        let mut bytes = 0usize;
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                if bytes == 0 {
+                    return Ok(None);
+                }
+                break;
+            }
+            let newline = available.iter().position(|&b| b == b'\n');
+            let chunk = &available[..newline.unwrap_or(available.len())];
+            bytes += chunk.len();
+            if bytes <= self.limit {
+                line.extend_from_slice(chunk);
+            } else {
+                if line.len() < SNIFF_HEAD_BYTES {
+                    let room = SNIFF_HEAD_BYTES - line.len();
+                    line.extend_from_slice(&chunk[..room.min(chunk.len())]);
+                }
+                line.truncate(SNIFF_HEAD_BYTES);
+                tail.extend_from_slice(&chunk[chunk.len().saturating_sub(SNIFF_TAIL_BYTES)..]);
+                let excess = tail.len().saturating_sub(SNIFF_TAIL_BYTES);
+                tail.drain(..excess);
+            }
+            let consumed = newline.map_or(available.len(), |i| i + 1);
+            self.reader.consume(consumed);
+            if newline.is_some() {
+                break;
+            }
+        }
+        if bytes > self.limit {
+            let id = sniff_id(&line, &tail);
+            return Ok(Some(Line::Oversized { bytes, id }));
+        }
+        Ok(Some(Line::Complete(line)))
+    }
+}
+
+static ID_FIELD: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#""id"\s*:\s*(-?\d+|"(?:[^"\\]|\\.)*")"#).unwrap());
+
+/// Best guess at the top-level id of a JSON-RPC request seen only in part: an `"id"`
+/// key before `"params"` starts, or one closing the object at the very end. Quotes
+/// inside strings are escaped, so neither matches string content. `Null` if neither
+/// is there.
+fn sniff_id(head: &[u8], tail: &[u8]) -> Value {
+    let head = String::from_utf8_lossy(head);
+    let before_params = head.find(r#""params""#).map_or(&head[..], |i| &head[..i]);
+    let tail = String::from_utf8_lossy(tail);
+    let closing = ID_FIELD
+        .captures_iter(&tail)
+        .last()
+        .filter(|c| tail[c.get(0).unwrap().end()..].trim() == "}");
+    ID_FIELD
+        .captures(before_params)
+        .or(closing)
+        .and_then(|c| serde_json::from_str(&c[1]).ok())
+        .unwrap_or(Value::Null)
+}
+
+/// Message of the -32602 error answering a line over the limit.
+pub fn oversized_message(bytes: usize, limit: usize) -> String {
+    format!(
+        "Request too large: {bytes} bytes, the limit is {limit} (YIPPIE_MAX_REQUEST_BYTES). \
+         Send large data in several smaller calls."
+    )
+}
+
+fn code_limit(tool_name: &str) -> Option<usize> {
+    match tool_name {
+        "studio-run_script" | "studio-test_script" | "studio-test_matrix" => Some(SCRIPT_CODE_LIMIT),
+        _ => None,
+    }
+}
+
+/// Refuse a `code` argument over the tool's limit. Only looks at the string's length.
+pub fn check_code_size(tool_name: &str, arguments: &Value) -> Result<(), String> {
+    let Some(limit) = code_limit(tool_name) else {
+        return Ok(());
+    };
+    match arguments.get("code").and_then(|v| v.as_str()) {
+        Some(code) if code.len() > limit => Err(format!(
+            "'code' is {} bytes; {tool_name} accepts at most {limit}. Move data out of the \
+             script, e.g. into a ModuleScript built in smaller calls.",
+            code.len()
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::io::BufReader;
+
+    /// Every line of `input`, read through a small buffer so lines span many fills.
+    async fn read_all(input: &[u8], limit: usize) -> Vec<Line> {
+        let mut lines = BoundedLines::new(BufReader::with_capacity(7, input), limit);
+        let mut read = Vec::new();
+        while let Some(line) = lines.next_line().await.unwrap() {
+            read.push(line);
+        }
+        read
+    }
+
+    fn complete(line: &Line) -> &str {
+        match line {
+            Line::Complete(bytes) => std::str::from_utf8(bytes).unwrap(),
+            Line::Oversized { bytes, .. } => panic!("line of {bytes} bytes was refused"),
+        }
+    }
+
+    fn oversized(line: &Line) -> (usize, &Value) {
+        match line {
+            Line::Oversized { bytes, id } => (*bytes, id),
+            Line::Complete(bytes) => panic!("line of {} bytes was accepted", bytes.len()),
+        }
+    }
+
+    #[tokio::test]
+    async fn lines_up_to_the_limit_are_read_whole() {
+        let lines = read_all(b"{\"id\":1}\n\n0123456789\nlast without newline", 20).await;
+        let read: Vec<&str> = lines.iter().map(complete).collect();
+        assert_eq!(read, ["{\"id\":1}", "", "0123456789", "last without newline"]);
+        assert!(read_all(b"", 20).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_line_over_the_limit_is_refused_and_reading_goes_on() {
+        let big = format!(r#"{{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{{"arguments":{{"code":"{}"}}}}}}"#, "x".repeat(500));
+        let input = format!("{big}\n{{\"id\":8}}\n");
+        let lines = read_all(input.as_bytes(), 100).await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(oversized(&lines[0]), (big.len(), &json!(7)));
+        assert_eq!(complete(&lines[1]), "{\"id\":8}");
+
+        // One byte over is over
+        let lines = read_all(b"0123456789\n01234567890\n", 10).await;
+        assert_eq!(complete(&lines[0]), "0123456789");
+        assert_eq!(oversized(&lines[1]).0, 11);
+    }
+
+    #[tokio::test]
+    async fn the_id_of_a_refused_line_is_found_at_either_end() {
+        let code = "y".repeat(SNIFF_HEAD_BYTES * 2);
+        let id_of = |line: String| async move {
+            let lines = read_all(line.as_bytes(), MIN_MAX_REQUEST_BYTES).await;
+            oversized(&lines[0]).1.clone()
+        };
+
+        // Ids before the params, whether numbers or strings
+        let before = format!(r#"{{"id": "call-\"1\"", "method":"tools/call","params":{{"arguments":{{"code":"{code}"}}}}}}"#);
+        assert_eq!(id_of(before).await, json!("call-\"1\""));
+        // After the params, where only the closing end is kept
+        let after = format!(r#"{{"method":"tools/call","params":{{"arguments":{{"code":"{code}"}}}},"id":-3}}"#);
+        assert_eq!(id_of(after).await, json!(-3));
+        // An "id" inside the arguments is not the request's
+        let nested = format!(r#"{{"method":"tools/call","params":{{"arguments":{{"id":5,"code":"{code}"}}}}}}"#);
+        assert_eq!(id_of(nested).await, Value::Null);
+        let in_code = format!(r#"{{"method":"tools/call","params":{{"arguments":{{"code":"{code}\"id\":9"}}}}}}"#);
+        assert_eq!(id_of(in_code).await, Value::Null);
+    }
+
+    #[test]
+    fn oversized_messages_name_the_size_and_the_setting() {
+        let message = oversized_message(50_000_000, DEFAULT_MAX_REQUEST_BYTES);
+        assert!(message.starts_with("Request too large: 50000000 bytes, the limit is 16777216 (YIPPIE_MAX_REQUEST_BYTES)."), "{message}");
+    }
+
+    #[test]
+    fn code_is_limited_for_script_tools_only() {
+        let code = |len: usize| json!({ "code": "x".repeat(len) });
+        for tool in ["studio-run_script", "studio-test_script", "studio-test_matrix"] {
+            assert!(check_code_size(tool, &code(SCRIPT_CODE_LIMIT)).is_ok());
+            let message = check_code_size(tool, &code(SCRIPT_CODE_LIMIT + 1)).unwrap_err();
+            assert!(message.starts_with(&format!("'code' is {} bytes; {tool} accepts at most {SCRIPT_CODE_LIMIT}.", SCRIPT_CODE_LIMIT + 1)), "{message}");
+        }
+        assert!(check_code_size("studio-var_set", &code(SCRIPT_CODE_LIMIT + 1)).is_ok());
+        assert!(check_code_size("studio-run_script", &json!({ "code": 5 })).is_ok());
+        assert!(check_code_size("studio-run_script", &Value::Null).is_ok());
+    }
+}
@@ -0,0 +1,180 @@
+//! Small LRU cache of tool results for tools whose annotations mark them cacheable.
+//!
+//! Entries are keyed by tool name and a hash of the arguments and expire after
+//! `YIPPIE_CACHE_TTL_SECS`. Any call to a tool that isn't read-only clears the whole
+//! cache, as does a plugin registering or disconnecting.
+
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::{Duration, Instant};
+
+struct Entry {
+    result: Value,
+    stored_at: Instant,
+    /// Tick of the last lookup or insert, for LRU eviction.
+    last_used: u64,
+}
+
+pub struct ResponseCache {
+    entries: HashMap<(String, u64), Entry>,
+    capacity: usize,
+    ttl: Duration,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    invalidations: u64,
+}
+
+/// Arguments are hashed in their serialized form. serde_json objects keep keys
+/// sorted, so argument order doesn't matter.
+fn key(tool: &str, arguments: &Value) -> (String, u64) {
+    let mut hasher = DefaultHasher::new();
+    arguments.to_string().hash(&mut hasher);
+    (tool.to_string(), hasher.finish())
+}
+
+impl ResponseCache {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            entries: HashMap::with_capacity(capacity),
+            capacity,
+            ttl,
+            tick: 0,
+            hits: 0,
+            misses: 0,
+            invalidations: 0,
+        }
+    }
+
+    /// The cached result, if present and not expired.
+    pub fn get(&mut self, tool: &str, arguments: &Value) -> Option<Value> {
+        self.tick += 1;
+        let key = key(tool, arguments);
+        match self.entries.get_mut(&key) {
+            Some(entry) if entry.stored_at.elapsed() < self.ttl => {
+                entry.last_used = self.tick;
+                self.hits += 1;
+                Some(entry.result.clone())
+            }
+            Some(_) => {
+                self.entries.remove(&key);
+                self.misses += 1;
+                None
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(&mut self, tool: &str, arguments: &Value, result: Value) {
+        self.tick += 1;
+        let key = key(tool, arguments);
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let ttl = self.ttl;
+            self.entries.retain(|_, e| e.stored_at.elapsed() < ttl);
+            if self.entries.len() >= self.capacity {
+                let oldest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, e)| e.last_used)
+                    .map(|(k, _)| k.clone());
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(key, Entry {
+            result,
+            stored_at: Instant::now(),
+            last_used: self.tick,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        if !self.entries.is_empty() {
+            self.entries.clear();
+            self.invalidations += 1;
+        }
+    }
+
+    /// Size and hit counts, for studio-status.
+    pub fn status(&self) -> Value {
+        json!({
+            "entries": self.entries.len(),
+            "capacity": self.capacity,
+            "ttlSecs": self.ttl.as_secs(),
+            "hits": self.hits,
+            "misses": self.misses,
+            "invalidations": self.invalidations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn the_least_recently_used_entry_is_evicted_at_capacity() {
+        let mut cache = ResponseCache::new(2, LONG);
+        cache.insert("studio-get_version", &json!({}), json!("version"));
+        cache.insert("studio-is_tweenable", &json!({ "className": "Part" }), json!("part"));
+        // A lookup makes the older entry the recent one
+        assert_eq!(cache.get("studio-get_version", &json!({})), Some(json!("version")));
+
+        cache.insert("studio-is_tweenable", &json!({ "className": "Frame" }), json!("frame"));
+        assert_eq!(cache.get("studio-is_tweenable", &json!({ "className": "Part" })), None);
+        assert_eq!(cache.get("studio-get_version", &json!({})), Some(json!("version")));
+        assert_eq!(cache.get("studio-is_tweenable", &json!({ "className": "Frame" })), Some(json!("frame")));
+
+        // Replacing an entry at capacity evicts nothing
+        cache.insert("studio-get_version", &json!({}), json!("newer"));
+        assert_eq!(cache.status()["entries"], 2);
+        assert_eq!(cache.get("studio-is_tweenable", &json!({ "className": "Frame" })), Some(json!("frame")));
+        assert_eq!(cache.get("studio-get_version", &json!({})), Some(json!("newer")));
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let mut cache = ResponseCache::new(4, Duration::ZERO);
+        cache.insert("studio-get_version", &json!({}), json!("version"));
+        assert_eq!(cache.get("studio-get_version", &json!({})), None);
+        let status = cache.status();
+        assert_eq!((&status["entries"], &status["hits"], &status["misses"]), (&json!(0), &json!(0), &json!(1)));
+
+        let mut cache = ResponseCache::new(4, LONG);
+        cache.insert("studio-get_version", &json!({}), json!("version"));
+        assert_eq!(cache.get("studio-get_version", &json!({})), Some(json!("version")));
+    }
+
+    #[test]
+    fn keys_ignore_argument_order_but_not_values_or_the_tool() {
+        let mut cache = ResponseCache::new(4, LONG);
+        let arguments: Value = serde_json::from_str(r#"{ "property": "Size", "className": "Part" }"#).unwrap();
+        cache.insert("studio-is_tweenable", &arguments, json!("size"));
+
+        let reordered: Value = serde_json::from_str(r#"{ "className": "Part", "property": "Size" }"#).unwrap();
+        assert_eq!(cache.get("studio-is_tweenable", &reordered), Some(json!("size")));
+        assert_eq!(cache.get("studio-is_tweenable", &json!({ "className": "Part", "property": "Color" })), None);
+        assert_eq!(cache.get("studio-is_tweenable", &json!({ "className": "Part", "property": "Size", "x": 1 })), None);
+        assert_eq!(cache.get("studio-get_version", &reordered), None);
+        let status = cache.status();
+        assert_eq!((&status["hits"], &status["misses"]), (&json!(1), &json!(3)));
+    }
+
+    #[test]
+    fn clearing_counts_an_invalidation_only_when_something_was_dropped() {
+        let mut cache = ResponseCache::new(4, LONG);
+        cache.clear();
+        assert_eq!(cache.status()["invalidations"], 0);
+        cache.insert("studio-get_version", &json!({}), json!("version"));
+        cache.clear();
+        assert_eq!(cache.get("studio-get_version", &json!({})), None);
+        assert_eq!(cache.status()["invalidations"], 1);
+    }
+}
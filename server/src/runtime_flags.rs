@@ -0,0 +1,135 @@
+//! The whitelist behind `studio-set_runtime_flag`: service properties that are safe to
+//! flip while testing and can always be put back.
+//!
+//! Anything not listed here is refused before the plugin sees it. The plugin applies
+//! changes through the same `studio-set_properties` handler that test matrix overrides
+//! use; the server remembers each flag's value from before its first change so it can
+//! restore it.
+
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy)]
+pub enum FlagKind {
+    Bool,
+    Number { min: f64, max: f64 },
+    /// Enum item names.
+    Enum(&'static [&'static str]),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeFlag {
+    /// `Service.Property`, as the tool takes it.
+    pub name: &'static str,
+    pub kind: FlagKind,
+    pub description: &'static str,
+}
+
+impl RuntimeFlag {
+    pub fn service(&self) -> &'static str {
+        self.name.split_once('.').map_or(self.name, |(service, _)| service)
+    }
+
+    pub fn property(&self) -> &'static str {
+        self.name.split_once('.').map_or(self.name, |(_, property)| property)
+    }
+
+    /// Check `value` against the flag's type and range.
+    pub fn validate(&self, value: &Value) -> Result<(), String> {
+        let name = self.name;
+        match self.kind {
+            FlagKind::Bool if value.is_boolean() => Ok(()),
+            FlagKind::Bool => Err(format!("{name} takes true or false")),
+            FlagKind::Number { min, max } => match value.as_f64() {
+                Some(n) if (min..=max).contains(&n) => Ok(()),
+                _ => Err(format!("{name} takes a number from {min} to {max}")),
+            },
+            FlagKind::Enum(items) => match value.as_str() {
+                Some(item) if items.contains(&item) => Ok(()),
+                _ => Err(format!("{name} takes one of {}", items.join(", "))),
+            },
+        }
+    }
+
+    fn allowed(&self) -> Value {
+        match self.kind {
+            FlagKind::Bool => json!("boolean"),
+            FlagKind::Number { min, max } => json!({ "min": min, "max": max }),
+            FlagKind::Enum(items) => json!(items),
+        }
+    }
+}
+
+pub const FLAGS: &[RuntimeFlag] = &[
+    RuntimeFlag {
+        name: "Workspace.StreamingEnabled",
+        kind: FlagKind::Bool,
+        description: "Instance streaming for the next playtest",
+    },
+    RuntimeFlag {
+        name: "Workspace.SignalBehavior",
+        kind: FlagKind::Enum(&["Default", "Deferred", "Immediate", "AncestryDeferred"]),
+        description: "When event handlers run relative to the code that fired them",
+    },
+    RuntimeFlag {
+        name: "Workspace.Gravity",
+        kind: FlagKind::Number { min: 0.0, max: 1000.0 },
+        description: "Gravity in studs/s²",
+    },
+    RuntimeFlag {
+        name: "Workspace.FallenPartsDestroyHeight",
+        kind: FlagKind::Number { min: -50_000.0, max: 0.0 },
+        description: "Height below which falling parts are destroyed",
+    },
+    RuntimeFlag {
+        name: "Players.CharacterAutoLoads",
+        kind: FlagKind::Bool,
+        description: "Spawn characters automatically",
+    },
+    RuntimeFlag {
+        name: "Players.RespawnTime",
+        kind: FlagKind::Number { min: 0.0, max: 60.0 },
+        description: "Seconds before a dead character respawns",
+    },
+    RuntimeFlag {
+        name: "Lighting.ClockTime",
+        kind: FlagKind::Number { min: 0.0, max: 24.0 },
+        description: "Time of day in hours",
+    },
+    RuntimeFlag {
+        name: "Lighting.GlobalShadows",
+        kind: FlagKind::Bool,
+        description: "Shadows from the sun and moon",
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static RuntimeFlag> {
+    FLAGS.iter().find(|flag| flag.name == name)
+}
+
+/// The error for a flag that isn't whitelisted.
+pub fn not_allowed(name: &str) -> String {
+    let names: Vec<&str> = FLAGS.iter().map(|flag| flag.name).collect();
+    format!(
+        "'{name}' is not on the runtime flag whitelist. Allowed flags: {}. Other settings can't be changed with this tool.",
+        names.join(", ")
+    )
+}
+
+/// The whitelist as returned by `list`.
+pub fn list() -> Value {
+    Value::Array(
+        FLAGS
+            .iter()
+            .map(|flag| json!({ "flag": flag.name, "allowed": flag.allowed(), "description": flag.description }))
+            .collect(),
+    )
+}
+
+/// `{ service: { property: value } }` for `studio-set_properties`.
+pub fn overrides<'a>(changes: impl IntoIterator<Item = (&'a RuntimeFlag, Value)>) -> Value {
+    let mut overrides = json!({});
+    for (flag, value) in changes {
+        overrides[flag.service()][flag.property()] = value;
+    }
+    json!({ "overrides": overrides })
+}
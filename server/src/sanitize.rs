@@ -0,0 +1,291 @@
+//! Cleaning of text that came from Studio before it reaches MCP clients or exports.
+//!
+//! Some Luau libraries print ANSI colour escapes and other control characters, which
+//! garble the text content of several clients. Log messages are cleaned when they are
+//! stored and tool results before they are sent (unless the call asks for raw text):
+//!
+//! - ANSI escape sequences (CSI, OSC, two-character escapes, also their 8-bit C1
+//!   forms) are removed. With `YIPPIE_ANSI_MARKERS`, foreground colours become plain
+//!   markers instead, e.g. `[red]failed[/red]`.
+//! - Other C0 and C1 control characters and DEL are removed; newline and tab stay.
+//!
+//! Invalid UTF-8 in bridge bodies is replaced with U+FFFD before parsing
+//! (`bridge_http.rs`), so this module only ever sees valid strings.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+const ESC: char = '\u{1b}';
+/// 8-bit forms of `ESC [`, `ESC ]` and the string terminator `ESC \`.
+const C1_CSI: char = '\u{9b}';
+const C1_OSC: char = '\u{9d}';
+const C1_ST: char = '\u{9c}';
+const BEL: char = '\u{7}';
+
+/// SGR foreground colours 30–37 (and 90–97, their bright forms), as marker names.
+const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sanitizer {
+    /// Turn SGR foreground colours into `[color]...[/color]` markers instead of
+    /// dropping them.
+    pub ansi_markers: bool,
+}
+
+impl Sanitizer {
+    pub fn new(ansi_markers: bool) -> Self {
+        Self { ansi_markers }
+    }
+
+    /// `text` without escape sequences and control characters. Borrows when there
+    /// is nothing to remove.
+    pub fn text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if !text.chars().any(needs_cleaning) {
+            return Cow::Borrowed(text);
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut open: Option<&'static str> = None;
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\n' | '\t' => out.push(c),
+                ESC => match chars.peek().copied() {
+                    Some('[') => {
+                        chars.next();
+                        self.csi(&mut chars, &mut open, &mut out);
+                    }
+                    Some(']') => {
+                        chars.next();
+                        skip_osc(&mut chars);
+                    }
+                    // Character set designations take one more character
+                    Some('(' | ')' | '*' | '+') => {
+                        chars.next();
+                        chars.next();
+                    }
+                    // Other two-character escapes end in one ASCII character; text
+                    // right after a lone ESC is kept
+                    Some('\u{20}'..='\u{7e}') => {
+                        chars.next();
+                    }
+                    _ => {}
+                },
+                C1_CSI => self.csi(&mut chars, &mut open, &mut out),
+                C1_OSC => skip_osc(&mut chars),
+                c if c.is_control() => {}
+                c => out.push(c),
+            }
+        }
+        if let Some(color) = open {
+            close_marker(color, &mut out);
+        }
+        Cow::Owned(out)
+    }
+
+    /// Clean every string in `value`, keys included. Returns whether anything changed.
+    pub fn value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(s) => match self.text(s) {
+                Cow::Owned(cleaned) => {
+                    *s = cleaned;
+                    true
+                }
+                Cow::Borrowed(_) => false,
+            },
+            Value::Array(items) => items.iter_mut().fold(false, |changed, item| self.value(item) | changed),
+            Value::Object(map) => {
+                let mut changed = false;
+                if map.keys().any(|k| k.chars().any(needs_cleaning)) {
+                    let entries = std::mem::take(map);
+                    for (key, item) in entries {
+                        map.insert(self.text(&key).into_owned(), item);
+                    }
+                    changed = true;
+                }
+                map.values_mut().fold(changed, |changed, item| self.value(item) | changed)
+            }
+            _ => false,
+        }
+    }
+
+    /// Read a CSI sequence after its introducer. Only SGR (`m`) sequences can leave
+    /// anything behind, and only in marker mode.
+    fn csi(
+        &self,
+        chars: &mut std::iter::Peekable<std::str::Chars<'_>>,
+        open: &mut Option<&'static str>,
+        out: &mut String,
+    ) {
+        let mut params = String::new();
+        while let Some(&c) = chars.peek() {
+            match c {
+                // Parameter and intermediate bytes
+                '\u{20}'..='\u{3f}' => {
+                    params.push(c);
+                    chars.next();
+                }
+                // Final byte
+                '\u{40}'..='\u{7e}' => {
+                    chars.next();
+                    if c == 'm' && self.ansi_markers {
+                        apply_sgr(&params, open, out);
+                    }
+                    return;
+                }
+                // Cut off; what follows is text again
+                _ => return,
+            }
+        }
+    }
+}
+
+fn needs_cleaning(c: char) -> bool {
+    c.is_control() && c != '\n' && c != '\t'
+}
+
+/// Skip an OSC sequence (e.g. a terminal title or hyperlink) up to its terminator.
+fn skip_osc(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) {
+    while let Some(c) = chars.next() {
+        match c {
+            BEL | C1_ST => return,
+            ESC => {
+                if chars.peek() == Some(&'\\') {
+                    chars.next();
+                }
+                return;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Open and close colour markers for an SGR parameter list. Attributes other than
+/// the foreground colour are dropped.
+fn apply_sgr(params: &str, open: &mut Option<&'static str>, out: &mut String) {
+    let codes: Vec<u16> = params.split([';', ':']).map(|p| p.parse().unwrap_or(0)).collect();
+    let mut i = 0;
+    while i < codes.len() {
+        match codes[i] {
+            0 | 39 => {
+                if let Some(color) = open.take() {
+                    close_marker(color, out);
+                }
+            }
+            code @ (30..=37 | 90..=97) => {
+                let color = COLORS[usize::from(code % 10)];
+                if *open != Some(color) {
+                    if let Some(previous) = open.take() {
+                        close_marker(previous, out);
+                    }
+                    out.push('[');
+                    out.push_str(color);
+                    out.push(']');
+                    *open = Some(color);
+                }
+            }
+            // 256-colour and RGB colours carry their own parameters
+            38 | 48 => match codes.get(i + 1) {
+                Some(5) => i += 2,
+                Some(2) => i += 4,
+                _ => {}
+            },
+            _ => {}
+        }
+        i += 1;
+    }
+}
+
+fn close_marker(color: &str, out: &mut String) {
+    out.push_str("[/");
+    out.push_str(color);
+    out.push(']');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn clean(text: &str) -> String {
+        Sanitizer::new(false).text(text).into_owned()
+    }
+
+    fn marked(text: &str) -> String {
+        Sanitizer::new(true).text(text).into_owned()
+    }
+
+    #[test]
+    fn plain_text_is_borrowed() {
+        for text in ["", "hello", "line\nnext\ttab", "héllo wörld 中文 🎮"] {
+            assert!(matches!(Sanitizer::default().text(text), Cow::Borrowed(_)), "{text:?}");
+        }
+    }
+
+    #[test]
+    fn nasty_inputs_are_cleaned() {
+        for (input, expected) in [
+            // Complete sequences
+            ("\x1b[31mred\x1b[0m", "red"),
+            ("\x1b[1;38;2;255;0;0mrgb\x1b[m", "rgb"),
+            ("\x1b]0;title\x07text", "text"),
+            ("\x1b]8;;https://x.test\x1b\\link\x1b]8;;\x1b\\", "link"),
+            ("\x1b(Bascii", "ascii"),
+            ("\x1bMup", "up"),
+            // Truncated: a CSI cut off by text or the end, an OSC without terminator
+            ("cut\x1b[31", "cut"),
+            ("cut\x1b[", "cut"),
+            ("cut\x1b", "cut"),
+            ("cut\x1b(", "cut"),
+            ("a\x1b[12\nb", "a\nb"),
+            ("before\x1b]0;never terminated", "before"),
+            ("before\u{9d}0;never terminated", "before"),
+            // 8-bit C1 forms and other C1 controls
+            ("\u{9b}32mgreen\u{9b}0m", "green"),
+            ("\u{9d}0;title\u{9c}after", "after"),
+            ("next\u{85}line\u{8d}\u{9f}", "nextline"),
+            // NULs, other C0 controls and DEL
+            ("a\0b\0", "ab"),
+            ("bell\x07 back\x08 del\x7f cr\r\n", "bell back del cr\n"),
+            // Newlines and tabs stay, also around sequences
+            ("\tindent\x1b[2K\n\x1b[1Anext\t", "\tindent\nnext\t"),
+            // Multibyte UTF-8 right before and after sequences is kept whole
+            ("é\x1b[31mñ\x1b[0m中", "éñ中"),
+            ("\x1b[31m🎮\x1b[0m", "🎮"),
+            ("\x1b[3中", "中"),
+            ("\x1bé", "é"),
+            ("\u{9b}🎮", "🎮"),
+            ("\x1b]0;tïtle 🎮\x07ü", "ü"),
+        ] {
+            assert_eq!(clean(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn markers_replace_foreground_colours() {
+        for (input, expected) in [
+            ("\x1b[31mfailed\x1b[0m ok", "[red]failed[/red] ok"),
+            ("\x1b[1;92mbright\x1b[39m", "[green]bright[/green]"),
+            ("\x1b[31ma\x1b[34mb\x1b[0m", "[red]a[/red][blue]b[/blue]"),
+            ("\x1b[31mred\x1b[31m still", "[red]red still[/red]"),
+            // Unclosed colours are closed at the end; truncated SGR does nothing
+            ("\x1b[33mwarn", "[yellow]warn[/yellow]"),
+            ("plain\x1b[31", "plain"),
+            ("\x1b[38;5;196mindexed\x1b[0m", "indexed"),
+            ("\u{9b}36mc1\u{9b}0m", "[cyan]c1[/cyan]"),
+            ("\x1b[35mé中\0\x1b[0m", "[magenta]é中[/magenta]"),
+        ] {
+            assert_eq!(marked(input), expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn values_are_cleaned_keys_included() {
+        let mut value = json!({
+            "\x1b[1mkey\x1b[0m": ["a\x1b[31mb", 1, null, { "nested": "c\0d" }],
+            "clean": "as is",
+        });
+        assert!(Sanitizer::default().value(&mut value));
+        assert_eq!(value, json!({ "key": ["ab", 1, null, { "nested": "cd" }], "clean": "as is" }));
+        assert!(!Sanitizer::default().value(&mut value));
+    }
+}
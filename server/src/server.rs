@@ -0,0 +1,355 @@
+//! Starting the server from code. [`ServerBuilder`] sets up the shared state and the
+//! background tasks the binary runs (capture folder heartbeat, Studio setting restore,
+//! alerting, idle cleanup, mock Studio), and `spawn` also serves the HTTP bridge.
+//! The stdio loop is left to the caller: the binary runs `serve_stdio`, an embedding
+//! program calls tools through a [`Dispatcher`].
+
+use anyhow::Context;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::task::{AbortHandle, JoinHandle};
+
+use crate::alerts;
+use crate::bridge_http;
+use crate::build_info::{BuildInfo, LaunchInfo};
+use crate::capture_peers;
+use crate::config::{AuthToken, Config};
+use crate::idle;
+use crate::log_archive::LogArchive;
+use crate::mcp_stdio::{DispatchError, Dispatcher};
+use crate::mock_studio::{self, Scenario};
+use crate::profiles::Settings;
+use crate::redact::Redactor;
+use crate::state::SharedState;
+use crate::studio_settings;
+use crate::wall_clock;
+use crate::types::McpToolDef;
+
+/// How long a shutdown requested over `POST /shutdown` lets plugin calls in flight
+/// finish before failing them.
+pub const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Configures and starts a server.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use roblox_studio_yippieblox_mcp_server::ServerBuilder;
+///
+/// let server = ServerBuilder::new().port(3334).token("change-me").spawn().await?;
+/// println!("Studio plugin connects to http://{}", server.local_addr());
+/// server.stop().await;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerBuilder {
+    config: Config,
+    mock_studio: bool,
+    mock_scenario: Option<PathBuf>,
+    status_report: bool,
+}
+
+impl Default for ServerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ServerBuilder {
+    /// Start from `Config::default()`, ignoring `YIPPIE_*` environment variables.
+    pub fn new() -> Self {
+        Self::from_config(Config::default())
+    }
+
+    /// Start from the `YIPPIE_*` environment variables, like the binary.
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(Self::from_config(Config::from_env()?))
+    }
+
+    pub fn from_config(config: Config) -> Self {
+        Self {
+            config,
+            mock_studio: false,
+            mock_scenario: None,
+            status_report: false,
+        }
+    }
+
+    /// Bridge port on 127.0.0.1. 0 picks a free port; see [`ServerHandle::local_addr`].
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.port = port;
+        self
+    }
+
+    /// Require this Bearer token from the Studio plugin.
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.config.token = Some(AuthToken::new(token));
+        self
+    }
+
+    /// Where captures, the capture index and the log archive are kept.
+    pub fn capture_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.capture_dir = dir.into();
+        self
+    }
+
+    /// Start with this configuration profile (see `YIPPIE_PROFILES`).
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.config.profile = Some(name.into());
+        self
+    }
+
+    /// Answer tool calls from a simulated plugin instead of Studio.
+    pub fn mock_studio(mut self, enabled: bool) -> Self {
+        self.mock_studio = enabled;
+        self
+    }
+
+    /// Canned responses for the simulated plugin (a `--mock-scenario` file). Enables
+    /// mock Studio.
+    pub fn mock_scenario(mut self, path: impl Into<PathBuf>) -> Self {
+        self.mock_studio = true;
+        self.mock_scenario = Some(path.into());
+        self
+    }
+
+    /// Shown in studio-status; set by the binary when it reports startup to a supervisor.
+    #[doc(hidden)]
+    pub fn status_report(mut self, enabled: bool) -> Self {
+        self.status_report = enabled;
+        self
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Set up the shared state and start the background tasks, without the HTTP
+    /// bridge. Must be called inside a Tokio runtime.
+    pub fn build(self) -> Result<Server, StartError> {
+        let config = self.config;
+        let settings = Settings::startup(&config).map_err(|e| StartError::new("profile", anyhow::Error::msg(e)))?;
+        let build = BuildInfo::get();
+        tracing::info!(
+            port = config.port,
+            capture_dir = %config.capture_dir.display(),
+            version = build.version,
+            commit = build.git_commit.unwrap_or("unknown"),
+            "YippieBlox MCP Server starting"
+        );
+
+        // Compile redaction patterns up front so a bad regex fails startup
+        let redactor = Redactor::new(&config.redact_patterns).map_err(|e| StartError::new("redaction", e))?;
+        let archive = if config.log_archive {
+            let dir = config.capture_dir.join("logs");
+            let archive = LogArchive::open(&dir, config.log_archive_retention).map_err(|e| StartError::new("log_archive", e))?;
+            tracing::info!(path = %dir.display(), "Log archive enabled");
+            archive.start_indexing();
+            Some(archive)
+        } else {
+            None
+        };
+        let launch = LaunchInfo {
+            mock_studio: self.mock_studio,
+            status_report: self.status_report,
+        };
+        if let Some(profile) = &settings.profile {
+            tracing::info!(profile = %profile, "Configuration profile active");
+        }
+        let state = SharedState::new(config.clone(), settings, launch, redactor, archive);
+
+        // Ensure capture directory exists
+        state.capture_manager().map_err(|e| StartError::new("capture_dir", e))?;
+        tracing::info!(path = %config.capture_dir.display(), "Capture directory ready");
+        if config.disable_os_capture {
+            tracing::info!("OS screenshots disabled by YIPPIE_DISABLE_OS_CAPTURE");
+        }
+        let scenario = match (&self.mock_scenario, self.mock_studio) {
+            (Some(path), _) => Some(mock_studio::load_scenario(path).map_err(|e| StartError::new("mock_scenario", e))?),
+            (None, true) => Some(Scenario::default()),
+            (None, false) => None,
+        };
+
+        let mut tasks = vec![
+            tokio::spawn(capture_peers::run(state.clone())).abort_handle(),
+            tokio::spawn(studio_settings::run(state.clone())).abort_handle(),
+            tokio::spawn(wall_clock::run(state.clone())).abort_handle(),
+        ];
+        if let Some(scenario) = scenario {
+            tracing::info!("Mock Studio mode enabled — tool calls are answered in-process");
+            tasks.push(tokio::spawn(mock_studio::run(state.clone(), scenario)).abort_handle());
+        }
+        if let Some(alerting) = state.alerting() {
+            tracing::info!(rules = config.alert_rules.len(), "Alerting hooks enabled");
+            tasks.push(tokio::spawn(alerts::run(state.clone(), alerting)).abort_handle());
+        }
+        if !config.hooks.is_empty() {
+            tracing::info!(hooks = config.hooks.len(), "Result hooks enabled");
+        }
+        if let Some(timeout) = config.idle_timeout {
+            tasks.push(tokio::spawn(idle::run(state.clone(), timeout, config.idle_stop_playtest)).abort_handle());
+        }
+
+        Ok(Server { config, state, tasks })
+    }
+
+    /// Bind the bridge on 127.0.0.1, then [`build`](Self::build) and serve it. Unlike
+    /// the binary, a process already holding the port is not killed; binding fails.
+    pub async fn spawn(mut self) -> Result<ServerHandle, StartError> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], self.config.port));
+        let listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("can't bind the bridge to {addr}"))
+            .map_err(|e| StartError::new("bridge_bind", e))?;
+        let local_addr = listener.local_addr().map_err(|e| StartError::new("bridge_bind", e.into()))?;
+        // studio-status and the plugin settings report the port actually bound
+        self.config.port = local_addr.port();
+
+        let server = self.build()?;
+        let bridge = tokio::spawn(bridge_http::serve(listener, server.config.clone(), server.state.clone()));
+        let dispatcher = server.dispatcher();
+        Ok(ServerHandle {
+            server,
+            local_addr,
+            bridge,
+            dispatcher,
+        })
+    }
+}
+
+/// Why [`ServerBuilder::build`] or [`ServerBuilder::spawn`] failed.
+#[derive(Debug)]
+pub struct StartError {
+    stage: &'static str,
+    error: anyhow::Error,
+}
+
+impl StartError {
+    fn new(stage: &'static str, error: anyhow::Error) -> Self {
+        Self { stage, error }
+    }
+
+    /// The step that failed: `profile`, `redaction`, `log_archive`, `capture_dir`,
+    /// `mock_scenario` or `bridge_bind`. The binary reports it on `--status-fd`.
+    pub fn stage(&self) -> &'static str {
+        self.stage
+    }
+
+    pub fn into_error(self) -> anyhow::Error {
+        self.error
+    }
+}
+
+impl std::fmt::Display for StartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "server startup failed at {}: {:#}", self.stage, self.error)
+    }
+}
+
+impl std::error::Error for StartError {}
+
+/// A server whose state and background tasks are running. The HTTP bridge and the MCP
+/// transport are up to the caller (`bridge_http::serve`, `serve_stdio`).
+pub struct Server {
+    config: Config,
+    state: SharedState,
+    tasks: Vec<AbortHandle>,
+}
+
+impl Server {
+    /// The configuration the server runs with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn state(&self) -> &SharedState {
+        &self.state
+    }
+
+    /// A new MCP session answering requests in-process.
+    pub fn dispatcher(&self) -> Dispatcher {
+        Dispatcher::new(self.state.clone())
+    }
+
+    /// Let plugin calls in flight finish for up to `timeout` and fail the rest, close
+    /// what the session opened in Studio and sync the log archive to disk. What the
+    /// binary does before [`shutdown`](Self::shutdown) when `POST /shutdown` asks it to.
+    pub async fn drain(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.state.pending_call_count().await > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let failed = self.state.fail_pending_calls("Server shutting down").await;
+        if failed > 0 {
+            tracing::warn!(failed, "Failed plugin calls still pending at shutdown");
+        }
+        idle::teardown(&self.state).await;
+        if let Some(archive) = self.state.log_archive() {
+            archive.sync();
+        }
+    }
+
+    /// Stop the background tasks and drop this server from the capture folder's
+    /// heartbeats. What the session opened in Studio is left as it is.
+    pub fn shutdown(self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        self.state.capture_peers().leave();
+    }
+}
+
+/// A running server with its HTTP bridge, returned by [`ServerBuilder::spawn`].
+pub struct ServerHandle {
+    server: Server,
+    local_addr: SocketAddr,
+    bridge: JoinHandle<anyhow::Result<()>>,
+    dispatcher: Dispatcher,
+}
+
+impl ServerHandle {
+    /// Address the bridge is bound to; the Studio plugin connects to it.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    pub fn state(&self) -> &SharedState {
+        self.server.state()
+    }
+
+    /// The MCP session behind [`call_tool`](Self::call_tool). Clones share it.
+    pub fn dispatcher(&self) -> Dispatcher {
+        self.dispatcher.clone()
+    }
+
+    /// Call a tool by the name clients see. See [`Dispatcher::call_tool`].
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<Value, DispatchError> {
+        self.dispatcher.call_tool(name, arguments).await
+    }
+
+    /// The tools as `tools/list` shows them.
+    pub fn tools(&self) -> Vec<McpToolDef> {
+        self.dispatcher.tools()
+    }
+
+    /// Resolves with the requester once `POST /shutdown` was accepted. Nothing stops
+    /// on its own: [`drain`](Self::drain), then [`stop`](Self::stop), as the binary does.
+    pub async fn shutdown_requested(&self) -> String {
+        self.server.state.shutdown_requested().await
+    }
+
+    /// See [`Server::drain`].
+    pub async fn drain(&self, timeout: Duration) {
+        self.server.drain(timeout).await
+    }
+
+    /// Close the bridge and stop the background tasks. Returns once the listener is
+    /// closed, so the port can be bound again.
+    pub async fn stop(self) {
+        self.bridge.abort();
+        let _ = self.bridge.await;
+        self.server.shutdown();
+    }
+}
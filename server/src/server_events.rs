@@ -0,0 +1,47 @@
+//! Internal server events, broadcast from `SharedState` to whoever subscribes
+//! (currently the alerting hooks in `alerts.rs`).
+//!
+//! Sending never blocks and never fails the caller: with no subscribers the event is
+//! dropped, and a subscriber that falls more than `CHANNEL_CAPACITY` events behind
+//! loses the oldest ones.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Events buffered per subscriber.
+pub const CHANNEL_CAPACITY: usize = 256;
+
+/// A plugin or playtest bridge registered. Data: `clientId`, `pluginVersion`, `playtestBridge`.
+pub const CLIENT_CONNECTED: &str = "client_connected";
+/// A client was removed or stopped polling. Data: as `client_connected`, plus `reason`
+/// (`removed` or `stale`).
+pub const CLIENT_DISCONNECTED: &str = "client_disconnected";
+/// A playtest has been running for a while; repeated while it keeps running.
+/// Data: `sessionId`, `mode`, `durationSecs`.
+pub const PLAYTEST_STALE: &str = "playtest_stale";
+/// Bridge requests kept failing auth. Data: `count`, `windowSecs`.
+pub const AUTH_FAILURES: &str = "auth_failures";
+/// Bridge requests over the last minute crossed the HttpService budget warning
+/// (`YIPPIE_HTTP_BUDGET_WARN`). Data: `requestsLastMinute`, `limitPerMinute`, `warnAt`.
+pub const HTTP_BUDGET: &str = "http_budget";
+
+/// Every event kind, for validating alert rules.
+pub const KINDS: &[&str] = &[CLIENT_CONNECTED, CLIENT_DISCONNECTED, PLAYTEST_STALE, AUTH_FAILURES, HTTP_BUDGET];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServerEvent {
+    pub kind: &'static str,
+    pub at: chrono::DateTime<chrono::Utc>,
+    /// Flat object of event fields.
+    pub data: Value,
+}
+
+impl ServerEvent {
+    pub fn new(kind: &'static str, data: Value) -> Self {
+        Self {
+            kind,
+            at: chrono::Utc::now(),
+            data,
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Per-connection MCP session state. The stdio loop owns one `SessionState` and passes
+//! it to the dispatcher (tool calls running concurrently share it through an `Arc`);
+//! anything shared by every session (plugin clients, queues, pending calls, logs)
+//! stays in `SharedState`. What concurrent calls of a session change (request times,
+//! the log subscription, warnings already shown) lives in its `SessionActivity`.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::client_roots::ClientRoots;
+use crate::outbound_queue::Priority;
+use crate::providers::Clock;
+
+/// Key under `initialize` `capabilities.experimental` where a client can declare
+/// `{ "priority": "background" }`.
+const SESSION_HINT_KEY: &str = "yippieblox/session";
+
+tokio::task_local! {
+    /// The MCP client (`name/version`) whose tool call is running. Records written
+    /// during the call (capture index entries) are stamped with it.
+    static CALL_CLIENT: Option<String>;
+}
+
+/// JSON-RPC error for a request sent before the handshake finished.
+pub const NOT_INITIALIZED: i64 = -32002;
+/// JSON-RPC error for a second `initialize`.
+pub const ALREADY_INITIALIZED: i64 = -32600;
+/// JSON-RPC error for a request that arrives once the server is shutting down.
+pub const SHUTTING_DOWN: i64 = -32000;
+
+/// Where a session is in the MCP lifecycle: `initialize`, its response, then the
+/// client's `notifications/initialized`. Only `ping` is answered outside `Ready`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lifecycle {
+    #[default]
+    Uninitialized,
+    /// `initialize` was answered; waiting for `notifications/initialized`.
+    Initializing,
+    Ready,
+    /// A shutdown was requested; nothing new is started.
+    ShuttingDown,
+}
+
+impl Lifecycle {
+    /// Whether a request for `method` may be handled now, or the JSON-RPC error code
+    /// and message refusing it. `lenient` admits requests before the handshake has
+    /// finished and a repeated `initialize`.
+    pub fn admit(self, method: &str, lenient: bool) -> Result<(), (i64, String)> {
+        match (self, method) {
+            (_, "ping") => Ok(()),
+            (Lifecycle::ShuttingDown, _) => Err((SHUTTING_DOWN, "Server is shutting down".into())),
+            (Lifecycle::Uninitialized, "initialize") => Ok(()),
+            _ if lenient => Ok(()),
+            (_, "initialize") => Err((
+                ALREADY_INITIALIZED,
+                "Session already initialized; initialize is sent once per connection".into(),
+            )),
+            (Lifecycle::Ready, _) => Ok(()),
+            (Lifecycle::Uninitialized, _) => Err((
+                NOT_INITIALIZED,
+                format!("Server not initialized: send initialize before {method}"),
+            )),
+            (Lifecycle::Initializing, _) => Err((
+                NOT_INITIALIZED,
+                format!("Server not initialized: send notifications/initialized before {method}"),
+            )),
+        }
+    }
+
+    /// The state after `initialize` was answered.
+    pub fn initialized(self) -> Self {
+        match self {
+            Lifecycle::Uninitialized => Lifecycle::Initializing,
+            other => other,
+        }
+    }
+
+    /// The state after the client's `notifications/initialized`.
+    pub fn ready(self) -> Self {
+        match self {
+            Lifecycle::Initializing => Lifecycle::Ready,
+            other => other,
+        }
+    }
+}
+
+/// `clientInfo` from `initialize`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientInfo {
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl ClientInfo {
+    /// `name/version`, or just the name when the client sent no version.
+    pub fn label(&self) -> String {
+        match &self.version {
+            Some(version) => format!("{}/{version}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Run `call` with `client` as the identity of the current tool call.
+pub async fn with_call_client<F: std::future::Future>(client: Option<String>, call: F) -> F::Output {
+    CALL_CLIENT.scope(client, call).await
+}
+
+/// The MCP client of the tool call running on this task, if any.
+pub fn call_client() -> Option<String> {
+    CALL_CLIENT.try_with(|client| client.clone()).ok().flatten()
+}
+
+/// A log subscription made with studio-logs_subscribe.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogSubscription {
+    /// `levels` the session subscribed with; None for every level.
+    pub levels: Option<Value>,
+}
+
+impl LogSubscription {
+    /// Arguments that renew the subscription after an idle cleanup dropped it.
+    pub fn resubscribe_arguments(&self) -> Value {
+        match &self.levels {
+            Some(levels) => json!({ "levels": levels }),
+            None => json!({}),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LogSubscriptions {
+    active: Option<LogSubscription>,
+    /// Dropped by an idle cleanup, renewed by the next request.
+    suspended: Option<LogSubscription>,
+}
+
+/// The mutable part of a session, shared by every copy of its `SessionState` and
+/// registered with `SharedState` so the idle watcher can find it.
+#[derive(Debug)]
+pub struct SessionActivity {
+    /// Tokio's clock, so the idle watcher's timers and this agree (also when paused).
+    last_request: Mutex<tokio::time::Instant>,
+    idle: AtomicBool,
+    logs: Mutex<LogSubscriptions>,
+    /// Plugin settings warnings the session was shown, as `clientId/code`.
+    delivered_warnings: Mutex<BTreeSet<String>>,
+}
+
+impl Default for SessionActivity {
+    fn default() -> Self {
+        Self {
+            last_request: Mutex::new(tokio::time::Instant::now()),
+            idle: AtomicBool::new(false),
+            logs: Mutex::default(),
+            delivered_warnings: Mutex::default(),
+        }
+    }
+}
+
+impl SessionActivity {
+    /// Note a request. Returns true if the session was idle until now.
+    pub fn record_request(&self) -> bool {
+        *self.last_request.lock().unwrap_or_else(|e| e.into_inner()) = tokio::time::Instant::now();
+        self.idle.swap(false, Ordering::SeqCst)
+    }
+
+    /// Time since the last request.
+    pub fn idle_for(&self) -> Duration {
+        self.last_request.lock().unwrap_or_else(|e| e.into_inner()).elapsed()
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(Ordering::SeqCst)
+    }
+
+    pub fn enter_idle(&self) {
+        self.idle.store(true, Ordering::SeqCst);
+    }
+
+    pub fn log_subscription(&self) -> Option<LogSubscription> {
+        self.logs.lock().unwrap_or_else(|e| e.into_inner()).active.clone()
+    }
+
+    /// Record a successful studio-logs_subscribe (`Some`) or studio-logs_unsubscribe.
+    pub fn set_log_subscription(&self, subscription: Option<LogSubscription>) {
+        let mut logs = self.logs.lock().unwrap_or_else(|e| e.into_inner());
+        logs.active = subscription;
+        logs.suspended = None;
+    }
+
+    /// Set the subscription aside after an idle cleanup dropped it in Studio.
+    pub fn suspend_log_subscription(&self) {
+        let mut logs = self.logs.lock().unwrap_or_else(|e| e.into_inner());
+        logs.suspended = logs.active.take().or(logs.suspended.take());
+    }
+
+    /// The subscription an idle cleanup set aside, to renew now.
+    pub fn take_suspended_log_subscription(&self) -> Option<LogSubscription> {
+        self.logs.lock().unwrap_or_else(|e| e.into_inner()).suspended.take()
+    }
+
+    /// Of the warnings `current` (keys), those not shown to the session yet, which
+    /// count as shown from now on. Warnings that cleared are forgotten, so they are
+    /// shown again if they come back.
+    pub fn undelivered_warnings(&self, current: &[String]) -> Vec<String> {
+        let mut delivered = self.delivered_warnings.lock().unwrap_or_else(|e| e.into_inner());
+        delivered.retain(|key| current.contains(key));
+        current.iter().filter(|key| delivered.insert((*key).clone())).cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionState {
+    pub id: String,
+    started_at: Instant,
+    /// Set by `initialize`.
+    client: Option<ClientInfo>,
+    /// Protocol version the client asked for in `initialize`.
+    requested_protocol_version: Option<String>,
+    /// Queue priority of this session's plugin calls.
+    priority: Priority,
+    /// Shared by every copy of the session, like the connection it belongs to.
+    roots: Arc<ClientRoots>,
+    lifecycle: Lifecycle,
+    /// Shared by every copy of the session.
+    activity: Arc<SessionActivity>,
+}
+
+impl SessionState {
+    pub fn new(id: String, roots: Arc<ClientRoots>) -> Self {
+        Self {
+            id,
+            started_at: Instant::now(),
+            client: None,
+            requested_protocol_version: None,
+            priority: Priority::default(),
+            roots,
+            lifecycle: Lifecycle::default(),
+            activity: Arc::default(),
+        }
+    }
+
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.lifecycle
+    }
+
+    pub fn set_lifecycle(&mut self, lifecycle: Lifecycle) {
+        if lifecycle != self.lifecycle {
+            tracing::debug!(session = %self.id, from = ?self.lifecycle, to = ?lifecycle, "MCP session lifecycle");
+            self.lifecycle = lifecycle;
+        }
+    }
+
+    /// Record what the client sent with `initialize` and move on to waiting for
+    /// `notifications/initialized`. A client that initializes again (only admitted
+    /// with `YIPPIE_LENIENT_LIFECYCLE`) replaces the earlier values.
+    pub fn initialize(&mut self, params: &Value) {
+        self.set_lifecycle(self.lifecycle.initialized());
+        self.client = params
+            .get("clientInfo")
+            .and_then(|v| serde_json::from_value(v.clone()).ok());
+        self.requested_protocol_version = params
+            .get("protocolVersion")
+            .and_then(|v| v.as_str())
+            .map(String::from);
+        self.roots.set_supported(params.pointer("/capabilities/roots").is_some());
+        let hint = params
+            .pointer("/capabilities/experimental")
+            .and_then(|e| e.get(SESSION_HINT_KEY))
+            .and_then(|h| h.get("priority"))
+            .and_then(|p| p.as_str());
+        self.priority = match hint.map(|h| (h, Priority::parse(h))) {
+            None => Priority::default(),
+            Some((_, Some(priority))) => priority,
+            Some((raw, None)) => {
+                tracing::warn!(session = %self.id, hint = raw, "Unknown session priority hint; using interactive");
+                Priority::default()
+            }
+        };
+        tracing::info!(
+            session = %self.id,
+            client = ?self.client.as_ref().map(|c| &c.name),
+            protocol_version = ?self.requested_protocol_version,
+            priority = ?self.priority,
+            "MCP session initialized"
+        );
+    }
+
+    pub fn client(&self) -> Option<&ClientInfo> {
+        self.client.as_ref()
+    }
+
+    /// `name/version` of the client, for logs and records.
+    pub fn client_label(&self) -> Option<String> {
+        self.client.as_ref().map(ClientInfo::label)
+    }
+
+    pub fn priority(&self) -> Priority {
+        self.priority
+    }
+
+    /// The client's filesystem roots, for `outputDir`.
+    pub fn roots(&self) -> &ClientRoots {
+        &self.roots
+    }
+
+    pub fn activity(&self) -> &Arc<SessionActivity> {
+        &self.activity
+    }
+
+    /// Identity and age of the session, for studio-status.
+    pub fn status(&self, protocol_version: &str, clock: &dyn Clock) -> Value {
+        json!({
+            "id": self.id,
+            "client": self.client.as_ref().map(|c| json!({ "name": c.name, "version": c.version })),
+            "protocolVersion": protocol_version,
+            "requestedProtocolVersion": self.requested_protocol_version,
+            "startedSecsAgo": clock.elapsed(self.started_at).as_secs(),
+            "priority": self.priority,
+            "lifecycle": self.lifecycle,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATES: [Lifecycle; 4] = [
+        Lifecycle::Uninitialized,
+        Lifecycle::Initializing,
+        Lifecycle::Ready,
+        Lifecycle::ShuttingDown,
+    ];
+    const METHODS: [&str; 4] = ["ping", "initialize", "tools/list", "tools/call"];
+
+    /// The error code `admit` answers with, or 0 when the request is handled.
+    fn code(state: Lifecycle, method: &str, lenient: bool) -> i64 {
+        state.admit(method, lenient).map_or_else(|(code, _)| code, |()| 0)
+    }
+
+    #[test]
+    fn admit_covers_every_state_and_method() {
+        // Rows follow STATES, columns follow METHODS
+        let strict = [
+            [0, 0, NOT_INITIALIZED, NOT_INITIALIZED],
+            [0, ALREADY_INITIALIZED, NOT_INITIALIZED, NOT_INITIALIZED],
+            [0, ALREADY_INITIALIZED, 0, 0],
+            [0, SHUTTING_DOWN, SHUTTING_DOWN, SHUTTING_DOWN],
+        ];
+        let lenient = [[0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, SHUTTING_DOWN, SHUTTING_DOWN, SHUTTING_DOWN]];
+        for (row, state) in STATES.into_iter().enumerate() {
+            for (column, method) in METHODS.into_iter().enumerate() {
+                assert_eq!(code(state, method, false), strict[row][column], "{state:?} {method}");
+                assert_eq!(code(state, method, true), lenient[row][column], "{state:?} {method} (lenient)");
+            }
+        }
+    }
+
+    #[test]
+    fn tool_call_before_initialized_notification_names_it() {
+        let (code, message) = Lifecycle::Initializing.admit("tools/call", false).unwrap_err();
+        assert_eq!(code, NOT_INITIALIZED);
+        assert_eq!(message, "Server not initialized: send notifications/initialized before tools/call");
+        let (_, message) = Lifecycle::Uninitialized.admit("tools/call", false).unwrap_err();
+        assert_eq!(message, "Server not initialized: send initialize before tools/call");
+    }
+
+    #[test]
+    fn second_initialize_is_refused() {
+        let (code, message) = Lifecycle::Ready.admit("initialize", false).unwrap_err();
+        assert_eq!(code, ALREADY_INITIALIZED);
+        assert_eq!(message, "Session already initialized; initialize is sent once per connection");
+    }
+
+    #[test]
+    fn transitions_only_move_forward_from_their_own_state() {
+        let initialized: Vec<Lifecycle> = STATES.iter().map(|s| s.initialized()).collect();
+        assert_eq!(
+            initialized,
+            [Lifecycle::Initializing, Lifecycle::Initializing, Lifecycle::Ready, Lifecycle::ShuttingDown]
+        );
+        let ready: Vec<Lifecycle> = STATES.iter().map(|s| s.ready()).collect();
+        assert_eq!(
+            ready,
+            [Lifecycle::Uninitialized, Lifecycle::Ready, Lifecycle::Ready, Lifecycle::ShuttingDown]
+        );
+    }
+
+    #[tokio::test]
+    async fn the_call_client_is_scoped_to_its_task() {
+        assert_eq!(call_client(), None);
+        let label = ClientInfo { name: "Harness".into(), version: Some("2.1".into()) }.label();
+        let inner = with_call_client(Some(label), async {
+            let outer = call_client();
+            // A nested call of another client doesn't leak back out
+            let nested = with_call_client(Some(ClientInfo { name: "Bare".into(), version: None }.label()), async { call_client() }).await;
+            (outer, nested, call_client())
+        })
+        .await;
+        assert_eq!(inner, (Some("Harness/2.1".into()), Some("Bare".into()), Some("Harness/2.1".into())));
+        assert_eq!(call_client(), None);
+    }
+}
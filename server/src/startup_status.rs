@@ -0,0 +1,172 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use roblox_studio_yippieblox_mcp_server::Config;
+
+/// Writes one machine-readable JSON object describing startup (ready or failed) to
+/// `--status-fd` and/or `--status-file`, for supervisors that shouldn't parse logs.
+/// Only the first report is written; later ones are ignored.
+pub struct StatusReporter {
+    sinks: Mutex<Option<Sinks>>,
+}
+
+struct Sinks {
+    fd: Option<StatusFd>,
+    file: Option<PathBuf>,
+}
+
+impl StatusReporter {
+    /// `fd` must come from `parse_status_fd`, which checked that it is open; the
+    /// reporter owns it from here on and closes it after the one report.
+    pub fn new(fd: Option<i32>, file: Option<PathBuf>) -> Self {
+        let fd = fd.map(StatusFd::take);
+        let sinks = (fd.is_some() || file.is_some()).then_some(Sinks { fd, file });
+        Self {
+            sinks: Mutex::new(sinks),
+        }
+    }
+
+    /// Pass a startup result through, reporting it as a failure of `stage` if it errored.
+    pub fn stage<T>(&self, stage: &str, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            self.failed(stage, &format!("{e:#}"));
+        }
+        result
+    }
+
+    pub fn failed(&self, stage: &str, error: &str) {
+        self.write(json!({
+            "status": "error",
+            "stage": stage,
+            "error": error,
+            "pid": std::process::id(),
+            "version": env!("CARGO_PKG_VERSION"),
+        }));
+    }
+
+    /// Report readiness: the bridge is bound at `addr` and the stdio loop is running.
+    pub fn ready(&self, config: &Config, addr: SocketAddr, mock_studio: bool) {
+        let mut features = vec!["http_bridge", "input_recording"];
+        if !config.disable_os_capture {
+            features.push("os_capture");
+        }
+        if !config.redact_patterns.is_empty() {
+            features.push("redaction");
+        }
+        if mock_studio {
+            features.push("mock_studio");
+        }
+        self.write(json!({
+            "status": "ready",
+            "port": addr.port(),
+            "bindAddress": addr.ip().to_string(),
+            "authMode": if config.token.is_some() { "token" } else { "none" },
+            "tokenFingerprint": config.token.as_ref().map(|t| t.fingerprint()),
+            "pid": std::process::id(),
+            "captureDir": config.capture_dir,
+            "version": env!("CARGO_PKG_VERSION"),
+            "features": features,
+        }));
+    }
+
+    fn write(&self, value: Value) {
+        let Some(sinks) = self.sinks.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+        let line = format!("{value}\n");
+        if let Some(fd) = sinks.fd {
+            let raw = fd.raw();
+            if let Err(e) = fd.write_and_close(&line) {
+                tracing::warn!(fd = raw, "Failed to write startup status: {e}");
+            }
+        }
+        if let Some(path) = &sinks.file {
+            if let Err(e) = write_file(path, &line) {
+                tracing::warn!(path = %path.display(), "Failed to write startup status: {e}");
+            }
+        }
+    }
+}
+
+/// clap parser for `--status-fd`: a descriptor number of 3 or more that is open in
+/// this process. It runs before the runtime or the log file open anything, so an open
+/// descriptor here can only have been inherited from the parent; an unused number is
+/// refused rather than written to later, when it may belong to something of ours.
+#[cfg(unix)]
+pub fn parse_status_fd(value: &str) -> Result<i32, String> {
+    let fd: i32 = value.parse().map_err(|_| format!("'{value}' is not a file descriptor number"))?;
+    if fd < 3 {
+        return Err("must be 3 or higher; 0-2 are stdin, stdout and stderr".into());
+    }
+    // SAFETY: F_GETFD only reads the descriptor flags and takes no pointer
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+    if flags == -1 {
+        return Err(format!("descriptor {fd} is not open; pass one the server inherits, e.g. 3>&1"));
+    }
+    // Keep it out of processes the server spawns; only the report goes to it
+    // SAFETY: as above, F_SETFD takes an int argument
+    unsafe { libc::fcntl(fd, libc::F_SETFD, flags | libc::FD_CLOEXEC) };
+    Ok(fd)
+}
+
+#[cfg(not(unix))]
+pub fn parse_status_fd(_value: &str) -> Result<i32, String> {
+    Err("--status-fd is only supported on Unix; use --status-file instead".into())
+}
+
+/// The inherited status descriptor. It is owned from startup, so it is closed exactly
+/// once: after the report is written, or when the reporter is dropped without one.
+#[cfg(unix)]
+struct StatusFd(std::fs::File);
+
+#[cfg(unix)]
+impl StatusFd {
+    fn take(fd: i32) -> Self {
+        use std::os::fd::FromRawFd;
+        // SAFETY: parse_status_fd checked the descriptor is open before anything in
+        // this process could open one, so it is the parent's and nothing else owns it
+        Self(unsafe { std::fs::File::from_raw_fd(fd) })
+    }
+
+    fn raw(&self) -> i32 {
+        use std::os::fd::AsRawFd;
+        self.0.as_raw_fd()
+    }
+
+    /// Write the status line, then close the descriptor so the reader sees EOF.
+    fn write_and_close(mut self, line: &str) -> Result<()> {
+        use std::io::Write;
+        self.0.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+struct StatusFd(i32);
+
+#[cfg(not(unix))]
+impl StatusFd {
+    fn take(fd: i32) -> Self {
+        Self(fd)
+    }
+
+    fn raw(&self) -> i32 {
+        self.0
+    }
+
+    fn write_and_close(self, _line: &str) -> Result<()> {
+        anyhow::bail!("--status-fd is only supported on Unix; use --status-file instead")
+    }
+}
+
+/// Write via a temp file + rename so a watcher never sees a partial file.
+fn write_file(path: &std::path::Path, line: &str) -> Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    std::fs::write(&tmp_path, line)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
@@ -1,27 +1,183 @@
-use std::collections::{HashMap, VecDeque};
-use std::path::PathBuf;
-use std::sync::Arc;
-use tokio::sync::{Mutex, Notify, oneshot};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot, watch, Mutex, Notify};
 
-use crate::types::{BridgeToolRequest, BridgeToolResponse, LogEntry};
+use crate::alerts::Alerting;
+use crate::async_calls::AsyncCalls;
+use crate::build_info::LaunchInfo;
+use crate::capture_peers::CapturePeers;
+use crate::captures::CaptureManager;
+use crate::clock_sync::ClockOffset;
+use crate::config::{Config, Role};
+use crate::event_order::EventOrdering;
+use crate::gui_snapshot::SnapshotStore;
+use crate::hooks::Hooks;
+use crate::ids::IdGenerator;
+use crate::input_recording::{BatchArrival, MAX_RECORDING_SAMPLES};
+use crate::log_archive::LogArchive;
+use crate::log_store::{LogStore, MAX_LOG_BUFFER};
+use crate::outbound_queue::{OutboundQueue, Priority, QueueDepths};
+use crate::path_index::PathIndex;
+use crate::place_stats::StatsStore;
+use crate::plugin_settings::{self, Expectations, PluginSettings, SettingsWarning};
+use crate::profiles::{self, Profile, Settings};
+use crate::providers::{Clock, Providers};
+use crate::readiness::Readiness;
+use crate::redact::Redactor;
+use crate::response_cache::ResponseCache;
+use crate::sanitize::Sanitizer;
+use crate::server_events::{self, ServerEvent};
+use crate::session::{SessionActivity, SessionState};
+use crate::studio_settings;
+use crate::time_report::TestTimings;
+use crate::tool_history::ToolHistory;
+use crate::traceback::SourceCache;
+use crate::traffic::{
+    self, BackgroundLevel, ClientTraffic, HttpBudget, RollingCounter, TrafficReport,
+};
+use crate::types::{BridgeToolRequest, BridgeToolResponse, InputSample, LogEntry, LogGroup};
+use crate::variables::VariableStore;
+use crate::wall_clock::WallClockWatch;
+use crate::wire::Casing;
 
 #[derive(Clone)]
 pub struct SharedState(Arc<Inner>);
 
 struct Inner {
-    clients: Mutex<HashMap<String, ClientState>>,
+    /// Keyed by client id, so iteration (status lists, routing ties) is in
+    /// registration order.
+    clients: Mutex<BTreeMap<String, ClientState>>,
     pending_calls: Mutex<HashMap<String, oneshot::Sender<BridgeToolResponse>>>,
-    log_buffer: Mutex<VecDeque<LogEntry>>,
-    log_seq: Mutex<u64>,
+    log_buffer: LogStore,
     playtest_state: Mutex<PlaytestState>,
-    capture_dir: PathBuf,
+    playtest_changed: Notify,
+    playtest_starts_in_flight: AtomicUsize,
+    input_recording: Mutex<Option<ActiveRecording>>,
+    held_keys: Mutex<BTreeSet<String>>,
+    /// Values of runtime flags from before studio-set_runtime_flag first changed them.
+    runtime_flag_originals: Mutex<BTreeMap<String, serde_json::Value>>,
+    /// Studio settings changed by studio-set_studio_setting, with their values before.
+    studio_setting_originals: Mutex<BTreeMap<String, studio_settings::Original>>,
+    session_resources: Mutex<SessionResources>,
+    /// MCP sessions by id. Entries die with the last copy of their session.
+    sessions: std::sync::Mutex<BTreeMap<String, Weak<SessionActivity>>>,
+    /// Woken when a session starts or ends an idle period.
+    activity: Notify,
+    log_archive: Option<Arc<LogArchive>>,
+    capture_peers: CapturePeers,
+    /// Present when `YIPPIE_TREE_INDEX` is on.
+    path_index: Option<std::sync::Mutex<PathIndex>>,
+    /// Absent when `YIPPIE_CACHE_TTL_SECS` or `YIPPIE_CACHE_SIZE` is 0.
+    response_cache: Option<std::sync::Mutex<ResponseCache>>,
+    /// Script sources fetched to annotate tracebacks.
+    script_sources: std::sync::Mutex<SourceCache>,
+    /// Values kept by studio-var_set for later calls.
+    variables: std::sync::Mutex<VariableStore>,
+    redactor: Redactor,
+    /// Strips ANSI escapes and control characters from logs and results.
+    sanitizer: Sanitizer,
+    config: Config,
+    /// Settings of the active profile, swapped whole by `switch_profile`.
+    settings: std::sync::RwLock<Arc<Settings>>,
+    /// Tool calls per MCP client (`name/version`).
+    tool_calls_by_client: std::sync::Mutex<BTreeMap<String, u64>>,
+    /// Recent tool calls for studio-get_tool_history.
+    tool_history: std::sync::Mutex<ToolHistory>,
+    /// Recent studio-place_stats snapshots, for `compareTo`.
+    place_stats: std::sync::Mutex<StatsStore>,
+    /// Calls started with `async: true`, for studio-get_operation, studio-wait_operation and
+    /// studio-cancel.
+    async_calls: std::sync::Mutex<AsyncCalls>,
+    /// Last wall-clock reading, to notice sleep/wake jumps before pruning clients.
+    wall_clock: std::sync::Mutex<WallClockWatch>,
+    /// Recent studio-gui_tree snapshots, for cursors and studio-gui_click.
+    gui_snapshots: std::sync::Mutex<SnapshotStore>,
+    /// Phase timings of studio-test_script calls, for studio-time_report.
+    test_timings: std::sync::Mutex<TestTimings>,
+    launch: LaunchInfo,
+    started_at: Instant,
+    /// Reorder windows for clients that number their events.
+    event_order: Mutex<EventOrdering>,
+    /// Request, client, session and capture ids.
+    ids: Arc<IdGenerator>,
+    /// Time reported to clients (logical in deterministic mode).
+    clock: Arc<dyn Clock>,
+    /// Internal events (client connects, stale playtests, auth failures).
+    events: broadcast::Sender<ServerEvent>,
+    /// Present when `YIPPIE_ALERT_RULES` has rules.
+    alerting: Option<Arc<Alerting>>,
+    /// Present when `YIPPIE_HOOKS` configures at least one hook.
+    hooks: Option<Arc<Hooks>>,
+    /// Recent bridge auth failures, at most `AUTH_FAILURE_THRESHOLD`.
+    auth_failures: std::sync::Mutex<VecDeque<Instant>>,
+    /// Bridge requests from all clients, against the HttpService budget.
+    bridge_requests: RollingCounter,
+    /// Set while bridge traffic is over the budget warning threshold.
+    http_budget_warned: AtomicBool,
+    /// `BackgroundLevel` as last computed from the request rate.
+    background_level: AtomicU8,
+    /// Who asked for a shutdown over `POST /shutdown`, once someone has.
+    shutdown: watch::Sender<Option<String>>,
+    /// Set once the MCP stdio loop is reading requests, for the startup report.
+    stdio_running: watch::Sender<bool>,
 }
 
 struct ClientState {
     plugin_version: String,
-    outbound_queue: VecDeque<BridgeToolRequest>,
+    /// Per-load key sent by plugins that support reconnect detection.
+    instance_key: Option<String>,
+    outbound_queue: OutboundQueue,
     notify: Arc<Notify>,
+    /// Wall-clock time of the last poll, for display.
     last_poll: chrono::DateTime<chrono::Utc>,
+    /// Monotonic time of the last poll, for staleness and routing.
+    last_poll_at: Instant,
+    /// Latest `studio-settings` the client reported.
+    settings: Option<PluginSettings>,
+    /// Updated by /pull and /push without holding the clients lock.
+    traffic: Arc<ClientTraffic>,
+    /// Offset of the client's clock, from the `clock` it sends with each request.
+    clock_offset: Arc<ClockOffset>,
+    /// Field casing of what the server sends this client.
+    casing: Casing,
+}
+
+/// Tools that need the Server DataModel / Play context of the playtest bridge.
+fn prefers_bridge(tool_name: &str) -> bool {
+    matches!(
+        tool_name,
+        "studio-virtualuser_key"
+            | "studio-virtualuser_mouse_button"
+            | "studio-virtualuser_move_mouse"
+            | "studio-virtualuser_sequence"
+            | "studio-virtualuser_record_start"
+            | "studio-virtualuser_record_stop"
+            | "studio-npc_driver_start"
+            | "studio-npc_driver_command"
+            | "studio-npc_driver_stop"
+            | "studio-get_bindable_actions"
+            | "studio-get_humanoid_description"
+            | "studio-get_proximity_prompts"
+            | "studio-trigger_proximity_prompt"
+            | "studio-click_gui_button"
+            | "studio-get_gui_tree"
+            | "studio-get_sound_playing"
+            | "studio-get_network_ownership"
+            | "studio-set_network_ownership"
+            | "studio-get_replication_stats"
+            | "studio-playtest_stop"
+    )
+}
+
+/// The first client of the preferred type, else the most recently polled one.
+fn route_target(clients: &BTreeMap<String, ClientState>, prefers_bridge: bool) -> Option<String> {
+    clients
+        .iter()
+        .find(|(_, c)| prefers_bridge == c.is_playtest_bridge())
+        .or_else(|| clients.iter().max_by_key(|(_, c)| c.last_poll_at))
+        .map(|(k, _)| k.clone())
 }
 
 impl ClientState {
@@ -29,6 +185,23 @@ impl ClientState {
     fn is_playtest_bridge(&self) -> bool {
         self.plugin_version.contains("playtest")
     }
+
+    /// Identity of the plugin instance behind this client: its instance key, or the
+    /// client id for plugins that don't send one (every re-register looks like a reload).
+    fn owner_key(&self, client_id: &str) -> String {
+        self.instance_key
+            .clone()
+            .unwrap_or_else(|| client_id.to_string())
+    }
+
+    /// Fields of `client_connected` / `client_disconnected` server events.
+    fn event_data(&self, client_id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "clientId": client_id,
+            "pluginVersion": self.plugin_version,
+            "playtestBridge": self.is_playtest_bridge(),
+        })
+    }
 }
 
 #[derive(Default)]
@@ -36,256 +209,2218 @@ pub struct PlaytestState {
     pub active: bool,
     pub session_id: Option<String>,
     pub mode: Option<String>,
+    /// When the current playtest session was first reported active.
+    pub started_at: Option<Instant>,
+    /// Last stage the playtest bridge reported; None until it reports one.
+    pub readiness: Option<Readiness>,
+    /// Player the bridge reported with `readiness`.
+    pub player: Option<String>,
+}
+
+/// Samples collected from `studio-input_sample` events while a recording is open.
+pub struct ActiveRecording {
+    pub name: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub samples: Vec<InputSample>,
+    /// Arrival times of the sample batches, for drift correction.
+    pub batches: Vec<BatchArrival>,
+    pub dropped: usize,
+}
+
+/// Plugin-side resources opened through tool calls, tracked so an idle cleanup can
+/// close them. Log subscriptions are kept per session (`SessionActivity`).
+#[derive(Debug, Default)]
+pub struct SessionResources {
+    /// checkpointId -> recording
+    pub open_checkpoints: BTreeMap<String, OpenCheckpoint>,
+    /// driverId -> target
+    pub npc_drivers: BTreeMap<String, String>,
+}
+
+/// A ChangeHistory recording opened with studio-checkpoint_begin.
+#[derive(Debug, Clone)]
+pub struct OpenCheckpoint {
+    pub name: String,
+    /// Owner key of the plugin instance holding the recording.
+    pub owner: String,
+    /// Set once the owning plugin can no longer finish the recording.
+    pub lost: Option<CheckpointLoss>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointLoss {
+    /// The owning plugin was removed. Cleared if the same instance registers again.
+    Disconnected,
+    /// A different plugin instance registered; Studio dropped the recording with the
+    /// old one.
+    Reloaded,
+}
+
+impl CheckpointLoss {
+    pub fn describe(self) -> &'static str {
+        match self {
+            CheckpointLoss::Disconnected => "the Studio plugin that opened it disconnected",
+            CheckpointLoss::Reloaded => {
+                "the Studio plugin reloaded, which discards open recordings"
+            }
+        }
+    }
+}
+
+/// Marks a playtest_play/run call as in flight until dropped.
+pub struct PlaytestStartGuard {
+    state: SharedState,
+}
+
+impl Drop for PlaytestStartGuard {
+    fn drop(&mut self) {
+        self.state
+            .0
+            .playtest_starts_in_flight
+            .fetch_sub(1, Ordering::SeqCst);
+        self.state.0.playtest_changed.notify_waiters();
+    }
+}
+
+/// Distinct messages tracked by `log_groups`. Entries with a message first seen after
+/// this many groups exist are only counted in `LogGroups::ungrouped`.
+const MAX_LOG_GROUPS: usize = 100;
+
+/// Result of `SharedState::log_groups`.
+pub struct LogGroups {
+    /// Most frequent first; ties go to the most recent.
+    pub groups: Vec<LogGroup>,
+    /// Buffered entries of the requested levels.
+    pub entries: usize,
+    /// Entries left out because `MAX_LOG_GROUPS` was reached.
+    pub ungrouped: usize,
+}
+
+/// Group the entries of `levels` by level and message.
+fn group_logs(buf: &mut dyn Iterator<Item = &LogEntry>, levels: &[&str]) -> LogGroups {
+    let mut index: HashMap<(&str, &str), usize> = HashMap::new();
+    let mut groups: Vec<LogGroup> = Vec::new();
+    let (mut entries, mut ungrouped) = (0, 0);
+    for entry in buf.filter(|e| levels.contains(&e.level.as_str())) {
+        entries += 1;
+        let key = (entry.level.as_str(), entry.message.as_str());
+        if let Some(&i) = index.get(&key) {
+            let group = &mut groups[i];
+            group.count += 1;
+            group.last_ts = entry.ts;
+            group.last_seq = entry.seq;
+        } else if groups.len() < MAX_LOG_GROUPS {
+            index.insert(key, groups.len());
+            groups.push(LogGroup {
+                level: entry.level.clone(),
+                message: entry.message.clone(),
+                count: 1,
+                first_ts: entry.ts,
+                last_ts: entry.ts,
+                first_seq: entry.seq,
+                last_seq: entry.seq,
+            });
+        } else {
+            ungrouped += 1;
+        }
+    }
+    groups.sort_by(|a, b| b.count.cmp(&a.count).then(b.last_seq.cmp(&a.last_seq)));
+    LogGroups {
+        groups,
+        entries,
+        ungrouped,
+    }
 }
 
-const MAX_LOG_BUFFER: usize = 500;
+/// Clients that haven't polled for this long are removed as stale.
+const STALE_CLIENT_AFTER: Duration = Duration::from_secs(60);
+
+/// Bridge auth failures within `AUTH_FAILURE_WINDOW` that raise an `auth_failures` event.
+const AUTH_FAILURE_THRESHOLD: usize = 5;
+const AUTH_FAILURE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Once over the budget warning threshold, traffic must drop below this share of it
+/// before the warning clears, so a rate hovering at the threshold doesn't flap.
+const HTTP_BUDGET_CLEAR_RATIO: f64 = 0.9;
 
 impl SharedState {
-    pub fn new(capture_dir: PathBuf) -> Self {
+    pub fn new(
+        config: Config,
+        settings: Settings,
+        launch: LaunchInfo,
+        redactor: Redactor,
+        log_archive: Option<Arc<LogArchive>>,
+    ) -> Self {
+        let providers = Providers::new(config.deterministic_seed);
+        Self::with_providers(config, settings, launch, redactor, log_archive, providers)
+    }
+
+    /// A state whose clock and random source are `providers` rather than the ones
+    /// `config` selects.
+    pub fn with_providers(
+        config: Config,
+        settings: Settings,
+        launch: LaunchInfo,
+        redactor: Redactor,
+        log_archive: Option<Arc<LogArchive>>,
+        providers: Providers,
+    ) -> Self {
+        let alerting = (!config.alert_rules.is_empty())
+            .then(|| Arc::new(Alerting::new(config.alert_rules.clone())));
+        let hooks = (!config.hooks.is_empty()).then(|| Arc::new(Hooks::new(config.hooks.clone())));
         Self(Arc::new(Inner {
-            clients: Mutex::new(HashMap::new()),
+            clients: Mutex::new(BTreeMap::new()),
             pending_calls: Mutex::new(HashMap::new()),
-            log_buffer: Mutex::new(VecDeque::with_capacity(MAX_LOG_BUFFER)),
-            log_seq: Mutex::new(0),
+            log_buffer: LogStore::default(),
             playtest_state: Mutex::new(PlaytestState::default()),
-            capture_dir,
+            playtest_changed: Notify::new(),
+            playtest_starts_in_flight: AtomicUsize::new(0),
+            input_recording: Mutex::new(None),
+            held_keys: Mutex::new(BTreeSet::new()),
+            runtime_flag_originals: Mutex::new(BTreeMap::new()),
+            studio_setting_originals: Mutex::new(BTreeMap::new()),
+            session_resources: Mutex::new(SessionResources::default()),
+            sessions: Default::default(),
+            activity: Notify::new(),
+            log_archive,
+            capture_peers: CapturePeers::new(&config.capture_dir),
+            path_index: config.tree_index.then(Default::default),
+            response_cache: config
+                .cache_ttl
+                .map(|ttl| std::sync::Mutex::new(ResponseCache::new(config.cache_size, ttl))),
+            script_sources: Default::default(),
+            variables: Default::default(),
+            tool_history: std::sync::Mutex::new(ToolHistory::new(config.tool_history_size)),
+            async_calls: std::sync::Mutex::new(AsyncCalls::new(providers.clock.clone())),
+            wall_clock: std::sync::Mutex::new(WallClockWatch::new(
+                Instant::now(),
+                providers.clock.wall_now(),
+            )),
+            gui_snapshots: Default::default(),
+            test_timings: Default::default(),
+            place_stats: Default::default(),
+            redactor,
+            sanitizer: Sanitizer::new(config.ansi_markers),
+            config,
+            settings: std::sync::RwLock::new(Arc::new(settings)),
+            tool_calls_by_client: std::sync::Mutex::new(BTreeMap::new()),
+            launch,
+            started_at: Instant::now(),
+            event_order: Mutex::new(EventOrdering::default()),
+            ids: Arc::new(IdGenerator::new(providers.rng)),
+            clock: providers.clock,
+            events: broadcast::channel(server_events::CHANNEL_CAPACITY).0,
+            alerting,
+            hooks,
+            auth_failures: std::sync::Mutex::new(VecDeque::with_capacity(AUTH_FAILURE_THRESHOLD)),
+            bridge_requests: RollingCounter::new(Instant::now()),
+            http_budget_warned: AtomicBool::new(false),
+            background_level: AtomicU8::new(BackgroundLevel::Full as u8),
+            shutdown: watch::channel(None).0,
+            stdio_running: watch::channel(false).0,
         }))
     }
 
-    pub fn capture_dir(&self) -> &PathBuf {
-        &self.0.capture_dir
+    /// A state on `config` with its startup profile (`Settings::startup`, as the server
+    /// resolves it) and no log archive, for unit tests.
+    #[cfg(test)]
+    pub(crate) fn for_tests(config: Config) -> SharedState {
+        let settings = Settings::startup(&config).unwrap();
+        let redactor = Redactor::new(&config.redact_patterns).unwrap();
+        Self::new(config, settings, LaunchInfo::default(), redactor, None)
     }
 
-    // ─── Client Management ────────────────────────────────────
+    /// Launch mode, transports and optional features, for studio-status and /health.
+    pub fn runtime_info(&self) -> serde_json::Value {
+        crate::build_info::runtime_info(
+            &self.0.config,
+            &self.0.launch,
+            self.0.started_at,
+            self.clock(),
+        )
+    }
 
-    pub async fn register_client(&self, client_id: String, plugin_version: String) {
-        let mut clients = self.0.clients.lock().await;
-        clients.insert(
-            client_id,
-            ClientState {
-                plugin_version,
-                outbound_queue: VecDeque::new(),
-                notify: Arc::new(Notify::new()),
-                last_poll: chrono::Utc::now(),
-            },
-        );
+    /// Whether `YIPPIE_DETERMINISTIC` is on.
+    pub fn is_deterministic(&self) -> bool {
+        self.0.config.deterministic_seed.is_some()
     }
 
-    pub async fn remove_client(&self, client_id: &str) {
-        self.0.clients.lock().await.remove(client_id);
+    /// Clock for everything reported to clients: timestamps, durations and ages.
+    pub fn clock(&self) -> &dyn Clock {
+        self.0.clock.as_ref()
     }
 
-    /// Remove clients that haven't polled in over 60 seconds.
-    pub async fn prune_stale_clients(&self) {
-        let mut clients = self.0.clients.lock().await;
-        let cutoff = chrono::Utc::now() - chrono::Duration::seconds(60);
-        let stale: Vec<String> = clients
-            .iter()
-            .filter(|(_, c)| c.last_poll < cutoff)
-            .map(|(k, _)| k.clone())
-            .collect();
-        for key in &stale {
-            tracing::info!(client_id = %key, "Removing stale client (no poll in 60s)");
-            clients.remove(key);
-        }
+    /// A new id that sorts after every id issued before it (see `ids.rs`).
+    pub fn next_id(&self) -> String {
+        self.0.ids.next()
     }
 
-    pub async fn has_connected_client(&self) -> bool {
-        self.prune_stale_clients().await;
-        !self.0.clients.lock().await.is_empty()
+    /// Open the capture directory with the configured OS-capture policy.
+    pub fn capture_manager(&self) -> anyhow::Result<CaptureManager> {
+        CaptureManager::new(
+            &self.0.config.capture_dir,
+            !self.0.config.disable_os_capture,
+            self.0.ids.clone(),
+            self.0.clock.clone(),
+        )
     }
 
-    pub async fn connected_client_count(&self) -> usize {
-        self.prune_stale_clients().await;
-        self.0.clients.lock().await.len()
+    /// Run `f` with a capture manager (writing to `output_dir` if given) on the blocking
+    /// pool. Saving updates `index.json`, which can wait seconds for another process's
+    /// lock; async code goes through here so that wait never holds a runtime worker.
+    pub async fn with_captures<T, F>(
+        &self,
+        output_dir: Option<&std::path::Path>,
+        f: F,
+    ) -> anyhow::Result<T>
+    where
+        F: FnOnce(CaptureManager) -> anyhow::Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        // Made here: the manager picks up the calling session's MCP client
+        let captures = self.capture_manager()?.with_output_dir(output_dir);
+        tokio::task::spawn_blocking(move || f(captures))
+            .await
+            .map_err(|e| anyhow::anyhow!("capture task failed: {e}"))?
     }
 
-    pub async fn first_client_id(&self) -> Option<String> {
-        self.0.clients.lock().await.keys().next().cloned()
+    /// Other servers using the capture folder.
+    pub fn capture_peers(&self) -> &CapturePeers {
+        &self.0.capture_peers
     }
 
-    /// Get info about all connected clients for status reporting.
-    pub async fn client_info(&self) -> Vec<(String, String, chrono::DateTime<chrono::Utc>, bool)> {
+    /// Prefix for tool names as MCP clients see them (`YIPPIE_TOOL_PREFIX`).
+    pub fn tool_prefix(&self) -> &str {
+        &self.0.config.tool_prefix
+    }
+
+    /// Lead buffer for latency-compensated virtualuser sequences (`YIPPIE_INPUT_LEAD_MS`).
+    pub fn input_lead(&self) -> Duration {
+        self.0.config.input_lead
+    }
+
+    /// Normalizers for `studio-logs_diff_sessions`.
+    pub fn log_diff_normalizers(&self) -> &[crate::log_diff::Normalizer] {
+        &self.0.config.log_diff_normalizers
+    }
+
+    /// Longest MCP message accepted on stdin (`YIPPIE_MAX_REQUEST_BYTES`).
+    pub fn max_request_bytes(&self) -> usize {
+        self.0.config.max_request_bytes
+    }
+
+    /// Whether stdio sessions skip the MCP handshake checks (`YIPPIE_LENIENT_LIFECYCLE`).
+    pub fn lenient_lifecycle(&self) -> bool {
+        self.0.config.lenient_lifecycle
+    }
+
+    /// Port the HTTP bridge listens on.
+    pub fn bridge_port(&self) -> u16 {
+        self.0.config.port
+    }
+
+    pub fn experimental_tools_enabled(&self) -> bool {
+        self.0.config.experimental_tools
+    }
+
+    /// Debug-only tools and endpoints are offered (`YIPPIE_DEBUG_ENDPOINTS`).
+    pub fn debug_endpoints_enabled(&self) -> bool {
+        self.0.config.debug_endpoints
+    }
+
+    /// Switched off by the operator with `YIPPIE_DISABLED_TOOLS`.
+    pub fn tool_disabled_by_policy(&self, tool_name: &str) -> bool {
+        self.0.config.disabled_tools.contains(tool_name)
+    }
+
+    /// Services watched for the path index (`YIPPIE_TREE_ROOTS`).
+    pub fn tree_roots(&self) -> &[String] {
+        &self.0.config.tree_roots
+    }
+
+    /// The instance path index, if `YIPPIE_TREE_INDEX` is on.
+    pub fn path_index(&self) -> Option<std::sync::MutexGuard<'_, PathIndex>> {
+        let index = self.0.path_index.as_ref()?;
+        Some(index.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Start a background path index refresh unless one is already running.
+    pub fn request_tree_refresh(&self) {
+        if self
+            .path_index()
+            .is_some_and(|mut index| index.begin_refresh())
+        {
+            tokio::spawn(crate::path_index::refresh(self.clone()));
+        }
+    }
+
+    /// The response cache, unless disabled (`YIPPIE_CACHE_TTL_SECS=0`).
+    pub fn response_cache(&self) -> Option<std::sync::MutexGuard<'_, ResponseCache>> {
+        let cache = self.0.response_cache.as_ref()?;
+        Some(cache.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Drop every cached result.
+    pub fn invalidate_response_cache(&self) {
+        if let Some(mut cache) = self.response_cache() {
+            cache.clear();
+        }
+    }
+
+    pub fn script_sources(&self) -> std::sync::MutexGuard<'_, SourceCache> {
         self.0
-            .clients
+            .script_sources
             .lock()
-            .await
-            .iter()
-            .map(|(k, c)| (k.clone(), c.plugin_version.clone(), c.last_poll, c.is_playtest_bridge()))
-            .collect()
+            .unwrap_or_else(|e| e.into_inner())
     }
 
-    // ─── Tool Request Queuing ─────────────────────────────────
+    pub fn variables(&self) -> std::sync::MutexGuard<'_, VariableStore> {
+        self.0.variables.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-    /// Enqueue a tool request to the appropriate client based on tool name.
-    ///
-    /// During playtest, two clients are registered: the main plugin and the playtest bridge.
-    /// Tools that run during playtest (virtualuser, npc_driver, playtest_stop, logs) go to the
-    /// bridge. Tools that must run in the plugin context (test_script, run_script, checkpoint,
-    /// playtest_play/run) go to the main plugin client.
-    ///
-    /// Falls back to most recently polled client if the preferred target isn't available.
-    pub async fn enqueue_tool_request(&self, request: BridgeToolRequest) -> bool {
-        let mut clients = self.0.clients.lock().await;
-        if clients.is_empty() {
-            return false;
-        }
+    /// Whether tool results with tracebacks get source context
+    /// (off with `YIPPIE_DISABLE_TRACEBACK_CONTEXT`).
+    pub fn traceback_context_enabled(&self) -> bool {
+        self.0.config.traceback_context
+    }
 
-        let prefers_bridge = matches!(
-            request.tool_name.as_str(),
-            "studio-virtualuser_key"
-                | "studio-virtualuser_mouse_button"
-                | "studio-virtualuser_move_mouse"
-                | "studio-npc_driver_start"
-                | "studio-npc_driver_command"
-                | "studio-npc_driver_stop"
-                | "studio-playtest_stop"
-        );
+    // ─── Configuration Profile ────────────────────────────────
 
-        // Find the target client key
-        let target_key = {
-            // First try to find the preferred client type
-            let preferred = clients.iter().find_map(|(k, c)| {
-                if prefers_bridge == c.is_playtest_bridge() {
-                    Some(k.clone())
-                } else {
-                    None
-                }
-            });
+    /// The settings in effect. A tool call reads them once, so a profile switch
+    /// doesn't change a call already in flight.
+    pub fn settings(&self) -> Arc<Settings> {
+        self.0
+            .settings
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
 
-            // Fall back to most recently polled client
-            preferred.or_else(|| {
-                clients
-                    .iter()
-                    .max_by_key(|(_, c)| c.last_poll)
-                    .map(|(k, _)| k.clone())
-            })
-        };
+    /// Configured profiles by name.
+    pub fn profiles(&self) -> &BTreeMap<String, Profile> {
+        &self.0.config.profiles
+    }
 
-        let total_clients = clients.len();
-        if let Some(key) = target_key {
-            if let Some(client) = clients.get_mut(&key) {
-                tracing::info!(
-                    tool = %request.tool_name,
-                    client_id = %key,
-                    is_bridge = client.is_playtest_bridge(),
-                    prefers_bridge = prefers_bridge,
-                    total_clients = total_clients,
-                    "Routing tool request"
-                );
-                client.outbound_queue.push_back(request);
-                client.notify.notify_one();
-                return true;
+    /// Activate the profile that lists this MCP client, unless a profile was chosen at
+    /// startup (`YIPPIE_PROFILE` / `--profile`), which always wins. The profile is
+    /// server-wide, so a client can't leave a read-only profile active for another.
+    pub fn match_client_profile(&self, client_name: &str) {
+        if self.0.config.profile.is_some() {
+            return;
+        }
+        if let Some(name) = profiles::for_client(&self.0.config.profiles, client_name) {
+            if let Err(e) = self.switch_profile(name, profiles::keep_read_only) {
+                tracing::warn!(client = client_name, error = %e, "Could not select the client's profile");
             }
         }
-        tracing::warn!("No client found for tool request");
-        false
     }
 
-    /// Drain all pending outbound requests for a client.
-    pub async fn drain_outbound(&self, client_id: &str) -> Vec<BridgeToolRequest> {
-        let mut clients = self.0.clients.lock().await;
-        if let Some(client) = clients.get_mut(client_id) {
-            client.last_poll = chrono::Utc::now();
-            let requests: Vec<BridgeToolRequest> = client.outbound_queue.drain(..).collect();
-            if !requests.is_empty() {
-                let names: Vec<&str> = requests.iter().map(|r| r.tool_name.as_str()).collect();
-                tracing::info!(
-                    client_id = %client_id,
-                    is_bridge = client.is_playtest_bridge(),
-                    tools = ?names,
-                    "Client drained requests"
-                );
-            }
-            requests
-        } else {
-            vec![]
-        }
+    /// Tool calls per MCP client name, for `/metrics`.
+    pub fn count_tool_call(&self, client: Option<&str>) {
+        let mut counts = self
+            .0
+            .tool_calls_by_client
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        *counts
+            .entry(client.unwrap_or("unknown").to_string())
+            .or_insert(0) += 1;
     }
 
-    /// Get the Notify handle for long-poll wakeup.
-    pub async fn get_notify(&self, client_id: &str) -> Option<Arc<Notify>> {
-        let clients = self.0.clients.lock().await;
-        clients.get(client_id).map(|c| c.notify.clone())
+    pub fn tool_calls_by_client(&self) -> BTreeMap<String, u64> {
+        self.0
+            .tool_calls_by_client
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
     }
 
-    // ─── Pending Calls ────────────────────────────────────────
+    /// Snapshots kept by studio-place_stats.
+    pub fn place_stats(&self) -> std::sync::MutexGuard<'_, StatsStore> {
+        self.0.place_stats.lock().unwrap_or_else(|e| e.into_inner())
+    }
 
-    pub async fn register_pending(
-        &self,
-        request_id: String,
-        sender: oneshot::Sender<BridgeToolResponse>,
-    ) {
+    pub fn async_calls(&self) -> std::sync::MutexGuard<'_, AsyncCalls> {
+        self.0.async_calls.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// GUI snapshots kept by studio-gui_tree and studio-gui_click.
+    pub fn gui_snapshots(&self) -> std::sync::MutexGuard<'_, SnapshotStore> {
         self.0
-            .pending_calls
+            .gui_snapshots
             .lock()
-            .await
-            .insert(request_id, sender);
+            .unwrap_or_else(|e| e.into_inner())
     }
 
-    /// Resolve a pending call. Returns true if the call was found and resolved.
-    pub async fn resolve_pending(&self, request_id: &str, response: BridgeToolResponse) -> bool {
-        if let Some(sender) = self.0.pending_calls.lock().await.remove(request_id) {
-            let _ = sender.send(response);
+    /// Phase timings of test_script calls, kept for studio-time_report.
+    pub fn test_timings(&self) -> std::sync::MutexGuard<'_, TestTimings> {
+        self.0
+            .test_timings
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Recent tool calls, shared by every MCP session of this server.
+    pub fn tool_history(&self) -> std::sync::MutexGuard<'_, ToolHistory> {
+        self.0
+            .tool_history
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make profile `name` (or `default`) active if `allow` accepts the change from the
+    /// current settings. The check and the swap happen under one lock. Returns the
+    /// previous and new settings.
+    pub fn switch_profile(
+        &self,
+        name: &str,
+        allow: impl FnOnce(&Settings, &Settings) -> Result<(), String>,
+    ) -> Result<(Arc<Settings>, Arc<Settings>), String> {
+        let next = Arc::new(Settings::resolve(&self.0.config, name)?);
+        let mut current = self.0.settings.write().unwrap_or_else(|e| e.into_inner());
+        allow(&current, &next)?;
+        let previous = std::mem::replace(&mut *current, next.clone());
+        drop(current);
+        tracing::info!(
+            from = previous.name(),
+            to = next.name(),
+            "Switched configuration profile"
+        );
+        Ok((previous, next))
+    }
+
+    // ─── Shutdown ─────────────────────────────────────────────
+
+    /// Ask whoever runs the server to shut it down; `requested_by` names the caller
+    /// for the log. False if a shutdown was already requested.
+    pub fn request_shutdown(&self, requested_by: String) -> bool {
+        self.0.shutdown.send_if_modified(|current| {
+            if current.is_some() {
+                return false;
+            }
+            *current = Some(requested_by);
             true
-        } else {
-            false
+        })
+    }
+
+    /// Whether a shutdown has been requested.
+    pub fn shutdown_pending(&self) -> bool {
+        self.0.shutdown.borrow().is_some()
+    }
+
+    /// Resolves with the requester once a shutdown has been requested.
+    pub async fn shutdown_requested(&self) -> String {
+        let mut rx = self.0.shutdown.subscribe();
+        // The sender lives in `self`, so the channel can't close while this waits
+        rx.wait_for(Option::is_some)
+            .await
+            .map(|r| r.clone().unwrap_or_default())
+            .unwrap_or_default()
+    }
+
+    /// Note that the MCP stdio loop is about to read its first request.
+    pub fn mark_stdio_running(&self) {
+        self.0.stdio_running.send_replace(true);
+    }
+
+    /// Resolves once the MCP stdio loop is running.
+    pub async fn stdio_running(&self) {
+        let mut rx = self.0.stdio_running.subscribe();
+        // The sender lives in `self`, so the channel can't close while this waits
+        let _ = rx.wait_for(|running| *running).await;
+    }
+
+    // ─── Server Events ────────────────────────────────────────
+
+    /// Broadcast an internal event. Dropped when nothing subscribes.
+    pub fn emit(&self, kind: &'static str, data: serde_json::Value) {
+        let _ = self.0.events.send(ServerEvent::new(kind, data));
+    }
+
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ServerEvent> {
+        self.0.events.subscribe()
+    }
+
+    /// Alerting hooks, if any rules are configured.
+    pub fn alerting(&self) -> Option<Arc<Alerting>> {
+        self.0.alerting.clone()
+    }
+
+    pub fn hooks(&self) -> Option<Arc<Hooks>> {
+        self.0.hooks.clone()
+    }
+
+    /// Count a rejected bridge request; every `AUTH_FAILURE_THRESHOLD` failures within
+    /// `AUTH_FAILURE_WINDOW` raise one `auth_failures` event.
+    pub fn record_auth_failure(&self) {
+        let mut failures = self
+            .0
+            .auth_failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        while failures
+            .front()
+            .is_some_and(|t| now.duration_since(*t) > AUTH_FAILURE_WINDOW)
+        {
+            failures.pop_front();
+        }
+        failures.push_back(now);
+        if failures.len() >= AUTH_FAILURE_THRESHOLD {
+            failures.clear();
+            drop(failures);
+            self.emit(
+                server_events::AUTH_FAILURES,
+                serde_json::json!({
+                    "count": AUTH_FAILURE_THRESHOLD,
+                    "windowSecs": AUTH_FAILURE_WINDOW.as_secs(),
+                }),
+            );
         }
     }
 
-    pub async fn pending_call_count(&self) -> usize {
-        self.0.pending_calls.lock().await.len()
+    // ─── HttpService Budget ───────────────────────────────────
+
+    /// Bridge requests per minute at which the budget warning starts.
+    fn http_budget_warn_at(&self) -> u64 {
+        (traffic::ROBLOX_HTTP_LIMIT_PER_MIN as f64 * self.0.config.http_budget_warn).ceil() as u64
     }
 
-    // ─── Log Buffer ───────────────────────────────────────────
+    /// Count one bridge request (register, pull or push) toward the HttpService budget.
+    /// Crossing the warning threshold logs a warning and raises an `http_budget` event;
+    /// falling back below it logs once more.
+    pub fn record_bridge_request(&self) {
+        self.0.bridge_requests.add(1);
+        let last_minute = self.0.bridge_requests.sum(traffic::ONE_MINUTE);
+        let warn_at = self.http_budget_warn_at();
+        // Load first: the flag rarely changes, and every request gets here
+        let warned = self.0.http_budget_warned.load(Ordering::Relaxed);
+        let cleared = (last_minute as f64) < warn_at as f64 * HTTP_BUDGET_CLEAR_RATIO;
+        if !warned
+            && last_minute >= warn_at
+            && !self.0.http_budget_warned.swap(true, Ordering::Relaxed)
+        {
+            tracing::warn!(
+                requests_last_minute = last_minute,
+                limit = traffic::ROBLOX_HTTP_LIMIT_PER_MIN,
+                "{}",
+                http_budget_warning(last_minute, warn_at)
+            );
+            self.emit(
+                server_events::HTTP_BUDGET,
+                serde_json::json!({
+                    "requestsLastMinute": last_minute,
+                    "limitPerMinute": traffic::ROBLOX_HTTP_LIMIT_PER_MIN,
+                    "warnAt": warn_at,
+                }),
+            );
+        } else if warned && cleared && self.0.http_budget_warned.swap(false, Ordering::Relaxed) {
+            tracing::info!(
+                requests_last_minute = last_minute,
+                "Bridge traffic back under the HttpService budget warning"
+            );
+        }
 
-    pub async fn push_log(&self, level: String, message: String, session_id: Option<String>) {
-        let mut seq = self.0.log_seq.lock().await;
-        *seq += 1;
-        let entry = LogEntry {
-            seq: *seq,
-            ts: chrono::Utc::now().timestamp_millis() as f64 / 1000.0,
-            level,
-            message,
-            session_id,
-        };
-        drop(seq);
+        let level = self.background_level();
+        let next = level.next(last_minute, warn_at);
+        if next != level
+            && self
+                .0
+                .background_level
+                .compare_exchange(
+                    level as u8,
+                    next as u8,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+        {
+            tracing::info!(requests_last_minute = last_minute, from = ?level, to = ?next, "Background traffic level changed");
+        }
+    }
 
-        let mut buf = self.0.log_buffer.lock().await;
-        if buf.len() >= MAX_LOG_BUFFER {
-            buf.pop_front();
+    /// Background traffic currently allowed (see `traffic::BackgroundLevel`).
+    pub fn background_level(&self) -> BackgroundLevel {
+        BackgroundLevel::from_u8(self.0.background_level.load(Ordering::Relaxed))
+    }
+
+    /// Combined bridge request rate against the HttpService limit, for studio-status,
+    /// /clients and /metrics.
+    pub fn http_budget(&self) -> HttpBudget {
+        let last_minute = self.0.bridge_requests.sum(traffic::ONE_MINUTE);
+        let warn_at = self.http_budget_warn_at();
+        let warned = self.0.http_budget_warned.load(Ordering::Relaxed);
+        HttpBudget {
+            requests_last_minute: last_minute,
+            limit_per_minute: traffic::ROBLOX_HTTP_LIMIT_PER_MIN,
+            warn_at,
+            warning: warned.then(|| http_budget_warning(last_minute, warn_at)),
+            background_level: self.background_level(),
         }
-        buf.push_back(entry);
     }
 
-    pub async fn get_logs(&self, since_seq: u64, limit: usize) -> Vec<LogEntry> {
-        let buf = self.0.log_buffer.lock().await;
-        buf.iter()
-            .filter(|e| e.seq > since_seq)
-            .take(limit)
-            .cloned()
-            .collect()
+    // ─── Redaction ────────────────────────────────────────────
+
+    /// Cleans escape sequences and control characters out of text from Studio.
+    pub fn sanitizer(&self) -> Sanitizer {
+        self.0.sanitizer
     }
 
-    pub async fn log_buffer_size(&self) -> usize {
-        self.0.log_buffer.lock().await.len()
+    /// Replace secrets in text bound for the log buffer or the MCP client.
+    pub fn redact<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        self.0.redactor.redact(text)
     }
 
-    // ─── Playtest State ───────────────────────────────────────
+    /// Tool arguments for a debug log line, with code shortened per `YIPPIE_LOG_CODE`.
+    pub fn arguments_for_log(&self, arguments: &serde_json::Value) -> String {
+        crate::log_args::render(arguments, self.settings().log_code, &self.0.redactor)
+    }
 
-    pub async fn update_playtest(&self, active: bool, session_id: Option<String>, mode: Option<String>) {
-        let mut state = self.0.playtest_state.lock().await;
-        state.active = active;
-        state.session_id = session_id;
-        state.mode = mode;
+    /// Redact every string in a JSON value, uncounted (see `Redactor::scrub_value`).
+    pub fn redact_value(&self, value: &mut serde_json::Value) -> bool {
+        self.0.redactor.scrub_value(value)
     }
 
-    pub async fn is_playtest_active(&self) -> bool {
-        self.0.playtest_state.lock().await.active
+    pub fn redaction_counts(&self) -> std::collections::BTreeMap<String, u64> {
+        self.0.redactor.counts()
     }
 
-    pub async fn playtest_info(&self) -> (bool, Option<String>, Option<String>) {
-        let state = self.0.playtest_state.lock().await;
-        (state.active, state.session_id.clone(), state.mode.clone())
+    // ─── Client Management ────────────────────────────────────
+
+    /// Held while numbered events are applied, so one client's events go through
+    /// in sequence order even when two pushes are handled concurrently.
+    pub async fn event_order(&self) -> tokio::sync::MutexGuard<'_, EventOrdering> {
+        self.0.event_order.lock().await
+    }
+
+    pub async fn register_client(
+        &self,
+        client_id: String,
+        plugin_version: String,
+        instance_key: Option<String>,
+        casing: Casing,
+    ) {
+        let client = ClientState {
+            plugin_version,
+            instance_key,
+            casing,
+            outbound_queue: OutboundQueue::default(),
+            notify: Arc::new(Notify::new()),
+            last_poll: chrono::Utc::now(),
+            last_poll_at: Instant::now(),
+            settings: None,
+            traffic: Arc::new(ClientTraffic::new()),
+            clock_offset: Arc::new(ClockOffset::new(self.0.started_at)),
+        };
+        let owner = (!client.is_playtest_bridge()).then(|| client.owner_key(&client_id));
+        self.emit(
+            server_events::CLIENT_CONNECTED,
+            client.event_data(&client_id),
+        );
+        self.0.clients.lock().await.insert(client_id, client);
+        // A new plugin may be a different Studio
+        self.invalidate_response_cache();
+        if let Some(owner) = owner {
+            self.reconcile_checkpoints_on_register(&owner).await;
+            // A newly registered plugin has no tree delta stream yet
+            self.request_tree_refresh();
+        }
+    }
+
+    pub async fn remove_client(&self, client_id: &str) {
+        let removed = self.0.clients.lock().await.remove(client_id);
+        let Some(client) = removed else {
+            return;
+        };
+        self.emit_disconnected(client_id, &client, "removed");
+        self.invalidate_response_cache();
+        self.0.event_order.lock().await.clients.remove(client_id);
+        if client.is_playtest_bridge() {
+            self.clear_held_keys("playtest bridge removed").await;
+            self.update_playtest_readiness(None, None).await;
+        } else {
+            self.mark_checkpoints_disconnected(&[client.owner_key(client_id)])
+                .await;
+        }
+    }
+
+    fn emit_disconnected(&self, client_id: &str, client: &ClientState, reason: &str) {
+        let mut data = client.event_data(client_id);
+        data["reason"] = reason.into();
+        self.emit(server_events::CLIENT_DISCONNECTED, data);
+    }
+
+    /// Read the wall clock and start a grace period for stale clients if it jumped
+    /// since the last reading (see `wall_clock.rs`).
+    pub fn check_wall_clock(&self) {
+        let mut watch = self.0.wall_clock.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(jump) = watch.check(Instant::now(), self.0.clock.wall_now()) {
+            tracing::warn!(
+                offset_secs = jump.offset_secs,
+                grace_secs = crate::wall_clock::GRACE.as_secs(),
+                "System clock jumped (sleep/wake or clock change); not pruning stale clients during the grace period"
+            );
+        }
+    }
+
+    /// Remove clients that haven't polled for `STALE_CLIENT_AFTER`, except during the
+    /// grace period after a wall-clock jump.
+    pub async fn prune_stale_clients(&self) {
+        self.check_wall_clock();
+        if self
+            .0
+            .wall_clock
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .in_grace(Instant::now())
+        {
+            return;
+        }
+        let mut clients = self.0.clients.lock().await;
+        let stale: Vec<String> = clients
+            .iter()
+            .filter(|(_, c)| c.last_poll_at.elapsed() >= STALE_CLIENT_AFTER)
+            .map(|(k, _)| k.clone())
+            .collect();
+        let mut bridge_removed = false;
+        let mut removed_owners = Vec::new();
+        for key in &stale {
+            tracing::info!(client_id = %key, "Removing stale client (no poll in 60s)");
+            if let Some(client) = clients.remove(key) {
+                self.emit_disconnected(key, &client, "stale");
+                if client.is_playtest_bridge() {
+                    bridge_removed = true;
+                } else {
+                    removed_owners.push(client.owner_key(key));
+                }
+            }
+        }
+        drop(clients);
+        if !stale.is_empty() {
+            self.invalidate_response_cache();
+            let mut order = self.0.event_order.lock().await;
+            for key in &stale {
+                order.clients.remove(key);
+            }
+        }
+        if bridge_removed {
+            self.clear_held_keys("playtest bridge went stale").await;
+            self.update_playtest_readiness(None, None).await;
+        }
+        if !removed_owners.is_empty() {
+            self.mark_checkpoints_disconnected(&removed_owners).await;
+        }
+    }
+
+    /// Owner key of a registered client, for attributing checkpoints to it.
+    pub async fn client_owner_key(&self, client_id: &str) -> Option<String> {
+        self.0
+            .clients
+            .lock()
+            .await
+            .get(client_id)
+            .map(|c| c.owner_key(client_id))
+    }
+
+    /// A main plugin registered. Checkpoints of the same instance (a reconnect) are
+    /// recovered; those of any other instance are marked lost for good, since a new
+    /// instance means the old plugin (and its recordings) is gone.
+    async fn reconcile_checkpoints_on_register(&self, owner: &str) {
+        let mut resources = self.0.session_resources.lock().await;
+        for (checkpoint_id, checkpoint) in resources.open_checkpoints.iter_mut() {
+            if checkpoint.owner == owner {
+                if checkpoint.lost == Some(CheckpointLoss::Disconnected) {
+                    tracing::info!(checkpoint_id = %checkpoint_id, "Plugin reconnected; checkpoint is usable again");
+                    checkpoint.lost = None;
+                }
+            } else if checkpoint.lost != Some(CheckpointLoss::Reloaded) {
+                tracing::warn!(
+                    checkpoint_id = %checkpoint_id,
+                    name = %checkpoint.name,
+                    "New plugin instance registered; checkpoint recording is unrecoverable"
+                );
+                checkpoint.lost = Some(CheckpointLoss::Reloaded);
+            }
+        }
+    }
+
+    /// Mark checkpoints of the given owners as disconnected, unless another client of
+    /// the same instance is still registered.
+    async fn mark_checkpoints_disconnected(&self, owners: &[String]) {
+        let live: HashSet<String> = {
+            let clients = self.0.clients.lock().await;
+            clients.iter().map(|(id, c)| c.owner_key(id)).collect()
+        };
+        let mut resources = self.0.session_resources.lock().await;
+        for (checkpoint_id, checkpoint) in resources.open_checkpoints.iter_mut() {
+            if checkpoint.lost.is_none()
+                && owners.contains(&checkpoint.owner)
+                && !live.contains(&checkpoint.owner)
+            {
+                tracing::warn!(
+                    checkpoint_id = %checkpoint_id,
+                    name = %checkpoint.name,
+                    "Plugin holding checkpoint was removed; checkpoint is unrecoverable unless it reconnects"
+                );
+                checkpoint.lost = Some(CheckpointLoss::Disconnected);
+            }
+        }
+    }
+
+    pub async fn has_connected_client(&self) -> bool {
+        self.prune_stale_clients().await;
+        !self.0.clients.lock().await.is_empty()
+    }
+
+    pub async fn connected_client_count(&self) -> usize {
+        self.prune_stale_clients().await;
+        self.0.clients.lock().await.len()
+    }
+
+    /// True if a playtest bridge client is registered. The bridge only runs inside a
+    /// playtest, so this also covers playtests started by hand in Studio.
+    pub async fn has_playtest_bridge(&self) -> bool {
+        self.0
+            .clients
+            .lock()
+            .await
+            .values()
+            .any(|c| c.is_playtest_bridge())
+    }
+
+    pub async fn first_client_id(&self) -> Option<String> {
+        self.0.clients.lock().await.keys().next().cloned()
+    }
+
+    /// What a plugin's settings are checked against, as of now, for a session that
+    /// does or doesn't hold a log subscription.
+    fn settings_expectations(&self, logs_subscribed: bool) -> Expectations {
+        Expectations {
+            port: self.0.config.port,
+            stale_after: STALE_CLIENT_AFTER,
+            logs_subscribed,
+        }
+    }
+
+    /// Store a client's reported settings and log the warnings that appeared or
+    /// cleared compared with its previous settings.
+    pub async fn set_plugin_settings(&self, client_id: &str, settings: PluginSettings) {
+        let expect = self.settings_expectations(self.logs_subscribed());
+        let mut clients = self.0.clients.lock().await;
+        let Some(client) = clients.get_mut(client_id) else {
+            return;
+        };
+        let before: Vec<SettingsWarning> = client
+            .settings
+            .as_ref()
+            .map(|s| plugin_settings::validate(s, &expect))
+            .unwrap_or_default();
+        let after = plugin_settings::validate(&settings, &expect);
+        for warning in after.iter().filter(|w| !before.contains(w)) {
+            tracing::warn!(
+                client_id,
+                code = warning.code,
+                "Plugin settings: {}",
+                warning.message
+            );
+        }
+        for warning in before
+            .iter()
+            .filter(|w| !after.iter().any(|a| a.code == w.code))
+        {
+            tracing::info!(
+                client_id,
+                code = warning.code,
+                "Plugin settings warning cleared"
+            );
+        }
+        client.settings = Some(settings);
+    }
+
+    /// Current settings warnings as `(clientId, warning)`, for a session that does or
+    /// doesn't hold a log subscription.
+    pub async fn plugin_settings_warnings(
+        &self,
+        logs_subscribed: bool,
+    ) -> Vec<(String, SettingsWarning)> {
+        let expect = self.settings_expectations(logs_subscribed);
+        self.0
+            .clients
+            .lock()
+            .await
+            .iter()
+            .filter_map(|(client_id, client)| Some((client_id, client.settings.as_ref()?)))
+            .flat_map(|(client_id, settings)| {
+                plugin_settings::validate(settings, &expect)
+                    .into_iter()
+                    .map(|warning| (client_id.clone(), warning))
+            })
+            .collect()
+    }
+
+    /// Reported settings and current warnings of every client that sent settings,
+    /// as seen by a session that does or doesn't hold a log subscription.
+    pub async fn plugin_settings_report(&self, logs_subscribed: bool) -> Vec<serde_json::Value> {
+        let expect = self.settings_expectations(logs_subscribed);
+        self.0
+            .clients
+            .lock()
+            .await
+            .iter()
+            .filter_map(|(client_id, client)| {
+                let settings = client.settings.as_ref()?;
+                Some(serde_json::json!({
+                    "clientId": client_id,
+                    "settings": settings,
+                    "warnings": plugin_settings::validate(settings, &expect),
+                }))
+            })
+            .collect()
+    }
+
+    /// Get info about all connected clients for status reporting.
+    pub async fn client_info(&self) -> Vec<(String, String, chrono::DateTime<chrono::Utc>, bool)> {
+        self.0
+            .clients
+            .lock()
+            .await
+            .iter()
+            .map(|(k, c)| {
+                (
+                    k.clone(),
+                    c.plugin_version.clone(),
+                    c.last_poll,
+                    c.is_playtest_bridge(),
+                )
+            })
+            .collect()
+    }
+
+    /// Where `enqueue_tool_request` would send each of `tools` right now, and the
+    /// clients it picks from. For `studio-list_tools_with_routing` and `/debug/routing`.
+    pub async fn routing_report(&self, tools: &[String]) -> serde_json::Value {
+        let role = |is_bridge: bool| {
+            if is_bridge {
+                Role::Bridge.as_str()
+            } else {
+                Role::Plugin.as_str()
+            }
+        };
+        let clients = self.0.clients.lock().await;
+        let routes: Vec<serde_json::Value> = tools
+            .iter()
+            .map(|tool| {
+                let prefers_bridge = self.prefers_bridge(tool);
+                let target = route_target(&clients, prefers_bridge);
+                let target_is_bridge = target.as_ref().and_then(|key| clients.get(key)).map(ClientState::is_playtest_bridge);
+                serde_json::json!({
+                    "tool": tool,
+                    "prefers": role(prefers_bridge),
+                    "overridden": self.0.config.routing_overrides.contains_key(tool),
+                    "routesTo": target,
+                    "routesToRole": target_is_bridge.map(role),
+                    // No client of the preferred role, so the most recently polled one gets it
+                    "fallback": target_is_bridge.is_some_and(|is_bridge| is_bridge != prefers_bridge),
+                })
+            })
+            .collect();
+        let clients: Vec<serde_json::Value> = clients
+            .iter()
+            .map(|(key, client)| {
+                serde_json::json!({
+                    "clientId": key,
+                    "role": role(client.is_playtest_bridge()),
+                    "pluginVersion": client.plugin_version,
+                    "lastPollMsAgo": client.last_poll_at.elapsed().as_millis() as u64,
+                    "queued": client.outbound_queue.depths(),
+                })
+            })
+            .collect();
+        serde_json::json!({ "clients": clients, "tools": routes })
+    }
+
+    /// Rolling traffic counters of every client, in registration order:
+    /// (client id, plugin version, is bridge, report).
+    pub async fn traffic_reports(&self) -> Vec<(String, String, bool, TrafficReport)> {
+        let clients: Vec<_> = self
+            .0
+            .clients
+            .lock()
+            .await
+            .iter()
+            .map(|(k, c)| {
+                (
+                    k.clone(),
+                    c.plugin_version.clone(),
+                    c.is_playtest_bridge(),
+                    c.traffic.clone(),
+                )
+            })
+            .collect();
+        // Summing the rings happens after the lock is released
+        clients
+            .into_iter()
+            .map(|(id, version, is_bridge, traffic)| (id, version, is_bridge, traffic.report()))
+            .collect()
+    }
+
+    /// Record the plugin clock a client sent a request at, for its offset estimate.
+    pub async fn record_plugin_clock(&self, client_id: &str, plugin_clock: f64) {
+        let offset = self
+            .0
+            .clients
+            .lock()
+            .await
+            .get(client_id)
+            .map(|c| c.clock_offset.clone());
+        if let Some(offset) = offset {
+            offset.record(plugin_clock);
+        }
+    }
+
+    /// Clock offset of the client a call to `tool_name` would be routed to now.
+    pub async fn routed_clock_offset(&self, tool_name: &str) -> Option<Arc<ClockOffset>> {
+        let clients = self.0.clients.lock().await;
+        let key = route_target(&clients, self.prefers_bridge(tool_name))?;
+        clients.get(&key).map(|c| c.clock_offset.clone())
+    }
+
+    /// A client's traffic counters, to update outside the clients lock.
+    pub async fn client_traffic(&self, client_id: &str) -> Option<Arc<ClientTraffic>> {
+        self.0
+            .clients
+            .lock()
+            .await
+            .get(client_id)
+            .map(|c| c.traffic.clone())
+    }
+
+    // ─── Tool Request Queuing ─────────────────────────────────
+
+    /// Whether a tool goes to the playtest bridge: the operator's override
+    /// (`YIPPIE_ROUTING_OVERRIDES`) if there is one, else the built-in preference.
+    fn prefers_bridge(&self, tool_name: &str) -> bool {
+        match self.0.config.routing_overrides.get(tool_name) {
+            Some(role) => *role == Role::Bridge,
+            None => prefers_bridge(tool_name),
+        }
+    }
+
+    /// Enqueue a tool request to the appropriate client based on tool name.
+    ///
+    /// During playtest, two clients are registered: the main plugin and the playtest bridge.
+    /// Tools that run during playtest (virtualuser, npc_driver, playtest_stop, logs) go to the
+    /// bridge. Tools that must run in the plugin context (test_script, run_script, checkpoint,
+    /// playtest_play/run) go to the main plugin client.
+    ///
+    /// Falls back to most recently polled client if the preferred target isn't available.
+    /// `YIPPIE_ROUTING_OVERRIDES` can change the preferred target per tool.
+    /// Returns the id of the client the request was queued for.
+    pub async fn enqueue_tool_request(
+        &self,
+        request: BridgeToolRequest,
+        priority: Priority,
+    ) -> Option<String> {
+        let mut clients = self.0.clients.lock().await;
+        if clients.is_empty() {
+            return None;
+        }
+
+        let prefers_bridge = self.prefers_bridge(&request.tool_name);
+        let target_key = route_target(&clients, prefers_bridge);
+
+        let total_clients = clients.len();
+        if let Some(key) = target_key {
+            if let Some(client) = clients.get_mut(&key) {
+                tracing::info!(
+                    tool = %request.tool_name,
+                    client_id = %key,
+                    is_bridge = client.is_playtest_bridge(),
+                    prefers_bridge = prefers_bridge,
+                    total_clients = total_clients,
+                    ?priority,
+                    "Routing tool request"
+                );
+                client.outbound_queue.push(request, priority);
+                if priority == Priority::Interactive || self.background_level().wakes_pull() {
+                    client.notify.notify_one();
+                }
+                return Some(key);
+            }
+        }
+        tracing::warn!("No client found for tool request");
+        None
+    }
+
+    /// Take the next batch of outbound requests for a client, highest priority first
+    /// (see `outbound_queue.rs`). What doesn't fit stays for the next pull.
+    pub async fn drain_outbound(&self, client_id: &str) -> Vec<BridgeToolRequest> {
+        let mut clients = self.0.clients.lock().await;
+        if let Some(client) = clients.get_mut(client_id) {
+            client.last_poll = chrono::Utc::now();
+            client.last_poll_at = Instant::now();
+            let requests = client.outbound_queue.drain_batch(self.background_level());
+            if !requests.is_empty() {
+                let mut timings = self.test_timings();
+                for request in &requests {
+                    timings.delivered(&request.request_id, client.last_poll_at);
+                }
+                drop(timings);
+                let names: Vec<&str> = requests.iter().map(|r| r.tool_name.as_str()).collect();
+                tracing::info!(
+                    client_id = %client_id,
+                    is_bridge = client.is_playtest_bridge(),
+                    tools = ?names,
+                    remaining = ?client.outbound_queue.depths(),
+                    "Client drained requests"
+                );
+            }
+            requests
+        } else {
+            vec![]
+        }
+    }
+
+    /// Take a request out of a client's queue if the client hasn't pulled it yet.
+    pub async fn withdraw_request(&self, client_id: &str, request_id: &str) -> bool {
+        let mut clients = self.0.clients.lock().await;
+        clients
+            .get_mut(client_id)
+            .is_some_and(|client| client.outbound_queue.remove(request_id))
+    }
+
+    /// Queue a request for one client, whatever its tool would be routed to.
+    pub async fn enqueue_for_client(
+        &self,
+        client_id: &str,
+        request: BridgeToolRequest,
+        priority: Priority,
+    ) -> bool {
+        let mut clients = self.0.clients.lock().await;
+        let Some(client) = clients.get_mut(client_id) else {
+            return false;
+        };
+        client.outbound_queue.push(request, priority);
+        client.notify.notify_one();
+        true
+    }
+
+    /// Queued requests per priority, summed over clients.
+    pub async fn queue_depths(&self) -> QueueDepths {
+        let clients = self.0.clients.lock().await;
+        let mut total = QueueDepths::default();
+        for client in clients.values() {
+            total += client.outbound_queue.depths();
+        }
+        total
+    }
+
+    /// Get the Notify handle for long-poll wakeup.
+    pub async fn get_notify(&self, client_id: &str) -> Option<Arc<Notify>> {
+        let clients = self.0.clients.lock().await;
+        clients.get(client_id).map(|c| c.notify.clone())
+    }
+
+    /// The long-poll wakeup and traffic counters of a client, in one lookup.
+    pub async fn poll_handles(
+        &self,
+        client_id: &str,
+    ) -> Option<(Arc<Notify>, Arc<ClientTraffic>, Casing)> {
+        let clients = self.0.clients.lock().await;
+        clients
+            .get(client_id)
+            .map(|c| (c.notify.clone(), c.traffic.clone(), c.casing))
+    }
+
+    // ─── Pending Calls ────────────────────────────────────────
+
+    pub async fn register_pending(
+        &self,
+        request_id: String,
+        sender: oneshot::Sender<BridgeToolResponse>,
+    ) {
+        self.0.pending_calls.lock().await.insert(request_id, sender);
+    }
+
+    /// Resolve a pending call. Returns true if the call was found and resolved.
+    pub async fn resolve_pending(&self, request_id: &str, response: BridgeToolResponse) -> bool {
+        if let Some(sender) = self.0.pending_calls.lock().await.remove(request_id) {
+            let _ = sender.send(response);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Forget a pending call nobody waits for any more.
+    pub async fn remove_pending(&self, request_id: &str) {
+        self.0.pending_calls.lock().await.remove(request_id);
+    }
+
+    /// Fail every pending call with `error`, so their awaits return now instead of at
+    /// their timeouts. Late plugin responses then find nothing to resolve.
+    pub async fn fail_pending_calls(&self, error: &str) -> usize {
+        let pending: Vec<_> = self.0.pending_calls.lock().await.drain().collect();
+        let count = pending.len();
+        for (request_id, sender) in pending {
+            let _ = sender.send(BridgeToolResponse {
+                request_id,
+                success: false,
+                result: None,
+                error: Some(error.to_string()),
+            });
+        }
+        count
+    }
+
+    pub async fn pending_call_count(&self) -> usize {
+        self.0.pending_calls.lock().await.len()
+    }
+
+    // ─── Log Buffer ───────────────────────────────────────────
+
+    pub fn push_log(&self, level: String, message: String, session_id: Option<String>) {
+        // Escapes first, so a colour code inside a secret doesn't hide it from redaction
+        let message = self.0.sanitizer.text(&message);
+        let message = self.redact(&message).into_owned();
+        let entry = self.0.log_buffer.push(|seq| LogEntry {
+            seq,
+            ts: self.clock().now().timestamp_millis() as f64 / 1000.0,
+            level,
+            message,
+            session_id,
+        });
+
+        if let Some(archive) = &self.0.log_archive {
+            archive.append(&entry);
+        }
+    }
+
+    pub fn get_logs(&self, since_seq: u64, limit: usize) -> Vec<Arc<LogEntry>> {
+        self.0.log_buffer.since(since_seq, limit)
+    }
+
+    /// Entries whose `ts` (server receive time, Unix seconds) is at or after `cutoff_ts`.
+    pub fn get_logs_since_ts(&self, cutoff_ts: f64) -> Vec<Arc<LogEntry>> {
+        self.0.log_buffer.matching(|e| e.ts >= cutoff_ts)
+    }
+
+    /// Buffered entries of one playtest session, oldest first.
+    pub fn session_logs(&self, session_id: &str) -> Vec<Arc<LogEntry>> {
+        self.0
+            .log_buffer
+            .matching(|e| e.session_id.as_deref() == Some(session_id))
+    }
+
+    /// Log entries as they are pushed, for following the log without polling.
+    pub fn subscribe_logs(&self) -> broadcast::Receiver<Arc<LogEntry>> {
+        self.0.log_buffer.subscribe()
+    }
+
+    /// Buffered entries of `levels` grouped by level and message.
+    pub fn log_groups(&self, levels: &[&str]) -> LogGroups {
+        self.0.log_buffer.read(|buf| group_logs(buf, levels))
+    }
+
+    /// Whether the log buffer is full, so older entries may have been evicted.
+    pub fn log_buffer_full(&self) -> bool {
+        self.0.log_buffer.len() >= MAX_LOG_BUFFER
+    }
+
+    /// The persistent log archive, if `YIPPIE_LOG_ARCHIVE` is on.
+    pub fn log_archive(&self) -> Option<&Arc<LogArchive>> {
+        self.0.log_archive.as_ref()
+    }
+
+    pub fn log_buffer_size(&self) -> usize {
+        self.0.log_buffer.len()
+    }
+
+    // ─── Playtest State ───────────────────────────────────────
+
+    pub async fn update_playtest(
+        &self,
+        active: bool,
+        session_id: Option<String>,
+        mode: Option<String>,
+    ) {
+        let mut state = self.0.playtest_state.lock().await;
+        // Keep the start time across repeated reports of the same session
+        let same_session = state.active && state.session_id == session_id;
+        state.started_at = match (active, same_session) {
+            (false, _) => None,
+            (true, true) => state.started_at,
+            (true, false) => Some(Instant::now()),
+        };
+        // Readiness arrives from the bridge and may come before this report, so it
+        // is only dropped when the playtest ends or another one replaces it
+        if !active || (state.active && !same_session) {
+            state.readiness = None;
+            state.player = None;
+        }
+        if state.active && (!active || !same_session) {
+            self.test_timings().playtest_stopped(Instant::now());
+        }
+        if active && !same_session {
+            self.test_timings().playtest_started(
+                Instant::now(),
+                session_id.as_deref(),
+                mode.as_deref(),
+            );
+        }
+        state.active = active;
+        state.session_id = session_id;
+        state.mode = mode;
+        drop(state);
+        self.0.playtest_changed.notify_waiters();
+        if !active {
+            self.clear_held_keys("playtest ended").await;
+            // NPC drivers live in the playtest DataModel and end with it
+            self.0.session_resources.lock().await.npc_drivers.clear();
+        }
+    }
+
+    /// Record a readiness stage reported by the playtest bridge.
+    pub async fn update_playtest_readiness(
+        &self,
+        readiness: Option<Readiness>,
+        player: Option<String>,
+    ) {
+        let mut state = self.0.playtest_state.lock().await;
+        if state.readiness != readiness {
+            tracing::info!(readiness = readiness.map(Readiness::as_str), player = ?player, "Playtest readiness changed");
+        }
+        state.readiness = readiness;
+        state.player = player;
+        drop(state);
+        self.0.playtest_changed.notify_waiters();
+    }
+
+    /// Readiness stage and player of the running playtest.
+    pub async fn playtest_readiness(&self) -> (Option<Readiness>, Option<String>) {
+        let state = self.0.playtest_state.lock().await;
+        (state.readiness, state.player.clone())
+    }
+
+    /// Woken whenever playtest state changes or a playtest start call finishes.
+    pub fn playtest_changed(&self) -> &Notify {
+        &self.0.playtest_changed
+    }
+
+    pub fn begin_playtest_start(&self) -> PlaytestStartGuard {
+        self.0
+            .playtest_starts_in_flight
+            .fetch_add(1, Ordering::SeqCst);
+        PlaytestStartGuard {
+            state: self.clone(),
+        }
+    }
+
+    pub fn playtest_start_in_flight(&self) -> bool {
+        self.0.playtest_starts_in_flight.load(Ordering::SeqCst) > 0
+    }
+
+    pub async fn is_playtest_active(&self) -> bool {
+        self.0.playtest_state.lock().await.active
+    }
+
+    pub async fn playtest_info(&self) -> (bool, Option<String>, Option<String>) {
+        let state = self.0.playtest_state.lock().await;
+        (state.active, state.session_id.clone(), state.mode.clone())
+    }
+
+    /// Session id, mode and running time of the active playtest.
+    pub async fn playtest_running_for(&self) -> Option<(Option<String>, Option<String>, Duration)> {
+        let state = self.0.playtest_state.lock().await;
+        let started_at = state.started_at.filter(|_| state.active)?;
+        Some((
+            state.session_id.clone(),
+            state.mode.clone(),
+            started_at.elapsed(),
+        ))
+    }
+
+    // ─── Held Keys ────────────────────────────────────────────
+
+    /// Record a key transition the bridge has confirmed. Space is a one-shot jump,
+    /// so it is never considered held.
+    pub async fn apply_key_transition(&self, key_code: &str, action: &str) {
+        if key_code == "Space" {
+            return;
+        }
+        let mut held = self.0.held_keys.lock().await;
+        if action == "up" {
+            held.remove(key_code);
+        } else {
+            held.insert(key_code.to_string());
+        }
+    }
+
+    /// Forget all held keys. Used when the bridge that held them is gone (or is about to
+    /// release them itself), so there is nothing left to send a release to.
+    pub async fn clear_held_keys(&self, reason: &str) {
+        let mut held = self.0.held_keys.lock().await;
+        if !held.is_empty() {
+            tracing::info!(keys = ?held, reason, "Clearing held virtual keys");
+            held.clear();
+        }
+    }
+
+    pub async fn release_held_keys(&self, key_codes: &[&str]) {
+        let mut held = self.0.held_keys.lock().await;
+        for key_code in key_codes {
+            held.remove(*key_code);
+        }
+    }
+
+    /// Keys the server believes are held on the playtest bridge, sorted.
+    pub async fn held_keys(&self) -> Vec<String> {
+        self.0.held_keys.lock().await.iter().cloned().collect()
+    }
+
+    // ─── Runtime Flags ────────────────────────────────────────
+
+    /// Remember a flag's value from before its first change. Later changes keep the
+    /// first value, so a restore always goes back to where the session started.
+    pub async fn record_runtime_flag_original(&self, flag: &str, value: serde_json::Value) {
+        self.0
+            .runtime_flag_originals
+            .lock()
+            .await
+            .entry(flag.to_string())
+            .or_insert(value);
+    }
+
+    pub async fn runtime_flag_original(&self, flag: &str) -> Option<serde_json::Value> {
+        self.0
+            .runtime_flag_originals
+            .lock()
+            .await
+            .get(flag)
+            .cloned()
+    }
+
+    pub async fn forget_runtime_flag(&self, flag: &str) {
+        self.0.runtime_flag_originals.lock().await.remove(flag);
+    }
+
+    /// Changed flags and the values they will be restored to.
+    pub async fn runtime_flag_originals(&self) -> BTreeMap<String, serde_json::Value> {
+        self.0.runtime_flag_originals.lock().await.clone()
+    }
+
+    // ─── Studio Settings ──────────────────────────────────────
+
+    /// Remember a setting's value from before its first change, with the playtest
+    /// running then. Later changes keep both.
+    pub async fn record_studio_setting_original(
+        &self,
+        setting: &str,
+        value: serde_json::Value,
+        playtest: Option<String>,
+    ) {
+        self.0
+            .studio_setting_originals
+            .lock()
+            .await
+            .entry(setting.to_string())
+            .or_insert(studio_settings::Original { value, playtest });
+    }
+
+    pub async fn forget_studio_setting(&self, setting: &str) {
+        self.0.studio_setting_originals.lock().await.remove(setting);
+    }
+
+    /// Changed settings and what they will be restored to.
+    pub async fn studio_setting_originals(&self) -> BTreeMap<String, studio_settings::Original> {
+        self.0.studio_setting_originals.lock().await.clone()
+    }
+
+    // ─── Session Activity ─────────────────────────────────────
+
+    /// Track a session for the idle watcher and the log subscription checks. It drops
+    /// out once every copy of the session is gone.
+    pub fn register_session(&self, session: &SessionState) {
+        let mut sessions = self.0.sessions.lock().unwrap_or_else(|e| e.into_inner());
+        sessions.retain(|_, activity| activity.strong_count() > 0);
+        sessions.insert(session.id.clone(), Arc::downgrade(session.activity()));
+        drop(sessions);
+        self.0.activity.notify_one();
+    }
+
+    /// Live sessions, by id.
+    pub fn sessions(&self) -> Vec<(String, Arc<SessionActivity>)> {
+        self.0
+            .sessions
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter_map(|(id, activity)| Some((id.clone(), activity.upgrade()?)))
+            .collect()
+    }
+
+    /// Note an MCP request of a session. Returns true if the session was idle until now.
+    pub fn record_activity(&self, session: &SessionActivity) -> bool {
+        let was_idle = session.record_request();
+        if was_idle {
+            self.0.activity.notify_one();
+        }
+        was_idle
+    }
+
+    /// Time since the last MCP request of any session, or since startup before the
+    /// first session.
+    pub fn idle_for(&self) -> Duration {
+        self.sessions()
+            .iter()
+            .map(|(_, activity)| activity.idle_for())
+            .min()
+            .unwrap_or_else(|| self.0.started_at.elapsed())
+    }
+
+    /// True once every session has gone idle.
+    pub fn is_idle(&self) -> bool {
+        let sessions = self.sessions();
+        !sessions.is_empty() && sessions.iter().all(|(_, activity)| activity.is_idle())
+    }
+
+    /// Woken (with a stored permit) when a session starts or ends an idle period.
+    pub fn activity(&self) -> &Notify {
+        &self.0.activity
+    }
+
+    /// Whether any session holds a log subscription.
+    pub fn logs_subscribed(&self) -> bool {
+        self.sessions()
+            .iter()
+            .any(|(_, activity)| activity.log_subscription().is_some())
+    }
+
+    /// Whether a session other than `session_id` holds a log subscription.
+    pub fn logs_subscribed_elsewhere(&self, session_id: &str) -> bool {
+        self.sessions()
+            .iter()
+            .any(|(id, activity)| id != session_id && activity.log_subscription().is_some())
+    }
+
+    pub async fn session_resources(&self) -> tokio::sync::MutexGuard<'_, SessionResources> {
+        self.0.session_resources.lock().await
+    }
+
+    pub fn clear_log_buffer(&self) -> usize {
+        self.0.log_buffer.clear()
+    }
+
+    // ─── Input Recording ──────────────────────────────────────
+
+    /// Open a recording. Returns false if one is already open.
+    pub async fn start_input_recording(&self, name: String) -> bool {
+        let mut recording = self.0.input_recording.lock().await;
+        if recording.is_some() {
+            return false;
+        }
+        *recording = Some(ActiveRecording {
+            name,
+            started_at: self.clock().now(),
+            samples: Vec::new(),
+            batches: Vec::new(),
+            dropped: 0,
+        });
+        true
+    }
+
+    /// Append samples to the open recording. Samples for another name (a stale
+    /// batch from an earlier recording) or beyond the size cap are dropped.
+    pub async fn append_input_samples(&self, name: &str, samples: Vec<InputSample>) {
+        let mut recording = self.0.input_recording.lock().await;
+        let Some(active) = recording.as_mut().filter(|r| r.name == name) else {
+            tracing::debug!(recording = %name, "Input samples for a recording that isn't open");
+            return;
+        };
+        if let Some(sample_ms) = samples.iter().map(InputSample::t_ms).max() {
+            if active.batches.len() < MAX_RECORDING_SAMPLES {
+                let received_ms = (self.clock().now() - active.started_at)
+                    .num_milliseconds()
+                    .max(0) as u64;
+                active.batches.push(BatchArrival {
+                    received_ms,
+                    sample_ms,
+                });
+            }
+        }
+        let room = MAX_RECORDING_SAMPLES.saturating_sub(active.samples.len());
+        if samples.len() > room {
+            active.dropped += samples.len() - room;
+        }
+        active.samples.extend(samples.into_iter().take(room));
+    }
+
+    /// Close the open recording and hand back what was collected.
+    pub async fn take_input_recording(&self) -> Option<ActiveRecording> {
+        self.0.input_recording.lock().await.take()
+    }
+
+    pub async fn input_recording_name(&self) -> Option<String> {
+        self.0
+            .input_recording
+            .lock()
+            .await
+            .as_ref()
+            .map(|r| r.name.clone())
+    }
+}
+
+fn http_budget_warning(last_minute: u64, warn_at: u64) -> String {
+    format!(
+        "Bridge traffic is at {last_minute} of Roblox's {} HttpService requests per minute (warning at {warn_at}). \
+         Every tool response and pushed event is a request: unsubscribe from logs when not needed and \
+         combine steps into fewer calls (studio-virtualuser_sequence, one studio-run_script).",
+        traffic::ROBLOX_HTTP_LIMIT_PER_MIN
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{OsRng, SystemClock};
+    use crate::redact::Redactor;
+
+    /// Real time, with a system clock that the test sets forward or back.
+    #[derive(Default)]
+    struct JumpingClock {
+        offset: std::sync::Mutex<chrono::Duration>,
+    }
+
+    impl JumpingClock {
+        fn jump(&self, by: chrono::Duration) {
+            *self.offset.lock().unwrap() += by;
+        }
+    }
+
+    impl Clock for JumpingClock {
+        fn now(&self) -> chrono::DateTime<chrono::Utc> {
+            SystemClock.now()
+        }
+
+        fn elapsed(&self, start: Instant) -> Duration {
+            SystemClock.elapsed(start)
+        }
+
+        fn age(&self, since: chrono::DateTime<chrono::Utc>) -> Duration {
+            SystemClock.age(since)
+        }
+
+        fn wall_now(&self) -> chrono::DateTime<chrono::Utc> {
+            chrono::Utc::now() + *self.offset.lock().unwrap()
+        }
+    }
+
+    /// A state on `clock` with one plugin client, `stale`, that last polled just past
+    /// `STALE_CLIENT_AFTER` ago.
+    async fn stale_client_state(clock: Arc<JumpingClock>) -> SharedState {
+        let config = Config::default();
+        let settings = Settings::resolve(&config, profiles::BASE_PROFILE).unwrap();
+        let redactor = Redactor::new(&config.redact_patterns).unwrap();
+        let launch = LaunchInfo {
+            mock_studio: false,
+            status_report: false,
+        };
+        let providers = Providers {
+            clock,
+            rng: Arc::new(OsRng),
+        };
+        let state =
+            SharedState::with_providers(config, settings, launch, redactor, None, providers);
+        state
+            .register_client("stale".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        let last_poll = Instant::now()
+            .checked_sub(STALE_CLIENT_AFTER + Duration::from_secs(1))
+            .unwrap();
+        state
+            .0
+            .clients
+            .lock()
+            .await
+            .get_mut("stale")
+            .unwrap()
+            .last_poll_at = last_poll;
+        state
+    }
+
+    async fn client_ids(state: &SharedState) -> Vec<String> {
+        state.0.clients.lock().await.keys().cloned().collect()
+    }
+
+    async fn clock_offset(state: &SharedState, client_id: &str) -> Arc<ClockOffset> {
+        state.0.clients.lock().await[client_id].clock_offset.clone()
+    }
+
+    #[tokio::test]
+    async fn routed_clock_offset_follows_routing_overrides() {
+        let config = Config {
+            routing_overrides: [("studio-run_script".to_string(), Role::Bridge)].into(),
+            ..Config::default()
+        };
+        let state = SharedState::for_tests(config);
+        state
+            .register_client("edit".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        state
+            .register_client("play".into(), "0.4.0-playtest".into(), None, Casing::Snake)
+            .await;
+        let (edit, play) = (
+            clock_offset(&state, "edit").await,
+            clock_offset(&state, "play").await,
+        );
+
+        let overridden = state
+            .routed_clock_offset("studio-run_script")
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&overridden, &play));
+        let default = state
+            .routed_clock_offset("studio-test_script")
+            .await
+            .unwrap();
+        assert!(Arc::ptr_eq(&default, &edit));
+    }
+
+    #[tokio::test]
+    async fn stale_clients_are_pruned_while_the_clock_holds_steady() {
+        let clock = Arc::new(JumpingClock::default());
+        let state = stale_client_state(clock.clone()).await;
+        // A drift within the threshold isn't a jump
+        clock.jump(chrono::Duration::seconds(5));
+        state.prune_stale_clients().await;
+        assert!(client_ids(&state).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_forward_clock_jump_holds_off_pruning() {
+        let clock = Arc::new(JumpingClock::default());
+        let state = stale_client_state(clock.clone()).await;
+        clock.jump(chrono::Duration::hours(8));
+        state.prune_stale_clients().await;
+        assert_eq!(client_ids(&state).await, ["stale"]);
+        // Still within the grace period on the next check, with no new jump
+        state.prune_stale_clients().await;
+        assert_eq!(client_ids(&state).await, ["stale"]);
+    }
+
+    #[tokio::test]
+    async fn a_backward_clock_jump_holds_off_pruning() {
+        let clock = Arc::new(JumpingClock::default());
+        let state = stale_client_state(clock.clone()).await;
+        // Far enough back that a wall-clock difference computed the wrong way round
+        // would underflow
+        clock.jump(-chrono::Duration::days(365));
+        state.prune_stale_clients().await;
+        assert_eq!(client_ids(&state).await, ["stale"]);
+
+        let mut watch = WallClockWatch::new(Instant::now(), clock.wall_now());
+        clock.jump(-chrono::Duration::hours(1));
+        let jump = watch.check(Instant::now(), clock.wall_now()).unwrap();
+        assert!(
+            (-3601..=-3599).contains(&jump.offset_secs),
+            "{}",
+            jump.offset_secs
+        );
+        assert!(watch.in_grace(Instant::now()));
+    }
+
+    #[tokio::test]
+    async fn enqueued_interactive_calls_reach_the_plugin_before_background_ones() {
+        let state = SharedState::for_tests(Config::default());
+        let request = |id: &str| BridgeToolRequest {
+            request_id: id.into(),
+            tool_name: "studio-run_script".into(),
+            arguments: serde_json::json!({}),
+        };
+        assert_eq!(
+            state
+                .enqueue_tool_request(request("early"), Priority::Interactive)
+                .await,
+            None
+        );
+
+        state
+            .register_client("edit".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        for id in ["b1", "b2", "b3"] {
+            assert_eq!(
+                state
+                    .enqueue_tool_request(request(id), Priority::Background)
+                    .await
+                    .as_deref(),
+                Some("edit")
+            );
+        }
+        state
+            .enqueue_tool_request(request("i1"), Priority::Interactive)
+            .await;
+        let drained: Vec<String> = state
+            .drain_outbound("edit")
+            .await
+            .into_iter()
+            .map(|r| r.request_id)
+            .collect();
+        assert_eq!(drained, ["i1", "b1", "b2"]);
+        let drained: Vec<String> = state
+            .drain_outbound("edit")
+            .await
+            .into_iter()
+            .map(|r| r.request_id)
+            .collect();
+        assert_eq!(drained, ["b3"]);
+    }
+
+    /// Make `client_id` miss polls for longer than `STALE_CLIENT_AFTER` and prune it.
+    async fn drop_client(state: &SharedState, client_id: &str) {
+        let last_poll = Instant::now()
+            .checked_sub(STALE_CLIENT_AFTER + Duration::from_secs(1))
+            .unwrap();
+        state
+            .0
+            .clients
+            .lock()
+            .await
+            .get_mut(client_id)
+            .unwrap()
+            .last_poll_at = last_poll;
+        state.prune_stale_clients().await;
+    }
+
+    async fn open_checkpoint(state: &SharedState, checkpoint_id: &str, client_id: &str) {
+        let owner = state.client_owner_key(client_id).await.unwrap();
+        let checkpoint = OpenCheckpoint {
+            name: format!("{checkpoint_id} edits"),
+            owner,
+            lost: None,
+        };
+        state
+            .session_resources()
+            .await
+            .open_checkpoints
+            .insert(checkpoint_id.into(), checkpoint);
+    }
+
+    async fn checkpoint_loss(state: &SharedState, checkpoint_id: &str) -> Option<CheckpointLoss> {
+        state.session_resources().await.open_checkpoints[checkpoint_id].lost
+    }
+
+    #[tokio::test]
+    async fn checkpoints_survive_a_reconnect_of_the_same_plugin_instance() {
+        let state = SharedState::for_tests(Config::default());
+        state
+            .register_client(
+                "edit-1".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        open_checkpoint(&state, "1", "edit-1").await;
+
+        drop_client(&state, "edit-1").await;
+        assert_eq!(
+            checkpoint_loss(&state, "1").await,
+            Some(CheckpointLoss::Disconnected)
+        );
+        // The same instance comes back under a new client id
+        state
+            .register_client(
+                "edit-2".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        assert_eq!(checkpoint_loss(&state, "1").await, None);
+    }
+
+    #[tokio::test]
+    async fn a_new_plugin_instance_makes_checkpoints_unrecoverable_for_good() {
+        let state = SharedState::for_tests(Config::default());
+        state
+            .register_client(
+                "edit-1".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        open_checkpoint(&state, "1", "edit-1").await;
+        open_checkpoint(&state, "2", "edit-1").await;
+        drop_client(&state, "edit-1").await;
+
+        // A reload registers a new instance, live or already disconnected
+        state
+            .register_client(
+                "edit-2".into(),
+                "0.4.0".into(),
+                Some("studio-b".into()),
+                Casing::Snake,
+            )
+            .await;
+        assert_eq!(
+            checkpoint_loss(&state, "1").await,
+            Some(CheckpointLoss::Reloaded)
+        );
+        open_checkpoint(&state, "3", "edit-2").await;
+        state
+            .register_client(
+                "edit-3".into(),
+                "0.4.0".into(),
+                Some("studio-c".into()),
+                Casing::Snake,
+            )
+            .await;
+        assert_eq!(
+            checkpoint_loss(&state, "3").await,
+            Some(CheckpointLoss::Reloaded)
+        );
+
+        // The old instance returning does not bring the recording back
+        state
+            .register_client(
+                "edit-4".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        assert_eq!(
+            checkpoint_loss(&state, "2").await,
+            Some(CheckpointLoss::Reloaded)
+        );
+        // And removing a client doesn't downgrade a reload to a disconnect
+        drop_client(&state, "edit-4").await;
+        assert_eq!(
+            checkpoint_loss(&state, "2").await,
+            Some(CheckpointLoss::Reloaded)
+        );
+    }
+
+    #[tokio::test]
+    async fn checkpoints_stay_usable_while_their_instance_has_a_client() {
+        let state = SharedState::for_tests(Config::default());
+        state
+            .register_client(
+                "edit-1".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        state
+            .register_client(
+                "edit-2".into(),
+                "0.4.0".into(),
+                Some("studio-a".into()),
+                Casing::Snake,
+            )
+            .await;
+        open_checkpoint(&state, "1", "edit-1").await;
+        drop_client(&state, "edit-1").await;
+        assert_eq!(checkpoint_loss(&state, "1").await, None);
+
+        // The playtest bridge neither owns checkpoints nor counts as a new instance
+        state
+            .register_client(
+                "play".into(),
+                "0.4.0-playtest".into(),
+                Some("studio-p".into()),
+                Casing::Snake,
+            )
+            .await;
+        drop_client(&state, "play").await;
+        assert_eq!(checkpoint_loss(&state, "1").await, None);
+
+        drop_client(&state, "edit-2").await;
+        assert_eq!(
+            checkpoint_loss(&state, "1").await,
+            Some(CheckpointLoss::Disconnected)
+        );
+    }
+
+    #[tokio::test]
+    async fn clients_without_an_instance_key_own_checkpoints_by_client_id() {
+        let state = SharedState::for_tests(Config::default());
+        state
+            .register_client("edit-1".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        open_checkpoint(&state, "1", "edit-1").await;
+        drop_client(&state, "edit-1").await;
+        state
+            .register_client("edit-1".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        assert_eq!(checkpoint_loss(&state, "1").await, None);
+        state
+            .register_client("edit-2".into(), "0.4.0".into(), None, Casing::Snake)
+            .await;
+        assert_eq!(
+            checkpoint_loss(&state, "1").await,
+            Some(CheckpointLoss::Reloaded)
+        );
+    }
+
+    #[tokio::test]
+    async fn pending_calls_resolve_by_opaque_request_ids() {
+        let state = SharedState::for_tests(Config::default());
+        let current = state.next_id();
+        // Ids of older plugins and servers: bare UUIDs and anything else echoed back
+        let ids = [
+            current.as_str(),
+            "6f1c1e9a-93c4-4b8e-9a47-2a3f6f0e8b21",
+            "legacy:7",
+        ];
+        let mut receivers = Vec::new();
+        for id in ids {
+            let (tx, rx) = oneshot::channel();
+            state.register_pending(id.to_string(), tx).await;
+            receivers.push(rx);
+        }
+        for (id, rx) in ids.into_iter().zip(receivers).rev() {
+            let response = BridgeToolResponse {
+                request_id: id.into(),
+                success: true,
+                result: None,
+                error: None,
+            };
+            assert!(state.resolve_pending(id, response).await);
+            assert_eq!(rx.await.unwrap().request_id, id);
+        }
+        let response = BridgeToolResponse {
+            request_id: current.clone(),
+            success: true,
+            result: None,
+            error: None,
+        };
+        assert!(
+            !state.resolve_pending(&current, response).await,
+            "resolved twice"
+        );
+    }
+
+    #[tokio::test]
+    async fn ids_from_every_part_of_the_state_share_one_ordering() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = SharedState::for_tests(Config {
+            capture_dir: dir.path().to_path_buf(),
+            ..Config::default()
+        });
+        let session = state.next_id();
+        let capture = state
+            .with_captures(None, |captures| {
+                captures.save_test_matrix_report(None, "{}")
+            })
+            .await
+            .unwrap();
+        let request = state.next_id();
+        assert!(
+            session < capture.id && capture.id < request,
+            "{session} {} {request}",
+            capture.id
+        );
+    }
+
+    #[tokio::test]
+    async fn bridge_traffic_over_the_threshold_warns_once_and_restricts_background_work() {
+        // Warning at 10 requests a minute
+        let state = SharedState::for_tests(Config {
+            http_budget_warn: 0.02,
+            ..Config::default()
+        });
+        let mut events = state.subscribe_events();
+        for _ in 0..9 {
+            state.record_bridge_request();
+        }
+        let budget = state.http_budget();
+        assert_eq!(
+            (budget.requests_last_minute, budget.warn_at, budget.warning),
+            (9, 10, None)
+        );
+        assert_eq!(state.background_level(), BackgroundLevel::Minimal);
+        assert!(events.try_recv().is_err());
+
+        state.record_bridge_request();
+        state.record_bridge_request();
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.kind, server_events::HTTP_BUDGET);
+        assert_eq!(
+            event.data,
+            serde_json::json!({ "requestsLastMinute": 10, "limitPerMinute": 500, "warnAt": 10 })
+        );
+        assert!(events.try_recv().is_err(), "warned twice");
+
+        let budget = state.http_budget();
+        assert_eq!(budget.requests_last_minute, 11);
+        assert!(budget.warning.unwrap().contains("at 11 of Roblox's 500"));
+        assert_eq!(budget.background_level, BackgroundLevel::Paused);
+        assert!(!state.background_level().wakes_pull());
+    }
+
+    #[test]
+    fn client_profiles_apply_unless_one_was_chosen_at_startup() {
+        let profiles = crate::profiles::parse_profiles(
+            r#"{"b-desktop": {"readOnly": true, "clients": ["Claude-Desktop"]}, "a-any": {"clients": ["claude-desktop", "cursor"]}}"#,
+        )
+        .unwrap();
+        // The first profile by name wins, and names match case-insensitively
+        assert_eq!(
+            crate::profiles::for_client(&profiles, "CLAUDE-DESKTOP"),
+            Some("a-any")
+        );
+        assert_eq!(crate::profiles::for_client(&profiles, "harness"), None);
+
+        let state = SharedState::for_tests(Config {
+            profiles: profiles.clone(),
+            ..Config::default()
+        });
+        state.match_client_profile("harness");
+        assert_eq!(state.settings().name(), crate::profiles::BASE_PROFILE);
+        state.match_client_profile("cursor");
+        assert_eq!(state.settings().name(), "a-any");
+
+        let pinned = SharedState::for_tests(Config {
+            profiles,
+            profile: Some("b-desktop".into()),
+            ..Config::default()
+        });
+        assert_eq!(pinned.settings().name(), "b-desktop");
+        pinned.match_client_profile("cursor");
+        assert_eq!(pinned.settings().name(), "b-desktop");
+    }
+
+    #[test]
+    fn a_client_profile_cannot_leave_read_only() {
+        let profiles = crate::profiles::parse_profiles(
+            r#"{"cautious": {"readOnly": true, "clients": ["claude-desktop"]}, "fast": {"clients": ["cursor"]}, "strict": {"readOnly": true, "clients": ["harness"]}}"#,
+        )
+        .unwrap();
+        let state = SharedState::for_tests(Config {
+            profiles,
+            ..Config::default()
+        });
+        state.match_client_profile("claude-desktop");
+        assert_eq!(state.settings().name(), "cautious");
+        state.match_client_profile("cursor");
+        assert_eq!(state.settings().name(), "cautious");
+        // Read-only to read-only is fine
+        state.match_client_profile("harness");
+        assert_eq!(state.settings().name(), "strict");
+    }
+
+    #[test]
+    fn tool_calls_are_counted_per_client() {
+        let state = SharedState::for_tests(Config::default());
+        for client in [Some("Harness/2.1"), None, Some("Harness/2.1")] {
+            state.count_tool_call(client);
+        }
+        assert_eq!(
+            state.tool_calls_by_client(),
+            BTreeMap::from([("Harness/2.1".to_string(), 2), ("unknown".to_string(), 1)])
+        );
     }
 }
@@ -0,0 +1,195 @@
+//! In-process benchmark of SharedState hot paths (`--bench-state`, run by
+//! `mcpctl bench --state`), so regressions in log buffering and request queuing show
+//! up without Studio or the stdio loop in between.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::bench::{Report, Summary};
+use crate::build_info::LaunchInfo;
+use crate::config::Config;
+use crate::outbound_queue::Priority;
+use crate::profiles::{self, Settings};
+use crate::redact::Redactor;
+use crate::state::SharedState;
+use crate::types::{BridgeToolRequest, LogEntry};
+use crate::wire::Casing;
+
+/// Plugin client the queued requests are routed to.
+const BENCH_CLIENT_ID: &str = "bench-client";
+
+/// Concurrent log readers in `log_read_contended`, like several tools and followers
+/// reading while Studio keeps logging.
+const LOG_READERS: usize = 10;
+
+/// Entries the baseline buffer keeps, as `LogStore` does.
+const BASELINE_LOG_BUFFER: usize = 500;
+
+/// Time `iterations` runs of each hot path after `warmup` untimed ones. Archive,
+/// alerting and hooks are left off so only the state itself is measured.
+pub async fn run(mut config: Config, iterations: usize, warmup: usize) -> Result<Report> {
+    config.log_archive = false;
+    config.alert_rules.clear();
+    config.hooks.clear();
+    let settings = Settings::resolve(&config, profiles::BASE_PROFILE).map_err(anyhow::Error::msg)?;
+    let redactor = Redactor::new(&config.redact_patterns)?;
+    let launch = LaunchInfo { mock_studio: false, status_report: false };
+    let state = SharedState::new(config, settings, launch, redactor, None);
+    state.register_client(BENCH_CLIENT_ID.into(), "bench".into(), None, Casing::Snake).await;
+
+    let mut report = Report::new("state", 1, warmup, None);
+
+    let summary = measure(iterations, warmup, |i| {
+        let state = state.clone();
+        async move {
+            state.push_log("output".into(), format!("bench log line {i}"), None);
+        }
+    })
+    .await;
+    report.results.insert("push_log".into(), summary);
+
+    let summary = measure(iterations, warmup, |i| {
+        let state = state.clone();
+        async move {
+            let request = BridgeToolRequest {
+                request_id: format!("bench-{i}"),
+                tool_name: "studio-status".into(),
+                arguments: serde_json::json!({}),
+            };
+            state.enqueue_tool_request(request, Priority::Interactive).await;
+            state.drain_outbound(BENCH_CLIENT_ID).await;
+        }
+    })
+    .await;
+    report.results.insert("enqueue_drain".into(), summary);
+
+    let (writer, reader) = (state.clone(), state.clone());
+    let summary = log_read_contended(
+        move |i| writer.push_log("output".into(), format!("bench log line {i}"), None),
+        move |since| reader.get_logs(since, 200).last().map_or(since, |e| e.seq),
+        iterations,
+        warmup,
+    )
+    .await;
+    report.results.insert("log_read_contended".into(), summary);
+
+    // The same load on the buffer LogStore replaced, to compare against
+    let baseline = Arc::new(MutexLogBuffer::default());
+    let (writer, reader) = (baseline.clone(), baseline);
+    let summary = log_read_contended(
+        move |i| writer.push(format!("bench log line {i}")),
+        move |since| reader.since(since, 200).last().map_or(since, |e| e.seq),
+        iterations,
+        warmup,
+    )
+    .await;
+    report.results.insert("log_read_contended_mutex".into(), summary);
+
+    Ok(report)
+}
+
+/// The log buffer before `LogStore`: one mutex around the entries, which readers
+/// clone out whole.
+#[derive(Default)]
+struct MutexLogBuffer {
+    entries: Mutex<(VecDeque<LogEntry>, u64)>,
+}
+
+impl MutexLogBuffer {
+    fn push(&self, message: String) {
+        let mut guard = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (entries, last_seq) = &mut *guard;
+        *last_seq += 1;
+        if entries.len() >= BASELINE_LOG_BUFFER {
+            entries.pop_front();
+        }
+        entries.push_back(LogEntry {
+            seq: *last_seq,
+            ts: 0.0,
+            level: "output".into(),
+            message,
+            session_id: None,
+        });
+    }
+
+    fn since(&self, since_seq: u64, limit: usize) -> Vec<LogEntry> {
+        let guard = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        guard.0.iter().filter(|e| e.seq > since_seq).take(limit).cloned().collect()
+    }
+}
+
+/// Latency of `LOG_READERS` tasks sharing `iterations` reads while one writer pushes
+/// entries without pause. `read` takes the last sequence number seen and returns the
+/// new one.
+async fn log_read_contended(
+    push: impl Fn(usize) + Send + Sync + 'static,
+    read: impl Fn(u64) -> u64 + Send + Sync + 'static,
+    iterations: usize,
+    warmup: usize,
+) -> Summary {
+    let read = Arc::new(read);
+    let writing = Arc::new(AtomicBool::new(true));
+    let writer = {
+        let writing = writing.clone();
+        tokio::spawn(async move {
+            let mut i = 0;
+            while writing.load(Ordering::Relaxed) {
+                push(i);
+                i += 1;
+                if i % 64 == 0 {
+                    tokio::task::yield_now().await;
+                }
+            }
+        })
+    };
+
+    let started = Instant::now();
+    let mut readers = tokio::task::JoinSet::new();
+    for reader in 0..LOG_READERS {
+        let read = read.clone();
+        let reads = iterations / LOG_READERS + usize::from(reader < iterations % LOG_READERS);
+        let skip = warmup / LOG_READERS;
+        readers.spawn(async move {
+            let mut latencies = Vec::with_capacity(reads);
+            let mut since = 0u64;
+            for i in 0..skip + reads {
+                let call = Instant::now();
+                since = read(since);
+                if i >= skip {
+                    latencies.push(call.elapsed());
+                }
+                tokio::task::yield_now().await;
+            }
+            latencies
+        });
+    }
+    let mut latencies = Vec::with_capacity(iterations);
+    while let Some(result) = readers.join_next().await {
+        latencies.extend(result.unwrap_or_default());
+    }
+    let elapsed = started.elapsed();
+    writing.store(false, Ordering::Relaxed);
+    let _ = writer.await;
+    Summary::from_latencies(latencies, 0, elapsed)
+}
+
+async fn measure<F, Fut>(iterations: usize, warmup: usize, op: F) -> Summary
+where
+    F: Fn(usize) -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    for i in 0..warmup {
+        op(i).await;
+    }
+    let mut latencies: Vec<Duration> = Vec::with_capacity(iterations);
+    let started = Instant::now();
+    for i in warmup..warmup + iterations {
+        let call = Instant::now();
+        op(i).await;
+        latencies.push(call.elapsed());
+    }
+    Summary::from_latencies(latencies, 0, started.elapsed())
+}
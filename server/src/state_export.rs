@@ -0,0 +1,938 @@
+//! Export and import of the server's persistent state, for moving a setup to another
+//! machine.
+//!
+//! Everything the server keeps between runs lives in the capture folder: the capture
+//! index and the files it lists (screenshots, recordings, test matrix reports, hook
+//! artifacts), replay files and the log archive. An export is a gzipped tarball with
+//! `manifest.json` followed by the folder's files under `data/`. Index paths are
+//! stored relative to the folder and pointed at the new folder on import.
+//!
+//! Import extracts into a staging directory inside the capture folder, checks every
+//! entry, upgrades older archive versions, then moves files into place category by
+//! category. A file that can't be moved is reported and the rest continue.
+//!
+//! Also used by the `mcpctl` binary, through the library.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::index_lock::{self, IndexLock};
+
+pub const STATE_FORMAT: &str = "yippieblox-state";
+pub const STATE_VERSION: u32 = 1;
+
+/// Upgrade steps, each taking staged data from the version it is listed with to the
+/// next. Version 1 is the first format, so there are none yet.
+type Migration = fn(&mut Manifest, &Path) -> Result<()>;
+const MIGRATIONS: &[(u32, Migration)] = &[];
+
+/// Where the bridge's export endpoint writes archives. Never exported itself.
+pub const EXPORTS_DIR: &str = "exports";
+const STAGING_PREFIX: &str = ".import-";
+/// Files that only mean something to the servers running on this folder: the index
+/// lock and the heartbeats of `capture_peers.rs`.
+const RUNTIME_NAMES: &[&str] = &[index_lock::LOCK_NAME, ".servers"];
+const MANIFEST_NAME: &str = "manifest.json";
+const DATA_DIR: &str = "data";
+const INDEX_NAME: &str = "index.json";
+
+/// Caps on what an import unpacks.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    bytes: u64,
+    entries: usize,
+    manifest_bytes: u64,
+}
+
+const IMPORT_LIMITS: Limits = Limits {
+    bytes: 4 * 1024 * 1024 * 1024,
+    entries: 100_000,
+    manifest_bytes: 64 * 1024,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    /// `index.json` and every file not in another category.
+    Captures,
+    /// `replays/`
+    Replays,
+    /// `logs/` (the log archive)
+    Logs,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [Category::Captures, Category::Replays, Category::Logs];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Category::Captures => "captures",
+            Category::Replays => "replays",
+            Category::Logs => "logs",
+        }
+    }
+
+    fn of(relative: &Path) -> Self {
+        match relative.components().next().and_then(|c| c.as_os_str().to_str()) {
+            Some("replays") => Category::Replays,
+            Some("logs") => Category::Logs,
+            _ => Category::Captures,
+        }
+    }
+}
+
+/// What to do when a category already has data in the destination folder.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Conflict {
+    /// Leave the category alone.
+    Skip,
+    /// Add new files and index entries and replace existing ones.
+    Overwrite,
+    /// Add new files and index entries and keep existing ones.
+    #[default]
+    Merge,
+}
+
+impl Conflict {
+    pub fn parse(raw: &str) -> Result<Self, String> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "overwrite" => Ok(Self::Overwrite),
+            "merge" => Ok(Self::Merge),
+            other => Err(format!("unknown conflict policy '{other}': expected skip, overwrite or merge")),
+        }
+    }
+}
+
+/// Conflict policy per category.
+#[derive(Debug, Clone, Default)]
+pub struct ImportPolicy(BTreeMap<Category, Conflict>);
+
+impl ImportPolicy {
+    pub fn get(&self, category: Category) -> Conflict {
+        self.0.get(&category).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, category: Category, conflict: Conflict) {
+        self.0.insert(category, conflict);
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategoryStats {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub created_at: String,
+    /// Program and version that wrote the archive.
+    pub exported_by: String,
+    /// Capture folder the archive was made from.
+    pub source_dir: String,
+    pub categories: BTreeMap<Category, CategoryStats>,
+}
+
+/// Files of the capture folder that belong in an export, relative and sorted.
+/// Symlinks, temp files, staging directories, earlier exports and the runtime files of
+/// running servers are left out.
+fn collect_files(capture_dir: &Path) -> Result<Vec<PathBuf>> {
+    fn walk(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+        let dir = root.join(relative);
+        for entry in std::fs::read_dir(&dir).with_context(|| format!("could not read {}", dir.display()))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name_str = name.to_string_lossy();
+            let rel = relative.join(&name);
+            let top_level = relative.as_os_str().is_empty();
+            if name_str.ends_with(".tmp") || (top_level
+                    && (name_str == EXPORTS_DIR
+                        || name_str.starts_with(STAGING_PREFIX)
+                        || RUNTIME_NAMES.contains(&name_str.as_ref()))) {
+                continue;
+            }
+            let kind = entry.file_type()?;
+            if kind.is_dir() {
+                walk(root, &rel, files)?;
+            } else if kind.is_file() {
+                files.push(rel);
+            }
+        }
+        Ok(())
+    }
+    let mut files = Vec::new();
+    if capture_dir.is_dir() {
+        walk(capture_dir, Path::new(""), &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `file_path` of every index entry inside `from`, made relative (export) or joined
+/// onto `to` (import). Entries pointing elsewhere keep their path.
+fn rebase_index(entries: &mut [Value], from: &Path, to: Option<&Path>) {
+    for entry in entries {
+        let Some(path) = entry.get("file_path").and_then(|p| p.as_str()) else {
+            continue;
+        };
+        let rebased = match to {
+            None => Path::new(path).strip_prefix(from).ok().map(|rel| rel.to_string_lossy().replace('\\', "/")),
+            Some(to) => Path::new(path)
+                .is_relative()
+                .then(|| to.join(path).to_string_lossy().to_string()),
+        };
+        if let Some(rebased) = rebased {
+            entry["file_path"] = Value::String(rebased);
+        }
+    }
+}
+
+fn read_index(path: &Path) -> Result<Vec<Value>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = std::fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+    serde_json::from_str(&data).with_context(|| format!("{} is not a JSON array", path.display()))
+}
+
+fn write_index(capture_dir: &Path, entries: &[Value]) -> Result<()> {
+    let tmp = index_lock::temp_path(capture_dir);
+    std::fs::write(&tmp, serde_json::to_string_pretty(entries)?)?;
+    std::fs::rename(&tmp, capture_dir.join(INDEX_NAME))?;
+    Ok(())
+}
+
+fn tar_header(size: u64) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mtime(chrono::Utc::now().timestamp().max(0) as u64);
+    header
+}
+
+/// Write the capture folder to a gzipped tarball at `out`. The archive is written
+/// to a temp file first, so a failed export leaves nothing behind.
+pub fn export(capture_dir: &Path, out: &Path, exported_by: &str) -> Result<Manifest> {
+    let files = collect_files(capture_dir)?;
+    let out_abs = std::path::absolute(out)?;
+    let capture_abs = std::path::absolute(capture_dir)?;
+    let files: Vec<PathBuf> = files.into_iter().filter(|rel| capture_abs.join(rel) != out_abs).collect();
+
+    let mut index = read_index(&capture_dir.join(INDEX_NAME))?;
+    rebase_index(&mut index, capture_dir, None);
+    rebase_index(&mut index, &capture_abs, None);
+    let index_bytes = serde_json::to_vec_pretty(&index)?;
+
+    let mut categories: BTreeMap<Category, CategoryStats> =
+        Category::ALL.iter().map(|c| (*c, CategoryStats::default())).collect();
+    for rel in &files {
+        let size = if rel == Path::new(INDEX_NAME) {
+            index_bytes.len() as u64
+        } else {
+            std::fs::metadata(capture_dir.join(rel))?.len()
+        };
+        let stats = categories.entry(Category::of(rel)).or_default();
+        stats.files += 1;
+        stats.bytes += size;
+    }
+    let manifest = Manifest {
+        format: STATE_FORMAT.into(),
+        version: STATE_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        exported_by: exported_by.into(),
+        source_dir: capture_abs.to_string_lossy().to_string(),
+        categories,
+    };
+
+    let tmp = out.with_extension("partial");
+    let result = (|| -> Result<()> {
+        let file = std::fs::File::create(&tmp).with_context(|| format!("could not create {}", tmp.display()))?;
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        let manifest_bytes = serde_json::to_vec_pretty(&manifest)?;
+        builder.append_data(&mut tar_header(manifest_bytes.len() as u64), MANIFEST_NAME, manifest_bytes.as_slice())?;
+        for rel in &files {
+            let name = Path::new(DATA_DIR).join(rel);
+            if rel == Path::new(INDEX_NAME) {
+                builder.append_data(&mut tar_header(index_bytes.len() as u64), &name, index_bytes.as_slice())?;
+                continue;
+            }
+            // Read exactly the size put in the header: the live log segment may grow meanwhile
+            let file = std::fs::File::open(capture_dir.join(rel))?;
+            let len = file.metadata()?.len();
+            builder.append_data(&mut tar_header(len), &name, file.take(len))?;
+        }
+        builder.into_inner()?.finish()?.flush()?;
+        Ok(())
+    })();
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp);
+        return Err(e);
+    }
+    std::fs::rename(&tmp, out)?;
+    Ok(manifest)
+}
+
+/// `path` as a relative path of plain components, or `None` for anything that could
+/// leave the directory it is joined onto.
+fn safe_relative(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!out.as_os_str().is_empty()).then_some(out)
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CategoryReport {
+    pub policy: Conflict,
+    /// Files and index entries added.
+    pub added: u64,
+    /// Existing files and index entries replaced.
+    pub replaced: u64,
+    /// Existing files and index entries kept instead of the imported ones.
+    pub kept: u64,
+    /// The category was left alone because the destination already had data in it.
+    pub skipped: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub manifest: Manifest,
+    /// Archive version before migration, when it was older than this server's.
+    pub migrated_from: Option<u32>,
+    pub categories: BTreeMap<Category, CategoryReport>,
+}
+
+/// An extracted, checked and migrated archive, waiting to be applied. The staging
+/// directory is removed when this is dropped.
+pub struct Staged {
+    capture_dir: PathBuf,
+    dir: PathBuf,
+    pub manifest: Manifest,
+    migrated_from: Option<u32>,
+    /// Relative paths of staged files, except the index.
+    files: Vec<PathBuf>,
+    /// Imported index entries, with paths pointing into the destination folder.
+    index: Vec<Value>,
+}
+
+impl Drop for Staged {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+impl Staged {
+    /// Extract `archive` into a staging directory inside `capture_dir`. Fails on
+    /// anything but regular files and directories, paths that could leave the
+    /// staging directory, a missing or foreign manifest, and archives from a newer
+    /// version.
+    pub fn extract(capture_dir: &Path, archive: &Path) -> Result<Self> {
+        Self::extract_within(capture_dir, archive, IMPORT_LIMITS)
+    }
+
+    fn extract_within(capture_dir: &Path, archive: &Path, limits: Limits) -> Result<Self> {
+        std::fs::create_dir_all(capture_dir)?;
+        let stamp = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let dir = capture_dir.join(format!("{STAGING_PREFIX}{}-{stamp}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let mut staged = Staged {
+            capture_dir: capture_dir.to_path_buf(),
+            dir,
+            manifest: Manifest {
+                format: String::new(),
+                version: 0,
+                created_at: String::new(),
+                exported_by: String::new(),
+                source_dir: String::new(),
+                categories: BTreeMap::new(),
+            },
+            migrated_from: None,
+            files: Vec::new(),
+            index: Vec::new(),
+        };
+
+        let file = std::fs::File::open(archive).with_context(|| format!("could not open {}", archive.display()))?;
+        let mut tar = tar::Archive::new(flate2::read::GzDecoder::new(file));
+        let mut manifest = None;
+        let mut total = 0u64;
+        let mut count = 0usize;
+        for entry in tar.entries().context("not a gzipped tar archive")? {
+            let mut entry = entry.context("corrupt archive entry")?;
+            count += 1;
+            if count > limits.entries {
+                bail!("archive has more than {} entries", limits.entries);
+            }
+            let raw_path = entry.path()?.into_owned();
+            let Some(path) = safe_relative(&raw_path) else {
+                bail!("archive entry {} has an unsafe path", raw_path.display());
+            };
+            match entry.header().entry_type() {
+                tar::EntryType::Directory => continue,
+                tar::EntryType::Regular => {}
+                other => bail!("archive entry {} is a {other:?}, only regular files are allowed", path.display()),
+            }
+            if path == Path::new(MANIFEST_NAME) {
+                let mut raw = String::new();
+                (&mut entry).take(limits.manifest_bytes).read_to_string(&mut raw)?;
+                manifest = Some(serde_json::from_str::<Manifest>(&raw).context("manifest.json is not a valid manifest")?);
+                continue;
+            }
+            let Ok(rel) = path.strip_prefix(DATA_DIR) else {
+                bail!("unexpected archive entry {}", path.display());
+            };
+            let rel = rel.to_path_buf();
+            let size = entry.header().size()?;
+            total += size;
+            if total > limits.bytes {
+                bail!("archive holds more than {} bytes", limits.bytes);
+            }
+            let dest = staged.dir.join(&rel);
+            if let Some(parent) = dest.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut out = std::fs::File::create(&dest)?;
+            std::io::copy(&mut (&mut entry).take(size), &mut out)?;
+            if rel != Path::new(INDEX_NAME) {
+                staged.files.push(rel);
+            }
+        }
+
+        let mut manifest = manifest.context("archive has no manifest.json")?;
+        if manifest.format != STATE_FORMAT {
+            bail!("not a YippieBlox state export (format '{}')", manifest.format);
+        }
+        if manifest.version > STATE_VERSION {
+            bail!(
+                "archive is version {}, this build reads up to {STATE_VERSION}; upgrade the server first",
+                manifest.version
+            );
+        }
+        let original = manifest.version;
+        for (from, migrate) in MIGRATIONS {
+            if manifest.version == *from {
+                migrate(&mut manifest, &staged.dir).with_context(|| format!("migrating archive from version {from}"))?;
+                manifest.version = from + 1;
+            }
+        }
+        if manifest.version != STATE_VERSION {
+            bail!("no migration from archive version {}", manifest.version);
+        }
+        staged.migrated_from = (original != STATE_VERSION).then_some(original);
+        staged.manifest = manifest;
+
+        let mut index = read_index(&staged.dir.join(INDEX_NAME))?;
+        rebase_index(&mut index, Path::new(""), Some(capture_dir));
+        staged.index = index;
+        staged.files.sort();
+        Ok(staged)
+    }
+
+    fn existing_ids(&self) -> Result<BTreeSet<String>> {
+        Ok(read_index(&self.capture_dir.join(INDEX_NAME))?
+            .iter()
+            .filter_map(|e| e.get("id").and_then(|v| v.as_str()).map(String::from))
+            .collect())
+    }
+
+    /// Files and index entries per category that already exist in the destination.
+    pub fn conflicts(&self) -> Result<BTreeMap<Category, u64>> {
+        let mut conflicts: BTreeMap<Category, u64> = Category::ALL.iter().map(|c| (*c, 0)).collect();
+        for rel in &self.files {
+            if self.capture_dir.join(rel).exists() {
+                *conflicts.entry(Category::of(rel)).or_default() += 1;
+            }
+        }
+        let existing = self.existing_ids()?;
+        let clashing = self
+            .index
+            .iter()
+            .filter(|e| e.get("id").and_then(|v| v.as_str()).is_some_and(|id| existing.contains(id)))
+            .count() as u64;
+        *conflicts.entry(Category::Captures).or_default() += clashing;
+        Ok(conflicts)
+    }
+
+    /// Whether the destination already has data in `category`.
+    fn destination_has(&self, category: Category) -> Result<bool> {
+        Ok(match category {
+            Category::Captures => {
+                !read_index(&self.capture_dir.join(INDEX_NAME))?.is_empty()
+                    || collect_files(&self.capture_dir)?
+                        .iter()
+                        .any(|rel| Category::of(rel) == Category::Captures && rel != Path::new(INDEX_NAME))
+            }
+            Category::Replays | Category::Logs => {
+                let dir = self.capture_dir.join(category.name());
+                dir.is_dir() && std::fs::read_dir(&dir)?.next().is_some()
+            }
+        })
+    }
+
+    /// Move staged data into the capture folder per `policy`. Errors are collected
+    /// per category; one failing file doesn't stop the rest.
+    pub fn apply(self, policy: &ImportPolicy) -> ImportReport {
+        let mut categories = BTreeMap::new();
+        let mut failed_files = BTreeSet::new();
+        for category in Category::ALL {
+            let conflict = policy.get(category);
+            let mut report = CategoryReport { policy: conflict, ..Default::default() };
+            match self.destination_has(category) {
+                Ok(true) if conflict == Conflict::Skip => {
+                    report.skipped = true;
+                    categories.insert(category, report);
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    report.errors.push(format!("{e:#}"));
+                    categories.insert(category, report);
+                    continue;
+                }
+            }
+            for rel in self.files.iter().filter(|rel| Category::of(rel) == category) {
+                let dest = self.capture_dir.join(rel);
+                let exists = dest.exists();
+                if exists && conflict != Conflict::Overwrite {
+                    report.kept += 1;
+                    continue;
+                }
+                let moved = (|| -> Result<()> {
+                    if let Some(parent) = dest.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    if exists {
+                        std::fs::remove_file(&dest)?;
+                    }
+                    std::fs::rename(self.dir.join(rel), &dest)?;
+                    Ok(())
+                })();
+                match moved {
+                    Ok(()) if exists => report.replaced += 1,
+                    Ok(()) => report.added += 1,
+                    Err(e) => {
+                        report.errors.push(format!("{}: {e:#}", rel.display()));
+                        failed_files.insert(self.capture_dir.join(rel));
+                    }
+                }
+            }
+            if category == Category::Captures {
+                if let Err(e) = self.merge_index(conflict, &failed_files, &mut report) {
+                    report.errors.push(format!("index.json: {e:#}"));
+                }
+            }
+            categories.insert(category, report);
+        }
+        ImportReport {
+            manifest: self.manifest.clone(),
+            migrated_from: self.migrated_from,
+            categories,
+        }
+    }
+
+    /// Add imported index entries to the destination index. Entries whose file could
+    /// not be moved are left out.
+    fn merge_index(&self, conflict: Conflict, failed_files: &BTreeSet<PathBuf>, report: &mut CategoryReport) -> Result<()> {
+        if self.index.is_empty() {
+            return Ok(());
+        }
+        // A server using this folder may add captures meanwhile
+        let _lock = IndexLock::acquire(&self.capture_dir)?;
+        let mut entries = read_index(&self.capture_dir.join(INDEX_NAME))?;
+        for imported in &self.index {
+            let file_failed = imported
+                .get("file_path")
+                .and_then(|p| p.as_str())
+                .is_some_and(|p| failed_files.contains(Path::new(p)));
+            if file_failed {
+                continue;
+            }
+            let id = imported.get("id").and_then(|v| v.as_str());
+            match entries.iter().position(|e| id.is_some() && e.get("id").and_then(|v| v.as_str()) == id) {
+                Some(i) if conflict == Conflict::Overwrite => {
+                    entries[i] = imported.clone();
+                    report.replaced += 1;
+                }
+                Some(_) => report.kept += 1,
+                None => {
+                    entries.push(imported.clone());
+                    report.added += 1;
+                }
+            }
+        }
+        write_index(&self.capture_dir, &entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::captures::CaptureManager;
+    use crate::ids::IdGenerator;
+    use crate::providers::{SeededRng, SystemClock};
+    use std::sync::Arc;
+
+    fn manager(dir: &Path, seed: u64) -> CaptureManager {
+        let ids = Arc::new(IdGenerator::new(Arc::new(SeededRng::new(seed))));
+        CaptureManager::new(dir, false, ids, Arc::new(SystemClock)).unwrap()
+    }
+
+    /// A capture folder with data in every category, plus files an export leaves out.
+    fn populate(dir: &Path) -> String {
+        let captures = manager(dir, 1);
+        captures.save_test_matrix_report(Some("nightly".into()), "{\"passed\":3}").unwrap();
+        captures.save_time_report(None, "frame times".into(), "t,ms\n0,16\n").unwrap();
+        let (_, snapshot) = captures.save_model_snapshot("Workspace", "{\"name\":\"Workspace\"}").unwrap();
+        write(dir, "replays/boot.json", "[{\"tool\":\"studio-status\"}]");
+        write(dir, "logs/segment-0001.jsonl", "{\"message\":\"hello\"}\n");
+        write(dir, ".servers/1234.json", "{}");
+        write(dir, "exports/old.tar.gz", "old");
+        write(dir, "draft.tmp", "partial");
+        snapshot
+    }
+
+    fn write(dir: &Path, rel: &str, content: &str) {
+        let path = dir.join(rel);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    fn read(dir: &Path, rel: &str) -> String {
+        std::fs::read_to_string(dir.join(rel)).unwrap()
+    }
+
+    fn export_to(dir: &Path, out: &Path) -> Manifest {
+        export(dir, out, "test").unwrap()
+    }
+
+    fn import(dir: &Path, archive: &Path, policy: &ImportPolicy) -> ImportReport {
+        Staged::extract(dir, archive).unwrap().apply(policy)
+    }
+
+    fn policy(conflict: Conflict) -> ImportPolicy {
+        let mut policy = ImportPolicy::default();
+        for category in Category::ALL {
+            policy.set(category, conflict);
+        }
+        policy
+    }
+
+    fn staging_dirs(dir: &Path) -> usize {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy().starts_with(STAGING_PREFIX))
+            .count()
+    }
+
+    #[test]
+    fn export_leaves_out_runtime_and_temp_files() {
+        let tmp = tempfile::tempdir().unwrap();
+        let src = tmp.path().join("src");
+        populate(&src);
+        let manifest = export_to(&src, &tmp.path().join("state.tar.gz"));
+
+        assert_eq!(manifest.format, STATE_FORMAT);
+        assert_eq!(manifest.version, STATE_VERSION);
+        let files = collect_files(&src).unwrap();
+        assert!(files.iter().all(|rel| !rel.starts_with(".servers") && !rel.starts_with(EXPORTS_DIR)));
+        assert!(!files.contains(&PathBuf::from("draft.tmp")));
+        assert_eq!(manifest.categories[&Category::Replays].files, 1);
+        assert_eq!(manifest.categories[&Category::Logs].files, 1);
+        // index.json, the report, the CSV and the snapshot
+        assert_eq!(manifest.categories[&Category::Captures].files, 4);
+    }
+
+    /// A fresh folder restored from an export lists the same captures, with paths in
+    /// the new folder, and serves the same files.
+    #[test]
+    fn round_trip_into_a_fresh_folder() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (src, dst) = (tmp.path().join("src"), tmp.path().join("dst"));
+        let snapshot = populate(&src);
+        let archive = tmp.path().join("state.tar.gz");
+        export_to(&src, &archive);
+
+        let report = import(&dst, &archive, &ImportPolicy::default());
+        assert_eq!(report.migrated_from, None);
+        for category in report.categories.values() {
+            assert!(category.errors.is_empty(), "{:?}", category.errors);
+            assert!(!category.skipped);
+        }
+
+        let before = manager(&src, 2).list_captures().unwrap();
+        let after = manager(&dst, 3).list_captures().unwrap();
+        assert_eq!(before.len(), after.len());
+        for (old, new) in before.iter().zip(&after) {
+            assert_eq!((&old.id, &old.capture_type, &old.tag, &old.note), (&new.id, &new.capture_type, &new.tag, &new.note));
+            let (old_path, new_path) = (old.file_path.as_ref().unwrap(), new.file_path.as_ref().unwrap());
+            let rel = Path::new(old_path).strip_prefix(&src).unwrap();
+            assert_eq!(Path::new(new_path), dst.join(rel));
+            assert_eq!(std::fs::read(old_path).unwrap(), std::fs::read(new_path).unwrap());
+        }
+        assert_eq!(
+            manager(&dst, 4).load_model_snapshot(&snapshot).unwrap(),
+            manager(&src, 5).load_model_snapshot(&snapshot).unwrap()
+        );
+        assert_eq!(read(&dst, "replays/boot.json"), read(&src, "replays/boot.json"));
+        assert_eq!(read(&dst, "logs/segment-0001.jsonl"), read(&src, "logs/segment-0001.jsonl"));
+        assert!(!dst.join(".servers").exists() && !dst.join(EXPORTS_DIR).exists());
+        assert_eq!(staging_dirs(&dst), 0);
+
+        // Exporting the restored folder gives the same contents again
+        let again = export_to(&dst, &tmp.path().join("again.tar.gz"));
+        let first = export_to(&src, &tmp.path().join("first.tar.gz"));
+        for category in Category::ALL {
+            assert_eq!(first.categories[&category].files, again.categories[&category].files);
+            assert_eq!(first.categories[&category].bytes, again.categories[&category].bytes);
+        }
+    }
+
+    /// A destination that already has `boot.json`, one of the imported index entries
+    /// (under its own tag) and an entry of its own.
+    fn conflicting_destination(tmp: &Path) -> (PathBuf, PathBuf, String) {
+        let (src, dst) = (tmp.join("src"), tmp.join("dst"));
+        populate(&src);
+        let archive = tmp.join("state.tar.gz");
+        export_to(&src, &archive);
+
+        let shared = manager(&src, 6).list_captures().unwrap().remove(0);
+        let dst_captures = manager(&dst, 7);
+        let mut clash = shared.clone();
+        clash.tag = Some("local".into());
+        clash.file_path = None;
+        dst_captures.record_capture(clash).unwrap();
+        let own = dst_captures.save_time_report(Some("own".into()), "mine".into(), "a\n").unwrap();
+        write(&dst, "replays/boot.json", "local replay");
+        (dst, archive, own.id)
+    }
+
+    fn tag_of(dir: &Path, id: &str) -> Option<String> {
+        manager(dir, 8).list_captures().unwrap().into_iter().find(|e| e.id == id).unwrap().tag
+    }
+
+    #[test]
+    fn merge_keeps_existing_data_and_adds_the_rest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dst, archive, own) = conflicting_destination(tmp.path());
+        let shared = manager(&tmp.path().join("src"), 9).list_captures().unwrap().remove(0);
+        let report = import(&dst, &archive, &policy(Conflict::Merge));
+
+        assert_eq!(read(&dst, "replays/boot.json"), "local replay");
+        assert_eq!(report.categories[&Category::Replays].kept, 1);
+        assert_eq!(tag_of(&dst, &shared.id).as_deref(), Some("local"));
+        assert_eq!(tag_of(&dst, &own).as_deref(), Some("own"));
+        let captures = &report.categories[&Category::Captures];
+        assert_eq!(captures.kept, 1);
+        assert_eq!(manager(&dst, 10).list_captures().unwrap().len(), 4);
+        assert!(dst.join("logs/segment-0001.jsonl").exists());
+    }
+
+    #[test]
+    fn overwrite_replaces_existing_data_and_keeps_the_rest() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dst, archive, own) = conflicting_destination(tmp.path());
+        let shared = manager(&tmp.path().join("src"), 11).list_captures().unwrap().remove(0);
+        let report = import(&dst, &archive, &policy(Conflict::Overwrite));
+
+        assert_eq!(read(&dst, "replays/boot.json"), "[{\"tool\":\"studio-status\"}]");
+        assert_eq!(report.categories[&Category::Replays].replaced, 1);
+        assert_eq!(tag_of(&dst, &shared.id), shared.tag);
+        assert_eq!(tag_of(&dst, &own).as_deref(), Some("own"));
+        assert_eq!(report.categories[&Category::Captures].replaced, 1);
+        assert_eq!(manager(&dst, 12).list_captures().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn skip_leaves_categories_with_data_alone() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dst, archive, own) = conflicting_destination(tmp.path());
+        let report = import(&dst, &archive, &policy(Conflict::Skip));
+
+        assert!(report.categories[&Category::Captures].skipped);
+        assert!(report.categories[&Category::Replays].skipped);
+        assert_eq!(read(&dst, "replays/boot.json"), "local replay");
+        assert_eq!(manager(&dst, 13).list_captures().unwrap().len(), 2);
+        assert_eq!(tag_of(&dst, &own).as_deref(), Some("own"));
+        // The destination had no logs, so they are imported
+        assert!(!report.categories[&Category::Logs].skipped);
+        assert_eq!(report.categories[&Category::Logs].added, 1);
+        assert!(dst.join("logs/segment-0001.jsonl").exists());
+    }
+
+    #[test]
+    fn conflicts_are_counted_before_applying() {
+        let tmp = tempfile::tempdir().unwrap();
+        let (dst, archive, _) = conflicting_destination(tmp.path());
+        let staged = Staged::extract(&dst, &archive).unwrap();
+        let conflicts = staged.conflicts().unwrap();
+        assert_eq!(conflicts[&Category::Replays], 1);
+        assert_eq!(conflicts[&Category::Captures], 1);
+        assert_eq!(conflicts[&Category::Logs], 0);
+        drop(staged);
+        assert_eq!(staging_dirs(&dst), 0);
+    }
+
+    #[test]
+    fn conflict_policy_names() {
+        assert_eq!(Conflict::parse(" Skip ").unwrap(), Conflict::Skip);
+        assert_eq!(Conflict::parse("OVERWRITE").unwrap(), Conflict::Overwrite);
+        assert_eq!(Conflict::parse("merge").unwrap(), Conflict::Merge);
+        assert!(Conflict::parse("replace").unwrap_err().contains("expected skip, overwrite or merge"));
+        assert_eq!(ImportPolicy::default().get(Category::Logs), Conflict::Merge);
+    }
+
+    // ─── Hostile archives ───
+
+    enum Entry<'a> {
+        File(&'a [u8], &'a [u8]),
+        Link(&'a [u8], tar::EntryType, &'a [u8]),
+    }
+
+    fn manifest_json(format: &str, version: u32) -> Vec<u8> {
+        serde_json::to_vec(&Manifest {
+            format: format.into(),
+            version,
+            created_at: String::new(),
+            exported_by: "test".into(),
+            source_dir: "/elsewhere".into(),
+            categories: BTreeMap::new(),
+        })
+        .unwrap()
+    }
+
+    /// Write entries with raw header names, bypassing the path checks of `tar::Builder`.
+    fn hostile_archive(path: &Path, entries: &[Entry]) {
+        let file = std::fs::File::create(path).unwrap();
+        let mut builder = tar::Builder::new(flate2::write::GzEncoder::new(file, flate2::Compression::default()));
+        for entry in entries {
+            let mut header = tar::Header::new_gnu();
+            let (name, data, kind, link): (&[u8], &[u8], _, &[u8]) = match entry {
+                Entry::File(name, data) => (name, data, tar::EntryType::Regular, b""),
+                Entry::Link(name, kind, target) => (name, b"", *kind, target),
+            };
+            header.as_old_mut().name[..name.len()].copy_from_slice(name);
+            header.as_old_mut().linkname[..link.len()].copy_from_slice(link);
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_entry_type(kind);
+            header.set_cksum();
+            builder.append(&header, data).unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap();
+    }
+
+    /// Import `entries` into a fresh folder inside a parent that must stay untouched.
+    fn import_hostile(entries: &[Entry], limits: Limits) -> String {
+        let tmp = tempfile::tempdir().unwrap();
+        let dst = tmp.path().join("dst");
+        let archive = tmp.path().join("hostile.tar.gz");
+        hostile_archive(&archive, entries);
+        let err = Staged::extract_within(&dst, &archive, limits).err().expect("archive should be refused");
+        assert_eq!(staging_dirs(&dst), 0, "staging directory left behind");
+        let mut left: Vec<_> = std::fs::read_dir(tmp.path()).unwrap().map(|e| e.unwrap().file_name()).collect();
+        left.sort();
+        assert_eq!(left, ["dst", "hostile.tar.gz"], "files written outside the capture folder");
+        assert_eq!(std::fs::read_dir(&dst).unwrap().count(), 0);
+        format!("{err:#}")
+    }
+
+    fn manifest() -> Vec<u8> {
+        manifest_json(STATE_FORMAT, STATE_VERSION)
+    }
+
+    #[test]
+    fn traversal_entries_are_refused() {
+        let m = manifest();
+        for name in [&b"../evil"[..], b"data/../../evil", b"data/../../../evil", b"/tmp/evil", b"data//../evil"] {
+            let err = import_hostile(&[Entry::File(b"manifest.json", &m), Entry::File(name, b"x")], IMPORT_LIMITS);
+            assert!(err.contains("unsafe path"), "{}: {err}", String::from_utf8_lossy(name));
+        }
+    }
+
+    #[test]
+    fn links_and_special_entries_are_refused() {
+        let m = manifest();
+        for (kind, target) in [
+            (tar::EntryType::Symlink, &b"/etc/passwd"[..]),
+            (tar::EntryType::Symlink, b"../.."),
+            (tar::EntryType::Link, b"manifest.json"),
+            (tar::EntryType::Fifo, b""),
+        ] {
+            let err = import_hostile(
+                &[Entry::File(b"manifest.json", &m), Entry::Link(b"data/link", kind, target)],
+                IMPORT_LIMITS,
+            );
+            assert!(err.contains("only regular files are allowed"), "{kind:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn entries_outside_data_are_refused() {
+        let m = manifest();
+        let err = import_hostile(&[Entry::File(b"manifest.json", &m), Entry::File(b"other/x", b"x")], IMPORT_LIMITS);
+        assert!(err.contains("unexpected archive entry other/x"), "{err}");
+    }
+
+    #[test]
+    fn entry_and_size_caps_are_enforced() {
+        let m = manifest();
+        let small = Limits { bytes: 10, entries: 3, manifest_bytes: 1024 };
+        let files = [
+            Entry::File(b"manifest.json", &m),
+            Entry::File(b"data/a", b"1"),
+            Entry::File(b"data/b", b"2"),
+            Entry::File(b"data/c", b"3"),
+        ];
+        let err = import_hostile(&files, small);
+        assert!(err.contains("more than 3 entries"), "{err}");
+
+        let big = [0u8; 11];
+        let err = import_hostile(&[Entry::File(b"manifest.json", &m), Entry::File(b"data/big", &big)], small);
+        assert!(err.contains("more than 10 bytes"), "{err}");
+
+        let tight = Limits { manifest_bytes: 8, ..small };
+        let err = import_hostile(&[Entry::File(b"manifest.json", &m)], tight);
+        assert!(err.contains("not a valid manifest"), "{err}");
+    }
+
+    #[test]
+    fn foreign_newer_and_missing_manifests_are_refused() {
+        let err = import_hostile(&[Entry::File(b"data/a", b"1")], IMPORT_LIMITS);
+        assert!(err.contains("no manifest.json"), "{err}");
+
+        let foreign = manifest_json("something-else", 1);
+        let err = import_hostile(&[Entry::File(b"manifest.json", &foreign)], IMPORT_LIMITS);
+        assert!(err.contains("not a YippieBlox state export"), "{err}");
+
+        let newer = manifest_json(STATE_FORMAT, STATE_VERSION + 1);
+        let err = import_hostile(&[Entry::File(b"manifest.json", &newer)], IMPORT_LIMITS);
+        assert!(err.contains("upgrade the server first"), "{err}");
+    }
+
+    #[test]
+    fn not_an_archive_is_refused() {
+        let tmp = tempfile::tempdir().unwrap();
+        let archive = tmp.path().join("junk.tar.gz");
+        std::fs::write(&archive, b"not gzip").unwrap();
+        let dst = tmp.path().join("dst");
+        assert!(Staged::extract(&dst, &archive).is_err());
+        assert_eq!(staging_dirs(&dst), 0);
+    }
+}
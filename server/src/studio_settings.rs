@@ -0,0 +1,152 @@
+//! The whitelist behind `studio-set_studio_setting`: Studio settings (`settings()`,
+//! not the place) that tests may need toggled, such as physics visualization or
+//! replication lag.
+//!
+//! Studio settings are the user's own preferences and persist across places and
+//! restarts, so every change is temporary. The server keeps the value from before the
+//! first change and puts it back when the playtest that was running at that change
+//! ends, or when the session is cleaned up (idle or disconnect, see `idle.rs`).
+//! Settings not listed here are refused before the plugin sees them.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+use crate::mcp_stdio::{call_plugin, TOOL_CALL_TIMEOUT};
+use crate::runtime_flags::{FlagKind, RuntimeFlag};
+use crate::state::SharedState;
+
+/// `Group.Property`, where the group is a child of `settings()` (Physics, Network).
+/// Validated like runtime flags.
+pub const SETTINGS: &[RuntimeFlag] = &[
+    RuntimeFlag {
+        name: "Physics.AreOwnersShown",
+        kind: FlagKind::Bool,
+        description: "Color parts by network owner",
+    },
+    RuntimeFlag {
+        name: "Physics.AreAssembliesShown",
+        kind: FlagKind::Bool,
+        description: "Outline physics assemblies",
+    },
+    RuntimeFlag {
+        name: "Physics.AreContactPointsShown",
+        kind: FlagKind::Bool,
+        description: "Show contact points between parts",
+    },
+    RuntimeFlag {
+        name: "Physics.AreAwakePartsHighlighted",
+        kind: FlagKind::Bool,
+        description: "Highlight parts that are not sleeping",
+    },
+    RuntimeFlag {
+        name: "Physics.ShowDecompositionGeometry",
+        kind: FlagKind::Bool,
+        description: "Show collision geometry instead of render geometry",
+    },
+    RuntimeFlag {
+        name: "Physics.AllowSleep",
+        kind: FlagKind::Bool,
+        description: "Let resting parts sleep",
+    },
+    RuntimeFlag {
+        name: "Physics.PhysicsEnvironmentalThrottle",
+        kind: FlagKind::Enum(&["DefaultAuto", "Disabled", "Always", "Skip2", "Skip4", "Skip8", "Skip16"]),
+        description: "Physics throttling under load",
+    },
+    RuntimeFlag {
+        name: "Network.IncomingReplicationLag",
+        kind: FlagKind::Number { min: 0.0, max: 5.0 },
+        description: "Simulated replication lag in seconds",
+    },
+];
+
+/// Value of a setting from before its first change, and the playtest that was
+/// running then. Settings changed outside a playtest wait for the session cleanup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Original {
+    pub value: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub playtest: Option<String>,
+}
+
+pub fn find(name: &str) -> Option<&'static RuntimeFlag> {
+    SETTINGS.iter().find(|setting| setting.name == name)
+}
+
+/// The error for a setting that isn't whitelisted.
+pub fn not_allowed(name: &str) -> String {
+    let names: Vec<&str> = SETTINGS.iter().map(|setting| setting.name).collect();
+    format!(
+        "'{name}' is not on the Studio setting whitelist. Allowed settings: {}. Other Studio preferences can't be changed with this tool.",
+        names.join(", ")
+    )
+}
+
+/// The whitelist as returned by `list`.
+pub fn list() -> Value {
+    Value::Array(
+        SETTINGS
+            .iter()
+            .map(|setting| {
+                let allowed = match setting.kind {
+                    FlagKind::Bool => json!("boolean"),
+                    FlagKind::Number { min, max } => json!({ "min": min, "max": max }),
+                    FlagKind::Enum(items) => json!(items),
+                };
+                json!({ "setting": setting.name, "allowed": allowed, "description": setting.description })
+            })
+            .collect(),
+    )
+}
+
+/// `{ settings: { group: { property: value } } }` for `studio-set_studio_settings`.
+pub fn changes<'a>(values: impl IntoIterator<Item = (&'a RuntimeFlag, Value)>) -> Value {
+    let mut settings = json!({});
+    for (setting, value) in values {
+        settings[setting.service()][setting.property()] = value;
+    }
+    json!({ "settings": settings })
+}
+
+/// Put back the given settings and forget them. On failure they stay recorded, so a
+/// later restore tries again.
+pub async fn restore(state: &SharedState, originals: &BTreeMap<String, Original>) -> Result<Value, String> {
+    let targets: Vec<(&RuntimeFlag, Value)> = originals
+        .iter()
+        .filter_map(|(name, original)| Some((find(name)?, original.value.clone())))
+        .collect();
+    if !targets.is_empty() {
+        call_plugin(state, "studio-set_studio_settings", changes(targets), TOOL_CALL_TIMEOUT).await?;
+    }
+    for name in originals.keys() {
+        state.forget_studio_setting(name).await;
+    }
+    Ok(originals.iter().map(|(name, original)| (name.clone(), original.value.clone())).collect())
+}
+
+/// Restore settings changed during a playtest once that playtest is over.
+pub async fn run(state: SharedState) {
+    loop {
+        // Register for wakeups before reading state so a change in between isn't missed
+        let changed = state.playtest_changed().notified();
+        tokio::pin!(changed);
+        changed.as_mut().enable();
+
+        let (active, session_id, _) = state.playtest_info().await;
+        let ended: BTreeMap<String, Original> = state
+            .studio_setting_originals()
+            .await
+            .into_iter()
+            .filter(|(_, original)| original.playtest.is_some() && (!active || original.playtest != session_id))
+            .collect();
+        if !ended.is_empty() {
+            match restore(&state, &ended).await {
+                Ok(restored) => tracing::info!(settings = %restored, "Playtest ended, restored Studio settings"),
+                Err(e) => tracing::warn!(error = %e, "Playtest ended, but restoring Studio settings failed"),
+            }
+        }
+        changed.await;
+    }
+}
@@ -0,0 +1,501 @@
+//! `studio-test_matrix`: run one test_script under several variants (playtest mode,
+//! edit-mode setup code, Lighting/Workspace property overrides) one after another.
+//!
+//! Each variant goes through setup → apply overrides → test → revert overrides. A
+//! failing variant doesn't stop the rest unless `failFast` is set; a failed revert
+//! always does, since later variants would start from an unknown state.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use crate::mcp_stdio::{call_plugin, TOOL_CALL_TIMEOUT};
+use crate::state::SharedState;
+
+/// Upper bound on variants in one matrix.
+pub const MAX_VARIANTS: usize = 16;
+
+/// Per-variant test timeout when none is given, in seconds.
+const DEFAULT_VARIANT_TIMEOUT_SECS: f64 = 30.0;
+
+/// Longest per-variant test timeout, in seconds.
+const MAX_VARIANT_TIMEOUT_SECS: f64 = 300.0;
+
+/// Longest total of all variant timeouts. The stdio loop handles one request at a
+/// time, so a matrix holds up everything else while it runs.
+const MAX_TOTAL_TIMEOUT_SECS: f64 = 1800.0;
+
+/// Extra time the plugin gets past the test timeout to stop the playtest and answer.
+pub const STOP_GRACE: Duration = Duration::from_secs(15);
+
+/// Services whose properties a variant may override.
+const OVERRIDABLE_SERVICES: &[&str] = &["Lighting", "Workspace"];
+
+/// Warnings/errors kept per variant in the summary (the artifact has all of them).
+const SUMMARY_ERRORS_PER_VARIANT: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    #[default]
+    Run,
+    Play,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct Variant {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub mode: Mode,
+    /// Edit-mode code run through studio-run_script before the test.
+    #[serde(default)]
+    pub setup: Option<String>,
+    /// `{ "Lighting": { "ClockTime": 0 }, "Workspace": { "Gravity": 50 } }`
+    #[serde(default)]
+    pub properties: BTreeMap<String, Map<String, Value>>,
+    /// Overrides the matrix-wide `timeout`, in seconds.
+    #[serde(default)]
+    pub timeout: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Matrix {
+    pub code: String,
+    pub variants: Vec<Variant>,
+    pub fail_fast: bool,
+}
+
+impl Matrix {
+    /// Parse and bound-check the tool arguments.
+    pub fn parse(arguments: &Value) -> Result<Self> {
+        let code = arguments
+            .get("code")
+            .and_then(|v| v.as_str())
+            .filter(|c| !c.trim().is_empty())
+            .context("Missing required argument: code")?
+            .to_string();
+        let list = arguments.get("variants").context("Missing required argument: variants")?;
+        let mut variants: Vec<Variant> = serde_json::from_value(list.clone()).context(
+            "'variants' must be an array of { name?, mode?, setup?, properties?, timeout? } objects",
+        )?;
+        if variants.is_empty() {
+            bail!("'variants' is empty");
+        }
+        if variants.len() > MAX_VARIANTS {
+            bail!("{} variants (max {MAX_VARIANTS})", variants.len());
+        }
+
+        let default_timeout = match arguments.get("timeout") {
+            None | Some(Value::Null) => DEFAULT_VARIANT_TIMEOUT_SECS,
+            Some(v) => v.as_f64().context("'timeout' must be a number of seconds")?,
+        };
+        let mut total = 0.0;
+        for (i, variant) in variants.iter_mut().enumerate() {
+            let timeout = variant.timeout.unwrap_or(default_timeout);
+            if !(timeout > 0.0 && timeout <= MAX_VARIANT_TIMEOUT_SECS) {
+                bail!("Variant {i}: timeout must be between 0 and {MAX_VARIANT_TIMEOUT_SECS} seconds");
+            }
+            variant.timeout = Some(timeout);
+            total += timeout;
+            for service in variant.properties.keys() {
+                if !OVERRIDABLE_SERVICES.contains(&service.as_str()) {
+                    bail!("Variant {i}: property overrides are limited to Lighting and Workspace, got '{service}'");
+                }
+            }
+            if variant.setup.as_deref().is_some_and(|s| s.trim().is_empty()) {
+                variant.setup = None;
+            }
+        }
+        if total > MAX_TOTAL_TIMEOUT_SECS {
+            bail!("Variant timeouts add up to {total}s (max {MAX_TOTAL_TIMEOUT_SECS}s)");
+        }
+
+        Ok(Self {
+            code,
+            variants,
+            fail_fast: arguments.get("failFast").and_then(|v| v.as_bool()).unwrap_or(false),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantStatus {
+    Passed,
+    Failed,
+    TimedOut,
+    SetupFailed,
+    ApplyFailed,
+    /// Not run, because of `failFast` or an earlier failed revert.
+    Skipped,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VariantOutcome {
+    pub index: usize,
+    pub name: String,
+    pub mode: Mode,
+    pub status: VariantStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    /// Overrides were applied but could not all be put back.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_error: Option<String>,
+    pub duration_ms: u64,
+    pub logs: Vec<Value>,
+    pub errors: Vec<Value>,
+}
+
+impl VariantOutcome {
+    fn new(index: usize, variant: &Variant) -> Self {
+        Self {
+            index,
+            name: variant.name.clone().unwrap_or_else(|| format!("variant {index}")),
+            mode: variant.mode,
+            status: VariantStatus::Skipped,
+            error: None,
+            value: None,
+            revert_error: None,
+            duration_ms: 0,
+            logs: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    fn passed(&self) -> bool {
+        self.status == VariantStatus::Passed
+    }
+
+    /// Summary entry: log counts and the first few warnings/errors instead of full logs.
+    pub fn summary(&self) -> Value {
+        let mut entry = serde_json::to_value(self).unwrap_or_default();
+        if let Some(obj) = entry.as_object_mut() {
+            obj.remove("logs");
+            obj.insert("logCount".into(), json!(self.logs.len()));
+            obj.insert("errorCount".into(), json!(self.errors.len()));
+            obj.insert(
+                "errors".into(),
+                json!(self.errors.iter().take(SUMMARY_ERRORS_PER_VARIANT).collect::<Vec<_>>()),
+            );
+        }
+        entry
+    }
+}
+
+/// Run every variant in order and return one outcome per variant.
+pub async fn run(state: &SharedState, matrix: &Matrix) -> Vec<VariantOutcome> {
+    let mut outcomes: Vec<VariantOutcome> = Vec::with_capacity(matrix.variants.len());
+    let mut stop_reason: Option<String> = None;
+
+    for (index, variant) in matrix.variants.iter().enumerate() {
+        let mut outcome = VariantOutcome::new(index, variant);
+        if let Some(reason) = &stop_reason {
+            outcome.error = Some(reason.clone());
+            outcomes.push(outcome);
+            continue;
+        }
+
+        let started = Instant::now();
+        run_variant(state, &matrix.code, variant, &mut outcome).await;
+        outcome.duration_ms = state.clock().elapsed(started).as_millis() as u64;
+        tracing::info!(variant = %outcome.name, status = ?outcome.status, "Test matrix variant finished");
+
+        if outcome.revert_error.is_some() {
+            stop_reason = Some(format!(
+                "Skipped: property overrides of '{}' could not be reverted",
+                outcome.name
+            ));
+        } else if matrix.fail_fast && !outcome.passed() {
+            stop_reason = Some(format!("Skipped: failFast after '{}' did not pass", outcome.name));
+        }
+        outcomes.push(outcome);
+    }
+    outcomes
+}
+
+/// setup → apply → test → revert. Overrides are reverted whenever they were applied.
+async fn run_variant(state: &SharedState, code: &str, variant: &Variant, outcome: &mut VariantOutcome) {
+    if let Some(setup) = &variant.setup {
+        if let Err(e) = call_plugin(state, "studio-run_script", json!({ "code": setup }), TOOL_CALL_TIMEOUT).await {
+            outcome.status = VariantStatus::SetupFailed;
+            outcome.error = Some(e);
+            return;
+        }
+    }
+
+    let mut previous = None;
+    if !variant.properties.is_empty() {
+        let overrides = json!({ "overrides": variant.properties });
+        match call_plugin(state, "studio-set_properties", overrides, TOOL_CALL_TIMEOUT).await {
+            Ok(result) => previous = Some(result.get("previous").cloned().unwrap_or_else(|| json!({}))),
+            Err(e) => {
+                outcome.status = VariantStatus::ApplyFailed;
+                outcome.error = Some(e);
+                return;
+            }
+        }
+    }
+
+    let timeout = variant.timeout.unwrap_or(DEFAULT_VARIANT_TIMEOUT_SECS);
+    let args = json!({ "code": code, "mode": variant.mode, "timeout": timeout });
+    let call_timeout = Duration::from_secs_f64(timeout) + STOP_GRACE;
+    match call_plugin(state, "studio-test_script", args, call_timeout).await {
+        Ok(result) => record_test_result(outcome, &result),
+        Err(e) => {
+            outcome.status = VariantStatus::Failed;
+            outcome.error = Some(e);
+        }
+    }
+
+    if let Some(previous) = previous {
+        let revert = json!({ "overrides": previous });
+        if let Err(e) = call_plugin(state, "studio-set_properties", revert, TOOL_CALL_TIMEOUT).await {
+            outcome.revert_error = Some(e);
+        }
+    }
+}
+
+fn record_test_result(outcome: &mut VariantOutcome, result: &Value) {
+    let list = |key: &str| result.get(key).and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    outcome.logs = list("logs");
+    outcome.errors = list("errors");
+    outcome.value = result.get("value").filter(|v| !v.is_null()).cloned();
+    outcome.error = result.get("error").and_then(|v| v.as_str()).map(String::from);
+    outcome.status = if result.get("timedOut").and_then(|v| v.as_bool()) == Some(true) {
+        VariantStatus::TimedOut
+    } else if result.get("success").and_then(|v| v.as_bool()) == Some(true) {
+        VariantStatus::Passed
+    } else {
+        VariantStatus::Failed
+    };
+}
+
+/// Counts across all outcomes.
+pub fn totals(outcomes: &[VariantOutcome]) -> Value {
+    let count = |status: VariantStatus| outcomes.iter().filter(|o| o.status == status).count();
+    json!({
+        "total": outcomes.len(),
+        "passed": count(VariantStatus::Passed),
+        "failed": outcomes.len() - count(VariantStatus::Passed) - count(VariantStatus::Skipped),
+        "skipped": count(VariantStatus::Skipped),
+        "durationMs": outcomes.iter().map(|o| o.duration_ms).sum::<u64>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock_studio::scripted;
+
+    /// A connected plugin answering each call with `answer(tool, arguments)`, `Err`
+    /// being a failed call. Returns the calls it got.
+    async fn scripted_plugin(
+        answer: impl Fn(&str, &Value) -> Result<Value, String> + Send + 'static,
+    ) -> (SharedState, scripted::Calls) {
+        let state = SharedState::for_tests(Config::default());
+        let calls = scripted::answering(&state, answer).await;
+        (state, calls)
+    }
+
+    /// Passes in run mode and fails in play mode; set_properties reports Gravity 196.2.
+    fn by_mode(tool: &str, arguments: &Value) -> Result<Value, String> {
+        match tool {
+            "studio-test_script" if arguments["mode"] == "play" => {
+                Ok(json!({ "success": false, "error": "expected 1, got 2", "logs": [{ "message": "a" }], "errors": [] }))
+            }
+            "studio-test_script" => Ok(json!({ "success": true, "value": 42, "logs": [{ "message": "a" }, { "message": "b" }] })),
+            "studio-set_properties" => Ok(json!({ "previous": { "Workspace": { "Gravity": 196.2 } } })),
+            _ => Ok(json!({ "success": true })),
+        }
+    }
+
+    fn matrix(variants: Value, fail_fast: bool) -> Matrix {
+        Matrix::parse(&json!({ "code": "return 42", "variants": variants, "failFast": fail_fast, "timeout": 10 })).unwrap()
+    }
+
+    fn statuses(outcomes: &[VariantOutcome]) -> Vec<VariantStatus> {
+        outcomes.iter().map(|o| o.status).collect()
+    }
+
+    #[test]
+    fn parse_fills_in_defaults() {
+        let args = json!({ "code": "return 1", "variants": [{}, { "mode": "play", "setup": "  ", "timeout": 5 }] });
+        let matrix = Matrix::parse(&args).unwrap();
+        assert!(!matrix.fail_fast);
+        assert_eq!(matrix.variants[0].mode, Mode::Run);
+        assert_eq!(matrix.variants[0].timeout, Some(DEFAULT_VARIANT_TIMEOUT_SECS));
+        assert_eq!(matrix.variants[1].timeout, Some(5.0));
+        // Blank setup code is no setup
+        assert!(matrix.variants[1].setup.is_none());
+    }
+
+    #[test]
+    fn parse_rejects_bad_matrices() {
+        let error = |args: Value| format!("{:#}", Matrix::parse(&args).unwrap_err());
+        let variants = |variants: Value| json!({ "code": "return 1", "variants": variants });
+        assert_eq!(error(json!({ "variants": [{}] })), "Missing required argument: code");
+        assert_eq!(error(json!({ "code": " ", "variants": [{}] })), "Missing required argument: code");
+        assert_eq!(error(json!({ "code": "return 1" })), "Missing required argument: variants");
+        assert_eq!(error(variants(json!([]))), "'variants' is empty");
+        assert_eq!(error(variants(json!(vec![json!({}); MAX_VARIANTS + 1]))), "17 variants (max 16)");
+        assert!(error(variants(json!([{ "mode": "server" }]))).starts_with("'variants' must be an array"));
+        assert!(error(variants(json!([{ "propertys": {} }]))).contains("unknown field `propertys`"));
+        assert_eq!(
+            error(variants(json!([{}, { "timeout": 0 }]))),
+            "Variant 1: timeout must be between 0 and 300 seconds"
+        );
+        assert_eq!(
+            error(variants(json!([{ "properties": { "Players": { "RespawnTime": 1 } } }]))),
+            "Variant 0: property overrides are limited to Lighting and Workspace, got 'Players'"
+        );
+        assert_eq!(
+            error(json!({ "code": "return 1", "variants": vec![json!({ "timeout": 300 }); 7] })),
+            "Variant timeouts add up to 2100s (max 1800s)"
+        );
+        assert_eq!(
+            error(json!({ "code": "return 1", "variants": [{}], "timeout": "soon" })),
+            "'timeout' must be a number of seconds"
+        );
+    }
+
+    #[tokio::test]
+    async fn a_variant_runs_setup_apply_test_revert_in_order() {
+        let (state, calls) = scripted_plugin(by_mode).await;
+        let variants = json!([{ "name": "low gravity", "setup": "workspace.Baseplate:Destroy()", "properties": { "Workspace": { "Gravity": 50 } }, "timeout": 7 }]);
+        let outcomes = run(&state, &matrix(variants, false)).await;
+
+        let calls = calls.all();
+        let sequence: Vec<(&str, &Value)> = calls.iter().map(|(tool, args)| (tool.as_str(), args)).collect();
+        assert_eq!(
+            sequence,
+            [
+                ("studio-run_script", &json!({ "code": "workspace.Baseplate:Destroy()" })),
+                ("studio-set_properties", &json!({ "overrides": { "Workspace": { "Gravity": 50 } } })),
+                ("studio-test_script", &json!({ "code": "return 42", "mode": "run", "timeout": 7.0 })),
+                ("studio-set_properties", &json!({ "overrides": { "Workspace": { "Gravity": 196.2 } } })),
+            ]
+        );
+        let outcome = &outcomes[0];
+        assert_eq!((outcome.name.as_str(), outcome.status), ("low gravity", VariantStatus::Passed));
+        assert_eq!(outcome.value, Some(json!(42)));
+        assert_eq!(outcome.logs.len(), 2);
+        assert!(outcome.error.is_none() && outcome.revert_error.is_none());
+    }
+
+    #[tokio::test]
+    async fn failures_do_not_stop_later_variants_without_fail_fast() {
+        let (state, calls) = scripted_plugin(by_mode).await;
+        let outcomes = run(&state, &matrix(json!([{ "mode": "play" }, { "mode": "run" }]), false)).await;
+        assert_eq!(statuses(&outcomes), [VariantStatus::Failed, VariantStatus::Passed]);
+        assert_eq!(outcomes[0].error.as_deref(), Some("expected 1, got 2"));
+        assert_eq!(outcomes[0].name, "variant 0");
+        assert_eq!(calls.tools(), ["studio-test_script", "studio-test_script"]);
+
+        let totals = totals(&outcomes);
+        assert_eq!((totals["total"].clone(), totals["passed"].clone()), (json!(2), json!(1)));
+        assert_eq!((totals["failed"].clone(), totals["skipped"].clone()), (json!(1), json!(0)));
+    }
+
+    #[tokio::test]
+    async fn fail_fast_skips_the_rest_after_a_failure() {
+        let (state, calls) = scripted_plugin(by_mode).await;
+        let variants = json!([{ "mode": "run" }, { "name": "play", "mode": "play" }, { "mode": "run" }, { "mode": "run" }]);
+        let outcomes = run(&state, &matrix(variants, true)).await;
+        assert_eq!(
+            statuses(&outcomes),
+            [VariantStatus::Passed, VariantStatus::Failed, VariantStatus::Skipped, VariantStatus::Skipped]
+        );
+        assert_eq!(outcomes[3].error.as_deref(), Some("Skipped: failFast after 'play' did not pass"));
+        assert_eq!(calls.tools().len(), 2);
+        assert_eq!(totals(&outcomes)["skipped"], 2);
+    }
+
+    #[tokio::test]
+    async fn setup_and_apply_failures_skip_the_test_of_their_variant() {
+        let (state, calls) = scripted_plugin(|tool, arguments| match tool {
+            "studio-run_script" => Err("setup exploded".into()),
+            "studio-set_properties" if arguments["overrides"]["Lighting"].is_object() => Err("locked".into()),
+            other => by_mode(other, arguments),
+        })
+        .await;
+        let variants = json!([
+            { "setup": "error('x')", "properties": { "Workspace": { "Gravity": 50 } } },
+            { "properties": { "Lighting": { "ClockTime": 0 } } },
+            {}
+        ]);
+        let outcomes = run(&state, &matrix(variants, false)).await;
+        assert_eq!(
+            statuses(&outcomes),
+            [VariantStatus::SetupFailed, VariantStatus::ApplyFailed, VariantStatus::Passed]
+        );
+        assert_eq!(outcomes[0].error.as_deref(), Some("setup exploded"));
+        assert_eq!(outcomes[1].error.as_deref(), Some("locked"));
+        // Nothing applied, so nothing reverted
+        assert_eq!(calls.tools(), ["studio-run_script", "studio-set_properties", "studio-test_script"]);
+    }
+
+    #[tokio::test]
+    async fn overrides_are_reverted_after_a_failed_test_call() {
+        let (state, calls) = scripted_plugin(|tool, arguments| match tool {
+            "studio-test_script" => Err("Plugin disconnected while processing tool call".into()),
+            other => by_mode(other, arguments),
+        })
+        .await;
+        let outcomes = run(&state, &matrix(json!([{ "properties": { "Workspace": { "Gravity": 50 } } }, {}]), false)).await;
+        assert_eq!(statuses(&outcomes), [VariantStatus::Failed, VariantStatus::Failed]);
+        assert_eq!(
+            calls.tools(),
+            ["studio-set_properties", "studio-test_script", "studio-set_properties", "studio-test_script"]
+        );
+    }
+
+    #[tokio::test]
+    async fn a_failed_revert_stops_the_matrix_even_without_fail_fast() {
+        let (state, calls) = scripted_plugin(|tool, arguments| match tool {
+            "studio-set_properties" if arguments["overrides"]["Workspace"]["Gravity"] == 196.2 => Err("revert refused".into()),
+            other => by_mode(other, arguments),
+        })
+        .await;
+        let variants = json!([{ "name": "heavy", "properties": { "Workspace": { "Gravity": 500 } } }, {}]);
+        let outcomes = run(&state, &matrix(variants, false)).await;
+        // The test itself passed; the next variant would start from a changed place
+        assert_eq!(statuses(&outcomes), [VariantStatus::Passed, VariantStatus::Skipped]);
+        assert_eq!(outcomes[0].revert_error.as_deref(), Some("revert refused"));
+        assert_eq!(outcomes[1].error.as_deref(), Some("Skipped: property overrides of 'heavy' could not be reverted"));
+        assert_eq!(calls.tools().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn timed_out_tests_are_reported_as_such() {
+        let (state, _) = scripted_plugin(|_, _| Ok(json!({ "success": false, "timedOut": true, "error": "timed out after 10s" }))).await;
+        let outcomes = run(&state, &matrix(json!([{}]), false)).await;
+        assert_eq!(statuses(&outcomes), [VariantStatus::TimedOut]);
+        assert_eq!(totals(&outcomes)["failed"], 1);
+    }
+
+    #[test]
+    fn summaries_count_logs_and_keep_the_first_errors() {
+        let variant: Variant = serde_json::from_value(json!({ "name": "noisy" })).unwrap();
+        let mut outcome = VariantOutcome::new(0, &variant);
+        let errors: Vec<Value> = (0..8).map(|i| json!({ "message": format!("error {i}") })).collect();
+        record_test_result(&mut outcome, &json!({ "success": true, "logs": [{}, {}, {}], "errors": errors }));
+
+        let summary = outcome.summary();
+        assert!(summary.get("logs").is_none());
+        assert_eq!((summary["logCount"].clone(), summary["errorCount"].clone()), (json!(3), json!(8)));
+        assert_eq!(summary["errors"].as_array().unwrap().len(), SUMMARY_ERRORS_PER_VARIANT);
+        assert_eq!(summary["errors"][4]["message"], "error 4");
+        assert_eq!(summary["status"], "passed");
+        assert!(summary.get("value").is_none() && summary.get("revertError").is_none());
+        // The full report keeps everything
+        assert_eq!(serde_json::to_value(&outcome).unwrap()["logs"].as_array().unwrap().len(), 3);
+    }
+}
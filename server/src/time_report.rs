@@ -0,0 +1,643 @@
+//! Where the wall-clock time of `studio-test_script` calls goes, for `studio-time_report`.
+//!
+//! The plugin runs a test as one request, so the server splits it into phases from
+//! what it sees of the call:
+//!
+//! - `queueWait`: queued until a client pulled the request (`drain_outbound`)
+//! - `startup`: pulled until the playtest was reported active (`update_playtest`)
+//! - `execution`: active until the playtest was reported stopped
+//! - `teardown`: stopped until the response arrived
+//!
+//! A missing mark (a stop report that came after the response, a call queued while
+//! another playtest was starting) leaves its phase at zero and the time with the phase
+//! before it; the mark is listed under `missing`. Marks out of order, e.g. a start
+//! reported before the request was pulled, are moved to the neighbouring mark so the
+//! phases never overlap and always add up to the total (`adjusted`).
+//!
+//! Offsets are measured with the server clock, so deterministic mode reports zeros.
+//! Records stay in memory; the newest `MAX_RECORDS` are kept.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::providers::Clock;
+
+pub const MAX_RECORDS: usize = 200;
+pub const DEFAULT_LAST: usize = 20;
+pub const DEFAULT_SLOWEST: usize = 3;
+pub const MAX_SLOWEST: usize = 20;
+
+/// Calls still waiting for a response; older ones (e.g. cancelled async calls) are
+/// dropped when more are opened.
+const MAX_OPEN: usize = 16;
+
+pub const TIME_REPORT_DIR: &str = "time_reports";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    QueueWait,
+    Startup,
+    Execution,
+    Teardown,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [Phase::QueueWait, Phase::Startup, Phase::Execution, Phase::Teardown];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::QueueWait => "queueWait",
+            Phase::Startup => "startup",
+            Phase::Execution => "execution",
+            Phase::Teardown => "teardown",
+        }
+    }
+
+    /// The mark the phase starts at, when the server has to observe it.
+    fn start_mark(self) -> Option<&'static str> {
+        match self {
+            Phase::QueueWait => None,
+            Phase::Startup => Some("delivered"),
+            Phase::Execution => Some("playtestStarted"),
+            Phase::Teardown => Some("playtestStopped"),
+        }
+    }
+}
+
+/// Offsets from the moment the call was queued. Only the response is always known.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Marks {
+    pub delivered: Option<Duration>,
+    pub playtest_started: Option<Duration>,
+    pub playtest_stopped: Option<Duration>,
+    pub responded: Duration,
+}
+
+/// Time per phase, in `Phase::ALL` order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attribution {
+    pub phases: [Duration; 4],
+    pub missing: Vec<&'static str>,
+    pub adjusted: bool,
+}
+
+/// Split `marks.responded` into phases.
+pub fn attribute(marks: &Marks) -> Attribution {
+    let mut boundaries = [Some(Duration::ZERO), marks.delivered, marks.playtest_started, marks.playtest_stopped];
+    let mut adjusted = false;
+    let mut floor = Duration::ZERO;
+    for boundary in boundaries.iter_mut().skip(1).flatten() {
+        let clamped = (*boundary).clamp(floor, marks.responded);
+        adjusted |= clamped != *boundary;
+        *boundary = clamped;
+        floor = clamped;
+    }
+
+    let mut phases = [Duration::ZERO; 4];
+    let mut missing = Vec::new();
+    for (i, phase) in Phase::ALL.into_iter().enumerate() {
+        let Some(start) = boundaries[i] else {
+            missing.extend(phase.start_mark());
+            continue;
+        };
+        let end = boundaries[i + 1..].iter().flatten().next().copied().unwrap_or(marks.responded);
+        phases[i] = end - start;
+    }
+    Attribution { phases, missing, adjusted }
+}
+
+/// A finished call.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimingRecord {
+    pub seq: u64,
+    pub request_id: String,
+    /// Playtest session the call ran, from the playtest reports or the result.
+    pub session_id: Option<String>,
+    pub mode: Option<String>,
+    /// `passed`, `failed`, `error` or `timedOut`.
+    pub outcome: &'static str,
+    /// When the call was queued (RFC 3339).
+    pub started_at: String,
+    pub queue_wait_ms: u64,
+    pub startup_ms: u64,
+    pub execution_ms: u64,
+    pub teardown_ms: u64,
+    pub total_ms: u64,
+    pub missing: Vec<&'static str>,
+    pub adjusted: bool,
+}
+
+impl TimingRecord {
+    fn phase_ms(&self, phase: Phase) -> u64 {
+        match phase {
+            Phase::QueueWait => self.queue_wait_ms,
+            Phase::Startup => self.startup_ms,
+            Phase::Execution => self.execution_ms,
+            Phase::Teardown => self.teardown_ms,
+        }
+    }
+
+    /// Whether the phase was measured rather than left at zero for a missing mark.
+    fn measured(&self, phase: Phase) -> bool {
+        phase.start_mark().is_none_or(|mark| !self.missing.contains(&mark))
+    }
+
+    /// The phase that took longest.
+    fn dominant_phase(&self) -> Phase {
+        Phase::ALL.into_iter().max_by_key(|p| self.phase_ms(*p)).unwrap_or(Phase::Execution)
+    }
+}
+
+/// A call waiting for its response.
+struct OpenCall {
+    queued: Instant,
+    started_at: String,
+    mode: Option<String>,
+    session_id: Option<String>,
+    delivered: Option<Instant>,
+    playtest_started: Option<Instant>,
+    playtest_stopped: Option<Instant>,
+}
+
+#[derive(Default)]
+pub struct TestTimings {
+    open: HashMap<String, OpenCall>,
+    records: VecDeque<TimingRecord>,
+    next_seq: u64,
+}
+
+impl TestTimings {
+    /// A test call was queued for the plugin.
+    pub fn open(&mut self, request_id: String, queued: Instant, started_at: String, mode: Option<String>) {
+        while self.open.len() >= MAX_OPEN {
+            let Some(oldest) = self.open.iter().min_by_key(|(_, call)| call.queued).map(|(id, _)| id.clone()) else {
+                break;
+            };
+            self.open.remove(&oldest);
+        }
+        let call = OpenCall {
+            queued,
+            started_at,
+            mode,
+            session_id: None,
+            delivered: None,
+            playtest_started: None,
+            playtest_stopped: None,
+        };
+        self.open.insert(request_id, call);
+    }
+
+    /// The call was withdrawn before it could run, e.g. no client took it.
+    pub fn discard(&mut self, request_id: &str) {
+        self.open.remove(request_id);
+    }
+
+    /// A client pulled the request. Only the first pull counts.
+    pub fn delivered(&mut self, request_id: &str, at: Instant) {
+        if let Some(call) = self.open.get_mut(request_id) {
+            call.delivered.get_or_insert(at);
+        }
+    }
+
+    /// A playtest session became active; open calls without one started it.
+    pub fn playtest_started(&mut self, at: Instant, session_id: Option<&str>, mode: Option<&str>) {
+        for call in self.open.values_mut().filter(|c| c.playtest_started.is_none()) {
+            call.playtest_started = Some(at);
+            call.session_id = session_id.map(String::from);
+            if call.mode.is_none() {
+                call.mode = mode.map(String::from);
+            }
+        }
+    }
+
+    /// The playtest ended; it stops the calls whose playtest started.
+    pub fn playtest_stopped(&mut self, at: Instant) {
+        for call in self.open.values_mut() {
+            if call.playtest_started.is_some() && call.playtest_stopped.is_none() {
+                call.playtest_stopped = Some(at);
+            }
+        }
+    }
+
+    /// The response arrived (or the wait ended). `session_id` is the one the result
+    /// names, used when no playtest report was seen.
+    pub fn finish(
+        &mut self,
+        request_id: &str,
+        at: Instant,
+        clock: &dyn Clock,
+        outcome: &'static str,
+        session_id: Option<String>,
+    ) -> Option<&TimingRecord> {
+        let call = self.open.remove(request_id)?;
+        // Measured through the clock as time since each moment, so deterministic mode
+        // gets zeros like every other reported duration
+        let since_queued = clock.elapsed(call.queued);
+        let offset = |moment: Instant| since_queued.saturating_sub(clock.elapsed(moment));
+        let marks = Marks {
+            delivered: call.delivered.map(offset),
+            playtest_started: call.playtest_started.map(offset),
+            playtest_stopped: call.playtest_stopped.map(offset),
+            responded: offset(at),
+        };
+        let attribution = attribute(&marks);
+        let ms = |d: Duration| d.as_millis() as u64;
+        // Rounded at the boundaries, so the phases add up to the total in milliseconds too
+        let mut boundary = Duration::ZERO;
+        let phase_ms = attribution.phases.map(|phase| {
+            let start = ms(boundary);
+            boundary += phase;
+            ms(boundary) - start
+        });
+        self.next_seq += 1;
+        let record = TimingRecord {
+            seq: self.next_seq,
+            request_id: request_id.to_string(),
+            session_id: call.session_id.or(session_id),
+            mode: call.mode,
+            outcome,
+            started_at: call.started_at,
+            queue_wait_ms: phase_ms[0],
+            startup_ms: phase_ms[1],
+            execution_ms: phase_ms[2],
+            teardown_ms: phase_ms[3],
+            total_ms: ms(marks.responded),
+            missing: attribution.missing,
+            adjusted: attribution.adjusted,
+        };
+        if self.records.len() >= MAX_RECORDS {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+        self.records.back()
+    }
+
+    /// The newest `last` records, oldest first.
+    pub fn last(&self, last: usize) -> Vec<TimingRecord> {
+        self.records.iter().skip(self.records.len().saturating_sub(last)).cloned().collect()
+    }
+}
+
+/// Columns of the CSV artifact, in order.
+pub const CSV_COLUMNS: [&str; 13] = [
+    "seq",
+    "requestId",
+    "sessionId",
+    "mode",
+    "outcome",
+    "startedAt",
+    "queueWaitMs",
+    "startupMs",
+    "executionMs",
+    "teardownMs",
+    "totalMs",
+    "missing",
+    "adjusted",
+];
+
+/// CSV rows for `render::to_csv`.
+pub fn csv_rows(records: &[TimingRecord]) -> Vec<Value> {
+    records
+        .iter()
+        .map(|r| {
+            let mut row = serde_json::to_value(r).unwrap_or_default();
+            row["missing"] = json!(r.missing.join(" "));
+            row
+        })
+        .collect()
+}
+
+/// Totals, averages and maxima per phase over `records`, and the `slowest` calls.
+pub fn summarize(records: &[TimingRecord], slowest: usize) -> Value {
+    let total_ms: u64 = records.iter().map(|r| r.total_ms).sum();
+    let mut phases = serde_json::Map::new();
+    for phase in Phase::ALL {
+        let measured: Vec<u64> = records.iter().filter(|r| r.measured(phase)).map(|r| r.phase_ms(phase)).collect();
+        let phase_total: u64 = records.iter().map(|r| r.phase_ms(phase)).sum();
+        phases.insert(
+            phase.as_str().into(),
+            json!({
+                "totalMs": phase_total,
+                "avgMs": measured.iter().sum::<u64>().checked_div(measured.len() as u64),
+                "maxMs": measured.iter().max(),
+                "measured": measured.len(),
+                "sharePercent": percent(phase_total, total_ms),
+            }),
+        );
+    }
+
+    let mut missing: HashMap<&str, usize> = HashMap::new();
+    for mark in records.iter().flat_map(|r| &r.missing) {
+        *missing.entry(mark).or_default() += 1;
+    }
+    let mut missing: Vec<(&str, usize)> = missing.into_iter().collect();
+    missing.sort();
+
+    let mut by_total: Vec<&TimingRecord> = records.iter().collect();
+    by_total.sort_by(|a, b| b.total_ms.cmp(&a.total_ms).then(a.seq.cmp(&b.seq)));
+    let slowest: Vec<Value> = by_total
+        .into_iter()
+        .take(slowest)
+        .map(|r| {
+            let mut entry = serde_json::to_value(r).unwrap_or_default();
+            entry["dominantPhase"] = json!(r.dominant_phase().as_str());
+            entry
+        })
+        .collect();
+
+    let mut outcomes: HashMap<&str, usize> = HashMap::new();
+    for record in records {
+        *outcomes.entry(record.outcome).or_default() += 1;
+    }
+    let mut outcomes: Vec<(&str, usize)> = outcomes.into_iter().collect();
+    outcomes.sort();
+
+    json!({
+        "sessions": records.len(),
+        "totalMs": total_ms,
+        "avgMs": total_ms.checked_div(records.len() as u64),
+        "phases": phases,
+        "outcomes": counts(outcomes),
+        "missingMarks": counts(missing),
+        "adjusted": records.iter().filter(|r| r.adjusted).count(),
+        "slowest": slowest,
+    })
+}
+
+fn counts(pairs: Vec<(&str, usize)>) -> Value {
+    Value::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), json!(v))).collect())
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    (part as f64 * 1000.0 / whole as f64).round() / 10.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render;
+    use chrono::{DateTime, Utc};
+
+    /// A clock stopped at `now`, so offsets come out exact.
+    struct FrozenClock {
+        now: Instant,
+    }
+
+    impl Clock for FrozenClock {
+        fn now(&self) -> DateTime<Utc> {
+            DateTime::UNIX_EPOCH
+        }
+
+        fn elapsed(&self, start: Instant) -> Duration {
+            self.now.saturating_duration_since(start)
+        }
+
+        fn age(&self, _since: DateTime<Utc>) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    fn marks(delivered: Option<u64>, started: Option<u64>, stopped: Option<u64>, responded: u64) -> Marks {
+        Marks {
+            delivered: delivered.map(ms),
+            playtest_started: started.map(ms),
+            playtest_stopped: stopped.map(ms),
+            responded: ms(responded),
+        }
+    }
+
+    fn phases_ms(attribution: &Attribution) -> [u64; 4] {
+        attribution.phases.map(|d| d.as_millis() as u64)
+    }
+
+    #[test]
+    fn a_complete_timeline_splits_at_each_mark() {
+        let attribution = attribute(&marks(Some(50), Some(2_050), Some(7_050), 7_300));
+        assert_eq!(phases_ms(&attribution), [50, 2_000, 5_000, 250]);
+        assert!(attribution.missing.is_empty());
+        assert!(!attribution.adjusted);
+    }
+
+    #[test]
+    fn missing_marks_leave_their_time_with_the_phase_before() {
+        // Not seen being pulled: the wait runs on to the playtest start
+        let attribution = attribute(&marks(None, Some(2_050), Some(7_050), 7_300));
+        assert_eq!(phases_ms(&attribution), [2_050, 0, 5_000, 250]);
+        assert_eq!(attribution.missing, ["delivered"]);
+        // A playtest already running when the call came: no start of its own
+        let attribution = attribute(&marks(Some(50), None, Some(7_050), 7_300));
+        assert_eq!(phases_ms(&attribution), [50, 7_000, 0, 250]);
+        assert_eq!(attribution.missing, ["playtestStarted"]);
+        // The stop report came after the response
+        let attribution = attribute(&marks(Some(50), Some(2_050), None, 7_300));
+        assert_eq!(phases_ms(&attribution), [50, 2_000, 5_250, 0]);
+        assert_eq!(attribution.missing, ["playtestStopped"]);
+        // Only the response
+        let attribution = attribute(&marks(None, None, None, 900));
+        assert_eq!(phases_ms(&attribution), [900, 0, 0, 0]);
+        assert_eq!(attribution.missing, ["delivered", "playtestStarted", "playtestStopped"]);
+        assert!(!attribution.adjusted);
+    }
+
+    #[test]
+    fn marks_out_of_order_are_moved_so_phases_never_overlap() {
+        // The playtest started before this request was pulled (queued behind another)
+        let attribution = attribute(&marks(Some(3_000), Some(1_000), Some(6_000), 6_500));
+        assert_eq!(phases_ms(&attribution), [3_000, 0, 3_000, 500]);
+        assert!(attribution.adjusted);
+        // Marks past the response end with it
+        let attribution = attribute(&marks(Some(100), Some(8_000), Some(9_000), 7_000));
+        assert_eq!(phases_ms(&attribution), [100, 6_900, 0, 0]);
+        assert!(attribution.adjusted && attribution.missing.is_empty());
+        // A stop before the start
+        let attribution = attribute(&marks(Some(100), Some(2_000), Some(1_500), 3_000));
+        assert_eq!(phases_ms(&attribution), [100, 1_900, 0, 1_000]);
+        assert!(attribution.adjusted);
+    }
+
+    #[test]
+    fn phases_always_add_up_to_the_total() {
+        let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+        let mut next = move |bound: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % bound
+        };
+        for _ in 0..2_000 {
+            let mut mark = || (next(4) != 0).then(|| next(10_000));
+            let (delivered, started, stopped) = (mark(), mark(), mark());
+            let responded = next(10_000);
+            let attribution = attribute(&marks(delivered, started, stopped, responded));
+            let sum: u64 = phases_ms(&attribution).iter().sum();
+            assert_eq!(sum, responded, "{delivered:?} {started:?} {stopped:?} {responded}");
+            let in_order = [delivered, started, stopped].into_iter().flatten().all(|m| m <= responded)
+                && [delivered, started, stopped].into_iter().flatten().is_sorted();
+            assert_eq!(attribution.adjusted, !in_order, "{delivered:?} {started:?} {stopped:?} {responded}");
+            assert_eq!(attribution.missing.len(), [delivered, started, stopped].iter().filter(|m| m.is_none()).count());
+        }
+    }
+
+    #[test]
+    fn test_calls_are_timed_from_the_bridge_and_playtest_events() {
+        let base = Instant::now();
+        let at = |offset: u64| base + ms(offset);
+        let clock = FrozenClock { now: at(60_000) };
+        let mut timings = TestTimings::default();
+
+        timings.open("r1".into(), at(0), "t1".into(), Some("run".into()));
+        timings.delivered("r1", at(40));
+        // A later pull of the same request (a retry) doesn't move the mark
+        timings.delivered("r1", at(900));
+        timings.playtest_started(at(1_040), Some("s1"), Some("play"));
+        timings.playtest_stopped(at(4_040));
+        let record = timings.finish("r1", at(4_200), &clock, "passed", Some("from-result".into())).unwrap().clone();
+        assert_eq!(
+            (record.queue_wait_ms, record.startup_ms, record.execution_ms, record.teardown_ms, record.total_ms),
+            (40, 1_000, 3_000, 160, 4_200)
+        );
+        // The playtest report names the session; the call's own mode wins
+        assert_eq!((record.session_id.as_deref(), record.mode.as_deref(), record.seq), (Some("s1"), Some("run"), 1));
+        assert!(record.missing.is_empty() && !record.adjusted);
+
+        // Opened while that playtest ran: no start mark, and the result names the session
+        timings.open("r2".into(), at(5_000), "t2".into(), None);
+        timings.delivered("r2", at(5_010));
+        let record = timings.finish("r2", at(5_500), &clock, "failed", Some("s2".into())).unwrap().clone();
+        assert_eq!((record.queue_wait_ms, record.startup_ms, record.total_ms), (10, 490, 500));
+        assert_eq!(record.missing, ["playtestStarted", "playtestStopped"]);
+        assert_eq!((record.session_id.as_deref(), record.mode), (Some("s2"), None));
+
+        // Two calls in one playtest share its start and stop
+        timings.open("r3".into(), at(10_000), "t3".into(), None);
+        timings.open("r4".into(), at(10_100), "t4".into(), None);
+        timings.playtest_started(at(10_500), Some("s3"), Some("play"));
+        timings.playtest_stopped(at(11_500));
+        let r3 = timings.finish("r3", at(11_600), &clock, "passed", None).unwrap().clone();
+        let r4 = timings.finish("r4", at(11_700), &clock, "timedOut", None).unwrap().clone();
+        assert_eq!((r3.execution_ms, r4.execution_ms), (1_000, 1_000));
+        assert_eq!((r3.queue_wait_ms, r4.queue_wait_ms), (500, 400));
+        assert_eq!(r4.missing, ["delivered"]);
+        assert_eq!(r4.mode.as_deref(), Some("play"));
+
+        // Unknown or withdrawn calls leave no record
+        timings.open("r5".into(), at(20_000), "t5".into(), None);
+        timings.discard("r5");
+        assert!(timings.finish("r5", at(20_100), &clock, "error", None).is_none());
+        let ids: Vec<String> = timings.last(10).into_iter().map(|r| r.request_id).collect();
+        assert_eq!(ids, ["r1", "r2", "r3", "r4"]);
+        assert_eq!(timings.last(2)[0].request_id, "r3");
+    }
+
+    #[test]
+    fn phases_in_milliseconds_add_up_to_the_total() {
+        let base = Instant::now();
+        let at = |micros: u64| base + Duration::from_micros(micros);
+        let mut timings = TestTimings::default();
+        timings.open("r1".into(), at(0), String::new(), None);
+        timings.delivered("r1", at(1_600));
+        timings.playtest_started(at(3_200), None, None);
+        timings.playtest_stopped(at(4_800));
+        let clock = FrozenClock { now: at(10_000) };
+        let record = timings.finish("r1", at(6_400), &clock, "passed", None).unwrap();
+        assert_eq!((record.queue_wait_ms, record.startup_ms, record.execution_ms, record.teardown_ms), (1, 2, 1, 2));
+        assert_eq!(record.total_ms, 6);
+    }
+
+    #[test]
+    fn old_open_calls_and_records_are_dropped() {
+        let base = Instant::now();
+        let clock = FrozenClock { now: base + ms(1_000_000) };
+        let mut timings = TestTimings::default();
+        for i in 0..MAX_OPEN as u64 + 1 {
+            timings.open(format!("r{i}"), base + ms(i), String::new(), None);
+        }
+        assert!(timings.finish("r0", base + ms(100), &clock, "passed", None).is_none());
+        assert!(timings.finish("r1", base + ms(100), &clock, "passed", None).is_some());
+
+        for i in 0..MAX_RECORDS as u64 + 5 {
+            timings.open(format!("k{i}"), base, String::new(), None);
+            timings.finish(&format!("k{i}"), base + ms(10), &clock, "passed", None);
+        }
+        let records = timings.last(usize::MAX);
+        assert_eq!(records.len(), MAX_RECORDS);
+        assert_eq!(records[0].request_id, "k5");
+        assert_eq!(records.last().unwrap().seq, MAX_RECORDS as u64 + 6);
+    }
+
+    fn record(seq: u64, outcome: &'static str, phases: [u64; 4], missing: &[&'static str]) -> TimingRecord {
+        TimingRecord {
+            seq,
+            request_id: format!("r{seq}"),
+            session_id: Some(format!("s{seq}")),
+            mode: Some("run".into()),
+            outcome,
+            started_at: format!("2026-10-17T10:00:0{seq}Z"),
+            queue_wait_ms: phases[0],
+            startup_ms: phases[1],
+            execution_ms: phases[2],
+            teardown_ms: phases[3],
+            total_ms: phases.iter().sum(),
+            missing: missing.to_vec(),
+            adjusted: seq == 2,
+        }
+    }
+
+    #[test]
+    fn summaries_average_measured_phases_and_list_the_slowest() {
+        let records = [
+            record(1, "passed", [100, 2_000, 5_000, 400], &[]),
+            record(2, "failed", [50, 6_950, 0, 300], &["playtestStarted"]),
+            record(3, "passed", [150, 1_000, 3_000, 0], &["playtestStopped"]),
+            record(4, "timedOut", [9_000, 1_000, 0, 0], &["playtestStarted", "playtestStopped"]),
+        ];
+        let summary = summarize(&records, 2);
+        assert_eq!((&summary["sessions"], &summary["totalMs"], &summary["avgMs"]), (&json!(4), &json!(28_950), &json!(7_237)));
+        assert_eq!(summary["outcomes"], json!({ "failed": 1, "passed": 2, "timedOut": 1 }));
+        assert_eq!(summary["missingMarks"], json!({ "playtestStarted": 2, "playtestStopped": 2 }));
+        assert_eq!(summary["adjusted"], 1);
+
+        // Zeros left by a missing mark don't pull the average down
+        let execution = &summary["phases"]["execution"];
+        assert_eq!(
+            (&execution["totalMs"], &execution["avgMs"], &execution["maxMs"], &execution["measured"]),
+            (&json!(8_000), &json!(4_000), &json!(5_000), &json!(2))
+        );
+        assert_eq!(execution["sharePercent"], 27.6);
+        let queue_wait = &summary["phases"]["queueWait"];
+        assert_eq!((&queue_wait["avgMs"], &queue_wait["measured"], &queue_wait["sharePercent"]), (&json!(2_325), &json!(4), &json!(32.1)));
+        assert_eq!(summary["phases"]["teardown"]["measured"], 2);
+
+        let slowest = summary["slowest"].as_array().unwrap();
+        assert_eq!(slowest.len(), 2);
+        assert_eq!((&slowest[0]["requestId"], &slowest[0]["dominantPhase"]), (&json!("r4"), &json!("queueWait")));
+        assert_eq!((&slowest[1]["requestId"], &slowest[1]["dominantPhase"]), (&json!("r1"), &json!("execution")));
+        // Ties go to the earlier call
+        let tied = summarize(&[record(1, "passed", [0, 0, 10, 0], &[]), record(2, "passed", [0, 10, 0, 0], &[])], 1);
+        assert_eq!(tied["slowest"][0]["requestId"], "r1");
+
+        let empty = summarize(&[], 3);
+        assert_eq!((&empty["avgMs"], &empty["phases"]["startup"]["avgMs"], &empty["phases"]["startup"]["sharePercent"]), (&Value::Null, &Value::Null, &json!(0.0)));
+    }
+
+    #[test]
+    fn csv_rows_have_one_line_per_call() {
+        let records = [record(1, "passed", [100, 2_000, 5_000, 400], &[]), record(2, "failed", [50, 6_950, 0, 300], &["delivered", "playtestStarted"])];
+        let csv = render::to_csv(&CSV_COLUMNS, &csv_rows(&records));
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "seq,requestId,sessionId,mode,outcome,startedAt,queueWaitMs,startupMs,executionMs,teardownMs,totalMs,missing,adjusted");
+        assert_eq!(lines[1], "1,r1,s1,run,passed,2026-10-17T10:00:01Z,100,2000,5000,400,7500,,false");
+        assert_eq!(lines[2], "2,r2,s2,run,failed,2026-10-17T10:00:02Z,50,6950,0,300,7300,delivered playtestStarted,true");
+        assert_eq!(lines.len(), 3);
+    }
+}
@@ -0,0 +1,116 @@
+//! Ring buffer of recent tool calls for `studio-get_tool_history`, so an assistant
+//! that lost its context (a reconnect, a second client) can see what was done.
+//!
+//! Every `tools/call` that reaches dispatch is recorded once it has finished, cached
+//! answers included; steps of `studio-replay` and `studio-transaction` are part of
+//! their call. Arguments aren't kept, only an error's first line. The size is
+//! `YIPPIE_TOOL_HISTORY_SIZE`; 0 keeps nothing.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+
+pub const DEFAULT_TOOL_HISTORY_SIZE: usize = 200;
+pub const MAX_TOOL_HISTORY_SIZE: usize = 10_000;
+
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// Longest error message kept with a failed call.
+const MAX_ERROR_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolCallRecord {
+    pub seq: u64,
+    /// When the call arrived (RFC 3339).
+    pub ts: String,
+    pub tool: String,
+    pub success: bool,
+    pub duration_ms: u64,
+    /// Answered from the response cache.
+    pub cached: bool,
+    /// MCP client `name/version`, if it sent clientInfo.
+    pub client: Option<String>,
+    pub session: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Filters of a history query.
+#[derive(Debug, Default)]
+pub struct HistoryQuery {
+    pub limit: usize,
+    pub tool: Option<String>,
+    pub since_seq: Option<u64>,
+    pub failed_only: bool,
+}
+
+pub struct ToolHistory {
+    entries: VecDeque<ToolCallRecord>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl ToolHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity.min(DEFAULT_TOOL_HISTORY_SIZE)),
+            capacity,
+            next_seq: 1,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Calls recorded since startup, including those no longer kept.
+    pub fn recorded(&self) -> u64 {
+        self.next_seq - 1
+    }
+
+    /// Add a finished call; `record.seq` is assigned here.
+    pub fn push(&mut self, mut record: ToolCallRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+        record.seq = self.next_seq;
+        self.next_seq += 1;
+        if let Some(error) = &mut record.error {
+            *error = first_line(error);
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(record);
+    }
+
+    /// The latest `query.limit` matching calls, oldest first, and whether older
+    /// matches were left out.
+    pub fn query(&self, query: &HistoryQuery) -> (Vec<ToolCallRecord>, bool) {
+        let mut matching: Vec<&ToolCallRecord> = self
+            .entries
+            .iter()
+            .rev()
+            .filter(|r| query.since_seq.is_none_or(|seq| r.seq > seq))
+            .filter(|r| query.tool.as_deref().is_none_or(|tool| r.tool == tool))
+            .filter(|r| !query.failed_only || !r.success)
+            .take(query.limit + 1)
+            .collect();
+        let truncated = matching.len() > query.limit;
+        matching.truncate(query.limit);
+        (matching.into_iter().rev().cloned().collect(), truncated)
+    }
+
+    /// Sequence number of the oldest call still kept.
+    pub fn oldest_seq(&self) -> Option<u64> {
+        self.entries.front().map(|r| r.seq)
+    }
+}
+
+fn first_line(message: &str) -> String {
+    let line = message.lines().find(|l| !l.trim().is_empty()).unwrap_or_default().trim();
+    match line.char_indices().nth(MAX_ERROR_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
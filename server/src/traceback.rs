@@ -0,0 +1,572 @@
+//! Source context for Luau tracebacks in tool results.
+//!
+//! Errors from scripts reach the agent as a message plus frames naming script paths
+//! and line numbers. For the tools that return script output (`TRACEBACK_TOOLS`), the
+//! frames are parsed and each is annotated with the text of its line, fetched from the
+//! plugin with the internal `studio-get_script_sources` tool, plus a short excerpt
+//! around the top frame. Fetched sources are cached for `SOURCE_TTL`; any call to a
+//! tool that may change Studio clears the cache.
+//!
+//! With the path index on, only paths it lists as scripts are fetched. Copies the
+//! client makes at runtime (PlayerScripts, PlayerGui, Backpack) are looked up under
+//! the Starter container they were copied from.
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use crate::mcp_stdio::call_plugin;
+use crate::state::SharedState;
+
+/// Tools whose results carry script errors and logs.
+pub const TRACEBACK_TOOLS: &[&str] = &[
+    "studio-run_script",
+    "studio-test_script",
+    "studio-logs_get",
+    "studio-wait_for_condition",
+];
+
+/// Frames annotated per result; the rest of a long traceback is left as it is.
+const MAX_FRAMES: usize = 8;
+
+/// Scripts fetched from the plugin per result.
+const MAX_FETCHED_SCRIPTS: usize = 4;
+
+/// Text scanned for frames per result. Log-heavy results are cut, not skipped.
+const MAX_SCANNED_BYTES: usize = 256 * 1024;
+
+/// Line text is cut to this many characters.
+const MAX_LINE_CHARS: usize = 160;
+
+/// Lines shown on each side of the top frame's line.
+const EXCERPT_RADIUS: usize = 3;
+
+/// Scripts larger than this aren't sent by the plugin.
+const MAX_SOURCE_BYTES: usize = 200_000;
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(5);
+
+const SOURCE_TTL: Duration = Duration::from_secs(60);
+const MAX_CACHED_SOURCES: usize = 32;
+
+/// Services a script path can start with. Anything else (URLs, `MCP:run_script`
+/// chunk names, version strings) is not a frame.
+const SCRIPT_ROOTS: &[&str] = &[
+    "Workspace",
+    "ServerScriptService",
+    "ReplicatedStorage",
+    "ReplicatedFirst",
+    "ServerStorage",
+    "StarterPlayer",
+    "StarterGui",
+    "StarterPack",
+    "Players",
+    "Lighting",
+    "SoundService",
+    "TestService",
+];
+
+/// Classes whose Source can be read.
+const SCRIPT_CLASSES: &[&str] = &["Script", "LocalScript", "ModuleScript"];
+
+/// Output stack lines: `Script 'ServerScriptService.Main', Line 12 - function spawnWave`.
+static STACK_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"Script '([^']+)', Line (\d+)(?: - (?:function|method|upvalue|field|local)? ?([\w.:]+))?")
+        .expect("static regex")
+});
+
+/// Error heads and `debug.traceback` lines: `ServerScriptService.Main:12: attempt to ...`,
+/// `ReplicatedStorage.Util:40 function clamp`.
+static PATH_LINE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|[\s(])([A-Za-z]\w*(?:\.[^\s.:'()]+)+):(\d+)(?::| function ([\w.:]+))?")
+        .expect("static regex")
+});
+
+/// One traceback frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    /// Full name of the script as it appeared in the traceback.
+    pub path: String,
+    pub line: usize,
+    pub function: Option<String>,
+}
+
+/// Frames in `text`, in order of appearance, without repeats.
+pub fn parse_frames(text: &str) -> Vec<Frame> {
+    let mut found: Vec<(usize, Frame)> = Vec::new();
+    let mut collect = |re: &Regex| {
+        for caps in re.captures_iter(text) {
+            let path = &caps[1];
+            let Ok(line) = caps[2].parse::<usize>() else {
+                continue;
+            };
+            if line == 0 || !SCRIPT_ROOTS.contains(&path.split('.').next().unwrap_or("")) {
+                continue;
+            }
+            let frame = Frame {
+                path: path.to_string(),
+                line,
+                function: caps.get(3).map(|m| m.as_str().to_string()),
+            };
+            found.push((caps.get(0).map_or(0, |m| m.start()), frame));
+        }
+    };
+    collect(&STACK_LINE_RE);
+    collect(&PATH_LINE_RE);
+    found.sort_by_key(|(at, _)| *at);
+
+    let mut frames: Vec<Frame> = Vec::new();
+    for (_, frame) in found {
+        match frames.iter_mut().find(|f| f.path == frame.path && f.line == frame.line) {
+            // The error head has no function name; a later stack line may
+            Some(seen) => {
+                if seen.function.is_none() {
+                    seen.function = frame.function;
+                }
+            }
+            None => frames.push(frame),
+        }
+        if frames.len() == MAX_FRAMES {
+            break;
+        }
+    }
+    frames
+}
+
+/// The edit-time path of a script the client copied at runtime, or the path itself.
+fn source_path(path: &str) -> String {
+    let parts: Vec<&str> = path.split('.').collect();
+    if parts.len() > 3 && parts[0] == "Players" {
+        let starter = match parts[2] {
+            "PlayerScripts" => Some("StarterPlayer.StarterPlayerScripts"),
+            "PlayerGui" => Some("StarterGui"),
+            "Backpack" => Some("StarterPack"),
+            _ => None,
+        };
+        if let Some(starter) = starter {
+            return format!("{starter}.{}", parts[3..].join("."));
+        }
+    }
+    path.to_string()
+}
+
+struct CachedSource {
+    /// `None` when the plugin had no readable script at the path.
+    lines: Option<Arc<Vec<String>>>,
+    fetched_at: Instant,
+}
+
+/// Script sources fetched for tracebacks, keyed by edit-time path.
+#[derive(Default)]
+pub struct SourceCache {
+    entries: HashMap<String, CachedSource>,
+}
+
+impl SourceCache {
+    fn get(&self, path: &str) -> Option<Option<Arc<Vec<String>>>> {
+        self.entries
+            .get(path)
+            .filter(|entry| entry.fetched_at.elapsed() < SOURCE_TTL)
+            .map(|entry| entry.lines.clone())
+    }
+
+    fn insert(&mut self, path: String, lines: Option<Arc<Vec<String>>>) {
+        self.entries.retain(|_, entry| entry.fetched_at.elapsed() < SOURCE_TTL);
+        if self.entries.len() >= MAX_CACHED_SOURCES {
+            if let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.fetched_at)
+                .map(|(path, _)| path.clone())
+            {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(path, CachedSource { lines, fetched_at: Instant::now() });
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[derive(Deserialize)]
+struct FetchedSources {
+    #[serde(default)]
+    sources: BTreeMap<String, String>,
+}
+
+/// Sources for `paths` (edit-time paths), from the cache or one plugin request.
+async fn load_sources(state: &SharedState, paths: &[String]) -> HashMap<String, Arc<Vec<String>>> {
+    let mut loaded = HashMap::new();
+    let mut missing = Vec::new();
+    {
+        let cache = state.script_sources();
+        for path in paths {
+            match cache.get(path) {
+                Some(Some(lines)) => {
+                    loaded.insert(path.clone(), lines);
+                }
+                Some(None) => {}
+                None => missing.push(path.clone()),
+            }
+        }
+    }
+    if missing.is_empty() {
+        return loaded;
+    }
+
+    let args = json!({ "paths": missing, "maxBytes": MAX_SOURCE_BYTES });
+    let fetched = call_plugin(state, "studio-get_script_sources", args, FETCH_TIMEOUT)
+        .await
+        .and_then(|v| serde_json::from_value::<FetchedSources>(v).map_err(|e| e.to_string()));
+    let mut fetched = match fetched {
+        Ok(fetched) => fetched.sources,
+        // Nothing is cached, so the next error tries again
+        Err(e) => {
+            tracing::debug!(error = %e, "Fetching script sources for a traceback failed");
+            return loaded;
+        }
+    };
+    let mut cache = state.script_sources();
+    for path in missing {
+        let lines = fetched
+            .remove(&path)
+            .map(|source| Arc::new(source.lines().map(String::from).collect::<Vec<_>>()));
+        if let Some(lines) = &lines {
+            loaded.insert(path.clone(), lines.clone());
+        }
+        cache.insert(path, lines);
+    }
+    loaded
+}
+
+fn clip(line: &str) -> String {
+    let line = line.trim_end();
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.to_string(),
+    }
+}
+
+/// Numbered lines around `line` (1-based), the frame's own line marked with `>`.
+fn excerpt(lines: &[String], line: usize) -> Vec<String> {
+    let first = line.saturating_sub(EXCERPT_RADIUS).max(1);
+    let last = (line + EXCERPT_RADIUS).min(lines.len());
+    let width = last.to_string().len();
+    (first..=last)
+        .map(|n| {
+            let marker = if n == line { '>' } else { ' ' };
+            format!("{marker} {n:>width$} | {}", clip(&lines[n - 1]))
+        })
+        .collect()
+}
+
+/// Source context for the frames in `text`: each frame with its line text and an
+/// excerpt around the first one whose source was found. `None` when `text` has no
+/// frames or no source could be found for any of them.
+pub async fn context_for(state: &SharedState, text: &str) -> Option<Value> {
+    let frames = parse_frames(text);
+    if frames.is_empty() {
+        return None;
+    }
+
+    let known_script = |path: &str| match state.path_index() {
+        Some(index) => index.class_of(path).is_some_and(|class| SCRIPT_CLASSES.contains(&class)),
+        None => true,
+    };
+    let mut to_fetch: Vec<String> = Vec::new();
+    for frame in &frames {
+        let path = source_path(&frame.path);
+        if !to_fetch.contains(&path) && to_fetch.len() < MAX_FETCHED_SCRIPTS && known_script(&path) {
+            to_fetch.push(path);
+        }
+    }
+    if to_fetch.is_empty() {
+        return None;
+    }
+    let sources = load_sources(state, &to_fetch).await;
+    if sources.is_empty() {
+        return None;
+    }
+
+    let mut top = None;
+    let annotated: Vec<Value> = frames
+        .iter()
+        .map(|frame| {
+            let path = source_path(&frame.path);
+            let lines = sources.get(&path);
+            let text = lines.and_then(|lines| lines.get(frame.line - 1)).map(|l| clip(l.trim_start()));
+            if top.is_none() && text.is_some() {
+                top = lines.map(|lines| {
+                    json!({
+                        "path": frame.path,
+                        "line": frame.line,
+                        "lines": excerpt(lines, frame.line),
+                    })
+                });
+            }
+            let mut out = json!({ "path": frame.path, "line": frame.line });
+            if let Some(function) = &frame.function {
+                out["function"] = json!(function);
+            }
+            if path != frame.path {
+                out["sourcePath"] = json!(path);
+            }
+            match text {
+                Some(text) => out["text"] = json!(text),
+                None if lines.is_some() => out["note"] = json!("line is past the end of the current source"),
+                None => {}
+            }
+            out
+        })
+        .collect();
+    Some(json!({ "frames": annotated, "excerpt": top }))
+}
+
+/// Collect the strings of `value` into `out` until it holds `MAX_SCANNED_BYTES`.
+fn collect_text(value: &Value, out: &mut String) {
+    if out.len() >= MAX_SCANNED_BYTES {
+        return;
+    }
+    match value {
+        Value::String(s) => {
+            let room = MAX_SCANNED_BYTES - out.len();
+            let mut cut = s.len().min(room);
+            while !s.is_char_boundary(cut) {
+                cut -= 1;
+            }
+            out.push_str(&s[..cut]);
+            out.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|item| collect_text(item, out)),
+        Value::Object(map) => map.values().for_each(|item| collect_text(item, out)),
+        _ => {}
+    }
+}
+
+/// Add `sourceContext` to a plugin result that contains traceback frames.
+pub async fn enrich_result(state: &SharedState, value: &mut Value) {
+    let mut text = String::new();
+    collect_text(value, &mut text);
+    if let (Some(context), Some(obj)) = (context_for(state, &text).await, value.as_object_mut()) {
+        obj.insert("sourceContext".into(), context);
+    }
+}
+
+/// Append the source context to an error message that contains traceback frames.
+pub async fn enrich_error(state: &SharedState, message: String) -> String {
+    let Some(context) = context_for(state, &message).await else {
+        return message;
+    };
+    let mut out = message;
+    out.push_str("\n\nSource context:");
+    for frame in context["frames"].as_array().into_iter().flatten() {
+        if let (Some(path), Some(line), Some(text)) = (frame["path"].as_str(), frame["line"].as_u64(), frame["text"].as_str()) {
+            out.push_str(&format!("\n{path}:{line}: {}", text.trim_start()));
+        }
+    }
+    if let (Some(path), Some(lines)) = (context["excerpt"]["path"].as_str(), context["excerpt"]["lines"].as_array()) {
+        out.push_str(&format!("\n\n{path}:"));
+        for line in lines.iter().filter_map(Value::as_str) {
+            out.push('\n');
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::mock_studio::scripted;
+    use std::path::Path;
+
+    fn fixture_dir() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/traceback")
+    }
+
+    fn fixture(name: &str) -> String {
+        let path = fixture_dir().join(name);
+        std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {e}", path.display()))
+    }
+
+    fn frame(path: &str, line: usize, function: Option<&str>) -> Frame {
+        Frame { path: path.into(), line, function: function.map(String::from) }
+    }
+
+    /// A connected plugin serving the scripts under `tests/fixtures/traceback/sources`.
+    async fn plugin_with_sources(state: &SharedState) -> scripted::Calls {
+        scripted::answering(state, |tool, args| {
+            assert_eq!(tool, "studio-get_script_sources");
+            let sources: BTreeMap<String, String> = args["paths"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter_map(Value::as_str)
+                .filter_map(|path| {
+                    let source = std::fs::read_to_string(fixture_dir().join("sources").join(format!("{path}.luau")));
+                    Some((path.to_string(), source.ok()?))
+                })
+                .collect();
+            Ok(json!({ "sources": sources }))
+        })
+        .await
+    }
+
+    #[test]
+    fn output_stack_lines_are_frames() {
+        assert_eq!(
+            parse_frames(&fixture("stack_lines.txt")),
+            [
+                frame("ServerScriptService.Main", 12, Some("spawnWave")),
+                frame("ServerScriptService.Main", 30, None),
+                frame("ReplicatedStorage.Modules.Combat", 4, Some("clamp")),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_line_frames_take_the_function_of_a_later_line() {
+        assert_eq!(
+            parse_frames(&fixture("path_lines.txt")),
+            [
+                frame("ReplicatedStorage.Modules.Combat", 27, Some("applyDamage")),
+                frame("ServerScriptService.Main", 12, Some("spawnWave")),
+                frame("ServerScriptService.Main", 45, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn frames_without_a_line_are_skipped() {
+        assert_eq!(
+            parse_frames(&fixture("nested_module.txt")),
+            [
+                frame("ReplicatedStorage.Modules.Combat.Hitbox", 8, Some("build")),
+                frame("Players.Builder.PlayerScripts.Client.Input", 19, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn long_tracebacks_keep_their_first_frames() {
+        let text: String = (1..=20).map(|n| format!("Script 'Workspace.Spawner', Line {n}\n")).collect();
+        let frames = parse_frames(&text);
+        assert_eq!(frames.len(), MAX_FRAMES);
+        assert_eq!(frames.last().unwrap().line, MAX_FRAMES);
+    }
+
+    #[tokio::test]
+    async fn frames_are_annotated_with_their_lines_and_an_excerpt() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = plugin_with_sources(&state).await;
+
+        let context = context_for(&state, &fixture("path_lines.txt")).await.unwrap();
+        assert_eq!(
+            context["frames"],
+            json!([
+                {
+                    "path": "ReplicatedStorage.Modules.Combat",
+                    "line": 27,
+                    "function": "applyDamage",
+                    "text": "error(\"Damage must be positive\")",
+                },
+                {
+                    "path": "ServerScriptService.Main",
+                    "line": 12,
+                    "function": "spawnWave",
+                    "text": "target.Humanoid.Health = target.Humanoid.Health - wave.damage",
+                },
+                {
+                    "path": "ServerScriptService.Main",
+                    "line": 45,
+                    "note": "line is past the end of the current source",
+                },
+            ])
+        );
+        assert_eq!(context["excerpt"]["path"], "ReplicatedStorage.Modules.Combat");
+        assert_eq!(context["excerpt"]["line"], 27);
+        let lines: Vec<&str> =
+            context["excerpt"]["lines"].as_array().unwrap().iter().map(|l| l.as_str().unwrap()).collect();
+        assert_eq!(lines.len(), 2 * EXCERPT_RADIUS + 1);
+        assert_eq!(lines[0], "  24 | function Combat.applyDamage(humanoid, amount)");
+        assert_eq!(lines[3], "> 27 | \t\terror(\"Damage must be positive\")");
+        assert_eq!(lines[6], "  30 | end");
+        // The long comment on line 26 is cut at the bound
+        let long = lines[2].strip_prefix("  26 | ").unwrap();
+        assert_eq!(long.chars().count(), MAX_LINE_CHARS + 1);
+        assert!(long.ends_with('…'), "{long}");
+
+        // Both scripts came in one request
+        assert_eq!(calls.all().len(), 1);
+        assert_eq!(
+            calls.all()[0].1["paths"],
+            json!(["ReplicatedStorage.Modules.Combat", "ServerScriptService.Main"])
+        );
+    }
+
+    #[tokio::test]
+    async fn runtime_copies_are_read_from_their_starter_container() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = plugin_with_sources(&state).await;
+
+        let context = context_for(&state, &fixture("nested_module.txt")).await.unwrap();
+        assert_eq!(
+            context["frames"],
+            json!([
+                { "path": "ReplicatedStorage.Modules.Combat.Hitbox", "line": 8, "function": "build" },
+                {
+                    "path": "Players.Builder.PlayerScripts.Client.Input",
+                    "line": 19,
+                    "sourcePath": "StarterPlayer.StarterPlayerScripts.Client.Input",
+                    "text": "Input.onJump()",
+                },
+            ])
+        );
+        assert_eq!(context["excerpt"]["path"], "Players.Builder.PlayerScripts.Client.Input");
+        assert_eq!(context["excerpt"]["lines"][3], "> 19 | \tInput.onJump()");
+        assert_eq!(
+            calls.all()[0].1["paths"],
+            json!(["ReplicatedStorage.Modules.Combat.Hitbox", "StarterPlayer.StarterPlayerScripts.Client.Input"])
+        );
+    }
+
+    #[tokio::test]
+    async fn sources_are_fetched_once_until_the_cache_is_cleared() {
+        let state = SharedState::for_tests(Config::default());
+        let calls = plugin_with_sources(&state).await;
+        let fetched_paths = |n: usize| calls.all()[n].1["paths"].clone();
+
+        // Misses: both scripts are fetched
+        context_for(&state, &fixture("stack_lines.txt")).await.unwrap();
+        assert_eq!(calls.all().len(), 1);
+        // Hits: nothing is fetched, and a script the plugin didn't have isn't asked for again
+        context_for(&state, &fixture("stack_lines.txt")).await.unwrap();
+        context_for(&state, &fixture("nested_module.txt")).await.unwrap();
+        assert_eq!(calls.all().len(), 2);
+        assert_eq!(
+            fetched_paths(1),
+            json!(["ReplicatedStorage.Modules.Combat.Hitbox", "StarterPlayer.StarterPlayerScripts.Client.Input"])
+        );
+        context_for(&state, &fixture("nested_module.txt")).await.unwrap();
+        assert_eq!(calls.all().len(), 2);
+
+        state.script_sources().clear();
+        context_for(&state, &fixture("path_lines.txt")).await.unwrap();
+        assert_eq!(calls.all().len(), 3);
+        assert_eq!(fetched_paths(2), json!(["ReplicatedStorage.Modules.Combat", "ServerScriptService.Main"]));
+    }
+
+    #[tokio::test]
+    async fn no_context_without_a_plugin_or_frames() {
+        let state = SharedState::for_tests(Config::default());
+        assert!(context_for(&state, &fixture("path_lines.txt")).await.is_none());
+        plugin_with_sources(&state).await;
+        assert!(context_for(&state, "Loaded map Arena in 0.91s").await.is_none());
+    }
+}
@@ -0,0 +1,706 @@
+//! Bridge traffic per client, for planning against Roblox's HttpService budget
+//! (500 requests per minute).
+//!
+//! Counters are rings of per-second buckets covering the last ten minutes. Each
+//! bucket is one `AtomicU64` holding the second it belongs to (high 32 bits) and its
+//! count (low 32 bits), so `/pull` and `/push` update them with a compare-exchange and
+//! never take a lock. A bucket whose second has passed out of the ring reads as empty.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Seconds kept, and so the longest window that can be read.
+const BUCKETS: usize = 600;
+
+/// Requests per minute HttpService allows.
+pub const ROBLOX_HTTP_LIMIT_PER_MIN: u64 = 500;
+
+pub const ONE_MINUTE: Duration = Duration::from_secs(60);
+pub const TEN_MINUTES: Duration = Duration::from_secs(600);
+
+pub struct RollingCounter {
+    origin: Instant,
+    buckets: Box<[AtomicU64]>,
+}
+
+impl RollingCounter {
+    pub fn new(origin: Instant) -> Self {
+        Self {
+            origin,
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    fn now_secs(&self) -> u64 {
+        self.origin.elapsed().as_secs()
+    }
+
+    pub fn add(&self, n: u64) {
+        self.add_at(self.now_secs(), n);
+    }
+
+    fn add_at(&self, second: u64, n: u64) {
+        let bucket = &self.buckets[second as usize % BUCKETS];
+        let n = n.min(u32::MAX as u64);
+        let mut current = bucket.load(Ordering::Relaxed);
+        loop {
+            let next = if current >> 32 == second {
+                let count = ((current & 0xFFFF_FFFF) + n).min(u32::MAX as u64);
+                (second << 32) | count
+            } else {
+                (second << 32) | n
+            };
+            match bucket.compare_exchange_weak(current, next, Ordering::Relaxed, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
+    /// Total over the last `window` (at most ten minutes), the current second included.
+    pub fn sum(&self, window: Duration) -> u64 {
+        self.sum_at(self.now_secs(), window)
+    }
+
+    fn sum_at(&self, now: u64, window: Duration) -> u64 {
+        let span = (window.as_secs() as usize).clamp(1, BUCKETS) as u64;
+        (now.saturating_sub(span - 1)..=now)
+            .map(|second| {
+                let value = self.buckets[second as usize % BUCKETS].load(Ordering::Relaxed);
+                if value >> 32 == second {
+                    value & 0xFFFF_FFFF
+                } else {
+                    0
+                }
+            })
+            .sum()
+    }
+}
+
+/// One client's bridge requests and payload sizes.
+pub struct ClientTraffic {
+    polls: RollingCounter,
+    /// Long polls that returned empty after waiting the full 25 s.
+    poll_timeouts: RollingCounter,
+    pushes: RollingCounter,
+    bytes_pulled: RollingCounter,
+    bytes_pushed: RollingCounter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowStats {
+    pub window_secs: u64,
+    pub polls_per_min: f64,
+    pub pushes_per_min: f64,
+    pub requests_per_min: f64,
+    pub bytes_pulled: u64,
+    pub bytes_pushed: u64,
+    /// Share of polls that waited the full long-poll timeout. `None` without polls.
+    pub long_poll_timeout_ratio: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficReport {
+    pub one_minute: WindowStats,
+    pub ten_minutes: WindowStats,
+}
+
+/// Average over the whole window, also for clients younger than it: a burst right
+/// after registering shouldn't read as a sustained rate.
+fn per_minute(count: u64, window: Duration) -> f64 {
+    let rate = count as f64 * 60.0 / window.as_secs_f64();
+    (rate * 10.0).round() / 10.0
+}
+
+impl ClientTraffic {
+    pub fn new() -> Self {
+        let origin = Instant::now();
+        Self {
+            polls: RollingCounter::new(origin),
+            poll_timeouts: RollingCounter::new(origin),
+            pushes: RollingCounter::new(origin),
+            bytes_pulled: RollingCounter::new(origin),
+            bytes_pushed: RollingCounter::new(origin),
+        }
+    }
+
+    pub fn record_poll(&self, bytes: usize, timed_out: bool) {
+        self.polls.add(1);
+        self.bytes_pulled.add(bytes as u64);
+        if timed_out {
+            self.poll_timeouts.add(1);
+        }
+    }
+
+    pub fn record_push(&self, bytes: usize) {
+        self.pushes.add(1);
+        self.bytes_pushed.add(bytes as u64);
+    }
+
+    pub fn window(&self, window: Duration) -> WindowStats {
+        let polls = self.polls.sum(window);
+        let pushes = self.pushes.sum(window);
+        let timeouts = self.poll_timeouts.sum(window);
+        WindowStats {
+            window_secs: window.as_secs(),
+            polls_per_min: per_minute(polls, window),
+            pushes_per_min: per_minute(pushes, window),
+            requests_per_min: per_minute(polls + pushes, window),
+            bytes_pulled: self.bytes_pulled.sum(window),
+            bytes_pushed: self.bytes_pushed.sum(window),
+            long_poll_timeout_ratio: (polls > 0)
+                .then(|| ((timeouts as f64 / polls as f64) * 100.0).round() / 100.0),
+        }
+    }
+
+    pub fn report(&self) -> TrafficReport {
+        TrafficReport {
+            one_minute: self.window(ONE_MINUTE),
+            ten_minutes: self.window(TEN_MINUTES),
+        }
+    }
+}
+
+/// How much background traffic the server currently allows, from the combined
+/// request rate against the budget warning threshold. The server holds background
+/// requests back accordingly, and `/pull` passes the level to the plugin
+/// (`X-Yippie-Background-Level`), which stretches its own background intervals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackgroundLevel {
+    /// Background requests wait until the level rises again.
+    Paused = 0,
+    Minimal = 1,
+    Reduced = 2,
+    Full = 3,
+}
+
+/// Share of the warning threshold at which each restricted level starts.
+const LEVEL_THRESHOLDS: [(BackgroundLevel, f64); 3] = [
+    (BackgroundLevel::Reduced, 0.5),
+    (BackgroundLevel::Minimal, 0.75),
+    (BackgroundLevel::Paused, 1.0),
+];
+
+/// A level is left upwards only once the rate is this share of the warning threshold
+/// below where it started, and one step at a time, so a rate near a threshold doesn't
+/// make the plugin switch back and forth.
+const LEVEL_HYSTERESIS: f64 = 0.1;
+
+impl BackgroundLevel {
+    pub fn from_u8(raw: u8) -> Self {
+        match raw {
+            0 => Self::Paused,
+            1 => Self::Minimal,
+            2 => Self::Reduced,
+            _ => Self::Full,
+        }
+    }
+
+    fn step_up(self) -> Self {
+        Self::from_u8(self as u8 + 1)
+    }
+
+    /// Where the rate this level is entered at starts, as a share of the warning threshold.
+    fn threshold(self) -> Option<f64> {
+        LEVEL_THRESHOLDS.iter().find(|(level, _)| *level == self).map(|(_, share)| *share)
+    }
+
+    /// The level after a request, given the requests of the last minute. Dropping is
+    /// immediate; rising needs the rate to clear the current level's threshold by
+    /// `LEVEL_HYSTERESIS`.
+    pub fn next(self, requests_last_minute: u64, warn_at: u64) -> Self {
+        let share = requests_last_minute as f64 / warn_at.max(1) as f64;
+        let target = LEVEL_THRESHOLDS
+            .iter()
+            .rev()
+            .find(|(_, threshold)| share >= *threshold)
+            .map_or(Self::Full, |(level, _)| *level);
+        if target <= self {
+            return target;
+        }
+        match self.threshold() {
+            Some(threshold) if share < threshold - LEVEL_HYSTERESIS => self.step_up(),
+            _ => self,
+        }
+    }
+
+    /// Most background requests handed over per pull.
+    pub fn background_batch(self) -> usize {
+        match self {
+            Self::Full | Self::Reduced => 2,
+            Self::Minimal => 1,
+            Self::Paused => 0,
+        }
+    }
+
+    /// Whether a new background request wakes a waiting pull. Below `Full` it waits for
+    /// a pull that happens anyway and goes out together with what else is queued.
+    pub fn wakes_pull(self) -> bool {
+        self == Self::Full
+    }
+}
+
+/// Combined bridge traffic against the HttpService limit.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpBudget {
+    pub requests_last_minute: u64,
+    pub limit_per_minute: u64,
+    pub warn_at: u64,
+    /// Set while traffic is over the warning threshold.
+    pub warning: Option<String>,
+    /// Background traffic currently allowed.
+    pub background_level: BackgroundLevel,
+}
+
+/// The budget and per-client reports in Prometheus text format, for `/metrics`.
+/// Per-client series carry `client_id`, `bridge` and `window` (`1m` or `10m`) labels.
+pub fn render_metrics(
+    budget: &HttpBudget,
+    clients: &[(String, String, bool, TrafficReport)],
+    tool_calls: &std::collections::BTreeMap<String, u64>,
+) -> String {
+    use std::fmt::Write;
+
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: Vec<(String, f64)>| {
+        let _ = writeln!(out, "# HELP {name} {help}");
+        let _ = writeln!(out, "# TYPE {name} gauge");
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{name}{labels} {value}");
+        }
+    };
+
+    gauge(
+        "yippie_bridge_requests_last_minute",
+        "Bridge requests from all clients over the last 60 seconds.",
+        vec![(String::new(), budget.requests_last_minute as f64)],
+    );
+    gauge(
+        "yippie_http_budget_limit_per_minute",
+        "Roblox HttpService requests allowed per minute.",
+        vec![(String::new(), budget.limit_per_minute as f64)],
+    );
+    gauge(
+        "yippie_http_budget_warn_at",
+        "Requests per minute at which the budget warning starts.",
+        vec![(String::new(), budget.warn_at as f64)],
+    );
+    gauge(
+        "yippie_http_budget_warning",
+        "1 while bridge traffic is over the budget warning threshold.",
+        vec![(String::new(), if budget.warning.is_some() { 1.0 } else { 0.0 })],
+    );
+    gauge(
+        "yippie_background_level",
+        "Background traffic allowed: 3 full, 2 reduced, 1 minimal, 0 paused.",
+        vec![(String::new(), budget.background_level as u8 as f64)],
+    );
+
+    let per_window = |field: fn(&WindowStats) -> Option<f64>| {
+        let mut samples = Vec::new();
+        for (client_id, _, is_bridge, report) in clients {
+            for (window, stats) in [("1m", &report.one_minute), ("10m", &report.ten_minutes)] {
+                if let Some(value) = field(stats) {
+                    let labels = format!("{{client_id=\"{client_id}\",bridge=\"{is_bridge}\",window=\"{window}\"}}");
+                    samples.push((labels, value));
+                }
+            }
+        }
+        samples
+    };
+
+    gauge(
+        "yippie_client_polls_per_minute",
+        "Pulls per minute, averaged over the window.",
+        per_window(|s| Some(s.polls_per_min)),
+    );
+    gauge(
+        "yippie_client_pushes_per_minute",
+        "Pushes per minute, averaged over the window.",
+        per_window(|s| Some(s.pushes_per_min)),
+    );
+    gauge(
+        "yippie_client_bytes_pulled",
+        "Response bytes sent to the client by /pull within the window.",
+        per_window(|s| Some(s.bytes_pulled as f64)),
+    );
+    gauge(
+        "yippie_client_bytes_pushed",
+        "Request bytes received from the client by /push within the window.",
+        per_window(|s| Some(s.bytes_pushed as f64)),
+    );
+    gauge(
+        "yippie_client_long_poll_timeout_ratio",
+        "Share of pulls that waited out the long-poll timeout empty.",
+        per_window(|s| s.long_poll_timeout_ratio),
+    );
+
+    let _ = writeln!(out, "# HELP yippie_tool_calls_total MCP tool calls by client (clientInfo name/version from initialize).");
+    let _ = writeln!(out, "# TYPE yippie_tool_calls_total counter");
+    for (client, count) in tool_calls {
+        let label = client.replace('\\', "\\\\").replace('"', "\\\"");
+        let _ = writeln!(out, "yippie_tool_calls_total{{mcp_client=\"{label}\"}} {count}");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows_sum_the_last_seconds_including_the_current_one() {
+        let counter = RollingCounter::new(Instant::now());
+        counter.add_at(10, 1);
+        counter.add_at(69, 2);
+        counter.add_at(70, 3);
+        counter.add_at(70, 4);
+        assert_eq!(counter.sum_at(70, ONE_MINUTE), 9);
+        assert_eq!(counter.sum_at(70, TEN_MINUTES), 10);
+        assert_eq!(counter.sum_at(129, ONE_MINUTE), 7);
+        assert_eq!(counter.sum_at(130, ONE_MINUTE), 0);
+        // Windows under a second read the current second, longer ones the whole ring
+        assert_eq!(counter.sum_at(70, Duration::ZERO), 7);
+        assert_eq!(counter.sum_at(70, Duration::from_secs(3600)), 10);
+        // Early on, the window reaches back to the origin only
+        assert_eq!(counter.sum_at(5, ONE_MINUTE), 0);
+    }
+
+    #[test]
+    fn buckets_from_an_earlier_lap_of_the_ring_read_as_empty() {
+        let counter = RollingCounter::new(Instant::now());
+        counter.add_at(5, 3);
+        assert_eq!(counter.sum_at(5 + BUCKETS as u64, TEN_MINUTES), 0);
+        // Reusing the bucket starts it over instead of adding to the stale count
+        counter.add_at(5 + BUCKETS as u64, 1);
+        assert_eq!(counter.sum_at(5 + BUCKETS as u64, ONE_MINUTE), 1);
+        assert_eq!(counter.sum_at(5 + BUCKETS as u64, TEN_MINUTES), 1);
+    }
+
+    #[test]
+    fn bucket_counts_saturate_instead_of_spilling_into_the_second() {
+        let counter = RollingCounter::new(Instant::now());
+        counter.add_at(7, u64::MAX);
+        counter.add_at(7, 5);
+        assert_eq!(counter.sum_at(7, ONE_MINUTE), u32::MAX as u64);
+        counter.add_at(8, 1);
+        assert_eq!(counter.sum_at(8, ONE_MINUTE), u32::MAX as u64 + 1);
+    }
+
+    #[test]
+    fn concurrent_adds_are_all_counted() {
+        let counter = RollingCounter::new(Instant::now());
+        std::thread::scope(|scope| {
+            for thread in 0..8u64 {
+                let counter = &counter;
+                scope.spawn(move || {
+                    for i in 0..1000u64 {
+                        counter.add_at(100 + (thread + i) % 3, 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(counter.sum_at(102, ONE_MINUTE), 8000);
+    }
+
+    #[test]
+    fn client_traffic_reports_rates_bytes_and_timeouts() {
+        let traffic = ClientTraffic::new();
+        assert_eq!(traffic.window(ONE_MINUTE).long_poll_timeout_ratio, None);
+
+        for timed_out in [true, false, false] {
+            traffic.record_poll(100, timed_out);
+        }
+        traffic.record_push(40);
+        traffic.record_push(2);
+
+        let report = traffic.report();
+        let minute = &report.one_minute;
+        assert_eq!((minute.polls_per_min, minute.pushes_per_min, minute.requests_per_min), (3.0, 2.0, 5.0));
+        assert_eq!((minute.bytes_pulled, minute.bytes_pushed), (300, 42));
+        assert_eq!(minute.long_poll_timeout_ratio, Some(0.33));
+        // The same requests averaged over ten minutes
+        let ten = &report.ten_minutes;
+        assert_eq!((ten.polls_per_min, ten.pushes_per_min, ten.requests_per_min), (0.3, 0.2, 0.5));
+        assert_eq!((ten.bytes_pulled, ten.bytes_pushed, ten.window_secs), (300, 42, 600));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["oneMinute"]["longPollTimeoutRatio"], 0.33);
+        assert_eq!(json["tenMinutes"]["windowSecs"], 600);
+    }
+
+    #[test]
+    fn per_minute_rounds_to_a_tenth() {
+        assert_eq!(per_minute(1, TEN_MINUTES), 0.1);
+        assert_eq!(per_minute(7, Duration::from_secs(90)), 4.7);
+        assert_eq!(per_minute(0, ONE_MINUTE), 0.0);
+    }
+
+    #[test]
+    fn background_level_drops_at_once_and_rises_one_step_past_the_hysteresis() {
+        use BackgroundLevel::*;
+        let warn_at = 100;
+        assert_eq!(Full.next(49, warn_at), Full);
+        assert_eq!(Full.next(50, warn_at), Reduced);
+        assert_eq!(Full.next(80, warn_at), Minimal);
+        assert_eq!(Reduced.next(100, warn_at), Paused);
+
+        // Rising needs the rate 10% of the threshold below where the level starts
+        assert_eq!(Paused.next(95, warn_at), Paused);
+        assert_eq!(Paused.next(91, warn_at), Paused);
+        assert_eq!(Paused.next(89, warn_at), Minimal);
+        assert_eq!(Minimal.next(70, warn_at), Minimal);
+        assert_eq!(Minimal.next(64, warn_at), Reduced);
+        // One step at a time, however far the rate fell
+        assert_eq!(Paused.next(0, warn_at), Minimal);
+        assert_eq!(Minimal.next(0, warn_at), Reduced);
+        assert_eq!(Reduced.next(45, warn_at), Reduced);
+        assert_eq!(Reduced.next(39, warn_at), Full);
+
+        // A zero threshold doesn't divide by zero
+        assert_eq!(Full.next(0, 0), Full);
+    }
+
+    #[test]
+    fn background_levels_limit_batches_and_wakeups() {
+        use BackgroundLevel::*;
+        assert_eq!([Full, Reduced, Minimal, Paused].map(BackgroundLevel::background_batch), [2, 2, 1, 0]);
+        assert_eq!([Full, Reduced, Minimal, Paused].map(BackgroundLevel::wakes_pull), [true, false, false, false]);
+        for level in [Full, Reduced, Minimal, Paused] {
+            assert_eq!(BackgroundLevel::from_u8(level as u8), level);
+        }
+        assert_eq!(BackgroundLevel::from_u8(200), Full);
+        assert_eq!(serde_json::to_value(Minimal).unwrap(), "minimal");
+    }
+
+    #[test]
+    fn metrics_render_the_budget_and_labelled_client_series() {
+        let budget = HttpBudget {
+            requests_last_minute: 420,
+            limit_per_minute: ROBLOX_HTTP_LIMIT_PER_MIN,
+            warn_at: 400,
+            warning: Some("over".into()),
+            background_level: BackgroundLevel::Paused,
+        };
+        let traffic = ClientTraffic::new();
+        traffic.record_poll(10, false);
+        let clients = vec![("edit".to_string(), "0.4.0".to_string(), false, traffic.report())];
+        let tool_calls = std::collections::BTreeMap::from([("Cli \"x\"/1.0".to_string(), 3)]);
+
+        let out = render_metrics(&budget, &clients, &tool_calls);
+        for line in [
+            "# TYPE yippie_bridge_requests_last_minute gauge",
+            "yippie_bridge_requests_last_minute 420",
+            "yippie_http_budget_limit_per_minute 500",
+            "yippie_http_budget_warning 1",
+            "yippie_background_level 0",
+            "yippie_client_polls_per_minute{client_id=\"edit\",bridge=\"false\",window=\"1m\"} 1",
+            "yippie_client_bytes_pulled{client_id=\"edit\",bridge=\"false\",window=\"10m\"} 10",
+            "yippie_client_long_poll_timeout_ratio{client_id=\"edit\",bridge=\"false\",window=\"1m\"} 0",
+            "yippie_tool_calls_total{mcp_client=\"Cli \\\"x\\\"/1.0\"} 3",
+        ] {
+            assert!(out.lines().any(|l| l == line), "missing {line:?} in:\n{out}");
+        }
+    }
+
+    // ─── Scheduling Simulation ────────────────────────────────
+
+    use crate::outbound_queue::{OutboundQueue, Priority};
+    use crate::types::BridgeToolRequest;
+
+    type LevelPolicy = fn(BackgroundLevel, u64, u64) -> BackgroundLevel;
+
+    /// The level straight from the thresholds, without hysteresis.
+    fn naive_level(_: BackgroundLevel, requests_last_minute: u64, warn_at: u64) -> BackgroundLevel {
+        let share = requests_last_minute as f64 / warn_at as f64;
+        LEVEL_THRESHOLDS
+            .iter()
+            .rev()
+            .find(|(_, threshold)| share >= *threshold)
+            .map_or(BackgroundLevel::Full, |(level, _)| *level)
+    }
+
+    /// One plugin long-polling one server, a second at a time. Every pull and push is
+    /// a request against the budget and moves the level, as in `record_bridge_request`.
+    /// Tool calls are sequential, so each interactive call wakes the pull and costs a
+    /// pull and a push; background requests only wake it at `Full` and otherwise ride
+    /// along with the next pull.
+    struct Simulation {
+        policy: LevelPolicy,
+        warn_at: u64,
+        counter: RollingCounter,
+        queue: OutboundQueue,
+        level: BackgroundLevel,
+        second: u64,
+        /// When the current long poll started waiting.
+        polling_since: u64,
+        next_id: u64,
+        transitions: usize,
+        max_rate: u64,
+        levels_seen: Vec<BackgroundLevel>,
+        /// Seconds each delivered request waited in the queue.
+        interactive_waits: Vec<u64>,
+        background_waits: Vec<u64>,
+    }
+
+    impl Simulation {
+        fn new(policy: LevelPolicy) -> Self {
+            Self {
+                policy,
+                warn_at: 400,
+                counter: RollingCounter::new(Instant::now()),
+                queue: OutboundQueue::default(),
+                level: BackgroundLevel::Full,
+                second: 0,
+                polling_since: 0,
+                next_id: 0,
+                transitions: 0,
+                max_rate: 0,
+                levels_seen: Vec::new(),
+                interactive_waits: Vec::new(),
+                background_waits: Vec::new(),
+            }
+        }
+
+        fn request(&mut self) {
+            self.counter.add_at(self.second, 1);
+            let rate = self.counter.sum_at(self.second, ONE_MINUTE);
+            self.max_rate = self.max_rate.max(rate);
+            let next = (self.policy)(self.level, rate, self.warn_at);
+            if next != self.level {
+                self.transitions += 1;
+                self.level = next;
+            }
+        }
+
+        fn enqueue(&mut self, priority: Priority) {
+            self.next_id += 1;
+            let request_id = format!("{}-{}", self.second, self.next_id);
+            let request = BridgeToolRequest { request_id, tool_name: format!("{priority:?}"), arguments: serde_json::Value::Null };
+            self.queue.push(request, priority);
+        }
+
+        /// The waiting pull returns, and the plugin pulls again until a pull comes back
+        /// empty (which then waits).
+        fn wake(&mut self) {
+            loop {
+                let batch = self.queue.drain_batch(self.level);
+                if batch.is_empty() {
+                    break;
+                }
+                self.request();
+                for request in batch {
+                    let queued_at: u64 = request.request_id.split('-').next().unwrap().parse().unwrap();
+                    let waits = match request.tool_name.as_str() {
+                        "Interactive" => &mut self.interactive_waits,
+                        _ => &mut self.background_waits,
+                    };
+                    waits.push(self.second - queued_at);
+                }
+                // The results
+                self.request();
+            }
+            self.polling_since = self.second;
+        }
+
+        /// Run `seconds` of load: interactive calls and background requests per second.
+        fn run(&mut self, seconds: u64, interactive_per_sec: f64, background_per_sec: f64) {
+            let (mut interactive_due, mut background_due) = (0.0, 0.0);
+            for _ in 0..seconds {
+                background_due += background_per_sec;
+                while background_due >= 1.0 {
+                    background_due -= 1.0;
+                    self.enqueue(Priority::Background);
+                    if self.level.wakes_pull() {
+                        self.wake();
+                    }
+                }
+                interactive_due += interactive_per_sec;
+                while interactive_due >= 1.0 {
+                    interactive_due -= 1.0;
+                    self.enqueue(Priority::Interactive);
+                    self.wake();
+                }
+                if self.second - self.polling_since >= 25 {
+                    // The long poll times out empty; the next one takes what is queued
+                    self.request();
+                    self.wake();
+                }
+                self.levels_seen.push(self.level);
+                self.second += 1;
+            }
+        }
+
+        fn background_queued(&self) -> usize {
+            self.queue.depths().background
+        }
+    }
+
+    #[test]
+    fn simulated_quiet_load_keeps_background_traffic_flowing() {
+        let mut sim = Simulation::new(BackgroundLevel::next);
+        sim.run(600, 0.25, 1.0);
+        assert!(sim.levels_seen.iter().all(|l| *l == BackgroundLevel::Full), "max rate {}", sim.max_rate);
+        assert!(sim.background_waits.iter().all(|w| *w == 0));
+        assert_eq!((sim.background_queued(), sim.background_waits.len()), (0, 600));
+    }
+
+    #[test]
+    fn simulated_busy_load_settles_with_background_riding_along() {
+        let mut sim = Simulation::new(BackgroundLevel::next);
+        sim.run(900, 2.0, 1.0);
+        // Background requests stop waking pulls before the warning threshold is reached
+        assert!(sim.max_rate < sim.warn_at, "max rate {}", sim.max_rate);
+        let settled = &sim.levels_seen[120..];
+        assert!(settled.iter().all(|l| *l == settled[0]), "level kept changing: {} transitions", sim.transitions);
+        assert!(settled[0] < BackgroundLevel::Full);
+        assert!(sim.interactive_waits.iter().all(|w| *w == 0));
+        // ...and still get through, at most a second late
+        assert!(sim.background_waits.iter().all(|w| *w <= 1));
+        assert_eq!(sim.background_queued(), 0);
+    }
+
+    #[test]
+    fn simulated_spike_pauses_background_work_then_catches_up() {
+        let mut sim = Simulation::new(BackgroundLevel::next);
+        sim.run(120, 0.25, 1.0);
+        sim.run(120, 4.0, 1.0);
+        assert_eq!(sim.level, BackgroundLevel::Paused);
+        let backlog = sim.background_queued();
+        assert!(backlog > 60, "{backlog}");
+        // Interactive calls never wait for the budget
+        assert!(sim.interactive_waits.iter().all(|w| *w == 0));
+
+        sim.run(300, 0.25, 1.0);
+        let recovered = sim.levels_seen.iter().rposition(|l| *l != BackgroundLevel::Full).unwrap() as u64;
+        assert!(recovered < 240 + 180, "still restricted at second {recovered}");
+        assert_eq!(sim.background_queued(), 0);
+        assert_eq!(sim.background_waits.len(), 540);
+        assert!(sim.max_rate < ROBLOX_HTTP_LIMIT_PER_MIN, "max rate {}", sim.max_rate);
+    }
+
+    #[test]
+    fn simulated_load_near_a_threshold_does_not_make_the_level_flap() {
+        // Interactive load swinging around half the warning threshold
+        let hovering = |sim: &mut Simulation| {
+            for minute in 0..20 {
+                let per_sec = if minute % 2 == 0 { 1.55 } else { 1.75 };
+                sim.run(60, per_sec, 0.5);
+            }
+        };
+        let mut with_hysteresis = Simulation::new(BackgroundLevel::next);
+        hovering(&mut with_hysteresis);
+        let mut naive = Simulation::new(naive_level);
+        hovering(&mut naive);
+
+        assert!(naive.transitions > 20, "the load didn't hover: {} transitions", naive.transitions);
+        assert!(with_hysteresis.transitions <= 4, "{} transitions", with_hysteresis.transitions);
+        assert!(with_hysteresis.interactive_waits.iter().all(|w| *w == 0));
+        assert_eq!(with_hysteresis.background_queued(), 0);
+    }
+}
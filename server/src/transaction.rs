@@ -0,0 +1,110 @@
+//! Step format and limits for `studio-transaction`.
+//!
+//! A transaction runs its steps inside one ChangeHistoryService checkpoint and
+//! cancels that checkpoint if any step fails, so the place is left as it was. Only
+//! tools whose edits land in the open recording can take part; they carry
+//! `ToolAnnotations::TRANSACTION_SAFE`.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::time::Duration;
+
+/// Upper bound on steps in one transaction.
+pub const MAX_TRANSACTION_STEPS: usize = 50;
+
+/// Longest time budget for all steps together. The checkpoint stays open while the
+/// steps run, and Studio records nothing else into it meanwhile.
+pub const MAX_TRANSACTION_TIMEOUT_MS: u64 = 600_000;
+
+/// Checkpoint name when the call doesn't give one.
+pub const DEFAULT_CHECKPOINT_NAME: &str = "MCP Transaction";
+
+/// One tool call of a transaction.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct TransactionStep {
+    pub tool: String,
+    #[serde(default = "empty_arguments")]
+    pub arguments: Value,
+}
+
+fn empty_arguments() -> Value {
+    Value::Object(Default::default())
+}
+
+/// Parse and bound-check the `steps` argument.
+pub fn parse_steps(value: &Value) -> Result<Vec<TransactionStep>> {
+    let steps: Vec<TransactionStep> = serde_json::from_value(value.clone())
+        .context("'steps' must be an array of steps, each with 'tool' and optional 'arguments' (object)")?;
+    if steps.is_empty() {
+        bail!("Transaction has no steps");
+    }
+    if steps.len() > MAX_TRANSACTION_STEPS {
+        bail!("Transaction has {} steps (max {MAX_TRANSACTION_STEPS})", steps.len());
+    }
+    for (i, step) in steps.iter().enumerate() {
+        if !step.arguments.is_object() {
+            bail!("Step {i}: 'arguments' must be an object");
+        }
+    }
+    Ok(steps)
+}
+
+/// Time budget for all steps: `timeoutMs` when given, otherwise one tool timeout
+/// per step, capped at `MAX_TRANSACTION_TIMEOUT_MS` either way.
+pub fn budget(timeout_ms: Option<u64>, steps: usize, tool_timeout: Duration) -> Result<Duration> {
+    let max = Duration::from_millis(MAX_TRANSACTION_TIMEOUT_MS);
+    match timeout_ms {
+        Some(0) => bail!("'timeoutMs' must be positive"),
+        Some(ms) if ms > MAX_TRANSACTION_TIMEOUT_MS => {
+            bail!("'timeoutMs' {ms} exceeds {MAX_TRANSACTION_TIMEOUT_MS}")
+        }
+        Some(ms) => Ok(Duration::from_millis(ms)),
+        None => Ok(tool_timeout.saturating_mul(steps as u32).min(max)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn steps_default_to_empty_arguments() {
+        let steps = parse_steps(&json!([{ "tool": "studio-run_script", "arguments": { "code": "return 1" } }, { "tool": "studio-set_pivot" }])).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0].arguments["code"], "return 1");
+        assert_eq!(steps[1].arguments, json!({}));
+    }
+
+    #[test]
+    fn malformed_step_lists_are_refused() {
+        let refused = |steps: Value| parse_steps(&steps).unwrap_err().to_string();
+        assert_eq!(refused(json!([])), "Transaction has no steps");
+        assert!(refused(json!("studio-run_script")).starts_with("'steps' must be an array"));
+        assert!(refused(json!([{ "arguments": {} }])).starts_with("'steps' must be an array"));
+        assert!(refused(json!([{ "tool": "studio-run_script", "args": {} }])).starts_with("'steps' must be an array"));
+        assert_eq!(refused(json!([{ "tool": "studio-run_script" }, { "tool": "studio-run_script", "arguments": [] }])), "Step 1: 'arguments' must be an object");
+
+        let too_many = vec![json!({ "tool": "studio-run_script" }); MAX_TRANSACTION_STEPS + 1];
+        assert_eq!(refused(json!(too_many)), format!("Transaction has {} steps (max {MAX_TRANSACTION_STEPS})", MAX_TRANSACTION_STEPS + 1));
+        let most = vec![json!({ "tool": "studio-run_script" }); MAX_TRANSACTION_STEPS];
+        assert_eq!(parse_steps(&json!(most)).unwrap().len(), MAX_TRANSACTION_STEPS);
+    }
+
+    #[test]
+    fn the_budget_is_shared_by_all_steps() {
+        let tool_timeout = Duration::from_secs(30);
+        assert_eq!(budget(Some(5_000), 3, tool_timeout).unwrap(), Duration::from_secs(5));
+        assert_eq!(budget(None, 3, tool_timeout).unwrap(), Duration::from_secs(90));
+        // Long lists don't get one timeout per step past the cap
+        assert_eq!(budget(None, MAX_TRANSACTION_STEPS, tool_timeout).unwrap(), Duration::from_millis(MAX_TRANSACTION_TIMEOUT_MS));
+        assert_eq!(budget(Some(MAX_TRANSACTION_TIMEOUT_MS), 1, tool_timeout).unwrap(), Duration::from_millis(MAX_TRANSACTION_TIMEOUT_MS));
+        assert_eq!(budget(Some(0), 1, tool_timeout).unwrap_err().to_string(), "'timeoutMs' must be positive");
+        assert_eq!(
+            budget(Some(MAX_TRANSACTION_TIMEOUT_MS + 1), 1, tool_timeout).unwrap_err().to_string(),
+            format!("'timeoutMs' {} exceeds {MAX_TRANSACTION_TIMEOUT_MS}", MAX_TRANSACTION_TIMEOUT_MS + 1)
+        );
+    }
+}
@@ -0,0 +1,56 @@
+//! Which property value types TweenService can drive, for `studio-is_tweenable`.
+//!
+//! The plugin only reports the property's Luau type (`typeof` of its value); the
+//! decision is made here from the types TweenService documents as tweenable.
+
+use serde_json::{json, Value};
+
+/// Value types TweenService interpolates.
+const INTERPOLATED: &[&str] = &[
+    "number", "CFrame", "Rect", "Color3", "UDim", "UDim2", "Vector2", "Vector2int16", "Vector3",
+];
+
+/// Value types a tween accepts but can't blend: the value jumps to the goal when the
+/// tween completes.
+const SNAPPED: &[&str] = &["boolean", "EnumItem"];
+
+/// Types that come up often enough to suggest a tweenable sibling property.
+fn alternative(value_type: &str) -> Option<&'static str> {
+    match value_type {
+        "BrickColor" => Some("BrickColor isn't tweenable; tween the Color3 property instead (e.g. Color for BaseParts)"),
+        "NumberSequence" | "ColorSequence" => {
+            Some("Sequences aren't tweenable; tween a number or Color3 property and rebuild the sequence from it, or change keypoints in a loop")
+        }
+        "NumberRange" => Some("NumberRange isn't tweenable; tween a NumberValue and copy it over on Changed"),
+        "Instance" | "nil" => Some("Instance references aren't tweenable"),
+        "string" => Some("Strings aren't tweenable; set the value in steps instead"),
+        _ => None,
+    }
+}
+
+/// The verdict for a property whose value has Luau type `value_type`.
+pub fn check(value_type: &str) -> Value {
+    let interpolated = INTERPOLATED.contains(&value_type);
+    let snapped = SNAPPED.contains(&value_type);
+    let note = if snapped {
+        Some(format!("{value_type} values can't be interpolated; the property jumps to the goal when the tween completes"))
+    } else if interpolated {
+        None
+    } else {
+        Some(
+            alternative(value_type)
+                .map(String::from)
+                .unwrap_or_else(|| format!("TweenService can't drive {value_type} values")),
+        )
+    };
+    json!({
+        "tweenable": interpolated || snapped,
+        "interpolated": interpolated,
+        "note": note,
+    })
+}
+
+/// Tweenable types, for the tool description.
+pub fn supported() -> String {
+    INTERPOLATED.iter().chain(SNAPPED).copied().collect::<Vec<_>>().join(", ")
+}
@@ -7,6 +7,7 @@ use serde_json::Value;
 /// If `id` is None, it's a notification.
 #[derive(Debug, Deserialize)]
 pub struct JsonRpcMessage {
+    #[allow(dead_code)]
     pub jsonrpc: String,
     pub id: Option<Value>,
     pub method: String,
@@ -32,6 +33,7 @@ pub struct JsonRpcError {
     pub data: Option<Value>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Serialize)]
 pub struct JsonRpcNotification {
     pub jsonrpc: String,
@@ -42,13 +44,43 @@ pub struct JsonRpcNotification {
 
 // ─── MCP Types ────────────────────────────────────────────────
 
+/// A tool as listed by `tools/list`.
 #[derive(Debug, Serialize)]
+#[non_exhaustive]
 pub struct McpToolDef {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     #[serde(rename = "inputSchema")]
     pub input_schema: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<ToolAnnotations>,
+    /// Hidden from tools/list and refused when experimental tools are disabled.
+    #[serde(skip)]
+    pub experimental: bool,
+}
+
+/// MCP tool annotations. Tools without them are treated as modifying Studio.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+#[non_exhaustive]
+pub struct ToolAnnotations {
+    #[serde(rename = "readOnlyHint")]
+    pub read_only_hint: bool,
+    /// Successful results may be served from the response cache. Server-side only.
+    #[serde(skip)]
+    pub cacheable: bool,
+    /// May run as a `studio-transaction` step: its edits join an open checkpoint, so
+    /// cancelling that checkpoint reverts them. Server-side only.
+    #[serde(skip)]
+    pub transaction_safe: bool,
+}
+
+impl ToolAnnotations {
+    pub const READ_ONLY: Self = Self { read_only_hint: true, cacheable: false, transaction_safe: false };
+    /// Read-only and stable enough within a session to cache.
+    pub const CACHEABLE: Self = Self { read_only_hint: true, cacheable: true, transaction_safe: false };
+    /// Modifies the edit DataModel in a way a checkpoint can take back.
+    pub const TRANSACTION_SAFE: Self = Self { read_only_hint: false, cacheable: false, transaction_safe: true };
 }
 
 #[derive(Debug, Serialize)]
@@ -72,16 +104,20 @@ pub enum McpContent {
 }
 
 // ─── Bridge Types (Rust ↔ Studio Plugin) ──────────────────────
+// Fields are snake_case and also read in camelCase; see wire.rs for output casing.
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BridgeToolRequest {
+    #[serde(alias = "requestId")]
     pub request_id: String,
+    #[serde(alias = "toolName")]
     pub tool_name: String,
     pub arguments: Value,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BridgeToolResponse {
+    #[serde(alias = "requestId")]
     pub request_id: String,
     pub success: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -92,14 +128,22 @@ pub struct BridgeToolResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct BridgeRegisterRequest {
-    #[serde(default)]
+    #[serde(default, alias = "pluginVersion")]
     pub plugin_version: String,
+    /// Stable for one plugin load, so a re-register can be told apart from a reload.
+    #[serde(default, alias = "instanceKey")]
+    pub instance_key: Option<String>,
+    /// Optional protocol features the plugin supports (e.g. `event_seq`).
+    #[serde(default)]
+    pub features: Vec<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct BridgeRegisterResponse {
     pub client_id: String,
     pub server_version: String,
+    /// The requested features the server will use with this client.
+    pub features: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -112,8 +156,12 @@ pub struct BridgePushPayload {
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BridgeEvent {
+    #[serde(alias = "eventType")]
     pub event_type: String,
     pub data: Value,
+    /// Per-client sequence number, from plugins that negotiated `event_seq`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
 }
 
 #[derive(Debug, Serialize)]
@@ -122,6 +170,15 @@ pub struct BridgeStatusResponse {
     pub pending_calls: usize,
     pub log_buffer_size: usize,
     pub playtest_active: bool,
+    pub redactions: std::collections::BTreeMap<String, u64>,
+    /// True after an idle cleanup, until the next MCP request.
+    pub idle: bool,
+    pub idle_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_archive: Option<crate::log_archive::ArchiveStatus>,
+    pub event_order: crate::event_order::EventOrderStats,
+    /// Requests waiting for a /pull, per priority, across clients.
+    pub queue: crate::outbound_queue::QueueDepths,
 }
 
 // ─── Domain Types ─────────────────────────────────────────────
@@ -136,6 +193,19 @@ pub struct LogEntry {
     pub session_id: Option<String>,
 }
 
+/// Buffered log entries with the same level and message, for studio-get_errors_grouped.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LogGroup {
+    pub level: String,
+    pub message: String,
+    pub count: usize,
+    pub first_ts: f64,
+    pub last_ts: f64,
+    pub first_seq: u64,
+    pub last_seq: u64,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CaptureMetadata {
     pub id: String,
@@ -151,6 +221,59 @@ pub struct CaptureMetadata {
     pub content_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<ScreenRegion>,
+    /// Per-call `outputDir` the file was written to, outside the capture directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<String>,
+    /// MCP client (`name/version` from `initialize`) whose tool call made the capture.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mcp_client: Option<String>,
+}
+
+/// Pixel rectangle on the primary screen, used to crop OS screenshots.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct ScreenRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// One sample from a recorded play session, as emitted by the playtest bridge in
+/// `studio-input_sample` events. `t_ms` is relative to the start of the recording.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum InputSample {
+    /// A key transition. `action` is "down" or "up".
+    Key {
+        #[serde(rename = "tMs")]
+        t_ms: u64,
+        #[serde(rename = "keyCode")]
+        key_code: String,
+        action: String,
+    },
+    /// The character's facing direction changed.
+    Look {
+        #[serde(rename = "tMs")]
+        t_ms: u64,
+        direction: Vec3,
+    },
+}
+
+impl InputSample {
+    pub fn t_ms(&self) -> u64 {
+        match self {
+            InputSample::Key { t_ms, .. } | InputSample::Look { t_ms, .. } => *t_ms,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
 }
 
 // ─── Helpers ──────────────────────────────────────────────────
@@ -194,6 +317,15 @@ impl McpToolResult {
         }
     }
 
+    /// Add an image block after the existing content.
+    pub fn with_image(mut self, data: String, mime_type: impl Into<String>) -> Self {
+        self.content.push(McpContent::Image {
+            data,
+            mime_type: mime_type.into(),
+        });
+        self
+    }
+
     pub fn to_value(&self) -> Value {
         serde_json::to_value(self).unwrap_or(Value::Null)
     }
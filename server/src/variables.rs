@@ -0,0 +1,729 @@
+//! Named JSON values kept by the server between tool calls (`studio-var_set`,
+//! `studio-var_get`, `studio-var_list`), and their `${var:name}` expansion in the
+//! `code` of script tools.
+//!
+//! Variables live in memory only. Session variables belong to one MCP session and
+//! shadow global ones of the same name; global variables are shared by every session
+//! of the server. Each scope is bounded in count and total size, and a variable may
+//! expire after a TTL.
+//!
+//! A placeholder is replaced according to where it stands in the Luau code:
+//! - in code, by a Luau literal of the value (`"text"`, `42`, `true`, `nil`, tables
+//!   for arrays and objects);
+//! - in a quoted or backtick string, by the value as text (strings as they are,
+//!   anything else as JSON), escaped for that string;
+//! - in a long-bracket string, by the same text unescaped;
+//! - in a comment, not at all.
+//!
+//! `$${var:name}` stands for a literal `${var:name}`.
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, Instant};
+
+pub const MAX_NAME_BYTES: usize = 64;
+/// Largest value, serialized as JSON.
+pub const MAX_VALUE_BYTES: usize = 64 * 1024;
+/// Most variables per scope (one session, or the global scope).
+pub const MAX_VARIABLES: usize = 256;
+/// Most bytes of values per scope.
+pub const MAX_SCOPE_BYTES: usize = 1024 * 1024;
+pub const MAX_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Tools whose `code` argument is expanded.
+pub const SCRIPT_TOOLS: &[&str] = &["studio-run_script", "studio-test_script"];
+
+const PLACEHOLDER: &str = "${var:";
+const ESCAPED_PLACEHOLDER: &str = "$${var:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Session,
+    Global,
+}
+
+impl Scope {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw {
+            "session" => Some(Self::Session),
+            "global" => Some(Self::Global),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Variable {
+    value: Value,
+    bytes: usize,
+    set_at: Instant,
+    expires_at: Option<Instant>,
+}
+
+impl Variable {
+    fn live(&self, now: Instant) -> bool {
+        self.expires_at.is_none_or(|at| at > now)
+    }
+
+    fn describe(&self, name: &str, scope: Scope, now: Instant) -> Value {
+        json!({
+            "name": name,
+            "scope": scope,
+            "type": json_type(&self.value),
+            "bytes": self.bytes,
+            "setSecsAgo": now.duration_since(self.set_at).as_secs(),
+            "expiresInSecs": self.expires_at.map(|at| at.duration_since(now).as_secs()),
+        })
+    }
+}
+
+type Variables = BTreeMap<String, Variable>;
+
+/// Every variable of the server: global ones and each session's.
+#[derive(Debug, Default)]
+pub struct VariableStore {
+    global: Variables,
+    sessions: HashMap<String, Variables>,
+}
+
+impl VariableStore {
+    fn scope_mut(&mut self, session: &str, scope: Scope) -> &mut Variables {
+        match scope {
+            Scope::Global => &mut self.global,
+            Scope::Session => self.sessions.entry(session.to_string()).or_default(),
+        }
+    }
+
+    fn scope(&self, session: &str, scope: Scope) -> Option<&Variables> {
+        match scope {
+            Scope::Global => Some(&self.global),
+            Scope::Session => self.sessions.get(session),
+        }
+    }
+
+    /// Store `value` under `name`, replacing an earlier one in the same scope.
+    /// Returns the description of the stored variable.
+    pub fn set(
+        &mut self,
+        session: &str,
+        scope: Scope,
+        name: &str,
+        value: Value,
+        ttl: Option<Duration>,
+        now: Instant,
+    ) -> Result<Value, String> {
+        check_name(name)?;
+        let bytes = value.to_string().len();
+        if bytes > MAX_VALUE_BYTES {
+            return Err(format!("Value of '{name}' is {bytes} bytes as JSON (max {MAX_VALUE_BYTES})"));
+        }
+        let variables = self.scope_mut(session, scope);
+        variables.retain(|_, v| v.live(now));
+        let replaced = variables.get(name).map_or(0, |v| v.bytes);
+        if !variables.contains_key(name) && variables.len() >= MAX_VARIABLES {
+            return Err(format!(
+                "The {} scope already holds {MAX_VARIABLES} variables; remove some with studio-var_set and delete=true",
+                scope_label(scope)
+            ));
+        }
+        let total: usize = variables.values().map(|v| v.bytes).sum::<usize>() - replaced + bytes;
+        if total > MAX_SCOPE_BYTES {
+            return Err(format!(
+                "Storing '{name}' would bring the {} scope to {total} bytes (max {MAX_SCOPE_BYTES})",
+                scope_label(scope)
+            ));
+        }
+        let variable = Variable { value, bytes, set_at: now, expires_at: ttl.map(|ttl| now + ttl) };
+        let description = variable.describe(name, scope, now);
+        variables.insert(name.to_string(), variable);
+        Ok(description)
+    }
+
+    /// Remove `name` from `scope`. Returns whether it was there (and not expired).
+    pub fn remove(&mut self, session: &str, scope: Scope, name: &str, now: Instant) -> bool {
+        self.scope_mut(session, scope).remove(name).is_some_and(|v| v.live(now))
+    }
+
+    /// The variable `name` of `scope`, or with no scope the session's before the
+    /// global one.
+    pub fn get(&self, session: &str, scope: Option<Scope>, name: &str, now: Instant) -> Option<(Scope, &Value)> {
+        let scopes = match scope {
+            Some(scope) => vec![scope],
+            None => vec![Scope::Session, Scope::Global],
+        };
+        scopes.into_iter().find_map(|scope| {
+            let variable = self.scope(session, scope)?.get(name).filter(|v| v.live(now))?;
+            Some((scope, &variable.value))
+        })
+    }
+
+    /// Description of `name` as `get` finds it, without the value.
+    pub fn describe(&self, session: &str, scope: Option<Scope>, name: &str, now: Instant) -> Option<Value> {
+        let (scope, _) = self.get(session, scope, name, now)?;
+        let variable = self.scope(session, scope)?.get(name)?;
+        Some(variable.describe(name, scope, now))
+    }
+
+    /// Descriptions of the live variables of `scope`, or of both scopes, by name.
+    pub fn list(&self, session: &str, scope: Option<Scope>, now: Instant) -> Vec<Value> {
+        let scopes = match scope {
+            Some(scope) => vec![scope],
+            None => vec![Scope::Session, Scope::Global],
+        };
+        scopes
+            .into_iter()
+            .filter_map(|scope| Some((scope, self.scope(session, scope)?)))
+            .flat_map(|(scope, variables)| {
+                variables
+                    .iter()
+                    .filter(|(_, v)| v.live(now))
+                    .map(move |(name, v)| v.describe(name, scope, now))
+            })
+            .collect()
+    }
+}
+
+fn scope_label(scope: Scope) -> &'static str {
+    match scope {
+        Scope::Session => "session",
+        Scope::Global => "global",
+    }
+}
+
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = name.len() <= MAX_NAME_BYTES
+        && name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid variable name '{name}': use letters, digits and '_', not starting with a digit, at most {MAX_NAME_BYTES} bytes"
+        ))
+    }
+}
+
+fn json_type(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Where a placeholder stands in the code.
+#[derive(Clone, Copy)]
+enum Context {
+    Code,
+    /// Inside a `'...'` or `"..."` string.
+    Quoted(u8),
+    /// In the text of a backtick string.
+    Backtick,
+    /// Inside a long-bracket string of this level.
+    Long(usize),
+}
+
+/// Code with its placeholders expanded, and the names it used.
+#[derive(Debug)]
+pub struct Expansion {
+    pub code: String,
+    pub variables: Vec<String>,
+}
+
+/// Expand the `${var:name}` placeholders of `code`, looking names up with `lookup`.
+/// `None` when the code has no placeholders or escapes to expand.
+pub fn expand(code: &str, lookup: impl Fn(&str) -> Option<Value>) -> Result<Option<Expansion>, String> {
+    if !code.contains(PLACEHOLDER) {
+        return Ok(None);
+    }
+    let mut expander = Expander {
+        src: code.as_bytes(),
+        lookup,
+        out: Vec::with_capacity(code.len()),
+        used: BTreeSet::new(),
+        undefined: BTreeSet::new(),
+    };
+    expander.code(0, code.len())?;
+    if !expander.undefined.is_empty() {
+        let names: Vec<&str> = expander.undefined.iter().map(String::as_str).collect();
+        return Err(format!(
+            "Undefined variable{} in 'code': {}. Set {} with studio-var_set first, or write $${{var:name}} for a literal ${{var:name}}.",
+            if names.len() == 1 { "" } else { "s" },
+            names.join(", "),
+            if names.len() == 1 { "it" } else { "them" },
+        ));
+    }
+    let code = String::from_utf8(expander.out).map_err(|_| "Expanded code is not valid UTF-8".to_string())?;
+    Ok(Some(Expansion { code, variables: expander.used.into_iter().collect() }))
+}
+
+struct Expander<'a, F> {
+    src: &'a [u8],
+    lookup: F,
+    out: Vec<u8>,
+    used: BTreeSet<String>,
+    undefined: BTreeSet<String>,
+}
+
+impl<F: Fn(&str) -> Option<Value>> Expander<'_, F> {
+    /// Expand `src[start..end]`, which is Luau code.
+    fn code(&mut self, start: usize, end: usize) -> Result<(), String> {
+        let unterminated = |what: &str| format!("Can't expand variables: unterminated {what} in 'code'");
+        let mut i = start;
+        while i < end {
+            let rest = &self.src[i..end];
+            if rest.starts_with(b"--") {
+                // Comments are copied as they are
+                let close = match long_bracket_level(self.src, i + 2) {
+                    Some(level) => scan_long_bracket(self.src, i + 2, level).ok_or_else(|| unterminated("comment"))?,
+                    None => self.src[i..end].iter().position(|&b| b == b'\n').map_or(end, |p| i + p),
+                };
+                self.out.extend_from_slice(&self.src[i..close]);
+                i = close;
+            } else if rest[0] == b'"' || rest[0] == b'\'' {
+                let close = scan_quoted(self.src, i).ok_or_else(|| unterminated("string"))?;
+                self.out.push(rest[0]);
+                self.text(i + 1, close - 1, Context::Quoted(rest[0]))?;
+                self.out.push(rest[0]);
+                i = close;
+            } else if rest[0] == b'`' {
+                i = self.backtick(i)?;
+            } else if let Some(level) = (rest[0] == b'[').then(|| long_bracket_level(self.src, i)).flatten() {
+                let close = scan_long_bracket(self.src, i, level).ok_or_else(|| unterminated("long string"))?;
+                let body = i + level + 2;
+                let tail = close - level - 2;
+                self.out.extend_from_slice(&self.src[i..body]);
+                self.text(body, tail, Context::Long(level))?;
+                self.out.extend_from_slice(&self.src[tail..close]);
+                i = close;
+            } else if let Some(next) = self.placeholder(i, end, Context::Code)? {
+                i = next;
+            } else {
+                self.out.push(rest[0]);
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand the contents of a string, `src[start..end]`, without its delimiters.
+    fn text(&mut self, start: usize, end: usize, context: Context) -> Result<(), String> {
+        let mut i = start;
+        while i < end {
+            if self.src[i] == b'\\' && !matches!(context, Context::Long(_)) {
+                let next = (i + 2).min(end);
+                self.out.extend_from_slice(&self.src[i..next]);
+                i = next;
+            } else if let Some(next) = self.placeholder(i, end, context)? {
+                i = next;
+            } else {
+                self.out.push(self.src[i]);
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand the backtick string starting at `start`, whose `{...}` expressions are
+    /// code. Returns the index just past it.
+    fn backtick(&mut self, start: usize) -> Result<usize, String> {
+        let unterminated = || "Can't expand variables: unterminated interpolated string in 'code'".to_string();
+        self.out.push(b'`');
+        let mut i = start + 1;
+        let mut text_start = i;
+        loop {
+            match self.src.get(i).copied() {
+                None => return Err(unterminated()),
+                Some(b'\\') => i += 2,
+                Some(b'$') if placeholder_end(self.src, i).is_some() => {
+                    // Expanded by `text`; its brace doesn't open an expression
+                    i = placeholder_end(self.src, i).unwrap_or(self.src.len());
+                }
+                Some(b'`') => {
+                    self.text(text_start, i, Context::Backtick)?;
+                    self.out.push(b'`');
+                    return Ok(i + 1);
+                }
+                Some(b'{') => {
+                    self.text(text_start, i, Context::Backtick)?;
+                    let close = scan_braced(self.src, i).ok_or_else(unterminated)?;
+                    self.out.push(b'{');
+                    self.code(i + 1, close - 1)?;
+                    self.out.push(b'}');
+                    i = close;
+                    text_start = i;
+                }
+                Some(_) => i += 1,
+            }
+        }
+    }
+
+    /// Expand a placeholder or escape at `i`. Returns the index just past it, or `None`
+    /// when there is none at `i`.
+    fn placeholder(&mut self, i: usize, end: usize, context: Context) -> Result<Option<usize>, String> {
+        let rest = &self.src[i..end];
+        if rest.starts_with(ESCAPED_PLACEHOLDER.as_bytes()) {
+            // In backtick text a bare brace would open an expression
+            let literal = if matches!(context, Context::Backtick) { "$\\{var:" } else { PLACEHOLDER };
+            self.out.extend_from_slice(literal.as_bytes());
+            return Ok(Some(i + ESCAPED_PLACEHOLDER.len()));
+        }
+        if !rest.starts_with(PLACEHOLDER.as_bytes()) {
+            return Ok(None);
+        }
+        let name_start = i + PLACEHOLDER.len();
+        let Some(close) = self.src[name_start..end].iter().position(|&b| b == b'}').map(|p| name_start + p) else {
+            return Err(format!("Unclosed placeholder at line {} of 'code': expected ${{var:name}}", self.line(i)));
+        };
+        let name = String::from_utf8_lossy(&self.src[name_start..close]).into_owned();
+        check_name(&name).map_err(|e| format!("{e} (placeholder at line {} of 'code')", self.line(i)))?;
+        match (self.lookup)(&name) {
+            Some(value) => {
+                let rendered = render(&value, context).map_err(|e| format!("Variable '{name}' {e}"))?;
+                self.out.extend_from_slice(rendered.as_bytes());
+                self.used.insert(name);
+            }
+            None => {
+                self.undefined.insert(name);
+            }
+        }
+        Ok(Some(close + 1))
+    }
+
+    fn line(&self, i: usize) -> usize {
+        self.src[..i].iter().filter(|&&b| b == b'\n').count() + 1
+    }
+}
+
+/// `value` as it goes into code at `context`.
+fn render(value: &Value, context: Context) -> Result<String, String> {
+    let text = || match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    match context {
+        Context::Code => Ok(lua_literal(value)),
+        Context::Quoted(quote) => Ok(escape(&text(), &[quote])),
+        Context::Backtick => Ok(escape(&text(), b"`{")),
+        Context::Long(level) => {
+            let text = text();
+            let close = format!("]{}]", "=".repeat(level));
+            if text.contains(&close) {
+                Err(format!("can't go into a long-bracket string: it contains '{close}'"))
+            } else {
+                Ok(text)
+            }
+        }
+    }
+}
+
+/// A Luau expression for `value`.
+fn lua_literal(value: &Value) -> String {
+    match value {
+        Value::Null => "nil".into(),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => format!("\"{}\"", escape(s, b"\"")),
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(lua_literal).collect();
+            format!("{{{}}}", items.join(", "))
+        }
+        Value::Object(fields) => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(key, value)| format!("[\"{}\"] = {}", escape(key, b"\""), lua_literal(value)))
+                .collect();
+            format!("{{{}}}", fields.join(", "))
+        }
+    }
+}
+
+/// Escape `text` for a Luau string delimited so that `special` need a backslash.
+/// Line breaks and other control characters are escaped too, so line numbers in
+/// errors still match the submitted code.
+fn escape(text: &str, special: &[u8]) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_ascii() && special.contains(&(c as u8)) => {
+                out.push('\\');
+                out.push(c);
+            }
+            c if c.is_control() && c.is_ascii() => out.push_str(&format!("\\{:03}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// If `bytes[i..]` opens a long bracket (`[[`, `[=[`, ...), return its level.
+fn long_bracket_level(bytes: &[u8], i: usize) -> Option<usize> {
+    if bytes.get(i) != Some(&b'[') {
+        return None;
+    }
+    let level = bytes[i + 1..].iter().take_while(|&&b| b == b'=').count();
+    (bytes.get(i + 1 + level) == Some(&b'[')).then_some(level)
+}
+
+/// Return the index just past the long bracket opened at `i`.
+fn scan_long_bracket(bytes: &[u8], i: usize, level: usize) -> Option<usize> {
+    let mut close = Vec::with_capacity(level + 2);
+    close.push(b']');
+    close.extend(std::iter::repeat_n(b'=', level));
+    close.push(b']');
+    let body = i + level + 2;
+    bytes[body..]
+        .windows(close.len())
+        .position(|w| w == close.as_slice())
+        .map(|p| body + p + close.len())
+}
+
+/// Return the index just past the quoted string starting at `i`.
+fn scan_quoted(bytes: &[u8], i: usize) -> Option<usize> {
+    let quote = bytes[i];
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'\n' => return None,
+            b if b == quote => return Some(j + 1),
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// If a placeholder or escaped placeholder starts at `i`, return the index just past
+/// its closing brace.
+fn placeholder_end(bytes: &[u8], i: usize) -> Option<usize> {
+    let rest = &bytes[i..];
+    if !rest.starts_with(PLACEHOLDER.as_bytes()) && !rest.starts_with(ESCAPED_PLACEHOLDER.as_bytes()) {
+        return None;
+    }
+    rest.iter().position(|&b| b == b'}').map(|p| i + p + 1)
+}
+
+/// Return the index just past the interpolated string starting at `i`, skipping over
+/// `{...}` expressions (which may contain strings of their own).
+fn scan_interpolated(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut j = i + 1;
+    while j < bytes.len() {
+        match bytes[j] {
+            b'\\' => j += 2,
+            b'$' if placeholder_end(bytes, j).is_some() => j = placeholder_end(bytes, j)?,
+            b'`' => return Some(j + 1),
+            b'{' => j = scan_braced(bytes, j)?,
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// Return the index just past the `}` matching the `{` at `i` of an interpolated
+/// string's expression.
+fn scan_braced(bytes: &[u8], i: usize) -> Option<usize> {
+    let mut depth = 1;
+    let mut j = i + 1;
+    while depth > 0 {
+        match *bytes.get(j)? {
+            b'{' => {
+                depth += 1;
+                j += 1;
+            }
+            b'}' => {
+                depth -= 1;
+                j += 1;
+            }
+            b'"' | b'\'' => j = scan_quoted(bytes, j)?,
+            b'`' => j = scan_interpolated(bytes, j)?,
+            b'[' => match long_bracket_level(bytes, j) {
+                Some(level) => j = scan_long_bracket(bytes, j, level)?,
+                None => j += 1,
+            },
+            _ => j += 1,
+        }
+    }
+    Some(j)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_with(code: &str, vars: Value) -> Result<Option<String>, String> {
+        expand(code, |name| vars.get(name).cloned()).map(|e| e.map(|e| e.code))
+    }
+
+    fn expanded(code: &str, vars: Value) -> String {
+        expand_with(code, vars).unwrap().expect("code has placeholders")
+    }
+
+    #[test]
+    fn placeholders_in_code_become_luau_literals() {
+        let vars = json!({"n": 42, "s": "a\"b", "flag": true, "none": null, "list": [1, "x"], "obj": {"k": 1}});
+        assert_eq!(expanded("print(${var:n}, ${var:s})", vars.clone()), "print(42, \"a\\\"b\")");
+        assert_eq!(expanded("return ${var:flag}, ${var:none}", vars.clone()), "return true, nil");
+        assert_eq!(expanded("local t = ${var:list}", vars.clone()), "local t = {1, \"x\"}");
+        assert_eq!(expanded("local t = ${var:obj}", vars), "local t = {[\"k\"] = 1}");
+    }
+
+    #[test]
+    fn placeholders_in_quoted_strings_are_escaped_for_their_quote() {
+        let vars = json!({"s": "it's \"quoted\"\n\\", "n": 7});
+        assert_eq!(expanded("print(\"v=${var:s}\")", vars.clone()), "print(\"v=it's \\\"quoted\\\"\\n\\\\\")");
+        assert_eq!(expanded("print('v=${var:s}')", vars.clone()), "print('v=it\\'s \"quoted\"\\n\\\\')");
+        // Values that aren't strings go in as JSON text
+        assert_eq!(expanded("print(\"${var:n}\")", vars), "print(\"7\")");
+    }
+
+    #[test]
+    fn placeholders_in_long_brackets_are_unescaped() {
+        let vars = json!({"s": "a\"b\\c", "close": "x]]y"});
+        assert_eq!(expanded("print([[${var:s}]])", vars.clone()), "print([[a\"b\\c]])");
+        assert_eq!(expanded("print([=[${var:close}]=])", vars.clone()), "print([=[x]]y]=])");
+        let err = expand_with("print([[${var:close}]])", vars).unwrap_err();
+        assert!(err.contains("Variable 'close' can't go into a long-bracket string"), "{err}");
+    }
+
+    #[test]
+    fn placeholders_in_backticks_are_text_and_their_braces_are_code() {
+        let vars = json!({"s": "{`}", "n": 3});
+        assert_eq!(expanded("print(`${var:s} {${var:n} + 1}`)", vars.clone()), "print(`\\{\\`} {3 + 1}`)");
+        assert_eq!(expanded("print(`$${var:s}`)", vars.clone()), "print(`$\\{var:s}`)");
+        assert_eq!(expanded("print(`{`${var:n}`}`)", vars), "print(`{`3`}`)");
+    }
+
+    #[test]
+    fn comments_are_left_alone() {
+        let code = "-- ${var:missing}\n--[[ ${var:missing} ]]\nprint(${var:n})";
+        assert_eq!(expanded(code, json!({"n": 1})), "-- ${var:missing}\n--[[ ${var:missing} ]]\nprint(1)");
+    }
+
+    #[test]
+    fn adjacent_placeholders_and_braces() {
+        let vars = json!({"a": 1, "b": 2});
+        assert_eq!(expanded("print(${var:a}${var:b})", vars.clone()), "print(12)");
+        assert_eq!(expanded("local t = {${var:a}}", vars.clone()), "local t = {1}");
+        assert_eq!(expanded("local t = {{${var:a}},{${var:b}}}", vars), "local t = {{1},{2}}");
+    }
+
+    #[test]
+    fn a_doubled_dollar_is_a_literal_placeholder() {
+        let vars = json!({"a": 1});
+        assert_eq!(expanded("print(\"$${var:a}\", ${var:a})", vars.clone()), "print(\"${var:a}\", 1)");
+        // An escaped placeholder doesn't need the variable to exist
+        assert_eq!(expanded("print(\"$${var:missing}\")", vars), "print(\"${var:missing}\")");
+    }
+
+    #[test]
+    fn code_without_placeholders_is_not_expanded() {
+        assert!(expand_with("print('${notavar}')", json!({})).unwrap().is_none());
+    }
+
+    #[test]
+    fn undefined_variables_are_named_in_the_error() {
+        let err = expand_with("print(${var:b}, ${var:a}, ${var:ok}, ${var:a})", json!({"ok": 1})).unwrap_err();
+        assert!(err.starts_with("Undefined variables in 'code': a, b."), "{err}");
+        let err = expand_with("print(${var:a})", json!({})).unwrap_err();
+        assert!(err.starts_with("Undefined variable in 'code': a. Set it"), "{err}");
+    }
+
+    #[test]
+    fn malformed_placeholders_are_errors() {
+        let err = expand_with("print(1)\nprint(${var:a)", json!({"a": 1})).unwrap_err();
+        assert!(err.starts_with("Unclosed placeholder at line 2"), "{err}");
+        let err = expand_with("print(${var:1a})", json!({})).unwrap_err();
+        assert!(err.contains("Invalid variable name '1a'"), "{err}");
+    }
+
+    #[test]
+    fn expansion_reports_the_variables_it_used() {
+        let vars = json!({"a": 1, "b": 2});
+        let expansion = expand("print(${var:b}, ${var:a}, ${var:b})", |n| vars.get(n).cloned()).unwrap().unwrap();
+        assert_eq!(expansion.variables, ["a", "b"]);
+    }
+
+    #[test]
+    fn values_and_scopes_are_bounded() {
+        let now = Instant::now();
+        let mut store = VariableStore::default();
+        let big = json!("x".repeat(MAX_VALUE_BYTES));
+        let err = store.set("s1", Scope::Session, "big", big, None, now).unwrap_err();
+        assert!(err.contains(&format!("(max {MAX_VALUE_BYTES})")), "{err}");
+
+        let chunk = json!("x".repeat(62_000));
+        for i in 0..16 {
+            store.set("s1", Scope::Global, &format!("v{i}"), chunk.clone(), None, now).unwrap();
+        }
+        let err = store.set("s1", Scope::Global, "v16", chunk.clone(), None, now).unwrap_err();
+        assert!(err.contains(&format!("global scope to 1054034 bytes (max {MAX_SCOPE_BYTES})")), "{err}");
+        // Replacing a variable counts only its new size
+        store.set("s1", Scope::Global, "v0", chunk.clone(), None, now).unwrap();
+        // Each session has its own budget
+        store.set("s1", Scope::Session, "v16", chunk, None, now).unwrap();
+
+        let mut store = VariableStore::default();
+        for i in 0..MAX_VARIABLES {
+            store.set("s1", Scope::Session, &format!("v{i}"), json!(i), None, now).unwrap();
+        }
+        let err = store.set("s1", Scope::Session, "one_more", json!(0), None, now).unwrap_err();
+        assert!(err.contains(&format!("already holds {MAX_VARIABLES} variables")), "{err}");
+        store.set("s2", Scope::Session, "one_more", json!(0), None, now).unwrap();
+    }
+
+    #[test]
+    fn session_variables_shadow_global_ones() {
+        let now = Instant::now();
+        let mut store = VariableStore::default();
+        store.set("s1", Scope::Global, "x", json!("global"), None, now).unwrap();
+        store.set("s1", Scope::Session, "x", json!("mine"), None, now).unwrap();
+
+        assert_eq!(store.get("s1", None, "x", now), Some((Scope::Session, &json!("mine"))));
+        assert_eq!(store.get("s2", None, "x", now), Some((Scope::Global, &json!("global"))));
+        assert_eq!(store.get("s1", Some(Scope::Global), "x", now), Some((Scope::Global, &json!("global"))));
+        assert_eq!(store.get("s2", Some(Scope::Session), "x", now), None);
+        assert_eq!(store.list("s1", None, now).len(), 2);
+        assert_eq!(store.list("s2", None, now).len(), 1);
+
+        assert!(store.remove("s1", Scope::Session, "x", now));
+        assert_eq!(store.get("s1", None, "x", now), Some((Scope::Global, &json!("global"))));
+    }
+
+    #[test]
+    fn variables_expire_after_their_ttl() {
+        let start = Instant::now();
+        let ttl = Duration::from_secs(10);
+        let mut store = VariableStore::default();
+        let described = store.set("s1", Scope::Session, "x", json!(1), Some(ttl), start).unwrap();
+        assert_eq!(described["expiresInSecs"], 10);
+
+        let later = start + Duration::from_secs(4);
+        assert_eq!(store.describe("s1", None, "x", later).unwrap()["expiresInSecs"], 6);
+        assert!(store.get("s1", None, "x", start + ttl - Duration::from_millis(1)).is_some());
+
+        let expired = start + ttl;
+        assert_eq!(store.get("s1", None, "x", expired), None);
+        assert!(store.list("s1", None, expired).is_empty());
+        assert!(!store.remove("s1", Scope::Session, "x", expired));
+    }
+
+    #[test]
+    fn expired_variables_free_their_slot() {
+        let start = Instant::now();
+        let mut store = VariableStore::default();
+        for i in 0..MAX_VARIABLES {
+            store.set("s1", Scope::Global, &format!("v{i}"), json!(i), Some(Duration::from_secs(1)), start).unwrap();
+        }
+        assert!(store.set("s1", Scope::Global, "late", json!(0), None, start).is_err());
+        store.set("s1", Scope::Global, "late", json!(0), None, start + Duration::from_secs(1)).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! `studio-capture_viewport_via_render`: a picture of the edit scene rendered by the
+//! plugin, for when OS screenshots aren't available and CaptureService can't be read.
+//!
+//! Plugins can't read back what Studio draws, so `tools/viewport_render.lua` casts one
+//! ray per pixel from the edit camera and shades each hit by part color and sun angle.
+//! It sends raw RGB rows as base64; the server checks the size, encodes a PNG, saves it
+//! under `renders/` and returns it as an image block. Pixel counts are capped so a
+//! render stays within the tool timeout and the bridge's 2 MB request body limit.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use serde_json::{json, Value};
+
+pub const RENDER_DIR: &str = "renders";
+
+pub const DEFAULT_WIDTH: u32 = 256;
+pub const DEFAULT_HEIGHT: u32 = 144;
+pub const MIN_SIZE: u32 = 16;
+pub const MAX_WIDTH: u32 = 480;
+pub const MAX_HEIGHT: u32 = 270;
+
+pub const DEFAULT_MAX_DISTANCE: f64 = 1_000.0;
+pub const MAX_MAX_DISTANCE: f64 = 10_000.0;
+
+/// Checked arguments, passed on to the plugin.
+#[derive(Debug, Clone)]
+pub struct RenderRequest {
+    pub width: u32,
+    pub height: u32,
+    pub path: Option<String>,
+    pub max_distance: f64,
+}
+
+impl RenderRequest {
+    pub fn parse(arguments: &Value) -> Result<Self, String> {
+        let size = |name: &str, default: u32, max: u32| match arguments.get(name).filter(|v| !v.is_null()) {
+            None => Ok(default),
+            Some(v) => v
+                .as_u64()
+                .filter(|n| (u64::from(MIN_SIZE)..=u64::from(max)).contains(n))
+                .map(|n| n as u32)
+                .ok_or_else(|| format!("'{name}' must be a whole number of pixels from {MIN_SIZE} to {max}")),
+        };
+        let max_distance = match arguments.get("maxDistance").filter(|v| !v.is_null()) {
+            None => DEFAULT_MAX_DISTANCE,
+            Some(v) => v
+                .as_f64()
+                .filter(|d| *d > 0.0 && *d <= MAX_MAX_DISTANCE)
+                .ok_or_else(|| format!("'maxDistance' must be a number of studs above 0 and at most {MAX_MAX_DISTANCE}"))?,
+        };
+        Ok(Self {
+            width: size("width", DEFAULT_WIDTH, MAX_WIDTH)?,
+            height: size("height", DEFAULT_HEIGHT, MAX_HEIGHT)?,
+            path: arguments.get("path").and_then(|v| v.as_str()).map(String::from),
+            max_distance,
+        })
+    }
+
+    pub fn plugin_args(&self) -> Value {
+        json!({
+            "width": self.width,
+            "height": self.height,
+            "path": self.path,
+            "maxDistance": self.max_distance,
+        })
+    }
+}
+
+/// A finished render: the PNG and what the plugin reported about it.
+pub struct Render {
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+    /// Everything the plugin returned except the pixels (camera, hit count, timing).
+    pub details: Value,
+}
+
+impl Render {
+    /// Decode the plugin's answer and encode the PNG. The pixel data must be exactly
+    /// the requested size.
+    pub fn from_plugin(mut result: Value, request: &RenderRequest) -> Result<Self, String> {
+        let Some(Value::String(pixels)) = result.as_object_mut().and_then(|r| r.remove("pixels")) else {
+            return Err("The plugin returned no pixel data".into());
+        };
+        let rgb = base64::engine::general_purpose::STANDARD
+            .decode(pixels.as_bytes())
+            .map_err(|e| format!("The plugin's pixel data is not valid base64: {e}"))?;
+        let expected = request.width as usize * request.height as usize * 3;
+        if rgb.len() != expected {
+            return Err(format!(
+                "The plugin returned {} bytes of pixels, expected {expected} for {}x{} RGB",
+                rgb.len(),
+                request.width,
+                request.height
+            ));
+        }
+        let png = encode_png(request.width, request.height, &rgb).map_err(|e| format!("PNG encoding failed: {e:#}"))?;
+        Ok(Self {
+            width: request.width,
+            height: request.height,
+            png,
+            details: result,
+        })
+    }
+
+    pub fn png_base64(&self) -> String {
+        base64::engine::general_purpose::STANDARD.encode(&self.png)
+    }
+}
+
+/// Encode 8-bit RGB rows as a PNG.
+pub fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().context("PNG header")?;
+    writer.write_image_data(rgb).context("PNG data")?;
+    writer.finish().context("PNG end")?;
+    Ok(png)
+}
@@ -0,0 +1,67 @@
+//! Wall-clock jumps, e.g. a laptop waking from sleep or an NTP step.
+//!
+//! Liveness runs on `Instant`, which doesn't jump, but whether it counts time spent
+//! asleep depends on the platform: after a wake a client may look like it hasn't
+//! polled for hours even though it polls again within a second. So the server
+//! compares how far the wall clock and the monotonic clock moved between readings;
+//! when they disagree by more than `JUMP_THRESHOLD`, clients get `GRACE` to poll
+//! again before any is pruned as stale.
+
+use chrono::{DateTime, Utc};
+use std::time::{Duration, Instant};
+
+use crate::state::SharedState;
+
+/// How often the wall clock is read, besides every stale-client check.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A disagreement between the clocks larger than this is a jump.
+const JUMP_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// How long after a jump stale clients are kept.
+pub const GRACE: Duration = Duration::from_secs(60);
+
+/// A jump `check` detected.
+pub struct Jump {
+    /// Wall-clock change minus monotonic change; negative when the clock went back.
+    pub offset_secs: i64,
+}
+
+pub struct WallClockWatch {
+    last: (Instant, DateTime<Utc>),
+    grace_until: Option<Instant>,
+}
+
+impl WallClockWatch {
+    pub fn new(now: Instant, wall_now: DateTime<Utc>) -> Self {
+        Self { last: (now, wall_now), grace_until: None }
+    }
+
+    /// Take a reading of both clocks. Returns the jump since the previous reading, if
+    /// there was one, and starts the grace period.
+    pub fn check(&mut self, now: Instant, wall_now: DateTime<Utc>) -> Option<Jump> {
+        let (last, last_wall) = std::mem::replace(&mut self.last, (now, wall_now));
+        let monotonic_ms = now.saturating_duration_since(last).as_millis() as i64;
+        let wall_ms = (wall_now - last_wall).num_milliseconds();
+        let offset_ms = wall_ms - monotonic_ms;
+        if offset_ms.unsigned_abs() <= JUMP_THRESHOLD.as_millis() as u64 {
+            return None;
+        }
+        self.grace_until = Some(now + GRACE);
+        Some(Jump { offset_secs: offset_ms / 1000 })
+    }
+
+    /// Whether stale clients are being kept after a jump.
+    pub fn in_grace(&self, now: Instant) -> bool {
+        self.grace_until.is_some_and(|until| now < until)
+    }
+}
+
+/// Read the wall clock every `CHECK_INTERVAL`, so a jump is noticed even while no
+/// tool call or stale-client check comes in.
+pub async fn run(state: SharedState) {
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        state.check_wall_clock();
+    }
+}
@@ -0,0 +1,283 @@
+//! Field casing of the bridge protocol.
+//!
+//! The bridge types are snake_case (`request_id`, `tool_name`), and every field also
+//! accepts its camelCase spelling (`requestId`) on input. Output is snake_case
+//! unless the client registered with the `camel_case` feature; then the top-level
+//! fields of /register and /pull responses are renamed. Tool arguments, results and
+//! event data are passed through unchanged either way.
+//!
+//! With `YIPPIE_BRIDGE_STRICT=1` the server warns about fields it doesn't know in
+//! /register and /push bodies, which would otherwise be ignored.
+
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+use crate::types::BridgeToolRequest;
+
+/// Feature a client requests at /register to receive camelCase fields.
+pub const CAMEL_CASE_FEATURE: &str = "camel_case";
+
+/// Fields of `BridgeRegisterRequest`.
+const REGISTER_FIELDS: &[&str] = &["plugin_version", "instance_key", "features"];
+/// Fields of `BridgePushPayload`.
+const PUSH_FIELDS: &[&str] = &["responses", "events"];
+/// Fields of `BridgeToolResponse`.
+const RESPONSE_FIELDS: &[&str] = &["request_id", "success", "result", "error"];
+/// Fields of `BridgeEvent`.
+const EVENT_FIELDS: &[&str] = &["event_type", "data", "seq"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Casing {
+    #[default]
+    Snake,
+    Camel,
+}
+
+impl Casing {
+    /// Casing for a client that registered with these features.
+    pub fn negotiate(features: &[String]) -> Self {
+        if features.iter().any(|f| f == CAMEL_CASE_FEATURE) {
+            Casing::Camel
+        } else {
+            Casing::Snake
+        }
+    }
+
+    /// Rename the top-level fields of an object, or of each object in an array.
+    pub fn apply(self, value: Value) -> Value {
+        if self == Casing::Snake {
+            return value;
+        }
+        match value {
+            Value::Object(fields) => {
+                Value::Object(fields.into_iter().map(|(key, value)| (snake_to_camel(&key), value)).collect())
+            }
+            Value::Array(items) => Value::Array(items.into_iter().map(|item| self.apply(item)).collect()),
+            other => other,
+        }
+    }
+}
+
+/// A request as /pull sends it, fields in the client's casing. Serialized straight
+/// from the queued request, so a multi-megabyte `code` argument isn't copied into an
+/// intermediate `Value` first.
+pub struct PulledRequest<'a>(pub &'a BridgeToolRequest, pub Casing);
+
+impl Serialize for PulledRequest<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let PulledRequest(request, casing) = self;
+        let key = |name: &str| match casing {
+            Casing::Snake => name.to_string(),
+            Casing::Camel => snake_to_camel(name),
+        };
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry(&key("request_id"), &request.request_id)?;
+        map.serialize_entry(&key("tool_name"), &request.tool_name)?;
+        map.serialize_entry(&key("arguments"), &request.arguments)?;
+        map.end()
+    }
+}
+
+fn snake_to_camel(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    let mut upper = false;
+    for c in name.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            out.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Fields of a /register body the server doesn't know, in either casing.
+pub fn unknown_register_fields(body: &Value) -> BTreeSet<String> {
+    let mut unknown = BTreeSet::new();
+    collect_unknown(body, REGISTER_FIELDS, "", &mut unknown);
+    unknown
+}
+
+/// Fields of a /push body the server doesn't know, in either casing. Fields of
+/// responses and events are named like `responses[].requestID`, once each.
+pub fn unknown_push_fields(body: &Value) -> BTreeSet<String> {
+    let mut unknown = BTreeSet::new();
+    collect_unknown(body, PUSH_FIELDS, "", &mut unknown);
+    for (list, known) in [("responses", RESPONSE_FIELDS), ("events", EVENT_FIELDS)] {
+        for item in body.get(list).and_then(Value::as_array).into_iter().flatten() {
+            collect_unknown(item, known, &format!("{list}[]."), &mut unknown);
+        }
+    }
+    unknown
+}
+
+fn collect_unknown(value: &Value, known: &[&str], prefix: &str, unknown: &mut BTreeSet<String>) {
+    let Some(fields) = value.as_object() else {
+        return;
+    };
+    for key in fields.keys() {
+        if !known.iter().any(|k| *k == key || snake_to_camel(k) == *key) {
+            unknown.insert(format!("{prefix}{key}"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{BridgeEvent, BridgePushPayload, BridgeRegisterRequest, BridgeRegisterResponse, BridgeToolResponse};
+    use serde_json::json;
+
+    /// The same body in both casings. Nested values keep mixed keys, which must come
+    /// through untouched.
+    fn both_casings(snake: Value) -> [Value; 2] {
+        let camel = Casing::Camel.apply(snake.clone());
+        [snake, camel]
+    }
+
+    #[test]
+    fn register_requests_read_in_either_casing() {
+        for body in both_casings(json!({
+            "plugin_version": "0.4.0",
+            "instance_key": "abc",
+            "features": ["event_seq", "camel_case"],
+        })) {
+            let register: BridgeRegisterRequest = serde_json::from_value(body.clone()).unwrap();
+            assert_eq!(register.plugin_version, "0.4.0", "{body}");
+            assert_eq!(register.instance_key.as_deref(), Some("abc"), "{body}");
+            assert_eq!(register.features, ["event_seq", "camel_case"], "{body}");
+            assert!(unknown_register_fields(&body).is_empty(), "{body}");
+        }
+        // Every field is optional
+        let register: BridgeRegisterRequest = serde_json::from_value(json!({})).unwrap();
+        assert_eq!((register.plugin_version.as_str(), register.instance_key), ("", None));
+    }
+
+    #[test]
+    fn push_payloads_read_in_either_casing() {
+        let response = json!({ "request_id": "r1", "success": false, "result": { "is_error": true }, "error": "boom" });
+        let event = json!({ "event_type": "log", "data": { "message": "hi", "session_id": "s" }, "seq": 7 });
+        for (response, event) in both_casings(response).into_iter().zip(both_casings(event)) {
+            let body = json!({ "responses": [response], "events": [event] });
+            let push: BridgePushPayload = serde_json::from_value(body.clone()).unwrap();
+            let [response] = &push.responses[..] else { panic!("{body}") };
+            assert_eq!(response.request_id, "r1");
+            assert!(!response.success);
+            assert_eq!(response.result, Some(json!({ "is_error": true })));
+            assert_eq!(response.error.as_deref(), Some("boom"));
+            let [event] = &push.events[..] else { panic!("{body}") };
+            assert_eq!(event.event_type, "log");
+            assert_eq!(event.data, json!({ "message": "hi", "session_id": "s" }));
+            assert_eq!(event.seq, Some(7));
+            assert!(unknown_push_fields(&body).is_empty(), "{body}");
+        }
+        let push: BridgePushPayload = serde_json::from_value(json!({})).unwrap();
+        assert!(push.responses.is_empty() && push.events.is_empty());
+    }
+
+    #[test]
+    fn tool_requests_read_in_either_casing() {
+        for body in both_casings(json!({ "request_id": "r1", "tool_name": "studio-status", "arguments": { "max_depth": 2 } })) {
+            let request: BridgeToolRequest = serde_json::from_value(body.clone()).unwrap();
+            assert_eq!(request.request_id, "r1", "{body}");
+            assert_eq!(request.tool_name, "studio-status", "{body}");
+            assert_eq!(request.arguments, json!({ "max_depth": 2 }), "{body}");
+        }
+    }
+
+    /// Outgoing messages are snake_case, which the plugin reads (`data.client_id`,
+    /// `req.tool_name`), unless the client negotiated `camel_case`.
+    #[test]
+    fn outgoing_messages_use_the_negotiated_casing() {
+        let register = BridgeRegisterResponse {
+            client_id: "c1".into(),
+            server_version: "0.4.0".into(),
+            features: vec!["event_seq".into()],
+        };
+        let register = serde_json::to_value(&register).unwrap();
+        assert_eq!(
+            Casing::negotiate(&["event_seq".into()]).apply(register.clone()),
+            json!({ "client_id": "c1", "server_version": "0.4.0", "features": ["event_seq"] })
+        );
+        assert_eq!(
+            Casing::negotiate(&[CAMEL_CASE_FEATURE.into()]).apply(register),
+            json!({ "clientId": "c1", "serverVersion": "0.4.0", "features": ["event_seq"] })
+        );
+
+        let pulled = vec![BridgeToolRequest {
+            request_id: "r1".into(),
+            tool_name: "studio-run_script".into(),
+            arguments: json!({ "script_timeout_ms": 100, "nested": { "a_b": 1 } }),
+        }];
+        let pulled = serde_json::to_value(&pulled).unwrap();
+        assert_eq!(
+            Casing::Snake.apply(pulled.clone()),
+            json!([{ "request_id": "r1", "tool_name": "studio-run_script", "arguments": { "script_timeout_ms": 100, "nested": { "a_b": 1 } } }])
+        );
+        assert_eq!(
+            Casing::Camel.apply(pulled),
+            json!([{ "requestId": "r1", "toolName": "studio-run_script", "arguments": { "script_timeout_ms": 100, "nested": { "a_b": 1 } } }])
+        );
+    }
+
+    #[test]
+    fn pulled_requests_serialize_like_the_request_in_its_casing() {
+        let request = BridgeToolRequest {
+            request_id: "r1".into(),
+            tool_name: "studio-run_script".into(),
+            arguments: json!({ "code": "print(1)", "script_timeout_ms": 100 }),
+        };
+        for casing in [Casing::Snake, Casing::Camel] {
+            let pulled = serde_json::to_value(PulledRequest(&request, casing)).unwrap();
+            assert_eq!(pulled, casing.apply(serde_json::to_value(&request).unwrap()));
+        }
+    }
+
+    #[test]
+    fn messages_round_trip_through_either_casing() {
+        let request = BridgeToolRequest {
+            request_id: "r1".into(),
+            tool_name: "studio-status".into(),
+            arguments: json!({ "tool_name": "kept" }),
+        };
+        let response = BridgeToolResponse {
+            request_id: "r1".into(),
+            success: true,
+            result: Some(json!({ "request_id": "kept" })),
+            error: None,
+        };
+        let event = BridgeEvent { event_type: "log".into(), data: json!({ "event_type": "kept" }), seq: None };
+        for casing in [Casing::Snake, Casing::Camel] {
+            let back: BridgeToolRequest = serde_json::from_value(casing.apply(serde_json::to_value(&request).unwrap())).unwrap();
+            assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(&request).unwrap());
+            let back: BridgeToolResponse =
+                serde_json::from_value(casing.apply(serde_json::to_value(&response).unwrap())).unwrap();
+            assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(&response).unwrap());
+            let back: BridgeEvent = serde_json::from_value(casing.apply(serde_json::to_value(&event).unwrap())).unwrap();
+            assert_eq!(serde_json::to_value(back).unwrap(), serde_json::to_value(&event).unwrap());
+        }
+        // Fields left out are left out of the output too
+        assert_eq!(serde_json::to_value(&event).unwrap(), json!({ "event_type": "log", "data": { "event_type": "kept" } }));
+        assert_eq!(
+            serde_json::to_value(&response).unwrap(),
+            json!({ "request_id": "r1", "success": true, "result": { "request_id": "kept" } })
+        );
+    }
+
+    #[test]
+    fn unknown_fields_are_named_in_their_own_casing() {
+        let body = json!({
+            "responses": [{ "requestId": "r1", "success": true, "requestID": "typo" }],
+            "events": [{ "event_type": "log", "data": {}, "eventSeq": 1 }],
+            "extra": true,
+        });
+        let unknown: Vec<String> = unknown_push_fields(&body).into_iter().collect();
+        assert_eq!(unknown, ["events[].eventSeq", "extra", "responses[].requestID"]);
+        let unknown: Vec<String> = unknown_register_fields(&json!({ "pluginVersion": "1", "plugin_name": "x" })).into_iter().collect();
+        assert_eq!(unknown, ["plugin_name"]);
+    }
+}
@@ -0,0 +1,104 @@
+//! Drives the server binary over stdio against its built-in mock Studio, the way an
+//! MCP client would.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::time::Duration;
+
+/// Time the mock plugin gets to register and report its settings before the first
+/// message, so runs don't depend on how fast it came up.
+const MOCK_SETTLE: Duration = Duration::from_millis(300);
+
+pub struct Server {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+    /// Every response, one JSON line each, in the order they arrived.
+    pub transcript: Vec<String>,
+}
+
+impl Server {
+    /// Start `--stdio --mock-studio` with captures in `capture_dir`, plus `env`, and
+    /// run the MCP handshake. `YIPPIE_*` variables of the test environment are not
+    /// passed on.
+    pub fn start(capture_dir: &Path, env: &[(&str, &str)]) -> Self {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_roblox-studio-yippieblox-mcp-server"));
+        command.args(["--stdio", "--mock-studio"]);
+        for (key, _) in std::env::vars().filter(|(key, _)| key.starts_with("YIPPIE_")) {
+            command.env_remove(key);
+        }
+        command
+            .env("YIPPIE_PORT", "0")
+            .env("YIPPIE_CAPTURE_DIR", capture_dir)
+            .env("RUST_LOG", "warn")
+            .envs(env.iter().copied())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        let mut child = command.spawn().expect("server binary starts");
+        let stdin = child.stdin.take().unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut server = Self { child, stdin, stdout, next_id: 0, transcript: Vec::new() };
+
+        let init = server.request(
+            "initialize",
+            json!({
+                "protocolVersion": "2024-11-05",
+                "capabilities": {},
+                "clientInfo": { "name": "integration-test", "version": "1" }
+            }),
+        );
+        assert!(init.get("result").is_some(), "initialize failed: {init}");
+        server.notify("notifications/initialized", json!({}));
+        std::thread::sleep(MOCK_SETTLE);
+        server
+    }
+
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Send a request and wait for its response. Notifications and requests from the
+    /// server in between are skipped.
+    pub fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let mut line = String::new();
+            let read = self.stdout.read_line(&mut line).expect("read from server");
+            assert!(read > 0, "server closed stdout while waiting for response {id}");
+            let message: Value = serde_json::from_str(&line).expect("server writes JSON lines");
+            if message.get("method").is_none() && message["id"] == id {
+                self.transcript.push(line.trim_end().to_string());
+                return message;
+            }
+        }
+    }
+
+    /// Call a tool and return its `result`.
+    pub fn call_tool(&mut self, name: &str, arguments: Value) -> Value {
+        let response = self.request("tools/call", json!({ "name": name, "arguments": arguments }));
+        response.get("result").cloned().unwrap_or_else(|| panic!("{name} failed: {response}"))
+    }
+
+    fn send(&mut self, message: &Value) {
+        writeln!(self.stdin, "{message}").expect("write to server");
+        self.stdin.flush().expect("flush to server");
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The text of a tool result's first content block.
+pub fn result_text(result: &Value) -> &str {
+    result["content"][0]["text"].as_str().unwrap_or_default()
+}
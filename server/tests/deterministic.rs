@@ -0,0 +1,67 @@
+//! `YIPPIE_DETERMINISTIC` runs of the same script produce byte-identical transcripts.
+
+mod common;
+
+use common::{result_text, Server};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// A session touching ids, timestamps, durations and orderings: status, a blocking and
+/// an async script, logs and captures.
+fn scripted_session(capture_dir: &Path, env: &[(&str, &str)]) -> Vec<String> {
+    let mut server = Server::start(capture_dir, env);
+    server.request("ping", json!({}));
+    server.call_tool("studio-status", json!({}));
+    server.call_tool("studio-run_script", json!({ "code": "print('hello')" }));
+
+    // The mock holds an endless loop for scriptTimeoutMs, so the call has a real duration
+    let started = server.call_tool(
+        "studio-run_script",
+        json!({ "code": "while true do end", "scriptTimeoutMs": 150, "async": true }),
+    );
+    let started: Value = serde_json::from_str(result_text(&started)).unwrap();
+    let request_id = started["id"].as_str().unwrap().to_string();
+    let operation = server.call_tool("studio-wait_operation", json!({ "id": request_id, "timeoutMs": 10_000 }));
+    assert_eq!(operation["structuredContent"]["timedOut"], false, "{operation}");
+
+    server.call_tool("studio-get_output", json!({}));
+    server.call_tool("studio-screenshot", json!({}));
+    server.call_tool("studio-screenshot", json!({}));
+    server.call_tool("studio-list_captures", json!({}));
+    server.call_tool("studio-status", json!({}));
+    std::mem::take(&mut server.transcript)
+}
+
+/// Run the session twice in the same capture directory, emptied in between, so paths
+/// in results match too.
+fn two_runs(env: &[(&str, &str)]) -> (Vec<String>, Vec<String>) {
+    let dir = tempfile::tempdir().unwrap();
+    let capture_dir = dir.path().join("captures");
+    let first = scripted_session(&capture_dir, env);
+    std::fs::remove_dir_all(&capture_dir).unwrap();
+    let second = scripted_session(&capture_dir, env);
+    (first, second)
+}
+
+#[test]
+fn deterministic_runs_produce_identical_transcripts() {
+    let (first, second) = two_runs(&[("YIPPIE_DETERMINISTIC", "1")]);
+    assert_eq!(first.len(), second.len());
+    for (i, (a, b)) in first.iter().zip(&second).enumerate() {
+        assert_eq!(a, b, "response {} differs between runs", i + 1);
+    }
+}
+
+#[test]
+fn seed_changes_the_ids() {
+    let (first, _) = two_runs(&[("YIPPIE_DETERMINISTIC", "1")]);
+    let (reseeded, _) = two_runs(&[("YIPPIE_DETERMINISTIC", "1"), ("YIPPIE_DETERMINISTIC_SEED", "7")]);
+    assert_ne!(first, reseeded);
+}
+
+#[test]
+fn normal_runs_differ() {
+    // Guards against a script that never shows anything run-specific
+    let (first, second) = two_runs(&[]);
+    assert_ne!(first, second);
+}
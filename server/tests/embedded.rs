@@ -0,0 +1,249 @@
+//! Embeds the server as a library through `ServerBuilder`, with a plugin speaking to
+//! the bridge over HTTP, and no server binary involved.
+
+use roblox_studio_yippieblox_mcp_server::{DispatchError, ServerBuilder};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+const TOKEN: &str = "embedded-test-token";
+
+/// A plugin that registers with the bridge and answers the calls it pulls.
+struct Plugin {
+    http: reqwest::Client,
+    base: String,
+    client_id: String,
+}
+
+impl Plugin {
+    async fn register(base: String) -> Self {
+        let http = reqwest::Client::new();
+        let registered: Value = http
+            .post(format!("{base}/register"))
+            .bearer_auth(TOKEN)
+            .json(&json!({ "plugin_version": "0.4.0", "features": ["event_seq"] }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let client_id = registered["client_id"].as_str().unwrap().to_string();
+        Self { http, base, client_id }
+    }
+
+    /// Pull once and answer every request with `answer(tool_name, arguments)`.
+    async fn serve_once(&self, answer: impl Fn(&str, &Value) -> Value) -> Vec<String> {
+        let pulled: Vec<Value> = self
+            .http
+            .get(format!("{}/pull", self.base))
+            .query(&[("clientId", &self.client_id)])
+            .bearer_auth(TOKEN)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let responses: Vec<Value> = pulled
+            .iter()
+            .map(|request| {
+                let result = answer(request["tool_name"].as_str().unwrap(), &request["arguments"]);
+                json!({ "request_id": request["request_id"], "success": true, "result": result })
+            })
+            .collect();
+        self.http
+            .post(format!("{}/push", self.base))
+            .query(&[("clientId", &self.client_id)])
+            .bearer_auth(TOKEN)
+            .json(&json!({ "responses": responses }))
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        pulled.iter().map(|r| r["tool_name"].as_str().unwrap().to_string()).collect()
+    }
+}
+
+#[tokio::test]
+async fn embedded_server_serves_a_plugin_over_the_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = ServerBuilder::new().port(0).token(TOKEN).capture_dir(dir.path()).spawn().await.unwrap();
+    let base = format!("http://{}", server.local_addr());
+    assert_ne!(server.local_addr().port(), 0);
+
+    // The bridge checks the token
+    let refused = reqwest::Client::new().get(format!("{base}/status")).send().await.unwrap();
+    assert_eq!(refused.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    // Build provenance is only shown with the token
+    let full_health = |authorized: bool| {
+        let request = reqwest::Client::new().get(format!("{base}/health?level=full"));
+        if authorized { request.bearer_auth(TOKEN) } else { request }.send()
+    };
+    assert_eq!(full_health(false).await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+    let health: Value = full_health(true).await.unwrap().json().await.unwrap();
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["build"]["version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(health["runtime"]["features"]["auth"], true);
+    assert_eq!(health["runtime"]["bridgePort"], server.local_addr().port());
+
+    // Without a plugin, calls fail as a tool error rather than hanging
+    let Err(DispatchError::Tool { message, .. }) = server.call_tool("studio-run_script", json!({ "code": "return 1" })).await
+    else {
+        panic!("a call went through without a plugin");
+    };
+    assert!(message.contains("No Roblox Studio plugin connected"), "{message}");
+
+    let plugin = Plugin::register(base.clone()).await;
+    let call = tokio::spawn({
+        let dispatcher = server.dispatcher();
+        async move { dispatcher.call_tool("studio-run_script", json!({ "code": "return 40 + 2" })).await }
+    });
+    let served = plugin
+        .serve_once(|tool, arguments| {
+            assert_eq!(tool, "studio-run_script");
+            assert_eq!(arguments["code"], "return 40 + 2");
+            json!({ "success": true, "returnValue": "42" })
+        })
+        .await;
+    assert_eq!(served, ["studio-run_script"]);
+    let result = tokio::time::timeout(Duration::from_secs(10), call).await.unwrap().unwrap().unwrap();
+    let text = result["content"][0]["text"].as_str().unwrap();
+    assert!(text.contains("42"), "{result}");
+
+    let status: Value = reqwest::Client::new()
+        .get(format!("{base}/status"))
+        .bearer_auth(TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["connected_clients"], 1, "{status}");
+    assert_eq!(status["pending_calls"], 0, "{status}");
+    assert!(server.tools().iter().any(|tool| tool.name == "studio-run_script"));
+
+    // The pull and push show up in the client's traffic and the budget
+    let get = |path: &str| reqwest::Client::new().get(format!("{base}{path}")).bearer_auth(TOKEN).send();
+    let clients: Value = get("/clients").await.unwrap().json().await.unwrap();
+    let client = &clients["clients"][0];
+    assert_eq!(client["clientId"], plugin.client_id.as_str(), "{clients}");
+    let minute = &client["traffic"]["oneMinute"];
+    assert_eq!((minute["pollsPerMin"].as_f64(), minute["pushesPerMin"].as_f64()), (Some(1.0), Some(1.0)), "{clients}");
+    assert!(minute["bytesPulled"].as_u64().unwrap() > 0 && minute["bytesPushed"].as_u64().unwrap() > 0, "{clients}");
+    assert_eq!(minute["longPollTimeoutRatio"], 0.0, "{clients}");
+    assert!(clients["httpBudget"]["requestsLastMinute"].as_u64().unwrap() >= 3, "{clients}");
+    assert_eq!(clients["httpBudget"]["backgroundLevel"], "full", "{clients}");
+    let metrics = get("/metrics").await.unwrap().text().await.unwrap();
+    let series = format!("yippie_client_pushes_per_minute{{client_id=\"{}\",bridge=\"false\",window=\"1m\"}} 1", plugin.client_id);
+    assert!(metrics.lines().any(|line| line == series), "{metrics}");
+
+    // Stopping closes the listener, so the port is free again
+    let addr = server.local_addr();
+    server.stop().await;
+    tokio::net::TcpListener::bind(addr).await.unwrap();
+}
+
+#[tokio::test]
+async fn built_server_answers_without_a_bridge() {
+    let dir = tempfile::tempdir().unwrap();
+    let server = ServerBuilder::new().capture_dir(dir.path()).mock_studio(true).build().unwrap();
+    let mut dispatcher = server.dispatcher();
+    let status = dispatcher.call_tool("studio-status", json!({})).await.unwrap();
+    let status: Value = serde_json::from_str(status["content"][0]["text"].as_str().unwrap()).unwrap();
+    assert_eq!(status["runtime"]["mockStudio"], true, "{status}");
+
+    // The simulated plugin registers from a spawned task
+    while server.state().connected_client_count().await == 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let ran = dispatcher.call_tool("studio-run_script", json!({ "code": "return 1" })).await;
+    assert!(ran.is_ok(), "{ran:?}");
+
+    let initialized = dispatcher.request("ping", json!({})).await.unwrap();
+    assert_eq!(initialized, json!({}));
+    server.shutdown();
+}
+
+#[tokio::test]
+async fn start_errors_name_the_failing_stage() {
+    let dir = tempfile::tempdir().unwrap();
+    let Err(error) = ServerBuilder::new().capture_dir(dir.path()).profile("no-such-profile").build() else {
+        panic!("an unknown profile was accepted");
+    };
+    assert_eq!(error.stage(), "profile");
+    assert!(error.to_string().starts_with("server startup failed at profile: "), "{error}");
+
+    let taken = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let Err(error) = ServerBuilder::new().capture_dir(dir.path()).port(port).spawn().await else {
+        panic!("bound a port that was taken");
+    };
+    assert_eq!(error.stage(), "bridge_bind");
+}
+
+#[tokio::test]
+async fn shutdown_needs_a_token_and_a_json_body() {
+    let dir = tempfile::tempdir().unwrap();
+    let confirm = json!({ "confirm": "shutdown", "requestedBy": "embedded-test" });
+
+    // Without a token any local page could post it, so it's refused
+    let open = ServerBuilder::new().port(0).capture_dir(dir.path()).spawn().await.unwrap();
+    let refused = reqwest::Client::new().post(format!("http://{}/shutdown", open.local_addr())).json(&confirm).send().await.unwrap();
+    assert_eq!(refused.status(), reqwest::StatusCode::FORBIDDEN);
+    open.stop().await;
+
+    let server = ServerBuilder::new().port(0).token(TOKEN).capture_dir(dir.path()).spawn().await.unwrap();
+    let url = format!("http://{}/shutdown", server.local_addr());
+    let post = || reqwest::Client::new().post(&url).bearer_auth(TOKEN);
+    // A cross-origin form or fetch can send text/plain without a preflight
+    let plain = post().header("content-type", "text/plain").body(confirm.to_string()).send().await.unwrap();
+    assert_eq!(plain.status(), reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let unconfirmed = post().json(&json!({ "reason": "test" })).send().await.unwrap();
+    assert_eq!(unconfirmed.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let accepted = post().json(&confirm).send().await.unwrap();
+    assert_eq!(accepted.status(), reqwest::StatusCode::ACCEPTED);
+    let body: Value = accepted.json().await.unwrap();
+    assert_eq!(body["alreadyRequested"], false, "{body}");
+    let requested_by = tokio::time::timeout(Duration::from_secs(5), server.shutdown_requested()).await.unwrap();
+    assert_eq!(requested_by, "embedded-test");
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn admin_export_and_import_take_json_and_a_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let open = ServerBuilder::new().port(0).capture_dir(dir.path()).spawn().await.unwrap();
+    let refused = reqwest::Client::new().post(format!("http://{}/admin/export", open.local_addr())).json(&json!({})).send().await.unwrap();
+    assert_eq!(refused.status(), reqwest::StatusCode::FORBIDDEN);
+    open.stop().await;
+
+    let server = ServerBuilder::new().port(0).token(TOKEN).capture_dir(dir.path()).spawn().await.unwrap();
+    let post = |path: &str| reqwest::Client::new().post(format!("http://{}{path}", server.local_addr())).bearer_auth(TOKEN);
+    // Query strings alone are no longer enough
+    let query_only = post("/admin/export").send().await.unwrap();
+    assert_eq!(query_only.status(), reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let exported: Value = post("/admin/export").json(&json!({})).send().await.unwrap().error_for_status().unwrap().json().await.unwrap();
+    let path = std::path::Path::new(exported["path"].as_str().unwrap());
+    let relative = path.strip_prefix(dir.path()).unwrap().to_str().unwrap().to_string();
+    let checked: Value = post("/admin/import")
+        .json(&json!({ "path": relative, "dryRun": true }))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(checked["conflicts"].is_object(), "{checked}");
+    let escaping = post("/admin/import").json(&json!({ "path": "../elsewhere.tar.gz" })).send().await.unwrap();
+    assert_eq!(escaping.status(), reqwest::StatusCode::BAD_REQUEST);
+    server.stop().await;
+}